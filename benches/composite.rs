@@ -0,0 +1,74 @@
+//! Compares compositing through the table of alpha reciprocals against
+//! dividing each component by the composited alpha, as compositing did
+//! before the table.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use mboard::raster::Pixel;
+
+/// Composites `over` onto `under` with a division per component.
+fn composite_over_with_division(under: &mut Pixel, over: &Pixel) {
+    let (r1, g1, b1, a1) = over.as_rgba();
+    let (r2, g2, b2, a2) = under.as_rgba();
+    let (a1, a2) = (a1 as u32, a2 as u32);
+
+    let a_o = (a1 + a2 - ((a1 * a2) >> 8)).min(255);
+    let component = |c1: u8, c2: u8| {
+        if a_o == 0 {
+            return 255;
+        }
+
+        let (c1, c2) = (c1 as u32, c2 as u32);
+        ((c1 * a1 + c2 * a2 - ((c2 * a2 * a1) >> 8)) / a_o).min(255)
+    };
+
+    *under = Pixel::new_rgba(
+        component(r1, r2) as u8,
+        component(g1, g2) as u8,
+        component(b1, b2) as u8,
+        a_o as u8,
+    );
+}
+
+/// Pixels of every alpha, so neither way of dividing benefits from
+/// compositing the same pixels over and over.
+fn pixels(seed: u32) -> Vec<Pixel> {
+    (0..256 * 256)
+        .map(|i: u32| {
+            let bits = i.wrapping_mul(2_654_435_761).wrapping_add(seed);
+            let [r, g, b, _] = bits.to_le_bytes();
+
+            Pixel::new_rgba(r, g, b, (i % 256) as u8)
+        })
+        .collect()
+}
+
+fn composite(c: &mut Criterion) {
+    let under = pixels(0);
+    let over = pixels(1);
+
+    let mut group = c.benchmark_group("composite_over");
+    group.bench_function("reciprocal_table", |b| {
+        b.iter(|| {
+            let mut under = under.clone();
+            for (pixel_under, pixel_over) in under.iter_mut().zip(&over) {
+                pixel_under.composite_over(black_box(pixel_over));
+            }
+
+            under
+        })
+    });
+    group.bench_function("division", |b| {
+        b.iter(|| {
+            let mut under = under.clone();
+            for (pixel_under, pixel_over) in under.iter_mut().zip(&over) {
+                composite_over_with_division(pixel_under, black_box(pixel_over));
+            }
+
+            under
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, composite);
+criterion_main!(benches);
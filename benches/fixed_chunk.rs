@@ -0,0 +1,45 @@
+//! Compares compositing a fill over a whole chunk stored as a
+//! `FixedRasterChunk`, as raster layers do for chunks of the sizes in
+//! `FIXED_CHUNK_SIZES`, against drawing a raster of the fill onto a
+//! `BoxRasterChunk`, as they do for chunks of other sizes.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use mboard::raster::{
+    chunks::{BoxRasterChunk, FixedRasterChunk},
+    Pixel,
+};
+
+const SIZE: usize = 256;
+
+fn composite_fill(c: &mut Criterion) {
+    let pixels = (0..SIZE * SIZE)
+        .map(|i| Pixel::new_rgba(i as u8, (i / SIZE) as u8, 0, (i % 251) as u8))
+        .collect();
+    let chunk = BoxRasterChunk::from_vec(pixels, SIZE, SIZE).expect("the pixels fill the chunk");
+    let fixed_chunk =
+        FixedRasterChunk::<SIZE>::try_from(chunk.clone()).expect("the chunk is of the fixed size");
+    let fill = Pixel::new_rgba(0, 0, 255, 100);
+
+    let mut group = c.benchmark_group("composite_fill");
+    group.bench_function("fixed_chunk", |b| {
+        b.iter_batched_ref(
+            || fixed_chunk.clone(),
+            |fixed_chunk| fixed_chunk.composite_fill(black_box(fill), false),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("box_chunk", |b| {
+        b.iter_batched_ref(
+            || chunk.clone(),
+            |chunk| {
+                let fill_chunk = BoxRasterChunk::new_fill(black_box(fill), SIZE, SIZE);
+                chunk.composite_over(&fill_chunk.as_window(), (0, 0).into());
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, composite_fill);
+criterion_main!(benches);
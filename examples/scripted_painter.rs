@@ -0,0 +1,88 @@
+//! Paints a small document headlessly through the public API and writes it to
+//! a PNG.
+//!
+//! ```sh
+//! cargo run --example scripted_painter -- painting.png
+//! ```
+
+use std::{env, error::Error, fs};
+
+use mboard::{
+    prelude::*,
+    vector::{layer::VectorShape, shapes::Path, shapes::Rectangle},
+};
+
+const WIDTH: usize = 512;
+const HEIGHT: usize = 384;
+
+/// The layers added by the builder, above its background layer.
+const PAINT_LAYER: usize = 1;
+const SHAPE_LAYER: usize = 2;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let output_path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "painting.png".to_string());
+
+    let mut canvas = CanvasBuilder::new(WIDTH, HEIGHT)
+        .background(colors::white())
+        .layer(LayerKind::Raster)
+        .layer(LayerKind::Vector)
+        .build();
+
+    // A sky fading into a sun low over the horizon
+    canvas.perform_raster_action(
+        PAINT_LAYER,
+        RasterLayerAction::fill_rect(
+            CanvasRect {
+                top_left: (0, 0).into(),
+                dimensions: Dimensions {
+                    width: WIDTH,
+                    height: HEIGHT / 2,
+                },
+            },
+            Pixel::new_rgb(120, 180, 235),
+        ),
+    );
+    canvas.perform_raster_action(
+        PAINT_LAYER,
+        RasterLayerAction::fill_oval(
+            CanvasRect {
+                top_left: (320, 100).into(),
+                dimensions: Dimensions {
+                    width: 120,
+                    height: 120,
+                },
+            },
+            Pixel::new_rgb(250, 200, 60),
+        ),
+    );
+
+    // Rolling hills stroked with curves
+    let mut hills = Path::new((0.0, 240.0), 6.0, Pixel::new_rgb(60, 140, 70));
+    hills.quadratic_to((128.0, 170.0), (256.0, 240.0)).cubic_to(
+        (320.0, 290.0),
+        (420.0, 180.0),
+        (512.0, 230.0),
+    );
+    canvas.perform_raster_action(PAINT_LAYER, RasterLayerAction::stroke_path(hills));
+
+    // A house that stays editable on the vector layer
+    canvas.perform_vector_action(
+        SHAPE_LAYER,
+        VectorLayerAction::AddShape(VectorShape::new(
+            (96.0, 260.0),
+            Rectangle::new(80.0, 64.0, Pixel::new_rgb(170, 80, 60)),
+        )),
+    );
+
+    let document_rect = canvas
+        .document_rect()
+        .ok_or("the builder should give the canvas a document size")?;
+    let png = canvas.rasterize_canvas_rect(document_rect).encode_png()?;
+    fs::write(&output_path, png)?;
+
+    println!("Wrote a {WIDTH}x{HEIGHT} painting to {output_path}");
+
+    Ok(())
+}
@@ -0,0 +1,179 @@
+//! Serves rendered tiles of a document over HTTP, the way a web map serves
+//! map tiles.
+//!
+//! ```sh
+//! cargo run --example tile_server
+//! curl -o tile.png http://127.0.0.1:8080/tiles/1/0/0.png
+//! ```
+//!
+//! Tiles are requested as `/tiles/{zoom}/{x}/{y}.png`. Every tile is
+//! `TILE_SIZE` pixels square, and each zoom level halves the scale of the
+//! previous one, so a tile at zoom `z` covers `TILE_SIZE << z` pixels of the
+//! canvas. Tiles are rendered through a single view, so panning between
+//! neighbouring tiles at the same zoom is served from the view cache.
+
+use std::{
+    error::Error,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+};
+
+use mboard::{canvas::DocumentPreset, prelude::*};
+
+const ADDRESS: &str = "127.0.0.1:8080";
+const TILE_SIZE: usize = 256;
+const MAX_ZOOM: u32 = 4;
+
+/// The tile a request path asks for, as its zoom and position in tiles.
+fn parse_tile_path(path: &str) -> Option<(u32, i32, i32)> {
+    let tile = path.strip_prefix("/tiles/")?.strip_suffix(".png")?;
+    let mut parts = tile.split('/').map(str::parse::<i64>);
+
+    let zoom = u32::try_from(parts.next()?.ok()?).ok()?;
+    let x = i32::try_from(parts.next()?.ok()?).ok()?;
+    let y = i32::try_from(parts.next()?.ok()?).ok()?;
+
+    (parts.next().is_none() && zoom <= MAX_ZOOM).then_some((zoom, x, y))
+}
+
+/// The canvas coordinate of the edge of a tile, or `None` if the tile, up to
+/// its far edge, lies outside the canvas coordinates.
+fn tile_edge(tile: i32, canvas_tile_size: i32) -> Option<i32> {
+    tile.checked_add(1)?.checked_mul(canvas_tile_size)?;
+
+    tile.checked_mul(canvas_tile_size)
+}
+
+/// The view rendering a tile at its zoom, or `None` for a tile too far from
+/// the origin to be in the canvas.
+fn tile_view(zoom: u32, x: i32, y: i32) -> Option<CanvasView> {
+    let canvas_tile_size = TILE_SIZE << zoom;
+
+    let mut view = CanvasView::new(TILE_SIZE, TILE_SIZE);
+    view.canvas_dimensions = Dimensions {
+        width: canvas_tile_size,
+        height: canvas_tile_size,
+    };
+    view.top_left = (
+        tile_edge(x, canvas_tile_size as i32)?,
+        tile_edge(y, canvas_tile_size as i32)?,
+    )
+        .into();
+
+    Some(view)
+}
+
+fn paint_document() -> Canvas {
+    let mut canvas = CanvasBuilder::from_preset(DocumentPreset::Square).build();
+    let document = DocumentPreset::Square.dimensions();
+
+    for (i, color) in [colors::red(), colors::green(), colors::blue()]
+        .into_iter()
+        .enumerate()
+    {
+        let offset = i as i32 * 512;
+
+        canvas.perform_raster_action(
+            1,
+            RasterLayerAction::fill_oval(
+                CanvasRect {
+                    top_left: (offset + 128, offset + 128).into(),
+                    dimensions: Dimensions {
+                        width: 768,
+                        height: 768,
+                    },
+                },
+                color,
+            ),
+        );
+    }
+    canvas.perform_raster_action(
+        1,
+        RasterLayerAction::draw_line(
+            (0, 0).into(),
+            (document.width as i32, document.height as i32).into(),
+            8,
+            colors::black(),
+        ),
+    );
+
+    canvas
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8]) {
+    let header = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+
+    // The client may have gone away, which only affects that client
+    let _ = stream
+        .write_all(header.as_bytes())
+        .and_then(|_| stream.write_all(body));
+}
+
+fn serve(canvas: &mut Canvas, stream: &mut TcpStream) -> Result<(), Box<dyn Error>> {
+    let mut request_line = String::new();
+    BufReader::new(&*stream).read_line(&mut request_line)?;
+
+    let mut request = request_line.split_whitespace();
+    let (method, path) = (request.next(), request.next());
+
+    let view = path
+        .and_then(parse_tile_path)
+        .and_then(|(zoom, x, y)| tile_view(zoom, x, y));
+
+    match (method, view) {
+        (Some("GET"), Some(view)) => {
+            let png = canvas.render(&view).encode_png()?;
+            respond(stream, "200 OK", "image/png", &png);
+        }
+        _ => respond(stream, "404 Not Found", "text/plain", b"no such tile\n"),
+    }
+
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut canvas = paint_document();
+    let listener = TcpListener::bind(ADDRESS)?;
+    println!("Serving tiles at http://{ADDRESS}/tiles/{{zoom}}/{{x}}/{{y}}.png");
+
+    for stream in listener.incoming() {
+        let mut stream = stream?;
+
+        if let Err(error) = serve(&mut canvas, &mut stream) {
+            eprintln!("Failed to serve a request: {error}");
+            respond(
+                &mut stream,
+                "500 Internal Server Error",
+                "text/plain",
+                b"failed to render tile\n",
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tile_paths_are_parsed() {
+        assert_eq!(parse_tile_path("/tiles/2/1/-3.png"), Some((2, 1, -3)));
+        assert_eq!(parse_tile_path("/tiles/9/0/0.png"), None);
+        assert_eq!(parse_tile_path("/tiles/1/0.png"), None);
+        assert_eq!(parse_tile_path("/tiles/1/0/0/0.png"), None);
+
+        let view = tile_view(1, 1, 0).unwrap();
+        assert_eq!(view.top_left, (512, 0).into());
+        assert_eq!(view.canvas_rect().dimensions.width, 512);
+
+        let (zoom, x, y) = parse_tile_path("/tiles/4/2000000/0.png").unwrap();
+        assert!(tile_view(zoom, x, y).is_none());
+        assert!(tile_view(0, -8_388_608, 0).is_some());
+        assert!(tile_view(0, i32::MIN, 0).is_none());
+    }
+}
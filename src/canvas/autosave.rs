@@ -0,0 +1,481 @@
+//! Saving only the chunks of a document that have changed since it was last
+//! saved, so large documents can be autosaved often.
+//!
+//! Each save returns a `SaveToken` remembering the content hash of every
+//! chunk it covered. The next save is given that token and writes only the
+//! chunks whose hashes differ, along with the layers and document size. Only
+//! the chunks within the canvas rects changed since the token are hashed, so
+//! the cost of a save follows how much has changed rather than the size of
+//! the document. Saves are loaded in the order they were written with
+//! `Canvas::load_incremental`, starting from a canvas the first of them was
+//! loaded into.
+//!
+//! Only the pixels of raster layers are saved. Text and vector layers are
+//! recorded so the layers of a document keep their order, but their content
+//! has to be saved by other means, as do the settings of layers such as
+//! their blend modes.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Read, Write},
+};
+
+use thiserror::Error;
+
+use crate::{
+    encoding::{
+        read_blob, read_chunk_position, read_document_dimensions, read_header, read_layers,
+        read_u32, write_blob, write_chunk_position, write_document_dimensions, write_header,
+        write_layers, write_u32, DecodeError,
+    },
+    primitives::{dimensions::Dimensions, position::ChunkPosition, rect::CanvasRect},
+    raster::{
+        chunks::{BoxRasterChunk, PngError},
+        RasterLayer,
+    },
+};
+
+use super::{sync::ChunkPatch, Canvas, LayerImplementation};
+
+const MAGIC: &[u8; 4] = b"MBIS";
+const VERSION: u8 = 1;
+
+/// An error from writing or loading an incremental save.
+#[derive(Error, Debug)]
+pub enum IncrementalSaveError {
+    #[error("failed to read or write the save: {0}")]
+    Io(#[from] io::Error),
+    #[error("failed to encode or decode a chunk: {0}")]
+    Png(#[from] PngError),
+    #[error("the data is not an incremental save of a supported version")]
+    InvalidFormat,
+    #[error("layer {0} is not a raster layer")]
+    NotARasterLayer(usize),
+    #[error("layer {0} is missing and its content isn't part of the save")]
+    MissingLayer(usize),
+    #[error("chunk {1:?} of layer {0} is not of the layer's chunk size")]
+    InvalidChunk(usize, ChunkPosition),
+}
+
+impl From<DecodeError> for IncrementalSaveError {
+    fn from(error: DecodeError) -> Self {
+        match error {
+            DecodeError::Io(error) => IncrementalSaveError::Io(error),
+            DecodeError::InvalidFormat => IncrementalSaveError::InvalidFormat,
+        }
+    }
+}
+
+/// The most changes the change log of a canvas remembers. Older changes are
+/// forgotten, and saves from before them compare every chunk instead.
+const MAX_LOGGED_CHANGES: usize = 4096;
+
+/// The canvas rects changed in each generation of a canvas since `since`, so
+/// saves only need to compare the chunks within them.
+#[derive(Debug, Clone, Default)]
+pub(super) struct ChangeLog {
+    /// The generation from which every change is logged.
+    since: u64,
+    changes: Vec<(u64, CanvasRect)>,
+}
+
+impl ChangeLog {
+    pub(super) fn record(&mut self, generation: u64, changed_canvas_rect: CanvasRect) {
+        if self.changes.len() == MAX_LOGGED_CHANGES {
+            self.since = self.changes[MAX_LOGGED_CHANGES / 2 - 1].0;
+            self.changes.drain(..MAX_LOGGED_CHANGES / 2);
+        }
+
+        self.changes.push((generation, changed_canvas_rect));
+    }
+
+    /// Forgets every change, for changes to the whole canvas that aren't
+    /// logged as rects.
+    pub(super) fn reset(&mut self, generation: u64) {
+        self.since = generation;
+        self.changes.clear();
+    }
+
+    /// The canvas rects changed after `generation`, or `None` if they are no
+    /// longer known.
+    fn changes_after(&self, generation: u64) -> Option<impl Iterator<Item = &CanvasRect>> {
+        (generation >= self.since).then(|| {
+            self.changes
+                .iter()
+                .filter(move |(changed_generation, _)| *changed_generation > generation)
+                .map(|(_, changed_canvas_rect)| changed_canvas_rect)
+        })
+    }
+}
+
+/// The content hashes of the chunks of a raster layer when it was saved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SavedLayer {
+    chunk_size: usize,
+    chunk_hashes: HashMap<ChunkPosition, u64>,
+}
+
+/// What a document looked like when it was last saved, to save only what has
+/// changed since. The default token is that of a document that has never
+/// been saved, so saving with it saves everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SaveToken {
+    /// The generation of the canvas when it was saved.
+    generation: u64,
+    /// The saved hashes of each layer, `None` for layers that aren't saved.
+    layers: Vec<Option<SavedLayer>>,
+}
+
+impl SaveToken {
+    fn saved_layer(&self, layer_num: usize) -> Option<&SavedLayer> {
+        self.layers.get(layer_num)?.as_ref()
+    }
+}
+
+impl Canvas {
+    /// A token of the current state of the document, as if it had just been
+    /// saved.
+    pub fn save_token(&self) -> SaveToken {
+        SaveToken {
+            generation: self.generation,
+            layers: self
+                .layers
+                .iter()
+                .map(|layer| match layer {
+                    LayerImplementation::RasterLayer(raster_layer) => Some(SavedLayer {
+                        chunk_size: raster_layer.chunk_size(),
+                        chunk_hashes: raster_layer.chunk_hashes(),
+                    }),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    /// The chunks that have changed since `token` was saved, with the token
+    /// of the document once they are saved. Only the chunks within the rects
+    /// changed since are hashed, unless the change log no longer goes back to
+    /// the token or a layer has been added or replaced since.
+    fn changes_since(&self, token: &SaveToken) -> (Vec<(usize, ChunkPosition)>, SaveToken) {
+        let mut dirty_chunks = vec![];
+        let mut layers = Vec::with_capacity(self.layers.len());
+
+        for (layer_num, layer) in self.layers.iter().enumerate() {
+            let raster_layer = match layer {
+                LayerImplementation::RasterLayer(raster_layer) => raster_layer,
+                _ => {
+                    layers.push(None);
+                    continue;
+                }
+            };
+            let saved_hashes = token
+                .saved_layer(layer_num)
+                .filter(|saved_layer| saved_layer.chunk_size == raster_layer.chunk_size())
+                .map(|saved_layer| &saved_layer.chunk_hashes);
+            let changes = self.change_log.changes_after(token.generation);
+
+            let (chunk_hashes, mut changed_chunk_positions) = match (saved_hashes, changes) {
+                (Some(saved_hashes), Some(changes)) => {
+                    let mut chunk_hashes = saved_hashes.clone();
+                    let chunk_positions: HashSet<ChunkPosition> = changes
+                        .flat_map(|changed_canvas_rect| {
+                            raster_layer.chunk_positions_in_rect(*changed_canvas_rect)
+                        })
+                        .collect();
+
+                    let changed_chunk_positions: Vec<ChunkPosition> = chunk_positions
+                        .into_iter()
+                        .filter(|chunk_position| {
+                            let chunk_hash = raster_layer.chunk_hash(*chunk_position);
+                            let changed = chunk_hash != saved_hashes.get(chunk_position).copied();
+
+                            match chunk_hash {
+                                Some(chunk_hash) => {
+                                    chunk_hashes.insert(*chunk_position, chunk_hash)
+                                }
+                                None => chunk_hashes.remove(chunk_position),
+                            };
+
+                            changed
+                        })
+                        .collect();
+
+                    (chunk_hashes, changed_chunk_positions)
+                }
+                (saved_hashes, _) => {
+                    let chunk_hashes = raster_layer.chunk_hashes();
+                    let chunk_positions: HashSet<&ChunkPosition> = chunk_hashes
+                        .keys()
+                        .chain(saved_hashes.into_iter().flat_map(HashMap::keys))
+                        .collect();
+
+                    let changed_chunk_positions: Vec<ChunkPosition> = chunk_positions
+                        .into_iter()
+                        .filter(|chunk_position| {
+                            chunk_hashes.get(chunk_position)
+                                != saved_hashes
+                                    .and_then(|saved_hashes| saved_hashes.get(chunk_position))
+                        })
+                        .copied()
+                        .collect();
+
+                    (chunk_hashes, changed_chunk_positions)
+                }
+            };
+            changed_chunk_positions
+                .sort_by_key(|chunk_position| (chunk_position.1, chunk_position.0));
+
+            dirty_chunks.extend(
+                changed_chunk_positions
+                    .into_iter()
+                    .map(|chunk_position| (layer_num, chunk_position)),
+            );
+            layers.push(Some(SavedLayer {
+                chunk_size: raster_layer.chunk_size(),
+                chunk_hashes,
+            }));
+        }
+
+        let token = SaveToken {
+            generation: self.generation,
+            layers,
+        };
+
+        (dirty_chunks, token)
+    }
+
+    /// The layers and chunk positions of raster layers whose contents have
+    /// changed since `token` was saved, including chunks that have been
+    /// cleared since. Every chunk of a layer added or replaced since is
+    /// included.
+    pub fn dirty_chunks_since(&self, token: &SaveToken) -> Vec<(usize, ChunkPosition)> {
+        self.changes_since(token).0
+    }
+
+    /// Writes the chunks that have changed since `token` was saved, along with
+    /// the layers and size of the document, returning the token to save the
+    /// next changes with. Chunks are written as PNG images.
+    pub fn save_incremental<W: Write>(
+        &self,
+        mut writer: W,
+        token: &SaveToken,
+    ) -> Result<SaveToken, IncrementalSaveError> {
+        write_header(&mut writer, MAGIC, VERSION)?;
+        write_document_dimensions(&mut writer, self.document_dimensions)?;
+
+        let layers: Vec<Option<usize>> = self
+            .layers
+            .iter()
+            .map(|layer| match layer {
+                LayerImplementation::RasterLayer(raster_layer) => Some(raster_layer.chunk_size()),
+                _ => None,
+            })
+            .collect();
+        write_layers(&mut writer, &layers)?;
+
+        let (dirty_chunks, next_token) = self.changes_since(token);
+        write_u32(&mut writer, dirty_chunks.len() as u32)?;
+        for (layer_num, chunk_position) in dirty_chunks {
+            let chunk = self
+                .raster_layer(layer_num)
+                .and_then(|raster_layer| raster_layer.chunk(chunk_position));
+            let png = match chunk {
+                Some(chunk) => chunk.encode_png()?,
+                None => Vec::new(),
+            };
+
+            write_u32(&mut writer, layer_num as u32)?;
+            write_chunk_position(&mut writer, chunk_position)?;
+            write_blob(&mut writer, &png)?;
+        }
+        writer.flush()?;
+
+        Ok(next_token)
+    }
+
+    /// Loads a save written by `Canvas::save_incremental` on top of this
+    /// canvas, adding the raster layers it has that the canvas doesn't. Saves
+    /// must be loaded in the order they were written. The whole save is read
+    /// and checked before the canvas is changed, so the canvas is left as it
+    /// was if it fails to load. The history isn't changed, since loading a
+    /// save isn't an edit.
+    pub fn load_incremental<R: Read>(&mut self, mut reader: R) -> Result<(), IncrementalSaveError> {
+        read_header(&mut reader, MAGIC, VERSION)?;
+        let document_dimensions = read_document_dimensions(&mut reader)?;
+
+        // The chunk size of each raster layer once the save is loaded
+        let mut chunk_sizes = vec![];
+        for (layer_num, chunk_size) in read_layers(&mut reader)?.into_iter().enumerate() {
+            chunk_sizes.push(match (self.layers.get(layer_num), chunk_size) {
+                (Some(LayerImplementation::RasterLayer(raster_layer)), Some(_)) => {
+                    Some(raster_layer.chunk_size())
+                }
+                (Some(_), None) => None,
+                (Some(_), Some(_)) => return Err(IncrementalSaveError::NotARasterLayer(layer_num)),
+                (None, Some(chunk_size)) => Some(chunk_size),
+                (None, None) => return Err(IncrementalSaveError::MissingLayer(layer_num)),
+            });
+        }
+
+        let patch_count = read_u32(&mut reader)?;
+        let mut patches = Vec::new();
+        for _ in 0..patch_count {
+            let layer_num = read_u32(&mut reader)? as usize;
+            let chunk_position = read_chunk_position(&mut reader)?;
+            let png = read_blob(&mut reader)?;
+            // Chunks that have been cleared are saved without an image
+            let chunk = if png.is_empty() {
+                None
+            } else {
+                Some(BoxRasterChunk::decode_png(&png)?)
+            };
+
+            let chunk_size = chunk_sizes
+                .get(layer_num)
+                .copied()
+                .flatten()
+                .ok_or(IncrementalSaveError::NotARasterLayer(layer_num))?;
+            let chunk_dimensions = Dimensions {
+                width: chunk_size,
+                height: chunk_size,
+            };
+            if chunk
+                .as_ref()
+                .is_some_and(|chunk| chunk.dimensions() != chunk_dimensions)
+            {
+                return Err(IncrementalSaveError::InvalidChunk(
+                    layer_num,
+                    chunk_position,
+                ));
+            }
+
+            patches.push(ChunkPatch {
+                layer_num,
+                chunk_position,
+                chunk,
+            });
+        }
+
+        for chunk_size in chunk_sizes.into_iter().skip(self.layers.len()).flatten() {
+            self.add_layer(RasterLayer::new(chunk_size).into());
+        }
+        for patch in patches {
+            self.apply_chunk_patch(patch);
+        }
+        self.document_dimensions = document_dimensions;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assert_raster_eq,
+        primitives::rect::CanvasRect,
+        raster::{pixels::colors, RasterLayerAction},
+    };
+
+    #[test]
+    fn incremental_saves_only_write_changed_chunks() {
+        let mut canvas = Canvas::default();
+        canvas.set_document_dimensions(Some(Dimensions {
+            width: 32,
+            height: 16,
+        }));
+        canvas.add_layer(RasterLayer::new(8).into());
+
+        let document_rect = canvas.document_rect().unwrap();
+        canvas.perform_raster_action(
+            0,
+            RasterLayerAction::fill_rect(document_rect, colors::red()),
+        );
+
+        let mut full_save = Vec::new();
+        let token = canvas
+            .save_incremental(&mut full_save, &SaveToken::default())
+            .unwrap();
+        assert!(canvas.dirty_chunks_since(&token).is_empty());
+
+        let changed_rect = CanvasRect {
+            top_left: (9, 1).into(),
+            dimensions: Dimensions {
+                width: 2,
+                height: 2,
+            },
+        };
+        canvas.perform_raster_action(
+            0,
+            RasterLayerAction::fill_rect(changed_rect, colors::blue()),
+        );
+        canvas.clear_rect(
+            0,
+            CanvasRect::at_origin(Dimensions {
+                width: 8,
+                height: 8,
+            }),
+        );
+        assert_eq!(
+            canvas.dirty_chunks_since(&token),
+            [(0, (0, 0).into()), (0, (1, 0).into())]
+        );
+
+        let mut autosave = Vec::new();
+        canvas.save_incremental(&mut autosave, &token).unwrap();
+        assert!(autosave.len() < full_save.len());
+
+        let mut restored = Canvas::default();
+        restored.load_incremental(full_save.as_slice()).unwrap();
+        restored.load_incremental(autosave.as_slice()).unwrap();
+        assert_eq!(restored.document_dimensions(), canvas.document_dimensions());
+        assert!(restored.diff(&canvas).is_empty());
+
+        let restored_raster = restored.rasterize_canvas_rect(document_rect);
+        let expected_raster = canvas.rasterize_canvas_rect(document_rect);
+        assert_raster_eq!(restored_raster, expected_raster);
+
+        assert!(matches!(
+            restored.load_incremental(&b"MBIS\x09"[..]),
+            Err(IncrementalSaveError::InvalidFormat)
+        ));
+    }
+
+    #[test]
+    fn bad_saves_leave_the_canvas_unchanged() {
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(8).into());
+        canvas.perform_raster_action(
+            0,
+            RasterLayerAction::fill_rect(
+                CanvasRect::at_origin(Dimensions {
+                    width: 20,
+                    height: 4,
+                }),
+                colors::red(),
+            ),
+        );
+        let mut save = Vec::new();
+        canvas
+            .save_incremental(&mut save, &SaveToken::default())
+            .unwrap();
+
+        let mut restored = Canvas::default();
+        assert!(matches!(
+            restored.load_incremental(&save[..save.len() - 1]),
+            Err(IncrementalSaveError::Io(_))
+        ));
+        assert_eq!(restored.layer_count(), 0);
+
+        let mut huge_chunks = Vec::new();
+        write_header(&mut huge_chunks, MAGIC, VERSION).unwrap();
+        write_document_dimensions(&mut huge_chunks, None).unwrap();
+        write_layers(&mut huge_chunks, &[Some(1 << 20)]).unwrap();
+        write_u32(&mut huge_chunks, 0).unwrap();
+        assert!(matches!(
+            restored.load_incremental(huge_chunks.as_slice()),
+            Err(IncrementalSaveError::InvalidFormat)
+        ));
+        assert_eq!(restored.layer_count(), 0);
+    }
+}
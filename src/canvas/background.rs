@@ -0,0 +1,136 @@
+//! What transparent regions of a canvas are composited over when it is
+//! rasterized, so that editors can show where the document is transparent.
+
+use crate::{
+    primitives::{dimensions::Dimensions, rect::CanvasRect},
+    raster::{chunks::BoxRasterChunk, pixels::colors, Pixel},
+};
+
+use super::Canvas;
+
+/// The base layers of a canvas are composited onto. Backgrounds are generated
+/// for each rect as it is rasterized, so they are unbounded like layers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CanvasBackground {
+    SolidColor(Pixel),
+    /// Squares of `size` canvas pixels alternating between `a` and `b`,
+    /// aligned so that the square at the canvas origin is `a`.
+    Checkerboard {
+        size: u32,
+        a: Pixel,
+        b: Pixel,
+    },
+    /// Leaves transparent regions transparent, such as for exporting images
+    /// with an alpha channel.
+    Transparent,
+}
+
+impl Default for CanvasBackground {
+    fn default() -> Self {
+        CanvasBackground::SolidColor(colors::white())
+    }
+}
+
+impl CanvasBackground {
+    /// The checkerboard editors commonly show behind transparent regions.
+    pub fn checkerboard() -> CanvasBackground {
+        CanvasBackground::Checkerboard {
+            size: 8,
+            a: colors::white(),
+            b: Pixel::new_rgb(204, 204, 204),
+        }
+    }
+
+    /// The background within a canvas rect.
+    pub fn rasterize_canvas_rect(&self, canvas_rect: CanvasRect) -> BoxRasterChunk {
+        let Dimensions { width, height } = canvas_rect.dimensions;
+
+        match *self {
+            CanvasBackground::SolidColor(pixel) => BoxRasterChunk::new_fill(pixel, width, height),
+            CanvasBackground::Transparent => BoxRasterChunk::new(width, height),
+            CanvasBackground::Checkerboard { size, a, b } => {
+                let size = size.max(1) as i64;
+                let square = |coordinate: i64| coordinate.div_euclid(size);
+
+                let mut raster = BoxRasterChunk::new(width, height);
+                for (y, row) in raster.pixels_mut().chunks_exact_mut(width).enumerate() {
+                    let square_y = square(canvas_rect.top_left.1 as i64 + y as i64);
+
+                    for (x, pixel) in row.iter_mut().enumerate() {
+                        let square_x = square(canvas_rect.top_left.0 as i64 + x as i64);
+
+                        *pixel = if (square_x + square_y) % 2 == 0 { a } else { b };
+                    }
+                }
+
+                raster
+            }
+        }
+    }
+}
+
+impl Canvas {
+    pub fn background(&self) -> CanvasBackground {
+        self.background
+    }
+
+    /// Sets what transparent regions of the canvas are composited over. Since
+    /// this changes the whole canvas, region observers are not notified and
+    /// the canvas should be redrawn.
+    pub fn set_background(&mut self, background: CanvasBackground) {
+        if self.background != background {
+            self.background = background;
+            self.invalidate_caches();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        canvas::CanvasView,
+        raster::{RasterLayer, RasterLayerAction},
+    };
+
+    #[test]
+    fn transparent_regions_show_the_background() {
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(8).into());
+        canvas.perform_raster_action(
+            0,
+            RasterLayerAction::fill_rect(
+                CanvasRect::at_origin(Dimensions {
+                    width: 2,
+                    height: 2,
+                }),
+                colors::red(),
+            ),
+        );
+
+        let view = CanvasView::new(4, 4);
+        assert_eq!(canvas.render(&view).pixels()[3], colors::white());
+
+        let (a, b) = (colors::black(), colors::blue());
+        canvas.set_background(CanvasBackground::Checkerboard { size: 1, a, b });
+        let render = canvas.render(&view);
+        assert!(render.pixels()[0].is_close(&colors::red(), 1));
+        assert_eq!(&render.pixels()[2..4], &[a, b]);
+        assert_eq!(&render.pixels()[14..16], &[b, a]);
+
+        let squares =
+            CanvasBackground::Checkerboard { size: 2, a, b }.rasterize_canvas_rect(CanvasRect {
+                top_left: (-3, -1).into(),
+                dimensions: Dimensions {
+                    width: 4,
+                    height: 1,
+                },
+            });
+        assert_eq!(squares.pixels(), &[b, a, a, b]);
+
+        canvas.set_background(CanvasBackground::Transparent);
+        let raster = canvas.rasterize_canvas_rect(view.canvas_rect());
+        assert_eq!(raster.pixels()[3].as_rgba().3, 0);
+    }
+}
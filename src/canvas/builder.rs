@@ -0,0 +1,181 @@
+use crate::{
+    primitives::{dimensions::Dimensions, rect::CanvasRect},
+    raster::{
+        chunk_size::auto_chunk_size, chunks::PixelFormat, pixels::colors, Pixel, RasterLayer,
+        RasterLayerAction,
+    },
+    text::TextLayer,
+    vector::VectorLayer,
+};
+
+use super::{CacheConfig, Canvas};
+
+/// The kinds of layer a `CanvasBuilder` can create.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum LayerKind {
+    Raster,
+    Text,
+    Vector,
+}
+
+/// Common document sizes to start from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum DocumentPreset {
+    /// A 1920x1080 document, the size of a full HD screen.
+    FullHd,
+    /// A 2048x2048 document for sketching.
+    Square,
+    /// A portrait A4 page at 300 DPI.
+    A4Portrait,
+}
+
+impl DocumentPreset {
+    pub fn dimensions(&self) -> Dimensions {
+        let (width, height) = match self {
+            DocumentPreset::FullHd => (1920, 1080),
+            DocumentPreset::Square => (2048, 2048),
+            DocumentPreset::A4Portrait => (2480, 3508),
+        };
+
+        Dimensions { width, height }
+    }
+}
+
+/// Builds a canvas that is ready to draw on, with its document size and
+/// initial layers set up.
+#[derive(Debug, Clone)]
+pub struct CanvasBuilder {
+    document_dimensions: Dimensions,
+    background: Option<Pixel>,
+    layers: Vec<LayerKind>,
+    chunk_size: Option<usize>,
+    pixel_format: PixelFormat,
+    cache_config: CacheConfig,
+}
+
+impl CanvasBuilder {
+    /// Starts a canvas with a document of `width` by `height`, with no layers
+    /// and a transparent background.
+    pub fn new(width: usize, height: usize) -> CanvasBuilder {
+        CanvasBuilder {
+            document_dimensions: Dimensions { width, height },
+            background: None,
+            layers: vec![],
+            chunk_size: None,
+            pixel_format: PixelFormat::default(),
+            cache_config: CacheConfig::default(),
+        }
+    }
+
+    /// Starts a canvas from a preset, with a white background and a raster
+    /// layer to draw on.
+    pub fn from_preset(preset: DocumentPreset) -> CanvasBuilder {
+        let Dimensions { width, height } = preset.dimensions();
+
+        let mut builder = CanvasBuilder::new(width, height);
+        builder.background(colors::white()).layer(LayerKind::Raster);
+
+        builder
+    }
+
+    /// Fills the document with `color` in a raster layer below the other layers.
+    pub fn background(&mut self, color: Pixel) -> &mut Self {
+        self.background = Some(color);
+        self
+    }
+
+    /// Adds a layer above the layers added so far.
+    pub fn layer(&mut self, kind: LayerKind) -> &mut Self {
+        self.layers.push(kind);
+        self
+    }
+
+    /// The chunk size of the raster layers created. Defaults to one suited to
+    /// viewing the whole document.
+    pub fn chunk_size(&mut self, chunk_size: usize) -> &mut Self {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
+    /// The pixel format raster layers store their pixels in.
+    pub fn pixel_format(&mut self, pixel_format: PixelFormat) -> &mut Self {
+        self.pixel_format = pixel_format;
+        self
+    }
+
+    /// The limits on how much the caches of the canvas hold.
+    pub fn cache_config(&mut self, cache_config: CacheConfig) -> &mut Self {
+        self.cache_config = cache_config;
+        self
+    }
+
+    pub fn build(&self) -> Canvas {
+        let chunk_size = self
+            .chunk_size
+            .unwrap_or_else(|| auto_chunk_size(self.document_dimensions));
+
+        let mut canvas = Canvas::with_pixel_format(self.pixel_format);
+        canvas.set_document_dimensions(Some(self.document_dimensions));
+        canvas.set_cache_config(self.cache_config);
+
+        if let Some(background) = self.background {
+            canvas.add_layer(RasterLayer::new(chunk_size).into());
+            canvas.perform_raster_action(
+                0,
+                RasterLayerAction::fill_rect(
+                    CanvasRect::at_origin(self.document_dimensions),
+                    background,
+                ),
+            );
+        }
+
+        for kind in self.layers.iter() {
+            match kind {
+                LayerKind::Raster => canvas.add_layer(RasterLayer::new(chunk_size).into()),
+                LayerKind::Text => canvas.add_layer(TextLayer::new().into()),
+                LayerKind::Vector => canvas.add_layer(VectorLayer::new().into()),
+            }
+        }
+
+        canvas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::CanvasView;
+
+    #[test]
+    fn builder_sets_up_document() {
+        let mut canvas = CanvasBuilder::new(64, 48)
+            .background(colors::blue())
+            .layer(LayerKind::Raster)
+            .layer(LayerKind::Text)
+            .chunk_size(16)
+            .pixel_format(PixelFormat::Rgb565A8)
+            .build();
+
+        assert_eq!(canvas.layers.len(), 3);
+        assert_eq!(canvas.pixel_format(), PixelFormat::Rgb565A8);
+        assert_eq!(
+            canvas.document_rect(),
+            Some(CanvasRect::at_origin(Dimensions {
+                width: 64,
+                height: 48
+            }))
+        );
+        assert!(canvas.text_layer(2).is_some());
+
+        let raster = canvas.render(&CanvasView::new(64, 48));
+        assert!(raster.pixels()[0].is_close(&colors::blue(), 5));
+        assert!(raster.pixels()[64 * 48 - 1].is_close(&colors::blue(), 5));
+
+        let preset_canvas = CanvasBuilder::from_preset(DocumentPreset::FullHd).build();
+        assert_eq!(preset_canvas.layers.len(), 2);
+        assert_eq!(
+            preset_canvas.document_dimensions(),
+            Some(DocumentPreset::FullHd.dimensions())
+        );
+    }
+}
@@ -1,3 +1,6 @@
+use std::{mem::size_of, ops::DerefMut};
+
+use bumpalo::Bump;
 use lru::LruCache;
 
 use crate::{
@@ -6,29 +9,79 @@ use crate::{
         position::{DrawPosition, UncheckedIntoPosition},
     },
     raster::chunks::{
-        nn_map::NearestNeighbourMap, raster_chunk::RcRasterChunk, BoxRasterChunk, RasterWindow,
+        nn_map::NearestNeighbourMapCache,
+        raster_chunk::{BumpRasterChunk, RasterChunk, RcRasterChunk},
+        BoxRasterChunk, RasterWindow, ScalingFilter,
     },
-    vector::shapes::{Oval, RasterizablePolygon},
+    raster::Pixel,
+    vector::shapes::{ConicGradient, Oval, RadialGradientDisc, RasterizablePolygon},
 };
 
-use super::{CanvasPosition, CanvasRect, CanvasView};
+#[cfg(feature = "cache-debug")]
+use super::recorder::{CacheEvent, CacheKind, CacheRecorder};
+use super::{rotation::sample_rotated_view, CanvasPosition, CanvasRect, CanvasView, ViewRotation};
 
 pub struct ShapeCache {
     oval_cache: LruCache<Oval, BoxRasterChunk>,
+    radial_gradient_cache: LruCache<RadialGradientDisc, BoxRasterChunk>,
+    conic_gradient_cache: LruCache<ConicGradient, BoxRasterChunk>,
 }
 
 impl ShapeCache {
     pub fn new() -> ShapeCache {
+        ShapeCache::with_capacity(CacheConfig::default().max_shape_entries)
+    }
+
+    /// Creates a cache holding at most `capacity` ovals, and a quarter as many
+    /// of each kind of gradient.
+    pub fn with_capacity(capacity: usize) -> ShapeCache {
+        let (oval_capacity, gradient_capacity) = ShapeCache::capacities(capacity);
+
         ShapeCache {
-            oval_cache: LruCache::new(32),
+            oval_cache: LruCache::new(oval_capacity),
+            radial_gradient_cache: LruCache::new(gradient_capacity),
+            conic_gradient_cache: LruCache::new(gradient_capacity),
         }
     }
 
+    /// The capacities of the oval and gradient caches, which hold at least one
+    /// shape each.
+    fn capacities(capacity: usize) -> (usize, usize) {
+        (capacity.max(1), (capacity / 4).max(1))
+    }
+
+    /// The most ovals the cache holds.
+    pub fn capacity(&self) -> usize {
+        self.oval_cache.cap()
+    }
+
+    /// Changes the most ovals the cache holds, evicting the least recently used
+    /// shapes past the new capacity.
+    pub fn resize(&mut self, capacity: usize) {
+        let (oval_capacity, gradient_capacity) = ShapeCache::capacities(capacity);
+
+        self.oval_cache.resize(oval_capacity);
+        self.radial_gradient_cache.resize(gradient_capacity);
+        self.conic_gradient_cache.resize(gradient_capacity);
+    }
+
     pub fn get_oval(&mut self, oval: Oval) -> &BoxRasterChunk {
         self.oval_cache
             .get_or_insert(oval, || oval.rasterize())
             .expect("this should never happen, as it only occurs with cache size 0")
     }
+
+    pub fn get_radial_gradient(&mut self, disc: RadialGradientDisc) -> &BoxRasterChunk {
+        self.radial_gradient_cache
+            .get_or_insert(disc, || disc.rasterize())
+            .expect("this should never happen, as it only occurs with cache size 0")
+    }
+
+    pub fn get_conic_gradient(&mut self, cone: ConicGradient) -> &BoxRasterChunk {
+        self.conic_gradient_cache
+            .get_or_insert(cone, || cone.rasterize())
+            .expect("this should never happen, as it only occurs with cache size 0")
+    }
 }
 
 impl Default for ShapeCache {
@@ -37,24 +90,283 @@ impl Default for ShapeCache {
     }
 }
 
-#[derive(Default)]
+/// The ratio between the scales of neighbouring zoom buckets.
+const ZOOM_BUCKET_BASE: f32 = 1.1;
+
+/// A range of view scales that share a cached raster. Views are rendered at
+/// the scale of their bucket, so that smoothly zooming within a bucket only
+/// resamples the cached raster instead of rendering the canvas again.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+struct ZoomBucket {
+    width_exponent: i32,
+    height_exponent: i32,
+}
+
+impl ZoomBucket {
+    fn exponent(factor: f32) -> i32 {
+        (factor.ln() / ZOOM_BUCKET_BASE.ln()).round() as i32
+    }
+
+    fn scaled_length(length: usize, exponent: i32) -> usize {
+        ((length as f32 * ZOOM_BUCKET_BASE.powi(exponent)).round() as usize).max(1)
+    }
+
+    pub fn from_view(view: &CanvasView) -> ZoomBucket {
+        let scale = view.view_dimensions.relative_scale(view.canvas_dimensions);
+
+        ZoomBucket {
+            width_exponent: ZoomBucket::exponent(scale.width_factor),
+            height_exponent: ZoomBucket::exponent(scale.height_factor),
+        }
+    }
+
+    /// A view of the same canvas rect as `view`, at the scale of this bucket.
+    pub fn quantize_view(&self, view: &CanvasView) -> CanvasView {
+        CanvasView {
+            top_left: view.top_left,
+            canvas_dimensions: view.canvas_dimensions,
+            view_dimensions: Dimensions {
+                width: ZoomBucket::scaled_length(view.canvas_dimensions.width, self.width_exponent),
+                height: ZoomBucket::scaled_length(
+                    view.canvas_dimensions.height,
+                    self.height_exponent,
+                ),
+            },
+            rotation: view.rotation,
+        }
+    }
+}
+
+/// The largest area, in pixels, that the view raster cache prerenders by
+/// default, which is a 4096 by 4096 raster.
+pub const DEFAULT_MAX_PRERENDER_AREA: usize = 4096 * 4096;
+
+/// Limits on how much the caches of a canvas hold, which bound their memory on
+/// large canvases.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct CacheConfig {
+    /// The most rasterized ovals kept for stamping. Gradients keep a quarter
+    /// as many of each kind.
+    pub max_shape_entries: usize,
+    /// The most nearest neighbour maps kept for scaling views.
+    pub max_nn_maps: usize,
+    /// The largest raster, in bytes, kept by the caches of rasterized canvas
+    /// rects and of rendered views. Rects and views too large for it are
+    /// rasterized again each time they are requested.
+    pub max_cached_raster_bytes: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            max_shape_entries: 32,
+            max_nn_maps: 128,
+            max_cached_raster_bytes: DEFAULT_MAX_PRERENDER_AREA * size_of::<Pixel>(),
+        }
+    }
+}
+
+/// The number of pixels in a raster of `bytes`.
+fn area_of_bytes(bytes: usize) -> usize {
+    bytes / size_of::<Pixel>()
+}
+
+/// The filter to scale a raster of `source_dimensions` to
+/// `destination_dimensions` with for a view. Smooth filters are only used when
+/// downscaling, so zoomed in views keep showing individual pixels.
+pub fn view_scaling_filter(
+    scaling_filter: ScalingFilter,
+    source_dimensions: Dimensions,
+    destination_dimensions: Dimensions,
+) -> ScalingFilter {
+    if destination_dimensions.width < source_dimensions.width
+        || destination_dimensions.height < source_dimensions.height
+    {
+        scaling_filter
+    } else {
+        ScalingFilter::NearestNeighbour
+    }
+}
+
+/// Scales `raster` into `destination` for a view, using the maps of
+/// `nn_map_cache` for nearest neighbour scaling. Rasters already rendered at
+/// the dimensions of `destination` are copied as they are.
+fn scale_for_view<D: DerefMut<Target = [Pixel]>>(
+    raster: &BoxRasterChunk,
+    destination: &mut RasterChunk<D>,
+    scaling_filter: ScalingFilter,
+    nn_map_cache: &NearestNeighbourMapCache,
+) {
+    let source_dimensions = raster.dimensions();
+    let destination_dimensions = destination.dimensions();
+
+    if source_dimensions == destination_dimensions {
+        destination.blit(&raster.as_window(), (0, 0).into());
+        return;
+    }
+
+    match view_scaling_filter(scaling_filter, source_dimensions, destination_dimensions) {
+        ScalingFilter::NearestNeighbour => nn_map_cache
+            .get_nn_map(source_dimensions, destination_dimensions)
+            .scale_using_map(raster, destination)
+            .expect("nn_map should be fetched with the dimensions of the rasters"),
+        smooth_filter => raster.scale_into(destination, smooth_filter),
+    }
+}
+
+/// Caches a render of the surroundings of the last view. Rasterizers passed to
+/// it are given a canvas rect and the view dimensions it is shown at, and may
+/// return a raster at either the dimensions of the canvas rect or the view
+/// dimensions, such as for layers that stay sharp when zoomed in. Rasters at
+/// the dimensions of the canvas rect are scaled for the view.
 pub struct CanvasViewRasterCache {
     cached_raster: Option<CachedScaledCanvasRaster>,
     nn_map_cache: NearestNeighbourMapCache,
+    max_prerender_area: usize,
+    scaling_filter: ScalingFilter,
+    #[cfg(feature = "cache-debug")]
+    recorder: CacheRecorder,
+}
+
+impl Default for CanvasViewRasterCache {
+    fn default() -> Self {
+        CanvasViewRasterCache {
+            cached_raster: None,
+            nn_map_cache: NearestNeighbourMapCache::new(CacheConfig::default().max_nn_maps),
+            max_prerender_area: DEFAULT_MAX_PRERENDER_AREA,
+            scaling_filter: ScalingFilter::default(),
+            #[cfg(feature = "cache-debug")]
+            recorder: CacheRecorder::new(CacheKind::View),
+        }
+    }
 }
 
 impl CanvasViewRasterCache {
-    fn prerender_view_area<R>(
-        view: &CanvasView,
-        nn_map_cache: &mut NearestNeighbourMapCache,
-        rasterizer: &mut R,
-    ) -> CachedScaledCanvasRaster
+    /// Drops the cached raster, keeping the nearest neighbour maps since they
+    /// don't depend on the contents of the canvas.
+    pub fn invalidate(&mut self) {
+        #[cfg(feature = "cache-debug")]
+        if let Some(cached_raster) = &self.cached_raster {
+            self.recorder
+                .record(CacheEvent::Eviction(cached_raster.view().canvas_rect()));
+        }
+
+        self.cached_raster = None;
+    }
+
+    #[cfg(feature = "cache-debug")]
+    pub(super) fn recorder(&self) -> &CacheRecorder {
+        &self.recorder
+    }
+
+    #[cfg(feature = "cache-debug")]
+    pub(super) fn recorder_mut(&mut self) -> &mut CacheRecorder {
+        &mut self.recorder
+    }
+
+    /// Records whether `view` is served from the cached raster, and the
+    /// raster that replaces it if it isn't.
+    #[cfg(feature = "cache-debug")]
+    fn record_lookup(&mut self, view: &CanvasView) {
+        let is_cached = self.cached_raster.as_ref().is_some_and(|cached_raster| {
+            cached_raster.zoom_bucket == ZoomBucket::from_view(view)
+                && cached_raster.has_view_cached(view)
+        });
+        if is_cached {
+            self.recorder.record(CacheEvent::Hit(view.canvas_rect()));
+            return;
+        }
+
+        self.recorder.record(CacheEvent::Miss(view.canvas_rect()));
+        if let Some(cached_raster) = &self.cached_raster {
+            self.recorder
+                .record(CacheEvent::Eviction(cached_raster.view().canvas_rect()));
+        }
+
+        let prerendered_view =
+            CanvasViewRasterCache::prerendered_view(view, self.max_prerender_area);
+        self.recorder
+            .record(CacheEvent::Prerender(prerendered_view.canvas_rect()));
+    }
+
+    #[cfg(test)]
+    pub fn has_cached_raster(&self) -> bool {
+        self.cached_raster.is_some()
+    }
+
+    /// The nearest neighbour maps used to scale views, which can be shared
+    /// with other canvases since they only depend on view dimensions.
+    pub fn nn_map_cache(&self) -> &NearestNeighbourMapCache {
+        &self.nn_map_cache
+    }
+
+    pub fn set_nn_map_cache(&mut self, nn_map_cache: NearestNeighbourMapCache) {
+        self.nn_map_cache = nn_map_cache;
+    }
+
+    pub fn scaling_filter(&self) -> ScalingFilter {
+        self.scaling_filter
+    }
+
+    /// Sets the filter used when views are downscaled, dropping the cached
+    /// raster since it was scaled with the previous filter.
+    pub fn set_scaling_filter(&mut self, scaling_filter: ScalingFilter) {
+        self.scaling_filter = scaling_filter;
+        self.invalidate();
+    }
+
+    /// The largest area, in pixels, of the raster prerendered around a view.
+    pub fn max_prerender_area(&self) -> usize {
+        self.max_prerender_area
+    }
+
+    /// Sets the largest area, in pixels, of the raster prerendered around a
+    /// view. Views whose surroundings would exceed it are rendered on their
+    /// own, and views exceeding it themselves aren't cached.
+    pub fn set_max_prerender_area(&mut self, max_prerender_area: usize) {
+        self.max_prerender_area = max_prerender_area;
+
+        let exceeds_area = self.cached_raster.as_ref().is_some_and(|cached_raster| {
+            cached_raster.cached_chunk.dimensions().area() > max_prerender_area
+        });
+        if exceeds_area {
+            self.invalidate();
+        }
+    }
+
+    /// Renders `view` without caching it, for views too large to cache.
+    fn render_view_uncached<R>(&mut self, view: &CanvasView, rasterizer: &mut R) -> BoxRasterChunk
     where
-        R: FnMut(&CanvasRect) -> BoxRasterChunk,
+        R: FnMut(&CanvasRect, Dimensions) -> BoxRasterChunk,
     {
+        self.invalidate();
+        #[cfg(feature = "cache-debug")]
+        self.recorder.record(CacheEvent::Miss(view.canvas_rect()));
+
+        let raster = rasterizer(&view.canvas_rect(), view.view_dimensions);
+        let mut scaled_raster =
+            BoxRasterChunk::new(view.view_dimensions.width, view.view_dimensions.height);
+        scale_for_view(
+            &raster,
+            &mut scaled_raster,
+            self.scaling_filter,
+            &self.nn_map_cache,
+        );
+
+        scaled_raster
+    }
+
+    /// The view that is prerendered to cache `view`, which is `view` at the
+    /// scale of its zoom bucket, expanded to its surroundings unless that would
+    /// exceed `max_prerender_area`.
+    pub fn prerendered_view(view: &CanvasView, max_prerender_area: usize) -> CanvasView {
+        let view = &view.bounding_view();
+        let view = &ZoomBucket::from_view(view).quantize_view(view);
+
         let requested_canvas_rect = view.canvas_rect();
-        let expanded_canvas_rect =
-            requested_canvas_rect.expand(requested_canvas_rect.dimensions.largest_dimension());
+        let expanded_canvas_rect = requested_canvas_rect
+            .try_expand(requested_canvas_rect.dimensions.largest_dimension())
+            .unwrap_or(requested_canvas_rect);
 
         let expanded_view = {
             let mut t = *view;
@@ -73,20 +385,91 @@ impl CanvasViewRasterCache {
             t
         };
 
-        let nn_map = nn_map_cache.get_nn_map_for_view(&expanded_view);
-        let raster_chunk = rasterizer(&expanded_view.canvas_rect())
-            .nn_scaled_with_map(nn_map)
-            .expect("nn_map should be fetched with size of expanded view");
+        if expanded_view.view_dimensions.width * expanded_view.view_dimensions.height
+            > max_prerender_area
+        {
+            *view
+        } else {
+            expanded_view
+        }
+    }
+
+    /// Caches a raster of `view` rendered elsewhere, such as on another thread,
+    /// replacing the cached raster.
+    #[cfg(feature = "threads")]
+    pub fn insert_prerendered_view(&mut self, view: &CanvasView, raster: BoxRasterChunk) {
+        if raster.dimensions() == view.view_dimensions {
+            #[cfg(feature = "cache-debug")]
+            self.recorder
+                .record(CacheEvent::Prerender(view.canvas_rect()));
+
+            self.cached_raster = Some(CachedScaledCanvasRaster {
+                cached_chunk_position: view.top_left,
+                cached_chunk: raster.into(),
+                canvas_dimensions: view.canvas_dimensions,
+                zoom_bucket: ZoomBucket::from_view(view),
+            });
+        }
+    }
+
+    /// Renders the surroundings of `view`, scaling into `recycled_chunk` instead
+    /// of allocating when it has the right dimensions and isn't shared.
+    fn prerender_view_area<R>(
+        view: &CanvasView,
+        nn_map_cache: &NearestNeighbourMapCache,
+        rasterizer: &mut R,
+        max_prerender_area: usize,
+        scaling_filter: ScalingFilter,
+        recycled_chunk: Option<&mut RcRasterChunk>,
+    ) -> CachedScaledCanvasRaster
+    where
+        R: FnMut(&CanvasRect, Dimensions) -> BoxRasterChunk,
+    {
+        let prerendered_view = CanvasViewRasterCache::prerendered_view(view, max_prerender_area);
+        let Dimensions { width, height } = prerendered_view.view_dimensions;
+
+        let raster_chunk = rasterizer(
+            &prerendered_view.canvas_rect(),
+            prerendered_view.view_dimensions,
+        );
+        let scale_into_new_chunk = || {
+            let mut new_chunk = BoxRasterChunk::new(width, height);
+            scale_for_view(&raster_chunk, &mut new_chunk, scaling_filter, nn_map_cache);
+
+            RcRasterChunk::from(new_chunk)
+        };
+
+        let cached_chunk = match recycled_chunk {
+            Some(recycled_chunk)
+                if recycled_chunk.dimensions() == prerendered_view.view_dimensions =>
+            {
+                match recycled_chunk.get_mut() {
+                    Some(mut recycled_pixels) => {
+                        scale_for_view(
+                            &raster_chunk,
+                            &mut recycled_pixels,
+                            scaling_filter,
+                            nn_map_cache,
+                        );
+                        recycled_chunk.clone()
+                    }
+                    None => scale_into_new_chunk(),
+                }
+            }
+            _ => scale_into_new_chunk(),
+        };
+
         CachedScaledCanvasRaster {
-            cached_chunk_position: expanded_view.top_left,
-            cached_chunk: raster_chunk.into(),
-            canvas_dimensions: expanded_view.canvas_dimensions,
+            cached_chunk_position: prerendered_view.top_left,
+            cached_chunk,
+            canvas_dimensions: prerendered_view.canvas_dimensions,
+            zoom_bucket: ZoomBucket::from_view(view),
         }
     }
 
     pub fn rerender_canvas_rect<R>(&mut self, canvas_rect: &CanvasRect, rasterizer: &mut R)
     where
-        R: FnMut(&CanvasRect) -> BoxRasterChunk,
+        R: FnMut(&CanvasRect, Dimensions) -> BoxRasterChunk,
     {
         if let Some(cached_canvas_raster) = &mut self.cached_raster {
             let cached_view = cached_canvas_raster.view();
@@ -94,8 +477,20 @@ impl CanvasViewRasterCache {
             if let Some(view_rect_needing_rerender) =
                 cached_view.transform_canvas_rect_to_view(canvas_rect)
             {
-                let new_chunk =
-                    rasterizer(canvas_rect).nn_scaled(view_rect_needing_rerender.dimensions);
+                #[cfg(feature = "cache-debug")]
+                self.recorder
+                    .record(CacheEvent::PartialRerender(*canvas_rect));
+
+                let mut new_chunk = BoxRasterChunk::new(
+                    view_rect_needing_rerender.dimensions.width,
+                    view_rect_needing_rerender.dimensions.height,
+                );
+                scale_for_view(
+                    &rasterizer(canvas_rect, view_rect_needing_rerender.dimensions),
+                    &mut new_chunk,
+                    self.scaling_filter,
+                    &self.nn_map_cache,
+                );
                 let draw_position: DrawPosition = view_rect_needing_rerender
                     .top_left
                     .unchecked_into_position();
@@ -120,58 +515,176 @@ impl CanvasViewRasterCache {
 
     fn get_chunk_from_cache<'a, R>(
         cached_canvas_raster: &'a mut CachedScaledCanvasRaster,
-        nn_map_cache: &mut NearestNeighbourMapCache,
+        nn_map_cache: &NearestNeighbourMapCache,
         view: &CanvasView,
         rasterizer: &mut R,
+        max_prerender_area: usize,
+        scaling_filter: ScalingFilter,
     ) -> RasterWindow<'a>
     where
-        R: FnMut(&CanvasRect) -> BoxRasterChunk,
+        R: FnMut(&CanvasRect, Dimensions) -> BoxRasterChunk,
     {
         // We don't use an if-let here due to some lifetime issues
         // it causes, primarily, this one https://github.com/rust-lang/rust/issues/54663
-        if view.scale_eq(&cached_canvas_raster.view()) && cached_canvas_raster.has_view_cached(view)
+        if cached_canvas_raster.zoom_bucket == ZoomBucket::from_view(view)
+            && cached_canvas_raster.has_view_cached(view)
         {
             cached_canvas_raster
                 .get_window(view)
                 .expect("cached view is checked to contain request")
         } else {
-            *cached_canvas_raster =
-                CanvasViewRasterCache::prerender_view_area(view, nn_map_cache, rasterizer);
+            *cached_canvas_raster = CanvasViewRasterCache::prerender_view_area(
+                view,
+                nn_map_cache,
+                rasterizer,
+                max_prerender_area,
+                scaling_filter,
+                Some(&mut cached_canvas_raster.cached_chunk),
+            );
             cached_canvas_raster
                 .get_window(view)
                 .expect("newly rendered view should contain request")
         }
     }
 
+    /// The cached raster of the visible canvas rect of `view`, rendering it if
+    /// it isn't cached. The raster is unturned and at the scale of the zoom
+    /// bucket of `view`, so its dimensions may differ slightly from the view
+    /// dimensions.
     pub fn get_chunk_or_rasterize<R>(
         &mut self,
         view: &CanvasView,
         rasterizer: &mut R,
-    ) -> RasterWindow
+    ) -> RasterWindow<'_>
     where
-        R: FnMut(&CanvasRect) -> BoxRasterChunk,
+        R: FnMut(&CanvasRect, Dimensions) -> BoxRasterChunk,
     {
+        let view = &view.bounding_view();
+        let quantized_view = ZoomBucket::from_view(view).quantize_view(view);
+        #[cfg(feature = "cache-debug")]
+        self.record_lookup(&quantized_view);
+
         let cached_canvas_raster = self.cached_raster.get_or_insert_with(|| {
-            CanvasViewRasterCache::prerender_view_area(view, &mut self.nn_map_cache, rasterizer)
+            CanvasViewRasterCache::prerender_view_area(
+                &quantized_view,
+                &self.nn_map_cache,
+                rasterizer,
+                self.max_prerender_area,
+                self.scaling_filter,
+                None,
+            )
         });
 
         CanvasViewRasterCache::get_chunk_from_cache(
             cached_canvas_raster,
-            &mut self.nn_map_cache,
-            view,
+            &self.nn_map_cache,
+            &quantized_view,
             rasterizer,
+            self.max_prerender_area,
+            self.scaling_filter,
         )
     }
+
+    /// Renders `view` at exactly its view dimensions, resampling the cached
+    /// raster of its zoom bucket. Rotated views are sampled from a render of
+    /// their bounding view.
+    pub fn render_view<R>(&mut self, view: &CanvasView, rasterizer: &mut R) -> BoxRasterChunk
+    where
+        R: FnMut(&CanvasRect, Dimensions) -> BoxRasterChunk,
+    {
+        if !view.rotation.is_none() {
+            let bounding_render = self.render_view(&view.bounding_view(), rasterizer);
+            let mut rotated_raster =
+                BoxRasterChunk::new(view.view_dimensions.width, view.view_dimensions.height);
+            sample_rotated_view(view, &bounding_render, &mut rotated_raster);
+
+            return rotated_raster;
+        }
+
+        if view.view_dimensions.area() > self.max_prerender_area {
+            return self.render_view_uncached(view, rasterizer);
+        }
+
+        let raster = self.get_chunk_or_rasterize(view, rasterizer).to_chunk();
+
+        if raster.dimensions() == view.view_dimensions {
+            raster
+        } else {
+            let mut scaled_raster =
+                BoxRasterChunk::new(view.view_dimensions.width, view.view_dimensions.height);
+            scale_for_view(
+                &raster,
+                &mut scaled_raster,
+                self.scaling_filter,
+                &self.nn_map_cache,
+            );
+
+            scaled_raster
+        }
+    }
+
+    /// Renders `view` at exactly its view dimensions into a bump allocator,
+    /// resampling the cached raster of its zoom bucket.
+    pub fn render_view_into_bump<'bump, R>(
+        &mut self,
+        view: &CanvasView,
+        bump: &'bump Bump,
+        rasterizer: &mut R,
+    ) -> BumpRasterChunk<'bump>
+    where
+        R: FnMut(&CanvasRect, Dimensions) -> BoxRasterChunk,
+    {
+        if !view.rotation.is_none() {
+            let bounding_render = self.render_view(&view.bounding_view(), rasterizer);
+            let mut rotated_raster = BumpRasterChunk::new(
+                view.view_dimensions.width,
+                view.view_dimensions.height,
+                bump,
+            );
+            sample_rotated_view(view, &bounding_render, &mut rotated_raster);
+
+            return rotated_raster;
+        }
+
+        if view.view_dimensions.area() > self.max_prerender_area {
+            return self
+                .render_view_uncached(view, rasterizer)
+                .as_window()
+                .to_chunk_into_bump(bump);
+        }
+
+        let raster = self.get_chunk_or_rasterize(view, rasterizer);
+
+        if raster.dimensions() == view.view_dimensions {
+            raster.to_chunk_into_bump(bump)
+        } else {
+            let raster = raster.to_chunk();
+            let mut scaled_raster = BumpRasterChunk::new(
+                view.view_dimensions.width,
+                view.view_dimensions.height,
+                bump,
+            );
+            scale_for_view(
+                &raster,
+                &mut scaled_raster,
+                self.scaling_filter,
+                &self.nn_map_cache,
+            );
+
+            scaled_raster
+        }
+    }
 }
 
 struct CachedScaledCanvasRaster {
     cached_chunk_position: CanvasPosition,
     canvas_dimensions: Dimensions,
     cached_chunk: RcRasterChunk,
+    zoom_bucket: ZoomBucket,
 }
 
 impl CachedScaledCanvasRaster {
-    pub fn get_window(&self, view: &CanvasView) -> Option<RasterWindow> {
+    pub fn get_window(&self, view: &CanvasView) -> Option<RasterWindow<'_>> {
         let cached_view = self.view();
 
         let requested_rect = cached_view.transform_canvas_rect_to_view(&view.canvas_rect())?;
@@ -193,22 +706,156 @@ impl CachedScaledCanvasRaster {
             top_left: self.cached_chunk_position,
             view_dimensions: self.cached_chunk.dimensions(),
             canvas_dimensions: self.canvas_dimensions,
+            rotation: ViewRotation::NONE,
         }
     }
 }
 
-#[derive(Default)]
-pub struct CanvasRectRasterCache(Option<CachedCanvasRaster>);
+pub struct CanvasRectRasterCache {
+    cached_raster: Option<CachedCanvasRaster>,
+    max_cached_raster_bytes: usize,
+    #[cfg(feature = "cache-debug")]
+    recorder: CacheRecorder,
+}
+
+impl Default for CanvasRectRasterCache {
+    fn default() -> Self {
+        CanvasRectRasterCache {
+            cached_raster: None,
+            max_cached_raster_bytes: CacheConfig::default().max_cached_raster_bytes,
+            #[cfg(feature = "cache-debug")]
+            recorder: CacheRecorder::new(CacheKind::CanvasRect),
+        }
+    }
+}
 
 impl CanvasRectRasterCache {
+    pub fn invalidate(&mut self) {
+        #[cfg(feature = "cache-debug")]
+        if let Some(cached_raster) = &self.cached_raster {
+            self.recorder
+                .record(CacheEvent::Eviction(cached_raster.cached_canvas_rect()));
+        }
+
+        self.cached_raster = None;
+    }
+
+    #[cfg(feature = "cache-debug")]
+    pub(super) fn recorder(&self) -> &CacheRecorder {
+        &self.recorder
+    }
+
+    #[cfg(feature = "cache-debug")]
+    pub(super) fn recorder_mut(&mut self) -> &mut CacheRecorder {
+        &mut self.recorder
+    }
+
+    /// Records whether `canvas_rect` is served from the cached raster, and
+    /// the raster that replaces it if it isn't.
+    #[cfg(feature = "cache-debug")]
+    fn record_lookup(&mut self, canvas_rect: &CanvasRect) {
+        let cached_canvas_rect = self
+            .cached_raster
+            .as_ref()
+            .map(CachedCanvasRaster::cached_canvas_rect);
+        if cached_canvas_rect.is_some_and(|cached_canvas_rect| {
+            cached_canvas_rect
+                .contains_with_offset(canvas_rect)
+                .is_some()
+        }) {
+            self.recorder.record(CacheEvent::Hit(*canvas_rect));
+            return;
+        }
+
+        self.recorder.record(CacheEvent::Miss(*canvas_rect));
+        if let Some(cached_canvas_rect) = cached_canvas_rect {
+            self.recorder
+                .record(CacheEvent::Eviction(cached_canvas_rect));
+        }
+
+        let prerendered_canvas_rect = CanvasRectRasterCache::prerendered_canvas_rect(
+            canvas_rect,
+            self.max_cached_raster_bytes,
+        );
+        self.recorder
+            .record(CacheEvent::Prerender(prerendered_canvas_rect));
+    }
+
+    pub fn max_cached_raster_bytes(&self) -> usize {
+        self.max_cached_raster_bytes
+    }
+
+    /// Sets the largest raster, in bytes, that is cached, dropping the cached
+    /// raster if it is larger.
+    pub fn set_max_cached_raster_bytes(&mut self, max_cached_raster_bytes: usize) {
+        self.max_cached_raster_bytes = max_cached_raster_bytes;
+
+        if let Some(cached_raster) = &self.cached_raster {
+            if !self.can_cache(&cached_raster.cached_canvas_rect()) {
+                self.invalidate();
+            }
+        }
+    }
+
+    /// The cached pixel of a canvas rect of a single pixel, if it is cached.
+    pub fn cached_pixel(&self, canvas_rect: &CanvasRect) -> Option<Pixel> {
+        self.cached_raster
+            .as_ref()?
+            .get_window(canvas_rect)?
+            .iter_pixels()
+            .next()
+    }
+
+    /// Whether a raster of `canvas_rect` fits within the cache.
+    pub fn can_cache(&self, canvas_rect: &CanvasRect) -> bool {
+        canvas_rect.dimensions.area() <= area_of_bytes(self.max_cached_raster_bytes)
+    }
+
+    /// The canvas rect that is prerendered to cache `canvas_rect`, which is
+    /// `canvas_rect` expanded to its surroundings unless that would exceed
+    /// `max_cached_raster_bytes`.
+    pub fn prerendered_canvas_rect(
+        canvas_rect: &CanvasRect,
+        max_cached_raster_bytes: usize,
+    ) -> CanvasRect {
+        canvas_rect
+            .try_expand(canvas_rect.dimensions.largest_dimension())
+            .ok()
+            .filter(|expanded_canvas_rect| {
+                expanded_canvas_rect.dimensions.area() <= area_of_bytes(max_cached_raster_bytes)
+            })
+            .unwrap_or(*canvas_rect)
+    }
+
+    /// Caches a raster of `canvas_rect` rendered elsewhere, such as on another
+    /// thread, replacing the cached raster.
+    #[cfg(feature = "threads")]
+    pub fn insert_prerendered_canvas_rect(
+        &mut self,
+        canvas_rect: &CanvasRect,
+        raster: BoxRasterChunk,
+    ) {
+        if raster.dimensions() == canvas_rect.dimensions && self.can_cache(canvas_rect) {
+            #[cfg(feature = "cache-debug")]
+            self.recorder.record(CacheEvent::Prerender(*canvas_rect));
+
+            self.cached_raster = Some(CachedCanvasRaster {
+                cached_chunk_position: canvas_rect.top_left,
+                cached_chunk: raster,
+            });
+        }
+    }
+
     fn prerender_canvas_rect_area<R>(
         canvas_rect: &CanvasRect,
         rasterizer: &mut R,
+        max_cached_raster_bytes: usize,
     ) -> CachedCanvasRaster
     where
         R: FnMut(&CanvasRect) -> BoxRasterChunk,
     {
-        let expanded_canvas_rect = canvas_rect.expand(canvas_rect.dimensions.largest_dimension());
+        let expanded_canvas_rect =
+            CanvasRectRasterCache::prerendered_canvas_rect(canvas_rect, max_cached_raster_bytes);
         let raster_chunk = rasterizer(&expanded_canvas_rect);
         CachedCanvasRaster {
             cached_chunk_position: expanded_canvas_rect.top_left,
@@ -220,11 +867,15 @@ impl CanvasRectRasterCache {
     where
         R: FnMut(&CanvasRect) -> BoxRasterChunk,
     {
-        if let Some(cached_canvas_raster) = &mut self.0 {
+        if let Some(cached_canvas_raster) = &mut self.cached_raster {
             if let Some(rect_offset) = cached_canvas_raster
                 .cached_canvas_rect()
                 .contains_with_offset(canvas_rect)
             {
+                #[cfg(feature = "cache-debug")]
+                self.recorder
+                    .record(CacheEvent::PartialRerender(*canvas_rect));
+
                 let new_chunk = rasterizer(canvas_rect);
                 let draw_position: DrawPosition = rect_offset.unchecked_into_position();
 
@@ -239,6 +890,7 @@ impl CanvasRectRasterCache {
         cached_canvas_raster: &'a mut CachedCanvasRaster,
         canvas_rect: &CanvasRect,
         rasterizer: &mut R,
+        max_cached_raster_bytes: usize,
     ) -> RasterWindow<'a>
     where
         R: FnMut(&CanvasRect) -> BoxRasterChunk,
@@ -250,8 +902,11 @@ impl CanvasRectRasterCache {
                 .get_window(canvas_rect)
                 .expect("cached canvas rect has been checked to contain request")
         } else {
-            *cached_canvas_raster =
-                CanvasRectRasterCache::prerender_canvas_rect_area(canvas_rect, rasterizer);
+            *cached_canvas_raster = CanvasRectRasterCache::prerender_canvas_rect_area(
+                canvas_rect,
+                rasterizer,
+                max_cached_raster_bytes,
+            );
 
             cached_canvas_raster
                 .get_window(canvas_rect)
@@ -259,19 +914,35 @@ impl CanvasRectRasterCache {
         }
     }
 
+    /// The cached raster of `canvas_rect`, rasterizing it and its surroundings
+    /// if it isn't cached. Callers should check `can_cache` first, since a
+    /// raster of `canvas_rect` is cached even if it is too large.
     pub fn get_chunk_or_rasterize<R>(
         &mut self,
         canvas_rect: &CanvasRect,
         rasterizer: &mut R,
-    ) -> RasterWindow
+    ) -> RasterWindow<'_>
     where
         R: FnMut(&CanvasRect) -> BoxRasterChunk,
     {
-        let cached_canvas_raster = self.0.get_or_insert_with(|| {
-            CanvasRectRasterCache::prerender_canvas_rect_area(canvas_rect, rasterizer)
+        #[cfg(feature = "cache-debug")]
+        self.record_lookup(canvas_rect);
+
+        let max_cached_raster_bytes = self.max_cached_raster_bytes;
+        let cached_canvas_raster = self.cached_raster.get_or_insert_with(|| {
+            CanvasRectRasterCache::prerender_canvas_rect_area(
+                canvas_rect,
+                rasterizer,
+                max_cached_raster_bytes,
+            )
         });
 
-        CanvasRectRasterCache::get_chunk_from_cache(cached_canvas_raster, canvas_rect, rasterizer)
+        CanvasRectRasterCache::get_chunk_from_cache(
+            cached_canvas_raster,
+            canvas_rect,
+            rasterizer,
+            max_cached_raster_bytes,
+        )
     }
 }
 
@@ -288,7 +959,7 @@ impl CachedCanvasRaster {
         }
     }
 
-    pub fn get_window(&self, canvas_rect: &CanvasRect) -> Option<RasterWindow> {
+    pub fn get_window(&self, canvas_rect: &CanvasRect) -> Option<RasterWindow<'_>> {
         self.cached_canvas_rect()
             .contains_with_offset(canvas_rect)
             .map(|canvas_rect_offset_from_cached| {
@@ -307,52 +978,23 @@ impl CachedCanvasRaster {
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
-struct ViewDimensions {
-    canvas_dimensions: Dimensions,
-    view_dimensions: Dimensions,
-}
-
-impl ViewDimensions {
-    pub fn from_view(view: &CanvasView) -> ViewDimensions {
-        ViewDimensions {
-            canvas_dimensions: view.canvas_dimensions,
-            view_dimensions: view.view_dimensions,
-        }
-    }
-}
-
-pub struct NearestNeighbourMapCache(LruCache<ViewDimensions, NearestNeighbourMap>);
-
-impl NearestNeighbourMapCache {
-    pub fn get_nn_map_for_view(&mut self, view: &CanvasView) -> &NearestNeighbourMap {
-        self.0
-            .get_or_insert(ViewDimensions::from_view(view), || {
-                view.create_nn_map_to_view_dimensions()
-            })
-            .expect("this should never happen, as it only occurs with cache size 0")
-    }
-}
-
-impl Default for NearestNeighbourMapCache {
-    fn default() -> Self {
-        NearestNeighbourMapCache(LruCache::new(128))
-    }
-}
-
 #[cfg(test)]
 mod tests {
 
     use super::{CachedCanvasRaster, CanvasRectRasterCache, CanvasViewRasterCache};
     use crate::{
         assert_raster_eq,
-        canvas::{CanvasRect, CanvasView},
+        canvas::{CanvasRect, CanvasView, ViewRotation},
         primitives::{
             dimensions::Dimensions,
             position::UncheckedIntoPosition,
             rect::{DrawRect, RasterRect},
         },
-        raster::{chunks::BoxRasterChunk, pixels::colors, source::Subsource},
+        raster::{
+            chunks::{BoxRasterChunk, ScalingFilter},
+            pixels::colors,
+            source::Subsource,
+        },
     };
 
     fn rasterizer_from_chunk(
@@ -370,6 +1012,16 @@ mod tests {
         }
     }
 
+    /// A view cache rasterizer reading canvas rects out of `raster_chunk`,
+    /// leaving the rasters at the dimensions of the canvas rects.
+    fn view_rasterizer_from_chunk(
+        raster_chunk: &BoxRasterChunk,
+    ) -> impl Fn(&CanvasRect, Dimensions) -> BoxRasterChunk + '_ {
+        let rasterizer = rasterizer_from_chunk(raster_chunk);
+
+        move |rect: &CanvasRect, _| rasterizer(rect)
+    }
+
     #[test]
     fn canvas_rect_rasterization_cache_caches_renders() {
         let mut cache = CanvasRectRasterCache::default();
@@ -392,7 +1044,7 @@ mod tests {
 
         let expected_cached_chunk = BoxRasterChunk::new_fill(colors::green(), 64 * 3, 64 * 3);
 
-        let cached_canvas_raster = cache.0.unwrap();
+        let cached_canvas_raster = cache.cached_raster.unwrap();
         let cached_chunk = cached_canvas_raster.cached_chunk;
 
         assert_eq!(
@@ -410,10 +1062,13 @@ mod tests {
         let render_chunk = BoxRasterChunk::new_fill(colors::green(), 64, 64);
         let cached_chunk = BoxRasterChunk::new_fill(colors::red(), 64, 64);
 
-        let mut cache = CanvasRectRasterCache(Some(CachedCanvasRaster {
-            cached_chunk_position: (0, 0).into(),
-            cached_chunk: cached_chunk.clone(),
-        }));
+        let mut cache = CanvasRectRasterCache {
+            cached_raster: Some(CachedCanvasRaster {
+                cached_chunk_position: (0, 0).into(),
+                cached_chunk: cached_chunk.clone(),
+            }),
+            ..Default::default()
+        };
 
         let canvas_rect = CanvasRect {
             top_left: (0, 0).into(),
@@ -451,7 +1106,7 @@ mod tests {
             render_chunk
         };
 
-        let mut rasterizer = rasterizer_from_chunk(&render_chunk);
+        let mut rasterizer = view_rasterizer_from_chunk(&render_chunk);
 
         {
             let canvas_view = CanvasView {
@@ -464,6 +1119,7 @@ mod tests {
                     width: 20,
                     height: 20,
                 },
+                rotation: ViewRotation::NONE,
             };
 
             let cached_chunk = canvas_view_raster_cache
@@ -500,6 +1156,7 @@ mod tests {
                     width: 20,
                     height: 20,
                 },
+                rotation: ViewRotation::NONE,
             };
 
             let cached_chunk = canvas_view_raster_cache
@@ -526,4 +1183,128 @@ mod tests {
             assert_raster_eq!(cached_chunk, expected_chunk);
         }
     }
+
+    #[test]
+    fn zooming_within_a_bucket_resamples_cached_raster() {
+        let mut canvas_view_raster_cache = CanvasViewRasterCache::default();
+        let render_chunk = BoxRasterChunk::new_fill(colors::blue(), 400, 400);
+
+        let mut rasterizations = 0;
+        let mut rasterizer = |rect: &CanvasRect, _| {
+            rasterizations += 1;
+            rasterizer_from_chunk(&render_chunk)(rect)
+        };
+
+        let expected_chunk = BoxRasterChunk::new_fill(colors::blue(), 100, 100);
+
+        let mut canvas_view = CanvasView {
+            top_left: (150, 150).into(),
+            view_dimensions: Dimensions {
+                width: 100,
+                height: 100,
+            },
+            canvas_dimensions: Dimensions {
+                width: 94,
+                height: 94,
+            },
+            rotation: ViewRotation::NONE,
+        };
+
+        // Each step is too far from the first scale for `scale_eq`, but stays
+        // within the same zoom bucket.
+        for canvas_length in [94, 93, 91, 89, 88] {
+            canvas_view.pin_resize_canvas(Dimensions {
+                width: canvas_length,
+                height: canvas_length,
+            });
+
+            let rendered_chunk =
+                canvas_view_raster_cache.render_view(&canvas_view, &mut rasterizer);
+
+            assert_raster_eq!(rendered_chunk, expected_chunk);
+        }
+
+        assert_eq!(rasterizations, 1);
+    }
+
+    #[test]
+    fn downscaled_views_use_scaling_filter() {
+        let checkerboard = (0..400 * 400)
+            .map(|i| {
+                if (i % 400 + i / 400) % 2 == 0 {
+                    colors::black()
+                } else {
+                    colors::white()
+                }
+            })
+            .collect();
+        let render_chunk = BoxRasterChunk::from_vec(checkerboard, 400, 400).unwrap();
+        let mut rasterizer = view_rasterizer_from_chunk(&render_chunk);
+
+        let canvas_view = CanvasView {
+            top_left: (150, 150).into(),
+            view_dimensions: Dimensions {
+                width: 32,
+                height: 32,
+            },
+            canvas_dimensions: Dimensions {
+                width: 64,
+                height: 64,
+            },
+            rotation: ViewRotation::NONE,
+        };
+
+        let mut canvas_view_raster_cache = CanvasViewRasterCache::default();
+        let nearest_chunk = canvas_view_raster_cache.render_view(&canvas_view, &mut rasterizer);
+        assert!(nearest_chunk
+            .pixels()
+            .iter()
+            .all(|pixel| *pixel == colors::black() || *pixel == colors::white()));
+
+        canvas_view_raster_cache.set_scaling_filter(ScalingFilter::Bilinear);
+        let bilinear_chunk = canvas_view_raster_cache.render_view(&canvas_view, &mut rasterizer);
+        assert!(bilinear_chunk.pixels().iter().all(|pixel| {
+            let (r, _, _, a) = pixel.as_rgba();
+            (64..192).contains(&r) && a == 255
+        }));
+    }
+
+    #[test]
+    fn prerender_recycles_allocation_and_respects_max_area() {
+        let mut canvas_view_raster_cache = CanvasViewRasterCache::default();
+        let render_chunk = BoxRasterChunk::new_fill(colors::green(), 400, 400);
+        let mut rasterizer = view_rasterizer_from_chunk(&render_chunk);
+
+        let mut canvas_view = CanvasView::new(20, 20);
+        canvas_view.translate((100, 100).into());
+
+        canvas_view_raster_cache.render_view(&canvas_view, &mut rasterizer);
+        let cached_pixels = |cache: &CanvasViewRasterCache| {
+            cache
+                .cached_raster
+                .as_ref()
+                .unwrap()
+                .cached_chunk
+                .pixels()
+                .as_ptr()
+        };
+        let first_allocation = cached_pixels(&canvas_view_raster_cache);
+
+        // Panning out of the prerendered area renders into the same allocation.
+        canvas_view.translate((200, 200).into());
+        canvas_view_raster_cache.render_view(&canvas_view, &mut rasterizer);
+        assert_eq!(cached_pixels(&canvas_view_raster_cache), first_allocation);
+
+        canvas_view_raster_cache.set_max_prerender_area(20 * 20);
+        canvas_view.translate((-200, -200).into());
+        let rendered_chunk = canvas_view_raster_cache.render_view(&canvas_view, &mut rasterizer);
+
+        let cached_raster = canvas_view_raster_cache.cached_raster.as_ref().unwrap();
+        assert_eq!(
+            cached_raster.cached_chunk.dimensions(),
+            canvas_view.view_dimensions
+        );
+        let expected_chunk = BoxRasterChunk::new_fill(colors::green(), 20, 20);
+        assert_raster_eq!(rendered_chunk, expected_chunk);
+    }
 }
@@ -0,0 +1,152 @@
+//! Exporting the selected part of a canvas and cropping the document to it.
+
+use crate::{
+    primitives::{rect::CanvasRect, transform::Affine},
+    raster::{chunks::BoxRasterChunk, CopyMode},
+};
+
+use super::{Canvas, LayerImplementation};
+
+impl Canvas {
+    /// The smallest canvas rect containing the selection, limited to the
+    /// document rect if the canvas has one. Inverted selections are only
+    /// bounded by the document rect.
+    fn selection_bounds(&self) -> Option<CanvasRect> {
+        let selection = self.selection.as_ref()?;
+
+        match (selection.bounds(), self.document_rect()) {
+            (Some(bounds), Some(document_rect)) => bounds.intersection(&document_rect),
+            (Some(bounds), None) => Some(bounds),
+            (None, document_rect) if selection.is_inverted() => document_rect,
+            (None, _) => None,
+        }
+    }
+
+    /// Rasterizes the smallest canvas rect containing the selection, with the
+    /// alpha of pixels scaled by how much they are selected so that unselected
+    /// pixels are transparent. Returns the raster with the canvas rect it
+    /// covers, or `None` if nothing is selected.
+    pub fn export_selection(&mut self) -> Option<(CanvasRect, BoxRasterChunk)> {
+        let bounds = self.selection_bounds()?;
+        let mut raster = self.rasterize_canvas_rect(bounds);
+
+        if let Some(selection) = &self.selection {
+            selection
+                .rasterize_canvas_rect(bounds)
+                .mask_alpha(&mut raster);
+        }
+
+        Some((bounds, raster))
+    }
+
+    /// Crops the document to the smallest canvas rect containing the
+    /// selection. Every layer is moved so that the top left of that rect
+    /// becomes the canvas origin, the pixels of raster layers outside of it
+    /// are dropped, and the document dimensions become its dimensions. The
+    /// selection and history are cleared, since they refer to the old
+    /// positions. Returns the new document rect, or `None` without changing
+    /// anything if nothing is selected.
+    pub fn crop_to_selection(&mut self) -> Option<CanvasRect> {
+        let bounds = self.selection_bounds()?;
+        let (dx, dy) = (-bounds.top_left.0, -bounds.top_left.1);
+
+        for layer in self.layers.iter_mut() {
+            match layer {
+                LayerImplementation::RasterLayer(raster_layer) => {
+                    let raster = raster_layer.extract_rect(bounds);
+                    let mut cropped = raster_layer.without_chunks();
+                    cropped.draw_raster((0, 0).into(), &raster, CopyMode::Blit);
+                    *raster_layer = cropped;
+                }
+                LayerImplementation::TextLayer(text_layer) => {
+                    let ids: Vec<_> = text_layer.iter().map(|(id, _)| id).collect();
+                    for id in ids {
+                        text_layer.edit_text(id, |text_object| {
+                            text_object.position = text_object.position.translate((dx, dy).into());
+                        });
+                    }
+                }
+                LayerImplementation::VectorLayer(vector_layer) => {
+                    let ids: Vec<_> = vector_layer.iter().map(|(id, _)| id).collect();
+                    vector_layer.transform_shapes(&ids, &Affine::translate(dx as f32, dy as f32));
+                }
+            }
+        }
+
+        self.selection = None;
+        self.history.clear();
+        self.document_dimensions = Some(bounds.dimensions);
+        self.invalidate_caches();
+
+        self.document_rect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        canvas::CanvasBackground,
+        primitives::dimensions::Dimensions,
+        raster::{pixels::colors, RasterLayer, RasterLayerAction, Selection},
+    };
+
+    #[test]
+    fn selections_are_exported_and_cropped_to() {
+        let mut canvas = Canvas::default();
+        canvas.set_background(CanvasBackground::Transparent);
+        canvas.set_document_dimensions(Some(Dimensions {
+            width: 32,
+            height: 32,
+        }));
+        canvas.add_layer(RasterLayer::new(8).into());
+        canvas.perform_raster_action(
+            0,
+            RasterLayerAction::fill_rect(
+                CanvasRect {
+                    top_left: (4, 4).into(),
+                    dimensions: Dimensions {
+                        width: 20,
+                        height: 20,
+                    },
+                },
+                colors::red(),
+            ),
+        );
+
+        assert!(canvas.export_selection().is_none());
+
+        let selected_rect = CanvasRect {
+            top_left: (10, 12).into(),
+            dimensions: Dimensions {
+                width: 6,
+                height: 4,
+            },
+        };
+        let oval_rect = CanvasRect {
+            top_left: (6, 6).into(),
+            dimensions: Dimensions {
+                width: 16,
+                height: 12,
+            },
+        };
+        canvas.set_selection(Some(Selection::oval(oval_rect)));
+        let (bounds, raster) = canvas.export_selection().unwrap();
+        assert!(oval_rect.contains_with_offset(&bounds).is_some());
+        assert_eq!(raster.dimensions(), bounds.dimensions);
+        let alphas: Vec<u8> = raster.pixels().iter().map(|p| p.as_rgba().3).collect();
+        assert!(alphas.contains(&u8::MAX));
+        assert_eq!(alphas[0], 0);
+
+        canvas.set_selection(Some(Selection::rect(selected_rect)));
+        assert_eq!(
+            canvas.crop_to_selection(),
+            Some(CanvasRect::at_origin(selected_rect.dimensions))
+        );
+        assert_eq!(canvas.document_dimensions(), Some(selected_rect.dimensions));
+        assert!(canvas.selection().is_none());
+        assert_eq!(canvas.pixel_at((0, 0).into()), colors::red());
+        assert_eq!(canvas.pixel_at((5, 3).into()), colors::red());
+        assert_eq!(canvas.pixel_at((6, 0).into()).as_rgba().3, 0);
+    }
+}
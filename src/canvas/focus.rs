@@ -0,0 +1,105 @@
+//! Where the user is working on the canvas, as hinted by the embedder, so
+//! that work on the rest of the viewport can wait until that region is done.
+
+use crate::primitives::rect::CanvasRect;
+
+use super::Canvas;
+
+/// The squared distance between the nearest pixels of two canvas rects, which
+/// is 0 if they overlap.
+fn squared_distance(a: &CanvasRect, b: &CanvasRect) -> u64 {
+    let gap = |a_start: i32, a_end: i32, b_start: i32, b_end: i32| {
+        (a_start as i64 - b_end as i64)
+            .max(b_start as i64 - a_end as i64)
+            .max(0) as u64
+    };
+
+    let (a_bottom_right, b_bottom_right) = (a.bottom_right(), b.bottom_right());
+    let dx = gap(
+        a.top_left.0,
+        a_bottom_right.0,
+        b.top_left.0,
+        b_bottom_right.0,
+    );
+    let dy = gap(
+        a.top_left.1,
+        a_bottom_right.1,
+        b.top_left.1,
+        b_bottom_right.1,
+    );
+
+    dx * dx + dy * dy
+}
+
+impl Canvas {
+    /// Hints that the user is working within `canvas_rect`, such as around the
+    /// cursor or the stroke being drawn. Work that can be done in any order,
+    /// such as the patches of `Canvas::render_dirty`, is done nearest to the
+    /// focus first. The hint lasts until it is replaced or cleared.
+    pub fn hint_focus(&mut self, canvas_rect: CanvasRect) {
+        self.focus = Some(canvas_rect);
+    }
+
+    pub fn clear_focus(&mut self) {
+        self.focus = None;
+    }
+
+    pub fn focus(&self) -> Option<CanvasRect> {
+        self.focus
+    }
+
+    /// Sorts canvas rects so that those nearest to the focus come first, such
+    /// as regions to prefetch with a `CacheWarmer`. Rects overlapping the focus
+    /// keep their order at the front. Without a focus, the order is unchanged.
+    pub fn order_by_focus(&self, canvas_rects: &mut [CanvasRect]) {
+        if let Some(focus) = self.focus {
+            canvas_rects.sort_by_key(|canvas_rect| squared_distance(&focus, canvas_rect));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        canvas::CanvasView,
+        primitives::dimensions::Dimensions,
+        raster::{pixels::colors, RasterLayer, RasterLayerAction},
+    };
+
+    #[test]
+    fn dirty_patches_nearest_to_the_focus_come_first() {
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(8).into());
+
+        let square = |x, y| CanvasRect {
+            top_left: (x, y).into(),
+            dimensions: Dimensions {
+                width: 4,
+                height: 4,
+            },
+        };
+        let view = CanvasView::new(32, 32);
+        canvas.render(&view);
+
+        for (x, y) in [(0, 0), (24, 24), (12, 12)] {
+            canvas.perform_raster_action(
+                0,
+                RasterLayerAction::fill_rect(square(x, y), colors::red()),
+            );
+        }
+        canvas.hint_focus(square(26, 26));
+
+        let patch_positions: Vec<(usize, usize)> = canvas
+            .render_dirty(&view)
+            .into_iter()
+            .map(|(view_rect, _)| (view_rect.top_left.0, view_rect.top_left.1))
+            .collect();
+        assert_eq!(patch_positions, [(24, 24), (12, 12), (0, 0)]);
+
+        canvas.clear_focus();
+        let mut canvas_rects = [square(24, 24), square(0, 0)];
+        canvas.order_by_focus(&mut canvas_rects);
+        assert_eq!(canvas_rects, [square(24, 24), square(0, 0)]);
+    }
+}
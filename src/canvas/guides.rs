@@ -0,0 +1,248 @@
+use crate::{
+    primitives::position::{CanvasPosition, PixelPosition},
+    raster::{chunks::BoxRasterChunk, source::MutRasterSource, Pixel},
+};
+
+use super::{Canvas, CanvasView};
+
+/// How far the arms of an anchor's cross extend from its center in the overlay.
+const ANCHOR_ARM_LENGTH: i32 = 3;
+
+/// A target in canvas space that positions can be snapped to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Guide {
+    /// A horizontal line at a canvas y coordinate.
+    Horizontal(i32),
+    /// A vertical line at a canvas x coordinate.
+    Vertical(i32),
+    /// A single point.
+    Anchor(CanvasPosition),
+}
+
+impl Guide {
+    /// The closest position on the guide to `position`.
+    pub fn closest_position(&self, position: CanvasPosition) -> CanvasPosition {
+        match *self {
+            Guide::Horizontal(y) => (position.0, y).into(),
+            Guide::Vertical(x) => (x, position.1).into(),
+            Guide::Anchor(anchor) => anchor,
+        }
+    }
+
+    /// The euclidean distance from `position` to the guide.
+    pub fn distance_to(&self, position: CanvasPosition) -> f32 {
+        let closest = self.closest_position(position);
+        let (dx, dy) = (
+            (closest.0 - position.0) as f32,
+            (closest.1 - position.1) as f32,
+        );
+
+        f32::sqrt(dx.powi(2) + dy.powi(2))
+    }
+}
+
+/// The result of snapping a position to a guide.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GuideSnap {
+    pub guide: Guide,
+    pub snapped_position: CanvasPosition,
+    pub distance: f32,
+}
+
+/// A registry of guides in a canvas.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Guides {
+    guides: Vec<Guide>,
+}
+
+impl Guides {
+    pub fn new() -> Guides {
+        Guides::default()
+    }
+
+    /// Adds a guide, returning `false` if an identical guide already exists.
+    pub fn add(&mut self, guide: Guide) -> bool {
+        if self.guides.contains(&guide) {
+            false
+        } else {
+            self.guides.push(guide);
+            true
+        }
+    }
+
+    /// Removes a guide, returning whether or not it existed.
+    pub fn remove(&mut self, guide: Guide) -> bool {
+        let len_before = self.guides.len();
+        self.guides.retain(|g| *g != guide);
+
+        self.guides.len() != len_before
+    }
+
+    pub fn clear(&mut self) {
+        self.guides.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.guides.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.guides.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Guide> {
+        self.guides.iter()
+    }
+
+    /// The guide closest to `position` that is at most `radius` away from it.
+    /// When guides are equally close, the one added first is returned.
+    pub fn nearest_guide(&self, position: CanvasPosition, radius: f32) -> Option<GuideSnap> {
+        self.guides
+            .iter()
+            .map(|guide| GuideSnap {
+                guide: *guide,
+                snapped_position: guide.closest_position(position),
+                distance: guide.distance_to(position),
+            })
+            .filter(|snap| snap.distance <= radius)
+            .fold(None, |nearest: Option<GuideSnap>, snap| match nearest {
+                Some(nearest) if nearest.distance <= snap.distance => Some(nearest),
+                _ => Some(snap),
+            })
+    }
+
+    /// Snaps a position to the nearest guide within `radius`, leaving it
+    /// unchanged if there is none.
+    pub fn snap(&self, position: CanvasPosition, radius: f32) -> CanvasPosition {
+        self.nearest_guide(position, radius)
+            .map(|snap| snap.snapped_position)
+            .unwrap_or(position)
+    }
+
+    /// Rasterizes the guides visible in a view onto a transparent overlay of the
+    /// view's dimensions.
+    pub fn rasterize_overlay(&self, view: &CanvasView, color: Pixel) -> BoxRasterChunk {
+        let view_dimensions = view.view_dimensions;
+        let mut overlay = BoxRasterChunk::new(view_dimensions.width, view_dimensions.height);
+
+        let draw_pixel = |overlay: &mut BoxRasterChunk, position: PixelPosition| {
+            if let Some(pixel) = overlay.mut_pixel_at_position(position) {
+                *pixel = color;
+            }
+        };
+
+        for guide in self.guides.iter() {
+            match *guide {
+                Guide::Horizontal(y) => {
+                    if let Some(view_position) =
+                        view.transform_canvas_to_view((view.top_left.0, y).into())
+                    {
+                        for x in 0..view_dimensions.width {
+                            draw_pixel(&mut overlay, (x, view_position.1).into());
+                        }
+                    }
+                }
+                Guide::Vertical(x) => {
+                    if let Some(view_position) =
+                        view.transform_canvas_to_view((x, view.top_left.1).into())
+                    {
+                        for y in 0..view_dimensions.height {
+                            draw_pixel(&mut overlay, (view_position.0, y).into());
+                        }
+                    }
+                }
+                Guide::Anchor(anchor) => {
+                    if let Some(view_position) = view.transform_canvas_to_view(anchor) {
+                        let center = (view_position.0 as i32, view_position.1 as i32);
+                        for offset in -ANCHOR_ARM_LENGTH..=ANCHOR_ARM_LENGTH {
+                            for (x, y) in
+                                [(center.0 + offset, center.1), (center.0, center.1 + offset)]
+                            {
+                                if x >= 0 && y >= 0 {
+                                    draw_pixel(&mut overlay, (x as usize, y as usize).into());
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        overlay
+    }
+}
+
+impl Canvas {
+    pub fn guides(&self) -> &Guides {
+        &self.guides
+    }
+
+    pub fn guides_mut(&mut self) -> &mut Guides {
+        &mut self.guides
+    }
+
+    /// Renders the overlay elements of the canvas (such as guides) for a view. The
+    /// overlay is transparent outside of those elements, so it can be composited
+    /// over the result of `Canvas::render`.
+    pub fn render_overlay(&self, view: &CanvasView, color: Pixel) -> BoxRasterChunk {
+        self.guides.rasterize_overlay(view, color)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raster::{pixels::colors, source::RasterSource};
+
+    #[test]
+    fn nearest_guide_within_radius() {
+        let mut guides = Guides::new();
+        guides.add(Guide::Horizontal(10));
+        guides.add(Guide::Vertical(-4));
+        guides.add(Guide::Anchor((3, 3).into()));
+
+        assert!(!guides.add(Guide::Horizontal(10)));
+        assert_eq!(guides.len(), 3);
+
+        let snap = guides.nearest_guide((0, 12).into(), 5.0).unwrap();
+        assert_eq!(snap.guide, Guide::Horizontal(10));
+        assert_eq!(snap.snapped_position, (0, 10).into());
+
+        let snap = guides.nearest_guide((-2, 30).into(), 5.0).unwrap();
+        assert_eq!(snap.guide, Guide::Vertical(-4));
+
+        let snap = guides.nearest_guide((4, 4).into(), 5.0).unwrap();
+        assert_eq!(snap.guide, Guide::Anchor((3, 3).into()));
+
+        assert_eq!(guides.nearest_guide((20, 20).into(), 5.0), None);
+        assert_eq!(guides.snap((20, 20).into(), 5.0), (20, 20).into());
+
+        assert!(guides.remove(Guide::Horizontal(10)));
+        assert_eq!(guides.nearest_guide((0, 12).into(), 2.0), None);
+    }
+
+    #[test]
+    fn overlay_draws_guides_in_view() {
+        let mut guides = Guides::new();
+        guides.add(Guide::Horizontal(5));
+        guides.add(Guide::Vertical(100));
+
+        let mut view = CanvasView::new(10, 10);
+        view.translate((0, 2).into());
+
+        let overlay = guides.rasterize_overlay(&view, colors::red());
+
+        for x in 0..10 {
+            assert_eq!(
+                overlay.pixel_at_position((x, 3).into()),
+                Some(colors::red())
+            );
+            assert_eq!(
+                overlay.pixel_at_position((x, 4).into()),
+                Some(colors::transparent())
+            );
+        }
+    }
+}
@@ -0,0 +1,397 @@
+//! Undoing and redoing actions performed on a canvas. Rather than keeping
+//! copies of whole layers, each action only keeps the chunks it changed, so
+//! the memory used grows with the area edited rather than the size of layers.
+//! Chunks are kept in the storage the layer had them in, shared with the
+//! layer until either is drawn on, so compressed chunks stay compressed.
+//! Actions recorded between `History::begin_group` and `History::end_group`,
+//! such as the dabs of a brush stroke, are undone and redone together.
+
+use std::{collections::VecDeque, sync::Arc};
+
+use super::LayerImplementation;
+use crate::{
+    primitives::{position::ChunkPosition, rect::CanvasRect},
+    raster::{chunks::ChunkStorage, CloneStamp, CopyMode, RasterLayer, RasterLayerAction},
+    text::{TextLayer, TextLayerAction},
+    vector::{VectorLayer, VectorLayerAction},
+};
+
+/// The number of steps, each a single action or a group of actions, that can
+/// be undone unless set otherwise.
+const DEFAULT_MAX_DEPTH: usize = 100;
+
+/// An action recorded in a canvas's history.
+#[derive(Debug, Clone)]
+pub enum HistoryAction {
+    Raster(RasterLayerAction),
+    CloneStamp {
+        source_layer_num: usize,
+        clone_stamp: CloneStamp,
+    },
+    CopyRegion {
+        source_layer_num: usize,
+        canvas_rect: CanvasRect,
+        mode: CopyMode,
+    },
+    /// Pasting a raster over a canvas rect.
+    Paste {
+        canvas_rect: CanvasRect,
+        mode: CopyMode,
+    },
+    /// Replacing the transparency of a layer with a mask.
+    ApplyAlpha,
+    /// Removing all of the content of a layer.
+    ClearLayer,
+    /// Making a canvas rect of a raster layer transparent.
+    ClearRect(CanvasRect),
+    Text(TextLayerAction),
+    Vector(VectorLayerAction),
+}
+
+/// The state of the part of a layer changed by an action.
+#[derive(Debug, Clone)]
+pub(super) enum LayerState {
+    /// The chunks of a raster layer changed by an action, with `None` for
+    /// chunks that were unallocated.
+    RasterChunks(Vec<(ChunkPosition, Option<Arc<ChunkStorage>>)>),
+    /// The text objects of a text layer.
+    Text(TextLayer),
+    /// The shapes of a vector layer.
+    Vector(VectorLayer),
+}
+
+impl LayerState {
+    /// The chunks of `raster_layer` that an action within `canvas_rect` can change.
+    pub fn snapshot_chunks(raster_layer: &RasterLayer, canvas_rect: CanvasRect) -> LayerState {
+        LayerState::snapshot_chunk_positions(
+            raster_layer,
+            raster_layer.chunk_positions_in_rect(canvas_rect),
+        )
+    }
+
+    pub fn snapshot_chunk_positions(
+        raster_layer: &RasterLayer,
+        chunk_positions: Vec<ChunkPosition>,
+    ) -> LayerState {
+        LayerState::RasterChunks(
+            chunk_positions
+                .into_iter()
+                .map(|chunk_position| (chunk_position, raster_layer.chunk_storage(chunk_position)))
+                .collect(),
+        )
+    }
+
+    /// Drops the chunks of a raster snapshot that were not changed, since
+    /// snapshots are taken of everything an action could change.
+    fn retain_changed(&mut self, raster_layer: &RasterLayer, changed_canvas_rect: CanvasRect) {
+        if let LayerState::RasterChunks(chunks) = self {
+            chunks.retain(|(chunk_position, _)| {
+                raster_layer
+                    .chunk_canvas_rect(*chunk_position)
+                    .intersects(&changed_canvas_rect)
+            });
+        }
+    }
+
+    /// Whether the state can be swapped with that of `layer`, which it can
+    /// if it is of the kind of the layer and its chunks are of the layer's
+    /// chunk size.
+    fn fits(&self, layer: &LayerImplementation) -> bool {
+        match (self, layer) {
+            (LayerState::RasterChunks(chunks), LayerImplementation::RasterLayer(raster_layer)) => {
+                chunks.iter().all(|(_, storage)| {
+                    storage
+                        .as_ref()
+                        .is_none_or(|storage| storage.fits(raster_layer.chunk_dimensions()))
+                })
+            }
+            (LayerState::Text(_), LayerImplementation::TextLayer(_))
+            | (LayerState::Vector(_), LayerImplementation::VectorLayer(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// Exchanges the stored state with the current state of `layer`. States
+    /// that don't `fit` the layer are left as they are.
+    fn swap_with(&mut self, layer: &mut LayerImplementation) {
+        match (self, layer) {
+            (LayerState::RasterChunks(chunks), LayerImplementation::RasterLayer(raster_layer)) => {
+                for (chunk_position, storage) in chunks.iter_mut() {
+                    *storage = raster_layer.swap_chunk_storage(*chunk_position, storage.take());
+                }
+            }
+            (LayerState::Text(objects), LayerImplementation::TextLayer(text_layer)) => {
+                text_layer.swap_objects(objects);
+            }
+            (LayerState::Vector(shapes), LayerImplementation::VectorLayer(vector_layer)) => {
+                vector_layer.swap_shapes(shapes);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct HistoryEntry {
+    layer_num: usize,
+    action: HistoryAction,
+    /// The state of the layer on the other side of the action from the
+    /// current state.
+    state: LayerState,
+    changed_canvas_rect: CanvasRect,
+}
+
+/// The actions performed on a canvas that can be undone and redone, in steps
+/// of a single action or a group of actions.
+#[derive(Debug, Clone)]
+pub struct History {
+    undo_stack: VecDeque<Vec<HistoryEntry>>,
+    redo_stack: Vec<Vec<HistoryEntry>>,
+    max_depth: usize,
+    /// How many groups have been begun and not yet ended.
+    group_depth: usize,
+    /// Whether the last step of the undo stack belongs to the open group, so
+    /// that actions recorded now are added to it.
+    group_started: bool,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History {
+            undo_stack: VecDeque::new(),
+            redo_stack: Vec::new(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            group_depth: 0,
+            group_started: false,
+        }
+    }
+}
+
+impl History {
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// The action that would be undone next, or the last action of the group
+    /// that would be.
+    pub fn undo_action(&self) -> Option<&HistoryAction> {
+        self.undo_stack
+            .back()
+            .and_then(|step| step.last())
+            .map(|entry| &entry.action)
+    }
+
+    /// The action that would be redone next, or the last action of the group
+    /// that would be.
+    pub fn redo_action(&self) -> Option<&HistoryAction> {
+        self.redo_stack
+            .last()
+            .and_then(|step| step.last())
+            .map(|entry| &entry.action)
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Sets how many steps can be undone, forgetting the oldest steps past the
+    /// new depth.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+        self.truncate();
+    }
+
+    /// Forgets every recorded action.
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.group_started = false;
+    }
+
+    /// Starts grouping the actions recorded until the matching
+    /// `History::end_group` into a single step, so they are undone and redone
+    /// together. Groups can be nested, with inner groups joining the outermost.
+    pub fn begin_group(&mut self) {
+        if self.group_depth == 0 {
+            self.group_started = false;
+        }
+
+        self.group_depth += 1;
+    }
+
+    /// Ends the group begun by the last `History::begin_group`. Ending a group
+    /// that wasn't begun does nothing.
+    pub fn end_group(&mut self) {
+        self.group_depth = self.group_depth.saturating_sub(1);
+
+        if self.group_depth == 0 {
+            self.group_started = false;
+        }
+    }
+
+    /// Whether a group has been begun and not ended.
+    pub fn is_grouping(&self) -> bool {
+        self.group_depth > 0
+    }
+
+    fn truncate(&mut self) {
+        while self.undo_stack.len() > self.max_depth {
+            self.undo_stack.pop_front();
+        }
+    }
+
+    /// Records an action performed on the layer at `layer_num`, given the state
+    /// of the layer from before it. Actions that could be redone are forgotten.
+    pub(super) fn record(
+        &mut self,
+        layer_num: usize,
+        action: HistoryAction,
+        mut state: LayerState,
+        layer: &LayerImplementation,
+        changed_canvas_rect: CanvasRect,
+    ) {
+        if let LayerImplementation::RasterLayer(raster_layer) = layer {
+            state.retain_changed(raster_layer, changed_canvas_rect);
+        }
+
+        let entry = HistoryEntry {
+            layer_num,
+            action,
+            state,
+            changed_canvas_rect,
+        };
+
+        self.redo_stack.clear();
+        match self.undo_stack.back_mut() {
+            Some(step) if self.group_started => step.push(entry),
+            _ => {
+                self.undo_stack.push_back(vec![entry]);
+                self.group_started = self.is_grouping();
+                self.truncate();
+            }
+        }
+    }
+
+    /// Restores the state of `layers` from before the last step, returning
+    /// the canvas rect that has been altered. Returns `None` without changing
+    /// anything if a layer the step was recorded on is missing or has changed
+    /// kind or chunk size, leaving the step to be undone. Undoing ends any
+    /// open group.
+    pub(super) fn undo(&mut self, layers: &mut [LayerImplementation]) -> Option<CanvasRect> {
+        self.group_depth = 0;
+        self.group_started = false;
+
+        let step = self.undo_stack.back_mut()?;
+        if !entries_fit(step, layers) {
+            return None;
+        }
+        let changed_canvas_rect = swap_entries(step.iter_mut().rev(), layers);
+        self.redo_stack.extend(self.undo_stack.pop_back());
+
+        changed_canvas_rect
+    }
+
+    /// Performs the last undone step again, returning the canvas rect that
+    /// has been altered. Like `History::undo`, nothing is changed if the step
+    /// no longer fits the layers. Redoing ends any open group.
+    pub(super) fn redo(&mut self, layers: &mut [LayerImplementation]) -> Option<CanvasRect> {
+        self.group_depth = 0;
+        self.group_started = false;
+
+        let step = self.redo_stack.last_mut()?;
+        if !entries_fit(step, layers) {
+            return None;
+        }
+        let changed_canvas_rect = swap_entries(step.iter_mut(), layers);
+        self.undo_stack.extend(self.redo_stack.pop());
+
+        changed_canvas_rect
+    }
+}
+
+/// Whether every entry of a step can be swapped with the layer it was
+/// recorded on, so that a step is swapped whole or not at all.
+fn entries_fit(entries: &[HistoryEntry], layers: &[LayerImplementation]) -> bool {
+    entries.iter().all(|entry| {
+        layers
+            .get(entry.layer_num)
+            .is_some_and(|layer| entry.state.fits(layer))
+    })
+}
+
+/// Swaps the entries of a step in order, returning the canvas rect spanning
+/// everything they altered.
+fn swap_entries<'a>(
+    entries: impl Iterator<Item = &'a mut HistoryEntry>,
+    layers: &mut [LayerImplementation],
+) -> Option<CanvasRect> {
+    entries
+        .filter_map(|entry| {
+            entry.state.swap_with(layers.get_mut(entry.layer_num)?);
+
+            Some(entry.changed_canvas_rect)
+        })
+        .reduce(|canvas_rect, entry_canvas_rect| canvas_rect.spanning_rect(&entry_canvas_rect))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        primitives::dimensions::Dimensions,
+        raster::{pixels::colors, Pixel},
+        text::TextLayer,
+    };
+
+    #[test]
+    fn steps_that_no_longer_fit_the_layers_are_kept_unchanged() {
+        let canvas_rect = CanvasRect::at_origin(Dimensions {
+            width: 4,
+            height: 4,
+        });
+        let action = RasterLayerAction::fill_rect(canvas_rect, colors::red());
+        let mut layers: Vec<LayerImplementation> =
+            vec![RasterLayer::new(4).into(), RasterLayer::new(4).into()];
+
+        let mut history = History::default();
+        history.begin_group();
+        for (layer_num, layer) in layers.iter_mut().enumerate() {
+            let LayerImplementation::RasterLayer(raster_layer) = layer else {
+                unreachable!("the layers are raster layers");
+            };
+            let state = LayerState::snapshot_chunks(raster_layer, canvas_rect);
+            raster_layer.perform_action(action.clone());
+            history.record(
+                layer_num,
+                HistoryAction::Raster(action.clone()),
+                state,
+                layer,
+                canvas_rect,
+            );
+        }
+        history.end_group();
+
+        let red_pixel = |layers: &[LayerImplementation]| -> Option<Pixel> {
+            let LayerImplementation::RasterLayer(raster_layer) = &layers[0] else {
+                return None;
+            };
+
+            raster_layer.chunk((0, 0).into())?.pixels().first().copied()
+        };
+
+        let mut changed_layers = layers.clone();
+        changed_layers[1] = TextLayer::new().into();
+        assert_eq!(history.undo(&mut changed_layers), None);
+        assert_eq!(red_pixel(&changed_layers), Some(colors::red()));
+        assert!(history.can_undo());
+        assert_eq!(history.undo(&mut layers[..1]), None);
+
+        assert_eq!(history.undo(&mut layers), Some(canvas_rect));
+        assert_eq!(red_pixel(&layers), None);
+        assert!(history.can_redo());
+    }
+}
@@ -0,0 +1,465 @@
+//! Saving documents to a directory so that a save interrupted at any point,
+//! such as by power loss or the process being killed, leaves the previous
+//! save intact.
+//!
+//! The chunks of raster layers are written to files named by the hash of
+//! their content, so chunks that haven't changed since the last save are not
+//! written again. Once every chunk of a save is on disk, a manifest listing
+//! the layers and chunks of the document is written to a temporary file and
+//! renamed into place, which commits the save. Manifests are checksummed and
+//! numbered, and loading falls back to the newest manifest whose chunks are
+//! all intact.
+//!
+//! Like incremental saves, only the pixels of raster layers are saved. Other
+//! layers are recorded so the layers of a document keep their order.
+
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+use crate::{
+    encoding::{
+        fnv_hash, read_chunk_position, read_document_dimensions, read_header, read_layers,
+        read_u32, read_u64, write_chunk_position, write_document_dimensions, write_header,
+        write_layers, write_u32, write_u64, DecodeError,
+    },
+    primitives::{dimensions::Dimensions, position::ChunkPosition},
+    raster::{
+        chunks::{BoxRasterChunk, PngError},
+        RasterLayer,
+    },
+};
+
+use super::{Canvas, LayerImplementation};
+
+const MAGIC: &[u8; 4] = b"MBJM";
+const VERSION: u8 = 1;
+
+const CHUNK_DIRECTORY: &str = "chunks";
+const MANIFEST_PREFIX: &str = "manifest-";
+const TEMPORARY_SUFFIX: &str = ".tmp";
+
+/// How many of the newest manifests are kept, so there is a save to fall
+/// back to if the newest one is damaged.
+pub const RETAINED_MANIFESTS: usize = 2;
+
+/// An error from writing or loading a journaled save.
+#[derive(Error, Debug)]
+pub enum JournalError {
+    #[error("failed to read or write the journal: {0}")]
+    Io(#[from] io::Error),
+    #[error("failed to encode a chunk: {0}")]
+    Png(#[from] PngError),
+    #[error("the journal has no intact save")]
+    NoValidSave,
+    #[error("layer {0} is missing and its content isn't part of the save")]
+    MissingLayer(usize),
+}
+
+fn checksum(bytes: &[u8]) -> u64 {
+    fnv_hash(bytes.iter().map(|byte| *byte as u64))
+}
+
+/// Writes a file under a temporary name, flushes it to disk and renames it
+/// into place, so the file is either absent or complete.
+fn write_atomically(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut temporary_path = path.as_os_str().to_owned();
+    temporary_path.push(TEMPORARY_SUFFIX);
+
+    let mut file = File::create(&temporary_path)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&temporary_path, path)
+}
+
+/// Flushes the entries of a directory to disk, so that files renamed into it
+/// survive power loss. Directories can only be opened as files on Unix.
+fn sync_directory(path: &Path) -> io::Result<()> {
+    if cfg!(unix) {
+        File::open(path)?.sync_all()?;
+    }
+
+    Ok(())
+}
+
+/// The layers and chunks of a save.
+struct Manifest {
+    document_dimensions: Option<Dimensions>,
+    /// The chunk size of each raster layer, `None` for layers that aren't saved.
+    layers: Vec<Option<usize>>,
+    /// The layer, position and content hash of each chunk.
+    chunks: Vec<(usize, ChunkPosition, u64)>,
+}
+
+impl Manifest {
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write(&mut bytes)
+            .expect("writing to a Vec should not fail");
+
+        let checksum = checksum(&bytes);
+        bytes.extend(checksum.to_le_bytes());
+
+        bytes
+    }
+
+    fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        write_header(writer, MAGIC, VERSION)?;
+        write_document_dimensions(writer, self.document_dimensions)?;
+        write_layers(writer, &self.layers)?;
+
+        write_u32(writer, self.chunks.len() as u32)?;
+        for (layer_num, chunk_position, content_hash) in &self.chunks {
+            write_u32(writer, *layer_num as u32)?;
+            write_chunk_position(writer, *chunk_position)?;
+            write_u64(writer, *content_hash)?;
+        }
+
+        Ok(())
+    }
+
+    /// Decodes a manifest, returning `None` if it is damaged or incomplete.
+    fn decode(bytes: &[u8]) -> Option<Manifest> {
+        let (mut contents, checksum_bytes) = bytes.split_at(bytes.len().checked_sub(8)?);
+        if checksum(contents).to_le_bytes() != checksum_bytes {
+            return None;
+        }
+
+        Manifest::read(&mut contents).ok()
+    }
+
+    fn read<R: Read>(reader: &mut R) -> Result<Manifest, DecodeError> {
+        read_header(reader, MAGIC, VERSION)?;
+        let document_dimensions = read_document_dimensions(reader)?;
+        let layers = read_layers(reader)?;
+
+        let chunk_count = read_u32(reader)?;
+        let mut chunks = Vec::new();
+        for _ in 0..chunk_count {
+            let layer_num = read_u32(reader)? as usize;
+            let chunk_position = read_chunk_position(reader)?;
+            let content_hash = read_u64(reader)?;
+
+            chunks.push((layer_num, chunk_position, content_hash));
+        }
+
+        Ok(Manifest {
+            document_dimensions,
+            layers,
+            chunks,
+        })
+    }
+}
+
+/// A directory that documents are saved to with `DocumentJournal::save` and
+/// recovered from with `DocumentJournal::load`.
+#[derive(Debug, Clone)]
+pub struct DocumentJournal {
+    directory: PathBuf,
+}
+
+impl DocumentJournal {
+    /// A journal in `directory`, which is created on the first save.
+    pub fn new(directory: impl Into<PathBuf>) -> DocumentJournal {
+        DocumentJournal {
+            directory: directory.into(),
+        }
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    fn chunk_path(&self, content_hash: u64) -> PathBuf {
+        self.directory
+            .join(CHUNK_DIRECTORY)
+            .join(format!("{content_hash:016x}.png"))
+    }
+
+    fn manifest_path(&self, generation: u64) -> PathBuf {
+        self.directory
+            .join(format!("{MANIFEST_PREFIX}{generation:016x}"))
+    }
+
+    /// The generations of the committed manifests, newest first.
+    fn generations(&self) -> io::Result<Vec<u64>> {
+        let mut generations = vec![];
+
+        for entry in fs::read_dir(&self.directory)? {
+            let file_name = entry?.file_name();
+            let generation = file_name
+                .to_str()
+                .and_then(|file_name| file_name.strip_prefix(MANIFEST_PREFIX))
+                .and_then(|generation| u64::from_str_radix(generation, 16).ok());
+
+            generations.extend(generation);
+        }
+        generations.sort_unstable_by(|a, b| b.cmp(a));
+
+        Ok(generations)
+    }
+
+    /// Saves the document, returning the generation of the save. The save is
+    /// committed once this returns, and a save interrupted before then leaves
+    /// earlier saves loadable.
+    pub fn save(&self, canvas: &Canvas) -> Result<u64, JournalError> {
+        let chunk_directory = self.directory.join(CHUNK_DIRECTORY);
+        fs::create_dir_all(&chunk_directory)?;
+
+        let mut manifest = Manifest {
+            document_dimensions: canvas.document_dimensions,
+            layers: vec![],
+            chunks: vec![],
+        };
+        for (layer_num, layer) in canvas.layers.iter().enumerate() {
+            let raster_layer = match layer {
+                LayerImplementation::RasterLayer(raster_layer) => raster_layer,
+                _ => {
+                    manifest.layers.push(None);
+                    continue;
+                }
+            };
+            manifest.layers.push(Some(raster_layer.chunk_size()));
+
+            for chunk_position in raster_layer.allocated_chunk_positions() {
+                let chunk = match raster_layer.chunk(chunk_position) {
+                    Some(chunk) => chunk,
+                    None => continue,
+                };
+                let content_hash = chunk.content_hash();
+
+                // Chunk files are named by their content, so an existing file
+                // already holds this chunk
+                let chunk_path = self.chunk_path(content_hash);
+                if !chunk_path.exists() {
+                    write_atomically(&chunk_path, &chunk.encode_png()?)?;
+                }
+                manifest
+                    .chunks
+                    .push((layer_num, chunk_position, content_hash));
+            }
+        }
+        sync_directory(&chunk_directory)?;
+
+        let generation = match self.generations()?.first() {
+            Some(generation) => generation + 1,
+            None => 0,
+        };
+        write_atomically(&self.manifest_path(generation), &manifest.encode())?;
+        sync_directory(&self.directory)?;
+
+        self.prune();
+
+        Ok(generation)
+    }
+
+    /// Removes the manifests older than the `RETAINED_MANIFESTS` newest, the
+    /// chunks only they use and files left behind by interrupted saves. The
+    /// save has already been committed, so files that can't be removed are
+    /// left for the next save to remove.
+    fn prune(&self) {
+        let generations = match self.generations() {
+            Ok(generations) => generations,
+            Err(_) => return,
+        };
+        let (retained, removed) = generations.split_at(generations.len().min(RETAINED_MANIFESTS));
+
+        for generation in removed {
+            let _ = fs::remove_file(self.manifest_path(*generation));
+        }
+
+        let mut used_chunks = HashSet::new();
+        for generation in retained {
+            match fs::read(self.manifest_path(*generation))
+                .ok()
+                .and_then(|bytes| Manifest::decode(&bytes))
+            {
+                Some(manifest) => used_chunks.extend(
+                    manifest
+                        .chunks
+                        .iter()
+                        .map(|(_, _, content_hash)| self.chunk_path(*content_hash)),
+                ),
+                // The chunks of a manifest that can't be read can't be told
+                // apart from unused ones
+                None => return,
+            }
+        }
+
+        for directory in [self.directory.clone(), self.directory.join(CHUNK_DIRECTORY)] {
+            let entries = match fs::read_dir(&directory) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+
+            for path in entries.filter_map(|entry| Some(entry.ok()?.path())) {
+                let is_temporary = path
+                    .to_str()
+                    .is_some_and(|path| path.ends_with(TEMPORARY_SUFFIX));
+                let is_unused_chunk = directory != self.directory && !used_chunks.contains(&path);
+
+                if is_temporary || is_unused_chunk {
+                    let _ = fs::remove_file(path);
+                }
+            }
+        }
+    }
+
+    /// Reads the layers of a manifest, returning `None` if any of its chunks
+    /// are missing or damaged.
+    fn read_layers(&self, manifest: &Manifest) -> Option<Vec<Option<RasterLayer>>> {
+        // Chunk sizes were checked when the manifest was read
+        let mut layers: Vec<Option<RasterLayer>> = manifest
+            .layers
+            .iter()
+            .map(|chunk_size| chunk_size.map(RasterLayer::new))
+            .collect();
+
+        for (layer_num, chunk_position, content_hash) in &manifest.chunks {
+            let bytes = fs::read(self.chunk_path(*content_hash)).ok()?;
+            let chunk = BoxRasterChunk::decode_png(&bytes).ok()?;
+            if chunk.content_hash() != *content_hash {
+                return None;
+            }
+
+            layers
+                .get_mut(*layer_num)?
+                .as_mut()?
+                .replace_chunk(*chunk_position, Some(chunk))?;
+        }
+
+        Some(layers)
+    }
+
+    /// Replaces the document of `canvas` with the newest intact save, returning
+    /// its generation. Layers that aren't saved are kept from the canvas. Like
+    /// `Canvas::restore`, the history is cleared.
+    pub fn load(&self, canvas: &mut Canvas) -> Result<u64, JournalError> {
+        for generation in self.generations()? {
+            let manifest = match fs::read(self.manifest_path(generation))
+                .ok()
+                .and_then(|bytes| Manifest::decode(&bytes))
+            {
+                Some(manifest) => manifest,
+                None => continue,
+            };
+            let layers = match self.read_layers(&manifest) {
+                Some(layers) => layers,
+                None => continue,
+            };
+
+            if let Some(layer_num) = (0..layers.len())
+                .find(|layer_num| layers[*layer_num].is_none() && *layer_num >= canvas.layers.len())
+            {
+                return Err(JournalError::MissingLayer(layer_num));
+            }
+
+            let mut unsaved_layers = std::mem::take(&mut canvas.layers).into_iter();
+            canvas.layers = layers
+                .into_iter()
+                .filter_map(|raster_layer| {
+                    let unsaved_layer = unsaved_layers.next();
+
+                    match raster_layer {
+                        Some(raster_layer) => Some(raster_layer.into()),
+                        None => unsaved_layer,
+                    }
+                })
+                .collect();
+            canvas.document_dimensions = manifest.document_dimensions;
+
+            canvas.history.clear();
+            canvas.invalidate_caches();
+
+            return Ok(generation);
+        }
+
+        Err(JournalError::NoValidSave)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assert_raster_eq,
+        primitives::rect::CanvasRect,
+        raster::{pixels::colors, RasterLayerAction},
+        vector::VectorLayer,
+    };
+
+    fn file_count(path: &Path) -> usize {
+        fs::read_dir(path).unwrap().count()
+    }
+
+    #[test]
+    fn interrupted_saves_fall_back_to_the_last_intact_save() {
+        let directory = std::env::temp_dir().join(format!("mboard-journal-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let journal = DocumentJournal::new(&directory);
+
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(4).into());
+        canvas.add_layer(VectorLayer::new().into());
+        let document_rect = CanvasRect::at_origin(Dimensions {
+            width: 8,
+            height: 4,
+        });
+        canvas.perform_raster_action(
+            0,
+            RasterLayerAction::fill_rect(document_rect, colors::red()),
+        );
+        assert_eq!(journal.save(&canvas).unwrap(), 0);
+        let first_raster = canvas.rasterize_canvas_rect(document_rect);
+
+        // Both chunks are red, so they share a file
+        assert_eq!(file_count(&directory.join(CHUNK_DIRECTORY)), 1);
+
+        canvas.perform_raster_action(
+            0,
+            RasterLayerAction::draw_line((0, 0).into(), (3, 3).into(), 0, colors::blue()),
+        );
+        assert_eq!(journal.save(&canvas).unwrap(), 1);
+        let second_raster = canvas.rasterize_canvas_rect(document_rect);
+        assert_eq!(file_count(&directory.join(CHUNK_DIRECTORY)), 2);
+
+        let mut restored = Canvas::default();
+        restored.add_layer(VectorLayer::new().into());
+        restored.add_layer(VectorLayer::new().into());
+        assert_eq!(journal.load(&mut restored).unwrap(), 1);
+        let restored_raster = restored.rasterize_canvas_rect(document_rect);
+        assert_raster_eq!(restored_raster, second_raster);
+
+        // A save killed after its chunk was written and while its manifest
+        // was written, and a chunk damaged after being committed
+        fs::write(journal.manifest_path(2), b"MBJM").unwrap();
+        let line_chunk = restored
+            .raster_layer(0)
+            .unwrap()
+            .chunk((0, 0).into())
+            .unwrap();
+        fs::write(journal.chunk_path(line_chunk.content_hash()), b"").unwrap();
+        // A manifest from a version with larger chunks than can be loaded
+        let huge_chunks = Manifest {
+            document_dimensions: None,
+            layers: vec![Some(1 << 20)],
+            chunks: vec![],
+        };
+        fs::write(journal.manifest_path(3), huge_chunks.encode()).unwrap();
+
+        assert_eq!(journal.load(&mut restored).unwrap(), 0);
+        let restored_raster = restored.rasterize_canvas_rect(document_rect);
+        assert_raster_eq!(restored_raster, first_raster);
+
+        fs::remove_dir_all(&directory).unwrap();
+        assert!(matches!(
+            journal.load(&mut restored),
+            Err(JournalError::Io(_))
+        ));
+    }
+}
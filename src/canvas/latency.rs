@@ -0,0 +1,250 @@
+//! How long raster actions and cache rerenders take, so that embedders can
+//! notice when settings such as chunk or brush sizes cause frames to be
+//! dropped and adapt them.
+//!
+//! Durations are measured with `std::time::Instant`, which isn't available
+//! on the web. There, `performance.now()` is used with the `wasm` feature,
+//! and nothing is measured without it.
+
+use std::{collections::VecDeque, time::Duration};
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+use wasm_bindgen::prelude::*;
+
+use super::Canvas;
+
+/// How many of the most recent durations of each kind of work are kept.
+pub const LATENCY_WINDOW: usize = 256;
+
+/// The time a frame at 60 frames per second has for all of its work.
+pub const FRAME_BUDGET: Duration = Duration::from_millis(16);
+
+/// The upper bounds of the buckets of latency histograms, in milliseconds.
+/// Durations past the last bound are counted in a final bucket.
+const BUCKET_BOUNDS_MS: [u64; 7] = [1, 2, 4, 8, 16, 33, 66];
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+#[wasm_bindgen]
+extern "C" {
+    /// The milliseconds since the page or worker started, which unlike
+    /// `Date.now()` has sub-millisecond precision and never goes backwards.
+    #[wasm_bindgen(js_namespace = performance, js_name = now)]
+    fn performance_now() -> f64;
+}
+
+/// A point in time to measure a duration from.
+#[derive(Debug, Copy, Clone)]
+pub(super) struct Stopwatch {
+    #[cfg(not(target_arch = "wasm32"))]
+    started: std::time::Instant,
+    #[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+    started_ms: f64,
+}
+
+impl Stopwatch {
+    pub(super) fn start() -> Stopwatch {
+        Stopwatch {
+            #[cfg(not(target_arch = "wasm32"))]
+            started: std::time::Instant::now(),
+            #[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+            started_ms: performance_now(),
+        }
+    }
+
+    /// The time since the stopwatch was started, or `None` if there is no
+    /// clock to measure it with.
+    fn elapsed(&self) -> Option<Duration> {
+        #[cfg(not(target_arch = "wasm32"))]
+        return Some(self.started.elapsed());
+
+        #[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+        return Some(Duration::from_secs_f64(
+            (performance_now() - self.started_ms).max(0.0) / 1000.0,
+        ));
+
+        #[cfg(all(target_arch = "wasm32", not(feature = "wasm")))]
+        return None;
+    }
+}
+
+/// The kinds of work whose durations are tracked.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(super) enum LatencyKind {
+    RasterAction,
+    CacheRerender,
+}
+
+/// The most recent durations of each kind of work.
+#[derive(Debug, Clone, Default)]
+pub(super) struct LatencyTracker {
+    raster_actions: VecDeque<Duration>,
+    cache_rerenders: VecDeque<Duration>,
+}
+
+impl LatencyTracker {
+    /// Records the time since `stopwatch` was started, forgetting the oldest
+    /// duration of its kind past `LATENCY_WINDOW`.
+    pub(super) fn record(&mut self, kind: LatencyKind, stopwatch: Stopwatch) {
+        let durations = match kind {
+            LatencyKind::RasterAction => &mut self.raster_actions,
+            LatencyKind::CacheRerender => &mut self.cache_rerenders,
+        };
+
+        if let Some(duration) = stopwatch.elapsed() {
+            if durations.len() == LATENCY_WINDOW {
+                durations.pop_front();
+            }
+            durations.push_back(duration);
+        }
+    }
+}
+
+/// Statistics of the most recent durations of a kind of work.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LatencySummary {
+    pub samples: usize,
+    pub mean: Duration,
+    pub median: Duration,
+    pub p95: Duration,
+    pub max: Duration,
+    /// The number of durations longer than `FRAME_BUDGET`.
+    pub over_budget: usize,
+    /// The number of durations up to each upper bound, exclusive of the
+    /// bounds before it. The last bucket has no upper bound.
+    pub histogram: Vec<(Option<Duration>, usize)>,
+}
+
+impl LatencySummary {
+    fn of(durations: &VecDeque<Duration>) -> LatencySummary {
+        let mut histogram: Vec<(Option<Duration>, usize)> = BUCKET_BOUNDS_MS
+            .iter()
+            .map(|bound_ms| Some(Duration::from_millis(*bound_ms)))
+            .chain([None])
+            .map(|bound| (bound, 0))
+            .collect();
+
+        if durations.is_empty() {
+            return LatencySummary {
+                histogram,
+                ..Default::default()
+            };
+        }
+
+        let mut sorted: Vec<Duration> = durations.iter().copied().collect();
+        sorted.sort_unstable();
+        let percentile = |percent: usize| sorted[(sorted.len() - 1) * percent / 100];
+
+        for duration in &sorted {
+            let bucket = histogram
+                .iter_mut()
+                .find(|(bound, _)| bound.is_none_or(|bound| *duration <= bound));
+
+            if let Some((_, count)) = bucket {
+                *count += 1;
+            }
+        }
+
+        LatencySummary {
+            samples: sorted.len(),
+            mean: sorted.iter().sum::<Duration>() / sorted.len() as u32,
+            median: percentile(50),
+            p95: percentile(95),
+            max: sorted[sorted.len() - 1],
+            over_budget: sorted
+                .iter()
+                .filter(|duration| **duration > FRAME_BUDGET)
+                .count(),
+            histogram,
+        }
+    }
+}
+
+/// How long recent work on a canvas has taken, from `Canvas::latency_report`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LatencyReport {
+    /// Raster actions that changed the canvas, including rerendering the
+    /// caches for them.
+    pub raster_actions: LatencySummary,
+    /// Rerendering the cached rasters of canvas rects and views after
+    /// changes, and prerendering views that aren't cached yet.
+    pub cache_rerenders: LatencySummary,
+}
+
+impl Canvas {
+    /// How long the last `LATENCY_WINDOW` raster actions and cache rerenders
+    /// took.
+    pub fn latency_report(&self) -> LatencyReport {
+        LatencyReport {
+            raster_actions: LatencySummary::of(&self.latency.raster_actions),
+            cache_rerenders: LatencySummary::of(&self.latency.cache_rerenders),
+        }
+    }
+
+    /// Forgets the recorded durations, such as after changing settings to
+    /// measure their effect.
+    pub fn clear_latency(&mut self) {
+        self.latency = LatencyTracker::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        canvas::CanvasView,
+        primitives::{dimensions::Dimensions, rect::CanvasRect},
+        raster::{pixels::colors, RasterLayer, RasterLayerAction},
+    };
+
+    #[test]
+    fn latencies_are_summarized_over_a_rolling_window() {
+        let durations: VecDeque<Duration> = [3, 1, 20, 5, 41]
+            .into_iter()
+            .map(Duration::from_millis)
+            .collect();
+        let summary = LatencySummary::of(&durations);
+
+        assert_eq!(summary.samples, 5);
+        assert_eq!(summary.mean, Duration::from_millis(14));
+        assert_eq!(summary.median, Duration::from_millis(5));
+        assert_eq!(summary.max, Duration::from_millis(41));
+        assert_eq!(summary.over_budget, 2);
+        let counts: Vec<usize> = summary.histogram.iter().map(|(_, count)| *count).collect();
+        assert_eq!(counts, [1, 0, 1, 1, 0, 1, 1, 0]);
+
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(8).into());
+        let action = RasterLayerAction::fill_rect(
+            CanvasRect::at_origin(Dimensions {
+                width: 4,
+                height: 4,
+            }),
+            colors::red(),
+        );
+        for _ in 0..LATENCY_WINDOW + 1 {
+            canvas.perform_raster_action(0, action.clone());
+        }
+        canvas.perform_raster_action(1, action.clone());
+
+        let report = canvas.latency_report();
+        assert_eq!(report.raster_actions.samples, LATENCY_WINDOW);
+        assert_eq!(report.cache_rerenders.samples, LATENCY_WINDOW);
+        assert!(report.raster_actions.max >= report.raster_actions.median);
+
+        canvas.clear_latency();
+        assert_eq!(canvas.latency_report().raster_actions.samples, 0);
+
+        let view = CanvasView::new(16, 16);
+        canvas.render(&view);
+        assert_eq!(canvas.latency_report().cache_rerenders.samples, 1);
+        canvas.render(&view);
+        assert_eq!(
+            canvas.latency_report().cache_rerenders.samples,
+            1,
+            "views served from the cache aren't rerenders"
+        );
+        canvas.perform_raster_action(0, action);
+        canvas.render(&view);
+        assert_eq!(canvas.latency_report().cache_rerenders.samples, 3);
+    }
+}
@@ -1,30 +1,126 @@
 use crate::{
+    error::MboardError,
     primitives::{
         dimensions::{Dimensions, Scale},
         position::{CanvasPosition, PixelPosition, UncheckedIntoPosition},
         rect::{CanvasRect, ViewRect},
+        transform::Affine,
     },
     raster::{
-        chunks::{nn_map::NearestNeighbourMap, raster_chunk::BumpRasterChunk, BoxRasterChunk},
-        pixels::colors,
-        RasterLayer, RasterLayerAction,
+        chunks::{
+            nn_map::{NearestNeighbourMap, NearestNeighbourMapCache},
+            raster_chunk::{BumpRasterChunk, RasterChunk},
+            BoxRasterChunk, PixelFormat, ScalingFilter,
+        },
+        BlendIf, BlendMode, CloneStamp, CopyMode, Glow, MaskLayer, Pixel, RasterLayer,
+        RasterLayerAction, RasterizeError, Selection, Spray, Subsource,
     },
+    text::{TextLayer, TextLayerAction},
+    vector::{ShapeId, VectorLayer, VectorLayerAction},
 };
 use bumpalo::Bump;
 use enum_dispatch::enum_dispatch;
+use std::{cmp::Ordering, ops::DerefMut};
 
+mod autosave;
+mod background;
+mod builder;
 mod cache;
-pub use cache::ShapeCache;
+mod crop;
+mod focus;
+mod guides;
+mod history;
+mod journal;
+mod latency;
+mod observer;
+mod pick;
+mod reader;
+#[cfg(feature = "cache-debug")]
+mod recorder;
+mod rng;
+mod rotation;
+mod scheduler;
+#[cfg(feature = "serde")]
+mod serialization;
+mod snapshot;
+mod sync;
+mod validate;
+#[cfg(feature = "threads")]
+mod warmer;
+mod workspace;
+pub use autosave::{IncrementalSaveError, SaveToken};
+pub use background::CanvasBackground;
+pub use builder::{CanvasBuilder, DocumentPreset, LayerKind};
+pub use cache::{CacheConfig, ShapeCache, DEFAULT_MAX_PRERENDER_AREA};
+pub use guides::{Guide, GuideSnap, Guides};
+pub use history::{History, HistoryAction};
+pub use journal::{DocumentJournal, JournalError, RETAINED_MANIFESTS};
+pub use latency::{LatencyReport, LatencySummary, FRAME_BUDGET, LATENCY_WINDOW};
+pub use observer::RegionObserverId;
+pub use reader::CanvasReader;
+#[cfg(feature = "cache-debug")]
+pub use recorder::{CacheEvent, CacheEventRecord, CacheKind, DEFAULT_CACHE_EVENT_CAPACITY};
+pub use rng::CanvasRng;
+pub use rotation::ViewRotation;
+pub use scheduler::{FrameScheduler, FrameWork};
+pub use snapshot::CanvasSnapshot;
+pub use sync::ChunkPatch;
+pub use validate::{ActionEffect, ActionError};
+#[cfg(feature = "threads")]
+pub use warmer::{CacheWarmer, WarmedTile};
+pub use workspace::{DocumentId, Workspace};
+
+use self::{
+    autosave::ChangeLog,
+    cache::{view_scaling_filter, CanvasRectRasterCache, CanvasViewRasterCache},
+    history::LayerState,
+    latency::{LatencyKind, LatencyTracker, Stopwatch},
+    observer::RegionObservers,
+    snapshot::SnapshotChunks,
+};
 
-use self::cache::{CanvasRectRasterCache, CanvasViewRasterCache};
+/// The least and greatest coordinates of the corners of a rect after a
+/// transform.
+fn transformed_bounds(
+    transform: &Affine,
+    top_left: (f32, f32),
+    dimensions: Dimensions,
+) -> ((f32, f32), (f32, f32)) {
+    let (left, top) = top_left;
+    let (right, bottom) = (
+        left + dimensions.width as f32,
+        top + dimensions.height as f32,
+    );
+
+    [(left, top), (right, top), (left, bottom), (right, bottom)]
+        .into_iter()
+        .map(|corner| transform.apply_snapped(corner.into()))
+        .fold(
+            (
+                (f32::INFINITY, f32::INFINITY),
+                (f32::NEG_INFINITY, f32::NEG_INFINITY),
+            ),
+            |(min, max), corner| {
+                (
+                    (min.0.min(corner.0), min.1.min(corner.1)),
+                    (max.0.max(corner.0), max.1.max(corner.1)),
+                )
+            },
+        )
+}
 
 /// A view positioned relative to a set of layers.
 /// The view has a scale and a width and height, the width and height are in pixel units.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CanvasView {
     pub top_left: CanvasPosition,
     pub view_dimensions: Dimensions,
     pub canvas_dimensions: Dimensions,
+    /// How far the canvas is turned about the middle of the view. The top left
+    /// and canvas dimensions describe the view before it is turned, so turning
+    /// a view keeps its middle and scale.
+    pub rotation: ViewRotation,
 }
 
 impl CanvasView {
@@ -35,9 +131,15 @@ impl CanvasView {
             top_left: (0, 0).into(),
             view_dimensions: Dimensions { width, height },
             canvas_dimensions: Dimensions { width, height },
+            rotation: ViewRotation::NONE,
         }
     }
 
+    /// Turn the canvas of a view further clockwise about the middle of the view.
+    pub fn rotate(&mut self, rotation: ViewRotation) {
+        self.rotation = self.rotation.then(rotation);
+    }
+
     /// Translate a view by an offset.
     pub fn translate(&mut self, d: CanvasPosition) {
         self.top_left = self.top_left.translate(d);
@@ -52,30 +154,26 @@ impl CanvasView {
     }
 
     /// Scale the canvas source of the view while preserving the middle of the view.
-    /// Negative or factors that scale the view too small are ignored.
+    /// Negative factors or factors that scale the view too small or too large are ignored.
     pub fn pin_scale_canvas(&mut self, factor: Scale) {
-        let new_dimensions = self.canvas_dimensions.scale(factor);
-
-        if new_dimensions.width < 1 || new_dimensions.height < 1 {
-            return;
+        if let Ok(new_dimensions) = self.canvas_dimensions.try_scale(factor) {
+            self.pin_resize_canvas(new_dimensions);
         }
-
-        self.pin_resize_canvas(new_dimensions);
     }
 
     /// Scale the canvas source and view dimensions of the view while preserving
-    /// the middle of the view. Negatives or factors that scale the view too small are ignored.
+    /// the middle of the view. Negative factors or factors that scale the view too small
+    /// or too large are ignored.
     pub fn pin_scale(&mut self, factor: Scale) {
-        let new_canvas_dimensions = self.canvas_dimensions.scale(factor);
-        let new_view_dimensions = self.view_dimensions.scale(factor);
-
-        if new_canvas_dimensions.width < 1
-            || new_canvas_dimensions.height < 1
-            || new_view_dimensions.width < 1
-            || new_view_dimensions.height < 1
-        {
-            return;
-        }
+        let (new_canvas_dimensions, new_view_dimensions) = match (
+            self.canvas_dimensions.try_scale(factor),
+            self.view_dimensions.try_scale(factor),
+        ) {
+            (Ok(new_canvas_dimensions), Ok(new_view_dimensions)) => {
+                (new_canvas_dimensions, new_view_dimensions)
+            }
+            _ => return,
+        };
 
         let difference = self.canvas_dimensions.difference(new_canvas_dimensions);
 
@@ -84,8 +182,54 @@ impl CanvasView {
         self.view_dimensions = new_view_dimensions;
     }
 
+    /// The transform from points in view space to points in canvas space,
+    /// including the rotation of the view.
+    pub fn view_to_canvas_transform(&self) -> Affine {
+        let (view_width, view_height) = (
+            self.view_dimensions.width as f32,
+            self.view_dimensions.height as f32,
+        );
+        let (canvas_width, canvas_height) = (
+            self.canvas_dimensions.width as f32,
+            self.canvas_dimensions.height as f32,
+        );
+
+        Affine::translate(-view_width / 2.0, -view_height / 2.0)
+            .then(&self.rotation.reversed().transform())
+            .then(&Affine::scale(
+                canvas_width / view_width,
+                canvas_height / view_height,
+            ))
+            .then(&Affine::translate(
+                self.top_left.0 as f32 + canvas_width / 2.0,
+                self.top_left.1 as f32 + canvas_height / 2.0,
+            ))
+    }
+
+    /// The transform from points in canvas space to points in view space, or
+    /// `None` if the view has no area.
+    pub fn canvas_to_view_transform(&self) -> Option<Affine> {
+        self.view_to_canvas_transform().inverse()
+    }
+
+    /// The bounds in view space of the corners of a canvas rect.
+    fn rotated_view_bounds(&self, r: &CanvasRect) -> Option<((f32, f32), (f32, f32))> {
+        Some(transformed_bounds(
+            &self.canvas_to_view_transform()?,
+            (r.top_left.0 as f32, r.top_left.1 as f32),
+            r.dimensions,
+        ))
+    }
+
     /// Transforms a point from view space to canvas space.
     pub fn transform_view_to_canvas(&self, p: PixelPosition) -> CanvasPosition {
+        if !self.rotation.is_none() {
+            return self
+                .view_to_canvas_transform()
+                .apply((p.0 as f32 + 0.5, p.1 as f32 + 0.5).into())
+                .containing_pixel();
+        }
+
         let scaled_point = self
             .canvas_dimensions
             .transform_point(p, self.view_dimensions);
@@ -96,6 +240,18 @@ impl CanvasView {
     /// Attempt to transform a position in canvas space to a position
     /// in view space. Canvas positions not in view will map to `None`;
     pub fn transform_canvas_to_view(&self, p: CanvasPosition) -> Option<PixelPosition> {
+        if !self.rotation.is_none() {
+            let view_point = self
+                .canvas_to_view_transform()?
+                .apply_snapped(p.into())
+                .round();
+
+            let in_view = (0..=self.view_dimensions.width as i32).contains(&view_point.0)
+                && (0..=self.view_dimensions.height as i32).contains(&view_point.1);
+
+            return in_view.then(|| view_point.unchecked_into_position());
+        }
+
         let translated_point = p.translate((-self.top_left.0, -self.top_left.1).into());
 
         let point_past_top_left = translated_point.0 < 0 || translated_point.1 < 0;
@@ -113,7 +269,18 @@ impl CanvasView {
 
     /// Attempt to transform a rect in canvas space to a rect
     /// in view space. Canvas rects not fully in view will map to `None`;
+    /// rects turned by the rotation of the view map to the view rect around them.
     pub fn transform_canvas_rect_to_view(&self, r: &CanvasRect) -> Option<ViewRect> {
+        if !self.rotation.is_none() {
+            let ((left, top), (right, bottom)) = self.rotated_view_bounds(r)?;
+            let in_view = left.floor() >= 0.0
+                && top.floor() >= 0.0
+                && right.ceil() <= self.view_dimensions.width as f32
+                && bottom.ceil() <= self.view_dimensions.height as f32;
+
+            return in_view.then(|| self.view_rect_covering(r)).flatten();
+        }
+
         let top_left = self.transform_canvas_to_view(r.top_left)?;
         let bottom_right = self.transform_canvas_to_view(r.bottom_right() + (1, 1).into())?;
 
@@ -123,8 +290,33 @@ impl CanvasView {
         ))
     }
 
+    /// The smallest view rect covering the part of a canvas rect that is in
+    /// view, or `None` if none of it is.
+    pub fn view_rect_covering(&self, r: &CanvasRect) -> Option<ViewRect> {
+        let ((left, top), (right, bottom)) = self.rotated_view_bounds(r)?;
+
+        let left = left.floor().max(0.0) as usize;
+        let top = top.floor().max(0.0) as usize;
+        let right = (right.ceil().max(0.0) as usize).min(self.view_dimensions.width);
+        let bottom = (bottom.ceil().max(0.0) as usize).min(self.view_dimensions.height);
+
+        let view_rect = ViewRect {
+            top_left: (left, top).into(),
+            dimensions: Dimensions {
+                width: right.saturating_sub(left),
+                height: bottom.saturating_sub(top),
+            },
+        };
+
+        (!view_rect.is_degenerate()).then_some(view_rect)
+    }
+
     /// Transform a rect in view space to a rect in canvas space.
     pub fn transform_view_rect_to_canvas(&self, r: &ViewRect) -> CanvasRect {
+        if !self.rotation.is_none() {
+            return self.canvas_rect_covering_view_rect(r);
+        }
+
         let top_left = self.transform_view_to_canvas(r.top_left);
         let bottom_right = self.transform_view_to_canvas(r.bottom_right());
 
@@ -137,6 +329,8 @@ impl CanvasView {
         NearestNeighbourMap::new(self.canvas_dimensions, self.view_dimensions)
     }
 
+    /// The canvas rect of the view before it is turned. Use
+    /// `CanvasView::visible_canvas_rect` for everything visible in the view.
     pub fn canvas_rect(&self) -> CanvasRect {
         CanvasRect {
             top_left: self.top_left,
@@ -144,6 +338,59 @@ impl CanvasView {
         }
     }
 
+    /// The smallest canvas rect covering a rect of the view as it is turned.
+    fn canvas_rect_covering_view_rect(&self, r: &ViewRect) -> CanvasRect {
+        let ((left, top), (right, bottom)) = transformed_bounds(
+            &self.view_to_canvas_transform(),
+            (r.top_left.0 as f32, r.top_left.1 as f32),
+            r.dimensions,
+        );
+
+        CanvasRect {
+            top_left: (left.floor() as i32, top.floor() as i32).into(),
+            dimensions: Dimensions {
+                width: (right.ceil() - left.floor()) as usize,
+                height: (bottom.ceil() - top.floor()) as usize,
+            },
+        }
+    }
+
+    /// The canvas rect covering everything visible in the view, which is
+    /// larger than `CanvasView::canvas_rect` when the view is turned by
+    /// anything but a half turn.
+    pub fn visible_canvas_rect(&self) -> CanvasRect {
+        if self.rotation.is_none() {
+            return self.canvas_rect();
+        }
+
+        self.canvas_rect_covering_view_rect(&ViewRect::at_origin(self.view_dimensions))
+    }
+
+    /// An unturned view of the visible canvas rect of this view at the same
+    /// scale, which rotated views are rendered from.
+    pub fn bounding_view(&self) -> CanvasView {
+        if self.rotation.is_none() {
+            return *self;
+        }
+
+        let visible_canvas_rect = self.visible_canvas_rect();
+        let scale = self.view_dimensions.relative_scale(self.canvas_dimensions);
+
+        CanvasView {
+            top_left: visible_canvas_rect.top_left,
+            canvas_dimensions: visible_canvas_rect.dimensions,
+            view_dimensions: Dimensions {
+                width: ((visible_canvas_rect.dimensions.width as f32 * scale.width_factor).round()
+                    as usize)
+                    .max(1),
+                height: ((visible_canvas_rect.dimensions.height as f32 * scale.height_factor)
+                    .round() as usize)
+                    .max(1),
+            },
+            rotation: ViewRotation::NONE,
+        }
+    }
+
     /// Compares equality of scales for two canvas views. Since scales can have some
     /// rounding, this equality evaluates as true for scales that are "close enough".
     pub fn scale_eq(&self, other: &CanvasView) -> bool {
@@ -159,25 +406,34 @@ impl CanvasView {
     /// A subview of this view that contains a given canvas rect. The scale of the subview
     /// is derived from this view.
     pub fn canvas_rect_subview(&self, canvas_rect: &CanvasRect) -> Option<CanvasView> {
-        let view_rect = self.transform_canvas_rect_to_view(canvas_rect)?;
+        let view_rect = self
+            .bounding_view()
+            .transform_canvas_rect_to_view(canvas_rect)?;
 
         Some(CanvasView {
             top_left: canvas_rect.top_left,
             canvas_dimensions: canvas_rect.dimensions,
             view_dimensions: view_rect.dimensions,
+            rotation: ViewRotation::NONE,
         })
     }
 }
 /// A logical layer in the canvas. Layers can be composited ontop of eachother.
 #[enum_dispatch]
+#[derive(Clone)]
 pub enum LayerImplementation {
     RasterLayer,
+    TextLayer,
+    VectorLayer,
 }
 
 #[enum_dispatch(LayerImplementation)]
 pub trait Layer {
     fn rasterize(&mut self, view: &CanvasView) -> BoxRasterChunk;
     fn rasterize_canvas_rect(&mut self, canvas_rect: CanvasRect) -> BoxRasterChunk;
+    /// Rasterizes a canvas rect of the layer. Unlike `Layer::rasterize_canvas_rect`
+    /// this only needs shared access to the layer, so it cannot fill any caches.
+    fn rasterize_canvas_rect_shared(&self, canvas_rect: CanvasRect) -> BoxRasterChunk;
     fn rasterize_into_bump<'bump>(
         &mut self,
         view: &CanvasView,
@@ -188,28 +444,340 @@ pub trait Layer {
         canvas_rect: CanvasRect,
         bump: &'bump Bump,
     ) -> BumpRasterChunk<'bump>;
+    /// Rasterizes a canvas rect of the layer like `Layer::rasterize_canvas_rect`,
+    /// returning an error instead of panicking if the rect doesn't pass
+    /// `Rect::try_new` or the layer's geometry is inconsistent.
+    fn try_rasterize_canvas_rect(
+        &mut self,
+        canvas_rect: CanvasRect,
+    ) -> Result<BoxRasterChunk, RasterizeError>;
+    /// Rasterizes a canvas rect of the layer into `bump` like
+    /// `Layer::rasterize_canvas_rect_into_bump`, returning an error instead of
+    /// panicking if the rect doesn't pass `Rect::try_new` or the layer's
+    /// geometry is inconsistent.
+    fn try_rasterize_canvas_rect_into_bump<'bump>(
+        &mut self,
+        canvas_rect: CanvasRect,
+        bump: &'bump Bump,
+    ) -> Result<BumpRasterChunk<'bump>, RasterizeError>;
     fn clear(&mut self);
+    /// The canvas rect spanning all of the layer's content, or `None` if the
+    /// layer is empty.
+    fn content_bounds(&self) -> Option<CanvasRect>;
+    /// The luminosity ranges limiting where the layer shows when composited.
+    fn blend_if(&self) -> Option<BlendIf>;
+    fn set_blend_if(&mut self, blend_if: Option<BlendIf>);
+    /// How the layer's colors are blended with the layers below it.
+    fn blend_mode(&self) -> BlendMode;
+    fn set_blend_mode(&mut self, blend_mode: BlendMode);
+    /// The glow drawn around the layer's content when composited.
+    fn glow(&self) -> Option<Glow>;
+    fn set_glow(&mut self, glow: Option<Glow>);
+    /// Whether `Layer::rasterize` draws the layer at the scale of the view
+    /// rather than scaling a raster of it, so views of the canvas should
+    /// rasterize it at their scale to keep it sharp.
+    fn rasterizes_at_view_scale(&self) -> bool {
+        false
+    }
+}
+
+/// Composites the raster of a layer over `canvas_rect` onto `base`, the
+/// composited raster of the layers below it. `base` and the layer raster may
+/// be at the scale of a view rather than the dimensions of `canvas_rect`.
+fn composite_layer<T: DerefMut<Target = [Pixel]>>(
+    base: &mut BoxRasterChunk,
+    mut layer_raster: RasterChunk<T>,
+    layer: &LayerImplementation,
+    canvas_rect: CanvasRect,
+) {
+    if let Some(glow) = layer.glow() {
+        let mut glow_raster = glow.rasterize_canvas_rect(canvas_rect, |source_rect| {
+            layer.rasterize_canvas_rect_shared(source_rect)
+        });
+        if glow_raster.dimensions() != base.dimensions() {
+            glow_raster = glow_raster.scaled(base.dimensions(), ScalingFilter::Bilinear);
+        }
+
+        base.composite_over(&glow_raster.as_window(), (0, 0).into());
+    }
+
+    if let Some(blend_if) = layer.blend_if() {
+        blend_if.apply(&mut layer_raster, base.pixels());
+    }
+
+    base.blend_over(&layer_raster.as_window(), (0, 0).into(), layer.blend_mode());
 }
 
 /// A collection of layers that can be rendered.
 #[derive(Default)]
 pub struct Canvas {
     layers: Vec<LayerImplementation>,
+    guides: Guides,
+    rng: CanvasRng,
     shape_cache: ShapeCache,
     rect_raster_cache: CanvasRectRasterCache,
     view_raster_cache: CanvasViewRasterCache,
+    region_observers: RegionObservers,
+    pixel_format: PixelFormat,
+    document_dimensions: Option<Dimensions>,
+    history: History,
+    selection: Option<Selection>,
+    /// Changed canvas rects that haven't been rasterized into the view cache.
+    stale_view_rects: Vec<CanvasRect>,
+    /// Changed canvas rects since the dirty rects were last taken.
+    dirty_rects: Vec<CanvasRect>,
+    /// Counts changes to the content of the canvas, so work based on an
+    /// earlier state can tell that it is stale.
+    generation: u64,
+    snapshot_chunks: SnapshotChunks,
+    /// Where the embedder has hinted the user is working.
+    focus: Option<CanvasRect>,
+    background: CanvasBackground,
+    latency: LatencyTracker,
+    /// Changed canvas rects by generation, so incremental saves only compare
+    /// the chunks within them.
+    change_log: ChangeLog,
 }
 
 impl Canvas {
+    /// Creates a canvas whose raster layers store their pixels in
+    /// `pixel_format`. A packed format such as `PixelFormat::Rgb565A8` uses
+    /// less memory per layer at the cost of color precision, which suits
+    /// devices with little memory.
+    pub fn with_pixel_format(pixel_format: PixelFormat) -> Canvas {
+        Canvas {
+            pixel_format,
+            ..Default::default()
+        }
+    }
+
+    pub fn pixel_format(&self) -> PixelFormat {
+        self.pixel_format
+    }
+
+    /// The size of the document, which starts at the canvas origin. Layers
+    /// extend indefinitely, so this only marks the area frontends should
+    /// present and export. Canvases without a document size are unbounded.
+    pub fn document_dimensions(&self) -> Option<Dimensions> {
+        self.document_dimensions
+    }
+
+    pub fn set_document_dimensions(&mut self, document_dimensions: Option<Dimensions>) {
+        self.document_dimensions = document_dimensions;
+    }
+
+    /// The maps used to scale views, which can also be used to scale other
+    /// rasters with `BoxRasterChunk::nn_scale_with_cache`.
+    pub fn nn_map_cache(&self) -> &NearestNeighbourMapCache {
+        self.view_raster_cache.nn_map_cache()
+    }
+
+    /// Replaces the maps used to scale views, such as with a cache shared with
+    /// other canvases.
+    pub fn set_nn_map_cache(&mut self, nn_map_cache: NearestNeighbourMapCache) {
+        self.view_raster_cache.set_nn_map_cache(nn_map_cache);
+    }
+
+    /// The limits on how much the caches of the canvas hold.
+    pub fn cache_config(&self) -> CacheConfig {
+        CacheConfig {
+            max_shape_entries: self.shape_cache.capacity(),
+            max_nn_maps: self.nn_map_cache().capacity(),
+            max_cached_raster_bytes: self.rect_raster_cache.max_cached_raster_bytes(),
+        }
+    }
+
+    /// Limits how much the caches of the canvas hold, evicting what no longer
+    /// fits. This replaces the max prerender area with the area of the largest
+    /// cached raster.
+    pub fn set_cache_config(&mut self, cache_config: CacheConfig) {
+        self.shape_cache.resize(cache_config.max_shape_entries);
+        self.nn_map_cache().set_capacity(cache_config.max_nn_maps);
+        self.rect_raster_cache
+            .set_max_cached_raster_bytes(cache_config.max_cached_raster_bytes);
+        self.view_raster_cache.set_max_prerender_area(
+            cache_config.max_cached_raster_bytes / std::mem::size_of::<Pixel>(),
+        );
+    }
+
+    /// The largest area, in pixels, rendered around a view so that panning
+    /// doesn't have to render the canvas again.
+    pub fn max_prerender_area(&self) -> usize {
+        self.view_raster_cache.max_prerender_area()
+    }
+
+    /// Limits the area rendered around views. Views whose surroundings would
+    /// be larger than `max_prerender_area` are rendered on their own, which
+    /// bounds the memory of the view cache on large displays.
+    pub fn set_max_prerender_area(&mut self, max_prerender_area: usize) {
+        self.view_raster_cache
+            .set_max_prerender_area(max_prerender_area);
+    }
+
+    /// The filter used when views show the canvas at less than its full size.
+    pub fn scaling_filter(&self) -> ScalingFilter {
+        self.view_raster_cache.scaling_filter()
+    }
+
+    /// Sets the filter used when views are downscaled. Views at or above the
+    /// size of the canvas always use nearest neighbour scaling so pixels stay
+    /// crisp when zoomed in.
+    pub fn set_scaling_filter(&mut self, scaling_filter: ScalingFilter) {
+        self.view_raster_cache.set_scaling_filter(scaling_filter);
+    }
+
+    /// The pixels raster actions are limited to, if any.
+    pub fn selection(&self) -> Option<&Selection> {
+        self.selection.as_ref()
+    }
+
+    /// Limits the pixels raster actions performed on the canvas can change,
+    /// or lifts the limit with `None`.
+    pub fn set_selection(&mut self, selection: Option<Selection>) {
+        self.selection = selection;
+    }
+
+    /// The canvas rect covered by the document, if it has a size.
+    pub fn document_rect(&self) -> Option<CanvasRect> {
+        self.document_dimensions.map(CanvasRect::at_origin)
+    }
+
+    /// Renders a view of the canvas with all layers composited.
+    ///
+    /// # Panics
+    ///
+    /// If the rects of the view don't pass `Rect::try_new` or a layer's
+    /// geometry is inconsistent. Use `Canvas::try_render` to report these.
     pub fn render(&mut self, view: &CanvasView) -> BoxRasterChunk {
+        self.refresh_view_cache();
+
+        let stopwatch = Stopwatch::start();
+        let background = self.background;
+        let scaling_filter = self.view_raster_cache.scaling_filter();
         let layers = &mut self.layers;
-        let raster = self
+        let mut rasterized = false;
+
+        let render = self
             .view_raster_cache
-            .get_chunk_or_rasterize(view, &mut |c| {
-                Canvas::rasterize_canvas_rect_uncached(layers, *c)
+            .render_view(view, &mut |c, view_dimensions| {
+                rasterized = true;
+                Canvas::rasterize_view_uncached(
+                    layers,
+                    background,
+                    *c,
+                    view_dimensions,
+                    scaling_filter,
+                )
             });
 
-        raster.to_chunk()
+        // Views served from the cache aren't rerenders
+        if rasterized {
+            self.latency.record(LatencyKind::CacheRerender, stopwatch);
+        }
+
+        render
+    }
+
+    /// The canvas rects changed by actions since this was last called or the
+    /// canvas was last rendered with `Canvas::render_dirty`.
+    pub fn take_dirty_rects(&mut self) -> Vec<CanvasRect> {
+        std::mem::take(&mut self.dirty_rects)
+    }
+
+    /// Renders only the parts of a view changed since the dirty rects were
+    /// last taken, returning each part along with where it goes in the view.
+    /// The parts are rasterized into the cached view raster rather than
+    /// rendering the whole view again, so frontends can patch their last
+    /// render with them. Parts nearest to the focus of `Canvas::hint_focus`
+    /// come first, so frontends presenting them over several frames show
+    /// where the user is working first.
+    pub fn render_dirty(&mut self, view: &CanvasView) -> Vec<(ViewRect, BoxRasterChunk)> {
+        let mut dirty_rects = self.take_dirty_rects();
+        if dirty_rects.is_empty() {
+            return Vec::new();
+        }
+        self.order_by_focus(&mut dirty_rects);
+
+        let render = self.render(view);
+        let view_canvas_rect = view.visible_canvas_rect();
+
+        dirty_rects
+            .iter()
+            .filter_map(|dirty_rect| {
+                let visible_rect = dirty_rect.intersection(&view_canvas_rect)?;
+
+                if !view.rotation.is_none() {
+                    let view_rect = view.view_rect_covering(&visible_rect)?;
+
+                    return Some((view_rect, render.subsource_at(view_rect)?));
+                }
+
+                let top_left = view.transform_canvas_to_view(visible_rect.top_left)?;
+                let past_bottom_right =
+                    view.transform_canvas_to_view(visible_rect.bottom_right() + (1, 1).into())?;
+                let view_rect = ViewRect {
+                    top_left,
+                    dimensions: Dimensions {
+                        width: past_bottom_right.0.saturating_sub(top_left.0),
+                        height: past_bottom_right.1.saturating_sub(top_left.1),
+                    },
+                };
+
+                if view_rect.is_degenerate() {
+                    return None;
+                }
+
+                Some((view_rect, render.subsource_at(view_rect)?))
+            })
+            .collect()
+    }
+
+    /// Renders a view like `Canvas::render`, returning an error instead of
+    /// panicking if the rects of the view don't pass `Rect::try_new` or a
+    /// layer's geometry is inconsistent.
+    pub fn try_render(&mut self, view: &CanvasView) -> Result<BoxRasterChunk, RasterizeError> {
+        CanvasRect::try_new(view.top_left, view.canvas_dimensions)?;
+        Dimensions::try_new(view.view_dimensions.width, view.view_dimensions.height)?;
+        self.try_refresh_view_cache()?;
+
+        let stopwatch = Stopwatch::start();
+        let background = self.background;
+        let scaling_filter = self.view_raster_cache.scaling_filter();
+        let layers = &mut self.layers;
+        let mut rasterized = false;
+        let mut rasterize_error = None;
+
+        let render = self
+            .view_raster_cache
+            .render_view(view, &mut |c, view_dimensions| {
+                rasterized = true;
+                Canvas::try_rasterize_view_uncached(
+                    layers,
+                    background,
+                    *c,
+                    view_dimensions,
+                    scaling_filter,
+                )
+                .unwrap_or_else(|error| {
+                    rasterize_error.get_or_insert(error);
+                    BoxRasterChunk::new(c.dimensions.width, c.dimensions.height)
+                })
+            });
+
+        if rasterized {
+            self.latency.record(LatencyKind::CacheRerender, stopwatch);
+        }
+
+        match rasterize_error {
+            Some(error) => {
+                // The cache was filled with blank rasters in place of the
+                // layers that couldn't be read
+                self.view_raster_cache.invalidate();
+
+                Err(error)
+            }
+            None => Ok(render),
+        }
     }
 
     pub fn render_into_bump<'bump>(
@@ -217,104 +785,883 @@ impl Canvas {
         view: &CanvasView,
         bump: &'bump Bump,
     ) -> BumpRasterChunk<'bump> {
-        let layers = &mut self.layers;
-        let raster = self
-            .view_raster_cache
-            .get_chunk_or_rasterize(view, &mut |c| {
-                Canvas::rasterize_canvas_rect_uncached(layers, *c)
-            });
+        self.refresh_view_cache();
 
-        raster.to_chunk_into_bump(bump)
+        let background = self.background;
+        let scaling_filter = self.view_raster_cache.scaling_filter();
+        let layers = &mut self.layers;
+        self.view_raster_cache
+            .render_view_into_bump(view, bump, &mut |c, view_dimensions| {
+                Canvas::rasterize_view_uncached(
+                    layers,
+                    background,
+                    *c,
+                    view_dimensions,
+                    scaling_filter,
+                )
+            })
     }
 
     fn rasterize_canvas_rect_uncached(
         layers: &mut Vec<LayerImplementation>,
+        background: CanvasBackground,
         canvas_rect: CanvasRect,
     ) -> BoxRasterChunk {
-        let Dimensions { width, height } = canvas_rect.dimensions;
-        let mut base = BoxRasterChunk::new_fill(colors::white(), width, height);
+        Canvas::try_rasterize_canvas_rect_uncached(layers, background, canvas_rect)
+            .expect("chunks of raster layers should be of their chunk size")
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    fn try_rasterize_canvas_rect_uncached(
+        layers: &mut Vec<LayerImplementation>,
+        background: CanvasBackground,
+        canvas_rect: CanvasRect,
+    ) -> Result<BoxRasterChunk, RasterizeError> {
+        let mut base = background.rasterize_canvas_rect(canvas_rect);
 
         let layer_bump = Bump::new();
         for layer in layers {
-            base.composite_over(
-                &layer
-                    .rasterize_canvas_rect_into_bump(canvas_rect, &layer_bump)
-                    .as_window(),
-                (0, 0).into(),
-            );
+            let layer_raster =
+                layer.try_rasterize_canvas_rect_into_bump(canvas_rect, &layer_bump)?;
+
+            composite_layer(&mut base, layer_raster, layer, canvas_rect);
         }
 
-        base
+        Ok(base)
+    }
+
+    /// Rasterizes each layer into its own raster in parallel, then composites
+    /// the rasters in order.
+    #[cfg(feature = "parallel")]
+    fn try_rasterize_canvas_rect_uncached(
+        layers: &mut Vec<LayerImplementation>,
+        background: CanvasBackground,
+        canvas_rect: CanvasRect,
+    ) -> Result<BoxRasterChunk, RasterizeError> {
+        use rayon::prelude::*;
+
+        let layer_rasters = layers
+            .par_iter_mut()
+            .map(|layer| layer.try_rasterize_canvas_rect(canvas_rect))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut base = background.rasterize_canvas_rect(canvas_rect);
+
+        for (layer, layer_raster) in layers.iter().zip(layer_rasters) {
+            composite_layer(&mut base, layer_raster, layer, canvas_rect);
+        }
+
+        Ok(base)
+    }
+
+    fn rasterize_view_uncached(
+        layers: &mut Vec<LayerImplementation>,
+        background: CanvasBackground,
+        canvas_rect: CanvasRect,
+        view_dimensions: Dimensions,
+        scaling_filter: ScalingFilter,
+    ) -> BoxRasterChunk {
+        Canvas::try_rasterize_view_uncached(
+            layers,
+            background,
+            canvas_rect,
+            view_dimensions,
+            scaling_filter,
+        )
+        .expect("chunks of raster layers should be of their chunk size")
+    }
+
+    /// Rasterizes a canvas rect for a view showing it at `view_dimensions`.
+    /// Layers that rasterize at the view scale are drawn at it and the other
+    /// layers are scaled to match, so text and shapes stay sharp when zoomed
+    /// in. If no layer rasterizes at the view scale the raster is left at the
+    /// dimensions of the canvas rect, for the view cache to scale.
+    fn try_rasterize_view_uncached(
+        layers: &mut Vec<LayerImplementation>,
+        background: CanvasBackground,
+        canvas_rect: CanvasRect,
+        view_dimensions: Dimensions,
+        scaling_filter: ScalingFilter,
+    ) -> Result<BoxRasterChunk, RasterizeError> {
+        if view_dimensions == canvas_rect.dimensions
+            || !layers.iter().any(Layer::rasterizes_at_view_scale)
+        {
+            return Canvas::try_rasterize_canvas_rect_uncached(layers, background, canvas_rect);
+        }
+
+        let filter = view_scaling_filter(scaling_filter, canvas_rect.dimensions, view_dimensions);
+        let view = CanvasView {
+            top_left: canvas_rect.top_left,
+            canvas_dimensions: canvas_rect.dimensions,
+            view_dimensions,
+            rotation: ViewRotation::NONE,
+        };
+        let rasterize_layer =
+            |layer: &mut LayerImplementation| -> Result<BoxRasterChunk, RasterizeError> {
+                if layer.rasterizes_at_view_scale() {
+                    Ok(layer.rasterize(&view))
+                } else {
+                    Ok(layer
+                        .try_rasterize_canvas_rect(canvas_rect)?
+                        .scaled(view_dimensions, filter))
+                }
+            };
+
+        #[cfg(not(feature = "parallel"))]
+        let layer_rasters = layers
+            .iter_mut()
+            .map(rasterize_layer)
+            .collect::<Result<Vec<_>, _>>()?;
+        #[cfg(feature = "parallel")]
+        let layer_rasters = {
+            use rayon::prelude::*;
+
+            layers
+                .par_iter_mut()
+                .map(rasterize_layer)
+                .collect::<Result<Vec<_>, _>>()?
+        };
+
+        let mut base = background
+            .rasterize_canvas_rect(canvas_rect)
+            .scaled(view_dimensions, filter);
+
+        for (layer, layer_raster) in layers.iter().zip(layer_rasters) {
+            composite_layer(&mut base, layer_raster, layer, canvas_rect);
+        }
+
+        Ok(base)
     }
 
     pub fn rasterize_canvas_rect(&mut self, canvas_rect: CanvasRect) -> BoxRasterChunk {
+        if !self.rect_raster_cache.can_cache(&canvas_rect) {
+            return Canvas::rasterize_canvas_rect_uncached(
+                &mut self.layers,
+                self.background,
+                canvas_rect,
+            );
+        }
+
+        let background = self.background;
         let layers = &mut self.layers;
         self.rect_raster_cache
             .get_chunk_or_rasterize(&canvas_rect, &mut |c| {
-                Canvas::rasterize_canvas_rect_uncached(layers, *c)
+                Canvas::rasterize_canvas_rect_uncached(layers, background, *c)
             })
             .to_chunk()
     }
 
+    /// Rasterizes a canvas rect into an RGBA image, such as to export part of
+    /// the document.
+    #[cfg(feature = "image")]
+    pub fn export_rect(&mut self, canvas_rect: CanvasRect) -> ::image::RgbaImage {
+        self.rasterize_canvas_rect(canvas_rect).to_rgba_image()
+    }
+
+    /// Rasterizes a canvas rect like `Canvas::rasterize_canvas_rect`, returning
+    /// an error if the rect doesn't pass `Rect::try_new` or a layer's geometry
+    /// is inconsistent. The raster isn't cached.
+    pub fn try_rasterize_canvas_rect(
+        &mut self,
+        canvas_rect: CanvasRect,
+    ) -> Result<BoxRasterChunk, MboardError> {
+        let canvas_rect = CanvasRect::try_new(canvas_rect.top_left, canvas_rect.dimensions)?;
+
+        Ok(Canvas::try_rasterize_canvas_rect_uncached(
+            &mut self.layers,
+            self.background,
+            canvas_rect,
+        )?)
+    }
+
     pub fn rasterize_canvas_rect_into_bump<'bump>(
         &mut self,
         canvas_rect: CanvasRect,
         bump: &'bump Bump,
     ) -> BumpRasterChunk<'bump> {
+        if !self.rect_raster_cache.can_cache(&canvas_rect) {
+            return Canvas::rasterize_canvas_rect_uncached(
+                &mut self.layers,
+                self.background,
+                canvas_rect,
+            )
+            .as_window()
+            .to_chunk_into_bump(bump);
+        }
+
+        let background = self.background;
         let layers = &mut self.layers;
         self.rect_raster_cache
             .get_chunk_or_rasterize(&canvas_rect, &mut |c| {
-                Canvas::rasterize_canvas_rect_uncached(layers, *c)
+                Canvas::rasterize_canvas_rect_uncached(layers, background, *c)
             })
             .to_chunk_into_bump(bump)
     }
 
-    pub fn add_layer(&mut self, layer: LayerImplementation) {
+    /// Rerenders a canvas rect that has been changed in all of the canvas caches
+    /// and notifies region observers of the change.
+    fn rerender_canvas_rect(&mut self, changed_canvas_rect: &CanvasRect) {
+        self.generation += 1;
+
+        let stopwatch = Stopwatch::start();
+        let background = self.background;
+        let layers = &mut self.layers;
+        self.rect_raster_cache
+            .rerender_canvas_rect(changed_canvas_rect, &mut |c| {
+                Canvas::rasterize_canvas_rect_uncached(layers, background, *c)
+            });
+        self.latency.record(LatencyKind::CacheRerender, stopwatch);
+        self.stale_view_rects.push(*changed_canvas_rect);
+        self.dirty_rects.push(*changed_canvas_rect);
+        self.change_log
+            .record(self.generation, *changed_canvas_rect);
+
+        self.region_observers.notify(changed_canvas_rect);
+    }
+
+    /// Rasterizes the canvas rects changed since the view cache was last used
+    /// into the cached view raster.
+    fn refresh_view_cache(&mut self) {
+        self.try_refresh_view_cache()
+            .expect("chunks of raster layers should be of their chunk size")
+    }
+
+    fn try_refresh_view_cache(&mut self) -> Result<(), RasterizeError> {
+        let background = self.background;
+        let scaling_filter = self.view_raster_cache.scaling_filter();
+        let layers = &mut self.layers;
+        let mut rasterize_error = None;
+
+        for stale_rect in self.stale_view_rects.drain(..) {
+            let stopwatch = Stopwatch::start();
+            let mut rasterized = false;
+            self.view_raster_cache
+                .rerender_canvas_rect(&stale_rect, &mut |c, view_dimensions| {
+                    rasterized = true;
+                    Canvas::try_rasterize_view_uncached(
+                        layers,
+                        background,
+                        *c,
+                        view_dimensions,
+                        scaling_filter,
+                    )
+                    .unwrap_or_else(|error| {
+                        rasterize_error.get_or_insert(error);
+                        BoxRasterChunk::new(c.dimensions.width, c.dimensions.height)
+                    })
+                });
+
+            if rasterized {
+                self.latency.record(LatencyKind::CacheRerender, stopwatch);
+            }
+        }
+
+        match rasterize_error {
+            Some(error) => {
+                self.view_raster_cache.invalidate();
+
+                Err(error)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Drops everything in the canvas caches, for changes that affect the
+    /// whole canvas.
+    fn invalidate_caches(&mut self) {
+        self.generation += 1;
+        self.change_log.reset(self.generation);
+        self.rect_raster_cache.invalidate();
+        self.view_raster_cache.invalidate();
+        self.stale_view_rects.clear();
+    }
+
+    /// Sets the luminosity ranges limiting where the layer at `layer_num` shows.
+    /// Since this can change the whole canvas, region observers are not
+    /// notified and the canvas should be redrawn. Returns whether the layer exists.
+    pub fn set_layer_blend_if(&mut self, layer_num: usize, blend_if: Option<BlendIf>) -> bool {
+        match self.layers.get_mut(layer_num) {
+            Some(layer) => {
+                layer.set_blend_if(blend_if);
+                self.invalidate_caches();
+
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Sets how the colors of the layer at `layer_num` are blended with the
+    /// layers below it. Like `Canvas::set_layer_blend_if`, region observers are
+    /// not notified. Returns whether the layer exists.
+    pub fn set_layer_blend_mode(&mut self, layer_num: usize, blend_mode: BlendMode) -> bool {
+        match self.layers.get_mut(layer_num) {
+            Some(layer) => {
+                layer.set_blend_mode(blend_mode);
+                self.invalidate_caches();
+
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Sets the glow drawn around the content of the layer at `layer_num`. Like
+    /// `Canvas::set_layer_blend_if`, region observers are not notified. Returns
+    /// whether the layer exists.
+    pub fn set_layer_glow(&mut self, layer_num: usize, glow: Option<Glow>) -> bool {
+        match self.layers.get_mut(layer_num) {
+            Some(layer) => {
+                layer.set_glow(glow);
+                self.invalidate_caches();
+
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Sets whether drawing on the raster layer at `layer_num` keeps the alpha
+    /// of its pixels. Nothing is redrawn, since the layer itself is unchanged.
+    /// Returns whether the layer is a raster layer.
+    pub fn set_layer_alpha_locked(&mut self, layer_num: usize, alpha_locked: bool) -> bool {
+        match self.layers.get_mut(layer_num) {
+            Some(LayerImplementation::RasterLayer(raster_layer)) => {
+                raster_layer.set_alpha_locked(alpha_locked);
+
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Registers a callback that is called with the canvas rect changed by
+    /// each action, after the canvas caches have been updated.
+    pub fn on_region_changed<F>(&mut self, observer: F) -> RegionObserverId
+    where
+        F: FnMut(&CanvasRect) + 'static,
+    {
+        self.region_observers.add(Box::new(observer))
+    }
+
+    /// Unregisters a callback added with `Canvas::on_region_changed`, returning
+    /// whether it was registered.
+    pub fn remove_region_observer(&mut self, id: RegionObserverId) -> bool {
+        self.region_observers.remove(id)
+    }
+
+    /// The number of layers of the canvas.
+    pub fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Adds a layer to the top of the canvas. Raster layers are converted to
+    /// the pixel format of the canvas.
+    pub fn add_layer(&mut self, mut layer: LayerImplementation) {
+        if let LayerImplementation::RasterLayer(raster_layer) = &mut layer {
+            raster_layer.set_pixel_format(self.pixel_format);
+        }
+
         self.layers.push(layer);
+        self.generation += 1;
     }
 
+    /// Performs an action on the raster layer at `layer_num`, returning the
+    /// canvas rect it altered, or `None` if there is no raster layer there.
+    ///
+    /// # Panics
+    ///
+    /// If the action doesn't pass `RasterLayer::check_action`. Use
+    /// `Canvas::try_perform_raster_action` with actions from untrusted input.
     pub fn perform_raster_action(
         &mut self,
         layer_num: usize,
         action: RasterLayerAction,
     ) -> Option<CanvasRect> {
         use LayerImplementation::*;
+        let stopwatch = Stopwatch::start();
         if let Some(layer) = self.layers.get_mut(layer_num) {
             match layer {
                 RasterLayer(raster_layer) => {
-                    let changed_canvas_rect =
-                        raster_layer.perform_action_with_cache(action, &mut self.shape_cache);
+                    let state = LayerState::snapshot_chunks(raster_layer, action.bounding_rect()?);
+                    let changed_canvas_rect = match &self.selection {
+                        Some(selection) => raster_layer.perform_action_in_selection(
+                            action.clone(),
+                            selection,
+                            &mut self.shape_cache,
+                        ),
+                        None => raster_layer
+                            .perform_action_with_cache(action.clone(), &mut self.shape_cache),
+                    };
 
-                    let layers = &mut self.layers;
                     if let Some(changed_canvas_rect) = changed_canvas_rect {
-                        self.rect_raster_cache
-                            .rerender_canvas_rect(&changed_canvas_rect, &mut |c| {
-                                Canvas::rasterize_canvas_rect_uncached(layers, *c)
-                            });
-                        self.view_raster_cache
-                            .rerender_canvas_rect(&changed_canvas_rect, &mut |c| {
-                                Canvas::rasterize_canvas_rect_uncached(layers, *c)
-                            });
+                        self.record_history(
+                            layer_num,
+                            HistoryAction::Raster(action),
+                            state,
+                            changed_canvas_rect,
+                        );
+                        self.rerender_canvas_rect(&changed_canvas_rect);
+                        self.latency.record(LatencyKind::RasterAction, stopwatch);
                     }
 
                     changed_canvas_rect
                 }
+                _ => None,
             }
         } else {
             None
         }
     }
+
+    /// Performs an action like `Canvas::perform_raster_action`, returning an
+    /// error instead of doing nothing if there is no raster layer at
+    /// `layer_num`, and instead of panicking if the action doesn't pass
+    /// `RasterLayer::check_action`.
+    pub fn try_perform_raster_action(
+        &mut self,
+        layer_num: usize,
+        action: RasterLayerAction,
+    ) -> Result<Option<CanvasRect>, MboardError> {
+        match self.layers.get(layer_num) {
+            Some(LayerImplementation::RasterLayer(raster_layer)) => {
+                raster_layer.check_action(&action)?
+            }
+            Some(_) => return Err(ActionError::NotARasterLayer(layer_num).into()),
+            None => return Err(ActionError::NoSuchLayer(layer_num).into()),
+        }
+
+        Ok(self.perform_raster_action(layer_num, action))
+    }
+
+    /// Reseeds the random number generator of the canvas used by randomized
+    /// actions such as sprays.
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = CanvasRng::new(seed);
+    }
+
+    /// Performs a spray on a raster layer, using the canvas random number
+    /// generator to pick the seed of the spray.
+    pub fn perform_spray(&mut self, layer_num: usize, spray: Spray) -> Option<CanvasRect> {
+        let spray = Spray {
+            seed: self.rng.next_u64(),
+            ..spray
+        };
+
+        self.perform_raster_action(layer_num, RasterLayerAction::Spray(spray))
+    }
+
+    /// Paints a clone stamp onto the raster layer at `layer_num`, copying pixels
+    /// from the layer at `source_layer_num`, which can be of any kind.
+    pub fn perform_clone_stamp(
+        &mut self,
+        source_layer_num: usize,
+        layer_num: usize,
+        clone_stamp: CloneStamp,
+    ) -> Option<CanvasRect> {
+        let source = self
+            .layers
+            .get(source_layer_num)?
+            .rasterize_canvas_rect_shared(clone_stamp.source_rect());
+
+        let (state, changed_canvas_rect) = match self.layers.get_mut(layer_num)? {
+            LayerImplementation::RasterLayer(raster_layer) => (
+                LayerState::snapshot_chunks(raster_layer, clone_stamp.destination_rect()),
+                raster_layer.apply_clone_stamp(clone_stamp, &source),
+            ),
+            _ => return None,
+        };
+
+        self.record_history(
+            layer_num,
+            HistoryAction::CloneStamp {
+                source_layer_num,
+                clone_stamp,
+            },
+            state,
+            changed_canvas_rect,
+        );
+        self.rerender_canvas_rect(&changed_canvas_rect);
+
+        Some(changed_canvas_rect)
+    }
+
+    /// Copies the pixels within `canvas_rect` out of the layer at `layer_num`,
+    /// which can be of any kind, for pasting with `Canvas::paste`. Raster layers
+    /// are read with `RasterLayer::extract_rect`. Returns `None` if there is no
+    /// layer at `layer_num`.
+    pub fn copy_rect(&self, layer_num: usize, canvas_rect: CanvasRect) -> Option<BoxRasterChunk> {
+        Some(
+            self.layers
+                .get(layer_num)?
+                .rasterize_canvas_rect_shared(canvas_rect),
+        )
+    }
+
+    /// Draws a raster onto the raster layer at `layer_num` with its top left at
+    /// `top_left`, such as one copied with `Canvas::copy_rect`, returning the
+    /// canvas rect that has been altered. Returns `None` if the layer is not a
+    /// raster layer.
+    pub fn paste(
+        &mut self,
+        layer_num: usize,
+        top_left: CanvasPosition,
+        raster: &BoxRasterChunk,
+        mode: CopyMode,
+    ) -> Option<CanvasRect> {
+        let canvas_rect = CanvasRect {
+            top_left,
+            dimensions: raster.dimensions(),
+        };
+        if canvas_rect.dimensions.width == 0 || canvas_rect.dimensions.height == 0 {
+            return None;
+        }
+
+        let (state, changed_canvas_rect) = match self.layers.get_mut(layer_num)? {
+            LayerImplementation::RasterLayer(raster_layer) => (
+                LayerState::snapshot_chunks(raster_layer, canvas_rect),
+                raster_layer.draw_raster(top_left, raster, mode),
+            ),
+            _ => return None,
+        };
+
+        self.record_history(
+            layer_num,
+            HistoryAction::Paste { canvas_rect, mode },
+            state,
+            changed_canvas_rect,
+        );
+        self.rerender_canvas_rect(&changed_canvas_rect);
+
+        Some(changed_canvas_rect)
+    }
+
+    /// Copies the pixels within `canvas_rect` from the raster layer at
+    /// `source_layer_num` onto the raster layer at `layer_num`, chunk by chunk
+    /// rather than by rasterizing the region first. Returns `None` if either
+    /// layer is not a raster layer.
+    pub fn copy_region_between_layers(
+        &mut self,
+        source_layer_num: usize,
+        layer_num: usize,
+        canvas_rect: CanvasRect,
+        mode: CopyMode,
+    ) -> Option<CanvasRect> {
+        let state = match self.layers.get(layer_num)? {
+            LayerImplementation::RasterLayer(raster_layer) => {
+                LayerState::snapshot_chunks(raster_layer, canvas_rect)
+            }
+            _ => return None,
+        };
+        let history_action = HistoryAction::CopyRegion {
+            source_layer_num,
+            canvas_rect,
+            mode,
+        };
+
+        if source_layer_num >= self.layers.len() {
+            return None;
+        }
+
+        let (source, destination) = match source_layer_num.cmp(&layer_num) {
+            Ordering::Less => {
+                let (below, above) = self.layers.split_at_mut(layer_num);
+                (&below[source_layer_num], &mut above[0])
+            }
+            Ordering::Greater => {
+                let (below, above) = self.layers.split_at_mut(source_layer_num);
+                (&above[0], &mut below[layer_num])
+            }
+            Ordering::Equal => {
+                let raster_layer = match &mut self.layers[layer_num] {
+                    LayerImplementation::RasterLayer(raster_layer) => raster_layer,
+                    _ => return None,
+                };
+
+                // Copy the region out first, since it is read while being written
+                let mut region = RasterLayer::new(raster_layer.chunk_size());
+                region.copy_region_from(raster_layer, canvas_rect, CopyMode::Blit);
+                let changed_canvas_rect = raster_layer.copy_region_from(&region, canvas_rect, mode);
+
+                self.record_history(layer_num, history_action, state, changed_canvas_rect);
+                self.rerender_canvas_rect(&changed_canvas_rect);
+
+                return Some(changed_canvas_rect);
+            }
+        };
+
+        let changed_canvas_rect = match (source, destination) {
+            (
+                LayerImplementation::RasterLayer(source),
+                LayerImplementation::RasterLayer(destination),
+            ) => destination.copy_region_from(source, canvas_rect, mode),
+            _ => return None,
+        };
+
+        self.record_history(layer_num, history_action, state, changed_canvas_rect);
+        self.rerender_canvas_rect(&changed_canvas_rect);
+
+        Some(changed_canvas_rect)
+    }
+
+    /// The raster layer at `layer_num`, if that layer is a raster layer.
+    pub fn raster_layer(&self, layer_num: usize) -> Option<&RasterLayer> {
+        match self.layers.get(layer_num)? {
+            LayerImplementation::RasterLayer(raster_layer) => Some(raster_layer),
+            _ => None,
+        }
+    }
+
+    /// Whether nothing is drawn within `canvas_rect` by any layer, such as to
+    /// warn before exporting an empty region. Pass `CanvasView::canvas_rect`
+    /// to check a view. Nothing is rasterized: raster layers are checked by
+    /// their chunks, and text and vector layers by the bounds of their
+    /// content, so those are only blank where they have no content at all.
+    pub fn is_blank(&self, canvas_rect: CanvasRect) -> bool {
+        self.layers.iter().all(|layer| {
+            // Glows reach past the content casting them
+            let reach = layer.glow().map_or(0, |glow| glow.radius as usize + 1);
+            let checked_rect = canvas_rect.expand(reach);
+
+            match layer {
+                LayerImplementation::RasterLayer(raster_layer) => {
+                    raster_layer.is_transparent_in(checked_rect)
+                }
+                layer => layer
+                    .content_bounds()
+                    .is_none_or(|bounds| bounds.intersection(&checked_rect).is_none()),
+            }
+        })
+    }
+
+    /// Replaces the transparency of the raster layer at `layer_num` with
+    /// `mask`, such as one extracted with `RasterLayer::extract_alpha` and
+    /// edited since. Returns the canvas rect that has been altered.
+    pub fn apply_layer_alpha(&mut self, layer_num: usize, mask: &MaskLayer) -> Option<CanvasRect> {
+        let (state, changed_canvas_rect) = match self.layers.get_mut(layer_num)? {
+            LayerImplementation::RasterLayer(raster_layer) => (
+                LayerState::snapshot_chunk_positions(
+                    raster_layer,
+                    raster_layer.allocated_chunk_positions(),
+                ),
+                raster_layer.apply_alpha(mask)?,
+            ),
+            _ => return None,
+        };
+
+        self.record_history(
+            layer_num,
+            HistoryAction::ApplyAlpha,
+            state,
+            changed_canvas_rect,
+        );
+        self.rerender_canvas_rect(&changed_canvas_rect);
+
+        Some(changed_canvas_rect)
+    }
+
+    /// Removes all of the content of the layer at `layer_num`, returning the
+    /// canvas rect that has been altered. Returns `None` if there is no layer
+    /// at `layer_num` or it is already empty.
+    pub fn clear_layer(&mut self, layer_num: usize) -> Option<CanvasRect> {
+        let layer = self.layers.get_mut(layer_num)?;
+        let changed_canvas_rect = layer.content_bounds()?;
+
+        let state = match layer {
+            LayerImplementation::RasterLayer(raster_layer) => LayerState::snapshot_chunk_positions(
+                raster_layer,
+                raster_layer.allocated_chunk_positions(),
+            ),
+            LayerImplementation::TextLayer(text_layer) => {
+                LayerState::Text(text_layer.snapshot_objects())
+            }
+            LayerImplementation::VectorLayer(vector_layer) => {
+                LayerState::Vector(vector_layer.snapshot_shapes())
+            }
+        };
+        layer.clear();
+
+        self.record_history(
+            layer_num,
+            HistoryAction::ClearLayer,
+            state,
+            changed_canvas_rect,
+        );
+        self.rerender_canvas_rect(&changed_canvas_rect);
+
+        Some(changed_canvas_rect)
+    }
+
+    /// Makes a canvas rect of the raster layer at `layer_num` transparent,
+    /// returning the canvas rect that has been altered. Returns `None` if the
+    /// layer is not a raster layer or has nothing allocated within the rect.
+    pub fn clear_rect(&mut self, layer_num: usize, canvas_rect: CanvasRect) -> Option<CanvasRect> {
+        let (state, changed_canvas_rect) = match self.layers.get_mut(layer_num)? {
+            LayerImplementation::RasterLayer(raster_layer) => (
+                LayerState::snapshot_chunks(raster_layer, canvas_rect),
+                raster_layer.clear_rect(canvas_rect)?,
+            ),
+            _ => return None,
+        };
+
+        self.record_history(
+            layer_num,
+            HistoryAction::ClearRect(canvas_rect),
+            state,
+            changed_canvas_rect,
+        );
+        self.rerender_canvas_rect(&changed_canvas_rect);
+
+        Some(changed_canvas_rect)
+    }
+
+    /// The text layer at `layer_num`, if that layer is a text layer.
+    pub fn text_layer(&self, layer_num: usize) -> Option<&TextLayer> {
+        match self.layers.get(layer_num)? {
+            LayerImplementation::TextLayer(text_layer) => Some(text_layer),
+            _ => None,
+        }
+    }
+
+    pub fn perform_text_action(
+        &mut self,
+        layer_num: usize,
+        action: TextLayerAction,
+    ) -> Option<CanvasRect> {
+        let (state, changed_canvas_rect) = match self.layers.get_mut(layer_num)? {
+            LayerImplementation::TextLayer(text_layer) => (
+                LayerState::Text(text_layer.snapshot_objects()),
+                text_layer.perform_action(action.clone())?,
+            ),
+            _ => return None,
+        };
+
+        self.record_history(
+            layer_num,
+            HistoryAction::Text(action),
+            state,
+            changed_canvas_rect,
+        );
+        self.rerender_canvas_rect(&changed_canvas_rect);
+
+        Some(changed_canvas_rect)
+    }
+
+    /// The vector layer at `layer_num`, if that layer is a vector layer.
+    pub fn vector_layer(&self, layer_num: usize) -> Option<&VectorLayer> {
+        match self.layers.get(layer_num)? {
+            LayerImplementation::VectorLayer(vector_layer) => Some(vector_layer),
+            _ => None,
+        }
+    }
+
+    /// Sets the cell size of the hit index of the vector layer at `layer_num`,
+    /// as described by `VectorLayer::set_hit_index_cell_size`. Returns whether
+    /// the layer is a vector layer.
+    pub fn set_vector_hit_index_cell_size(
+        &mut self,
+        layer_num: usize,
+        cell_size: Option<usize>,
+    ) -> bool {
+        match self.layers.get_mut(layer_num) {
+            Some(LayerImplementation::VectorLayer(vector_layer)) => {
+                vector_layer.set_hit_index_cell_size(cell_size);
+
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The topmost shape at `position` on the vector layer at `layer_num`,
+    /// using the layer's hit index if it has one.
+    pub fn vector_shape_at(
+        &mut self,
+        layer_num: usize,
+        position: CanvasPosition,
+    ) -> Option<ShapeId> {
+        match self.layers.get_mut(layer_num)? {
+            LayerImplementation::VectorLayer(vector_layer) => vector_layer.hit_test(position),
+            _ => None,
+        }
+    }
+
+    pub fn perform_vector_action(
+        &mut self,
+        layer_num: usize,
+        action: VectorLayerAction,
+    ) -> Option<CanvasRect> {
+        let (state, changed_canvas_rect) = match self.layers.get_mut(layer_num)? {
+            LayerImplementation::VectorLayer(vector_layer) => (
+                LayerState::Vector(vector_layer.snapshot_shapes()),
+                vector_layer.perform_action(action.clone())?,
+            ),
+            _ => return None,
+        };
+
+        self.record_history(
+            layer_num,
+            HistoryAction::Vector(action),
+            state,
+            changed_canvas_rect,
+        );
+        self.rerender_canvas_rect(&changed_canvas_rect);
+
+        Some(changed_canvas_rect)
+    }
+
+    /// The actions performed on the canvas that can be undone and redone.
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    pub fn history_mut(&mut self) -> &mut History {
+        &mut self.history
+    }
+
+    fn record_history(
+        &mut self,
+        layer_num: usize,
+        action: HistoryAction,
+        state: LayerState,
+        changed_canvas_rect: CanvasRect,
+    ) {
+        if let Some(layer) = self.layers.get(layer_num) {
+            self.history
+                .record(layer_num, action, state, layer, changed_canvas_rect);
+        }
+    }
+
+    /// Undoes the last action performed on the canvas, returning the canvas
+    /// rect that has been altered. Returns `None` if there is nothing to undo.
+    pub fn undo(&mut self) -> Option<CanvasRect> {
+        let changed_canvas_rect = self.history.undo(&mut self.layers)?;
+        self.rerender_canvas_rect(&changed_canvas_rect);
+
+        Some(changed_canvas_rect)
+    }
+
+    /// Performs the last undone action again, returning the canvas rect that
+    /// has been altered. Returns `None` if there is nothing to redo.
+    pub fn redo(&mut self) -> Option<CanvasRect> {
+        let changed_canvas_rect = self.history.redo(&mut self.layers)?;
+        self.rerender_canvas_rect(&changed_canvas_rect);
+
+        Some(changed_canvas_rect)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{
-        primitives::rect::ViewRect,
+        assert_raster_eq,
+        primitives::{dimensions::GeometryError, rect::ViewRect},
         raster::{
-            chunks::{translate_rect_position_to_flat_index, IndexableByPosition},
-            Pixel, RasterLayerAction,
+            chunks::translate_rect_position_to_flat_index, pixels::colors, CopyMode, GlowStyle,
+            LuminosityRange, Pixel, RasterLayerAction,
         },
+        text::{test_font::test_font_bytes, Font, TextObject},
     };
 
     #[test]
@@ -325,17 +1672,126 @@ mod tests {
         assert_eq!(view.transform_view_to_canvas((5, 5).into()), (0, 0).into());
         assert_eq!(view.transform_view_to_canvas((0, 5).into()), (-5, 0).into());
 
-        view.translate((5, 5).into());
-        view.canvas_dimensions = Dimensions {
-            width: 20,
-            height: 20,
+        view.translate((5, 5).into());
+        view.canvas_dimensions = Dimensions {
+            width: 20,
+            height: 20,
+        };
+        assert_eq!(view.transform_view_to_canvas((0, 1).into()), (0, 2).into());
+        assert_eq!(
+            view.transform_view_to_canvas((5, 5).into()),
+            (10, 10).into()
+        );
+        assert_eq!(view.transform_view_to_canvas((5, 1).into()), (10, 2).into());
+    }
+
+    #[test]
+    fn rotated_views_turn_the_canvas() {
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(8).into());
+        canvas.perform_raster_action(
+            0,
+            RasterLayerAction::fill_rect(
+                CanvasRect {
+                    top_left: (0, 0).into(),
+                    dimensions: Dimensions {
+                        width: 2,
+                        height: 1,
+                    },
+                },
+                colors::red(),
+            ),
+        );
+
+        let mut view = CanvasView::new(4, 4);
+        view.rotate(ViewRotation::QUARTER_TURN);
+        assert_eq!(view.transform_view_to_canvas((3, 1).into()), (1, 0).into());
+        assert_eq!(
+            view.transform_canvas_to_view((0, 0).into()),
+            Some((4, 0).into())
+        );
+        assert_eq!(view.visible_canvas_rect(), view.canvas_rect());
+
+        let render = canvas.render(&view);
+        let red_pixels: Vec<usize> = render
+            .pixels()
+            .iter()
+            .enumerate()
+            .filter(|(_, pixel)| pixel.is_close(&colors::red(), 2))
+            .map(|(index, _)| index)
+            .collect();
+        assert_eq!(red_pixels, vec![3, 7]);
+
+        canvas.take_dirty_rects();
+        let blue_rect = CanvasRect {
+            top_left: (0, 3).into(),
+            dimensions: Dimensions {
+                width: 1,
+                height: 1,
+            },
+        };
+        canvas.perform_raster_action(0, RasterLayerAction::fill_rect(blue_rect, colors::blue()));
+        let patches = canvas.render_dirty(&view);
+        assert_eq!(patches.len(), 1);
+        assert_eq!(
+            patches[0].0,
+            ViewRect {
+                top_left: (0, 0).into(),
+                dimensions: blue_rect.dimensions,
+            }
+        );
+        assert!(patches[0].1.pixels()[0].is_close(&colors::blue(), 2));
+
+        view.rotate(ViewRotation::from_degrees(-45));
+        assert_eq!(view.rotation.degrees(), 45);
+        let visible_canvas_rect = view.visible_canvas_rect();
+        assert_eq!(visible_canvas_rect.top_left, (-1, -1).into());
+        assert_eq!(visible_canvas_rect.dimensions.width, 6);
+        assert_eq!(canvas.render(&view).dimensions(), view.view_dimensions);
+    }
+
+    #[test]
+    fn cache_config_bounds_cached_rasters() {
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(8).into());
+
+        let cache_config = CacheConfig {
+            max_shape_entries: 4,
+            max_nn_maps: 2,
+            max_cached_raster_bytes: 16 * 16 * std::mem::size_of::<Pixel>(),
         };
-        assert_eq!(view.transform_view_to_canvas((0, 1).into()), (0, 2).into());
+        canvas.set_cache_config(cache_config);
+        assert_eq!(canvas.cache_config(), cache_config);
+        assert_eq!(canvas.max_prerender_area(), 16 * 16);
+
+        canvas.render(&CanvasView::new(8, 8));
+        assert!(canvas.view_raster_cache.has_cached_raster());
+
+        let large_view = CanvasView::new(32, 32);
+        let render = canvas.render(&large_view);
+        assert_eq!(render.dimensions(), large_view.view_dimensions);
+        assert!(!canvas.view_raster_cache.has_cached_raster());
+
+        let small_rect = CanvasRect::at_origin(Dimensions {
+            width: 8,
+            height: 8,
+        });
         assert_eq!(
-            view.transform_view_to_canvas((5, 5).into()),
-            (10, 10).into()
+            CanvasRectRasterCache::prerendered_canvas_rect(
+                &small_rect,
+                cache_config.max_cached_raster_bytes
+            ),
+            small_rect
+        );
+        assert!(!canvas
+            .rect_raster_cache
+            .can_cache(&large_view.canvas_rect()));
+        assert_eq!(
+            canvas
+                .rasterize_canvas_rect(large_view.canvas_rect())
+                .dimensions(),
+            large_view.canvas_dimensions
         );
-        assert_eq!(view.transform_view_to_canvas((5, 1).into()), (10, 2).into());
     }
 
     #[test]
@@ -576,6 +2032,7 @@ mod tests {
                 width: 5,
                 height: 5,
             },
+            rotation: ViewRotation::NONE,
         };
 
         assert_eq!(
@@ -605,6 +2062,7 @@ mod tests {
                 width: 5,
                 height: 5,
             },
+            rotation: ViewRotation::NONE,
         };
 
         let canvas_rect_a = CanvasRect {
@@ -636,6 +2094,7 @@ mod tests {
                 width: 20,
                 height: 20,
             },
+            rotation: ViewRotation::NONE,
         };
 
         let canvas_rect_b = CanvasRect {
@@ -670,6 +2129,7 @@ mod tests {
                 width: 10,
                 height: 10,
             },
+            rotation: ViewRotation::NONE,
         };
 
         {
@@ -691,7 +2151,8 @@ mod tests {
                     canvas_dimensions: Dimensions {
                         width: 20,
                         height: 20
-                    }
+                    },
+                    rotation: ViewRotation::NONE,
                 }
             );
         }
@@ -715,7 +2176,8 @@ mod tests {
                     canvas_dimensions: Dimensions {
                         width: 5,
                         height: 5
-                    }
+                    },
+                    rotation: ViewRotation::NONE,
                 }
             );
         }
@@ -739,7 +2201,8 @@ mod tests {
                     canvas_dimensions: Dimensions {
                         width: 20,
                         height: 20
-                    }
+                    },
+                    rotation: ViewRotation::NONE,
                 }
             );
         }
@@ -763,9 +2226,679 @@ mod tests {
                     canvas_dimensions: Dimensions {
                         width: 20,
                         height: 20
-                    }
+                    },
+                    rotation: ViewRotation::NONE,
                 }
             );
         }
     }
+
+    #[test]
+    fn clone_stamp_between_layers() {
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(16).into());
+        canvas.add_layer(RasterLayer::new(16).into());
+
+        canvas.perform_raster_action(
+            0,
+            RasterLayerAction::fill_rect(
+                CanvasRect {
+                    top_left: (0, 0).into(),
+                    dimensions: Dimensions {
+                        width: 16,
+                        height: 16,
+                    },
+                },
+                colors::blue(),
+            ),
+        );
+
+        let clone_stamp = CloneStamp {
+            center: (40, 40).into(),
+            source_offset: (-32, -32).into(),
+            diameter: 10,
+            falloff: Default::default(),
+        };
+
+        let view = CanvasView::new(64, 64);
+        canvas.render(&view);
+
+        let changed_rect = canvas.perform_clone_stamp(0, 1, clone_stamp).unwrap();
+        assert_eq!(changed_rect, clone_stamp.destination_rect());
+
+        assert!(canvas.perform_clone_stamp(0, 2, clone_stamp).is_none());
+
+        let raster = canvas.render(&view);
+        let position =
+            translate_rect_position_to_flat_index((40, 40).into(), raster.dimensions()).unwrap();
+        assert!(raster.pixels()[position].is_close(&colors::blue(), 2));
+    }
+
+    #[test]
+    fn region_observers_are_notified() {
+        use std::{cell::RefCell, rc::Rc};
+
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(16).into());
+
+        let changed_rects = Rc::new(RefCell::new(vec![]));
+        let observer_id = {
+            let changed_rects = changed_rects.clone();
+            canvas
+                .on_region_changed(move |canvas_rect| changed_rects.borrow_mut().push(*canvas_rect))
+        };
+
+        let rect = CanvasRect {
+            top_left: (3, 5).into(),
+            dimensions: Dimensions {
+                width: 20,
+                height: 4,
+            },
+        };
+        canvas.perform_raster_action(0, RasterLayerAction::fill_rect(rect, colors::red()));
+        canvas.perform_raster_action(1, RasterLayerAction::fill_rect(rect, colors::red()));
+
+        assert_eq!(*changed_rects.borrow(), vec![rect]);
+
+        assert!(canvas.remove_region_observer(observer_id));
+        assert!(!canvas.remove_region_observer(observer_id));
+
+        canvas.perform_raster_action(0, RasterLayerAction::fill_rect(rect, colors::red()));
+        assert_eq!(changed_rects.borrow().len(), 1);
+    }
+
+    #[test]
+    fn clearing_reports_changed_rects() {
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(8).into());
+
+        let red_rect = CanvasRect {
+            top_left: (2, 2).into(),
+            dimensions: Dimensions {
+                width: 12,
+                height: 4,
+            },
+        };
+        canvas.perform_raster_action(0, RasterLayerAction::fill_rect(red_rect, colors::red()));
+
+        let right_half = CanvasRect {
+            top_left: (8, 0).into(),
+            dimensions: Dimensions {
+                width: 8,
+                height: 8,
+            },
+        };
+        assert_eq!(canvas.clear_rect(0, right_half), Some(right_half));
+        assert!(canvas
+            .raster_layer(0)
+            .unwrap()
+            .chunk((1, 0).into())
+            .is_none());
+        assert_eq!(canvas.clear_rect(0, right_half), None);
+
+        let view = CanvasView::new(16, 8);
+        assert!(canvas.render(&view).pixels()[3 * 16 + 4].is_close(&colors::red(), 2));
+
+        assert_eq!(
+            canvas.clear_layer(0),
+            Some(CanvasRect::at_origin(Dimensions {
+                width: 8,
+                height: 8,
+            }))
+        );
+        assert!(canvas.render(&view).pixels()[3 * 16 + 4].is_close(&colors::white(), 2));
+        assert_eq!(canvas.clear_layer(0), None);
+
+        canvas.undo();
+        assert!(canvas.render(&view).pixels()[3 * 16 + 4].is_close(&colors::red(), 2));
+        canvas.undo();
+        assert!(canvas.render(&view).pixels()[3 * 16 + 12].is_close(&colors::red(), 2));
+    }
+
+    #[test]
+    fn blank_regions_are_found_without_rasterizing() {
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(8).into());
+        canvas.add_layer(VectorLayer::new().into());
+
+        let square = |x, y, size| CanvasRect {
+            top_left: (x, y).into(),
+            dimensions: Dimensions {
+                width: size,
+                height: size,
+            },
+        };
+        assert!(canvas.is_blank(square(0, 0, 16)));
+
+        canvas.perform_raster_action(
+            0,
+            RasterLayerAction::fill_rect(square(2, 2, 4), colors::red()),
+        );
+        assert!(!canvas.is_blank(square(0, 0, 16)));
+        assert!(canvas.is_blank(square(7, 0, 1)));
+        assert!(canvas.is_blank(square(8, 8, 8)));
+
+        canvas.set_layer_glow(0, Some(Glow::new(colors::blue(), 2, GlowStyle::Soft)));
+        assert!(!canvas.is_blank(square(7, 0, 1)));
+        assert!(canvas.is_blank(square(9, 9, 1)));
+    }
+
+    #[test]
+    fn blend_mode_blends_layer_with_layers_below() {
+        let mut canvas = Canvas::default();
+        let rect = CanvasRect::at_origin(Dimensions {
+            width: 8,
+            height: 8,
+        });
+
+        canvas.add_layer(RasterLayer::new(8).into());
+        canvas.add_layer(RasterLayer::new(8).into());
+        canvas.perform_raster_action(0, RasterLayerAction::fill_rect(rect, colors::grey()));
+        canvas.perform_raster_action(1, RasterLayerAction::fill_rect(rect, colors::red()));
+
+        let view = CanvasView::new(8, 8);
+        assert!(canvas.render(&view).pixels()[0].is_close(&colors::red(), 2));
+
+        assert!(canvas.set_layer_blend_mode(1, BlendMode::Multiply));
+        assert!(!canvas.set_layer_blend_mode(2, BlendMode::Screen));
+        assert!(canvas.render(&view).pixels()[0].is_close(&Pixel::new_rgb(128, 0, 0), 2));
+    }
+
+    #[test]
+    fn blend_if_limits_layer_to_underlying_luminosity() {
+        let mut canvas = Canvas::default();
+        let mut dark_layer = RasterLayer::new(32);
+        let mut red_layer = RasterLayer::new(32);
+
+        let left_half = CanvasRect {
+            top_left: (0, 0).into(),
+            dimensions: Dimensions {
+                width: 16,
+                height: 32,
+            },
+        };
+        let rect = CanvasRect {
+            top_left: (0, 0).into(),
+            dimensions: Dimensions {
+                width: 32,
+                height: 32,
+            },
+        };
+
+        dark_layer.perform_action(RasterLayerAction::fill_rect(left_half, colors::black()));
+        red_layer.perform_action(RasterLayerAction::fill_rect(rect, colors::red()));
+
+        canvas.add_layer(dark_layer.into());
+        canvas.add_layer(red_layer.into());
+
+        let view = CanvasView::new(32, 32);
+        let pixel_at = |raster: &BoxRasterChunk, x: usize, y: usize| {
+            let position =
+                translate_rect_position_to_flat_index((x, y).into(), raster.dimensions()).unwrap();
+            raster.pixels()[position]
+        };
+
+        let raster = canvas.render(&view);
+        assert!(pixel_at(&raster, 4, 4).is_close(&colors::red(), 2));
+
+        assert!(canvas.set_layer_blend_if(
+            1,
+            Some(BlendIf {
+                underlying: LuminosityRange {
+                    low: (128, 128),
+                    high: (255, 255),
+                },
+                ..BlendIf::default()
+            }),
+        ));
+        assert!(!canvas.set_layer_blend_if(2, None));
+
+        let raster = canvas.render(&view);
+        assert!(pixel_at(&raster, 4, 4).is_close(&colors::black(), 2));
+        assert!(pixel_at(&raster, 24, 4).is_close(&colors::red(), 2));
+    }
+
+    #[test]
+    fn copy_region_between_layers_with_different_chunk_sizes() {
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(8).into());
+        canvas.add_layer(RasterLayer::new(16).into());
+
+        let red_rect = CanvasRect {
+            top_left: (4, 4).into(),
+            dimensions: Dimensions {
+                width: 10,
+                height: 10,
+            },
+        };
+        let whole_rect = CanvasRect::at_origin(Dimensions {
+            width: 32,
+            height: 32,
+        });
+        let copied_rect = CanvasRect::at_origin(Dimensions {
+            width: 12,
+            height: 12,
+        });
+
+        canvas.perform_raster_action(0, RasterLayerAction::fill_rect(red_rect, colors::red()));
+
+        let pixel_at = |canvas: &Canvas, x: usize, y: usize| {
+            let raster = canvas.layers[1].rasterize_canvas_rect_shared(whole_rect);
+            raster.pixels()[y * 32 + x]
+        };
+
+        for (mode, expected_outside_red) in [
+            (CopyMode::Blit, colors::transparent()),
+            (CopyMode::Composite, colors::blue()),
+        ] {
+            canvas
+                .perform_raster_action(1, RasterLayerAction::fill_rect(whole_rect, colors::blue()));
+
+            assert_eq!(
+                canvas.copy_region_between_layers(0, 1, copied_rect, mode),
+                Some(copied_rect)
+            );
+
+            assert_eq!(
+                pixel_at(&canvas, 2, 2).as_rgba().3,
+                expected_outside_red.as_rgba().3
+            );
+            assert!(pixel_at(&canvas, 5, 5).is_close(&colors::red(), 2));
+            assert_eq!(pixel_at(&canvas, 13, 13), colors::blue());
+        }
+
+        assert!(canvas
+            .copy_region_between_layers(0, 2, copied_rect, CopyMode::Blit)
+            .is_none());
+    }
+
+    #[test]
+    fn undo_and_redo_restore_changed_chunks() {
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(8).into());
+
+        let whole_rect = CanvasRect::at_origin(Dimensions {
+            width: 32,
+            height: 32,
+        });
+        let red_rect = CanvasRect {
+            top_left: (2, 2).into(),
+            dimensions: Dimensions {
+                width: 4,
+                height: 4,
+            },
+        };
+        let blue_rect = CanvasRect {
+            top_left: (4, 4).into(),
+            dimensions: Dimensions {
+                width: 20,
+                height: 20,
+            },
+        };
+
+        canvas.perform_raster_action(0, RasterLayerAction::fill_rect(red_rect, colors::red()));
+        let red_raster = canvas.layers[0].rasterize_canvas_rect_shared(whole_rect);
+
+        canvas.perform_raster_action(0, RasterLayerAction::fill_rect(blue_rect, colors::blue()));
+        let blue_raster = canvas.layers[0].rasterize_canvas_rect_shared(whole_rect);
+        assert!(matches!(
+            canvas.history().undo_action(),
+            Some(HistoryAction::Raster(RasterLayerAction::FillRect(..)))
+        ));
+
+        assert_eq!(canvas.undo(), Some(blue_rect));
+        assert_eq!(
+            canvas.layers[0].rasterize_canvas_rect_shared(whole_rect),
+            red_raster
+        );
+        assert!(canvas.history().can_redo());
+
+        assert_eq!(canvas.redo(), Some(blue_rect));
+        assert_eq!(
+            canvas.layers[0].rasterize_canvas_rect_shared(whole_rect),
+            blue_raster
+        );
+
+        canvas.undo();
+        canvas.undo();
+        assert!(!canvas.history().can_undo());
+        assert!(canvas.layers[0]
+            .rasterize_canvas_rect_shared(whole_rect)
+            .pixels()
+            .iter()
+            .all(|pixel| pixel.as_rgba().3 == 0));
+
+        canvas.redo();
+        canvas.perform_raster_action(0, RasterLayerAction::fill_rect(red_rect, colors::green()));
+        assert!(!canvas.history().can_redo());
+        assert_eq!(canvas.redo(), None);
+    }
+
+    #[test]
+    fn grouped_actions_are_one_undo_step() {
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(8).into());
+        canvas.history_mut().set_max_depth(2);
+        let dab = |x| {
+            RasterLayerAction::fill_rect(
+                CanvasRect {
+                    top_left: (x, 0).into(),
+                    dimensions: Dimensions {
+                        width: 4,
+                        height: 4,
+                    },
+                },
+                colors::red(),
+            )
+        };
+
+        canvas.perform_raster_action(0, dab(0));
+        canvas.history_mut().begin_group();
+        for x in 1..200 {
+            canvas.perform_raster_action(0, dab(x * 4));
+        }
+        canvas.history_mut().end_group();
+
+        assert_eq!(
+            canvas.undo(),
+            Some(CanvasRect {
+                top_left: (4, 0).into(),
+                dimensions: Dimensions {
+                    width: 199 * 4,
+                    height: 4,
+                },
+            })
+        );
+        assert!(canvas.history().can_undo());
+        assert_eq!(canvas.undo(), Some(dab(0).bounding_rect().unwrap()));
+        assert!(!canvas.history().can_undo());
+    }
+
+    #[test]
+    fn undo_restores_chunks_in_their_compressed_storage() {
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(8).into());
+        let filled_rect = CanvasRect::at_origin(Dimensions {
+            width: 64,
+            height: 64,
+        });
+        canvas.perform_raster_action(0, RasterLayerAction::fill_rect(filled_rect, colors::red()));
+        let compressed_bytes = canvas.raster_layer(0).unwrap().chunk_bytes();
+
+        canvas.perform_raster_action(
+            0,
+            RasterLayerAction::draw_line((0, 0).into(), (63, 63).into(), 0, colors::blue()),
+        );
+        assert!(canvas.raster_layer(0).unwrap().chunk_bytes() > compressed_bytes);
+
+        canvas.undo();
+        assert_eq!(
+            canvas.raster_layer(0).unwrap().chunk_bytes(),
+            compressed_bytes
+        );
+        let raster = canvas
+            .raster_layer(0)
+            .unwrap()
+            .clone()
+            .rasterize_canvas_rect(filled_rect);
+        let expected = BoxRasterChunk::new_fill(colors::red(), 64, 64);
+        assert_raster_eq!(raster, expected);
+    }
+
+    #[test]
+    fn selection_limits_raster_actions() {
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(8).into());
+
+        let whole_rect = CanvasRect::at_origin(Dimensions {
+            width: 16,
+            height: 16,
+        });
+        let selected_rect = CanvasRect {
+            top_left: (10, 2).into(),
+            dimensions: Dimensions {
+                width: 3,
+                height: 3,
+            },
+        };
+
+        canvas.set_selection(Some(Selection::rect(selected_rect)));
+        canvas.perform_raster_action(0, RasterLayerAction::fill_rect(whole_rect, colors::blue()));
+
+        let raster = canvas.layers[0].rasterize_canvas_rect_shared(whole_rect);
+        let blue_pixels = raster
+            .pixels()
+            .iter()
+            .filter(|pixel| **pixel == colors::blue())
+            .count();
+        assert_eq!(blue_pixels, 9);
+        assert_eq!(raster.pixels()[3 * 16 + 11], colors::blue());
+
+        canvas.undo();
+        assert!(canvas.layers[0]
+            .rasterize_canvas_rect_shared(whole_rect)
+            .pixels()
+            .iter()
+            .all(|pixel| pixel.as_rgba().3 == 0));
+    }
+
+    #[test]
+    fn render_dirty_patches_changed_parts_of_the_view() {
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(8).into());
+
+        let mut view = CanvasView::new(20, 20);
+        view.translate((-4, -4).into());
+        canvas.render(&view);
+        assert!(canvas.render_dirty(&view).is_empty());
+
+        let red_rect = CanvasRect {
+            top_left: (2, 3).into(),
+            dimensions: Dimensions {
+                width: 5,
+                height: 4,
+            },
+        };
+        let offscreen_rect = CanvasRect {
+            top_left: (100, 100).into(),
+            dimensions: Dimensions {
+                width: 2,
+                height: 2,
+            },
+        };
+        canvas.perform_raster_action(0, RasterLayerAction::fill_rect(red_rect, colors::red()));
+        canvas.perform_raster_action(
+            0,
+            RasterLayerAction::fill_rect(offscreen_rect, colors::red()),
+        );
+
+        let patches = canvas.render_dirty(&view);
+        assert!(canvas.take_dirty_rects().is_empty());
+        assert_eq!(patches.len(), 1);
+
+        let (view_rect, patch) = &patches[0];
+        assert_eq!(
+            *view_rect,
+            ViewRect {
+                top_left: (6, 7).into(),
+                dimensions: red_rect.dimensions,
+            }
+        );
+        assert!(patch
+            .pixels()
+            .iter()
+            .all(|pixel| pixel.is_close(&colors::red(), 2)));
+        assert_eq!(
+            canvas.render(&view).subsource_at(*view_rect).as_ref(),
+            Some(patch)
+        );
+
+        canvas.perform_raster_action(0, RasterLayerAction::fill_rect(red_rect, colors::blue()));
+        assert_eq!(canvas.take_dirty_rects(), [red_rect]);
+    }
+
+    #[test]
+    fn copying_and_pasting_between_layers() {
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(8).into());
+        canvas.add_layer(RasterLayer::new(8).into());
+
+        let red_rect = CanvasRect {
+            top_left: (4, 4).into(),
+            dimensions: Dimensions {
+                width: 8,
+                height: 8,
+            },
+        };
+        canvas.perform_raster_action(0, RasterLayerAction::fill_rect(red_rect, colors::red()));
+
+        let copy_rect = CanvasRect {
+            top_left: (6, 6).into(),
+            dimensions: Dimensions {
+                width: 8,
+                height: 8,
+            },
+        };
+        let copied = canvas.copy_rect(0, copy_rect).unwrap();
+        assert_eq!(copied.pixels()[0], colors::red());
+        assert_eq!(copied.pixels()[7].as_rgba().3, 0);
+
+        let whole_rect = CanvasRect::at_origin(Dimensions {
+            width: 32,
+            height: 32,
+        });
+        canvas.perform_raster_action(1, RasterLayerAction::fill_rect(whole_rect, colors::blue()));
+
+        let pasted_rect = canvas.paste(1, (20, 20).into(), &copied, CopyMode::Blit);
+        assert_eq!(pasted_rect.map(|rect| rect.top_left), Some((20, 20).into()));
+
+        let pasted_layer = canvas.layers[1].rasterize_canvas_rect_shared(whole_rect);
+        assert_eq!(pasted_layer.pixels()[20 * 32 + 20], colors::red());
+        assert_eq!(pasted_layer.pixels()[20 * 32 + 27].as_rgba().3, 0);
+        assert_eq!(pasted_layer.pixels()[19 * 32 + 20], colors::blue());
+
+        canvas.undo();
+        let restored_layer = canvas.layers[1].rasterize_canvas_rect_shared(whole_rect);
+        assert_eq!(restored_layer.pixels()[20 * 32 + 27], colors::blue());
+    }
+
+    #[test]
+    fn fallible_entry_points_report_bad_input() {
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(8).into());
+        canvas.add_layer(TextLayer::new().into());
+
+        let rect = |x, width| CanvasRect {
+            top_left: (x, 0).into(),
+            dimensions: Dimensions { width, height: 4 },
+        };
+        let fill = |canvas_rect| RasterLayerAction::fill_rect(canvas_rect, colors::red());
+
+        assert_eq!(
+            canvas
+                .try_perform_raster_action(0, fill(rect(0, 4)))
+                .unwrap(),
+            Some(rect(0, 4))
+        );
+        assert!(matches!(
+            canvas.try_perform_raster_action(0, fill(rect(i32::MAX - 2, 4))),
+            Err(MboardError::Rasterize(RasterizeError::Geometry(
+                GeometryError::CoordinateOverflow
+            )))
+        ));
+        assert!(matches!(
+            canvas.try_perform_raster_action(1, fill(rect(0, 4))),
+            Err(MboardError::Action(ActionError::NotARasterLayer(1)))
+        ));
+        assert!(matches!(
+            canvas.try_perform_raster_action(2, fill(rect(0, 4))),
+            Err(MboardError::Action(ActionError::NoSuchLayer(2)))
+        ));
+
+        let raster = canvas.try_rasterize_canvas_rect(rect(0, 4)).unwrap();
+        let expected = canvas.rasterize_canvas_rect(rect(0, 4));
+        assert_raster_eq!(raster, expected);
+        assert!(matches!(
+            canvas.try_rasterize_canvas_rect(rect(0, 0)),
+            Err(MboardError::Geometry(
+                GeometryError::DegenerateDimensions { .. }
+            ))
+        ));
+        assert!(BoxRasterChunk::try_new(usize::MAX, 2).is_err());
+        assert_eq!(
+            canvas.layers[1].try_rasterize_canvas_rect(rect(0, 0)),
+            Err(RasterizeError::Geometry(
+                GeometryError::DegenerateDimensions {
+                    width: 0,
+                    height: 4
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn text_layers_are_rendered_at_the_view_scale() {
+        let font = Font::from_bytes(test_font_bytes()).unwrap();
+        let mut text_layer = TextLayer::new();
+        text_layer.add_text(TextObject::new(
+            "AI",
+            font,
+            25.0,
+            (2, 3).into(),
+            colors::red(),
+        ));
+
+        let mut canvas = Canvas::default();
+        canvas.set_background(CanvasBackground::Transparent);
+        canvas.add_layer(RasterLayer::new(16).into());
+        canvas.add_layer(text_layer.clone().into());
+        canvas.perform_raster_action(
+            0,
+            RasterLayerAction::fill_rect(
+                CanvasRect {
+                    top_left: (32, 24).into(),
+                    dimensions: Dimensions {
+                        width: 8,
+                        height: 8,
+                    },
+                },
+                colors::blue(),
+            ),
+        );
+
+        let view = CanvasView {
+            top_left: (0, 0).into(),
+            canvas_dimensions: Dimensions {
+                width: 40,
+                height: 32,
+            },
+            view_dimensions: Dimensions {
+                width: 160,
+                height: 128,
+            },
+            rotation: ViewRotation::NONE,
+        };
+        // Cached views are rendered at the scale of their zoom bucket, so
+        // render the view uncached to compare it with the text layer
+        canvas.set_max_prerender_area(view.view_dimensions.area() - 1);
+        let render = canvas.render(&view);
+        let mut expected = text_layer.rasterize(&view);
+        expected.blit(
+            &BoxRasterChunk::new_fill(colors::blue(), 32, 32).as_window(),
+            (128, 96).into(),
+        );
+
+        // Fully transparent pixels may differ in color
+        let visible = |raster: &BoxRasterChunk| -> Vec<_> {
+            raster
+                .pixels()
+                .iter()
+                .map(|pixel| match pixel.as_rgba() {
+                    (_, _, _, 0) => (0, 0, 0, 0),
+                    rgba => rgba,
+                })
+                .collect()
+        };
+        assert_eq!(visible(&render), visible(&expected));
+    }
 }
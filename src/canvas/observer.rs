@@ -0,0 +1,40 @@
+//! Notifying interested parties of changes to the canvas, so they can redraw
+//! changed regions without polling.
+
+use crate::primitives::rect::CanvasRect;
+
+/// Identifies a region observer registered with `Canvas::on_region_changed`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct RegionObserverId(usize);
+
+type RegionObserver = Box<dyn FnMut(&CanvasRect)>;
+
+#[derive(Default)]
+pub(super) struct RegionObservers {
+    observers: Vec<(RegionObserverId, RegionObserver)>,
+    next_id: usize,
+}
+
+impl RegionObservers {
+    pub fn add(&mut self, observer: RegionObserver) -> RegionObserverId {
+        let id = RegionObserverId(self.next_id);
+        self.next_id += 1;
+
+        self.observers.push((id, observer));
+
+        id
+    }
+
+    pub fn remove(&mut self, id: RegionObserverId) -> bool {
+        let observer_count = self.observers.len();
+        self.observers.retain(|(observer_id, _)| *observer_id != id);
+
+        self.observers.len() != observer_count
+    }
+
+    pub fn notify(&mut self, changed_canvas_rect: &CanvasRect) {
+        for (_, observer) in self.observers.iter_mut() {
+            observer(changed_canvas_rect);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! Finding what is drawn at a point of the canvas, for eyedroppers and for
+//! selecting layers by clicking on them.
+
+use crate::{
+    primitives::{dimensions::Dimensions, position::CanvasPosition, rect::CanvasRect},
+    raster::Pixel,
+};
+
+use super::{composite_layer, Canvas, Layer};
+
+/// The canvas rect of the single pixel at `position`.
+fn pixel_rect(position: CanvasPosition) -> CanvasRect {
+    CanvasRect {
+        top_left: position,
+        dimensions: Dimensions {
+            width: 1,
+            height: 1,
+        },
+    }
+}
+
+impl Canvas {
+    /// The color of the pixel at `position` with all layers composited, as it
+    /// is rendered. The cached raster of canvas rects is read if it covers
+    /// the position, otherwise only that pixel of each layer is rasterized.
+    pub fn pixel_at(&self, position: CanvasPosition) -> Pixel {
+        let canvas_rect = pixel_rect(position);
+
+        if let Some(pixel) = self.rect_raster_cache.cached_pixel(&canvas_rect) {
+            return pixel;
+        }
+
+        let mut base = self.background.rasterize_canvas_rect(canvas_rect);
+        for layer in self.layers.iter() {
+            composite_layer(
+                &mut base,
+                layer.rasterize_canvas_rect_shared(canvas_rect),
+                layer,
+                canvas_rect,
+            );
+        }
+
+        base.pixels()[0]
+    }
+
+    /// The topmost layer with a pixel that isn't completely transparent at
+    /// `position`, or `None` if every layer is transparent there.
+    pub fn topmost_layer_at(&self, position: CanvasPosition) -> Option<usize> {
+        let canvas_rect = pixel_rect(position);
+
+        self.layers.iter().rposition(|layer| {
+            layer
+                .rasterize_canvas_rect_shared(canvas_rect)
+                .pixels()
+                .iter()
+                .any(|pixel| pixel.as_rgba().3 > 0)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raster::{pixels::colors, RasterLayer, RasterLayerAction};
+
+    #[test]
+    fn picking_pixels_and_layers() {
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(4).into());
+        canvas.add_layer(RasterLayer::new(4).into());
+
+        let square = |x, y| CanvasRect {
+            top_left: (x, y).into(),
+            dimensions: Dimensions {
+                width: 4,
+                height: 4,
+            },
+        };
+        canvas.perform_raster_action(0, RasterLayerAction::fill_rect(square(0, 0), colors::red()));
+        canvas.perform_raster_action(
+            1,
+            RasterLayerAction::fill_rect(square(2, 2), Pixel::new_rgba(0, 0, 255, 128)),
+        );
+
+        assert!(canvas.pixel_at((1, 1).into()).is_close(&colors::red(), 1));
+        assert_eq!(canvas.pixel_at((10, 10).into()), colors::white());
+        assert_eq!(canvas.topmost_layer_at((1, 1).into()), Some(0));
+        assert_eq!(canvas.topmost_layer_at((3, 3).into()), Some(1));
+        assert_eq!(canvas.topmost_layer_at((10, 10).into()), None);
+
+        let uncached = canvas.pixel_at((3, 3).into());
+        canvas.rasterize_canvas_rect(square(0, 0));
+        assert_eq!(canvas.pixel_at((3, 3).into()), uncached);
+        assert!(uncached.is_close(&Pixel::new_rgb(127, 0, 128), 1));
+    }
+}
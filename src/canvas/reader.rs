@@ -0,0 +1,133 @@
+use std::sync::Arc;
+
+use crate::{primitives::rect::CanvasRect, raster::chunks::BoxRasterChunk};
+
+use super::{
+    composite_layer, rotation::sample_rotated_view, Canvas, CanvasBackground, CanvasView, Layer,
+    LayerImplementation,
+};
+
+/// A read-only snapshot of the layers of a canvas. Readers are cheap to clone
+/// and can be sent to other threads, so a renderer thread can keep rasterizing
+/// a consistent state while the canvas continues to accept actions.
+#[derive(Clone)]
+pub struct CanvasReader {
+    layers: Arc<[LayerImplementation]>,
+    background: CanvasBackground,
+}
+
+impl CanvasReader {
+    /// The number of layers in the snapshot.
+    pub fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Rasterizes a canvas rect of the snapshot with all layers composited.
+    pub fn rasterize_canvas_rect(&self, canvas_rect: CanvasRect) -> BoxRasterChunk {
+        let mut base = self.background.rasterize_canvas_rect(canvas_rect);
+
+        for layer in self.layers.iter() {
+            composite_layer(
+                &mut base,
+                layer.rasterize_canvas_rect_shared(canvas_rect),
+                layer,
+                canvas_rect,
+            );
+        }
+
+        base
+    }
+
+    /// Renders a view of the snapshot with all layers composited.
+    pub fn render(&self, view: &CanvasView) -> BoxRasterChunk {
+        let bounding_view = view.bounding_view();
+        let mut raster = self.rasterize_canvas_rect(bounding_view.canvas_rect());
+
+        raster.nn_scale(bounding_view.view_dimensions);
+
+        if view.rotation.is_none() {
+            raster
+        } else {
+            let mut rotated_raster =
+                BoxRasterChunk::new(view.view_dimensions.width, view.view_dimensions.height);
+            sample_rotated_view(view, &raster, &mut rotated_raster);
+
+            rotated_raster
+        }
+    }
+}
+
+impl Canvas {
+    /// Takes a read-only snapshot of the current state of the canvas. Edits made
+    /// to the canvas after this are not visible to the reader. The chunks of
+    /// raster layers are shared with the canvas until they are drawn on, so
+    /// taking a reader doesn't copy their pixels.
+    pub fn reader(&self) -> CanvasReader {
+        CanvasReader {
+            layers: self.layers.as_slice().into(),
+            background: self.background,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::CanvasReader;
+    use crate::{
+        assert_raster_eq,
+        canvas::{Canvas, CanvasView},
+        primitives::{dimensions::Dimensions, rect::CanvasRect},
+        raster::{pixels::colors, RasterLayer, RasterLayerAction},
+    };
+
+    fn fill_action(top_left: (i32, i32)) -> RasterLayerAction {
+        RasterLayerAction::fill_rect(
+            CanvasRect {
+                top_left: top_left.into(),
+                dimensions: Dimensions {
+                    width: 8,
+                    height: 8,
+                },
+            },
+            colors::red(),
+        )
+    }
+
+    #[test]
+    fn reader_is_unaffected_by_later_edits() {
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(16).into());
+        canvas.perform_raster_action(0, fill_action((0, 0)));
+
+        let view = CanvasView::new(32, 32);
+        let reader = canvas.reader();
+        let expected = canvas.render(&view);
+
+        canvas.perform_raster_action(0, fill_action((16, 16)));
+
+        let rendered = reader.render(&view);
+        assert_raster_eq!(rendered, expected);
+    }
+
+    #[test]
+    fn reader_renders_on_another_thread() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<CanvasReader>();
+
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(16).into());
+        canvas.perform_raster_action(0, fill_action((4, 4)));
+
+        let view = CanvasView::new(24, 24);
+        let expected = canvas.render(&view);
+
+        let reader = canvas.reader();
+        let rendered = thread::spawn(move || reader.render(&view))
+            .join()
+            .expect("render thread should not panic");
+
+        assert_raster_eq!(rendered, expected);
+    }
+}
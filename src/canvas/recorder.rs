@@ -0,0 +1,198 @@
+//! Recording what the raster caches of a canvas do, so that rendering
+//! artifacts caused by stale or misplaced cached rasters can be reconstructed
+//! from the events leading up to them.
+//!
+//! Recording is only compiled in with the `cache-debug` feature. Each cache
+//! keeps its most recent events in a ring buffer, and `Canvas::cache_events`
+//! interleaves the events of all of them in the order they happened.
+
+use std::{
+    collections::VecDeque,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::primitives::rect::CanvasRect;
+
+use super::Canvas;
+
+/// How many events each cache keeps by default.
+pub const DEFAULT_CACHE_EVENT_CAPACITY: usize = 256;
+
+/// Orders the events of every recorder, so those of different caches can be
+/// interleaved.
+static NEXT_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// The cache an event happened in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CacheKind {
+    /// The cache of rasters of views, scaled to their zoom.
+    View,
+    /// The cache of rasters of canvas rects at their full size.
+    CanvasRect,
+}
+
+/// Something a raster cache did, along with the canvas rect it was done to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CacheEvent {
+    /// A request was served from the cached raster.
+    Hit(CanvasRect),
+    /// A request wasn't within the cached raster, so it was rendered.
+    Miss(CanvasRect),
+    /// A raster of the canvas rect was rendered and cached, such as the
+    /// surroundings of a missed request or a raster rendered on another
+    /// thread.
+    Prerender(CanvasRect),
+    /// The part of the cached raster within the canvas rect was rendered
+    /// again after it changed.
+    PartialRerender(CanvasRect),
+    /// The cached raster of the canvas rect was dropped.
+    Eviction(CanvasRect),
+}
+
+/// An event along with when it happened relative to others.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CacheEventRecord {
+    /// Increases with each event recorded by any cache.
+    pub sequence: u64,
+    pub cache: CacheKind,
+    pub event: CacheEvent,
+}
+
+/// The most recent events of a cache, dropping the oldest once `capacity`
+/// are kept.
+#[derive(Debug, Clone)]
+pub(super) struct CacheRecorder {
+    cache: CacheKind,
+    events: VecDeque<CacheEventRecord>,
+    capacity: usize,
+}
+
+impl CacheRecorder {
+    pub(super) fn new(cache: CacheKind) -> CacheRecorder {
+        CacheRecorder {
+            cache,
+            events: VecDeque::new(),
+            capacity: DEFAULT_CACHE_EVENT_CAPACITY,
+        }
+    }
+
+    pub(super) fn record(&mut self, event: CacheEvent) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+
+        self.events.push_back(CacheEventRecord {
+            sequence: NEXT_SEQUENCE.fetch_add(1, Ordering::Relaxed),
+            cache: self.cache,
+            event,
+        });
+    }
+
+    pub(super) fn events(&self) -> impl Iterator<Item = &CacheEventRecord> {
+        self.events.iter()
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    /// Keeps at most `capacity` events, dropping the oldest that don't fit.
+    pub(super) fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+
+        while self.events.len() > capacity {
+            self.events.pop_front();
+        }
+    }
+}
+
+impl Canvas {
+    /// The events recorded by the raster caches of the canvas, oldest first.
+    pub fn cache_events(&self) -> Vec<CacheEventRecord> {
+        let mut events: Vec<CacheEventRecord> = self
+            .view_raster_cache
+            .recorder()
+            .events()
+            .chain(self.rect_raster_cache.recorder().events())
+            .copied()
+            .collect();
+        events.sort_by_key(|record| record.sequence);
+
+        events
+    }
+
+    pub fn clear_cache_events(&mut self) {
+        self.view_raster_cache.recorder_mut().clear();
+        self.rect_raster_cache.recorder_mut().clear();
+    }
+
+    /// Limits how many events each raster cache keeps, dropping the oldest
+    /// that no longer fit.
+    pub fn set_cache_event_capacity(&mut self, capacity: usize) {
+        self.view_raster_cache.recorder_mut().set_capacity(capacity);
+        self.rect_raster_cache.recorder_mut().set_capacity(capacity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        canvas::CanvasView,
+        primitives::dimensions::Dimensions,
+        raster::{pixels::colors, RasterLayer, RasterLayerAction},
+    };
+
+    #[test]
+    fn cache_events_are_recorded_in_order() {
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(16).into());
+
+        let view = CanvasView::new(8, 8);
+        canvas.render(&view);
+        canvas.render(&view);
+
+        let changed_rect = CanvasRect::at_origin(Dimensions {
+            width: 4,
+            height: 4,
+        });
+        canvas.perform_raster_action(0, RasterLayerAction::fill_rect(changed_rect, colors::red()));
+        canvas.render(&view);
+
+        let view_events: Vec<CacheEvent> = canvas
+            .cache_events()
+            .into_iter()
+            .filter(|record| record.cache == CacheKind::View)
+            .map(|record| record.event)
+            .collect();
+        assert!(matches!(
+            view_events.as_slice(),
+            [
+                CacheEvent::Miss(_),
+                CacheEvent::Prerender(_),
+                CacheEvent::Hit(_),
+                CacheEvent::PartialRerender(rerendered_rect),
+                CacheEvent::Hit(_),
+            ] if *rerendered_rect == changed_rect
+        ));
+
+        let sequences: Vec<u64> = canvas
+            .cache_events()
+            .iter()
+            .map(|record| record.sequence)
+            .collect();
+        assert!(sequences.windows(2).all(|pair| pair[0] < pair[1]));
+
+        canvas.set_cache_event_capacity(2);
+        assert_eq!(canvas.cache_events().len(), 2);
+        canvas.clear_cache_events();
+        assert!(canvas.cache_events().is_empty());
+    }
+}
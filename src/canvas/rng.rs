@@ -0,0 +1,61 @@
+//! A small deterministic random number generator, so that randomized actions
+//! can be replayed exactly from a seed.
+
+/// A xorshift64* generator. It is not suitable for anything security related.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CanvasRng {
+    state: u64,
+}
+
+impl CanvasRng {
+    pub fn new(seed: u64) -> CanvasRng {
+        // A zero state would only ever produce zeroes
+        CanvasRng {
+            state: seed ^ 0x9E37_79B9_7F4A_7C15,
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// A uniformly distributed float in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+impl Default for CanvasRng {
+    fn default() -> Self {
+        CanvasRng::new(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut rng_a = CanvasRng::new(42);
+        let mut rng_b = CanvasRng::new(42);
+        let mut rng_c = CanvasRng::new(43);
+
+        let sequence_a: Vec<u64> = (0..8).map(|_| rng_a.next_u64()).collect();
+        let sequence_b: Vec<u64> = (0..8).map(|_| rng_b.next_u64()).collect();
+        let sequence_c: Vec<u64> = (0..8).map(|_| rng_c.next_u64()).collect();
+
+        assert_eq!(sequence_a, sequence_b);
+        assert_ne!(sequence_a, sequence_c);
+
+        for _ in 0..100 {
+            let f = rng_a.next_f32();
+            assert!((0.0..1.0).contains(&f));
+        }
+    }
+}
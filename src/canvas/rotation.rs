@@ -0,0 +1,87 @@
+//! Rotation of views, so the canvas can be turned to a comfortable angle while
+//! drawing without changing its contents.
+
+use std::ops::{Deref, DerefMut};
+
+use crate::{
+    primitives::transform::Affine,
+    raster::{chunks::raster_chunk::RasterChunk, Pixel},
+};
+
+use super::CanvasView;
+
+/// How far a view turns the canvas clockwise, in whole degrees from 0 to 359.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ViewRotation(u16);
+
+impl ViewRotation {
+    pub const NONE: ViewRotation = ViewRotation(0);
+    pub const QUARTER_TURN: ViewRotation = ViewRotation(90);
+    pub const HALF_TURN: ViewRotation = ViewRotation(180);
+    pub const THREE_QUARTER_TURN: ViewRotation = ViewRotation(270);
+
+    /// A rotation of `degrees` clockwise, wrapped to a single turn. Negative
+    /// degrees turn counterclockwise.
+    pub fn from_degrees(degrees: i32) -> ViewRotation {
+        ViewRotation(degrees.rem_euclid(360) as u16)
+    }
+
+    pub fn degrees(&self) -> u16 {
+        self.0
+    }
+
+    pub fn is_none(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// This rotation followed by `other`.
+    pub fn then(&self, other: ViewRotation) -> ViewRotation {
+        ViewRotation::from_degrees(self.0 as i32 + other.0 as i32)
+    }
+
+    /// The rotation turning back by as much as this one turns.
+    pub fn reversed(&self) -> ViewRotation {
+        ViewRotation::from_degrees(-(self.0 as i32))
+    }
+
+    /// The transform turning points clockwise about the origin. Quarter turns
+    /// are exact, so they map pixels onto pixels.
+    pub fn transform(&self) -> Affine {
+        let (sin, cos) = match self.0 {
+            0 => (0.0, 1.0),
+            90 => (1.0, 0.0),
+            180 => (0.0, -1.0),
+            270 => (-1.0, 0.0),
+            degrees => (degrees as f32).to_radians().sin_cos(),
+        };
+
+        Affine {
+            a: cos,
+            b: sin,
+            c: -sin,
+            d: cos,
+            ..Affine::IDENTITY
+        }
+    }
+}
+
+/// Samples the render of the bounding view of a rotated view into a raster of
+/// the view, turning it by the rotation of the view.
+pub(super) fn sample_rotated_view<T, D>(
+    view: &CanvasView,
+    bounding_render: &RasterChunk<T>,
+    destination: &mut RasterChunk<D>,
+) where
+    T: Deref<Target = [Pixel]>,
+    D: DerefMut<Target = [Pixel]>,
+{
+    if let Some(canvas_to_bounding_view) = view.bounding_view().canvas_to_view_transform() {
+        bounding_render.sample_nearest_into(
+            destination,
+            &view
+                .view_to_canvas_transform()
+                .then(&canvas_to_bounding_view),
+        );
+    }
+}
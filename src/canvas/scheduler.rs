@@ -0,0 +1,156 @@
+//! Deciding how much rendering work to do on each animation frame.
+//!
+//! Embedders report canvas rects that need redrawing and drive a
+//! `FrameScheduler` with the timestamps of their frame callbacks, such as
+//! `requestAnimationFrame`. Decisions are based on elapsed time rather than
+//! frame counts, so the policy is the same at any display refresh rate.
+
+use crate::primitives::rect::CanvasRect;
+
+/// The rendering work that should be done for a frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FrameWork {
+    /// Nothing needs to be drawn.
+    Skip,
+    /// Only the given canvas rect needs to be redrawn.
+    Delta(CanvasRect),
+    /// Nothing has changed for a while, so the whole view should be redrawn
+    /// at full quality to refine cheaper renders made while it was changing.
+    Refine,
+}
+
+/// Tracks dirty canvas rects between frames and decides the work for each frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameScheduler {
+    pending: Option<CanvasRect>,
+    min_frame_interval: f64,
+    settle_delay: f64,
+    last_render: Option<f64>,
+    needs_refinement: bool,
+}
+
+impl Default for FrameScheduler {
+    fn default() -> Self {
+        FrameScheduler {
+            pending: None,
+            min_frame_interval: 0.0,
+            settle_delay: 150.0,
+            last_render: None,
+            needs_refinement: false,
+        }
+    }
+}
+
+impl FrameScheduler {
+    /// Creates a scheduler that renders on every frame with changes and
+    /// refines after 150 milliseconds without changes.
+    pub fn new() -> FrameScheduler {
+        FrameScheduler::default()
+    }
+
+    /// Limits delta renders to at most `frames_per_second`, merging changes
+    /// made in between into a single render.
+    pub fn with_max_frame_rate(self, frames_per_second: f64) -> FrameScheduler {
+        FrameScheduler {
+            min_frame_interval: if frames_per_second > 0.0 {
+                1000.0 / frames_per_second
+            } else {
+                0.0
+            },
+            ..self
+        }
+    }
+
+    /// Sets how long there must be no changes before a refinement is scheduled.
+    pub fn with_settle_delay(self, settle_delay_ms: f64) -> FrameScheduler {
+        FrameScheduler {
+            settle_delay: settle_delay_ms.max(0.0),
+            ..self
+        }
+    }
+
+    /// Records that a canvas rect needs to be redrawn.
+    pub fn mark_dirty(&mut self, canvas_rect: CanvasRect) {
+        self.pending = Some(match self.pending {
+            Some(pending) => pending.spanning_rect(&canvas_rect),
+            None => canvas_rect,
+        });
+    }
+
+    /// Requests a full quality render once changes have settled, for example
+    /// after the view has been resized.
+    pub fn request_refinement(&mut self) {
+        self.needs_refinement = true;
+    }
+
+    /// Whether there is work that a future tick will ask for.
+    pub fn has_pending_work(&self) -> bool {
+        self.pending.is_some() || self.needs_refinement
+    }
+
+    /// Decides the work for a frame, given the frame's timestamp in milliseconds.
+    pub fn tick(&mut self, timestamp_ms: f64) -> FrameWork {
+        let elapsed = self
+            .last_render
+            .map(|last_render| timestamp_ms - last_render)
+            // A clock that went backwards should not stall rendering
+            .filter(|elapsed| *elapsed >= 0.0);
+
+        if let Some(pending) = self.pending {
+            if elapsed.is_some_and(|elapsed| elapsed < self.min_frame_interval) {
+                return FrameWork::Skip;
+            }
+
+            self.pending = None;
+            self.last_render = Some(timestamp_ms);
+            self.needs_refinement = true;
+
+            return FrameWork::Delta(pending);
+        }
+
+        if self.needs_refinement && elapsed.is_none_or(|elapsed| elapsed >= self.settle_delay) {
+            self.last_render = Some(timestamp_ms);
+            self.needs_refinement = false;
+
+            return FrameWork::Refine;
+        }
+
+        FrameWork::Skip
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::dimensions::Dimensions;
+
+    fn rect(x: i32, y: i32, width: usize, height: usize) -> CanvasRect {
+        CanvasRect {
+            top_left: (x, y).into(),
+            dimensions: Dimensions { width, height },
+        }
+    }
+
+    #[test]
+    fn delta_then_refine_after_settling() {
+        let mut scheduler = FrameScheduler::new()
+            .with_max_frame_rate(30.0)
+            .with_settle_delay(100.0);
+
+        assert_eq!(scheduler.tick(0.0), FrameWork::Skip);
+
+        scheduler.mark_dirty(rect(0, 0, 10, 10));
+        assert_eq!(scheduler.tick(5.0), FrameWork::Delta(rect(0, 0, 10, 10)));
+
+        // Changes within the frame interval are merged into the next delta
+        scheduler.mark_dirty(rect(20, 20, 5, 5));
+        scheduler.mark_dirty(rect(-5, 0, 5, 5));
+        assert_eq!(scheduler.tick(20.0), FrameWork::Skip);
+        assert_eq!(scheduler.tick(40.0), FrameWork::Delta(rect(-5, 0, 30, 25)));
+
+        assert_eq!(scheduler.tick(100.0), FrameWork::Skip);
+        assert_eq!(scheduler.tick(140.0), FrameWork::Refine);
+        assert_eq!(scheduler.tick(500.0), FrameWork::Skip);
+        assert!(!scheduler.has_pending_work());
+    }
+}
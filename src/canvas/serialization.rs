@@ -0,0 +1,137 @@
+//! Serde support for canvases, so that documents can be saved and restored.
+//! Only the content of a canvas is serialized; caches and history start empty
+//! when a canvas is deserialized.
+
+use std::borrow::Cow;
+
+use serde::{ser, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{Canvas, CanvasRng, Guides, LayerImplementation};
+use crate::{
+    primitives::dimensions::Dimensions,
+    raster::{chunks::PixelFormat, RasterLayer},
+};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Layer")]
+enum SerializedLayer<'a> {
+    Raster(Cow<'a, RasterLayer>),
+}
+
+/// Raster layers are serialized with their pixels. Text layers hold fonts and
+/// vector layers hold arbitrary polygons, neither of which can be serialized,
+/// so serializing them is an error.
+impl Serialize for LayerImplementation {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            LayerImplementation::RasterLayer(raster_layer) => {
+                SerializedLayer::Raster(Cow::Borrowed(raster_layer)).serialize(serializer)
+            }
+            LayerImplementation::TextLayer(_) => {
+                Err(ser::Error::custom("text layers can't be serialized"))
+            }
+            LayerImplementation::VectorLayer(_) => {
+                Err(ser::Error::custom("vector layers can't be serialized"))
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for LayerImplementation {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match SerializedLayer::deserialize(deserializer)? {
+            SerializedLayer::Raster(raster_layer) => Ok(raster_layer.into_owned().into()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Canvas")]
+struct SerializedCanvas<'a> {
+    layers: Cow<'a, [LayerImplementation]>,
+    guides: Cow<'a, Guides>,
+    rng: Cow<'a, CanvasRng>,
+    pixel_format: PixelFormat,
+    document_dimensions: Option<Dimensions>,
+}
+
+impl Serialize for Canvas {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializedCanvas {
+            layers: Cow::Borrowed(&self.layers),
+            guides: Cow::Borrowed(&self.guides),
+            rng: Cow::Borrowed(&self.rng),
+            pixel_format: self.pixel_format,
+            document_dimensions: self.document_dimensions,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Canvas {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let serialized_canvas = SerializedCanvas::deserialize(deserializer)?;
+
+        Ok(Canvas {
+            layers: serialized_canvas.layers.into_owned(),
+            guides: serialized_canvas.guides.into_owned(),
+            rng: serialized_canvas.rng.into_owned(),
+            pixel_format: serialized_canvas.pixel_format,
+            document_dimensions: serialized_canvas.document_dimensions,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assert_raster_eq,
+        canvas::CanvasView,
+        primitives::rect::CanvasRect,
+        raster::{pixels::colors, RasterLayerAction},
+        text::TextLayer,
+    };
+
+    #[test]
+    fn canvas_round_trips_through_serde() {
+        let mut canvas = Canvas::with_pixel_format(PixelFormat::Rgb565A8);
+        canvas.add_layer(RasterLayer::new(16).into());
+        canvas.perform_raster_action(
+            0,
+            RasterLayerAction::fill_rect(
+                CanvasRect {
+                    top_left: (40, 8).into(),
+                    dimensions: Dimensions {
+                        width: 10,
+                        height: 4,
+                    },
+                },
+                colors::blue(),
+            ),
+        );
+
+        let json = serde_json::to_string(&canvas).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        // Only the chunks that were drawn on are saved.
+        assert_eq!(
+            value["layers"][0]["Raster"]["chunks"]
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+
+        let mut restored_canvas: Canvas = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored_canvas.pixel_format(), PixelFormat::Rgb565A8);
+
+        let view = CanvasView::new(64, 32);
+        let expected_render = canvas.render(&view);
+        let restored_render = restored_canvas.render(&view);
+        assert_raster_eq!(restored_render, expected_render);
+
+        canvas.add_layer(TextLayer::new().into());
+        assert!(serde_json::to_string(&canvas).is_err());
+    }
+}
@@ -0,0 +1,226 @@
+//! Checkpoints of the whole document of a canvas, for reverting to a saved
+//! state or recovering from a crash.
+//!
+//! The chunks of raster layers are kept compressed behind shared pointers,
+//! and a canvas remembers the chunks of the snapshots it has taken that are
+//! still alive. Chunks that haven't changed since an earlier snapshot share
+//! its allocation, so taking snapshots regularly only allocates for the
+//! chunks that were edited in between.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError, Weak},
+};
+
+use crate::{
+    primitives::{dimensions::Dimensions, position::ChunkPosition},
+    raster::{
+        chunks::{BoxRasterChunk, ChunkStorage},
+        RasterLayer,
+    },
+};
+
+use super::{Canvas, Guides, LayerImplementation};
+
+/// The chunks of the snapshots taken of a canvas that are still alive, keyed
+/// by the hash of their content.
+#[derive(Default)]
+pub(super) struct SnapshotChunks {
+    chunks: Mutex<HashMap<u64, Weak<ChunkStorage>>>,
+}
+
+impl SnapshotChunks {
+    /// The stored chunk with the same content as `chunk`, or the chunk
+    /// compressed into a new allocation if no snapshot still has one.
+    fn share(&self, chunk: &BoxRasterChunk) -> Arc<ChunkStorage> {
+        let content_hash = chunk.content_hash();
+        // A panic while the lock is held can't leave the chunks inconsistent
+        let mut chunks = self.chunks.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let shared_chunk = chunks
+            .get(&content_hash)
+            .and_then(Weak::upgrade)
+            .filter(|storage| storage.matches(chunk));
+
+        shared_chunk.unwrap_or_else(|| {
+            let storage = Arc::new(ChunkStorage::compress(chunk.clone()));
+            chunks.insert(content_hash, Arc::downgrade(&storage));
+
+            storage
+        })
+    }
+
+    /// Forgets the chunks of snapshots that have been dropped.
+    fn prune(&self) {
+        self.chunks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|_, storage| storage.strong_count() > 0);
+    }
+}
+
+#[derive(Clone)]
+enum LayerSnapshot {
+    Raster {
+        /// The layer's settings, without any chunks.
+        layer: RasterLayer,
+        chunks: Vec<(ChunkPosition, Arc<ChunkStorage>)>,
+    },
+    /// Text and vector layers, which are small enough to copy.
+    Copied(LayerImplementation),
+}
+
+impl LayerSnapshot {
+    fn restore(&self) -> LayerImplementation {
+        match self {
+            LayerSnapshot::Raster { layer, chunks } => {
+                let mut raster_layer = layer.without_chunks();
+
+                for (chunk_position, storage) in chunks {
+                    raster_layer.replace_chunk_storage(*chunk_position, storage.clone());
+                }
+
+                raster_layer.into()
+            }
+            LayerSnapshot::Copied(layer) => layer.clone(),
+        }
+    }
+}
+
+/// The layers, document size and guides of a canvas at the time it was
+/// taken, to be restored with `Canvas::restore`. Snapshots are cheap to clone
+/// and can be sent to other threads, such as to be saved in the background.
+#[derive(Clone)]
+pub struct CanvasSnapshot {
+    layers: Vec<LayerSnapshot>,
+    document_dimensions: Option<Dimensions>,
+    guides: Guides,
+}
+
+impl CanvasSnapshot {
+    pub fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub fn document_dimensions(&self) -> Option<Dimensions> {
+        self.document_dimensions
+    }
+}
+
+impl Canvas {
+    /// Takes a snapshot of the document, sharing the chunks that haven't
+    /// changed since earlier snapshots that are still alive.
+    pub fn snapshot(&self) -> CanvasSnapshot {
+        self.snapshot_chunks.prune();
+
+        let layers = self
+            .layers
+            .iter()
+            .map(|layer| match layer {
+                LayerImplementation::RasterLayer(raster_layer) => LayerSnapshot::Raster {
+                    layer: raster_layer.without_chunks(),
+                    chunks: raster_layer
+                        .allocated_chunk_positions()
+                        .into_iter()
+                        .filter_map(|chunk_position| {
+                            let chunk = raster_layer.chunk(chunk_position)?;
+
+                            Some((chunk_position, self.snapshot_chunks.share(&chunk)))
+                        })
+                        .collect(),
+                },
+                layer => LayerSnapshot::Copied(layer.clone()),
+            })
+            .collect();
+
+        CanvasSnapshot {
+            layers,
+            document_dimensions: self.document_dimensions,
+            guides: self.guides.clone(),
+        }
+    }
+
+    /// Replaces the document with a snapshot of it. Since the layers may have
+    /// changed entirely, the history is cleared and the canvas should be
+    /// redrawn.
+    pub fn restore(&mut self, snapshot: &CanvasSnapshot) {
+        self.layers = snapshot.layers.iter().map(LayerSnapshot::restore).collect();
+        self.document_dimensions = snapshot.document_dimensions;
+        self.guides = snapshot.guides.clone();
+
+        self.history.clear();
+        self.invalidate_caches();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assert_raster_eq,
+        primitives::rect::CanvasRect,
+        raster::{pixels::colors, RasterLayerAction},
+        vector::VectorLayer,
+    };
+
+    fn raster_chunks(snapshot: &CanvasSnapshot) -> &[(ChunkPosition, Arc<ChunkStorage>)] {
+        match &snapshot.layers[0] {
+            LayerSnapshot::Raster { chunks, .. } => chunks,
+            LayerSnapshot::Copied(_) => panic!("the first layer should be a raster layer"),
+        }
+    }
+
+    #[test]
+    fn snapshots_restore_documents_and_share_unchanged_chunks() {
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(4).into());
+        canvas.add_layer(VectorLayer::new().into());
+
+        let document_rect = CanvasRect::at_origin(Dimensions {
+            width: 8,
+            height: 4,
+        });
+        canvas.perform_raster_action(
+            0,
+            RasterLayerAction::fill_rect(document_rect, colors::red()),
+        );
+        canvas.perform_raster_action(
+            0,
+            RasterLayerAction::draw_line((1, 1).into(), (6, 2).into(), 0, colors::blue()),
+        );
+        let saved_raster = canvas.rasterize_canvas_rect(document_rect);
+        let saved = canvas.snapshot();
+
+        canvas.perform_raster_action(
+            0,
+            RasterLayerAction::fill_rect(
+                CanvasRect::at_origin(Dimensions {
+                    width: 4,
+                    height: 4,
+                }),
+                colors::green(),
+            ),
+        );
+        let edited = canvas.snapshot();
+
+        let (saved_chunks, edited_chunks) = (raster_chunks(&saved), raster_chunks(&edited));
+        let shared_chunk_count = saved_chunks
+            .iter()
+            .filter(|(chunk_position, storage)| {
+                edited_chunks
+                    .iter()
+                    .any(|(edited_position, edited_storage)| {
+                        edited_position == chunk_position && Arc::ptr_eq(storage, edited_storage)
+                    })
+            })
+            .count();
+        assert_eq!(saved_chunks.len(), 2);
+        assert_eq!(shared_chunk_count, 1);
+
+        canvas.restore(&saved);
+        assert_eq!(canvas.layer_count(), 2);
+        assert!(!canvas.history().can_undo());
+        let restored_raster = canvas.rasterize_canvas_rect(document_rect);
+        assert_raster_eq!(restored_raster, saved_raster);
+    }
+}
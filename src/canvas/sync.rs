@@ -0,0 +1,206 @@
+//! Synchronization of canvas state by exchanging changed chunks.
+//!
+//! Chunks are compared by content hash, so only chunks that actually differ
+//! between two canvases need to be sent across threads or the network.
+
+use std::collections::HashSet;
+
+use crate::{
+    primitives::{position::ChunkPosition, rect::CanvasRect},
+    raster::{chunks::BoxRasterChunk, RasterLayer},
+};
+
+use super::{Canvas, LayerImplementation};
+
+/// A replacement for the contents of a single chunk in a layer of a canvas.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkPatch {
+    pub layer_num: usize,
+    pub chunk_position: ChunkPosition,
+    /// The new contents of the chunk, `None` if the chunk should be unallocated.
+    pub chunk: Option<BoxRasterChunk>,
+}
+
+fn raster_layer(layers: &[LayerImplementation], layer_num: usize) -> Option<&RasterLayer> {
+    match layers.get(layer_num)? {
+        LayerImplementation::RasterLayer(raster_layer) => Some(raster_layer),
+        _ => None,
+    }
+}
+
+impl Canvas {
+    /// The layers and chunk positions whose contents differ between this canvas
+    /// and another. Chunks allocated in only one of the canvases are included,
+    /// as are all chunks of layers that only exist in one of the canvases.
+    pub fn diff(&self, other: &Canvas) -> Vec<(usize, ChunkPosition)> {
+        let mut differences = vec![];
+        let layer_count = self.layers.len().max(other.layers.len());
+
+        for layer_num in 0..layer_count {
+            let hashes = raster_layer(&self.layers, layer_num)
+                .map(RasterLayer::chunk_hashes)
+                .unwrap_or_default();
+            let other_hashes = raster_layer(&other.layers, layer_num)
+                .map(RasterLayer::chunk_hashes)
+                .unwrap_or_default();
+
+            let chunk_positions: HashSet<&ChunkPosition> =
+                hashes.keys().chain(other_hashes.keys()).collect();
+
+            let mut changed_chunk_positions: Vec<ChunkPosition> = chunk_positions
+                .into_iter()
+                .filter(|chunk_position| {
+                    hashes.get(chunk_position) != other_hashes.get(chunk_position)
+                })
+                .copied()
+                .collect();
+
+            changed_chunk_positions
+                .sort_by_key(|chunk_position| (chunk_position.1, chunk_position.0));
+
+            differences.extend(
+                changed_chunk_positions
+                    .into_iter()
+                    .map(|chunk_position| (layer_num, chunk_position)),
+            );
+        }
+
+        differences
+    }
+
+    /// Creates a patch carrying the current contents of a chunk of this canvas.
+    /// Returns `None` if there is no raster layer at `layer_num`.
+    pub fn create_chunk_patch(
+        &self,
+        layer_num: usize,
+        chunk_position: ChunkPosition,
+    ) -> Option<ChunkPatch> {
+        let raster_layer = raster_layer(&self.layers, layer_num)?;
+
+        Some(ChunkPatch {
+            layer_num,
+            chunk_position,
+            chunk: raster_layer
+                .chunk(chunk_position)
+                .map(|chunk| chunk.into_owned()),
+        })
+    }
+
+    /// Creates patches for all the differences between this canvas and another,
+    /// which when applied to `other` bring it up to date with this canvas.
+    pub fn create_chunk_patches_for(&self, other: &Canvas) -> Vec<ChunkPatch> {
+        self.diff(other)
+            .into_iter()
+            .filter_map(|(layer_num, chunk_position)| {
+                self.create_chunk_patch(layer_num, chunk_position)
+            })
+            .collect()
+    }
+
+    /// Applies a patch to a chunk of this canvas, returning the canvas rect that has
+    /// been altered by it. Returns `None` if there is no raster layer at the patch's
+    /// layer or the patched chunk does not match the layer's chunk size.
+    pub fn apply_chunk_patch(&mut self, patch: ChunkPatch) -> Option<CanvasRect> {
+        let ChunkPatch {
+            layer_num,
+            chunk_position,
+            chunk,
+        } = patch;
+
+        let changed_canvas_rect = match self.layers.get_mut(layer_num)? {
+            LayerImplementation::RasterLayer(raster_layer) => {
+                raster_layer.replace_chunk(chunk_position, chunk)?
+            }
+            _ => return None,
+        };
+
+        self.rerender_canvas_rect(&changed_canvas_rect);
+
+        Some(changed_canvas_rect)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        assert_raster_eq,
+        canvas::{Canvas, CanvasView},
+        primitives::{dimensions::Dimensions, rect::CanvasRect},
+        raster::{pixels::colors, RasterLayer, RasterLayerAction},
+    };
+
+    fn canvas_with_layers(num_layers: usize) -> Canvas {
+        let mut canvas = Canvas::default();
+
+        for _ in 0..num_layers {
+            canvas.add_layer(RasterLayer::new(16).into());
+        }
+
+        canvas
+    }
+
+    #[test]
+    fn identical_canvases_have_no_diff() {
+        let mut canvas_a = canvas_with_layers(1);
+        let mut canvas_b = canvas_with_layers(1);
+
+        let action = RasterLayerAction::fill_rect(
+            CanvasRect {
+                top_left: (-4, -4).into(),
+                dimensions: Dimensions {
+                    width: 8,
+                    height: 8,
+                },
+            },
+            colors::red(),
+        );
+
+        canvas_a.perform_raster_action(0, action.clone());
+        canvas_b.perform_raster_action(0, action);
+
+        assert!(canvas_a.diff(&canvas_b).is_empty());
+    }
+
+    #[test]
+    fn patches_bring_canvases_in_sync() {
+        let mut source = canvas_with_layers(2);
+        let mut mirror = canvas_with_layers(2);
+
+        let view = CanvasView::new(32, 32);
+
+        // Render the mirror first so patches have to update its caches
+        mirror.render(&view);
+
+        source.perform_raster_action(
+            1,
+            RasterLayerAction::fill_rect(
+                CanvasRect {
+                    top_left: (10, 10).into(),
+                    dimensions: Dimensions {
+                        width: 10,
+                        height: 4,
+                    },
+                },
+                colors::blue(),
+            ),
+        );
+
+        let diff = source.diff(&mirror);
+        assert_eq!(
+            diff,
+            vec![(1, (0, 0).into()), (1, (1, 0).into())],
+            "only chunks touched by the fill should differ"
+        );
+
+        for patch in source.create_chunk_patches_for(&mirror) {
+            assert!(mirror.apply_chunk_patch(patch).is_some());
+        }
+
+        assert!(source.diff(&mirror).is_empty());
+
+        let expected = source.render(&view);
+        let rendered = mirror.render(&view);
+
+        assert_raster_eq!(rendered, expected);
+    }
+}
@@ -0,0 +1,128 @@
+//! Checking what an action would do to a canvas before performing it, so
+//! that frontends can warn about expensive actions such as filling a huge
+//! rect.
+
+use thiserror::Error;
+
+use super::{Canvas, LayerImplementation};
+use crate::{primitives::rect::CanvasRect, raster::RasterLayerAction};
+
+/// An error from an action that can't be performed on a canvas.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionError {
+    #[error("there is no layer {0}")]
+    NoSuchLayer(usize),
+    #[error("layer {0} is not a raster layer")]
+    NotARasterLayer(usize),
+}
+
+/// What performing an action would change.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ActionEffect {
+    /// A canvas rect containing every pixel the action can alter, or `None`
+    /// if it can't alter anything.
+    pub dirty_rect: Option<CanvasRect>,
+    /// The number of chunks that would be allocated.
+    pub new_chunks: usize,
+    /// The most memory the action would allocate for new chunks, in bytes.
+    /// Chunks left with a single color by a fill take less.
+    pub memory_delta: usize,
+}
+
+impl Canvas {
+    /// What performing `action` on the raster layer at `layer_num` would
+    /// change, found without performing it. Actions within a selection may
+    /// change less.
+    pub fn validate_action(
+        &self,
+        layer_num: usize,
+        action: &RasterLayerAction,
+    ) -> Result<ActionEffect, ActionError> {
+        let raster_layer = match self.layers.get(layer_num) {
+            Some(LayerImplementation::RasterLayer(raster_layer)) => raster_layer,
+            Some(_) => return Err(ActionError::NotARasterLayer(layer_num)),
+            None => return Err(ActionError::NoSuchLayer(layer_num)),
+        };
+
+        let dirty_rect = action.bounding_rect();
+        // Erasing only ever changes chunks that are already allocated
+        let allocates_chunks = !matches!(
+            action,
+            RasterLayerAction::EraseRect(..) | RasterLayerAction::EraseOval(..)
+        );
+        let new_chunks = match dirty_rect {
+            Some(dirty_rect) if allocates_chunks => {
+                raster_layer.unallocated_chunk_count(dirty_rect)
+            }
+            _ => 0,
+        };
+        let chunk_size = raster_layer.chunk_size();
+        let bytes_per_pixel = raster_layer.pixel_format().bytes_per_pixel();
+
+        Ok(ActionEffect {
+            dirty_rect,
+            new_chunks,
+            memory_delta: new_chunks
+                .saturating_mul(chunk_size * chunk_size)
+                .saturating_mul(bytes_per_pixel),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        primitives::dimensions::Dimensions,
+        raster::{pixels::colors, RasterLayer},
+        text::TextLayer,
+    };
+
+    #[test]
+    fn validating_actions_does_not_perform_them() {
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(10).into());
+        canvas.add_layer(TextLayer::new().into());
+
+        canvas.perform_raster_action(
+            0,
+            RasterLayerAction::fill_rect(
+                CanvasRect::at_origin(Dimensions {
+                    width: 10,
+                    height: 10,
+                }),
+                colors::red(),
+            ),
+        );
+
+        let huge_rect = CanvasRect::at_origin(Dimensions {
+            width: 100_000,
+            height: 100_000,
+        });
+        let fill = RasterLayerAction::fill_rect(huge_rect, colors::blue());
+        let effect = canvas.validate_action(0, &fill).unwrap();
+
+        assert_eq!(effect.dirty_rect, Some(huge_rect));
+        assert_eq!(effect.new_chunks, 10_000 * 10_000 - 1);
+        assert_eq!(effect.memory_delta, effect.new_chunks * 10 * 10 * 4);
+        assert_eq!(
+            canvas
+                .raster_layer(0)
+                .unwrap()
+                .unallocated_chunk_count(huge_rect),
+            effect.new_chunks
+        );
+
+        let erase = RasterLayerAction::erase_rect(huge_rect, 255);
+        assert_eq!(canvas.validate_action(0, &erase).unwrap().new_chunks, 0);
+
+        assert_eq!(
+            canvas.validate_action(1, &fill),
+            Err(ActionError::NotARasterLayer(1))
+        );
+        assert_eq!(
+            canvas.validate_action(2, &fill),
+            Err(ActionError::NoSuchLayer(2))
+        );
+    }
+}
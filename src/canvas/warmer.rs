@@ -0,0 +1,291 @@
+//! Prerendering the surroundings of views on a worker thread.
+//!
+//! A `CacheWarmer` rasterizes from a `CanvasReader` snapshot, so the canvas
+//! stays free to accept actions while it works. Completed tiles are sent back
+//! over a channel and installed into the canvas caches with
+//! `Canvas::receive_warmed_tiles`, which drops tiles rendered from a snapshot
+//! that edits have since made stale.
+
+use std::{
+    sync::mpsc::{self, Receiver, Sender},
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    primitives::rect::CanvasRect,
+    raster::chunks::{BoxRasterChunk, ScalingFilter},
+};
+
+use super::{
+    cache::{view_scaling_filter, CanvasRectRasterCache, CanvasViewRasterCache},
+    Canvas, CanvasReader, CanvasView,
+};
+
+enum WarmerMessage {
+    Snapshot(CanvasReader, u64),
+    WarmView(CanvasView),
+    WarmCanvasRect(CanvasRect),
+}
+
+enum WarmedRegion {
+    View(CanvasView),
+    CanvasRect(CanvasRect),
+}
+
+/// A raster prerendered by a `CacheWarmer`, waiting to be installed into the
+/// caches of the canvas it was rendered from.
+pub struct WarmedTile {
+    region: WarmedRegion,
+    raster: BoxRasterChunk,
+    generation: u64,
+}
+
+impl WarmedTile {
+    /// The canvas rect covered by the tile.
+    pub fn canvas_rect(&self) -> CanvasRect {
+        match &self.region {
+            WarmedRegion::View(view) => view.canvas_rect(),
+            WarmedRegion::CanvasRect(canvas_rect) => *canvas_rect,
+        }
+    }
+}
+
+/// A handle to a worker thread prerendering regions of a canvas. The worker
+/// stops when the handle is dropped.
+pub struct CacheWarmer {
+    messages: Option<Sender<WarmerMessage>>,
+    tiles: Option<Receiver<WarmedTile>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl CacheWarmer {
+    fn send(&self, message: WarmerMessage) {
+        if let Some(messages) = &self.messages {
+            // The worker only stops once the handle is dropped, so this can't fail
+            let _ = messages.send(message);
+        }
+    }
+
+    /// Prerenders the surroundings of `view` like `Canvas::render` would.
+    pub fn warm_view(&self, view: &CanvasView) {
+        self.send(WarmerMessage::WarmView(*view));
+    }
+
+    /// Prerenders the surroundings of `canvas_rect` like
+    /// `Canvas::rasterize_canvas_rect` would.
+    pub fn warm_canvas_rect(&self, canvas_rect: &CanvasRect) {
+        self.send(WarmerMessage::WarmCanvasRect(*canvas_rect));
+    }
+
+    /// Replaces the snapshot the worker renders from with the current state of
+    /// `canvas`, so tiles rendered after this aren't stale.
+    pub fn update_snapshot(&self, canvas: &Canvas) {
+        self.send(WarmerMessage::Snapshot(canvas.reader(), canvas.generation));
+    }
+
+    fn work(
+        messages: Receiver<WarmerMessage>,
+        tiles: Sender<WarmedTile>,
+        mut reader: CanvasReader,
+        mut generation: u64,
+        max_prerender_area: usize,
+        max_cached_raster_bytes: usize,
+        scaling_filter: ScalingFilter,
+    ) {
+        for message in messages {
+            let tile = match message {
+                WarmerMessage::Snapshot(new_reader, new_generation) => {
+                    reader = new_reader;
+                    generation = new_generation;
+                    continue;
+                }
+                WarmerMessage::WarmView(view) => {
+                    let prerendered_view =
+                        CanvasViewRasterCache::prerendered_view(&view, max_prerender_area);
+
+                    let raster = reader.rasterize_canvas_rect(prerendered_view.canvas_rect());
+                    let scaling_filter = view_scaling_filter(
+                        scaling_filter,
+                        raster.dimensions(),
+                        prerendered_view.view_dimensions,
+                    );
+
+                    WarmedTile {
+                        raster: raster.scaled(prerendered_view.view_dimensions, scaling_filter),
+                        region: WarmedRegion::View(prerendered_view),
+                        generation,
+                    }
+                }
+                WarmerMessage::WarmCanvasRect(canvas_rect) => {
+                    let prerendered_canvas_rect = CanvasRectRasterCache::prerendered_canvas_rect(
+                        &canvas_rect,
+                        max_cached_raster_bytes,
+                    );
+
+                    WarmedTile {
+                        raster: reader.rasterize_canvas_rect(prerendered_canvas_rect),
+                        region: WarmedRegion::CanvasRect(prerendered_canvas_rect),
+                        generation,
+                    }
+                }
+            };
+
+            if tiles.send(tile).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+impl Drop for CacheWarmer {
+    fn drop(&mut self) {
+        // Closing the channels ends the worker's loop, and without the tile
+        // receiver it stops after the tile it is rendering rather than working
+        // through every queued request first
+        self.messages = None;
+        self.tiles = None;
+
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Canvas {
+    /// Starts a worker thread that prerenders regions of the canvas requested
+    /// through the returned `CacheWarmer`, rendering from a snapshot of the
+    /// current state of the canvas.
+    pub fn spawn_cache_warmer(&self) -> CacheWarmer {
+        let (message_sender, message_receiver) = mpsc::channel();
+        let (tile_sender, tile_receiver) = mpsc::channel();
+
+        let reader = self.reader();
+        let generation = self.generation;
+        let max_prerender_area = self.max_prerender_area();
+        let max_cached_raster_bytes = self.cache_config().max_cached_raster_bytes;
+        let scaling_filter = self.scaling_filter();
+
+        let worker = thread::spawn(move || {
+            CacheWarmer::work(
+                message_receiver,
+                tile_sender,
+                reader,
+                generation,
+                max_prerender_area,
+                max_cached_raster_bytes,
+                scaling_filter,
+            )
+        });
+
+        CacheWarmer {
+            messages: Some(message_sender),
+            tiles: Some(tile_receiver),
+            worker: Some(worker),
+        }
+    }
+
+    /// Installs the tiles completed by `cache_warmer` into the canvas caches
+    /// without waiting for more, skipping tiles rendered from a stale
+    /// snapshot. Returns the number of tiles installed.
+    pub fn receive_warmed_tiles(&mut self, cache_warmer: &CacheWarmer) -> usize {
+        let mut installed = 0;
+
+        for tile in cache_warmer.tiles.iter().flat_map(Receiver::try_iter) {
+            if tile.generation != self.generation {
+                continue;
+            }
+
+            match tile.region {
+                WarmedRegion::View(view) => self
+                    .view_raster_cache
+                    .insert_prerendered_view(&view, tile.raster),
+                WarmedRegion::CanvasRect(canvas_rect) => self
+                    .rect_raster_cache
+                    .insert_prerendered_canvas_rect(&canvas_rect, tile.raster),
+            }
+
+            installed += 1;
+        }
+
+        installed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{
+        assert_raster_eq,
+        canvas::{Canvas, CanvasView},
+        primitives::{dimensions::Dimensions, rect::CanvasRect},
+        raster::{pixels::colors, RasterLayer, RasterLayerAction},
+    };
+
+    fn fill_action(top_left: (i32, i32)) -> RasterLayerAction {
+        RasterLayerAction::fill_rect(
+            CanvasRect {
+                top_left: top_left.into(),
+                dimensions: Dimensions {
+                    width: 8,
+                    height: 8,
+                },
+            },
+            colors::red(),
+        )
+    }
+
+    #[test]
+    fn warmed_tiles_are_installed_unless_stale() {
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(16).into());
+        canvas.perform_raster_action(0, fill_action((4, 4)));
+
+        let mut view = CanvasView::new(32, 32);
+        view.translate((100, 100).into());
+
+        let cache_warmer = canvas.spawn_cache_warmer();
+
+        // Edits after the snapshot make the tiles rendered from it stale
+        canvas.perform_raster_action(0, fill_action((104, 104)));
+        cache_warmer.warm_view(&view);
+        let stale_tile = cache_warmer.tiles.as_ref().unwrap().recv().unwrap();
+        assert_ne!(stale_tile.generation, canvas.generation);
+
+        cache_warmer.update_snapshot(&canvas);
+        cache_warmer.warm_view(&view);
+
+        let started = Instant::now();
+        while canvas.receive_warmed_tiles(&cache_warmer) == 0 {
+            assert!(started.elapsed() < Duration::from_secs(10));
+            std::thread::yield_now();
+        }
+        assert!(canvas.view_raster_cache.has_cached_raster());
+
+        let expected = canvas.reader().render(&view);
+        let rendered = canvas.render(&view);
+        assert_raster_eq!(rendered, expected);
+    }
+
+    #[test]
+    fn dropping_a_warmer_skips_queued_requests() {
+        let mut canvas = Canvas::default();
+        canvas.add_layer(RasterLayer::new(64).into());
+        canvas.perform_raster_action(0, fill_action((4, 4)));
+        let view = CanvasView::new(256, 256);
+
+        let cache_warmer = canvas.spawn_cache_warmer();
+        let started = Instant::now();
+        cache_warmer.warm_view(&view);
+        cache_warmer.tiles.as_ref().unwrap().recv().unwrap();
+        let tile_duration = started.elapsed();
+
+        for _ in 0..100 {
+            cache_warmer.warm_view(&view);
+        }
+        let started = Instant::now();
+        drop(cache_warmer);
+
+        assert!(started.elapsed() < tile_duration * 20);
+    }
+}
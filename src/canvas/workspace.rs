@@ -0,0 +1,160 @@
+use std::collections::BTreeMap;
+
+use super::{Canvas, ShapeCache};
+use crate::raster::chunks::nn_map::NearestNeighbourMapCache;
+
+/// Identifies a document within a `Workspace`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DocumentId(usize);
+
+/// Several open canvases, of which one is active. Caches that don't depend on
+/// the contents of a canvas are shared between the documents: rasterized brush
+/// shapes are lent to the active document, and all documents use the same maps
+/// to scale views. Each document keeps its own view caches, so switching back
+/// to a document doesn't require it to be rendered from scratch.
+#[derive(Default)]
+pub struct Workspace {
+    documents: BTreeMap<DocumentId, Canvas>,
+    next_id: usize,
+    active: Option<DocumentId>,
+    /// The shared shape cache while no document is active, otherwise the
+    /// cache the active document had before the shared cache was lent to it.
+    shape_cache: ShapeCache,
+    nn_map_cache: NearestNeighbourMapCache,
+}
+
+impl Workspace {
+    pub fn new() -> Workspace {
+        Workspace::default()
+    }
+
+    /// Adds a document to the workspace, making it active if no document is.
+    pub fn add_document(&mut self, mut canvas: Canvas) -> DocumentId {
+        let id = DocumentId(self.next_id);
+        self.next_id += 1;
+
+        canvas.set_nn_map_cache(self.nn_map_cache.clone());
+
+        self.documents.insert(id, canvas);
+        if self.active.is_none() {
+            self.set_active(id);
+        }
+
+        id
+    }
+
+    /// Removes a document from the workspace, returning it if it existed. If
+    /// the document was active, no document is active afterwards.
+    pub fn remove_document(&mut self, id: DocumentId) -> Option<Canvas> {
+        if self.active == Some(id) {
+            self.swap_shared_caches(id);
+            self.active = None;
+        }
+
+        self.documents.remove(&id)
+    }
+
+    /// Makes a document the active one, returning whether it exists.
+    pub fn set_active(&mut self, id: DocumentId) -> bool {
+        if !self.documents.contains_key(&id) {
+            return false;
+        }
+
+        if let Some(active) = self.active {
+            self.swap_shared_caches(active);
+        }
+        self.swap_shared_caches(id);
+        self.active = Some(id);
+
+        true
+    }
+
+    /// Swaps the shape cache held by the workspace with that of a document,
+    /// lending the shared cache to the document or returning it from it.
+    fn swap_shared_caches(&mut self, id: DocumentId) {
+        if let Some(canvas) = self.documents.get_mut(&id) {
+            std::mem::swap(&mut self.shape_cache, &mut canvas.shape_cache);
+        }
+    }
+
+    pub fn active_id(&self) -> Option<DocumentId> {
+        self.active
+    }
+
+    pub fn active(&self) -> Option<&Canvas> {
+        self.active.and_then(|id| self.documents.get(&id))
+    }
+
+    pub fn active_mut(&mut self) -> Option<&mut Canvas> {
+        self.active.and_then(|id| self.documents.get_mut(&id))
+    }
+
+    /// A document of the workspace. Documents that aren't active don't use
+    /// the shared caches, so they are best only drawn on while active.
+    pub fn document(&self, id: DocumentId) -> Option<&Canvas> {
+        self.documents.get(&id)
+    }
+
+    pub fn document_mut(&mut self, id: DocumentId) -> Option<&mut Canvas> {
+        self.documents.get_mut(&id)
+    }
+
+    /// The ids of the documents in the order they were added.
+    pub fn document_ids(&self) -> impl Iterator<Item = DocumentId> + '_ {
+        self.documents.keys().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{canvas::CanvasView, raster::RasterLayer};
+
+    #[test]
+    fn switching_documents_keeps_view_caches() {
+        let mut workspace = Workspace::new();
+
+        let first = workspace.add_document(Canvas::default());
+        let second = workspace.add_document(Canvas::default());
+        assert_eq!(workspace.active_id(), Some(first));
+
+        let view = CanvasView::new(32, 32);
+        let canvas = workspace.active_mut().unwrap();
+        canvas.add_layer(RasterLayer::new(16).into());
+        canvas.render(&view);
+
+        assert!(workspace.set_active(second));
+        workspace.active_mut().unwrap().render(&view);
+
+        assert!(workspace.set_active(first));
+        assert!(workspace
+            .document(first)
+            .unwrap()
+            .view_raster_cache
+            .has_cached_raster());
+        assert!(workspace
+            .document(second)
+            .unwrap()
+            .view_raster_cache
+            .has_cached_raster());
+
+        assert!(workspace
+            .document(first)
+            .unwrap()
+            .nn_map_cache()
+            .shares_maps_with(workspace.document(second).unwrap().nn_map_cache()));
+
+        assert!(workspace.remove_document(first).is_some());
+        assert_eq!(workspace.active_id(), None);
+        assert!(!workspace.set_active(first));
+        assert_eq!(workspace.len(), 1);
+    }
+}
@@ -0,0 +1,272 @@
+//! The binary encoding shared by the save formats of the crate: incremental
+//! saves, journal manifests and layer files. Each format has its own magic
+//! and version, but the parts they have in common, such as the document size,
+//! the table of layers and the chunks of raster layers, are written and read
+//! here so the formats can't drift apart. Values read from saves are checked
+//! before they are used, so damaged or malicious saves are rejected rather
+//! than making the crate allocate without bound.
+//!
+//! Integers are little endian.
+
+use std::io::{self, Read, Write};
+
+use thiserror::Error;
+
+use crate::{
+    primitives::{dimensions::Dimensions, position::ChunkPosition},
+    raster::chunk_size::is_valid_chunk_size,
+};
+
+const RASTER_LAYER: u8 = 0;
+const UNSAVED_LAYER: u8 = 1;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// An error from reading a value of a save.
+#[derive(Error, Debug)]
+pub(crate) enum DecodeError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("the data is not of a supported format")]
+    InvalidFormat,
+}
+
+pub(crate) fn write_u8<W: Write>(writer: &mut W, value: u8) -> io::Result<()> {
+    writer.write_all(&[value])
+}
+
+pub(crate) fn write_u32<W: Write>(writer: &mut W, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub(crate) fn write_u64<W: Write>(writer: &mut W, value: u64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn read_bytes<R: Read, const N: usize>(reader: &mut R) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(bytes)
+}
+
+pub(crate) fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    Ok(read_bytes::<_, 1>(reader)?[0])
+}
+
+pub(crate) fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    Ok(u32::from_le_bytes(read_bytes(reader)?))
+}
+
+pub(crate) fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    Ok(u64::from_le_bytes(read_bytes(reader)?))
+}
+
+/// Hashes `values` with FNV-1a, which unlike the hashers of the standard
+/// library is stable across platforms and builds, so hashes can be saved and
+/// compared between peers. Each value is mixed in whole rather than byte by
+/// byte.
+pub(crate) fn fnv_hash(values: impl IntoIterator<Item = u64>) -> u64 {
+    values.into_iter().fold(FNV_OFFSET_BASIS, |hash, value| {
+        (hash ^ value).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Writes the magic and version that start a save.
+pub(crate) fn write_header<W: Write>(
+    writer: &mut W,
+    magic: &[u8; 4],
+    version: u8,
+) -> io::Result<()> {
+    writer.write_all(magic)?;
+    write_u8(writer, version)
+}
+
+/// Reads the start of a save, failing if it isn't `magic` and `version`.
+pub(crate) fn read_header<R: Read>(
+    reader: &mut R,
+    magic: &[u8; 4],
+    version: u8,
+) -> Result<(), DecodeError> {
+    if &read_bytes::<_, 4>(reader)? != magic || read_u8(reader)? != version {
+        return Err(DecodeError::InvalidFormat);
+    }
+
+    Ok(())
+}
+
+pub(crate) fn write_document_dimensions<W: Write>(
+    writer: &mut W,
+    document_dimensions: Option<Dimensions>,
+) -> io::Result<()> {
+    match document_dimensions {
+        Some(Dimensions { width, height }) => {
+            write_u8(writer, 1)?;
+            write_u32(writer, width as u32)?;
+            write_u32(writer, height as u32)
+        }
+        None => write_u8(writer, 0),
+    }
+}
+
+/// Reads the size of a document, which must pass `Dimensions::try_new`.
+pub(crate) fn read_document_dimensions<R: Read>(
+    reader: &mut R,
+) -> Result<Option<Dimensions>, DecodeError> {
+    match read_u8(reader)? {
+        0 => Ok(None),
+        1 => {
+            let width = read_u32(reader)? as usize;
+            let height = read_u32(reader)? as usize;
+
+            Dimensions::try_new(width, height)
+                .map(Some)
+                .map_err(|_| DecodeError::InvalidFormat)
+        }
+        _ => Err(DecodeError::InvalidFormat),
+    }
+}
+
+pub(crate) fn write_chunk_size<W: Write>(writer: &mut W, chunk_size: usize) -> io::Result<()> {
+    write_u32(writer, chunk_size as u32)
+}
+
+/// Reads the chunk size of a raster layer, which must pass
+/// `is_valid_chunk_size`.
+pub(crate) fn read_chunk_size<R: Read>(reader: &mut R) -> Result<usize, DecodeError> {
+    let chunk_size = read_u32(reader)? as usize;
+
+    if is_valid_chunk_size(chunk_size) {
+        Ok(chunk_size)
+    } else {
+        Err(DecodeError::InvalidFormat)
+    }
+}
+
+/// Writes the layers of a document as the chunk size of each raster layer,
+/// `None` for layers whose content isn't part of the save.
+pub(crate) fn write_layers<W: Write>(writer: &mut W, layers: &[Option<usize>]) -> io::Result<()> {
+    write_u32(writer, layers.len() as u32)?;
+
+    for chunk_size in layers {
+        match chunk_size {
+            Some(chunk_size) => {
+                write_u8(writer, RASTER_LAYER)?;
+                write_chunk_size(writer, *chunk_size)?;
+            }
+            None => write_u8(writer, UNSAVED_LAYER)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the layers written by `write_layers`.
+pub(crate) fn read_layers<R: Read>(reader: &mut R) -> Result<Vec<Option<usize>>, DecodeError> {
+    let layer_count = read_u32(reader)?;
+    let mut layers = Vec::new();
+
+    for _ in 0..layer_count {
+        layers.push(match read_u8(reader)? {
+            RASTER_LAYER => Some(read_chunk_size(reader)?),
+            UNSAVED_LAYER => None,
+            _ => return Err(DecodeError::InvalidFormat),
+        });
+    }
+
+    Ok(layers)
+}
+
+pub(crate) fn write_chunk_position<W: Write>(
+    writer: &mut W,
+    chunk_position: ChunkPosition,
+) -> io::Result<()> {
+    writer.write_all(&chunk_position.0.to_le_bytes())?;
+    writer.write_all(&chunk_position.1.to_le_bytes())
+}
+
+pub(crate) fn read_chunk_position<R: Read>(reader: &mut R) -> io::Result<ChunkPosition> {
+    Ok((
+        i32::from_le_bytes(read_bytes(reader)?),
+        i32::from_le_bytes(read_bytes(reader)?),
+    )
+        .into())
+}
+
+/// Writes bytes prefixed with their length, such as an encoded chunk.
+pub(crate) fn write_blob<W: Write>(writer: &mut W, bytes: &[u8]) -> io::Result<()> {
+    write_u32(writer, bytes.len() as u32)?;
+    writer.write_all(bytes)
+}
+
+/// Reads bytes written by `write_blob`. Only as many bytes as the reader
+/// holds are allocated, whatever length the save claims.
+pub(crate) fn read_blob<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let length = read_u32(reader)? as usize;
+
+    let mut bytes = Vec::new();
+    reader.take(length as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != length {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv_hashes_bytes_like_fnv_1a() {
+        assert_eq!(fnv_hash([]), 0xcbf29ce484222325);
+        assert_eq!(fnv_hash(b"a".map(u64::from)), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv_hash(b"foobar".map(u64::from)), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn layers_and_dimensions_are_checked_when_read() {
+        let mut bytes = Vec::new();
+        write_document_dimensions(
+            &mut bytes,
+            Some(Dimensions {
+                width: 640,
+                height: 480,
+            }),
+        )
+        .unwrap();
+        write_layers(&mut bytes, &[Some(64), None]).unwrap();
+
+        let mut reader = bytes.as_slice();
+        assert_eq!(
+            read_document_dimensions(&mut reader).unwrap(),
+            Some(Dimensions {
+                width: 640,
+                height: 480
+            })
+        );
+        assert_eq!(read_layers(&mut reader).unwrap(), [Some(64), None]);
+
+        let mut bytes = Vec::new();
+        write_layers(&mut bytes, &[Some(1 << 20)]).unwrap();
+        assert!(matches!(
+            read_layers(&mut bytes.as_slice()),
+            Err(DecodeError::InvalidFormat)
+        ));
+
+        let mut bytes = Vec::new();
+        write_document_dimensions(
+            &mut bytes,
+            Some(Dimensions {
+                width: 0,
+                height: 1,
+            }),
+        )
+        .unwrap();
+        assert!(matches!(
+            read_document_dimensions(&mut bytes.as_slice()),
+            Err(DecodeError::InvalidFormat)
+        ));
+    }
+}
@@ -0,0 +1,37 @@
+//! The error type of the fallible entry points of the crate, such as
+//! `Canvas::try_perform_raster_action`, for embedders that would rather
+//! report bad input than panic, such as a wasm instance driven by JavaScript.
+//!
+//! Each module keeps its own error type, and `MboardError` wraps all of them
+//! so that callers can use `?` across the crate.
+
+use thiserror::Error;
+
+use crate::{
+    canvas::{ActionError, IncrementalSaveError, JournalError},
+    primitives::dimensions::GeometryError,
+    raster::{
+        chunks::{InvalidPixelSliceSize, PngError},
+        LayerFileError, RasterizeError,
+    },
+};
+
+#[derive(Error, Debug)]
+pub enum MboardError {
+    #[error(transparent)]
+    Geometry(#[from] GeometryError),
+    #[error(transparent)]
+    Action(#[from] ActionError),
+    #[error(transparent)]
+    Rasterize(#[from] RasterizeError),
+    #[error(transparent)]
+    PixelSliceSize(#[from] InvalidPixelSliceSize),
+    #[error(transparent)]
+    Png(#[from] PngError),
+    #[error(transparent)]
+    IncrementalSave(#[from] IncrementalSaveError),
+    #[error(transparent)]
+    Journal(#[from] JournalError),
+    #[error(transparent)]
+    LayerFile(#[from] LayerFileError),
+}
@@ -1,9 +1,16 @@
-#![feature(int_roundings)]
-#![feature(new_uninit)]
-#![feature(maybe_uninit_write_slice)]
+#![cfg_attr(feature = "nightly", feature(int_roundings))]
 #![deny(clippy::unwrap_used)]
 
 pub mod canvas;
+mod encoding;
+pub mod error;
+pub mod prelude;
 pub mod primitives;
 pub mod raster;
+pub mod text;
+pub mod tools;
 pub mod vector;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use error::MboardError;
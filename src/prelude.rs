@@ -0,0 +1,46 @@
+//! The canonical types of the crate, re-exported for glob importing.
+//!
+//! The types here are the stable surface of the crate, and are kept
+//! compatible between releases where possible. Everything else that is public
+//! may change as the internals evolve, and items hidden from the docs are
+//! internal and shouldn't be relied on.
+//!
+//! Embedders driving the canvas from untrusted input should use the `try_*`
+//! entry points, such as `Canvas::try_perform_raster_action` and
+//! `Canvas::try_render`, which return a `MboardError` where the others panic.
+//!
+//! ```
+//! use mboard::prelude::*;
+//!
+//! let mut canvas = CanvasBuilder::new(64, 64).layer(LayerKind::Raster).build();
+//! canvas.try_perform_raster_action(
+//!     0,
+//!     RasterLayerAction::fill_rect(
+//!         CanvasRect::at_origin(Dimensions { width: 8, height: 8 }),
+//!         colors::red(),
+//!     ),
+//! )?;
+//! let raster = canvas.try_render(&CanvasView::new(64, 64))?;
+//! # Ok::<(), MboardError>(())
+//! ```
+
+pub use crate::{
+    canvas::{
+        Canvas, CanvasBuilder, CanvasSnapshot, CanvasView, Layer, LayerImplementation, LayerKind,
+        ViewRotation,
+    },
+    error::MboardError,
+    primitives::{
+        dimensions::{Dimensions, Scale},
+        position::{CanvasPoint, CanvasPosition, ChunkPosition, DrawPosition, PixelPosition},
+        rect::{CanvasRect, DrawRect, ViewRect},
+    },
+    raster::{
+        chunks::{raster_chunk::RasterChunk, BoxRasterChunk, RasterWindow},
+        pixels::colors,
+        BlendMode, MutRasterSource, Pixel, RasterLayer, RasterLayerAction, RasterSource,
+        RasterizeError,
+    },
+    text::{TextLayer, TextLayerAction},
+    vector::{VectorLayer, VectorLayerAction},
+};
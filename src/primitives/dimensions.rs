@@ -1,14 +1,36 @@
-use crate::raster::{
-    iter::PixelPositionIterator,
-    source::{BoundedPosition, RasterSource},
-};
+use thiserror::Error;
+
+use crate::raster::{iter::PixelPositionIterator, source::BoundedPosition};
 
 use super::{
     position::{DrawPosition, PixelPosition, UncheckedIntoPosition},
-    rect::Rect,
+    rect::{RasterRect, Rect},
 };
 
+/// The largest width or height allowed by validated constructors. Keeping sizes
+/// within this bound keeps every coordinate of a rect representable as an `i32`
+/// and the area of a rect representable as a `usize`.
+pub const MAX_DIMENSION: usize = 1 << 15;
+
+#[derive(Error, Debug, Clone, Copy, PartialEq)]
+pub enum GeometryError {
+    #[error("dimensions {width}x{height} have no area")]
+    DegenerateDimensions { width: usize, height: usize },
+    #[error(
+        "dimensions {width}x{height} exceed the maximum of {MAX_DIMENSION} in either direction"
+    )]
+    DimensionsTooLarge { width: usize, height: usize },
+    #[error("scale ({width_factor}, {height_factor}) is negative or not finite")]
+    InvalidScale {
+        width_factor: f32,
+        height_factor: f32,
+    },
+    #[error("rect extends past the range of its coordinate type")]
+    CoordinateOverflow,
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Scale {
     pub width_factor: f32,
     pub height_factor: f32,
@@ -48,12 +70,25 @@ impl Scale {
 
 /// The dimensions of a 2d object.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Dimensions {
     pub width: usize,
     pub height: usize,
 }
 
 impl Dimensions {
+    /// Creates dimensions, returning an error if they have no area or are
+    /// larger than `MAX_DIMENSION`.
+    pub fn try_new(width: usize, height: usize) -> Result<Dimensions, GeometryError> {
+        if width == 0 || height == 0 {
+            Err(GeometryError::DegenerateDimensions { width, height })
+        } else if width > MAX_DIMENSION || height > MAX_DIMENSION {
+            Err(GeometryError::DimensionsTooLarge { width, height })
+        } else {
+            Ok(Dimensions { width, height })
+        }
+    }
+
     /// Transform a point from another dimension space to this one, preserving the relative
     /// offset from the top-left.
     pub fn transform_point(&self, p: PixelPosition, src_dimensions: Dimensions) -> PixelPosition {
@@ -77,6 +112,34 @@ impl Dimensions {
         }
     }
 
+    /// Scale the dimensions, returning an error if the scale is invalid or the
+    /// scaled dimensions would not pass `Dimensions::try_new`.
+    pub fn try_scale(&self, scale: Scale) -> Result<Dimensions, GeometryError> {
+        let Scale {
+            width_factor,
+            height_factor,
+        } = scale;
+
+        if !width_factor.is_finite()
+            || !height_factor.is_finite()
+            || width_factor < 0.0
+            || height_factor < 0.0
+        {
+            return Err(GeometryError::InvalidScale {
+                width_factor,
+                height_factor,
+            });
+        }
+
+        let new_width = ((self.width as f32) * width_factor).round();
+        let new_height = ((self.height as f32) * height_factor).round();
+
+        // Saturate before converting so huge results are reported as too large
+        let clamp = |d: f32| d.min(MAX_DIMENSION as f32 + 1.0) as usize;
+
+        Dimensions::try_new(clamp(new_width), clamp(new_height))
+    }
+
     /// The difference between this dimension and another.
     pub fn difference(&self, other: Dimensions) -> (i32, i32) {
         (
@@ -98,11 +161,34 @@ impl Dimensions {
         usize::max(self.width, self.height)
     }
 
+    /// The number of pixels in a rect of the dimensions.
+    pub fn area(&self) -> usize {
+        self.width * self.height
+    }
+
     /// Iterator over pixel positions in rect described by dimensions.
     pub fn iter_pixels(&self) -> PixelPositionIterator {
         PixelPositionIterator::new(*self)
     }
 
+    /// Iterator over the tiles partitioning the rect described by dimensions,
+    /// in row-major order. Tiles are `tile_size` pixels square, except along
+    /// the right and bottom edges where they are cut short.
+    pub fn iter_tiles(&self, tile_size: usize) -> impl Iterator<Item = RasterRect> {
+        let Dimensions { width, height } = *self;
+        let tile_size = tile_size.max(1);
+
+        (0..height).step_by(tile_size).flat_map(move |tile_y| {
+            (0..width).step_by(tile_size).map(move |tile_x| RasterRect {
+                top_left: (tile_x, tile_y).into(),
+                dimensions: Dimensions {
+                    width: tile_size.min(width - tile_x),
+                    height: tile_size.min(height - tile_y),
+                },
+            })
+        })
+    }
+
     /// Whether or not a position is contained within a dimension.
     pub fn contains(&self, p: PixelPosition) -> bool {
         p.0 <= self.width && p.1 <= self.height
@@ -1,3 +1,4 @@
 pub mod dimensions;
 pub mod position;
 pub mod rect;
+pub mod transform;
@@ -7,6 +7,7 @@ use super::dimensions::Dimensions;
 /// Generic position with underlying storage type for coordindates. Implements
 /// basic operations like converting between different position types and translation.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Position<T>(pub T, pub T);
 
 impl<T: Mul<Output = T> + Copy> Position<T> {
@@ -96,6 +97,27 @@ pub type CanvasPosition = Position<i32>;
 pub type DrawPosition = Position<i32>;
 pub type LayerPosition = Position<i32>;
 pub type ChunkPosition = Position<i32>;
+/// A position in canvas space with subpixel precision, for placing shapes
+/// that are resolved to pixels when they are rasterized.
+pub type CanvasPoint = Position<f32>;
+
+/// Division rounding towards negative infinity.
+#[cfg(feature = "nightly")]
+fn div_floor(a: i32, b: i32) -> i32 {
+    a.div_floor(b)
+}
+
+/// Division rounding towards negative infinity.
+#[cfg(not(feature = "nightly"))]
+fn div_floor(a: i32, b: i32) -> i32 {
+    let quotient = a / b;
+
+    if a % b != 0 && ((a < 0) != (b < 0)) {
+        quotient - 1
+    } else {
+        quotient
+    }
+}
 
 impl CanvasPosition {
     /// Translate a canvas position by some portion of an offset.
@@ -106,8 +128,8 @@ impl CanvasPosition {
     /// The chunk containing a canvas position.
     pub fn containing_chunk(&self, chunk_size: usize) -> ChunkPosition {
         (
-            self.0.div_floor(chunk_size as i32),
-            self.1.div_floor(chunk_size as i32),
+            div_floor(self.0, chunk_size as i32),
+            div_floor(self.1, chunk_size as i32),
         )
             .into()
     }
@@ -122,6 +144,24 @@ impl CanvasPosition {
     }
 }
 
+impl CanvasPoint {
+    /// The canvas position of the pixel containing the point.
+    pub fn containing_pixel(&self) -> CanvasPosition {
+        (self.0.floor() as i32, self.1.floor() as i32).into()
+    }
+
+    /// The canvas position nearest to the point.
+    pub fn round(&self) -> CanvasPosition {
+        (self.0.round() as i32, self.1.round() as i32).into()
+    }
+}
+
+impl From<CanvasPosition> for CanvasPoint {
+    fn from(p: CanvasPosition) -> Self {
+        Self(p.0 as f32, p.1 as f32)
+    }
+}
+
 impl ChunkPosition {
     /// Get the dimension of chunks spanned between this position and another chunk position.
     pub fn span(&self, other: ChunkPosition) -> Dimensions {
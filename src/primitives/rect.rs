@@ -3,11 +3,12 @@ use std::ops::Neg;
 use num::{cast::AsPrimitive, PrimInt, Signed};
 
 use super::{
-    dimensions::Dimensions,
+    dimensions::{Dimensions, GeometryError},
     position::{Position, UncheckedIntoPosition},
 };
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rect<T> {
     pub top_left: Position<T>,
     pub dimensions: Dimensions,
@@ -33,10 +34,91 @@ impl<T: PrimInt + AsPrimitive<usize>> Rect<T>
 where
     usize: AsPrimitive<T>,
 {
+    /// Creates a rect, returning an error if its dimensions do not pass
+    /// `Dimensions::try_new` or its bottom right is not representable.
+    pub fn try_new(
+        top_left: Position<T>,
+        dimensions: Dimensions,
+    ) -> Result<Rect<T>, GeometryError> {
+        let dimensions = Dimensions::try_new(dimensions.width, dimensions.height)?;
+
+        let extent = |d: usize| -> Option<T> { T::from(d - 1) };
+        let bottom_right_representable = extent(dimensions.width)
+            .and_then(|width| top_left.0.checked_add(&width))
+            .and(extent(dimensions.height).and_then(|height| top_left.1.checked_add(&height)));
+
+        if bottom_right_representable.is_none() {
+            return Err(GeometryError::CoordinateOverflow);
+        }
+
+        Ok(Rect {
+            top_left,
+            dimensions,
+        })
+    }
+
     pub fn is_degenerate(&self) -> bool {
         self.dimensions.is_degenerate()
     }
 
+    /// Expands `self` in all directions by `margin`, returning an error if the
+    /// expanded rect would not pass `Rect::try_new`.
+    pub fn try_expand(&self, margin: usize) -> Result<Rect<T>, GeometryError> {
+        let margin_as_t = T::from(margin).ok_or(GeometryError::CoordinateOverflow)?;
+
+        let top_left = (
+            self.top_left.0.checked_sub(&margin_as_t),
+            self.top_left.1.checked_sub(&margin_as_t),
+        );
+        let dimensions = (
+            margin
+                .checked_mul(2)
+                .and_then(|m| self.dimensions.width.checked_add(m)),
+            margin
+                .checked_mul(2)
+                .and_then(|m| self.dimensions.height.checked_add(m)),
+        );
+
+        match (top_left, dimensions) {
+            ((Some(left), Some(top)), (Some(width), Some(height))) => {
+                Rect::try_new((left, top).into(), Dimensions { width, height })
+            }
+            _ => Err(GeometryError::CoordinateOverflow),
+        }
+    }
+
+    /// Whether or not this rect shares any positions with another.
+    pub fn intersects(&self, other: &Rect<T>) -> bool {
+        self.intersection(other).is_some()
+    }
+
+    /// The rect of positions shared by this rect and another, if there are any.
+    pub fn intersection(&self, other: &Rect<T>) -> Option<Rect<T>> {
+        if self.is_degenerate() || other.is_degenerate() {
+            return None;
+        }
+
+        let bottom_right = self.bottom_right();
+        let other_bottom_right = other.bottom_right();
+
+        let top_left: Position<T> = (
+            self.top_left.0.max(other.top_left.0),
+            self.top_left.1.max(other.top_left.1),
+        )
+            .into();
+        let bottom_right: Position<T> = (
+            bottom_right.0.min(other_bottom_right.0),
+            bottom_right.1.min(other_bottom_right.1),
+        )
+            .into();
+
+        if top_left.0 > bottom_right.0 || top_left.1 > bottom_right.1 {
+            None
+        } else {
+            Some(Rect::from_points(top_left, bottom_right))
+        }
+    }
+
     pub fn translate(&self, offset: Position<T>) -> Rect<T> {
         Rect {
             top_left: self.top_left.translate(offset),
@@ -104,9 +186,22 @@ where
 }
 
 impl Rect<i32> {
+    /// Iterator over the tiles partitioning the rect, in row-major order. See
+    /// `Dimensions::iter_tiles`.
+    pub fn iter_tiles(&self, tile_size: usize) -> impl Iterator<Item = Rect<i32>> {
+        let top_left = self.top_left;
+
+        self.dimensions
+            .iter_tiles(tile_size)
+            .map(move |tile| Rect::<i32> {
+                top_left: top_left.translate(tile.top_left.unchecked_into_position()),
+                dimensions: tile.dimensions,
+            })
+    }
+
     pub fn subrect_contained_in(&self, dimensions: Dimensions) -> Option<Rect<usize>> {
-        let bound_top_left = dimensions.bound_position(self.top_left.into());
-        let bound_bottom_right = dimensions.bound_position(self.bottom_right().into());
+        let bound_top_left = dimensions.bound_position(self.top_left);
+        let bound_bottom_right = dimensions.bound_position(self.bottom_right());
 
         let self_top_left_past_other_bottom_right =
             bound_top_left.delta.0 < 0 || bound_top_left.delta.1 < 0;
@@ -123,14 +218,6 @@ impl Rect<i32> {
         let bottom_right_relative_to_self =
             bound_bottom_right.position.unchecked_into_position() + self.top_left.mul(-1);
 
-        println!(
-            "{:?} {:?} {:?} {:?}",
-            bound_top_left,
-            bound_bottom_right,
-            top_left_relative_to_self,
-            bottom_right_relative_to_self
-        );
-
         Some(Rect::<usize>::from_points(
             top_left_relative_to_self.unchecked_into_position(),
             bottom_right_relative_to_self.unchecked_into_position(),
@@ -193,3 +280,168 @@ pub type CanvasRect = Rect<i32>;
 pub type ViewRect = Rect<usize>;
 pub type DrawRect = Rect<i32>;
 pub type RasterRect = Rect<usize>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::dimensions::{Scale, MAX_DIMENSION};
+
+    #[test]
+    fn validated_constructors() {
+        let dimensions = Dimensions {
+            width: 4,
+            height: 2,
+        };
+
+        assert_eq!(
+            CanvasRect::try_new((-3, 5).into(), dimensions),
+            Ok(CanvasRect {
+                top_left: (-3, 5).into(),
+                dimensions
+            })
+        );
+        assert_eq!(
+            CanvasRect::try_new(
+                (0, 0).into(),
+                Dimensions {
+                    width: 0,
+                    height: 2
+                }
+            ),
+            Err(GeometryError::DegenerateDimensions {
+                width: 0,
+                height: 2
+            })
+        );
+        assert_eq!(
+            CanvasRect::try_new((i32::MAX - 1, 0).into(), dimensions),
+            Err(GeometryError::CoordinateOverflow)
+        );
+        assert_eq!(
+            ViewRect::try_new((0, 0).into(), dimensions)
+                .and_then(|rect| rect.try_expand(1))
+                .map(|rect| rect.top_left),
+            Err(GeometryError::CoordinateOverflow)
+        );
+
+        assert_eq!(
+            dimensions.try_scale(Scale {
+                width_factor: 2.0,
+                height_factor: 0.5
+            }),
+            Ok(Dimensions {
+                width: 8,
+                height: 1
+            })
+        );
+        assert!(matches!(
+            dimensions.try_scale(Scale {
+                width_factor: -1.0,
+                height_factor: 1.0
+            }),
+            Err(GeometryError::InvalidScale { .. })
+        ));
+        assert!(matches!(
+            dimensions.try_scale(Scale {
+                width_factor: f32::INFINITY,
+                height_factor: 1.0
+            }),
+            Err(GeometryError::InvalidScale { .. })
+        ));
+        assert_eq!(
+            dimensions.try_scale(Scale {
+                width_factor: 0.1,
+                height_factor: 1.0
+            }),
+            Err(GeometryError::DegenerateDimensions {
+                width: 0,
+                height: 2
+            })
+        );
+        assert_eq!(
+            dimensions.try_scale(Scale {
+                width_factor: 1e30,
+                height_factor: 1.0
+            }),
+            Err(GeometryError::DimensionsTooLarge {
+                width: MAX_DIMENSION + 1,
+                height: 2
+            })
+        );
+    }
+
+    #[test]
+    fn rect_intersection() {
+        let rect_a = CanvasRect {
+            top_left: (-2, -2).into(),
+            dimensions: Dimensions {
+                width: 5,
+                height: 5,
+            },
+        };
+        let rect_b = CanvasRect {
+            top_left: (1, 0).into(),
+            dimensions: Dimensions {
+                width: 10,
+                height: 1,
+            },
+        };
+        let rect_c = CanvasRect {
+            top_left: (3, 3).into(),
+            dimensions: Dimensions {
+                width: 1,
+                height: 1,
+            },
+        };
+
+        assert_eq!(
+            rect_a.intersection(&rect_b),
+            Some(CanvasRect {
+                top_left: (1, 0).into(),
+                dimensions: Dimensions {
+                    width: 2,
+                    height: 1
+                }
+            })
+        );
+        assert!(rect_b.intersects(&rect_a));
+        assert!(!rect_a.intersects(&rect_c));
+        assert_eq!(rect_a.intersection(&rect_a), Some(rect_a));
+    }
+
+    #[test]
+    fn tiles_partition_rects() {
+        let canvas_rect = CanvasRect {
+            top_left: (-2, 3).into(),
+            dimensions: Dimensions {
+                width: 5,
+                height: 3,
+            },
+        };
+
+        let tiles: Vec<CanvasRect> = canvas_rect.iter_tiles(2).collect();
+        let tile_dimensions: Vec<(usize, usize)> = tiles
+            .iter()
+            .map(|tile| (tile.dimensions.width, tile.dimensions.height))
+            .collect();
+
+        assert_eq!(
+            tile_dimensions,
+            [(2, 2), (2, 2), (1, 2), (2, 1), (2, 1), (1, 1)]
+        );
+        assert_eq!(tiles[0].top_left, canvas_rect.top_left);
+        assert_eq!(tiles[5].bottom_right(), canvas_rect.bottom_right());
+        assert_eq!(
+            tiles
+                .iter()
+                .map(|tile| tile.dimensions.width * tile.dimensions.height)
+                .sum::<usize>(),
+            15
+        );
+        let degenerate = Dimensions {
+            width: 0,
+            height: 3,
+        };
+        assert_eq!(degenerate.iter_tiles(4).count(), 0);
+    }
+}
@@ -0,0 +1,152 @@
+//! Affine transforms of points, such as the translation, scaling and rotation
+//! of vector shapes.
+
+use super::position::CanvasPoint;
+
+/// An affine transform mapping `(x, y)` to
+/// `(a * x + c * y + e, b * x + d * y + f)`. Angles are clockwise, since the y
+/// axis points down.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Affine {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+}
+
+impl Default for Affine {
+    fn default() -> Self {
+        Affine::IDENTITY
+    }
+}
+
+impl Affine {
+    pub const IDENTITY: Affine = Affine {
+        a: 1.0,
+        b: 0.0,
+        c: 0.0,
+        d: 1.0,
+        e: 0.0,
+        f: 0.0,
+    };
+
+    pub fn translate(dx: f32, dy: f32) -> Affine {
+        Affine {
+            e: dx,
+            f: dy,
+            ..Affine::IDENTITY
+        }
+    }
+
+    pub fn scale(sx: f32, sy: f32) -> Affine {
+        Affine {
+            a: sx,
+            d: sy,
+            ..Affine::IDENTITY
+        }
+    }
+
+    pub fn rotate(radians: f32) -> Affine {
+        let (sin, cos) = radians.sin_cos();
+
+        Affine {
+            a: cos,
+            b: sin,
+            c: -sin,
+            d: cos,
+            ..Affine::IDENTITY
+        }
+    }
+
+    /// The transform applied about `pivot` instead of the origin, so that
+    /// `pivot` stays in place.
+    pub fn about(&self, pivot: CanvasPoint) -> Affine {
+        Affine::translate(-pivot.0, -pivot.1)
+            .then(self)
+            .then(&Affine::translate(pivot.0, pivot.1))
+    }
+
+    /// The transform applying `self` and then `next`.
+    pub fn then(&self, next: &Affine) -> Affine {
+        Affine {
+            a: next.a * self.a + next.c * self.b,
+            b: next.b * self.a + next.d * self.b,
+            c: next.a * self.c + next.c * self.d,
+            d: next.b * self.c + next.d * self.d,
+            e: next.a * self.e + next.c * self.f + next.e,
+            f: next.b * self.e + next.d * self.f + next.f,
+        }
+    }
+
+    pub fn apply(&self, point: CanvasPoint) -> CanvasPoint {
+        (
+            self.a * point.0 + self.c * point.1 + self.e,
+            self.b * point.0 + self.d * point.1 + self.f,
+        )
+            .into()
+    }
+
+    /// The point a transform maps `point` to, with coordinates within a
+    /// thousandth of a whole number rounded to it. Rotations by multiples of
+    /// a quarter turn then land exactly on pixel boundaries rather than
+    /// slightly off of them.
+    pub fn apply_snapped(&self, point: CanvasPoint) -> CanvasPoint {
+        let snap = |v: f32| {
+            let rounded = v.round();
+            if (v - rounded).abs() < 1e-3 {
+                rounded
+            } else {
+                v
+            }
+        };
+        let point = self.apply(point);
+
+        (snap(point.0), snap(point.1)).into()
+    }
+
+    /// The transform without its translation.
+    pub fn linear(&self) -> Affine {
+        Affine {
+            e: 0.0,
+            f: 0.0,
+            ..*self
+        }
+    }
+
+    pub fn translation(&self) -> CanvasPoint {
+        (self.e, self.f).into()
+    }
+
+    /// Whether the transform only translates points.
+    pub fn is_translation(&self) -> bool {
+        self.linear() == Affine::IDENTITY
+    }
+
+    /// The transform undoing this one, or `None` if it collapses points onto
+    /// a line.
+    pub fn inverse(&self) -> Option<Affine> {
+        let determinant = self.a * self.d - self.b * self.c;
+        if determinant.abs() <= f32::EPSILON {
+            return None;
+        }
+
+        let (a, b, c, d) = (
+            self.d / determinant,
+            -self.b / determinant,
+            -self.c / determinant,
+            self.a / determinant,
+        );
+
+        Some(Affine {
+            a,
+            b,
+            c,
+            d,
+            e: -(a * self.e + c * self.f),
+            f: -(b * self.e + d * self.f),
+        })
+    }
+}
@@ -0,0 +1,99 @@
+//! Hiding parts of a layer based on luminosity while it is composited, so
+//! textures can be blended into the layers below without painting a mask.
+
+use std::ops::DerefMut;
+
+use super::{chunks::raster_chunk::RasterChunk, Pixel};
+
+/// A range of luminosities in which pixels are visible. Pixels are hidden
+/// below `low.0`, fade in until `low.1`, are fully visible until `high.0`
+/// and fade out until `high.1`, above which they are hidden. Splitting the
+/// ends of the range apart feathers the transition.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LuminosityRange {
+    pub low: (u8, u8),
+    pub high: (u8, u8),
+}
+
+impl Default for LuminosityRange {
+    fn default() -> Self {
+        LuminosityRange {
+            low: (0, 0),
+            high: (255, 255),
+        }
+    }
+}
+
+impl LuminosityRange {
+    /// How visible a pixel of `luminosity` is, from 0 to 1.
+    pub fn visibility(&self, luminosity: u8) -> f32 {
+        let fade_in = if luminosity >= self.low.1 {
+            1.0
+        } else if luminosity <= self.low.0 {
+            0.0
+        } else {
+            (luminosity - self.low.0) as f32 / (self.low.1 - self.low.0) as f32
+        };
+
+        let fade_out = if luminosity <= self.high.0 {
+            1.0
+        } else if luminosity >= self.high.1 {
+            0.0
+        } else {
+            (self.high.1 - luminosity) as f32 / (self.high.1 - self.high.0) as f32
+        };
+
+        fade_in.min(fade_out)
+    }
+}
+
+/// Luminosity ranges that limit where a layer shows, applied during compositing.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlendIf {
+    /// The range of the layer's own luminosity that is shown.
+    pub this_layer: LuminosityRange,
+    /// The range of luminosity of the layers below that the layer shows over.
+    pub underlying: LuminosityRange,
+}
+
+impl BlendIf {
+    /// Reduces the alpha of the pixels of `layer_raster` that are outside the
+    /// ranges, where `underlying` is the composited raster of the layers below.
+    pub fn apply<T: DerefMut<Target = [Pixel]>>(
+        &self,
+        layer_raster: &mut RasterChunk<T>,
+        underlying: &[Pixel],
+    ) {
+        for (pixel, underlying_pixel) in layer_raster.pixels_mut().iter_mut().zip(underlying) {
+            let visibility = self.this_layer.visibility(pixel.luminosity())
+                * self.underlying.visibility(underlying_pixel.luminosity());
+
+            let (r, g, b, a) = pixel.as_rgba();
+            *pixel = Pixel::new_rgba(r, g, b, (a as f32 * visibility).round() as u8);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn luminosity_range_feathering() {
+        let range = LuminosityRange {
+            low: (50, 100),
+            high: (200, 200),
+        };
+
+        assert_eq!(range.visibility(0), 0.0);
+        assert_eq!(range.visibility(50), 0.0);
+        assert_eq!(range.visibility(75), 0.5);
+        assert_eq!(range.visibility(100), 1.0);
+        assert_eq!(range.visibility(200), 1.0);
+        assert_eq!(range.visibility(201), 0.0);
+        assert_eq!(LuminosityRange::default().visibility(0), 1.0);
+        assert_eq!(LuminosityRange::default().visibility(255), 1.0);
+    }
+}
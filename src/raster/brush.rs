@@ -0,0 +1,240 @@
+//! Brushes that paint strokes of stamps along a path of canvas positions.
+
+use crate::primitives::{dimensions::Dimensions, position::CanvasPosition, rect::CanvasRect};
+
+use super::{chunks::BoxRasterChunk, pixels::Pixel};
+
+/// The shape of a single stamp of a brush.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum BrushShape {
+    #[default]
+    Round,
+    Square,
+}
+
+/// How a stroke is painted. Stamps of the brush are placed along the stroke
+/// closely enough that they overlap into a continuous line, however far apart
+/// the positions of the stroke are.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Brush {
+    pub shape: BrushShape,
+    pub diameter: u32,
+    pub color: Pixel,
+    /// The distance between stamps as a fraction of the diameter, placing
+    /// stamps at least a pixel apart.
+    pub spacing: f32,
+    /// The most the stroke covers any pixel, from 0 to 255. Overlapping stamps
+    /// of the same stroke don't build up past it.
+    pub opacity: u8,
+    /// How much of the radius of a stamp is fully covered before fading out
+    /// towards its edge, from 0 to 1.
+    pub hardness: f32,
+}
+
+impl Brush {
+    /// A hard round brush with stamps a quarter of its diameter apart.
+    pub fn new(diameter: u32, color: Pixel) -> Brush {
+        Brush {
+            shape: BrushShape::Round,
+            diameter,
+            color,
+            spacing: 0.25,
+            opacity: u8::MAX,
+            hardness: 1.0,
+        }
+    }
+
+    fn step(&self) -> f32 {
+        (self.diameter as f32 * self.spacing).max(1.0)
+    }
+
+    /// The positions of the centers of the stamps along a stroke through
+    /// `points`. The distance left over at the end of one segment carries into
+    /// the next, so stamps stay evenly spaced around corners.
+    pub fn stamp_positions(&self, points: &[CanvasPosition]) -> Vec<CanvasPosition> {
+        let first = match points.first() {
+            Some(first) => *first,
+            None => return vec![],
+        };
+
+        let step = self.step();
+        let mut stamps = vec![first];
+        let mut distance_to_next = step;
+
+        for (from, to) in points.iter().zip(points.iter().skip(1)) {
+            let (dx, dy) = ((to.0 - from.0) as f32, (to.1 - from.1) as f32);
+            let length = dx.hypot(dy);
+            let mut travelled = distance_to_next;
+
+            while travelled <= length {
+                let t = travelled / length;
+                stamps.push(
+                    (
+                        from.0 + (dx * t).round() as i32,
+                        from.1 + (dy * t).round() as i32,
+                    )
+                        .into(),
+                );
+                travelled += step;
+            }
+
+            distance_to_next = travelled - length;
+        }
+
+        stamps
+    }
+
+    /// The canvas rect covered by a stamp centered at `center`.
+    fn stamp_rect(&self, center: CanvasPosition) -> CanvasRect {
+        let diameter = self.diameter.max(1) as usize;
+
+        CanvasRect {
+            top_left: (
+                center.0 - diameter as i32 / 2,
+                center.1 - diameter as i32 / 2,
+            )
+                .into(),
+            dimensions: Dimensions {
+                width: diameter,
+                height: diameter,
+            },
+        }
+    }
+
+    /// The coverage of each pixel of a stamp, from 0 to 255.
+    fn stamp_coverage(&self) -> Vec<u8> {
+        let diameter = self.diameter.max(1) as usize;
+        let radius = diameter as f32 / 2.0;
+        let inner_radius = radius * self.hardness.clamp(0.0, 1.0);
+        let max_coverage = self.opacity as f32;
+
+        Dimensions {
+            width: diameter,
+            height: diameter,
+        }
+        .iter_pixels()
+        .map(|position| {
+            let (x, y) = (
+                position.0 as f32 + 0.5 - radius,
+                position.1 as f32 + 0.5 - radius,
+            );
+            let distance = match self.shape {
+                BrushShape::Round => x.hypot(y),
+                BrushShape::Square => x.abs().max(y.abs()),
+            };
+
+            // Fully covered within the inner radius, fading out linearly to
+            // the edge, which is antialiased over a pixel.
+            let coverage =
+                ((radius + 0.5 - distance) / (radius - inner_radius + 1.0)).clamp(0.0, 1.0);
+
+            (coverage * max_coverage).round() as u8
+        })
+        .collect()
+    }
+
+    /// Rasterizes a stroke through `points`, returning it with the canvas rect
+    /// it covers, or `None` if there are no points.
+    pub fn rasterize_stroke(
+        &self,
+        points: &[CanvasPosition],
+    ) -> Option<(CanvasRect, BoxRasterChunk)> {
+        let stamp_rects: Vec<CanvasRect> = self
+            .stamp_positions(points)
+            .into_iter()
+            .map(|center| self.stamp_rect(center))
+            .collect();
+        let stroke_rect = stamp_rects
+            .iter()
+            .copied()
+            .reduce(|a, b| a.spanning_rect(&b))?;
+
+        let Dimensions { width, height } = stroke_rect.dimensions;
+        let stamp_coverage = self.stamp_coverage();
+        let stamp_width = self.diameter.max(1) as usize;
+        let mut coverage = vec![0u8; width * height];
+
+        // Taking the most coverage of any stamp rather than compositing stamps
+        // keeps the stroke from building up where stamps overlap.
+        for stamp_rect in stamp_rects {
+            let left = (stamp_rect.top_left.0 - stroke_rect.top_left.0) as usize;
+            let top = (stamp_rect.top_left.1 - stroke_rect.top_left.1) as usize;
+
+            for (stamp_row, row) in stamp_coverage
+                .chunks_exact(stamp_width)
+                .zip(coverage[top * width..].chunks_exact_mut(width))
+            {
+                for (stroke_value, stamp_value) in
+                    row[left..left + stamp_width].iter_mut().zip(stamp_row)
+                {
+                    *stroke_value = (*stroke_value).max(*stamp_value);
+                }
+            }
+        }
+
+        let (r, g, b, a) = self.color.as_rgba();
+        let pixels = coverage
+            .into_iter()
+            .map(|value| Pixel::new_rgba(r, g, b, ((a as u32 * value as u32) / 255) as u8))
+            .collect();
+        let stroke = BoxRasterChunk::from_vec(pixels, width, height)
+            .expect("a pixel is produced for every position of the stroke rect");
+
+        Some((stroke_rect, stroke))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        canvas::Layer,
+        raster::{pixels::colors, RasterLayer, RasterSource},
+    };
+
+    #[test]
+    fn strokes_have_no_gaps_between_distant_points() {
+        let brush = Brush {
+            opacity: 128,
+            ..Brush::new(4, colors::red())
+        };
+        let mut raster_layer = RasterLayer::new(16);
+
+        let changed_rect =
+            raster_layer.draw_stroke(&[(0, 8).into(), (40, 8).into(), (40, 30).into()], &brush);
+        assert_eq!(
+            changed_rect,
+            Some(CanvasRect {
+                top_left: (-2, 6).into(),
+                dimensions: Dimensions {
+                    width: 44,
+                    height: 26,
+                },
+            })
+        );
+
+        let raster = raster_layer.rasterize_canvas_rect_shared(changed_rect.unwrap());
+        for x in 0..=40 {
+            let alpha = raster
+                .pixel_at_position((x as usize + 2, 2).into())
+                .unwrap()
+                .as_rgba()
+                .3;
+            assert!(
+                (alpha as i32 - 128).abs() <= 1,
+                "{x} has an alpha of {alpha}"
+            );
+        }
+        for y in 8..=30 {
+            let alpha = raster
+                .pixel_at_position((42, y - 6).into())
+                .unwrap()
+                .as_rgba()
+                .3;
+            assert!(
+                (alpha as i32 - 128).abs() <= 1,
+                "{y} has an alpha of {alpha}"
+            );
+        }
+    }
+}
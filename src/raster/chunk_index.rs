@@ -0,0 +1,130 @@
+//! An index of the allocated chunks of a raster layer grouped into regions,
+//! so the allocated chunks within a rect can be found without probing every
+//! chunk position of it.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::primitives::position::ChunkPosition;
+
+/// How many chunks wide and tall each region of the index is.
+const REGION_SIZE: i32 = 16;
+
+fn region_of(chunk_position: ChunkPosition) -> (i32, i32) {
+    (
+        chunk_position.0.div_euclid(REGION_SIZE),
+        chunk_position.1.div_euclid(REGION_SIZE),
+    )
+}
+
+/// The allocated chunk positions of a layer, grouped by the region of
+/// `REGION_SIZE` by `REGION_SIZE` chunks they are in.
+#[derive(Debug, Clone, Default)]
+pub(super) struct ChunkIndex {
+    regions: HashMap<(i32, i32), HashSet<ChunkPosition>>,
+}
+
+impl ChunkIndex {
+    pub(super) fn insert(&mut self, chunk_position: ChunkPosition) {
+        self.regions
+            .entry(region_of(chunk_position))
+            .or_default()
+            .insert(chunk_position);
+    }
+
+    pub(super) fn remove(&mut self, chunk_position: ChunkPosition) {
+        let region = region_of(chunk_position);
+
+        if let Some(chunk_positions) = self.regions.get_mut(&region) {
+            chunk_positions.remove(&chunk_position);
+
+            if chunk_positions.is_empty() {
+                self.regions.remove(&region);
+            }
+        }
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.regions.clear();
+    }
+
+    /// The indexed chunk positions from `top_left` to `bottom_right`
+    /// inclusive, from top to bottom and left to right. Whichever of the
+    /// occupied regions or the regions of the span are fewer are visited.
+    pub(super) fn positions_in(
+        &self,
+        top_left: ChunkPosition,
+        bottom_right: ChunkPosition,
+    ) -> Vec<ChunkPosition> {
+        let (left, top) = region_of(top_left);
+        let (right, bottom) = region_of(bottom_right);
+        let region_count = (right - left + 1) as usize * (bottom - top + 1) as usize;
+
+        let is_in_span = |chunk_position: &&ChunkPosition| {
+            (top_left.0..=bottom_right.0).contains(&chunk_position.0)
+                && (top_left.1..=bottom_right.1).contains(&chunk_position.1)
+        };
+
+        let mut chunk_positions: Vec<ChunkPosition> = if region_count <= self.regions.len() {
+            (top..=bottom)
+                .flat_map(|y| (left..=right).map(move |x| (x, y)))
+                .filter_map(|region| self.regions.get(&region))
+                .flatten()
+                .filter(is_in_span)
+                .copied()
+                .collect()
+        } else {
+            self.regions
+                .iter()
+                .filter(|(region, _)| {
+                    (left..=right).contains(&region.0) && (top..=bottom).contains(&region.1)
+                })
+                .flat_map(|(_, chunk_positions)| chunk_positions)
+                .filter(is_in_span)
+                .copied()
+                .collect()
+        };
+        chunk_positions.sort_by_key(|chunk_position| (chunk_position.1, chunk_position.0));
+
+        chunk_positions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions_are_found_by_region() {
+        let mut chunk_index = ChunkIndex::default();
+        for chunk_position in [(-1, -1), (0, 0), (15, 3), (16, 3), (500, -500)] {
+            chunk_index.insert(chunk_position.into());
+        }
+
+        assert_eq!(
+            chunk_index.positions_in((-1, -1).into(), (16, 3).into()),
+            vec![
+                (-1, -1).into(),
+                (0, 0).into(),
+                (15, 3).into(),
+                (16, 3).into()
+            ]
+        );
+        assert_eq!(
+            chunk_index.positions_in((0, -1000).into(), (1000, 1000).into()),
+            vec![
+                (500, -500).into(),
+                (0, 0).into(),
+                (15, 3).into(),
+                (16, 3).into()
+            ]
+        );
+
+        chunk_index.remove((15, 3).into());
+        chunk_index.remove((500, -500).into());
+        assert_eq!(
+            chunk_index.positions_in((1, 1).into(), (1000, 1000).into()),
+            vec![(16, 3).into()]
+        );
+        assert_eq!(chunk_index.regions.len(), 3);
+    }
+}
@@ -0,0 +1,160 @@
+//! Choosing the chunk size of raster layers.
+//!
+//! Small chunks waste less memory on sparse drawings but make rendering and
+//! large actions touch many chunks, while large chunks do the opposite. The
+//! best size depends on the size of the view and of the actions performed, so
+//! layers record the actions performed on them to recommend a size later.
+
+use crate::primitives::{dimensions::Dimensions, rect::CanvasRect};
+
+pub const MIN_AUTO_CHUNK_SIZE: usize = 32;
+
+/// wasm32 memory is limited to 4GiB and grows in large steps, so chunks are
+/// kept smaller to avoid allocating memory that isn't drawn on.
+#[cfg(target_arch = "wasm32")]
+pub const MAX_AUTO_CHUNK_SIZE: usize = 256;
+#[cfg(not(target_arch = "wasm32"))]
+pub const MAX_AUTO_CHUNK_SIZE: usize = 512;
+
+/// The largest chunk size accepted from saved data, which bounds the memory a
+/// damaged or malicious file can make a layer allocate.
+pub const MAX_CHUNK_SIZE: usize = 4096;
+
+/// Whether a chunk size read from saved data can be used for a layer.
+pub fn is_valid_chunk_size(chunk_size: usize) -> bool {
+    (1..=MAX_CHUNK_SIZE).contains(&chunk_size)
+}
+
+fn clamp_chunk_size(chunk_size: usize) -> usize {
+    chunk_size
+        .next_power_of_two()
+        .clamp(MIN_AUTO_CHUNK_SIZE, MAX_AUTO_CHUNK_SIZE)
+}
+
+/// A chunk size for layers shown in a view of `view_dimensions`, so that the
+/// view spans a handful of chunks in each direction.
+pub fn auto_chunk_size(view_dimensions: Dimensions) -> usize {
+    clamp_chunk_size(view_dimensions.largest_dimension() / 4)
+}
+
+/// Statistics about the rects changed by actions on a layer.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ChunkUsage {
+    action_count: usize,
+    /// The sum of the natural logs of the largest side of each changed rect.
+    log_extent_sum: f64,
+}
+
+impl ChunkUsage {
+    pub fn record(&mut self, changed_canvas_rect: &CanvasRect) {
+        let extent = changed_canvas_rect.dimensions.largest_dimension().max(1);
+
+        self.action_count += 1;
+        self.log_extent_sum += (extent as f64).ln();
+    }
+
+    pub fn action_count(&self) -> usize {
+        self.action_count
+    }
+
+    /// The typical largest side of a changed rect. This is a geometric mean so
+    /// that occasional huge fills don't outweigh many small strokes.
+    pub fn typical_extent(&self) -> Option<f64> {
+        if self.action_count == 0 {
+            None
+        } else {
+            Some((self.log_extent_sum / self.action_count as f64).exp())
+        }
+    }
+
+    /// The chunk size that suits the recorded actions, so that a typical action
+    /// touches only a few chunks. Returns `None` if nothing has been recorded.
+    pub fn recommended_chunk_size(&self) -> Option<usize> {
+        self.typical_extent()
+            .map(|extent| clamp_chunk_size((extent * 2.0).ceil() as usize))
+    }
+}
+
+/// How the allocated chunks of a layer are filled, for deciding whether
+/// freeing or compressing them is worthwhile.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LayerOccupancy {
+    pub populated_chunks: usize,
+    /// Populated chunks that are completely transparent, which could be freed.
+    pub transparent_chunks: usize,
+    /// Populated chunks of a single color that isn't completely transparent,
+    /// which could be stored as that color.
+    pub single_color_chunks: usize,
+}
+
+impl LayerOccupancy {
+    fn fraction(&self, chunks: usize) -> f32 {
+        if self.populated_chunks == 0 {
+            0.0
+        } else {
+            chunks as f32 / self.populated_chunks as f32
+        }
+    }
+
+    pub fn transparent_fraction(&self) -> f32 {
+        self.fraction(self.transparent_chunks)
+    }
+
+    pub fn single_color_fraction(&self) -> f32 {
+        self.fraction(self.single_color_chunks)
+    }
+
+    /// The fraction of populated chunks that could be freed or stored as a
+    /// single color.
+    pub fn compactable_fraction(&self) -> f32 {
+        self.fraction(self.transparent_chunks + self.single_color_chunks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect_with_extent(extent: usize) -> CanvasRect {
+        CanvasRect::at_origin(Dimensions {
+            width: extent,
+            height: extent / 2 + 1,
+        })
+    }
+
+    #[test]
+    fn chunk_size_recommendations() {
+        assert_eq!(
+            auto_chunk_size(Dimensions {
+                width: 1920,
+                height: 1080
+            }),
+            512
+        );
+        assert_eq!(
+            auto_chunk_size(Dimensions {
+                width: 300,
+                height: 200
+            }),
+            128
+        );
+        assert_eq!(
+            auto_chunk_size(Dimensions {
+                width: 0,
+                height: 0
+            }),
+            32
+        );
+
+        let mut usage = ChunkUsage::default();
+        assert_eq!(usage.recommended_chunk_size(), None);
+
+        for _ in 0..99 {
+            usage.record(&rect_with_extent(20));
+        }
+        usage.record(&rect_with_extent(10_000));
+
+        assert_eq!(usage.action_count(), 100);
+        assert_eq!(usage.recommended_chunk_size(), Some(64));
+    }
+}
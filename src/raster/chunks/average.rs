@@ -0,0 +1,101 @@
+//! The average color of the pixels of a chunk, weighted by their opacity, for
+//! sampling colors from the canvas such as with brushes that mix with it.
+
+use std::ops::Deref;
+
+use super::raster_chunk::RasterChunk;
+use crate::raster::{pixels::colors, Pixel};
+
+/// Sums of the channels of pixels, with colors weighted by their opacity so
+/// that transparent pixels don't pull the average color towards black.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ColorSum {
+    red: u64,
+    green: u64,
+    blue: u64,
+    alpha: u64,
+    pixel_count: u64,
+}
+
+impl ColorSum {
+    pub fn new() -> ColorSum {
+        ColorSum::default()
+    }
+
+    /// Adds `count` pixels of the same color.
+    pub fn record(&mut self, pixel: Pixel, count: u64) {
+        let (r, g, b, a) = pixel.as_rgba();
+        let (r, g, b, a) = (r as u64, g as u64, b as u64, a as u64);
+
+        self.red += r * a * count;
+        self.green += g * a * count;
+        self.blue += b * a * count;
+        self.alpha += a * count;
+        self.pixel_count += count;
+    }
+
+    /// Adds a row of pixels, summing it before adding it to the totals.
+    pub fn record_row(&mut self, row: &[Pixel]) {
+        let (mut red, mut green, mut blue, mut alpha) = (0, 0, 0, 0);
+
+        for pixel in row {
+            let (r, g, b, a) = pixel.as_rgba();
+            let (r, g, b, a) = (r as u32, g as u32, b as u32, a as u32);
+
+            red += (r * a) as u64;
+            green += (g * a) as u64;
+            blue += (b * a) as u64;
+            alpha += a as u64;
+        }
+
+        self.red += red;
+        self.green += green;
+        self.blue += blue;
+        self.alpha += alpha;
+        self.pixel_count += row.len() as u64;
+    }
+
+    /// Adds the sums of another `ColorSum` to this one.
+    pub fn merge(&mut self, other: &ColorSum) {
+        self.red += other.red;
+        self.green += other.green;
+        self.blue += other.blue;
+        self.alpha += other.alpha;
+        self.pixel_count += other.pixel_count;
+    }
+
+    /// The number of pixels that have been added.
+    pub fn pixel_count(&self) -> u64 {
+        self.pixel_count
+    }
+
+    /// The average color of the pixels weighted by their opacity, with the
+    /// average opacity of every pixel. This is transparent if no pixel has
+    /// any opacity.
+    pub fn average(&self) -> Pixel {
+        if self.alpha == 0 {
+            return colors::transparent();
+        }
+
+        let weighted_mean = |sum: u64| ((sum + self.alpha / 2) / self.alpha) as u8;
+        let alpha = (self.alpha + self.pixel_count / 2) / self.pixel_count;
+
+        Pixel::new_rgba(
+            weighted_mean(self.red),
+            weighted_mean(self.green),
+            weighted_mean(self.blue),
+            alpha as u8,
+        )
+    }
+}
+
+impl<T: Deref<Target = [Pixel]>> RasterChunk<T> {
+    /// The average color of the chunk, weighting colors by their opacity.
+    pub fn average_color(&self) -> Pixel {
+        let mut color_sum = ColorSum::new();
+        // The rows of a chunk are contiguous, so they are summed as one
+        color_sum.record_row(self.pixels());
+
+        color_sum.average()
+    }
+}
@@ -0,0 +1,149 @@
+//! Square chunks with a size known at compile time, for hot paths where
+//! the compiler can unroll and vectorize the row math.
+
+use crate::{
+    primitives::{dimensions::Dimensions, position::PixelPosition},
+    raster::{
+        pixels::{colors, Pixel},
+        source::Component,
+    },
+};
+
+use super::{raster_chunk::BoxRasterChunk, raster_window::RasterWindow};
+
+/// A square chunk of `N` by `N` pixels. Convert to and from `BoxRasterChunk`
+/// to use it with the rest of the raster module.
+#[derive(Debug, PartialEq, Eq)]
+pub struct FixedRasterChunk<const N: usize> {
+    rows: Box<[[Pixel; N]; N]>,
+}
+
+impl<const N: usize> Clone for FixedRasterChunk<N> {
+    fn clone(&self) -> Self {
+        // Cloning the rows as a slice copies them on the heap, where cloning
+        // the boxed array would copy them through the stack.
+        let rows: Box<[[Pixel; N]]> = self.rows.as_slice().into();
+
+        FixedRasterChunk {
+            rows: rows.try_into().expect("there are exactly N rows"),
+        }
+    }
+}
+
+impl<const N: usize> FixedRasterChunk<N> {
+    /// Creates a chunk that is completely transparent.
+    pub fn new() -> FixedRasterChunk<N> {
+        FixedRasterChunk::new_fill(colors::transparent())
+    }
+
+    pub fn new_fill(pixel: Pixel) -> FixedRasterChunk<N> {
+        // Allocating through a `Vec` keeps large chunks off of the stack.
+        let rows: Box<[[Pixel; N]]> = vec![[pixel; N]; N].into_boxed_slice();
+
+        FixedRasterChunk {
+            rows: rows.try_into().expect("there are exactly N rows"),
+        }
+    }
+
+    pub const fn dimensions() -> Dimensions {
+        Dimensions {
+            width: N,
+            height: N,
+        }
+    }
+
+    pub fn rows(&self) -> &[[Pixel; N]; N] {
+        &self.rows
+    }
+
+    pub fn rows_mut(&mut self) -> &mut [[Pixel; N]; N] {
+        &mut self.rows
+    }
+
+    pub fn pixels(&self) -> &[Pixel] {
+        self.rows.as_flattened()
+    }
+
+    pub fn pixel_at_position(&self, position: PixelPosition) -> Option<Pixel> {
+        self.rows.get(position.1)?.get(position.0).copied()
+    }
+
+    pub fn mut_pixel_at_position(&mut self, position: PixelPosition) -> Option<&mut Pixel> {
+        self.rows.get_mut(position.1)?.get_mut(position.0)
+    }
+
+    /// Takes the whole chunk as a raster window.
+    pub fn as_window(&self) -> RasterWindow<'_> {
+        RasterWindow::from_slice(self.pixels(), N, N).expect("a chunk has N * N pixels")
+    }
+
+    pub fn fill(&mut self, pixel: Pixel) {
+        for row in self.rows.iter_mut() {
+            *row = [pixel; N];
+        }
+    }
+
+    /// Composites `pixel` over every pixel of the chunk, keeping the alpha of
+    /// the chunk's pixels if `preserve_alpha`, as on alpha locked layers.
+    pub fn composite_fill(&mut self, pixel: Pixel, preserve_alpha: bool) {
+        if preserve_alpha {
+            for row in self.rows.iter_mut() {
+                for dest in row.iter_mut() {
+                    dest.composite_over_preserving_alpha(&pixel);
+                }
+            }
+        } else {
+            let source_row = [pixel; N];
+            for row in self.rows.iter_mut() {
+                Pixel::composite_row(row, &source_row);
+            }
+        }
+    }
+
+    /// Copies the chunk into a `BoxRasterChunk`.
+    pub fn to_chunk(&self) -> BoxRasterChunk {
+        BoxRasterChunk::from_vec(self.pixels().to_vec(), N, N).expect("a chunk has N * N pixels")
+    }
+
+    /// Draws another chunk of the same size over this one using alpha
+    /// compositing.
+    pub fn composite_over(&mut self, source: &FixedRasterChunk<N>) {
+        for (dest_row, source_row) in self.rows.iter_mut().zip(source.rows.iter()) {
+            Pixel::composite_row(dest_row, source_row);
+        }
+    }
+}
+
+impl<const N: usize> Default for FixedRasterChunk<N> {
+    fn default() -> Self {
+        FixedRasterChunk::new()
+    }
+}
+
+/// Converts a chunk of `N` by `N` pixels, giving the chunk back if it is of any
+/// other size.
+impl<const N: usize> TryFrom<BoxRasterChunk> for FixedRasterChunk<N> {
+    type Error = BoxRasterChunk;
+
+    fn try_from(raster_chunk: BoxRasterChunk) -> Result<Self, Self::Error> {
+        if raster_chunk.dimensions() != FixedRasterChunk::<N>::dimensions() {
+            return Err(raster_chunk);
+        }
+
+        let rows = raster_chunk
+            .into_pixels()
+            .chunks_exact(N)
+            .map(|row| row.try_into().expect("rows are exactly N pixels"))
+            .collect::<Box<[[Pixel; N]]>>();
+
+        Ok(FixedRasterChunk {
+            rows: rows.try_into().expect("there are exactly N rows"),
+        })
+    }
+}
+
+impl<const N: usize> From<FixedRasterChunk<N>> for BoxRasterChunk {
+    fn from(fixed_chunk: FixedRasterChunk<N>) -> Self {
+        fixed_chunk.to_chunk()
+    }
+}
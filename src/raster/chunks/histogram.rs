@@ -0,0 +1,77 @@
+//! Counts of the values of each channel of the pixels of a chunk, for levels
+//! and auto-contrast adjustments.
+
+use std::ops::Deref;
+
+use super::raster_chunk::RasterChunk;
+use crate::raster::Pixel;
+
+/// The number of pixels with each value of each channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    pub red: [u64; 256],
+    pub green: [u64; 256],
+    pub blue: [u64; 256],
+    pub alpha: [u64; 256],
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram {
+            red: [0; 256],
+            green: [0; 256],
+            blue: [0; 256],
+            alpha: [0; 256],
+        }
+    }
+}
+
+impl Histogram {
+    pub fn new() -> Histogram {
+        Histogram::default()
+    }
+
+    /// Counts `count` pixels of the same color.
+    pub fn record(&mut self, pixel: Pixel, count: u64) {
+        let (r, g, b, a) = pixel.as_rgba();
+
+        self.red[r as usize] += count;
+        self.green[g as usize] += count;
+        self.blue[b as usize] += count;
+        self.alpha[a as usize] += count;
+    }
+
+    pub fn record_pixels<I: IntoIterator<Item = Pixel>>(&mut self, pixels: I) {
+        for pixel in pixels {
+            self.record(pixel, 1);
+        }
+    }
+
+    /// Adds the counts of another histogram to this one.
+    pub fn merge(&mut self, other: &Histogram) {
+        for (counts, other_counts) in [
+            (&mut self.red, &other.red),
+            (&mut self.green, &other.green),
+            (&mut self.blue, &other.blue),
+            (&mut self.alpha, &other.alpha),
+        ] {
+            for (count, other_count) in counts.iter_mut().zip(other_counts.iter()) {
+                *count += other_count;
+            }
+        }
+    }
+
+    /// The number of pixels that have been counted.
+    pub fn pixel_count(&self) -> u64 {
+        self.alpha.iter().sum()
+    }
+}
+
+impl<T: Deref<Target = [Pixel]>> RasterChunk<T> {
+    pub fn histogram(&self) -> Histogram {
+        let mut histogram = Histogram::new();
+        histogram.record_pixels(self.pixels().iter().copied());
+
+        histogram
+    }
+}
@@ -0,0 +1,67 @@
+//! Converting chunks to and from the images of the `image` crate, for
+//! importing and exporting with the wider Rust imaging ecosystem.
+
+use ::image::{Rgba, RgbaImage};
+
+use super::BoxRasterChunk;
+use crate::raster::Pixel;
+
+impl BoxRasterChunk {
+    /// Copies the chunk into an RGBA image of the same size.
+    pub fn to_rgba_image(&self) -> RgbaImage {
+        let width = self.dimensions().width;
+        let pixels = self.pixels();
+
+        RgbaImage::from_fn(width as u32, self.dimensions().height as u32, |x, y| {
+            let (r, g, b, a) = pixels[y as usize * width + x as usize].as_rgba();
+            Rgba([r, g, b, a])
+        })
+    }
+
+    /// Copies an RGBA image into a chunk of the same size.
+    pub fn from_rgba_image(image: &RgbaImage) -> BoxRasterChunk {
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let mut chunk = BoxRasterChunk::new(width, height);
+
+        for (pixel, Rgba([r, g, b, a])) in chunk.pixels_mut().iter_mut().zip(image.pixels()) {
+            *pixel = Pixel::new_rgba(*r, *g, *b, *a);
+        }
+
+        chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        canvas::Canvas,
+        primitives::{dimensions::Dimensions, rect::CanvasRect},
+        raster::RasterLayer,
+    };
+
+    #[test]
+    fn images_are_imported_into_layers_and_exported_from_canvases() {
+        let image = RgbaImage::from_fn(3, 2, |x, y| Rgba([x as u8 * 100, y as u8 * 100, 50, 200]));
+
+        let mut raster_layer = RasterLayer::new(2);
+        let changed_rect = raster_layer.import_image((-1, 1).into(), &image);
+        let image_rect = CanvasRect {
+            top_left: (-1, 1).into(),
+            dimensions: Dimensions {
+                width: 3,
+                height: 2,
+            },
+        };
+        assert_eq!(changed_rect, image_rect);
+        assert_eq!(raster_layer.extract_rect(image_rect).to_rgba_image(), image);
+
+        let mut canvas = Canvas::default();
+        canvas.add_layer(raster_layer.into());
+        let exported = canvas.export_rect(image_rect);
+
+        let expected = canvas.rasterize_canvas_rect(image_rect);
+        assert_eq!(exported.dimensions(), (3, 2));
+        assert_eq!(BoxRasterChunk::from_rgba_image(&exported), expected);
+    }
+}
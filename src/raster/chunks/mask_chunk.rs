@@ -0,0 +1,47 @@
+//! Single channel counterparts of `BoxRasterChunk` and `RasterWindow`, holding
+//! a coverage value from 0 to 255 per pixel at a quarter of the memory.
+
+use std::ops::{Deref, DerefMut};
+
+use crate::raster::Pixel;
+
+use super::{raster_chunk::RasterChunk, raster_window::RasterWindow};
+
+/// A rectangle of coverage values, such as a selection or the transparency of
+/// a raster.
+pub type MaskChunk = RasterChunk<Box<[u8]>>;
+
+/// A reference to a sub-rectangle of a mask chunk.
+pub type MaskWindow<'a> = RasterWindow<'a, u8>;
+
+impl MaskChunk {
+    /// The alpha channel of a raster chunk.
+    pub fn from_alpha<T: Deref<Target = [Pixel]>>(raster_chunk: &RasterChunk<T>) -> MaskChunk {
+        RasterChunk {
+            pixels: raster_chunk
+                .pixels()
+                .iter()
+                .map(|pixel| pixel.as_rgba().3)
+                .collect(),
+            dimensions: raster_chunk.dimensions(),
+        }
+    }
+
+    /// Whether every value of the chunk is 0.
+    pub fn is_empty(&self) -> bool {
+        self.pixels.iter().all(|value| *value == 0)
+    }
+
+    /// Scales the alpha of each pixel of `raster_chunk` by the value at the
+    /// same position, which must have the dimensions of the mask.
+    pub fn mask_alpha<T: DerefMut<Target = [Pixel]>>(&self, raster_chunk: &mut RasterChunk<T>) {
+        if raster_chunk.dimensions() != self.dimensions {
+            return;
+        }
+
+        for (pixel, value) in raster_chunk.pixels_mut().iter_mut().zip(self.pixels.iter()) {
+            let (r, g, b, a) = pixel.as_rgba();
+            *pixel = Pixel::new_rgba(r, g, b, ((a as u32 * *value as u32) / 255) as u8);
+        }
+    }
+}
@@ -5,16 +5,52 @@
 //!
 //! `RasterWindow` is a borrow of some raster data, this can be a full
 //! chunk or part of a `Pixel` slice.
-
+//!
+//! Both are generic over the `Component` stored for each pixel, and
+//! `MaskChunk` and `MaskWindow` are their single channel counterparts, for
+//! masks of coverage values.
+//!
+//! `FixedRasterChunk` is a square chunk with its size fixed at compile time,
+//! converted to and from `BoxRasterChunk` at the boundaries of hot paths.
+//!
+//! `ChunkStorage` holds chunks of few colors compressed, such as those covered
+//! by a single fill, and full chunks of the sizes in `FIXED_CHUNK_SIZES` as
+//! `FixedRasterChunk`s while whole chunks are composited onto.
+//!
+//! Chunks scale with nearest-neighbour sampling by default, or with a smooth
+//! `ScalingFilter`.
+
+pub mod average;
+pub mod fixed_chunk;
+pub mod histogram;
+#[cfg(feature = "image")]
+pub mod image;
+pub mod mask_chunk;
 pub mod nn_map;
+pub mod packed;
+pub mod png;
 pub mod raster_chunk;
 pub mod raster_window;
+pub mod resample;
+pub mod storage;
 mod util;
 
-pub use raster_chunk::BoxRasterChunk;
+pub use average::ColorSum;
+pub use fixed_chunk::FixedRasterChunk;
+pub use histogram::Histogram;
+pub use mask_chunk::{MaskChunk, MaskWindow};
+pub use packed::{PackedRasterChunk, PixelFormat};
+pub use png::PngError;
+pub use raster_chunk::{ArcRasterChunk, BoxRasterChunk};
 pub use raster_window::RasterWindow;
+pub use resample::ScalingFilter;
+pub use storage::ChunkStorage;
+#[doc(hidden)]
 pub use util::translate_rect_position_to_flat_index;
+#[doc(hidden)]
+#[allow(deprecated)]
 pub use util::IndexableByPosition;
+pub use util::InvalidPixelSliceSize;
 
 #[cfg(test)]
 mod tests {
@@ -81,6 +117,46 @@ mod tests {
         assert_raster_eq!(expected_raster_chunk, raster_chunk);
     }
 
+    #[test]
+    fn filling_regions_and_row_spans() {
+        use crate::raster::source::MutRasterSource;
+
+        let draw_rect = DrawRect {
+            top_left: (-2, 3).into(),
+            dimensions: Dimensions {
+                width: 5,
+                height: 9,
+            },
+        };
+
+        let mut raster_chunk = BoxRasterChunk::new(6, 6);
+        raster_chunk.fill_region(draw_rect, colors::red());
+
+        let mut expected = BoxRasterChunk::new(6, 6);
+        expected.fill_rect(colors::red(), draw_rect);
+        assert_raster_eq!(raster_chunk, expected);
+
+        raster_chunk.fill_row_span(0, 4..10, colors::blue());
+        raster_chunk.fill_row_span(1, 7..9, colors::blue());
+        raster_chunk.fill_row_span(6, 0..6, colors::blue());
+        assert_eq!(
+            raster_chunk.row(0).unwrap(),
+            [
+                colors::transparent(),
+                colors::transparent(),
+                colors::transparent(),
+                colors::transparent(),
+                colors::blue(),
+                colors::blue()
+            ]
+        );
+        assert!(raster_chunk
+            .row(1)
+            .unwrap()
+            .iter()
+            .all(|pixel| *pixel == colors::transparent()));
+    }
+
     #[test]
     fn complete_blit() {
         let mut raster_chunk = BoxRasterChunk::new_fill(colors::red(), 8, 8);
@@ -187,6 +263,31 @@ mod tests {
         }
     }
 
+    #[test]
+    fn compositing_stores_opaque_and_skips_clear_pixels() {
+        let backdrop = Pixel::new_rgba(10, 20, 30, 40);
+        let mut raster_chunk = BoxRasterChunk::new_fill(backdrop, 3, 1);
+        let source = BoxRasterChunk::from_vec(
+            vec![
+                Pixel::new_rgb(1, 2, 3),
+                Pixel::new_rgba(1, 2, 3, 0),
+                Pixel::new_rgba(255, 255, 255, 128),
+            ],
+            3,
+            1,
+        )
+        .unwrap();
+
+        raster_chunk.composite_over(&source.as_window(), (0, 0).into());
+
+        let mut composited = backdrop;
+        composited.composite_over(&source.pixels()[2]);
+        assert_eq!(
+            raster_chunk.pixels(),
+            &[Pixel::new_rgb(1, 2, 3), backdrop, composited]
+        );
+    }
+
     #[test]
     fn medium_compositing() {
         let mut raster_chunk = BoxRasterChunk::new_fill(Pixel::new_rgb(128, 128, 128), 8, 8);
@@ -515,4 +616,278 @@ mod tests {
 
         assert_raster_eq!(subsource, expected);
     }
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn rgba_bytes_are_in_pixel_order() {
+        let mut raster_chunk = BoxRasterChunk::new_fill(Pixel::new_rgba(1, 2, 3, 4), 2, 1);
+        raster_chunk.fill_rect(
+            Pixel::new_rgba(5, 6, 7, 8),
+            DrawRect {
+                top_left: (1, 0).into(),
+                dimensions: Dimensions {
+                    width: 1,
+                    height: 1,
+                },
+            },
+        );
+
+        assert_eq!(raster_chunk.as_rgba_bytes(), &[1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn rgba_bytes_round_trip() {
+        let bytes = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+        let raster_chunk = BoxRasterChunk::from_rgba_bytes(&bytes, 3, 1).unwrap();
+
+        assert_eq!(raster_chunk.pixels()[1], Pixel::new_rgba(5, 6, 7, 8));
+        assert_eq!(raster_chunk.as_rgba_bytes(), &bytes);
+        assert!(BoxRasterChunk::from_rgba_bytes(&bytes, 2, 2).is_err());
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let pixels = vec![
+            colors::red(),
+            Pixel::new_rgba(10, 20, 30, 40),
+            colors::transparent(),
+            colors::blue(),
+            colors::white(),
+            Pixel::new_rgba(0, 255, 0, 128),
+        ];
+        let raster_chunk = BoxRasterChunk::from_vec(pixels, 3, 2).unwrap();
+
+        let png = raster_chunk.encode_png().unwrap();
+        assert_eq!(BoxRasterChunk::decode_png(&png).unwrap(), raster_chunk);
+
+        let mut greyscale_png = Vec::new();
+        {
+            let mut encoder = ::png::Encoder::new(&mut greyscale_png, 2, 1);
+            encoder.set_color(::png::ColorType::Grayscale);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[0, 200]).unwrap();
+        }
+        assert_eq!(
+            BoxRasterChunk::decode_png(&greyscale_png).unwrap().pixels(),
+            &[colors::black(), Pixel::new_rgb(200, 200, 200)]
+        );
+
+        assert!(BoxRasterChunk::new(0, 4).encode_png().is_err());
+        assert!(BoxRasterChunk::decode_png(&png[..png.len() / 2]).is_err());
+    }
+
+    #[test]
+    fn png_gamma_is_converted_to_srgb() {
+        let encode_grey = |gamma: Option<f32>, srgb: bool| {
+            let mut png = Vec::new();
+            let mut encoder = ::png::Encoder::new(&mut png, 3, 1);
+            encoder.set_color(::png::ColorType::Grayscale);
+            if let Some(gamma) = gamma {
+                encoder.set_source_gamma(::png::ScaledFloat::new(gamma));
+            }
+            if srgb {
+                encoder.set_source_srgb(::png::SrgbRenderingIntent::Perceptual);
+            }
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[0, 128, 255]).unwrap();
+            drop(writer);
+
+            BoxRasterChunk::decode_png(&png).unwrap()
+        };
+        let greys = |values: [u8; 3]| values.map(|value| Pixel::new_rgb(value, value, value));
+
+        // Linear images are brightened rather than shown as dark as their bytes
+        assert_eq!(
+            encode_grey(Some(1.0), false).pixels(),
+            &greys([0, 188, 255])
+        );
+        assert_eq!(
+            encode_grey(Some(1.0 / 2.2), false).pixels(),
+            &greys([0, 128, 255])
+        );
+        assert_eq!(encode_grey(Some(1.0), true).pixels(), &greys([0, 128, 255]));
+        assert_eq!(encode_grey(None, false).pixels(), &greys([0, 128, 255]));
+    }
+
+    #[test]
+    fn mask_chunks_blit_and_mask_alpha() {
+        use super::MaskChunk;
+
+        let mut mask = MaskChunk::new(4, 4);
+        mask.fill_rect(
+            200,
+            DrawRect {
+                top_left: (-1, -1).into(),
+                dimensions: Dimensions {
+                    width: 3,
+                    height: 3,
+                },
+            },
+        );
+
+        let source = MaskChunk::from_vec(vec![10, 20, 30, 40], 2, 2).unwrap();
+        mask.blit(&source.as_window(), (3, 3).into());
+
+        assert_eq!(
+            mask.pixels(),
+            &[200, 200, 0, 0, 200, 200, 0, 0, 0, 0, 0, 0, 0, 0, 0, 10]
+        );
+        assert!(MaskChunk::from_vec(vec![0; 3], 2, 2).is_err());
+
+        let mut raster_chunk = BoxRasterChunk::new_fill(colors::red(), 4, 4);
+        mask.mask_alpha(&mut raster_chunk);
+        assert_eq!(raster_chunk.pixels()[0].as_rgba().3, 200);
+        assert_eq!(raster_chunk.pixels()[2].as_rgba().3, 0);
+        assert_eq!(MaskChunk::from_alpha(&raster_chunk), mask);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn indexable_by_position_agrees_with_raster_source() {
+        use super::IndexableByPosition;
+
+        let raster_chunk = BoxRasterChunk::new_fill_dynamic(
+            &mut |position| Pixel::new_rgb(position.0 as u8, position.1 as u8, 0),
+            4,
+            4,
+        );
+        let raster_window = RasterWindow::new(&raster_chunk, (1, 1).into(), 2, 2).unwrap();
+
+        let index = raster_window
+            .get_index_from_position((1, 0).into())
+            .unwrap();
+        assert_eq!(
+            Some(raster_chunk.pixels()[index]),
+            RasterSource::pixel_at_position(&raster_window, (1, 0).into())
+        );
+        assert_eq!(raster_window.get_row_slice(1), raster_window.row(1));
+        assert_eq!(raster_window.get_index_from_position((2, 0).into()), None);
+
+        let bounded = raster_chunk.get_index_from_bounded_position((-1, 5).into());
+        assert_eq!(
+            (bounded.index, bounded.x_delta, bounded.y_delta),
+            (12, 1, -2)
+        );
+    }
+
+    #[test]
+    fn fixed_chunks_convert_to_and_from_dynamic_chunks() {
+        use super::FixedRasterChunk;
+
+        let mut raster_chunk = BoxRasterChunk::new(4, 4);
+        raster_chunk.fill_rect(
+            colors::red(),
+            DrawRect {
+                top_left: (1, 2).into(),
+                dimensions: Dimensions {
+                    width: 2,
+                    height: 1,
+                },
+            },
+        );
+
+        let mut fixed_chunk = FixedRasterChunk::<4>::try_from(raster_chunk.clone()).unwrap();
+        assert_eq!(fixed_chunk.rows()[2][1], colors::red());
+        assert_eq!(
+            fixed_chunk.pixel_at_position((3, 3).into()),
+            Some(colors::transparent())
+        );
+        assert_eq!(fixed_chunk.pixel_at_position((4, 0).into()), None);
+
+        let blue = FixedRasterChunk::<4>::new_fill(colors::blue());
+        fixed_chunk.composite_over(&blue);
+        raster_chunk.composite_over(&blue.as_window(), (0, 0).into());
+        assert_eq!(BoxRasterChunk::from(fixed_chunk), raster_chunk);
+
+        assert!(FixedRasterChunk::<8>::try_from(raster_chunk).is_err());
+    }
+
+    #[test]
+    fn masks_composite_and_scale_like_rasters() {
+        use super::{nn_map::NearestNeighbourMap, MaskChunk};
+
+        let mut mask = MaskChunk::new_fill(128, 2, 2);
+        let over = MaskChunk::from_vec(vec![0, 255, 128, 64], 2, 2).unwrap();
+        mask.composite_over(&over.as_window(), (0, 0).into());
+        assert_eq!(mask.pixels(), &[128, 255, 192, 160]);
+
+        let scaled = over.nn_scaled_with_map(&NearestNeighbourMap::new(
+            over.dimensions(),
+            Dimensions {
+                width: 4,
+                height: 4,
+            },
+        ));
+        assert_eq!(scaled.unwrap().pixels()[..4], [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn iterating_over_window_and_chunk_pixels() {
+        let mut raster_chunk = BoxRasterChunk::new(4, 3);
+
+        for (position, pixel) in raster_chunk.enumerate_pixels_mut() {
+            if position.0 == 2 {
+                *pixel = colors::red();
+            }
+        }
+        for row in raster_chunk.iter_rows_mut().skip(2) {
+            row[1] = colors::blue();
+        }
+
+        let raster_window = RasterWindow::new(&raster_chunk, (1, 1).into(), 2, 2).unwrap();
+
+        assert_eq!(
+            raster_window.iter_rows().collect::<Vec<_>>(),
+            [
+                &[colors::transparent(), colors::red()],
+                &[colors::blue(), colors::red()]
+            ]
+        );
+        assert_eq!(
+            raster_window.iter_pixels().collect::<Vec<_>>(),
+            [
+                colors::transparent(),
+                colors::red(),
+                colors::blue(),
+                colors::red()
+            ]
+        );
+        assert_eq!(
+            raster_window
+                .enumerate_pixels()
+                .filter(|(_, pixel)| *pixel == colors::red())
+                .map(|(position, _)| (position.0, position.1))
+                .collect::<Vec<_>>(),
+            [(1, 0), (1, 1)]
+        );
+    }
+
+    #[test]
+    fn arc_chunks_are_shared_across_threads_until_modified() {
+        let mut shared = super::ArcRasterChunk::from(BoxRasterChunk::new_fill(colors::red(), 4, 4));
+        let copy = shared.clone();
+
+        let rendered = std::thread::spawn(move || copy.pixels().to_vec())
+            .join()
+            .unwrap();
+        assert_eq!(rendered, vec![colors::red(); 16]);
+
+        let copy = shared.clone();
+        assert!(shared.get_mut().is_none());
+
+        let mut diverged = shared.diverge();
+        diverged.get_mut().unwrap().fill_rect(
+            colors::blue(),
+            DrawRect::at_origin(Dimensions {
+                width: 2,
+                height: 2,
+            }),
+        );
+        assert_eq!(diverged.pixels()[0], colors::blue());
+        assert_eq!(copy.pixels()[0], colors::red());
+
+        drop(copy);
+        assert!(shared.get_mut().is_some());
+    }
 }
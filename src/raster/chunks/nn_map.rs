@@ -1,13 +1,15 @@
 use bumpalo::Bump;
-use std::{mem::MaybeUninit, ops::DerefMut};
+use lru::LruCache;
+use std::{
+    mem::MaybeUninit,
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex, PoisonError},
+};
 use thiserror::Error;
 
-use crate::{primitives::dimensions::Dimensions, raster::Pixel};
+use crate::{primitives::dimensions::Dimensions, raster::source::Component};
 
-use super::{
-    raster_chunk::{BumpRasterChunk, RasterChunk},
-    translate_rect_position_to_flat_index,
-};
+use super::{raster_chunk::RasterChunk, translate_rect_position_to_flat_index};
 
 #[derive(Error, Debug)]
 pub enum InvalidScaleError {
@@ -53,7 +55,7 @@ impl NearestNeighbourMap {
                     source_dimensions.transform_point((column, row).into(), destination_dimensions);
 
                 let source_index =
-                    translate_rect_position_to_flat_index(nearest.into(), source_dimensions)
+                    translate_rect_position_to_flat_index(nearest, source_dimensions)
                         .expect("transformation should provide position bounded inside source");
                 index_mappings.push(source_index);
             }
@@ -66,7 +68,7 @@ impl NearestNeighbourMap {
         }
     }
 
-    pub fn scale_using_map<S: DerefMut<Target = [Pixel]>, D: DerefMut<Target = [Pixel]>>(
+    pub fn scale_using_map<P: Component, S: Deref<Target = [P]>, D: DerefMut<Target = [P]>>(
         &self,
         source_chunk: &RasterChunk<S>,
         destination_chunk: &mut RasterChunk<D>,
@@ -98,11 +100,11 @@ impl NearestNeighbourMap {
         Ok(())
     }
 
-    pub fn scale_using_map_into_bump<'bump, S: DerefMut<Target = [Pixel]>>(
+    pub fn scale_using_map_into_bump<'bump, P: Component, S: Deref<Target = [P]>>(
         &self,
         source_chunk: &RasterChunk<S>,
         bump: &'bump Bump,
-    ) -> Result<BumpRasterChunk<'bump>, InvalidScaleError> {
+    ) -> Result<RasterChunk<bumpalo::boxed::Box<'bump, [P]>>, InvalidScaleError> {
         if source_chunk.dimensions() != self.source_dimensions {
             return Err(InvalidScaleError::InvalidSourceDimensions {
                 dimensions_given: source_chunk.dimensions(),
@@ -110,7 +112,7 @@ impl NearestNeighbourMap {
             });
         }
 
-        let chunk_pixels: &'bump mut [MaybeUninit<Pixel>] = bump.alloc_slice_fill_copy(
+        let chunk_pixels: &'bump mut [MaybeUninit<P>] = bump.alloc_slice_fill_copy(
             self.destination_dimensions.width * self.destination_dimensions.height,
             MaybeUninit::uninit(),
         );
@@ -132,11 +134,12 @@ impl NearestNeighbourMap {
         // of how it's `#[repr(transparent)]` but the documentation reccomends doing
         // it this way instead
         let chunk_pixels = unsafe {
-            let initialized_pixels = std::mem::transmute::<_, &'bump mut [Pixel]>(chunk_pixels);
+            let initialized_pixels =
+                std::mem::transmute::<&'bump mut [MaybeUninit<P>], &'bump mut [P]>(chunk_pixels);
             bumpalo::boxed::Box::from_raw(initialized_pixels)
         };
 
-        Ok(BumpRasterChunk {
+        Ok(RasterChunk {
             pixels: chunk_pixels,
             dimensions: self.destination_dimensions,
         })
@@ -151,6 +154,72 @@ impl NearestNeighbourMap {
     }
 }
 
+/// A least recently used cache of nearest neighbour maps, keyed by their source
+/// and destination dimensions. Clones of a cache share their maps, so a single
+/// cache can serve the views of several canvases as well as scaling done
+/// elsewhere, such as for thumbnails and exports.
+#[derive(Clone)]
+pub struct NearestNeighbourMapCache(Arc<Mutex<NearestNeighbourMaps>>);
+
+/// Maps keyed by their source and destination dimensions.
+type NearestNeighbourMaps = LruCache<(Dimensions, Dimensions), Arc<NearestNeighbourMap>>;
+
+impl NearestNeighbourMapCache {
+    /// Creates a cache holding at most `capacity` maps.
+    pub fn new(capacity: usize) -> NearestNeighbourMapCache {
+        NearestNeighbourMapCache(Arc::new(Mutex::new(LruCache::new(capacity))))
+    }
+
+    /// The most maps the cache holds.
+    pub fn capacity(&self) -> usize {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner).cap()
+    }
+
+    /// Changes the most maps the cache holds, evicting the least recently used
+    /// maps past the new capacity. Clones of the cache share its capacity.
+    pub fn set_capacity(&self, capacity: usize) {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .resize(capacity);
+    }
+
+    /// The map from `source_dimensions` to `destination_dimensions`, calculating
+    /// it if it isn't cached.
+    pub fn get_nn_map(
+        &self,
+        source_dimensions: Dimensions,
+        destination_dimensions: Dimensions,
+    ) -> Arc<NearestNeighbourMap> {
+        let create_nn_map = || {
+            Arc::new(NearestNeighbourMap::new(
+                source_dimensions,
+                destination_dimensions,
+            ))
+        };
+
+        // A panic while the lock is held can't leave the cache inconsistent
+        let mut nn_maps = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+
+        // The cache can only fail to hold the map if its capacity is 0
+        nn_maps
+            .get_or_insert((source_dimensions, destination_dimensions), create_nn_map)
+            .cloned()
+            .unwrap_or_else(create_nn_map)
+    }
+
+    /// Whether `other` shares its maps with this cache.
+    pub fn shares_maps_with(&self, other: &NearestNeighbourMapCache) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Default for NearestNeighbourMapCache {
+    fn default() -> Self {
+        NearestNeighbourMapCache::new(128)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::{
@@ -159,7 +228,7 @@ mod test {
         raster::{chunks::BoxRasterChunk, Pixel},
     };
 
-    use super::NearestNeighbourMap;
+    use super::{NearestNeighbourMap, NearestNeighbourMapCache};
 
     #[test]
     fn scaling_using_map_is_same_as_without() {
@@ -189,4 +258,34 @@ mod test {
 
         assert_raster_eq!(scaled, expected_scaled);
     }
+
+    #[test]
+    fn cloned_caches_share_maps() {
+        let nn_map_cache = NearestNeighbourMapCache::default();
+        let shared_nn_map_cache = nn_map_cache.clone();
+
+        let source_dimensions = Dimensions {
+            width: 4,
+            height: 2,
+        };
+        let new_dimensions = Dimensions {
+            width: 7,
+            height: 3,
+        };
+
+        let chunk =
+            BoxRasterChunk::from_vec((0..8).map(|i| Pixel::new_rgb(i * 30, 0, 0)).collect(), 4, 2)
+                .unwrap();
+        let mut scaled = chunk.clone();
+        scaled.nn_scale_with_cache(new_dimensions, &nn_map_cache);
+
+        let mut expected_scaled = chunk.clone();
+        expected_scaled.nn_scale(new_dimensions);
+        assert_raster_eq!(scaled, expected_scaled);
+
+        assert!(std::sync::Arc::ptr_eq(
+            &nn_map_cache.get_nn_map(source_dimensions, new_dimensions),
+            &shared_nn_map_cache.get_nn_map(source_dimensions, new_dimensions)
+        ));
+    }
 }
@@ -0,0 +1,110 @@
+//! Compact storage for chunks that are not being drawn on, trading color
+//! precision for memory.
+
+use super::BoxRasterChunk;
+use crate::{primitives::dimensions::Dimensions, raster::Pixel};
+
+/// How the pixels of a layer are stored.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PixelFormat {
+    /// 8 bits for each of red, green, blue and alpha.
+    #[default]
+    Rgba8888,
+    /// 5 bits of red, 6 of green and 5 of blue, with 8 bits of alpha, using
+    /// 3 bytes per pixel instead of 4. Colors are expanded to RGBA when they
+    /// are drawn on or composited.
+    Rgb565A8,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            PixelFormat::Rgba8888 => 4,
+            PixelFormat::Rgb565A8 => 3,
+        }
+    }
+}
+
+fn quantize(component: u8, max: u32) -> u16 {
+    ((component as u32 * max + 127) / 255) as u16
+}
+
+fn expand(component: u16, max: u32) -> u8 {
+    ((component as u32 * 255 + max / 2) / max) as u8
+}
+
+/// A chunk stored as RGB565 color with a separate alpha plane.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackedRasterChunk {
+    colors: Box<[u16]>,
+    alphas: Box<[u8]>,
+    dimensions: Dimensions,
+}
+
+impl PackedRasterChunk {
+    pub fn pack(raster_chunk: &BoxRasterChunk) -> PackedRasterChunk {
+        let (colors, alphas) = raster_chunk
+            .pixels()
+            .iter()
+            .map(|pixel| {
+                let (r, g, b, a) = pixel.as_rgba();
+                let color = (quantize(r, 31) << 11) | (quantize(g, 63) << 5) | quantize(b, 31);
+
+                (color, a)
+            })
+            .unzip::<_, _, Vec<_>, Vec<_>>();
+
+        PackedRasterChunk {
+            colors: colors.into_boxed_slice(),
+            alphas: alphas.into_boxed_slice(),
+            dimensions: raster_chunk.dimensions(),
+        }
+    }
+
+    pub fn unpack(&self) -> BoxRasterChunk {
+        let pixels = self
+            .colors
+            .iter()
+            .zip(self.alphas.iter())
+            .map(|(color, alpha)| {
+                Pixel::new_rgba(
+                    expand(color >> 11, 31),
+                    expand((color >> 5) & 0x3F, 63),
+                    expand(color & 0x1F, 31),
+                    *alpha,
+                )
+            })
+            .collect();
+
+        BoxRasterChunk::from_vec(pixels, self.dimensions.width, self.dimensions.height)
+            .expect("packed chunk should have a pixel for every position")
+    }
+
+    pub fn dimensions(&self) -> Dimensions {
+        self.dimensions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raster::pixels::colors;
+
+    #[test]
+    fn packing_round_trip() {
+        let mut pixels = vec![colors::transparent(); 4];
+        pixels[0] = colors::red();
+        pixels[1] = colors::white();
+        pixels[2] = Pixel::new_rgba(100, 150, 200, 50);
+
+        let raster_chunk = BoxRasterChunk::from_vec(pixels, 2, 2).unwrap();
+        let unpacked = PackedRasterChunk::pack(&raster_chunk).unpack();
+
+        assert_eq!(unpacked.pixels()[0], colors::red());
+        assert_eq!(unpacked.pixels()[1], colors::white());
+        assert_eq!(unpacked.pixels()[3], colors::transparent());
+        assert!(unpacked.pixels()[2].is_close(&Pixel::new_rgba(100, 150, 200, 50), 5));
+        assert_eq!(unpacked.pixels()[2].as_rgba().3, 50);
+    }
+}
@@ -0,0 +1,140 @@
+//! Encoding chunks as PNG images and decoding PNG images into chunks.
+//!
+//! The pixels of chunks are in sRGB, so encoded images are marked as sRGB,
+//! and decoded images that declare another gamma with a `gAMA` chunk are
+//! converted to sRGB. Embedded ICC profiles are not interpreted, and images
+//! with them are assumed to be close enough to sRGB to be used as they are.
+
+use thiserror::Error;
+
+use super::BoxRasterChunk;
+use crate::raster::{pixels::linear_to_srgb, Pixel};
+
+/// How far the gamma of an image can be from the `gAMA` value of 1/2.2 that
+/// approximates sRGB and still be treated as sRGB. Many encoders write that
+/// value for sRGB images without an `sRGB` chunk.
+const SRGB_GAMMA_TOLERANCE: f32 = 0.01;
+
+/// A lookup table converting the color components of an image encoded with
+/// the gamma of its `gAMA` chunk to sRGB, or `None` if the image is already
+/// in sRGB or doesn't say what it is encoded in.
+fn srgb_conversion(info: &::png::Info) -> Option<[u8; 256]> {
+    if info.srgb.is_some() || info.icc_profile.is_some() {
+        return None;
+    }
+
+    let gamma = info.gama_chunk?.into_value();
+    if gamma <= 0.0 || (gamma * 2.2 - 1.0).abs() <= SRGB_GAMMA_TOLERANCE {
+        return None;
+    }
+
+    let mut table = [0; 256];
+    for (encoded, converted) in table.iter_mut().enumerate() {
+        let linear = (encoded as f32 / 255.0).powf(1.0 / gamma);
+        *converted = (linear_to_srgb(linear) * 255.0).round() as u8;
+    }
+
+    Some(table)
+}
+
+#[derive(Error, Debug)]
+pub enum PngError {
+    #[error("failed to decode PNG: {0}")]
+    Decode(#[from] ::png::DecodingError),
+    #[error("failed to encode PNG: {0}")]
+    Encode(#[from] ::png::EncodingError),
+    #[error("PNG images can't be {0}x{1}")]
+    InvalidDimensions(usize, usize),
+    #[error("PNG color type {0:?} with bit depth {1:?} is not supported")]
+    UnsupportedFormat(::png::ColorType, ::png::BitDepth),
+}
+
+impl BoxRasterChunk {
+    /// Encodes the chunk as an RGBA PNG image marked as sRGB. Returns an error
+    /// if the chunk has no pixels, since PNG images can't be empty.
+    pub fn encode_png(&self) -> Result<Vec<u8>, PngError> {
+        let dimensions = self.dimensions();
+        let (width, height) = match (
+            u32::try_from(dimensions.width),
+            u32::try_from(dimensions.height),
+        ) {
+            (Ok(width), Ok(height)) if width > 0 && height > 0 => (width, height),
+            _ => {
+                return Err(PngError::InvalidDimensions(
+                    dimensions.width,
+                    dimensions.height,
+                ))
+            }
+        };
+
+        let bytes: Vec<u8> = self
+            .pixels()
+            .iter()
+            .flat_map(|pixel| {
+                let (r, g, b, a) = pixel.as_rgba();
+                [r, g, b, a]
+            })
+            .collect();
+
+        let mut png = Vec::new();
+        let mut encoder = ::png::Encoder::new(&mut png, width, height);
+        encoder.set_color(::png::ColorType::Rgba);
+        encoder.set_depth(::png::BitDepth::Eight);
+        encoder.set_source_srgb(::png::SrgbRenderingIntent::Perceptual);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&bytes)?;
+        writer.finish()?;
+
+        Ok(png)
+    }
+
+    /// Decodes a PNG image into a chunk of the same size. Images of any color
+    /// type and bit depth are converted to 8 bit RGBA, and images with another
+    /// gamma are converted to sRGB. Only the first frame of animated images is
+    /// decoded.
+    pub fn decode_png(bytes: &[u8]) -> Result<BoxRasterChunk, PngError> {
+        let mut decoder = ::png::Decoder::new(bytes);
+        decoder.set_transformations(::png::Transformations::normalize_to_color8());
+
+        let mut reader = decoder.read_info()?;
+        let srgb_conversion = srgb_conversion(reader.info());
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let frame = reader.next_frame(&mut buffer)?;
+        let bytes = &buffer[..frame.buffer_size()];
+
+        let to_pixels = |channels: usize, f: fn(&[u8]) -> Pixel| -> Vec<Pixel> {
+            bytes.chunks_exact(channels).map(f).collect()
+        };
+
+        let mut pixels = match (frame.color_type, frame.bit_depth) {
+            (::png::ColorType::Rgba, ::png::BitDepth::Eight) => {
+                to_pixels(4, |p| Pixel::new_rgba(p[0], p[1], p[2], p[3]))
+            }
+            (::png::ColorType::Rgb, ::png::BitDepth::Eight) => {
+                to_pixels(3, |p| Pixel::new_rgb(p[0], p[1], p[2]))
+            }
+            (::png::ColorType::GrayscaleAlpha, ::png::BitDepth::Eight) => {
+                to_pixels(2, |p| Pixel::new_rgba(p[0], p[0], p[0], p[1]))
+            }
+            (::png::ColorType::Grayscale, ::png::BitDepth::Eight) => {
+                to_pixels(1, |p| Pixel::new_rgb(p[0], p[0], p[0]))
+            }
+            (color_type, bit_depth) => {
+                return Err(PngError::UnsupportedFormat(color_type, bit_depth))
+            }
+        };
+
+        if let Some(table) = srgb_conversion {
+            for pixel in pixels.iter_mut() {
+                let (r, g, b, a) = pixel.as_rgba();
+                *pixel =
+                    Pixel::new_rgba(table[r as usize], table[g as usize], table[b as usize], a);
+            }
+        }
+
+        let (width, height) = (frame.width as usize, frame.height as usize);
+        BoxRasterChunk::from_vec(pixels, width, height)
+            .map_err(|_| PngError::InvalidDimensions(width, height))
+    }
+}
@@ -1,28 +1,30 @@
 use std::{
     fmt::Display,
-    mem::MaybeUninit,
-    ops::{Deref, DerefMut, Sub},
+    ops::{Deref, DerefMut},
     rc::Rc,
+    slice::{ChunksExactMut, IterMut},
+    sync::Arc,
 };
 
 use bumpalo::Bump;
 
 use crate::{
+    encoding::fnv_hash,
     primitives::{
-        dimensions::Dimensions,
+        dimensions::{Dimensions, GeometryError},
         position::{DrawPosition, PixelPosition, UncheckedIntoPosition},
-        rect::DrawRect,
+        rect::{DrawRect, RasterRect},
     },
     raster::{
         iter::NearestNeighbourMappingIterator,
-        pixels::colors,
-        source::{BoundedPosition, MutRasterSource, RasterSource, Subsource},
+        pixels::BlendMode,
+        source::{Component, MutRasterSource, RasterSource, Subsource},
         Pixel,
     },
 };
 
 use super::{
-    nn_map::{InvalidScaleError, NearestNeighbourMap},
+    nn_map::{InvalidScaleError, NearestNeighbourMap, NearestNeighbourMapCache},
     raster_window::RasterWindow,
     translate_rect_position_to_flat_index,
     util::InvalidPixelSliceSize,
@@ -30,25 +32,30 @@ use super::{
 
 pub type BoxRasterChunk = RasterChunk<Box<[Pixel]>>;
 pub type RcRasterChunk = RasterChunk<Rc<[Pixel]>>;
+/// A chunk that can be shared across threads, such as rendered chunks handed
+/// from a rendering thread to the rest of an application.
+pub type ArcRasterChunk = RasterChunk<Arc<[Pixel]>>;
 pub type BumpRasterChunk<'bump> = RasterChunk<bumpalo::boxed::Box<'bump, [Pixel]>>;
 
-/// A square collection of pixels.
+/// A rectangular collection of pixels, stored in row-major order. The pixel
+/// type is usually `Pixel`, but can be any `Component` such as the coverage
+/// values of a `MaskChunk`.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RasterChunk<T> {
     pub(super) pixels: T,
     pub(super) dimensions: Dimensions,
 }
 
-impl Subsource for BoxRasterChunk {
-    fn subsource_at<'a>(&'a self, subrect: crate::primitives::rect::RasterRect) -> Option<Self>
+impl<P: Component> Subsource for RasterChunk<Box<[P]>> {
+    fn subsource_at(&self, subrect: RasterRect) -> Option<Self>
     where
         Self: Sized,
     {
         Some(self.as_window().subsource_at(subrect)?.to_chunk())
     }
 
-    fn subsource_within_at<'a, S: RasterSource>(
-        &'a self,
+    fn subsource_within_at<S: RasterSource>(
+        &self,
         other: &S,
         position: DrawPosition,
     ) -> Option<Self>
@@ -63,12 +70,14 @@ impl Subsource for BoxRasterChunk {
     }
 }
 
-impl<T: Deref<Target = [Pixel]>> RasterSource for RasterChunk<T> {
+impl<P: Component, T: Deref<Target = [P]>> RasterSource for RasterChunk<T> {
+    type Pixel = P;
+
     fn dimensions(&self) -> Dimensions {
         self.dimensions
     }
 
-    fn row(&self, row_num: usize) -> Option<&[Pixel]> {
+    fn row(&self, row_num: usize) -> Option<&[P]> {
         let row_start_index =
             translate_rect_position_to_flat_index((0, row_num).into(), self.dimensions)?;
         let row_end_index = translate_rect_position_to_flat_index(
@@ -79,11 +88,7 @@ impl<T: Deref<Target = [Pixel]>> RasterSource for RasterChunk<T> {
         Some(&self.pixels[row_start_index..row_end_index + 1])
     }
 
-    fn subrow_from_position(
-        &self,
-        start_position: PixelPosition,
-        width: usize,
-    ) -> Option<&[Pixel]> {
+    fn subrow_from_position(&self, start_position: PixelPosition, width: usize) -> Option<&[P]> {
         let row_start_index =
             translate_rect_position_to_flat_index(start_position, self.dimensions)?;
         let row_end_index = translate_rect_position_to_flat_index(
@@ -94,7 +99,7 @@ impl<T: Deref<Target = [Pixel]>> RasterSource for RasterChunk<T> {
         Some(&self.pixels[row_start_index..row_end_index + 1])
     }
 
-    fn bounded_subrow_from_position(&self, start_position: DrawPosition, width: usize) -> &[Pixel] {
+    fn bounded_subrow_from_position(&self, start_position: DrawPosition, width: usize) -> &[P] {
         let end_position = self
             .dimensions
             .bound_position(start_position + (width as i32 - 1, 0).into())
@@ -109,12 +114,12 @@ impl<T: Deref<Target = [Pixel]>> RasterSource for RasterChunk<T> {
         &self.pixels[row_start_index..row_end_index + 1]
     }
 
-    fn pixel_at_position(&self, position: PixelPosition) -> Option<Pixel> {
+    fn pixel_at_position(&self, position: PixelPosition) -> Option<P> {
         translate_rect_position_to_flat_index(position, self.dimensions)
             .map(|index| self.pixels[index])
     }
 
-    fn pixel_at_bounded_position(&self, position: DrawPosition) -> Pixel {
+    fn pixel_at_bounded_position(&self, position: DrawPosition) -> P {
         self.pixels[translate_rect_position_to_flat_index(
             self.dimensions.bound_position(position).position,
             self.dimensions,
@@ -123,8 +128,8 @@ impl<T: Deref<Target = [Pixel]>> RasterSource for RasterChunk<T> {
     }
 }
 
-impl<T: DerefMut<Target = [Pixel]>> MutRasterSource for RasterChunk<T> {
-    fn mut_row(&mut self, row_num: usize) -> Option<&mut [Pixel]> {
+impl<P: Component, T: DerefMut<Target = [P]>> MutRasterSource for RasterChunk<T> {
+    fn mut_row(&mut self, row_num: usize) -> Option<&mut [P]> {
         let row_start_index =
             translate_rect_position_to_flat_index((0, row_num).into(), self.dimensions)?;
         let row_end_index = translate_rect_position_to_flat_index(
@@ -139,7 +144,7 @@ impl<T: DerefMut<Target = [Pixel]>> MutRasterSource for RasterChunk<T> {
         &mut self,
         start_position: PixelPosition,
         width: usize,
-    ) -> Option<&mut [Pixel]> {
+    ) -> Option<&mut [P]> {
         let row_start_index =
             translate_rect_position_to_flat_index(start_position, self.dimensions)?;
         let row_end_index = translate_rect_position_to_flat_index(
@@ -154,7 +159,7 @@ impl<T: DerefMut<Target = [Pixel]>> MutRasterSource for RasterChunk<T> {
         &mut self,
         start_position: DrawPosition,
         width: usize,
-    ) -> &mut [Pixel] {
+    ) -> &mut [P] {
         let end_position = self
             .dimensions
             .bound_position(start_position + (width as i32 - 1, 0).into())
@@ -169,12 +174,12 @@ impl<T: DerefMut<Target = [Pixel]>> MutRasterSource for RasterChunk<T> {
         &mut self.pixels[row_start_index..row_end_index + 1]
     }
 
-    fn mut_pixel_at_position(&mut self, position: PixelPosition) -> Option<&mut Pixel> {
+    fn mut_pixel_at_position(&mut self, position: PixelPosition) -> Option<&mut P> {
         translate_rect_position_to_flat_index(position, self.dimensions)
             .map(|index| &mut self.pixels[index])
     }
 
-    fn mut_pixel_at_bounded_position(&mut self, position: DrawPosition) -> &mut Pixel {
+    fn mut_pixel_at_bounded_position(&mut self, position: DrawPosition) -> &mut P {
         &mut self.pixels[translate_rect_position_to_flat_index(
             self.dimensions.bound_position(position).position,
             self.dimensions,
@@ -189,11 +194,9 @@ impl<T: Deref<Target = [Pixel]>> Display for RasterChunk<T> {
     }
 }
 
-type RowOperation = fn(&mut [Pixel], &[Pixel]) -> ();
-
-impl<T: Deref<Target = [Pixel]>> RasterChunk<T> {
+impl<P: Component, T: Deref<Target = [P]>> RasterChunk<T> {
     /// Takes the whole chunk as a raster window.
-    pub fn as_window(&self) -> RasterWindow {
+    pub fn as_window(&self) -> RasterWindow<'_, P> {
         RasterWindow {
             backing: self.pixels.as_ref(),
             top_left: (0, 0).into(),
@@ -202,7 +205,7 @@ impl<T: Deref<Target = [Pixel]>> RasterChunk<T> {
         }
     }
 
-    pub fn pixels(&self) -> &[Pixel] {
+    pub fn pixels(&self) -> &[P] {
         &self.pixels
     }
 
@@ -211,30 +214,182 @@ impl<T: Deref<Target = [Pixel]>> RasterChunk<T> {
     }
 }
 
+impl BoxRasterChunk {
+    /// Creates a chunk from RGBA8 bytes in row-major order, the layout of
+    /// images decoded by most image crates and of the data of a JS `ImageData`.
+    /// Returns an error if there aren't 4 bytes for each pixel, with the
+    /// `buffer_size` of the error being the number of bytes.
+    pub fn from_rgba_bytes(
+        bytes: &[u8],
+        width: usize,
+        height: usize,
+    ) -> Result<BoxRasterChunk, InvalidPixelSliceSize> {
+        if width * height * 4 != bytes.len() {
+            return Err(InvalidPixelSliceSize {
+                desired_width: width,
+                desired_height: height,
+                buffer_size: bytes.len(),
+            });
+        }
+
+        // `Pixel` stores red in its lowest byte, so each pixel is a
+        // little-endian `u32`, which compiles down to a copy on little-endian
+        // targets.
+        let pixels = bytes
+            .chunks_exact(4)
+            .map(|rgba| Pixel(u32::from_le_bytes([rgba[0], rgba[1], rgba[2], rgba[3]])))
+            .collect();
+
+        BoxRasterChunk::from_vec(pixels, width, height)
+    }
+}
+
+impl<T: Deref<Target = [Pixel]>> RasterChunk<T> {
+    /// The pixels of the chunk as RGBA8 bytes in row-major order, without
+    /// copying. Only available on little-endian targets, where the byte order
+    /// of a `Pixel` is already RGBA.
+    #[cfg(target_endian = "little")]
+    pub fn as_rgba_bytes(&self) -> &[u8] {
+        let pixels: &[Pixel] = &self.pixels;
+
+        // SAFETY: `Pixel` is a transparent wrapper around `u32`, which has no
+        // padding and stricter alignment than `u8`.
+        unsafe {
+            std::slice::from_raw_parts(pixels.as_ptr() as *const u8, std::mem::size_of_val(pixels))
+        }
+    }
+
+    /// A hash of the dimensions and pixel contents of the chunk. The hash
+    /// is stable across processes so it can be compared between peers.
+    pub fn content_hash(&self) -> u64 {
+        let dimensions = [self.dimensions.width, self.dimensions.height];
+
+        fnv_hash(
+            dimensions
+                .into_iter()
+                .map(|length| length as u32 as u64)
+                .chain(self.pixels.iter().map(|pixel| pixel.0 as u64)),
+        )
+    }
+}
+
 impl<T: DerefMut<Target = [Pixel]>> RasterChunk<T> {
+    /// Draws a render window onto the raster chunk at `dest_position`, blending
+    /// colors with `blend_mode` before alpha compositing. If the window at
+    /// `dest_position` is not contained within the chunk, the portion of the
+    /// destination outside the chunk is ignored.
+    pub fn blend_over<S: RasterSource<Pixel = Pixel> + Subsource>(
+        &mut self,
+        source: &S,
+        dest_position: DrawPosition,
+        blend_mode: BlendMode,
+    ) {
+        self.perform_zipped_row_operation(source, dest_position, |d, s| {
+            for (pixel_d, pixel_s) in d.iter_mut().zip(s.iter()) {
+                pixel_d.blend_over(pixel_s, blend_mode);
+            }
+        });
+    }
+
+    /// Copies another raster onto this chunk like `blit`, but keeps the alpha
+    /// of the pixels of this chunk so only their colors change.
+    pub fn blit_preserving_alpha<S: RasterSource<Pixel = Pixel> + Subsource>(
+        &mut self,
+        source: &S,
+        dest_position: DrawPosition,
+    ) {
+        self.perform_zipped_row_operation(source, dest_position, |d, s| {
+            for (pixel_d, pixel_s) in d.iter_mut().zip(s.iter()) {
+                pixel_d.blit_preserving_alpha(pixel_s);
+            }
+        });
+    }
+
+    /// Composites another raster over this chunk like `composite_over`, but
+    /// keeps the alpha of the pixels of this chunk so only their colors change.
+    pub fn composite_over_preserving_alpha<S: RasterSource<Pixel = Pixel> + Subsource>(
+        &mut self,
+        source: &S,
+        dest_position: DrawPosition,
+    ) {
+        self.perform_zipped_row_operation(source, dest_position, |d, s| {
+            for (pixel_d, pixel_s) in d.iter_mut().zip(s.iter()) {
+                pixel_d.composite_over_preserving_alpha(pixel_s);
+            }
+        });
+    }
+}
+
+impl<P: Component, T: DerefMut<Target = [P]>> RasterChunk<T> {
+    pub fn pixels_mut(&mut self) -> &mut [P] {
+        &mut self.pixels
+    }
+
+    /// Iterates mutably over the rows of the chunk from top to bottom. Use
+    /// `as_window` to iterate without mutating.
+    pub fn iter_rows_mut(&mut self) -> ChunksExactMut<'_, P> {
+        let width = self.dimensions.width;
+
+        // Chunks without width have no pixels, so any non-zero chunk size works
+        self.pixels.chunks_exact_mut(width.max(1))
+    }
+
+    /// Iterates mutably over the pixels of the chunk in row-major order.
+    pub fn iter_pixels_mut(&mut self) -> IterMut<'_, P> {
+        self.pixels.iter_mut()
+    }
+
+    /// Iterates mutably over the pixels of the chunk in row-major order along
+    /// with their positions in the chunk.
+    pub fn enumerate_pixels_mut<'s>(
+        &'s mut self,
+    ) -> impl Iterator<Item = (PixelPosition, &'s mut P)> + 's
+    where
+        P: 's,
+    {
+        self.iter_rows_mut().enumerate().flat_map(|(y, row)| {
+            row.iter_mut()
+                .enumerate()
+                .map(move |(x, pixel)| ((x, y).into(), pixel))
+        })
+    }
+
+    /// Performs an operation on each row of the part of `draw_rect` contained
+    /// in the chunk.
     fn perform_row_operation<F>(&mut self, draw_rect: DrawRect, operation: &mut F)
     where
-        F: FnMut(&mut [Pixel]),
+        F: FnMut(&mut [P]),
     {
-        for row_num in 0..draw_rect.dimensions.height {
-            if row_num >= self.dimensions.height + draw_rect.top_left.1 as usize {
-                break;
+        let contained_rect = match draw_rect.subrect_contained_in(self.dimensions) {
+            Some(contained_rect) if !contained_rect.is_degenerate() => contained_rect,
+            _ => return,
+        };
+
+        let top_left: PixelPosition = (
+            (draw_rect.top_left.0 + contained_rect.top_left.0 as i32) as usize,
+            (draw_rect.top_left.1 + contained_rect.top_left.1 as i32) as usize,
+        )
+            .into();
+
+        for row_num in 0..contained_rect.dimensions.height {
+            if let Some(dest_slice) = self.mut_subrow_from_position(
+                top_left + (0, row_num).into(),
+                contained_rect.dimensions.width,
+            ) {
+                operation(dest_slice)
             }
-
-            let dest_slice = self.mut_bounded_subrow_from_position(
-                draw_rect.top_left + (0, row_num as i32).into(),
-                draw_rect.dimensions.width,
-            );
-            operation(dest_slice)
         }
     }
 
-    fn perform_zipped_row_operation<S: RasterSource + Subsource>(
+    fn perform_zipped_row_operation<S, F>(
         &mut self,
         source: &S,
         dest_position: DrawPosition,
-        operation: RowOperation,
-    ) {
+        mut operation: F,
+    ) where
+        S: RasterSource<Pixel = P> + Subsource,
+        F: FnMut(&mut [P], &[P]),
+    {
         let bounded_top_left = self.bound_position(dest_position);
         if let Some(shrunk_source) = source.subsource_within_at(&*self, dest_position) {
             for row_num in 0..shrunk_source.dimensions().height {
@@ -259,27 +414,28 @@ impl<T: DerefMut<Target = [Pixel]>> RasterChunk<T> {
     /// Blits a render window onto the raster chunk at `dest_position`.
     /// If the window at `dest_position` is not contained within the chunk,
     /// the portion of the destination outside the chunk is ignored.
-    pub fn blit<S: RasterSource + Subsource>(&mut self, source: &S, dest_position: DrawPosition) {
+    pub fn blit<S: RasterSource<Pixel = P> + Subsource>(
+        &mut self,
+        source: &S,
+        dest_position: DrawPosition,
+    ) {
         self.perform_zipped_row_operation(source, dest_position, |d, s| d.copy_from_slice(s));
     }
 
-    pub fn fill_rect(&mut self, pixel: Pixel, draw_rect: DrawRect) {
+    /// Sets the pixels within `draw_rect`, ignoring the portion outside the chunk.
+    pub fn fill_rect(&mut self, pixel: P, draw_rect: DrawRect) {
         self.perform_row_operation(draw_rect, &mut |d| d.fill(pixel));
     }
 
     /// Draws a render window onto the raster chunk at `dest_position` using alpha compositing.
     /// If the window at `dest_position` is not contained within the chunk,
     /// the portion of the destination outside the chunk is ignored.
-    pub fn composite_over<S: RasterSource + Subsource>(
+    pub fn composite_over<S: RasterSource<Pixel = P> + Subsource>(
         &mut self,
         source: &S,
         dest_position: DrawPosition,
     ) {
-        self.perform_zipped_row_operation(source, dest_position, |d, s| {
-            for (pixel_d, pixel_s) in d.iter_mut().zip(s.iter()) {
-                pixel_d.composite_over(pixel_s);
-            }
-        });
+        self.perform_zipped_row_operation(source, dest_position, P::composite_row);
     }
 
     /// Shift the pixels in a raster chunk horizontally to the left. Pixels
@@ -355,13 +511,13 @@ impl<T: DerefMut<Target = [Pixel]>> RasterChunk<T> {
     }
 }
 
-impl BoxRasterChunk {
-    pub fn into_pixels(self) -> Box<[Pixel]> {
+impl<P: Component> RasterChunk<Box<[P]>> {
+    pub fn into_pixels(self) -> Box<[P]> {
         self.pixels
     }
 
     /// Create a new raster chunk filled in with a pixel value.
-    pub fn new_fill(pixel: Pixel, width: usize, height: usize) -> BoxRasterChunk {
+    pub fn new_fill(pixel: P, width: usize, height: usize) -> Self {
         let pixels = vec![pixel; width * height];
 
         RasterChunk {
@@ -371,11 +527,11 @@ impl BoxRasterChunk {
     }
 
     /// Create a new raster chunk where each pixel value is filled in by a closure given the pixel's location.
-    pub fn new_fill_dynamic<F>(f: &mut F, width: usize, height: usize) -> BoxRasterChunk
+    pub fn new_fill_dynamic<F>(f: &mut F, width: usize, height: usize) -> Self
     where
-        F: FnMut(PixelPosition) -> Pixel,
+        F: FnMut(PixelPosition) -> P,
     {
-        let mut pixels = vec![colors::transparent(); width * height];
+        let mut pixels = vec![P::empty(); width * height];
 
         for row in 0..width {
             for column in 0..height {
@@ -390,16 +546,32 @@ impl BoxRasterChunk {
     }
 
     /// Create a new raster chunk that is completely transparent.
-    pub fn new(width: usize, height: usize) -> BoxRasterChunk {
-        BoxRasterChunk::new_fill(colors::transparent(), width, height)
+    pub fn new(width: usize, height: usize) -> Self {
+        Self::new_fill(P::empty(), width, height)
+    }
+
+    /// Creates a raster chunk filled with a pixel value like
+    /// `RasterChunk::new_fill`, returning an error instead of allocating if
+    /// the dimensions don't pass `Dimensions::try_new`.
+    pub fn try_new_fill(pixel: P, width: usize, height: usize) -> Result<Self, GeometryError> {
+        let Dimensions { width, height } = Dimensions::try_new(width, height)?;
+
+        Ok(Self::new_fill(pixel, width, height))
+    }
+
+    /// Creates a transparent raster chunk like `RasterChunk::new`, returning
+    /// an error instead of allocating if the dimensions don't pass
+    /// `Dimensions::try_new`.
+    pub fn try_new(width: usize, height: usize) -> Result<Self, GeometryError> {
+        Self::try_new_fill(P::empty(), width, height)
     }
 
     /// Creates a raster chunk from
     pub fn from_vec(
-        pixels: Vec<Pixel>,
+        pixels: Vec<P>,
         width: usize,
         height: usize,
-    ) -> Result<RasterChunk<Box<[Pixel]>>, InvalidPixelSliceSize> {
+    ) -> Result<RasterChunk<Box<[P]>>, InvalidPixelSliceSize> {
         if width * height != pixels.len() {
             Err(InvalidPixelSliceSize {
                 desired_height: height,
@@ -424,8 +596,8 @@ impl BoxRasterChunk {
     }
 
     /// A chunk scaled to a new size using the nearest-neighbour algorithm.
-    pub fn nn_scaled(&mut self, new_size: Dimensions) -> BoxRasterChunk {
-        let mut new_chunk = BoxRasterChunk::new(new_size.width, new_size.height);
+    pub fn nn_scaled(&mut self, new_size: Dimensions) -> Self {
+        let mut new_chunk = Self::new(new_size.width, new_size.height);
 
         for (dest_position, source_position) in
             NearestNeighbourMappingIterator::new(self.dimensions, new_size)
@@ -451,8 +623,7 @@ impl BoxRasterChunk {
         }
 
         let destination_dimensions = nn_map.destination_dimensions();
-        let mut new_chunk =
-            BoxRasterChunk::new(destination_dimensions.width, destination_dimensions.height);
+        let mut new_chunk = Self::new(destination_dimensions.width, destination_dimensions.height);
 
         nn_map.scale_using_map(self, &mut new_chunk)?;
 
@@ -465,24 +636,51 @@ impl BoxRasterChunk {
     pub fn nn_scaled_with_map(
         &self,
         nn_map: &NearestNeighbourMap,
-    ) -> Result<BoxRasterChunk, InvalidScaleError> {
+    ) -> Result<Self, InvalidScaleError> {
         let destination_dimensions = nn_map.destination_dimensions();
-        let mut new_chunk =
-            BoxRasterChunk::new(destination_dimensions.width, destination_dimensions.height);
+        let mut new_chunk = Self::new(destination_dimensions.width, destination_dimensions.height);
 
         nn_map.scale_using_map(self, &mut new_chunk)?;
 
         Ok(new_chunk)
     }
 
+    /// Scales the chunk to a new size using the nearest-neighbour algorithm,
+    /// reusing the mapping from `nn_map_cache` if it has been calculated before.
+    pub fn nn_scale_with_cache(
+        &mut self,
+        new_size: Dimensions,
+        nn_map_cache: &NearestNeighbourMapCache,
+    ) {
+        if new_size == self.dimensions {
+            return;
+        }
+
+        *self = self.nn_scaled_with_cache(new_size, nn_map_cache);
+    }
+
+    /// A chunk scaled to a new size using the nearest-neighbour algorithm,
+    /// reusing the mapping from `nn_map_cache` if it has been calculated before.
+    pub fn nn_scaled_with_cache(
+        &self,
+        new_size: Dimensions,
+        nn_map_cache: &NearestNeighbourMapCache,
+    ) -> Self {
+        let nn_map = nn_map_cache.get_nn_map(self.dimensions, new_size);
+
+        self.nn_scaled_with_map(&nn_map)
+            .expect("nn_map should be fetched with size of chunk")
+    }
+
     /// Scales the chunk by a factor using the nearest-neighbour algorithm and
     /// place the result into a bump.
     pub fn nn_scale_into_bump<'bump>(
         &mut self,
         new_size: Dimensions,
         bump: &'bump Bump,
-    ) -> BumpRasterChunk<'bump> {
-        let mut new_chunk = BumpRasterChunk::new(new_size.width, new_size.height, bump);
+    ) -> RasterChunk<bumpalo::boxed::Box<'bump, [P]>> {
+        let mut new_chunk =
+            RasterChunk::<bumpalo::boxed::Box<[P]>>::new(new_size.width, new_size.height, bump);
 
         for (dest_position, source_position) in
             NearestNeighbourMappingIterator::new(self.dimensions, new_size)
@@ -504,21 +702,26 @@ impl BoxRasterChunk {
         &mut self,
         nn_map: &NearestNeighbourMap,
         bump: &'bump Bump,
-    ) -> Result<BumpRasterChunk<'bump>, InvalidScaleError> {
+    ) -> Result<RasterChunk<bumpalo::boxed::Box<'bump, [P]>>, InvalidScaleError> {
         nn_map.scale_using_map_into_bump(self, bump)
     }
 }
 
-impl<'bump> BumpRasterChunk<'bump> {
-    pub fn into_pixels(self) -> bumpalo::boxed::Box<'bump, [Pixel]> {
+impl<'bump, P: Component> RasterChunk<bumpalo::boxed::Box<'bump, [P]>> {
+    pub fn into_pixels(self) -> bumpalo::boxed::Box<'bump, [P]> {
         self.pixels
     }
 
     /// Create a new raster chunk filled in with a pixel value.
-    pub fn new_fill(pixel: Pixel, width: usize, height: usize, bump: &Bump) -> BumpRasterChunk {
+    pub fn new_fill(
+        pixel: P,
+        width: usize,
+        height: usize,
+        bump: &Bump,
+    ) -> RasterChunk<bumpalo::boxed::Box<'_, [P]>> {
         let pixels = bumpalo::vec![in bump; pixel; width * height];
 
-        BumpRasterChunk {
+        RasterChunk {
             pixels: pixels.into_boxed_slice(),
             dimensions: Dimensions { width, height },
         }
@@ -526,20 +729,24 @@ impl<'bump> BumpRasterChunk<'bump> {
 
     /// Create a new raster chunk where each pixel value is filled in by a closure given the pixel's location.
     pub fn new_fill_dynamic(
-        f: fn(PixelPosition) -> Pixel,
+        f: fn(PixelPosition) -> P,
         width: usize,
         height: usize,
         bump: &Bump,
-    ) -> BumpRasterChunk {
+    ) -> RasterChunk<bumpalo::boxed::Box<'_, [P]>> {
         let dimensions = Dimensions { width, height };
         let pixels = bumpalo::boxed::Box::from_iter_in(dimensions.iter_pixels().map(f), bump);
 
-        BumpRasterChunk { pixels, dimensions }
+        RasterChunk { pixels, dimensions }
     }
 
     /// Create a new raster chunk that is completely transparent.
-    pub fn new(width: usize, height: usize, bump: &Bump) -> BumpRasterChunk {
-        BumpRasterChunk::new_fill(colors::transparent(), width, height, bump)
+    pub fn new(
+        width: usize,
+        height: usize,
+        bump: &Bump,
+    ) -> RasterChunk<bumpalo::boxed::Box<'_, [P]>> {
+        Self::new_fill(P::empty(), width, height, bump)
     }
 
     /// Scales the chunk by a factor using the nearest-neighbour algorithm and
@@ -548,8 +755,9 @@ impl<'bump> BumpRasterChunk<'bump> {
         &mut self,
         new_size: Dimensions,
         bump: &'other_bump Bump,
-    ) -> BumpRasterChunk<'other_bump> {
-        let mut new_chunk = BumpRasterChunk::new(new_size.width, new_size.height, bump);
+    ) -> RasterChunk<bumpalo::boxed::Box<'other_bump, [P]>> {
+        let mut new_chunk =
+            RasterChunk::<bumpalo::boxed::Box<[P]>>::new(new_size.width, new_size.height, bump);
 
         for (dest_position, source_position) in
             NearestNeighbourMappingIterator::new(self.dimensions, new_size)
@@ -571,14 +779,14 @@ impl<'bump> BumpRasterChunk<'bump> {
         &mut self,
         nn_map: &NearestNeighbourMap,
         bump: &'other_bump Bump,
-    ) -> Result<BumpRasterChunk<'other_bump>, InvalidScaleError> {
+    ) -> Result<RasterChunk<bumpalo::boxed::Box<'other_bump, [P]>>, InvalidScaleError> {
         nn_map.scale_using_map_into_bump(self, bump)
     }
 }
 
-impl RcRasterChunk {
+impl<P: Component> RasterChunk<Rc<[P]>> {
     /// Create a new raster chunk filled in with a pixel value.
-    pub fn new_fill(pixel: Pixel, width: usize, height: usize) -> RcRasterChunk {
+    pub fn new_fill(pixel: P, width: usize, height: usize) -> Self {
         let pixels = vec![pixel; width * height];
 
         RasterChunk {
@@ -588,12 +796,8 @@ impl RcRasterChunk {
     }
 
     /// Create a new raster chunk where each pixel value is filled in by a closure given the pixel's location.
-    pub fn new_fill_dynamic(
-        f: fn(PixelPosition) -> Pixel,
-        width: usize,
-        height: usize,
-    ) -> RcRasterChunk {
-        let mut pixels = vec![colors::transparent(); width * height];
+    pub fn new_fill_dynamic(f: fn(PixelPosition) -> P, width: usize, height: usize) -> Self {
+        let mut pixels = vec![P::empty(); width * height];
 
         for row in 0..width {
             for column in 0..height {
@@ -608,13 +812,13 @@ impl RcRasterChunk {
     }
 
     /// Create a new raster chunk that is completely transparent.
-    pub fn new(width: usize, height: usize) -> RcRasterChunk {
-        RcRasterChunk::new_fill(colors::transparent(), width, height)
+    pub fn new(width: usize, height: usize) -> Self {
+        Self::new_fill(P::empty(), width, height)
     }
 }
 
-impl RcRasterChunk {
-    pub fn get_mut(&mut self) -> Option<RasterChunk<&mut [Pixel]>> {
+impl<P: Component> RasterChunk<Rc<[P]>> {
+    pub fn get_mut(&mut self) -> Option<RasterChunk<&mut [P]>> {
         let pixels = Rc::get_mut(&mut self.pixels)?;
 
         Some(RasterChunk {
@@ -624,25 +828,105 @@ impl RcRasterChunk {
     }
 
     pub fn diverge(&self) -> Self {
-        let mut pixels = Box::new_uninit_slice(self.pixels.len());
+        let pixels = Rc::from(&*self.pixels);
 
-        MaybeUninit::write_slice(&mut pixels, &*self.pixels);
+        RasterChunk {
+            pixels,
+            dimensions: self.dimensions,
+        }
+    }
+}
 
-        let pixels = unsafe { pixels.assume_init() };
-        let pixels = Rc::from(pixels);
+impl<P: Component> From<RasterChunk<Box<[P]>>> for RasterChunk<Rc<[P]>> {
+    fn from(box_raster_chunk: RasterChunk<Box<[P]>>) -> Self {
+        RasterChunk {
+            pixels: Rc::from(box_raster_chunk.pixels),
+            dimensions: box_raster_chunk.dimensions,
+        }
+    }
+}
+
+impl<P: Component> RasterChunk<Arc<[P]>> {
+    /// Create a new raster chunk filled in with a pixel value.
+    pub fn new_fill(pixel: P, width: usize, height: usize) -> Self {
+        let pixels = vec![pixel; width * height];
+
+        RasterChunk {
+            pixels: Arc::from(pixels.into_boxed_slice()),
+            dimensions: Dimensions { width, height },
+        }
+    }
+
+    /// Create a new raster chunk that is completely transparent.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self::new_fill(P::empty(), width, height)
+    }
+
+    /// The pixels of the chunk to modify, or `None` if they are shared with
+    /// another copy of the chunk.
+    pub fn get_mut(&mut self) -> Option<RasterChunk<&mut [P]>> {
+        let pixels = Arc::get_mut(&mut self.pixels)?;
+
+        Some(RasterChunk {
+            pixels,
+            dimensions: self.dimensions,
+        })
+    }
+
+    /// A copy of the chunk that doesn't share its pixels with any other.
+    pub fn diverge(&self) -> Self {
+        let pixels = Arc::from(&*self.pixels);
 
-        RcRasterChunk {
+        RasterChunk {
             pixels,
             dimensions: self.dimensions,
         }
     }
 }
 
-impl From<BoxRasterChunk> for RcRasterChunk {
-    fn from(box_raster_chunk: BoxRasterChunk) -> Self {
-        RcRasterChunk {
-            pixels: Rc::from(box_raster_chunk.pixels),
+impl<P: Component> From<RasterChunk<Box<[P]>>> for RasterChunk<Arc<[P]>> {
+    fn from(box_raster_chunk: RasterChunk<Box<[P]>>) -> Self {
+        RasterChunk {
+            pixels: Arc::from(box_raster_chunk.pixels),
             dimensions: box_raster_chunk.dimensions,
         }
     }
 }
+
+/// Chunks are serialized as their width and height followed by their pixels
+/// in row-major order.
+#[cfg(feature = "serde")]
+impl<P: Component + serde::Serialize, T: Deref<Target = [P]>> serde::Serialize for RasterChunk<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("RasterChunk", 3)?;
+        state.serialize_field("width", &self.dimensions.width)?;
+        state.serialize_field("height", &self.dimensions.height)?;
+        state.serialize_field("pixels", &*self.pixels)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, P: Component + serde::Deserialize<'de>> serde::Deserialize<'de>
+    for RasterChunk<Box<[P]>>
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        #[serde(rename = "RasterChunk")]
+        struct SerializedRasterChunk<P> {
+            width: usize,
+            height: usize,
+            pixels: Vec<P>,
+        }
+
+        let SerializedRasterChunk {
+            width,
+            height,
+            pixels,
+        } = SerializedRasterChunk::deserialize(deserializer)?;
+
+        RasterChunk::from_vec(pixels, width, height).map_err(serde::de::Error::custom)
+    }
+}
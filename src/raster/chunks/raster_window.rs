@@ -1,29 +1,29 @@
-use std::{fmt::Display, mem::MaybeUninit, ops::Deref};
+use std::{fmt::Display, ops::Deref};
 
 use bumpalo::Bump;
 
 use crate::{
     primitives::{
         dimensions::Dimensions,
-        position::{DrawPosition, PixelPosition, UncheckedIntoPosition},
+        position::{DrawPosition, PixelPosition},
         rect::{DrawRect, RasterRect},
     },
     raster::{
-        source::{BoundedPosition, RasterSource, Subsource},
+        source::{Component, RasterSource, Subsource},
         Pixel,
     },
 };
 
 use super::{
-    raster_chunk::{BoxRasterChunk, BumpRasterChunk, RasterChunk},
+    raster_chunk::RasterChunk,
     translate_rect_position_to_flat_index,
     util::{display_raster_row, InvalidPixelSliceSize},
 };
 
 /// A reference to a sub-rectangle of a raster chunk.
 #[derive(Debug, Clone, Copy)]
-pub struct RasterWindow<'a> {
-    pub(super) backing: &'a [Pixel],
+pub struct RasterWindow<'a, P = Pixel> {
+    pub(super) backing: &'a [P],
     pub(super) top_left: PixelPosition,
     pub(super) dimensions: Dimensions,
     pub(super) backing_dimensions: Dimensions,
@@ -32,10 +32,7 @@ pub struct RasterWindow<'a> {
 impl<'a> Display for RasterWindow<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut s = String::new();
-        for row_num in 0..self.dimensions.height {
-            let row_slice = self
-                .row(row_num)
-                .expect("row_num should always be less than height");
+        for row_slice in self.iter_rows() {
             s += "|";
             s += display_raster_row(row_slice).as_str();
             s += "|\n";
@@ -45,14 +42,14 @@ impl<'a> Display for RasterWindow<'a> {
     }
 }
 
-impl<'a> RasterWindow<'a> {
+impl<'a, P: Component> RasterWindow<'a, P> {
     /// Creates a raster chunk window from a sub-rectangle of a raster chunk. The window area must be completely contained in the chunk.
-    pub fn new<T: Deref<Target = [Pixel]>>(
+    pub fn new<T: Deref<Target = [P]>>(
         chunk: &'a RasterChunk<T>,
         top_left: PixelPosition,
         width: usize,
         height: usize,
-    ) -> Option<RasterWindow<'a>> {
+    ) -> Option<RasterWindow<'a, P>> {
         let over_width = top_left.0 + width > chunk.dimensions().width;
         let over_height = top_left.1 + height > chunk.dimensions().height;
         if over_width || over_height {
@@ -69,10 +66,10 @@ impl<'a> RasterWindow<'a> {
 
     /// Creates a window from the entirety of a slice, the rectangle's area must be exactly the size of the slice.
     pub fn from_slice(
-        slice: &'a [Pixel],
+        slice: &'a [P],
         width: usize,
         height: usize,
-    ) -> Result<RasterWindow<'a>, InvalidPixelSliceSize> {
+    ) -> Result<RasterWindow<'a, P>, InvalidPixelSliceSize> {
         if width * height != slice.len() {
             Err(InvalidPixelSliceSize {
                 desired_height: height,
@@ -97,7 +94,7 @@ impl<'a> RasterWindow<'a> {
         bottom: usize,
         left: usize,
         right: usize,
-    ) -> Option<RasterWindow<'a>> {
+    ) -> Option<RasterWindow<'a, P>> {
         if left + right >= self.dimensions.width || top + bottom >= self.dimensions.height {
             return None;
         }
@@ -125,74 +122,36 @@ impl<'a> RasterWindow<'a> {
     }
 
     /// Creates a raster chunk by copying the data in a window.
-    pub fn to_chunk(&self) -> BoxRasterChunk {
-        let mut chunk_pixels: Box<[MaybeUninit<Pixel>]> =
-            Box::new_uninit_slice(self.dimensions.width * self.dimensions.height);
+    pub fn to_chunk(&self) -> RasterChunk<Box<[P]>> {
+        let mut chunk_pixels = Vec::with_capacity(self.dimensions.width * self.dimensions.height);
 
         for row in 0..self.dimensions.height {
-            let row_start_position = (0, row);
-            let row_start_source_index = translate_rect_position_to_flat_index(
-                self.top_left + row_start_position.into(),
-                self.backing_dimensions,
-            )
-            .expect("position should be in source by construction");
-            let row_end_position = (self.dimensions.width - 1, row);
-            let row_end_source_index = translate_rect_position_to_flat_index(
-                self.top_left + row_end_position.into(),
-                self.backing_dimensions,
-            )
-            .expect("position should be in source by construction");
-            let row_start_new_index = row * self.dimensions.width;
-            let row_end_new_index = row * self.dimensions.width + self.dimensions.width - 1;
-
-            MaybeUninit::write_slice(
-                &mut chunk_pixels[row_start_new_index..(row_end_new_index + 1)],
-                &self.backing[row_start_source_index..(row_end_source_index + 1)],
-            );
+            chunk_pixels.extend_from_slice(self.row(row).expect("row should be less than height"));
         }
 
-        // We initialize the entire chunk within the for loop, so this is sound
-        let chunk_pixels = unsafe { chunk_pixels.assume_init() };
-
-        BoxRasterChunk {
-            pixels: chunk_pixels,
+        RasterChunk {
+            pixels: chunk_pixels.into_boxed_slice(),
             dimensions: self.dimensions,
         }
     }
 
     /// Creates a raster chunk in a bump by copying the data in a window.
-    pub fn to_chunk_into_bump<'bump>(&self, bump: &'bump Bump) -> BumpRasterChunk<'bump> {
-        let chunk_pixels: &'bump mut [MaybeUninit<Pixel>] = bump.alloc_slice_fill_copy(
+    pub fn to_chunk_into_bump<'bump>(
+        &self,
+        bump: &'bump Bump,
+    ) -> RasterChunk<bumpalo::boxed::Box<'bump, [P]>> {
+        let mut chunk_pixels = bumpalo::collections::Vec::with_capacity_in(
             self.dimensions.width * self.dimensions.height,
-            MaybeUninit::uninit(),
+            bump,
         );
 
         for row in 0..self.dimensions.height {
-            let row_start_position = (0, row);
-            let row_start_source_index =
-                translate_rect_position_to_flat_index(row_start_position.into(), self.dimensions)
-                    .expect("position should be in source by construction");
-            let row_end_position = (self.dimensions.width - 1, row);
-            let row_end_source_index =
-                translate_rect_position_to_flat_index(row_end_position.into(), self.dimensions)
-                    .expect("position should be in source by construction");
-            let row_start_new_index = row * self.dimensions.width;
-            let row_end_new_index = row * self.dimensions.width + self.dimensions.width - 1;
-            MaybeUninit::write_slice(
-                &mut chunk_pixels[row_start_new_index..(row_end_new_index + 1)],
-                &self.backing[row_start_source_index..(row_end_source_index + 1)],
-            );
+            chunk_pixels.extend_from_slice(self.row(row).expect("row should be less than height"));
         }
 
-        // Technically we could transmute `chunk_pixels` into `bumpalo::boxed::Box` because
-        // of how it's `#[repr(transparent)]` but the documentation reccomends doing
-        // it this way instead
-        let chunk_pixels = unsafe {
-            let initialized_pixels = std::mem::transmute::<_, &'bump mut [Pixel]>(chunk_pixels);
-            bumpalo::boxed::Box::from_raw(initialized_pixels)
-        };
+        let chunk_pixels = chunk_pixels.into_boxed_slice();
 
-        BumpRasterChunk {
+        RasterChunk {
             pixels: chunk_pixels,
             dimensions: self.dimensions,
         }
@@ -201,10 +160,41 @@ impl<'a> RasterWindow<'a> {
     pub fn dimensions(&self) -> Dimensions {
         self.dimensions
     }
+
+    /// Iterates over the rows of the window from top to bottom.
+    pub fn iter_rows(&self) -> impl Iterator<Item = &'a [P]> + 'a {
+        let RasterWindow {
+            backing,
+            top_left,
+            dimensions,
+            backing_dimensions,
+        } = *self;
+
+        (0..dimensions.height).map(move |row_num| {
+            let row_start = (top_left.1 + row_num) * backing_dimensions.width + top_left.0;
+
+            &backing[row_start..row_start + dimensions.width]
+        })
+    }
+
+    /// Iterates over the pixels of the window in row-major order.
+    pub fn iter_pixels(&self) -> impl Iterator<Item = P> + 'a {
+        self.iter_rows().flatten().copied()
+    }
+
+    /// Iterates over the pixels of the window in row-major order along with
+    /// their positions in the window.
+    pub fn enumerate_pixels(&self) -> impl Iterator<Item = (PixelPosition, P)> + 'a {
+        self.iter_rows().enumerate().flat_map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .map(move |(x, pixel)| ((x, y).into(), *pixel))
+        })
+    }
 }
 
-impl<'s> Subsource for RasterWindow<'s> {
-    fn subsource_at<'a>(&'a self, subrect: RasterRect) -> Option<Self>
+impl<'s, P: Component> Subsource for RasterWindow<'s, P> {
+    fn subsource_at(&self, subrect: RasterRect) -> Option<Self>
     where
         Self: Sized,
     {
@@ -213,13 +203,13 @@ impl<'s> Subsource for RasterWindow<'s> {
             .then_some(RasterWindow {
                 backing: self.backing,
                 backing_dimensions: self.backing_dimensions,
-                top_left: self.top_left.translate(subrect.top_left.into()),
+                top_left: self.top_left.translate(subrect.top_left),
                 dimensions: subrect.dimensions,
             })
     }
 
-    fn subsource_within_at<'a, S: RasterSource>(
-        &'a self,
+    fn subsource_within_at<S: RasterSource>(
+        &self,
         other: &S,
         position: DrawPosition,
     ) -> Option<Self>
@@ -239,12 +229,14 @@ impl<'s> Subsource for RasterWindow<'s> {
     }
 }
 
-impl<'s> RasterSource for RasterWindow<'s> {
+impl<'s, P: Component> RasterSource for RasterWindow<'s, P> {
+    type Pixel = P;
+
     fn dimensions(&self) -> Dimensions {
         self.dimensions
     }
 
-    fn row(&self, row_num: usize) -> Option<&[Pixel]> {
+    fn row(&self, row_num: usize) -> Option<&[P]> {
         let row_start_offset = (0, row_num).into();
         let row_end_offset = (self.dimensions.width - 1, row_num).into();
 
@@ -264,11 +256,7 @@ impl<'s> RasterSource for RasterWindow<'s> {
         Some(&self.backing[row_start_index..row_end_index + 1])
     }
 
-    fn subrow_from_position(
-        &self,
-        start_position: PixelPosition,
-        width: usize,
-    ) -> Option<&[Pixel]> {
+    fn subrow_from_position(&self, start_position: PixelPosition, width: usize) -> Option<&[P]> {
         let row_end_offset = start_position + (width - 1, 0).into();
 
         if !self.dimensions.contains(start_position) || !self.dimensions.contains(row_end_offset) {
@@ -287,7 +275,7 @@ impl<'s> RasterSource for RasterWindow<'s> {
         Some(&self.backing[row_start_index..row_end_index + 1])
     }
 
-    fn bounded_subrow_from_position(&self, start_position: DrawPosition, width: usize) -> &[Pixel] {
+    fn bounded_subrow_from_position(&self, start_position: DrawPosition, width: usize) -> &[P] {
         let end_position = self
             .dimensions
             .bound_position(start_position + (width as i32 - 1, 0).into())
@@ -307,7 +295,7 @@ impl<'s> RasterSource for RasterWindow<'s> {
         &self.backing[row_start_index..row_end_index + 1]
     }
 
-    fn pixel_at_position(&self, position: PixelPosition) -> Option<Pixel> {
+    fn pixel_at_position(&self, position: PixelPosition) -> Option<P> {
         self.dimensions
             .contains(position)
             .then_some(
@@ -320,7 +308,7 @@ impl<'s> RasterSource for RasterWindow<'s> {
             .flatten()
     }
 
-    fn pixel_at_bounded_position(&self, position: DrawPosition) -> Pixel {
+    fn pixel_at_bounded_position(&self, position: DrawPosition) -> P {
         self.backing[translate_rect_position_to_flat_index(
             self.dimensions.bound_position(position).position,
             self.dimensions,
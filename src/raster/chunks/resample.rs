@@ -0,0 +1,442 @@
+//! Smooth scaling of raster chunks, for when nearest-neighbour scaling looks
+//! too blocky, such as when zoomed out.
+//!
+//! Filters are applied separably on premultiplied colors, so transparent
+//! pixels don't bleed their color into their neighbours. When downscaling,
+//! the filters are widened to cover every source pixel that contributes to a
+//! destination pixel.
+
+use std::ops::{Deref, DerefMut};
+
+use bumpalo::Bump;
+
+use crate::{
+    primitives::{dimensions::Dimensions, position::CanvasPoint, transform::Affine},
+    raster::{iter::NearestNeighbourMappingIterator, pixels::colors, Pixel},
+};
+
+use super::raster_chunk::{BoxRasterChunk, BumpRasterChunk, RasterChunk};
+
+/// How pixels are interpolated when a raster is scaled.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum ScalingFilter {
+    /// Each pixel takes the value of the nearest source pixel. This is the
+    /// fastest filter and keeps edges sharp, but looks blocky.
+    #[default]
+    NearestNeighbour,
+    /// Linear interpolation between the nearest source pixels.
+    Bilinear,
+    /// Cubic interpolation with a Catmull-Rom spline, which is smooth but
+    /// keeps edges sharper than bilinear filtering.
+    Bicubic,
+}
+
+impl ScalingFilter {
+    /// The distance from its middle at which the filter stops contributing,
+    /// in source pixels when not downscaling.
+    fn radius(&self) -> f32 {
+        match self {
+            ScalingFilter::NearestNeighbour => 0.5,
+            ScalingFilter::Bilinear => 1.0,
+            ScalingFilter::Bicubic => 2.0,
+        }
+    }
+
+    fn weight(&self, x: f32) -> f32 {
+        let x = x.abs();
+
+        match self {
+            ScalingFilter::NearestNeighbour => {
+                if x < 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            ScalingFilter::Bilinear => (1.0 - x).max(0.0),
+            ScalingFilter::Bicubic => {
+                if x < 1.0 {
+                    1.5 * x * x * x - 2.5 * x * x + 1.0
+                } else if x < 2.0 {
+                    -0.5 * x * x * x + 2.5 * x * x - 4.0 * x + 2.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+/// The source pixels contributing to a destination pixel along one axis,
+/// starting at `start`.
+struct Taps {
+    start: usize,
+    weights: Vec<f32>,
+}
+
+fn axis_taps(source_length: usize, destination_length: usize, filter: ScalingFilter) -> Vec<Taps> {
+    let ratio = source_length as f32 / destination_length as f32;
+    let filter_scale = ratio.max(1.0);
+    let support = filter.radius() * filter_scale;
+
+    (0..destination_length)
+        .map(|destination_index| {
+            let center = (destination_index as f32 + 0.5) * ratio;
+            let start = ((center - support).floor().max(0.0) as usize).min(source_length - 1);
+            let end = ((center + support).ceil() as usize).clamp(start + 1, source_length);
+
+            let mut weights: Vec<f32> = (start..end)
+                .map(|source_index| {
+                    filter.weight((source_index as f32 + 0.5 - center) / filter_scale)
+                })
+                .collect();
+
+            let total: f32 = weights.iter().sum();
+            if total.abs() > f32::EPSILON {
+                weights.iter_mut().for_each(|weight| *weight /= total);
+            } else {
+                // Only possible when the window is cut off by an edge
+                weights.iter_mut().for_each(|weight| *weight = 0.0);
+                let nearest = ((center as usize).clamp(start, end - 1)) - start;
+                weights[nearest] = 1.0;
+            }
+
+            Taps { start, weights }
+        })
+        .collect()
+}
+
+fn premultiplied(pixel: Pixel) -> [f32; 4] {
+    let (r, g, b, a) = pixel.as_rgba();
+    let alpha = a as f32 / 255.0;
+
+    [
+        r as f32 * alpha,
+        g as f32 * alpha,
+        b as f32 * alpha,
+        a as f32,
+    ]
+}
+
+fn unpremultiplied(color: [f32; 4]) -> Pixel {
+    let a = color[3].clamp(0.0, 255.0);
+
+    if a < 0.5 {
+        return colors::transparent();
+    }
+
+    let channel = |c: f32| (c * 255.0 / a).round().clamp(0.0, 255.0) as u8;
+
+    Pixel::new_rgba(
+        channel(color[0]),
+        channel(color[1]),
+        channel(color[2]),
+        a.round() as u8,
+    )
+}
+
+impl<T: Deref<Target = [Pixel]>> RasterChunk<T> {
+    /// Scales the chunk into `destination`, which determines the size it is
+    /// scaled to.
+    pub fn scale_into<D: DerefMut<Target = [Pixel]>>(
+        &self,
+        destination: &mut RasterChunk<D>,
+        filter: ScalingFilter,
+    ) {
+        let source_dimensions = self.dimensions;
+        let destination_dimensions = destination.dimensions;
+
+        if source_dimensions.width == 0
+            || source_dimensions.height == 0
+            || destination_dimensions.width == 0
+            || destination_dimensions.height == 0
+        {
+            return;
+        }
+
+        if filter == ScalingFilter::NearestNeighbour {
+            for (destination_position, source_position) in
+                NearestNeighbourMappingIterator::new(source_dimensions, destination_dimensions)
+            {
+                destination.pixels[destination_position.1 * destination_dimensions.width
+                    + destination_position.0] =
+                    self.pixels[source_position.1 * source_dimensions.width + source_position.0];
+            }
+
+            return;
+        }
+
+        let column_taps = axis_taps(
+            source_dimensions.width,
+            destination_dimensions.width,
+            filter,
+        );
+        let row_taps = axis_taps(
+            source_dimensions.height,
+            destination_dimensions.height,
+            filter,
+        );
+
+        // Scale each row horizontally, then the columns of the result vertically
+        let mut horizontally_scaled =
+            Vec::with_capacity(destination_dimensions.width * source_dimensions.height);
+        for source_row in self.pixels.chunks_exact(source_dimensions.width) {
+            for taps in column_taps.iter() {
+                let mut color = [0.0; 4];
+
+                for (pixel, weight) in source_row[taps.start..].iter().zip(taps.weights.iter()) {
+                    let pixel_color = premultiplied(*pixel);
+                    for channel in 0..4 {
+                        color[channel] += pixel_color[channel] * weight;
+                    }
+                }
+
+                horizontally_scaled.push(color);
+            }
+        }
+
+        for (destination_row, taps) in destination
+            .pixels
+            .chunks_exact_mut(destination_dimensions.width)
+            .zip(row_taps.iter())
+        {
+            for (column, destination_pixel) in destination_row.iter_mut().enumerate() {
+                let mut color = [0.0; 4];
+
+                for (row_offset, weight) in taps.weights.iter().enumerate() {
+                    let source_color = horizontally_scaled
+                        [(taps.start + row_offset) * destination_dimensions.width + column];
+                    for channel in 0..4 {
+                        color[channel] += source_color[channel] * weight;
+                    }
+                }
+
+                *destination_pixel = unpremultiplied(color);
+            }
+        }
+    }
+
+    /// A chunk scaled to a new size with `filter`.
+    pub fn scaled(&self, new_size: Dimensions, filter: ScalingFilter) -> BoxRasterChunk {
+        let mut new_chunk = BoxRasterChunk::new(new_size.width, new_size.height);
+        self.scale_into(&mut new_chunk, filter);
+
+        new_chunk
+    }
+
+    /// The chunk drawn through a transform about its top left, sampled
+    /// bilinearly, with the offset of the top left of the result from the
+    /// origin. Returns `None` if the transform isn't invertible.
+    pub fn transformed(&self, transform: &Affine) -> Option<(CanvasPoint, BoxRasterChunk)> {
+        let inverse = transform.inverse()?;
+        let Dimensions { width, height } = self.dimensions;
+
+        let (right, bottom) = (width as f32, height as f32);
+        let corners = [(0.0, 0.0), (right, 0.0), (0.0, bottom), (right, bottom)]
+            .into_iter()
+            .map(|corner| transform.apply_snapped(corner.into()));
+        let (min, max) = corners.fold(
+            (
+                (f32::INFINITY, f32::INFINITY),
+                (f32::NEG_INFINITY, f32::NEG_INFINITY),
+            ),
+            |(min, max), corner| {
+                (
+                    (min.0.min(corner.0), min.1.min(corner.1)),
+                    (max.0.max(corner.0), max.1.max(corner.1)),
+                )
+            },
+        );
+
+        let offset: CanvasPoint = (min.0.floor(), min.1.floor()).into();
+        let new_size = Dimensions {
+            width: (max.0.ceil() - offset.0) as usize,
+            height: (max.1.ceil() - offset.1) as usize,
+        };
+
+        let source_pixel = |x: i64, y: i64| {
+            if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+                [0.0; 4]
+            } else {
+                premultiplied(self.pixels[y as usize * width + x as usize])
+            }
+        };
+
+        let pixels = new_size
+            .iter_pixels()
+            .map(|position| {
+                let source = inverse.apply(
+                    (
+                        position.0 as f32 + 0.5 + offset.0,
+                        position.1 as f32 + 0.5 + offset.1,
+                    )
+                        .into(),
+                );
+                let (x, y) = (source.0 - 0.5, source.1 - 0.5);
+                let (left, top) = (x.floor(), y.floor());
+                let (tx, ty) = (x - left, y - top);
+                let (left, top) = (left as i64, top as i64);
+
+                let mut color = [0.0; 4];
+                for (dx, dy, weight) in [
+                    (0, 0, (1.0 - tx) * (1.0 - ty)),
+                    (1, 0, tx * (1.0 - ty)),
+                    (0, 1, (1.0 - tx) * ty),
+                    (1, 1, tx * ty),
+                ] {
+                    let sample = source_pixel(left + dx, top + dy);
+                    for (channel, sample) in color.iter_mut().zip(sample) {
+                        *channel += sample * weight;
+                    }
+                }
+
+                unpremultiplied(color)
+            })
+            .collect();
+
+        let chunk = BoxRasterChunk::from_vec(pixels, new_size.width, new_size.height)
+            .expect("a pixel is produced for every position of the new size");
+
+        Some((offset, chunk))
+    }
+
+    /// Fills `destination` with the pixels of this chunk nearest to where
+    /// `transform` maps the centers of its pixels to. Pixels mapped outside of
+    /// this chunk are transparent.
+    pub fn sample_nearest_into<D: DerefMut<Target = [Pixel]>>(
+        &self,
+        destination: &mut RasterChunk<D>,
+        transform: &Affine,
+    ) {
+        let Dimensions { width, height } = self.dimensions;
+        let destination_width = destination.dimensions.width;
+
+        for (index, pixel) in destination.pixels.iter_mut().enumerate() {
+            let source = transform.apply(
+                (
+                    (index % destination_width) as f32 + 0.5,
+                    (index / destination_width) as f32 + 0.5,
+                )
+                    .into(),
+            );
+            let (x, y) = (source.0.floor(), source.1.floor());
+
+            *pixel = if x < 0.0 || y < 0.0 || x >= width as f32 || y >= height as f32 {
+                colors::transparent()
+            } else {
+                self.pixels[y as usize * width + x as usize]
+            };
+        }
+    }
+
+    /// A chunk scaled to a new size with bilinear filtering.
+    pub fn bilinear_scaled(&self, new_size: Dimensions) -> BoxRasterChunk {
+        self.scaled(new_size, ScalingFilter::Bilinear)
+    }
+
+    /// A chunk scaled to a new size with bicubic filtering.
+    pub fn bicubic_scaled(&self, new_size: Dimensions) -> BoxRasterChunk {
+        self.scaled(new_size, ScalingFilter::Bicubic)
+    }
+
+    /// A chunk scaled to a new size with `filter`, placed into a bump.
+    pub fn scaled_into_bump<'bump>(
+        &self,
+        new_size: Dimensions,
+        filter: ScalingFilter,
+        bump: &'bump Bump,
+    ) -> BumpRasterChunk<'bump> {
+        let mut new_chunk = BumpRasterChunk::new(new_size.width, new_size.height, bump);
+        self.scale_into(&mut new_chunk, filter);
+
+        new_chunk
+    }
+
+    /// A chunk scaled to a new size with bilinear filtering, placed into a bump.
+    pub fn bilinear_scaled_into_bump<'bump>(
+        &self,
+        new_size: Dimensions,
+        bump: &'bump Bump,
+    ) -> BumpRasterChunk<'bump> {
+        self.scaled_into_bump(new_size, ScalingFilter::Bilinear, bump)
+    }
+
+    /// A chunk scaled to a new size with bicubic filtering, placed into a bump.
+    pub fn bicubic_scaled_into_bump<'bump>(
+        &self,
+        new_size: Dimensions,
+        bump: &'bump Bump,
+    ) -> BumpRasterChunk<'bump> {
+        self.scaled_into_bump(new_size, ScalingFilter::Bicubic, bump)
+    }
+}
+
+impl BoxRasterChunk {
+    /// Scales the chunk to a new size with bilinear filtering.
+    pub fn bilinear_scale(&mut self, new_size: Dimensions) {
+        if new_size != self.dimensions {
+            *self = self.bilinear_scaled(new_size);
+        }
+    }
+
+    /// Scales the chunk to a new size with bicubic filtering.
+    pub fn bicubic_scale(&mut self, new_size: Dimensions) {
+        if new_size != self.dimensions {
+            *self = self.bicubic_scaled(new_size);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScalingFilter;
+    use crate::{
+        assert_raster_eq,
+        primitives::dimensions::Dimensions,
+        raster::{
+            chunks::BoxRasterChunk,
+            pixels::colors,
+            source::{MutRasterSource, RasterSource},
+            Pixel,
+        },
+    };
+
+    #[test]
+    fn smooth_filters_interpolate_without_bleeding_transparency() {
+        let mut chunk = BoxRasterChunk::new(2, 1);
+        *chunk.mut_pixel_at_position((0, 0).into()).unwrap() = colors::red();
+        *chunk.mut_pixel_at_position((1, 0).into()).unwrap() = colors::blue();
+
+        let scaled = chunk.bilinear_scaled(Dimensions {
+            width: 4,
+            height: 1,
+        });
+        let middle = scaled.pixel_at_position((1, 0).into()).unwrap();
+        assert!(middle.is_close(&Pixel::new_rgb(191, 0, 64), 2));
+
+        // A transparent pixel's color doesn't show in its neighbours
+        let mut faded = BoxRasterChunk::new(2, 1);
+        *faded.mut_pixel_at_position((0, 0).into()).unwrap() = colors::green();
+        for filter in [ScalingFilter::Bilinear, ScalingFilter::Bicubic] {
+            let scaled = faded.scaled(
+                Dimensions {
+                    width: 4,
+                    height: 1,
+                },
+                filter,
+            );
+            let (_, g, _, a) = scaled.pixel_at_position((1, 0).into()).unwrap().as_rgba();
+            assert_eq!(g, 255);
+            assert!(a > 0 && a < 255);
+        }
+
+        // Downscaling a flat color keeps it
+        let flat = BoxRasterChunk::new_fill(colors::blue(), 9, 9);
+        let expected = BoxRasterChunk::new_fill(colors::blue(), 2, 2);
+        let downscaled = flat.bicubic_scaled(Dimensions {
+            width: 2,
+            height: 2,
+        });
+        assert_raster_eq!(downscaled, expected);
+    }
+}
@@ -0,0 +1,301 @@
+//! Storage for the chunks of raster layers. Chunks made of few colors, such
+//! as the chunks covered by a single large fill, are stored compressed, and
+//! layers with a packed pixel format store their chunks packed.
+//!
+//! Chunks of the sizes in `FIXED_CHUNK_SIZES` that whole-chunk fills are
+//! composited onto are stored as `FixedRasterChunk`s, so the row math of the
+//! fills has bounds known at compile time. They are converted to and from
+//! `BoxRasterChunk` when they are drawn on or read in any other way.
+
+use std::{borrow::Cow, mem};
+
+use super::{BoxRasterChunk, FixedRasterChunk, PackedRasterChunk, PixelFormat};
+use crate::{
+    primitives::dimensions::Dimensions,
+    raster::{pixels::colors, Pixel},
+};
+
+/// The chunk sizes stored as `FixedRasterChunk`s.
+pub const FIXED_CHUNK_SIZES: [usize; 2] = [128, 256];
+
+/// The pixels of a chunk, stored in whichever form takes the least memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChunkStorage {
+    /// Every pixel of the chunk is the same.
+    Uniform(Pixel),
+    /// Runs of the same pixel and their lengths, in row-major order.
+    Rle(Vec<(Pixel, u32)>),
+    /// The pixels in the packed `PixelFormat::Rgb565A8`.
+    Packed(PackedRasterChunk),
+    Full(BoxRasterChunk),
+    /// A full chunk of 128 by 128 pixels with its size known at compile time.
+    Fixed128(FixedRasterChunk<128>),
+    /// A full chunk of 256 by 256 pixels with its size known at compile time.
+    Fixed256(FixedRasterChunk<256>),
+}
+
+impl ChunkStorage {
+    /// Stores a chunk in the most compact form for its pixels. Chunks with
+    /// more runs than would save memory are kept as they are.
+    pub fn compress(chunk: BoxRasterChunk) -> ChunkStorage {
+        let pixels = chunk.pixels();
+        let max_runs = mem::size_of_val(pixels) / 2 / mem::size_of::<(Pixel, u32)>();
+
+        let mut runs: Vec<(Pixel, u32)> = Vec::new();
+        for pixel in pixels {
+            match runs.last_mut() {
+                Some((run_pixel, length)) if run_pixel == pixel => *length += 1,
+                _ => {
+                    if runs.len() >= max_runs.max(1) {
+                        return ChunkStorage::Full(chunk);
+                    }
+
+                    runs.push((*pixel, 1));
+                }
+            }
+        }
+
+        match runs.as_slice() {
+            [(pixel, _)] => ChunkStorage::Uniform(*pixel),
+            _ => ChunkStorage::Rle(runs),
+        }
+    }
+
+    /// Stores a full chunk as a `FixedRasterChunk` if it is of a size in
+    /// `FIXED_CHUNK_SIZES`.
+    fn fixed_or_full(chunk: BoxRasterChunk) -> ChunkStorage {
+        let chunk = match FixedRasterChunk::<128>::try_from(chunk) {
+            Ok(fixed_chunk) => return ChunkStorage::Fixed128(fixed_chunk),
+            Err(chunk) => chunk,
+        };
+
+        match FixedRasterChunk::<256>::try_from(chunk) {
+            Ok(fixed_chunk) => ChunkStorage::Fixed256(fixed_chunk),
+            Err(chunk) => ChunkStorage::Full(chunk),
+        }
+    }
+
+    /// Whether the chunk is stored in less memory than its pixels take.
+    pub fn is_compressed(&self) -> bool {
+        !matches!(
+            self,
+            ChunkStorage::Full(_) | ChunkStorage::Fixed128(_) | ChunkStorage::Fixed256(_)
+        )
+    }
+
+    /// Whether the stored pixels are those of a chunk of `dimensions`.
+    pub fn fits(&self, dimensions: Dimensions) -> bool {
+        match self {
+            ChunkStorage::Uniform(_) => true,
+            ChunkStorage::Rle(runs) => {
+                runs.iter()
+                    .map(|(_, length)| *length as usize)
+                    .sum::<usize>()
+                    == dimensions.area()
+            }
+            ChunkStorage::Packed(packed_chunk) => packed_chunk.dimensions() == dimensions,
+            ChunkStorage::Full(chunk) => chunk.dimensions() == dimensions,
+            ChunkStorage::Fixed128(_) => FixedRasterChunk::<128>::dimensions() == dimensions,
+            ChunkStorage::Fixed256(_) => FixedRasterChunk::<256>::dimensions() == dimensions,
+        }
+    }
+
+    /// The bytes taken by the stored pixels.
+    pub fn byte_size(&self) -> usize {
+        match self {
+            ChunkStorage::Uniform(_) => mem::size_of::<Pixel>(),
+            ChunkStorage::Rle(runs) => runs.len() * mem::size_of::<(Pixel, u32)>(),
+            ChunkStorage::Packed(packed_chunk) => {
+                packed_chunk.dimensions().area() * PixelFormat::Rgb565A8.bytes_per_pixel()
+            }
+            ChunkStorage::Full(chunk) => mem::size_of_val(chunk.pixels()),
+            ChunkStorage::Fixed128(fixed_chunk) => mem::size_of_val(fixed_chunk.pixels()),
+            ChunkStorage::Fixed256(fixed_chunk) => mem::size_of_val(fixed_chunk.pixels()),
+        }
+    }
+
+    /// The pixels of the chunk, expanded to `dimensions` if they are
+    /// compressed.
+    pub fn to_chunk(&self, dimensions: Dimensions) -> Cow<'_, BoxRasterChunk> {
+        match self {
+            ChunkStorage::Uniform(pixel) => Cow::Owned(BoxRasterChunk::new_fill(
+                *pixel,
+                dimensions.width,
+                dimensions.height,
+            )),
+            ChunkStorage::Rle(runs) => {
+                let pixels = runs
+                    .iter()
+                    .flat_map(|(pixel, length)| std::iter::repeat_n(*pixel, *length as usize))
+                    .collect();
+
+                Cow::Owned(
+                    BoxRasterChunk::from_vec(pixels, dimensions.width, dimensions.height)
+                        .expect("runs of a chunk should cover every pixel of it"),
+                )
+            }
+            ChunkStorage::Packed(packed_chunk) => Cow::Owned(packed_chunk.unpack()),
+            ChunkStorage::Full(chunk) => Cow::Borrowed(chunk),
+            ChunkStorage::Fixed128(fixed_chunk) => Cow::Owned(fixed_chunk.to_chunk()),
+            ChunkStorage::Fixed256(fixed_chunk) => Cow::Owned(fixed_chunk.to_chunk()),
+        }
+    }
+
+    /// The pixels of the chunk for drawing on, expanding them into a full
+    /// chunk of `dimensions` first if they are stored in another form.
+    pub fn to_mut(&mut self, dimensions: Dimensions) -> &mut BoxRasterChunk {
+        if !matches!(self, ChunkStorage::Full(_)) {
+            *self = ChunkStorage::Full(self.to_chunk(dimensions).into_owned());
+        }
+
+        match self {
+            ChunkStorage::Full(chunk) => chunk,
+            _ => unreachable!("chunk storage should be made full above"),
+        }
+    }
+
+    /// Composites `pixel` over every pixel of a chunk of `dimensions`, keeping
+    /// their alpha if `preserve_alpha`. Compressed chunks stay compressed
+    /// where they can, and full chunks of a size in `FIXED_CHUNK_SIZES` are
+    /// stored as `FixedRasterChunk`s to composite onto.
+    pub fn composite_fill(&mut self, pixel: Pixel, dimensions: Dimensions, preserve_alpha: bool) {
+        let composite = |dest: &mut Pixel| {
+            if preserve_alpha {
+                dest.composite_over_preserving_alpha(&pixel);
+            } else {
+                dest.composite_over(&pixel);
+            }
+        };
+
+        if !preserve_alpha && pixel.as_rgba().3 == 255 {
+            *self = ChunkStorage::Uniform(pixel);
+            return;
+        }
+
+        match self {
+            ChunkStorage::Uniform(dest) => composite(dest),
+            ChunkStorage::Rle(runs) => runs.iter_mut().for_each(|(dest, _)| composite(dest)),
+            ChunkStorage::Fixed128(fixed_chunk) => {
+                fixed_chunk.composite_fill(pixel, preserve_alpha)
+            }
+            ChunkStorage::Fixed256(fixed_chunk) => {
+                fixed_chunk.composite_fill(pixel, preserve_alpha)
+            }
+            ChunkStorage::Full(_) | ChunkStorage::Packed(_) => {
+                let chunk = mem::replace(self, ChunkStorage::Uniform(colors::transparent()))
+                    .into_chunk(dimensions);
+                *self = ChunkStorage::fixed_or_full(chunk);
+
+                match self {
+                    ChunkStorage::Fixed128(fixed_chunk) => {
+                        fixed_chunk.composite_fill(pixel, preserve_alpha)
+                    }
+                    ChunkStorage::Fixed256(fixed_chunk) => {
+                        fixed_chunk.composite_fill(pixel, preserve_alpha)
+                    }
+                    ChunkStorage::Full(chunk) => chunk.iter_pixels_mut().for_each(composite),
+                    _ => unreachable!("chunk storage should be made full above"),
+                }
+            }
+        }
+    }
+
+    /// Packs the chunk if it is stored in full, leaving chunks stored in any
+    /// other form as they are.
+    pub fn pack(&mut self) {
+        let packed_chunk = match self {
+            ChunkStorage::Full(chunk) => PackedRasterChunk::pack(chunk),
+            ChunkStorage::Fixed128(fixed_chunk) => PackedRasterChunk::pack(&fixed_chunk.to_chunk()),
+            ChunkStorage::Fixed256(fixed_chunk) => PackedRasterChunk::pack(&fixed_chunk.to_chunk()),
+            _ => return,
+        };
+
+        *self = ChunkStorage::Packed(packed_chunk);
+    }
+
+    /// Whether the stored pixels are the pixels of `chunk`, without
+    /// expanding them.
+    pub fn matches(&self, chunk: &BoxRasterChunk) -> bool {
+        match self {
+            ChunkStorage::Uniform(pixel) => chunk.pixels().iter().all(|p| p == pixel),
+            ChunkStorage::Rle(runs) => {
+                let mut pixels = chunk.pixels().iter();
+                let runs_match = runs.iter().all(|(pixel, length)| {
+                    (0..*length).all(|_| pixels.next().is_some_and(|p| p == pixel))
+                });
+
+                runs_match && pixels.next().is_none()
+            }
+            ChunkStorage::Packed(packed_chunk) => packed_chunk.unpack() == *chunk,
+            ChunkStorage::Full(stored_chunk) => stored_chunk == chunk,
+            ChunkStorage::Fixed128(fixed_chunk) => {
+                chunk.dimensions() == FixedRasterChunk::<128>::dimensions()
+                    && fixed_chunk.pixels() == chunk.pixels()
+            }
+            ChunkStorage::Fixed256(fixed_chunk) => {
+                chunk.dimensions() == FixedRasterChunk::<256>::dimensions()
+                    && fixed_chunk.pixels() == chunk.pixels()
+            }
+        }
+    }
+
+    pub fn into_chunk(self, dimensions: Dimensions) -> BoxRasterChunk {
+        match self {
+            ChunkStorage::Full(chunk) => chunk,
+            ChunkStorage::Fixed128(fixed_chunk) => fixed_chunk.into(),
+            ChunkStorage::Fixed256(fixed_chunk) => fixed_chunk.into(),
+            storage => storage.to_chunk(dimensions).into_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{primitives::rect::DrawRect, raster::pixels::colors};
+
+    #[test]
+    fn chunks_are_stored_compactly() {
+        let dimensions = Dimensions {
+            width: 8,
+            height: 8,
+        };
+
+        let uniform = ChunkStorage::compress(BoxRasterChunk::new_fill(colors::red(), 8, 8));
+        assert_eq!(uniform, ChunkStorage::Uniform(colors::red()));
+        assert_eq!(
+            uniform.to_chunk(dimensions).into_owned(),
+            BoxRasterChunk::new_fill(colors::red(), 8, 8)
+        );
+
+        let mut striped = BoxRasterChunk::new_fill(colors::red(), 8, 8);
+        striped.fill_rect(
+            colors::blue(),
+            DrawRect {
+                top_left: (0, 4).into(),
+                dimensions: Dimensions {
+                    width: 8,
+                    height: 4,
+                },
+            },
+        );
+        let rle = ChunkStorage::compress(striped.clone());
+        assert_eq!(
+            rle,
+            ChunkStorage::Rle(vec![(colors::red(), 32), (colors::blue(), 32)])
+        );
+        assert!(rle.byte_size() < striped.pixels().len() * 4);
+        assert!(rle.matches(&striped));
+        assert!(!uniform.matches(&striped));
+        assert_eq!(rle.into_chunk(dimensions), striped);
+
+        let noisy = BoxRasterChunk::new_fill_dynamic(
+            &mut |position| Pixel::new_rgb(position.0 as u8, position.1 as u8, 0),
+            8,
+            8,
+        );
+        let full = ChunkStorage::compress(noisy.clone());
+        assert!(!full.is_compressed());
+        assert_eq!(full.into_chunk(dimensions), noisy);
+    }
+}
@@ -1,11 +1,15 @@
+use std::ops::Deref;
+
 use crate::{
     primitives::{
         dimensions::Dimensions,
-        position::{DrawPosition, PixelPosition, Position},
+        position::{DrawPosition, PixelPosition},
     },
-    raster::{pixels::colors, Pixel},
+    raster::{pixels::colors, source::RasterSource, Pixel},
 };
 
+use super::{raster_chunk::RasterChunk, raster_window::RasterWindow};
+
 #[macro_export]
 macro_rules! assert_raster_eq {
     ($a:ident, $b:ident) => {
@@ -21,6 +25,10 @@ pub struct BoundedIndex {
 }
 
 /// A value that can be indexed by `PixelPosition`, providing pixels. It must make sense to get slices representing rows from the value.
+#[deprecated(
+    since = "0.1.0",
+    note = "use `RasterSource` and `MutRasterSource`, which are implemented for every chunk type"
+)]
 pub trait IndexableByPosition {
     /// Returns an index to the backing collection that corresponds to the position supplied.
     fn get_index_from_position(&self, position: PixelPosition) -> Option<usize>;
@@ -33,6 +41,68 @@ pub trait IndexableByPosition {
     fn get_row_slice(&self, row_num: usize) -> Option<&[Pixel]>;
 }
 
+#[allow(deprecated)]
+impl<T: Deref<Target = [Pixel]>> IndexableByPosition for RasterChunk<T> {
+    fn get_index_from_position(&self, position: PixelPosition) -> Option<usize> {
+        translate_rect_position_to_flat_index(position, self.dimensions)
+    }
+
+    fn get_index_from_bounded_position(&self, position: DrawPosition) -> BoundedIndex {
+        let bounded_position = self.dimensions.bound_position(position);
+
+        BoundedIndex {
+            index: translate_rect_position_to_flat_index(
+                bounded_position.position,
+                self.dimensions,
+            )
+            .expect("position is bounded"),
+            x_delta: bounded_position.delta.0,
+            y_delta: bounded_position.delta.1,
+        }
+    }
+
+    fn bound_position(&self, position: DrawPosition) -> PixelPosition {
+        self.dimensions.bound_position(position).position
+    }
+
+    fn get_row_slice(&self, row_num: usize) -> Option<&[Pixel]> {
+        self.row(row_num)
+    }
+}
+
+#[allow(deprecated)]
+impl<'a> IndexableByPosition for RasterWindow<'a> {
+    fn get_index_from_position(&self, position: PixelPosition) -> Option<usize> {
+        if position.0 >= self.dimensions.width || position.1 >= self.dimensions.height {
+            return None;
+        }
+
+        translate_rect_position_to_flat_index(self.top_left + position, self.backing_dimensions)
+    }
+
+    fn get_index_from_bounded_position(&self, position: DrawPosition) -> BoundedIndex {
+        let bounded_position = self.dimensions.bound_position(position);
+
+        BoundedIndex {
+            index: translate_rect_position_to_flat_index(
+                self.top_left + bounded_position.position,
+                self.backing_dimensions,
+            )
+            .expect("position is bounded"),
+            x_delta: bounded_position.delta.0,
+            y_delta: bounded_position.delta.1,
+        }
+    }
+
+    fn bound_position(&self, position: DrawPosition) -> PixelPosition {
+        self.dimensions.bound_position(position).position
+    }
+
+    fn get_row_slice(&self, row_num: usize) -> Option<&[Pixel]> {
+        self.row(row_num)
+    }
+}
+
 /// Failure to create a `RasterWindow` from a slice due to incompatible sizing.
 #[derive(Debug)]
 pub struct InvalidPixelSliceSize {
@@ -51,6 +121,8 @@ impl std::fmt::Display for InvalidPixelSliceSize {
     }
 }
 
+impl std::error::Error for InvalidPixelSliceSize {}
+
 pub fn translate_rect_position_to_flat_index(
     position: PixelPosition,
     dimensions: Dimensions,
@@ -69,7 +141,7 @@ pub fn translate_rect_position_to_flat_index(
 }
 
 pub fn get_color_character_for_pixel(p: &Pixel) -> &'static str {
-    let mut color_characters = vec![
+    let mut color_characters = [
         (colors::red(), "r"),
         (colors::blue(), "b"),
         (colors::green(), "g"),
@@ -86,7 +158,7 @@ pub fn get_color_character_for_pixel(p: &Pixel) -> &'static str {
     });
 
     color_characters
-        .get(0)
+        .first()
         .expect("color character array should never be empty")
         .1
 }
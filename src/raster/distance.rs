@@ -0,0 +1,299 @@
+//! Distance transforms, measuring how far each pixel is from the nearest pixel
+//! of a mask. They are the basis of effects that grow or shrink shapes, such as
+//! glows, feathering and stroke expansion.
+
+use crate::primitives::{dimensions::Dimensions, position::PixelPosition, rect::CanvasRect};
+
+use super::chunks::BoxRasterChunk;
+
+/// A large distance standing in for infinity, small enough that squaring
+/// positions added to it doesn't overflow.
+const FAR: f32 = 1e20;
+
+/// The exact Euclidean distance from every pixel of an area to the nearest
+/// pixel of a mask over it, computed in two passes of the distance transform of
+/// Felzenszwalb and Huttenlocher. Pixels within the mask have a distance of 0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistanceField {
+    dimensions: Dimensions,
+    squared_distances: Vec<f32>,
+}
+
+impl DistanceField {
+    /// Computes the distance field of a row-major mask. Returns `None` if the
+    /// mask doesn't have as many values as `dimensions` has positions.
+    pub fn from_mask(mask: &[bool], dimensions: Dimensions) -> Option<DistanceField> {
+        if mask.len() != dimensions.width * dimensions.height {
+            return None;
+        }
+
+        let squared_distances = mask
+            .iter()
+            .map(|in_mask| if *in_mask { 0.0 } else { FAR })
+            .collect();
+
+        Some(DistanceField::transform(dimensions, squared_distances))
+    }
+
+    /// Computes the distance field of the mask of pixels of `source` with any opacity.
+    pub fn from_alpha(source: &BoxRasterChunk) -> DistanceField {
+        let squared_distances = source
+            .pixels()
+            .iter()
+            .map(|pixel| if pixel.as_rgba().3 > 0 { 0.0 } else { FAR })
+            .collect();
+
+        DistanceField::transform(source.dimensions(), squared_distances)
+    }
+
+    fn transform(dimensions: Dimensions, mut squared_distances: Vec<f32>) -> DistanceField {
+        let Dimensions { width, height } = dimensions;
+
+        let longest_side = width.max(height);
+        let mut line = vec![0.0; longest_side];
+        let mut transformed = vec![0.0; longest_side];
+        let mut parabolas = vec![0; longest_side];
+        let mut boundaries = vec![0.0; longest_side + 1];
+
+        for x in 0..width {
+            for y in 0..height {
+                line[y] = squared_distances[y * width + x];
+            }
+            transform_line(
+                &line[..height],
+                &mut transformed[..height],
+                &mut parabolas,
+                &mut boundaries,
+            );
+            for y in 0..height {
+                squared_distances[y * width + x] = transformed[y];
+            }
+        }
+
+        for row in squared_distances.chunks_exact_mut(width.max(1)) {
+            line[..width].copy_from_slice(row);
+            transform_line(&line[..width], row, &mut parabolas, &mut boundaries);
+        }
+
+        DistanceField {
+            dimensions,
+            squared_distances,
+        }
+    }
+
+    pub fn dimensions(&self) -> Dimensions {
+        self.dimensions
+    }
+
+    /// The squared distance at a position, avoiding a square root when only
+    /// comparing distances. Returns `None` if the position is outside the field.
+    pub fn squared_distance(&self, position: PixelPosition) -> Option<f32> {
+        if position.0 >= self.dimensions.width || position.1 >= self.dimensions.height {
+            return None;
+        }
+
+        Some(self.squared_distances[position.1 * self.dimensions.width + position.0])
+    }
+
+    /// The distance at a position. Returns `None` if the position is outside
+    /// the field.
+    pub fn distance(&self, position: PixelPosition) -> Option<f32> {
+        self.squared_distance(position).map(f32::sqrt)
+    }
+
+    /// The distance at every position of the field, row by row.
+    pub fn distances(&self) -> impl Iterator<Item = f32> + '_ {
+        self.squared_distances.iter().map(|d| d.sqrt())
+    }
+
+    /// The part of the field within a rect, which must be contained in the field.
+    fn crop(&self, top_left: PixelPosition, dimensions: Dimensions) -> DistanceField {
+        let squared_distances = (0..dimensions.height)
+            .flat_map(|y| {
+                let row_start = (top_left.1 + y) * self.dimensions.width + top_left.0;
+
+                self.squared_distances[row_start..row_start + dimensions.width].iter()
+            })
+            .copied()
+            .collect();
+
+        DistanceField {
+            dimensions,
+            squared_distances,
+        }
+    }
+}
+
+/// Computes distance fields over large areas a tile at a time, so that memory
+/// use is bounded by the tile size rather than the size of the area. Each tile
+/// is computed from its content and a margin of `max_distance` around it, so
+/// distances up to `max_distance` are exact and greater distances are reported
+/// as infinite.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TiledDistanceTransform {
+    tile_size: usize,
+    max_distance: u32,
+}
+
+impl TiledDistanceTransform {
+    pub fn new(tile_size: usize, max_distance: u32) -> TiledDistanceTransform {
+        TiledDistanceTransform {
+            tile_size: tile_size.max(1),
+            max_distance,
+        }
+    }
+
+    /// Computes the distance field of each tile covering `canvas_rect`, to the
+    /// pixels with any opacity. `rasterizer` is called to read the content of
+    /// each tile with its margin, and `f` is called with the canvas rect of each
+    /// tile along with its distance field.
+    pub fn for_each_tile<R, F>(&self, canvas_rect: CanvasRect, mut rasterizer: R, mut f: F)
+    where
+        R: FnMut(CanvasRect) -> BoxRasterChunk,
+        F: FnMut(CanvasRect, &DistanceField),
+    {
+        let max_squared_distance = self.max_distance as f32 * self.max_distance as f32;
+
+        for tile_rect in canvas_rect.iter_tiles(self.tile_size) {
+            let source_rect = tile_rect
+                .try_expand(self.max_distance as usize)
+                .unwrap_or(tile_rect);
+
+            let offset_in_source: PixelPosition = (
+                (tile_rect.top_left.0 - source_rect.top_left.0) as usize,
+                (tile_rect.top_left.1 - source_rect.top_left.1) as usize,
+            )
+                .into();
+
+            let mut tile_field = DistanceField::from_alpha(&rasterizer(source_rect))
+                .crop(offset_in_source, tile_rect.dimensions);
+
+            for squared_distance in tile_field.squared_distances.iter_mut() {
+                if *squared_distance > max_squared_distance {
+                    *squared_distance = f32::INFINITY;
+                }
+            }
+
+            f(tile_rect, &tile_field);
+        }
+    }
+}
+
+/// The one dimensional squared distance transform of `f` into `d`, taking the
+/// lower envelope of the parabolas rooted at each sample.
+fn transform_line(f: &[f32], d: &mut [f32], parabolas: &mut [usize], boundaries: &mut [f32]) {
+    let n = f.len();
+    if n == 0 {
+        return;
+    }
+
+    let intersection = |q: usize, p: usize| {
+        let (q_f, p_f) = (q as f32, p as f32);
+        ((f[q] + q_f * q_f) - (f[p] + p_f * p_f)) / (2.0 * q_f - 2.0 * p_f)
+    };
+
+    let mut k = 0;
+    parabolas[0] = 0;
+    boundaries[0] = f32::NEG_INFINITY;
+    boundaries[1] = f32::INFINITY;
+
+    for q in 1..n {
+        let mut s = intersection(q, parabolas[k]);
+        while s <= boundaries[k] {
+            k -= 1;
+            s = intersection(q, parabolas[k]);
+        }
+
+        k += 1;
+        parabolas[k] = q;
+        boundaries[k] = s;
+        boundaries[k + 1] = f32::INFINITY;
+    }
+
+    k = 0;
+    for (q, distance) in d.iter_mut().enumerate() {
+        while boundaries[k + 1] < q as f32 {
+            k += 1;
+        }
+
+        let offset = q as f32 - parabolas[k] as f32;
+        *distance = offset * offset + f[parabolas[k]];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raster::pixels::colors;
+
+    #[test]
+    fn tiled_distances_match_whole_field() {
+        let dimensions = Dimensions {
+            width: 20,
+            height: 12,
+        };
+        let mask: Vec<bool> = (0..dimensions.height)
+            .flat_map(|y| (0..dimensions.width).map(move |x| (x, y)))
+            .map(|(x, y)| (x, y) == (3, 4) || (x, y) == (15, 9))
+            .collect();
+
+        let field = DistanceField::from_mask(&mask, dimensions).unwrap();
+        assert_eq!(field.distance((3, 4).into()), Some(0.0));
+        assert_eq!(field.distance((6, 8).into()), Some(5.0));
+        assert_eq!(field.distance((20, 0).into()), None);
+
+        let source = {
+            let pixels = mask
+                .iter()
+                .map(|in_mask| {
+                    if *in_mask {
+                        colors::black()
+                    } else {
+                        colors::transparent()
+                    }
+                })
+                .collect();
+            BoxRasterChunk::from_vec(pixels, dimensions.width, dimensions.height).unwrap()
+        };
+        let read_source = |rect: CanvasRect| {
+            let mut raster = BoxRasterChunk::new(rect.dimensions.width, rect.dimensions.height);
+            raster.blit(
+                &source.as_window(),
+                (-rect.top_left.0, -rect.top_left.1).into(),
+            );
+            raster
+        };
+
+        let max_distance = 6;
+        let mut tiles = 0;
+        TiledDistanceTransform::new(8, max_distance).for_each_tile(
+            CanvasRect::at_origin(dimensions),
+            read_source,
+            |tile_rect, tile_field| {
+                tiles += 1;
+
+                for y in 0..tile_rect.dimensions.height {
+                    for x in 0..tile_rect.dimensions.width {
+                        let expected = field
+                            .distance(
+                                (
+                                    tile_rect.top_left.0 as usize + x,
+                                    tile_rect.top_left.1 as usize + y,
+                                )
+                                    .into(),
+                            )
+                            .unwrap();
+                        let distance = tile_field.distance((x, y).into()).unwrap();
+
+                        if expected <= max_distance as f32 {
+                            assert_eq!(distance, expected);
+                        } else {
+                            assert_eq!(distance, f32::INFINITY);
+                        }
+                    }
+                }
+            },
+        );
+        assert_eq!(tiles, 6);
+    }
+}
@@ -0,0 +1,287 @@
+//! Convolution filters such as blurs, sharpening and edge detection, which
+//! replace each pixel with a weighted sum of the pixels around it.
+
+use std::hash::{Hash, Hasher};
+
+use super::{chunks::BoxRasterChunk, Pixel};
+use crate::primitives::rect::CanvasRect;
+
+/// A square grid of weights, centered on the pixel being filtered, that the
+/// pixels around it are summed with.
+///
+/// Colors are weighted by their opacity, so transparent pixels don't darken
+/// blurs. Kernels whose weights sum to zero, such as edge detection, find the
+/// magnitude of changes in color and keep the opacity of each pixel. Other
+/// kernels are applied to the opacity too, so blurs soften the edges of
+/// content.
+#[derive(Debug, Clone)]
+pub struct Kernel {
+    size: usize,
+    /// Weights in row-major order.
+    weights: Vec<f32>,
+}
+
+impl Kernel {
+    /// A kernel `size` pixels wide with weights in row-major order. Returns
+    /// `None` unless `size` is odd, so the kernel has a center, and there is a
+    /// weight for every position.
+    pub fn new(size: usize, weights: Vec<f32>) -> Option<Kernel> {
+        (size % 2 == 1 && weights.len() == size * size).then_some(Kernel { size, weights })
+    }
+
+    /// A blur averaging the pixels within `radius` of each pixel equally.
+    pub fn box_blur(radius: usize) -> Kernel {
+        let size = radius * 2 + 1;
+
+        Kernel {
+            size,
+            weights: vec![1.0 / (size * size) as f32; size * size],
+        }
+    }
+
+    /// A blur weighting pixels by a bell curve with a standard deviation of
+    /// `sigma` pixels, reaching 3 standard deviations out.
+    pub fn gaussian_blur(sigma: f32) -> Kernel {
+        if sigma <= 0.0 {
+            return Kernel::box_blur(0);
+        }
+
+        let radius = (sigma * 3.0).ceil() as i32;
+        let size = radius as usize * 2 + 1;
+
+        let weights: Vec<f32> = (-radius..=radius)
+            .flat_map(|y| (-radius..=radius).map(move |x| (x, y)))
+            .map(|(x, y)| (-((x * x + y * y) as f32) / (2.0 * sigma * sigma)).exp())
+            .collect();
+        let total: f32 = weights.iter().sum();
+
+        Kernel {
+            size,
+            weights: weights.into_iter().map(|weight| weight / total).collect(),
+        }
+    }
+
+    /// Sharpens by subtracting the pixels beside each pixel from it.
+    pub fn sharpen() -> Kernel {
+        Kernel {
+            size: 3,
+            weights: vec![0.0, -1.0, 0.0, -1.0, 5.0, -1.0, 0.0, -1.0, 0.0],
+        }
+    }
+
+    /// The Sobel operator finding changes in color from left to right, which
+    /// highlights vertical edges.
+    pub fn sobel_horizontal() -> Kernel {
+        Kernel {
+            size: 3,
+            weights: vec![-1.0, 0.0, 1.0, -2.0, 0.0, 2.0, -1.0, 0.0, 1.0],
+        }
+    }
+
+    /// The Sobel operator finding changes in color from top to bottom, which
+    /// highlights horizontal edges.
+    pub fn sobel_vertical() -> Kernel {
+        Kernel {
+            size: 3,
+            weights: vec![-1.0, -2.0, -1.0, 0.0, 0.0, 0.0, 1.0, 2.0, 1.0],
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// How far the kernel reaches from the pixel being filtered.
+    pub fn radius(&self) -> usize {
+        self.size / 2
+    }
+
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+
+    fn is_zero_sum(&self) -> bool {
+        self.weights.iter().sum::<f32>().abs() < 1e-6
+    }
+
+    /// Filters `source`, which includes a margin of `radius` pixels around
+    /// the area filtered so that pixels near the edges have neighbours. The
+    /// result is smaller than `source` by the margin on every side.
+    pub fn convolve(&self, source: &BoxRasterChunk) -> BoxRasterChunk {
+        let source_dimensions = source.dimensions();
+        let margin = self.radius() * 2;
+        let width = source_dimensions.width.saturating_sub(margin);
+        let height = source_dimensions.height.saturating_sub(margin);
+
+        let premultiplied: Vec<[f32; 4]> = source
+            .pixels()
+            .iter()
+            .map(|pixel| {
+                let (r, g, b, a) = pixel.as_norm_rgba();
+                [r * a, g * a, b * a, a]
+            })
+            .collect();
+        let is_zero_sum = self.is_zero_sum();
+
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0.0; 4];
+
+                for (kernel_y, weights) in self.weights.chunks_exact(self.size).enumerate() {
+                    let row_start = (y + kernel_y) * source_dimensions.width + x;
+                    let row = &premultiplied[row_start..row_start + self.size];
+
+                    for (weight, source) in weights.iter().zip(row) {
+                        for (channel_sum, channel) in sum.iter_mut().zip(source) {
+                            *channel_sum += weight * channel;
+                        }
+                    }
+                }
+
+                let pixel = if is_zero_sum {
+                    let center_alpha = premultiplied
+                        [(y + self.radius()) * source_dimensions.width + x + self.radius()][3];
+
+                    Pixel::new_rgba_norm(
+                        sum[0].abs().min(1.0),
+                        sum[1].abs().min(1.0),
+                        sum[2].abs().min(1.0),
+                        center_alpha,
+                    )
+                } else {
+                    let alpha = sum[3].clamp(0.0, 1.0);
+                    let unpremultiply = |channel: f32| {
+                        if alpha > 0.0 {
+                            (channel / alpha).clamp(0.0, 1.0)
+                        } else {
+                            0.0
+                        }
+                    };
+
+                    Pixel::new_rgba_norm(
+                        unpremultiply(sum[0]),
+                        unpremultiply(sum[1]),
+                        unpremultiply(sum[2]),
+                        alpha,
+                    )
+                };
+
+                pixels.push(pixel);
+            }
+        }
+
+        BoxRasterChunk::from_vec(pixels, width, height)
+            .expect("a filtered pixel should be computed for every position")
+    }
+
+    /// Filters the pixels within `canvas_rect`, rasterizing the rect with a
+    /// margin of the kernel's radius with `rasterizer` so that pixels outside
+    /// of the rect are included in the sums of the pixels near its edges.
+    pub fn rasterize_canvas_rect<F>(&self, canvas_rect: CanvasRect, rasterizer: F) -> BoxRasterChunk
+    where
+        F: FnOnce(CanvasRect) -> BoxRasterChunk,
+    {
+        self.convolve(&rasterizer(canvas_rect.expand(self.radius())))
+    }
+}
+
+impl PartialEq for Kernel {
+    fn eq(&self, other: &Self) -> bool {
+        self.size == other.size
+            && self
+                .weights
+                .iter()
+                .zip(other.weights.iter())
+                .all(|(a, b)| a.to_bits() == b.to_bits())
+    }
+}
+
+impl Eq for Kernel {}
+
+impl Hash for Kernel {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.size.hash(state);
+
+        for weight in &self.weights {
+            weight.to_bits().hash(state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        canvas::Layer,
+        primitives::{dimensions::Dimensions, rect::DrawRect},
+        raster::{pixels::colors, RasterLayer, RasterLayerAction, RasterSource},
+    };
+
+    #[test]
+    fn kernels_filter_across_chunks() {
+        assert!(Kernel::new(2, vec![0.25; 4]).is_none());
+        assert!(Kernel::new(3, vec![1.0; 8]).is_none());
+        let total: f32 = Kernel::gaussian_blur(1.5).weights().iter().sum();
+        assert!((total - 1.0).abs() < 1e-4);
+
+        let mut raster_layer = RasterLayer::new(4);
+        raster_layer.perform_action(RasterLayerAction::fill_rect(
+            CanvasRect {
+                top_left: (0, 0).into(),
+                dimensions: Dimensions {
+                    width: 4,
+                    height: 8,
+                },
+            },
+            colors::red(),
+        ));
+        raster_layer.perform_action(RasterLayerAction::fill_rect(
+            CanvasRect {
+                top_left: (4, 0).into(),
+                dimensions: Dimensions {
+                    width: 4,
+                    height: 8,
+                },
+            },
+            colors::blue(),
+        ));
+
+        let blurred_rect = CanvasRect {
+            top_left: (2, 2).into(),
+            dimensions: Dimensions {
+                width: 4,
+                height: 4,
+            },
+        };
+        raster_layer.perform_action(RasterLayerAction::ApplyKernel(
+            blurred_rect,
+            Kernel::box_blur(1),
+        ));
+
+        let blurred = raster_layer.rasterize_canvas_rect(blurred_rect);
+        let row = blurred.row(1).unwrap();
+        assert_eq!(row[0], colors::red());
+        assert!(row[1].is_close(&Pixel::new_rgb(170, 0, 85), 1));
+        assert!(row[2].is_close(&Pixel::new_rgb(85, 0, 170), 1));
+        assert_eq!(row[3], colors::blue());
+
+        let mut edge = BoxRasterChunk::new_fill(colors::black(), 3, 3);
+        edge.fill_rect(
+            colors::white(),
+            DrawRect {
+                top_left: (2, 0).into(),
+                dimensions: Dimensions {
+                    width: 1,
+                    height: 3,
+                },
+            },
+        );
+        let edges = Kernel::sobel_horizontal().convolve(&edge);
+        assert_eq!(edges.pixels(), &[colors::white()]);
+        assert_eq!(
+            Kernel::sobel_vertical().convolve(&edge).pixels(),
+            &[colors::black()]
+        );
+    }
+}
@@ -0,0 +1,155 @@
+//! Glows and outlines cast outwards from the edges of a layer's content.
+
+use crate::primitives::{dimensions::Dimensions, position::DrawPosition, rect::CanvasRect};
+
+use super::{
+    chunks::BoxRasterChunk,
+    distance::{DistanceField, TiledDistanceTransform},
+    pixels::colors,
+    Pixel,
+};
+
+/// How the strength of a glow changes with distance from the edge it is cast from.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GlowStyle {
+    /// Fades out linearly until `radius` pixels from the edge.
+    #[default]
+    Soft,
+    /// A solid outline `radius` pixels wide.
+    Outline,
+}
+
+/// A glow or outline cast outwards from the edges of the visible pixels of a
+/// raster. Pixels with any opacity cast the glow, and the glow is only drawn
+/// where there are no such pixels, so it never covers the content casting it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Glow {
+    pub color: Pixel,
+    /// How far the glow reaches from the edge in pixels.
+    pub radius: u32,
+    pub style: GlowStyle,
+}
+
+impl Glow {
+    pub fn new(color: Pixel, radius: u32, style: GlowStyle) -> Glow {
+        Glow {
+            color,
+            radius,
+            style,
+        }
+    }
+
+    /// How strong the glow is at `distance` pixels from the edge, from 0 to 1.
+    fn strength(&self, distance: f32) -> f32 {
+        if distance == 0.0 || self.radius == 0 {
+            return 0.0;
+        }
+
+        let radius = self.radius as f32;
+        match self.style {
+            GlowStyle::Soft => ((radius + 1.0 - distance) / radius).clamp(0.0, 1.0),
+            GlowStyle::Outline => (radius + 1.0 - distance).clamp(0.0, 1.0),
+        }
+    }
+
+    /// Rasterizes the glow cast by the visible pixels of `source`, covering the
+    /// same area as `source`. Content within `radius` of the edges of `source`
+    /// can only cast a glow if it is included, so callers should rasterize
+    /// sources with a margin of `radius` and crop the result.
+    pub fn rasterize(&self, source: &BoxRasterChunk) -> BoxRasterChunk {
+        self.rasterize_distance_field(&DistanceField::from_alpha(source))
+    }
+
+    fn rasterize_distance_field(&self, distance_field: &DistanceField) -> BoxRasterChunk {
+        let Dimensions { width, height } = distance_field.dimensions();
+        let (r, g, b, a) = self.color.as_rgba();
+
+        let glow_pixels = distance_field
+            .distances()
+            .map(|distance| {
+                let alpha = (a as f32 * self.strength(distance)).round() as u8;
+
+                if alpha == 0 {
+                    colors::transparent()
+                } else {
+                    Pixel::new_rgba(r, g, b, alpha)
+                }
+            })
+            .collect();
+
+        BoxRasterChunk::from_vec(glow_pixels, width, height)
+            .expect("glow pixels should match the distance field dimensions")
+    }
+
+    /// Rasterizes the glow over `canvas_rect`, using `rasterizer` to read the
+    /// content casting it. The glow is computed in tiles, so `rasterizer` is
+    /// called for each tile with a margin of `radius` around it.
+    pub fn rasterize_canvas_rect<F>(&self, canvas_rect: CanvasRect, rasterizer: F) -> BoxRasterChunk
+    where
+        F: FnMut(CanvasRect) -> BoxRasterChunk,
+    {
+        let mut glow =
+            BoxRasterChunk::new(canvas_rect.dimensions.width, canvas_rect.dimensions.height);
+
+        TiledDistanceTransform::new(GLOW_TILE_SIZE, self.radius + 1).for_each_tile(
+            canvas_rect,
+            rasterizer,
+            |tile_rect, distance_field| {
+                let draw_position: DrawPosition = (
+                    tile_rect.top_left.0 - canvas_rect.top_left.0,
+                    tile_rect.top_left.1 - canvas_rect.top_left.1,
+                )
+                    .into();
+
+                glow.blit(
+                    &self.rasterize_distance_field(distance_field).as_window(),
+                    draw_position,
+                );
+            },
+        );
+
+        glow
+    }
+}
+
+/// The size of the tiles glows are computed in, bounding the memory used by
+/// the distance transform.
+const GLOW_TILE_SIZE: usize = 256;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::rect::DrawRect;
+
+    #[test]
+    fn glow_fades_from_edge() {
+        let mut source = BoxRasterChunk::new(16, 16);
+        source.fill_rect(
+            colors::black(),
+            DrawRect {
+                top_left: (6, 6).into(),
+                dimensions: Dimensions {
+                    width: 4,
+                    height: 4,
+                },
+            },
+        );
+
+        let alpha_at =
+            |raster: &BoxRasterChunk, x: usize, y: usize| raster.pixels()[y * 16 + x].as_rgba().3;
+
+        let soft = Glow::new(colors::red(), 4, GlowStyle::Soft).rasterize(&source);
+        assert_eq!(alpha_at(&soft, 7, 7), 0);
+        assert_eq!(alpha_at(&soft, 5, 7), 255);
+        assert!(alpha_at(&soft, 3, 7) < alpha_at(&soft, 4, 7));
+        assert_eq!(alpha_at(&soft, 1, 7), 0);
+
+        let outline = Glow::new(colors::red(), 2, GlowStyle::Outline).rasterize(&source);
+        assert_eq!(alpha_at(&outline, 4, 7), 255);
+        assert_eq!(alpha_at(&outline, 3, 7), 0);
+        assert_eq!(alpha_at(&outline, 5, 5), 255);
+        assert_eq!(alpha_at(&outline, 3, 3), 0);
+    }
+}
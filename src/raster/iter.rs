@@ -1,5 +1,5 @@
 use super::{
-    chunks::BoxRasterChunk,
+    chunks::ChunkStorage,
     layer::{ChunkRect, ChunkRectPosition},
     RasterLayer,
 };
@@ -8,7 +8,7 @@ use crate::primitives::{
     dimensions::Dimensions,
     position::{ChunkPosition, PixelPosition, Position, UncheckedIntoPosition},
 };
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 /// Iterator over individual `PixelPosition`s in a dimension space.
 pub struct PixelPositionIterator {
@@ -105,8 +105,8 @@ pub type RasterChunkIteratorMut<'a> = GenericRasterChunkIterator<&'a mut RasterL
 
 pub trait RasterLayerReference {}
 
-impl<'a> RasterLayerReference for &'a RasterLayer {}
-impl<'a> RasterLayerReference for &'a mut RasterLayer {}
+impl RasterLayerReference for &RasterLayer {}
+impl RasterLayerReference for &mut RasterLayer {}
 
 pub struct GenericRasterChunkIterator<T: RasterLayerReference> {
     raster_layer: T,
@@ -125,7 +125,7 @@ impl<T: RasterLayerReference> GenericRasterChunkIterator<T> {
 }
 
 impl<'a> Iterator for GenericRasterChunkIterator<&'a RasterLayer> {
-    type Item = (Option<&'a BoxRasterChunk>, ChunkRectPosition);
+    type Item = (Option<&'a ChunkStorage>, ChunkRectPosition);
 
     fn next(&mut self) -> Option<Self::Item> {
         let chunk_rect = self.chunk_rect;
@@ -191,7 +191,7 @@ impl<'a> Iterator for GenericRasterChunkIterator<&'a RasterLayer> {
             .top_left_in_chunk(chunk_position)
             .expect("chunk_position is constructed to be in chunk_rect");
 
-        let raster_chunk = chunks.get(&chunk_position);
+        let raster_chunk = chunks.get(&chunk_position).map(Arc::as_ref);
 
         let chunk_rect_position = ChunkRectPosition {
             top_left_in_chunk,
@@ -210,7 +210,7 @@ impl<'a> Iterator for GenericRasterChunkIterator<&'a RasterLayer> {
 }
 
 impl<'a> Iterator for GenericRasterChunkIterator<&'a mut RasterLayer> {
-    type Item = (Option<&'a mut BoxRasterChunk>, ChunkRectPosition);
+    type Item = (Option<&'a mut ChunkStorage>, ChunkRectPosition);
 
     fn next<'b>(&'b mut self) -> Option<Self::Item> {
         let chunk_rect = self.chunk_rect;
@@ -223,8 +223,8 @@ impl<'a> Iterator for GenericRasterChunkIterator<&'a mut RasterLayer> {
         // borrow.
         let chunks = unsafe {
             std::mem::transmute::<
-                &'b mut HashMap<ChunkPosition, BoxRasterChunk>,
-                &'a mut HashMap<ChunkPosition, BoxRasterChunk>,
+                &'b mut HashMap<ChunkPosition, Arc<ChunkStorage>>,
+                &'a mut HashMap<ChunkPosition, Arc<ChunkStorage>>,
             >(&mut self.raster_layer.chunks)
         };
 
@@ -279,7 +279,7 @@ impl<'a> Iterator for GenericRasterChunkIterator<&'a mut RasterLayer> {
             .top_left_in_chunk(chunk_position)
             .expect("chunk_position is constructed to be in chunk_rect");
 
-        let raster_chunk = chunks.get_mut(&chunk_position);
+        let raster_chunk = chunks.get_mut(&chunk_position).map(Arc::make_mut);
 
         let chunk_rect_position = ChunkRectPosition {
             top_left_in_chunk,
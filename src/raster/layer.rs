@@ -1,842 +1,3847 @@
 use super::{
-    chunks::{raster_chunk::BumpRasterChunk, BoxRasterChunk, RasterWindow},
+    blend_if::BlendIf,
+    brush::Brush,
+    chunk_index::ChunkIndex,
+    chunk_size::{auto_chunk_size, ChunkUsage, LayerOccupancy},
+    chunks::{
+        raster_chunk::BumpRasterChunk, BoxRasterChunk, ChunkStorage, ColorSum, Histogram,
+        MaskChunk, PixelFormat, RasterWindow,
+    },
+    distance::{DistanceField, TiledDistanceTransform},
+    filter::Kernel,
+    glow::Glow,
     iter::{RasterChunkIterator, RasterChunkIteratorMut},
-    pixels::{colors, Pixel},
+    mask::MaskLayer,
+    pixels::{colors, BlendMode, HsvAdjustment, Pixel},
+    selection::Selection,
 };
 use crate::{
-    canvas::{CanvasView, Layer, ShapeCache},
+    canvas::{CanvasRng, CanvasView, Layer, ShapeCache},
     primitives::{
-        dimensions::Dimensions,
+        dimensions::{Dimensions, GeometryError},
         position::{
             CanvasPosition, ChunkPosition, DrawPosition, PixelPosition, UncheckedIntoPosition,
         },
-        rect::CanvasRect,
+        rect::{CanvasRect, DrawRect, RasterRect},
+    },
+    text::TextObject,
+    vector::shapes::{
+        ConicGradient, ConvexPolygon, Falloff, Oval, Path, Polygon, RadialGradientDisc,
+        RasterizablePolygon, RoundedRectangle,
     },
-    vector::shapes::{Oval, RasterizablePolygon},
 };
-use std::collections::HashMap;
+use std::{borrow::Cow, collections::HashMap, sync::Arc};
+use thiserror::Error;
+
+/// An error from reading or drawing on the chunks of a raster layer whose
+/// geometry is inconsistent, such as a chunk that isn't of the layer's chunk
+/// size, or from a rect that doesn't pass `Rect::try_new`.
+#[derive(Error, Debug, Clone, Copy, PartialEq)]
+pub enum RasterizeError {
+    #[error("{window_rect:?} of chunk {chunk_position:?} is outside of the chunk, rasterizing {canvas_rect:?}")]
+    WindowOutsideChunk {
+        chunk_position: ChunkPosition,
+        canvas_rect: CanvasRect,
+        window_rect: RasterRect,
+    },
+    #[error("chunk {chunk_position:?} doesn't hold the {chunk_dimensions:?} pixels of a chunk of its layer")]
+    ChunkSizeMismatch {
+        chunk_position: ChunkPosition,
+        chunk_dimensions: Dimensions,
+    },
+    #[error(transparent)]
+    Geometry(#[from] GeometryError),
+}
 
 /// A layer made of raw pixel data. All layers will eventually
 /// be composited onto a raster layer for presentation.
+#[derive(Clone)]
 pub struct RasterLayer {
     pub(super) chunk_size: usize,
-    pub(super) chunks: HashMap<ChunkPosition, BoxRasterChunk>,
+    /// The allocated chunks. Chunks are stored in full while they are drawn
+    /// on, after which chunks of layers with a packed pixel format are packed
+    /// and chunks of few colors, such as those covered by a single fill, may
+    /// be compressed. The storage of a chunk is shared between clones of the
+    /// layer until one of them draws on the chunk, so cloning a layer, such as
+    /// for a `CanvasReader`, doesn't copy its pixels.
+    pub(super) chunks: HashMap<ChunkPosition, Arc<ChunkStorage>>,
+    /// The positions of the allocated chunks, for finding the allocated
+    /// chunks within a rect.
+    chunk_index: ChunkIndex,
+    pixel_format: PixelFormat,
     blank_chunk: BoxRasterChunk,
+    chunk_usage: ChunkUsage,
+    blend_if: Option<BlendIf>,
+    blend_mode: BlendMode,
+    glow: Option<Glow>,
+    /// Whether drawing on the layer keeps the alpha of its pixels, so only
+    /// the colors of what has already been drawn change.
+    alpha_locked: bool,
 }
 
-impl RasterLayer {
-    pub fn new(chunk_size: usize) -> RasterLayer {
-        RasterLayer {
-            chunk_size,
-            chunks: HashMap::new(),
-            blank_chunk: BoxRasterChunk::new_fill(colors::transparent(), chunk_size, chunk_size),
-        }
+/// Composites a window onto a chunk of a layer, keeping the alpha of the
+/// chunk's pixels if the layer is alpha locked.
+fn composite_onto_chunk(
+    raster_chunk: &mut BoxRasterChunk,
+    source: &RasterWindow,
+    dest_position: DrawPosition,
+    alpha_locked: bool,
+) {
+    if alpha_locked {
+        raster_chunk.composite_over_preserving_alpha(source, dest_position);
+    } else {
+        raster_chunk.composite_over(source, dest_position);
     }
 }
 
-/// An editing action that can be applied to a raster canvas.
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub enum RasterLayerAction {
-    /// Fills a rect with `pixel`.
-    FillRect(CanvasRect, Pixel),
-    /// Draws an oval bounded by a canvas rect, filled with `pixel`.
-    FillOval(CanvasRect, Pixel),
-}
-
-impl RasterLayerAction {
-    pub fn fill_rect(canvas_rect: CanvasRect, pixel: Pixel) -> RasterLayerAction {
-        RasterLayerAction::FillRect(canvas_rect, pixel)
-    }
-
-    pub fn fill_oval(canvas_rect: CanvasRect, pixel: Pixel) -> RasterLayerAction {
-        RasterLayerAction::FillOval(canvas_rect, pixel)
+/// Copies a window onto a chunk of a layer, keeping the alpha of the chunk's
+/// pixels if the layer is alpha locked.
+fn blit_onto_chunk(
+    raster_chunk: &mut BoxRasterChunk,
+    source: &RasterWindow,
+    dest_position: DrawPosition,
+    alpha_locked: bool,
+) {
+    if alpha_locked {
+        raster_chunk.blit_preserving_alpha(source, dest_position);
+    } else {
+        raster_chunk.blit(source, dest_position);
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub struct ChunkRectPosition {
-    pub top_left_in_chunk: PixelPosition,
-    pub width: usize,
-    pub height: usize,
-    pub x_chunk_offset: usize,
-    pub y_chunk_offset: usize,
-    pub x_pixel_offset: usize,
-    pub y_pixel_offset: usize,
-}
-
-/// A rectangle in chunk-space, also denotes where it starts
-/// and ends in the top-left and bottom-right chunks.
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub struct ChunkRect {
-    pub top_left_chunk: ChunkPosition,
-    pub chunk_dimensions: Dimensions,
-    pub top_left_in_chunk: PixelPosition,
-    pub bottom_right_in_chunk: PixelPosition,
+/// How the pixels of a chunk are filled, for the occupancy of a layer.
+enum ChunkFill {
+    Transparent,
+    SingleColor,
+    Mixed,
 }
 
-impl ChunkRect {
-    /// Get the position most top-left within a chunk that is within the chunk rect.
-    /// Returns `None` if the requested position is not within this chunk-rect.
-    pub fn top_left_in_chunk(&self, chunk_position: ChunkPosition) -> Option<PixelPosition> {
-        let bottom_right_chunk = self.top_left_chunk.translate(
-            (
-                self.chunk_dimensions.width as i32,
-                self.chunk_dimensions.height as i32,
-            )
-                .into(),
-        );
+impl ChunkFill {
+    fn of<I: Iterator<Item = Pixel>>(mut pixels: I) -> ChunkFill {
+        let first_pixel = match pixels.next() {
+            Some(first_pixel) => first_pixel,
+            None => return ChunkFill::Transparent,
+        };
+        let (mut is_transparent, mut is_single_color) = (first_pixel.as_rgba().3 == 0, true);
 
-        let position_past_top_left =
-            chunk_position.0 < self.top_left_chunk.0 || chunk_position.1 < self.top_left_chunk.1;
-        let position_past_bottom_right =
-            chunk_position.0 > bottom_right_chunk.0 || chunk_position.1 > bottom_right_chunk.1;
-        if position_past_top_left || position_past_bottom_right {
-            None
-        } else {
-            let left_in_chunk = if chunk_position.0 == self.top_left_chunk.0 {
-                self.top_left_in_chunk.0
-            } else {
-                0
-            };
+        for pixel in pixels {
+            is_transparent &= pixel.as_rgba().3 == 0;
+            is_single_color &= pixel == first_pixel;
 
-            let top_in_chunk = if chunk_position.1 == self.top_left_chunk.1 {
-                self.top_left_in_chunk.1
-            } else {
-                0
-            };
+            if !is_transparent && !is_single_color {
+                return ChunkFill::Mixed;
+            }
+        }
 
-            Some((left_in_chunk, top_in_chunk).into())
+        if is_transparent {
+            ChunkFill::Transparent
+        } else {
+            ChunkFill::SingleColor
         }
     }
 }
 
 impl RasterLayer {
-    fn find_chunk_rect_in_canvas_rect(&self, canvas_rect: CanvasRect) -> ChunkRect {
-        let CanvasRect {
-            top_left,
-            dimensions,
-        } = canvas_rect;
+    pub fn new(chunk_size: usize) -> RasterLayer {
+        RasterLayer {
+            chunk_size,
+            chunks: HashMap::new(),
+            chunk_index: ChunkIndex::default(),
+            pixel_format: PixelFormat::default(),
+            blank_chunk: BoxRasterChunk::new_fill(colors::transparent(), chunk_size, chunk_size),
+            chunk_usage: ChunkUsage::default(),
+            blend_if: None,
+            blend_mode: BlendMode::Normal,
+            glow: None,
+            alpha_locked: false,
+        }
+    }
 
-        let top_left_chunk = top_left.containing_chunk(self.chunk_size);
-        let top_left_in_chunk = top_left.position_in_containing_chunk(self.chunk_size);
+    /// A layer with the same chunk size, pixel format and blending as this
+    /// one, but none of its chunks.
+    pub(crate) fn without_chunks(&self) -> RasterLayer {
+        RasterLayer {
+            pixel_format: self.pixel_format,
+            blend_if: self.blend_if,
+            blend_mode: self.blend_mode,
+            glow: self.glow,
+            alpha_locked: self.alpha_locked,
+            ..RasterLayer::new(self.chunk_size)
+        }
+    }
 
-        let bottom_right =
-            top_left.translate((dimensions.width as i32 - 1, dimensions.height as i32 - 1).into());
-        let bottom_right_chunk = bottom_right.containing_chunk(self.chunk_size);
-        let bottom_right_in_chunk = bottom_right.position_in_containing_chunk(self.chunk_size);
+    /// Replaces the chunk at a position with stored pixels, sharing them if
+    /// they are compressed.
+    pub(crate) fn replace_chunk_storage(
+        &mut self,
+        chunk_position: ChunkPosition,
+        storage: Arc<ChunkStorage>,
+    ) -> Option<CanvasRect> {
+        if !storage.is_compressed() {
+            return self.replace_chunk(
+                chunk_position,
+                Some(storage.to_chunk(self.chunk_dimensions()).into_owned()),
+            );
+        }
 
-        let chunk_span = top_left_chunk.span(bottom_right_chunk);
+        self.chunks.insert(chunk_position, storage);
+        self.chunk_index.insert(chunk_position);
 
-        ChunkRect {
-            top_left_chunk,
-            chunk_dimensions: chunk_span,
-            top_left_in_chunk,
-            bottom_right_in_chunk,
-        }
+        Some(self.chunk_canvas_rect(chunk_position))
     }
 
-    fn iter_chunks_in_rect(&self, chunk_rect: ChunkRect) -> RasterChunkIterator {
-        RasterChunkIterator::new(self, chunk_rect)
+    /// The stored pixels of the chunk at a chunk position, shared with the
+    /// layer until either is drawn on.
+    pub(crate) fn chunk_storage(&self, chunk_position: ChunkPosition) -> Option<Arc<ChunkStorage>> {
+        self.chunks.get(&chunk_position).cloned()
     }
 
-    fn iter_mut_chunks_in_rect(&mut self, chunk_rect: ChunkRect) -> RasterChunkIteratorMut {
-        RasterChunkIteratorMut::new(self, chunk_rect)
+    /// Puts stored pixels at a chunk position without copying them,
+    /// unallocating it if `storage` is `None`, and returns the pixels that
+    /// were stored there. Full chunks are packed if the layer is packed.
+    pub(crate) fn swap_chunk_storage(
+        &mut self,
+        chunk_position: ChunkPosition,
+        storage: Option<Arc<ChunkStorage>>,
+    ) -> Option<Arc<ChunkStorage>> {
+        match storage {
+            Some(mut storage) => {
+                if self.pixel_format == PixelFormat::Rgb565A8 && !storage.is_compressed() {
+                    Arc::make_mut(&mut storage).pack();
+                }
+
+                self.chunk_index.insert(chunk_position);
+                self.chunks.insert(chunk_position, storage)
+            }
+            None => {
+                self.chunk_index.remove(chunk_position);
+                self.chunks.remove(&chunk_position)
+            }
+        }
     }
 
-    /// Composites a `RasterWindow` onto the layer with the top left at the position provided.
-    fn composite_over(&mut self, top_left: CanvasPosition, source: &RasterWindow) -> CanvasRect {
-        let canvas_rect = CanvasRect {
-            top_left,
-            dimensions: source.dimensions(),
-        };
+    /// Stores the pixels of the layer in `pixel_format`.
+    pub fn with_pixel_format(mut self, pixel_format: PixelFormat) -> RasterLayer {
+        self.set_pixel_format(pixel_format);
 
-        let chunk_rect = self.find_chunk_rect_in_canvas_rect(canvas_rect);
-        let mut raster_chunks_need_insert = HashMap::new();
-        let chunk_size = self.chunk_size;
+        self
+    }
 
-        for (raster_chunk, chunk_rect_position) in self.iter_mut_chunks_in_rect(chunk_rect) {
-            let ChunkRectPosition {
-                top_left_in_chunk,
-                width: _,
-                height: _,
-                x_chunk_offset,
-                y_chunk_offset,
-                x_pixel_offset,
-                y_pixel_offset,
-            } = chunk_rect_position;
+    pub fn alpha_locked(&self) -> bool {
+        self.alpha_locked
+    }
 
-            let pixel_offset: (i32, i32) = (x_pixel_offset as i32, y_pixel_offset as i32);
+    /// Sets whether drawing on the layer keeps the alpha of its pixels. While
+    /// locked, fills, shapes and other composited drawing only recolor what
+    /// has already been drawn, and transparent areas stay transparent.
+    pub fn set_alpha_locked(&mut self, alpha_locked: bool) {
+        self.alpha_locked = alpha_locked;
+    }
 
-            let top_left_in_chunk: (i32, i32) =
-                (top_left_in_chunk.0 as i32, top_left_in_chunk.1 as i32);
+    pub fn pixel_format(&self) -> PixelFormat {
+        self.pixel_format
+    }
 
-            let top_left_in_chunk = (
-                top_left_in_chunk.0 - pixel_offset.0,
-                top_left_in_chunk.1 - pixel_offset.1,
-            );
+    /// Converts the pixels of the layer to `pixel_format`. Converting to a
+    /// format with less precision loses color information.
+    pub fn set_pixel_format(&mut self, pixel_format: PixelFormat) {
+        self.pixel_format = pixel_format;
 
-            if let Some(raster_chunk) = raster_chunk {
-                raster_chunk.composite_over(source, top_left_in_chunk.into());
-            } else {
-                let mut raster_chunk = BoxRasterChunk::new(chunk_size, chunk_size);
-                let chunk_position = chunk_rect
-                    .top_left_chunk
-                    .translate((x_chunk_offset, y_chunk_offset).unchecked_into_position());
-                raster_chunk.composite_over(source, top_left_in_chunk.into());
-                raster_chunks_need_insert.insert(chunk_position, raster_chunk);
+        match pixel_format {
+            PixelFormat::Rgba8888 => {
+                let chunk_dimensions = self.chunk_dimensions();
+
+                for storage in self.chunks.values_mut() {
+                    if let ChunkStorage::Packed(_) = **storage {
+                        Arc::make_mut(storage).to_mut(chunk_dimensions);
+                    }
+                }
             }
-        }
+            PixelFormat::Rgb565A8 => {
+                let chunk_positions = self.allocated_chunk_positions();
 
-        for (chunk_position, raster_chunk) in raster_chunks_need_insert {
-            self.chunks.insert(chunk_position, raster_chunk);
+                self.pack_chunks_at(chunk_positions);
+            }
         }
-
-        canvas_rect
     }
 
-    /// Performs a raster canvas action, returning the canvas rect that
-    /// has been altered by it.
-    pub fn perform_action_with_cache(
-        &mut self,
-        action: RasterLayerAction,
-        shape_cache: &mut ShapeCache,
-    ) -> Option<CanvasRect> {
-        use RasterLayerAction::*;
-        match action {
-            FillRect(canvas_rect, pixel) => {
-                let chunk_rect = self.find_chunk_rect_in_canvas_rect(canvas_rect);
-                let chunk_size = self.chunk_size;
-                let mut raster_chunks_need_insert = HashMap::new();
-
-                for (raster_chunk, chunk_rect_position) in self.iter_mut_chunks_in_rect(chunk_rect)
-                {
-                    let ChunkRectPosition {
-                        top_left_in_chunk,
-                        width,
-                        height,
-                        x_chunk_offset,
-                        y_chunk_offset,
-                        x_pixel_offset: _,
-                        y_pixel_offset: _,
-                    } = chunk_rect_position;
-
-                    let draw_chunk = BoxRasterChunk::new_fill(pixel, width, height);
-                    if let Some(raster_chunk) = raster_chunk {
-                        raster_chunk.composite_over(
-                            &draw_chunk.as_window(),
-                            top_left_in_chunk.unchecked_into_position(),
-                        );
-                    } else {
-                        let mut raster_chunk = BoxRasterChunk::new(chunk_size, chunk_size);
-                        let chunk_position = chunk_rect
-                            .top_left_chunk
-                            .translate((x_chunk_offset, y_chunk_offset).unchecked_into_position());
-                        raster_chunk.composite_over(
-                            &draw_chunk.as_window(),
-                            top_left_in_chunk.unchecked_into_position(),
-                        );
-                        raster_chunks_need_insert.insert(chunk_position, raster_chunk);
-                    }
-                }
+    /// Packs the chunks at the chunk positions that have been drawn on, if
+    /// the layer's pixel format is packed.
+    fn pack_chunks_at(&mut self, chunk_positions: impl IntoIterator<Item = ChunkPosition>) {
+        if self.pixel_format != PixelFormat::Rgb565A8 {
+            return;
+        }
 
-                for (chunk_position, raster_chunk) in raster_chunks_need_insert {
-                    self.chunks.insert(chunk_position, raster_chunk);
+        for chunk_position in chunk_positions {
+            if let Some(storage) = self.chunks.get_mut(&chunk_position) {
+                if !storage.is_compressed() {
+                    Arc::make_mut(storage).pack();
                 }
-
-                Some(canvas_rect)
             }
-            FillOval(rect, pixel) => {
-                let oval = Oval::build_from_bound(
-                    rect.dimensions.width as u32,
-                    rect.dimensions.height as u32,
-                )
-                .color(pixel)
-                .build();
-
-                let oval_raster = shape_cache.get_oval(oval);
+        }
+    }
 
-                let canvas_rect = self.composite_over(rect.top_left, &oval_raster.as_window());
+    /// Packs the allocated chunks covering `canvas_rect` like
+    /// `pack_chunks_at`, visiting only the chunks a change to the rect can
+    /// have drawn on.
+    fn pack_chunks_in(&mut self, canvas_rect: CanvasRect) {
+        if self.pixel_format == PixelFormat::Rgb565A8 {
+            let chunk_positions = self.allocated_chunk_positions_in_rect(canvas_rect);
 
-                Some(canvas_rect)
-            }
+            self.pack_chunks_at(chunk_positions);
         }
     }
 
-    /// Performs a raster canvas action, returning the canvas rect that
-    /// has been altered by it.
-    pub fn perform_action(&mut self, action: RasterLayerAction) -> Option<CanvasRect> {
-        use RasterLayerAction::*;
-        match action {
-            FillRect(canvas_rect, pixel) => {
-                let chunk_rect = self.find_chunk_rect_in_canvas_rect(canvas_rect);
-                let mut raster_chunks_need_insert = HashMap::new();
-                let chunk_size = self.chunk_size;
+    /// Compresses the chunks at the chunk positions that are stored in full
+    /// and made of few enough colors to take less memory compressed.
+    fn compress_chunks_at(&mut self, chunk_positions: impl IntoIterator<Item = ChunkPosition>) {
+        let chunk_dimensions = self.chunk_dimensions();
 
-                for (raster_chunk, chunk_rect_position) in self.iter_mut_chunks_in_rect(chunk_rect)
-                {
-                    let ChunkRectPosition {
-                        top_left_in_chunk,
-                        width,
-                        height,
-                        x_chunk_offset,
-                        y_chunk_offset,
-                        x_pixel_offset: _,
-                        y_pixel_offset: _,
-                    } = chunk_rect_position;
-
-                    let draw_chunk = BoxRasterChunk::new_fill(pixel, width, height);
-
-                    if let Some(raster_chunk) = raster_chunk {
-                        raster_chunk.composite_over(
-                            &draw_chunk.as_window(),
-                            top_left_in_chunk.unchecked_into_position(),
-                        );
-                    } else {
-                        let mut raster_chunk = BoxRasterChunk::new(chunk_size, chunk_size);
-                        let chunk_position = chunk_rect
-                            .top_left_chunk
-                            .translate((x_chunk_offset, y_chunk_offset).unchecked_into_position());
-                        raster_chunk.composite_over(
-                            &draw_chunk.as_window(),
-                            top_left_in_chunk.unchecked_into_position(),
-                        );
-                        raster_chunks_need_insert.insert(chunk_position, raster_chunk);
-                    }
+        for chunk_position in chunk_positions {
+            if let Some(storage) = self.chunks.get_mut(&chunk_position) {
+                if storage.is_compressed() {
+                    continue;
                 }
 
-                for (chunk_position, raster_chunk) in raster_chunks_need_insert {
-                    self.chunks.insert(chunk_position, raster_chunk);
+                let compressed =
+                    ChunkStorage::compress(storage.to_chunk(chunk_dimensions).into_owned());
+                if compressed.is_compressed() {
+                    *storage = Arc::new(compressed);
                 }
-
-                Some(canvas_rect)
-            }
-            FillOval(rect, pixel) => {
-                let oval = Oval::build_from_bound(
-                    rect.dimensions.width as u32,
-                    rect.dimensions.height as u32,
-                )
-                .color(pixel)
-                .build();
-
-                let canvas_rect = self.composite_over(rect.top_left, &oval.rasterize().as_window());
-
-                Some(canvas_rect)
             }
         }
     }
-}
 
-impl Layer for RasterLayer {
-    fn rasterize(&mut self, view: &CanvasView) -> BoxRasterChunk {
-        let mut raster = self.rasterize_canvas_rect(CanvasRect {
-            top_left: view.top_left,
-            dimensions: view.canvas_dimensions,
-        });
+    /// Compresses the chunks completely covered by `canvas_rect`, which a fill
+    /// of it leaves with few colors.
+    fn compress_chunks_covered_by(&mut self, canvas_rect: CanvasRect) {
+        let covered_chunk_positions: Vec<ChunkPosition> = self
+            .chunk_positions_in_rect(canvas_rect)
+            .into_iter()
+            .filter(|chunk_position| {
+                canvas_rect
+                    .contains_with_offset(&self.chunk_canvas_rect(*chunk_position))
+                    .is_some()
+            })
+            .collect();
+
+        self.compress_chunks_at(covered_chunk_positions);
+    }
 
-        raster.nn_scale(view.view_dimensions);
+    /// Compresses every chunk of the layer made of few enough colors to take
+    /// less memory compressed. Compressed chunks are decompressed again when
+    /// they are drawn on.
+    pub fn compress_chunks(&mut self) {
+        let chunk_positions: Vec<ChunkPosition> = self.chunks.keys().copied().collect();
 
-        raster
+        self.compress_chunks_at(chunk_positions);
     }
 
-    fn rasterize_canvas_rect(&mut self, canvas_rect: CanvasRect) -> BoxRasterChunk {
-        let chunk_rect = self.find_chunk_rect_in_canvas_rect(canvas_rect);
+    /// The bytes taken by the pixels of the allocated chunks of the layer.
+    pub fn chunk_bytes(&self) -> usize {
+        self.chunks
+            .values()
+            .map(|storage| storage.byte_size())
+            .sum()
+    }
 
-        let Dimensions {
-            width: view_width,
-            height: view_height,
-        } = canvas_rect.dimensions;
-        let mut raster_result = BoxRasterChunk::new(view_width, view_height);
+    /// Creates a layer with a chunk size suited to a view of `view_dimensions`.
+    pub fn new_auto(view_dimensions: Dimensions) -> RasterLayer {
+        RasterLayer::new(auto_chunk_size(view_dimensions))
+    }
 
-        for (raster_chunk, chunk_rect_position) in self.iter_chunks_in_rect(chunk_rect) {
-            let ChunkRectPosition {
-                top_left_in_chunk,
-                width,
-                height,
-                x_chunk_offset: _,
-                y_chunk_offset: _,
-                x_pixel_offset,
-                y_pixel_offset,
-            } = chunk_rect_position;
+    /// Statistics about the actions performed on the layer so far.
+    pub fn chunk_usage(&self) -> &ChunkUsage {
+        &self.chunk_usage
+    }
 
-            let raster_chunk = raster_chunk.unwrap_or(&self.blank_chunk);
+    /// A chunk size that would suit the actions performed on the layer so far
+    /// better than the current one, if there is one.
+    pub fn recommended_chunk_size(&self) -> Option<usize> {
+        self.chunk_usage
+            .recommended_chunk_size()
+            .filter(|chunk_size| *chunk_size != self.chunk_size)
+    }
 
-            let raster_window =
-                RasterWindow::new(raster_chunk, top_left_in_chunk, width, height)
-                .expect("ChunkRectPosition returned by iter_chunks_in_rect should be completely contained in chunk");
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
 
-            let draw_position_in_result: DrawPosition =
-                (x_pixel_offset, y_pixel_offset).unchecked_into_position();
+    /// The chunk at a chunk position, if it has been allocated. Chunks of
+    /// layers with a packed pixel format and compressed chunks are unpacked
+    /// into a copy.
+    pub fn chunk(&self, chunk_position: ChunkPosition) -> Option<Cow<'_, BoxRasterChunk>> {
+        self.chunks
+            .get(&chunk_position)
+            .map(|storage| storage.to_chunk(self.chunk_dimensions()))
+    }
 
-            raster_result.blit(&raster_window, draw_position_in_result);
+    pub(crate) fn chunk_dimensions(&self) -> Dimensions {
+        Dimensions {
+            width: self.chunk_size,
+            height: self.chunk_size,
         }
+    }
 
-        raster_result
+    /// The canvas rect covered by the chunk at a chunk position.
+    pub fn chunk_canvas_rect(&self, chunk_position: ChunkPosition) -> CanvasRect {
+        CanvasRect {
+            top_left: chunk_position.mul(self.chunk_size as i32),
+            dimensions: Dimensions {
+                width: self.chunk_size,
+                height: self.chunk_size,
+            },
+        }
     }
 
-    fn clear(&mut self) {
-        self.chunks.clear();
+    /// The content hash of the chunk at a position, or `None` if it isn't
+    /// allocated.
+    pub fn chunk_hash(&self, chunk_position: ChunkPosition) -> Option<u64> {
+        self.chunk(chunk_position).map(|chunk| chunk.content_hash())
     }
 
-    fn rasterize_into_bump<'bump>(
-        &mut self,
-        view: &CanvasView,
-        bump: &'bump bumpalo::Bump,
-    ) -> BumpRasterChunk<'bump> {
-        if view.canvas_dimensions != view.view_dimensions {
-            let mut raster = self.rasterize_canvas_rect_into_bump(
-                CanvasRect {
-                    top_left: view.top_left,
-                    dimensions: view.canvas_dimensions,
-                },
-                bump,
+    /// Content hashes of every allocated chunk in the layer.
+    pub fn chunk_hashes(&self) -> HashMap<ChunkPosition, u64> {
+        self.chunks
+            .iter()
+            .map(|(chunk_position, storage)| {
+                (
+                    *chunk_position,
+                    storage.to_chunk(self.chunk_dimensions()).content_hash(),
+                )
+            })
+            .collect()
+    }
+
+    /// How the allocated chunks of the layer are filled. Chunks that are only
+    /// stored compressed are read without decompressing them.
+    pub fn occupancy(&self) -> LayerOccupancy {
+        let fills = self.chunks.values().map(|storage| match &**storage {
+            ChunkStorage::Uniform(pixel) => ChunkFill::of(std::iter::once(*pixel)),
+            ChunkStorage::Rle(runs) => ChunkFill::of(runs.iter().map(|(pixel, _)| *pixel)),
+            ChunkStorage::Packed(packed_chunk) => {
+                ChunkFill::of(packed_chunk.unpack().pixels().iter().copied())
+            }
+            ChunkStorage::Full(chunk) => ChunkFill::of(chunk.pixels().iter().copied()),
+            ChunkStorage::Fixed128(fixed_chunk) => {
+                ChunkFill::of(fixed_chunk.pixels().iter().copied())
+            }
+            ChunkStorage::Fixed256(fixed_chunk) => {
+                ChunkFill::of(fixed_chunk.pixels().iter().copied())
+            }
+        });
+
+        let mut occupancy = LayerOccupancy::default();
+        for fill in fills {
+            occupancy.populated_chunks += 1;
+
+            match fill {
+                ChunkFill::Transparent => occupancy.transparent_chunks += 1,
+                ChunkFill::SingleColor => occupancy.single_color_chunks += 1,
+                ChunkFill::Mixed => {}
+            }
+        }
+
+        occupancy
+    }
+
+    /// Counts the values of each channel of the pixels within `canvas_rect`.
+    /// Only allocated chunks are visited, and chunks of a single color are
+    /// counted without reading their pixels.
+    pub fn histogram(&self, canvas_rect: CanvasRect) -> Histogram {
+        let mut histogram = Histogram::new();
+        let mut allocated_pixels = 0;
+
+        for chunk_position in self.allocated_chunk_positions_in_rect(canvas_rect) {
+            let chunk_canvas_rect = self.chunk_canvas_rect(chunk_position);
+            let covered_rect = match chunk_canvas_rect.intersection(&canvas_rect) {
+                Some(covered_rect) => covered_rect,
+                None => continue,
+            };
+            let covered_pixels = covered_rect.dimensions.area() as u64;
+            allocated_pixels += covered_pixels;
+
+            if let Some(ChunkStorage::Uniform(pixel)) =
+                self.chunks.get(&chunk_position).map(Arc::as_ref)
+            {
+                histogram.record(*pixel, covered_pixels);
+                continue;
+            }
+
+            let chunk = match self.chunk(chunk_position) {
+                Some(chunk) => chunk,
+                None => continue,
+            };
+
+            let top_left_in_chunk = (
+                (covered_rect.top_left.0 - chunk_canvas_rect.top_left.0) as usize,
+                (covered_rect.top_left.1 - chunk_canvas_rect.top_left.1) as usize,
             );
-            raster.nn_scale_into_bump(view.view_dimensions, bump)
-        } else {
-            self.rasterize_canvas_rect_into_bump(
-                CanvasRect {
-                    top_left: view.top_left,
-                    dimensions: view.canvas_dimensions,
+            let covered_window = RasterWindow::new(
+                &chunk,
+                top_left_in_chunk.into(),
+                covered_rect.dimensions.width,
+                covered_rect.dimensions.height,
+            )
+            .expect("the intersection of a chunk's canvas rect should be within the chunk");
+
+            histogram.record_pixels(covered_window.iter_pixels());
+        }
+
+        let unallocated_pixels = canvas_rect.dimensions.area() as u64 - allocated_pixels;
+        histogram.record(colors::transparent(), unallocated_pixels);
+
+        histogram
+    }
+
+    /// Whether every pixel within `canvas_rect` is completely transparent.
+    /// Only allocated chunks are visited, and chunks of a single color are
+    /// checked without reading their pixels.
+    pub fn is_transparent_in(&self, canvas_rect: CanvasRect) -> bool {
+        for chunk_position in self.allocated_chunk_positions_in_rect(canvas_rect) {
+            if let Some(ChunkStorage::Uniform(pixel)) =
+                self.chunks.get(&chunk_position).map(Arc::as_ref)
+            {
+                if pixel.as_rgba().3 > 0 {
+                    return false;
+                }
+                continue;
+            }
+
+            let chunk = match self.chunk(chunk_position) {
+                Some(chunk) => chunk,
+                None => continue,
+            };
+            let chunk_canvas_rect = self.chunk_canvas_rect(chunk_position);
+            let covered_rect = match chunk_canvas_rect.intersection(&canvas_rect) {
+                Some(covered_rect) => covered_rect,
+                None => continue,
+            };
+
+            let top_left_in_chunk = (
+                (covered_rect.top_left.0 - chunk_canvas_rect.top_left.0) as usize,
+                (covered_rect.top_left.1 - chunk_canvas_rect.top_left.1) as usize,
+            );
+            let covered_window = RasterWindow::new(
+                &chunk,
+                top_left_in_chunk.into(),
+                covered_rect.dimensions.width,
+                covered_rect.dimensions.height,
+            )
+            .expect("the intersection of a chunk's canvas rect should be within the chunk");
+
+            if covered_window
+                .iter_pixels()
+                .any(|pixel| pixel.as_rgba().3 > 0)
+            {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// The average color of the pixels within `canvas_rect`, weighting colors
+    /// by their opacity, such as for brushes that pick up the color of the
+    /// canvas. Only allocated chunks are visited, and chunks of a single color
+    /// are added without reading their pixels.
+    pub fn average_color(&self, canvas_rect: CanvasRect) -> Pixel {
+        let mut color_sum = ColorSum::new();
+        let mut allocated_pixels = 0;
+
+        for chunk_position in self.allocated_chunk_positions_in_rect(canvas_rect) {
+            let chunk_canvas_rect = self.chunk_canvas_rect(chunk_position);
+            let covered_rect = match chunk_canvas_rect.intersection(&canvas_rect) {
+                Some(covered_rect) => covered_rect,
+                None => continue,
+            };
+            let covered_pixels = covered_rect.dimensions.area() as u64;
+            allocated_pixels += covered_pixels;
+
+            if let Some(ChunkStorage::Uniform(pixel)) =
+                self.chunks.get(&chunk_position).map(Arc::as_ref)
+            {
+                color_sum.record(*pixel, covered_pixels);
+                continue;
+            }
+
+            let chunk = match self.chunk(chunk_position) {
+                Some(chunk) => chunk,
+                None => continue,
+            };
+
+            let top_left_in_chunk = (
+                (covered_rect.top_left.0 - chunk_canvas_rect.top_left.0) as usize,
+                (covered_rect.top_left.1 - chunk_canvas_rect.top_left.1) as usize,
+            );
+            let covered_window = RasterWindow::new(
+                &chunk,
+                top_left_in_chunk.into(),
+                covered_rect.dimensions.width,
+                covered_rect.dimensions.height,
+            )
+            .expect("the intersection of a chunk's canvas rect should be within the chunk");
+
+            for row in covered_window.iter_rows() {
+                color_sum.record_row(row);
+            }
+        }
+
+        let unallocated_pixels = canvas_rect.dimensions.area() as u64 - allocated_pixels;
+        color_sum.record(colors::transparent(), unallocated_pixels);
+
+        color_sum.average()
+    }
+
+    /// Computes the distance from each pixel in `canvas_rect` to the nearest
+    /// pixel of the layer with any opacity, a chunk sized tile at a time. `f` is
+    /// called with the canvas rect of each tile and its distance field, in
+    /// which distances greater than `max_distance` are infinite.
+    pub fn for_each_distance_tile<F>(&self, canvas_rect: CanvasRect, max_distance: u32, f: F)
+    where
+        F: FnMut(CanvasRect, &DistanceField),
+    {
+        TiledDistanceTransform::new(self.chunk_size, max_distance).for_each_tile(
+            canvas_rect,
+            |source_rect| self.rasterize_canvas_rect_shared(source_rect),
+            f,
+        );
+    }
+
+    /// The positions of the chunks covering `canvas_rect`, whether they are
+    /// allocated or not.
+    pub fn chunk_positions_in_rect(&self, canvas_rect: CanvasRect) -> Vec<ChunkPosition> {
+        if canvas_rect.is_degenerate() {
+            return Vec::new();
+        }
+
+        let ChunkRect {
+            top_left_chunk,
+            chunk_dimensions,
+            ..
+        } = self.find_chunk_rect_in_canvas_rect(canvas_rect);
+
+        (0..chunk_dimensions.height as i32)
+            .flat_map(|y| (0..chunk_dimensions.width as i32).map(move |x| (x, y)))
+            .map(|offset| top_left_chunk.translate(offset.into()))
+            .collect()
+    }
+
+    /// The number of chunks covering `canvas_rect` that haven't been
+    /// allocated, counted without visiting each chunk of the rect.
+    pub fn unallocated_chunk_count(&self, canvas_rect: CanvasRect) -> usize {
+        if canvas_rect.is_degenerate() {
+            return 0;
+        }
+
+        let chunk_rect = self.find_chunk_rect_in_canvas_rect(canvas_rect);
+        let allocated_chunk_count = self.allocated_chunk_positions_in_rect(canvas_rect).len();
+
+        chunk_rect.chunk_dimensions.area() - allocated_chunk_count
+    }
+
+    /// The positions of every allocated chunk of the layer, in no particular
+    /// order.
+    pub fn allocated_chunk_positions(&self) -> Vec<ChunkPosition> {
+        self.chunks.keys().copied().collect()
+    }
+
+    /// The positions of the allocated chunks covering `canvas_rect`, from top
+    /// to bottom and left to right. The chunks are found through an index of
+    /// regions of chunks, so this is fast for both small rects and large,
+    /// mostly empty ones.
+    pub fn allocated_chunk_positions_in_rect(&self, canvas_rect: CanvasRect) -> Vec<ChunkPosition> {
+        if canvas_rect.is_degenerate() {
+            return Vec::new();
+        }
+
+        let ChunkRect {
+            top_left_chunk,
+            chunk_dimensions,
+            ..
+        } = self.find_chunk_rect_in_canvas_rect(canvas_rect);
+        let bottom_right_chunk = top_left_chunk.translate(
+            (
+                chunk_dimensions.width as i32 - 1,
+                chunk_dimensions.height as i32 - 1,
+            )
+                .into(),
+        );
+
+        self.chunk_index
+            .positions_in(top_left_chunk, bottom_right_chunk)
+    }
+
+    /// The allocated chunks covering `canvas_rect` along with their
+    /// positions, in the order of `RasterLayer::allocated_chunk_positions_in_rect`.
+    /// Nothing is visited for the unallocated parts of the rect, so operations
+    /// such as exporting scale with the content of the layer rather than the
+    /// area of the rect.
+    pub fn iter_allocated_chunks_in_rect(
+        &self,
+        canvas_rect: CanvasRect,
+    ) -> impl Iterator<Item = (ChunkPosition, Cow<'_, BoxRasterChunk>)> + '_ {
+        self.allocated_chunk_positions_in_rect(canvas_rect)
+            .into_iter()
+            .filter_map(|chunk_position| Some((chunk_position, self.chunk(chunk_position)?)))
+    }
+
+    /// The transparency of the layer as a mask with the same chunk size, for
+    /// editing it apart from the colors of the layer.
+    pub fn extract_alpha(&self) -> MaskLayer {
+        let mut mask = MaskLayer::new(self.chunk_size);
+
+        for chunk_position in self.allocated_chunk_positions() {
+            if let Some(chunk) = self.chunk(chunk_position) {
+                let alpha = MaskChunk::from_alpha(&chunk);
+
+                if !alpha.is_empty() {
+                    mask.replace_chunk(chunk_position, Some(alpha));
+                }
+            }
+        }
+
+        mask
+    }
+
+    /// Replaces the transparency of every allocated pixel of the layer with
+    /// the value of `mask` at the same position, the inverse of
+    /// `RasterLayer::extract_alpha`. Unallocated areas have no color to reveal,
+    /// so they stay transparent. Returns the canvas rect that has been altered,
+    /// or `None` if nothing is allocated.
+    pub fn apply_alpha(&mut self, mask: &MaskLayer) -> Option<CanvasRect> {
+        let mut changed_canvas_rect: Option<CanvasRect> = None;
+
+        for chunk_position in self.allocated_chunk_positions() {
+            let chunk_canvas_rect = self.chunk_canvas_rect(chunk_position);
+            let alpha = mask.rasterize_canvas_rect(chunk_canvas_rect);
+
+            let chunk_dimensions = self.chunk_dimensions();
+            let Some(storage) = self.chunks.get_mut(&chunk_position) else {
+                continue;
+            };
+            for (pixel, a) in Arc::make_mut(storage)
+                .to_mut(chunk_dimensions)
+                .pixels_mut()
+                .iter_mut()
+                .zip(alpha.pixels())
+            {
+                let (r, g, b, _) = pixel.as_rgba();
+                *pixel = Pixel::new_rgba(r, g, b, *a);
+            }
+
+            changed_canvas_rect = Some(match changed_canvas_rect {
+                Some(canvas_rect) => canvas_rect.spanning_rect(&chunk_canvas_rect),
+                None => chunk_canvas_rect,
+            });
+        }
+
+        if let Some(changed_canvas_rect) = changed_canvas_rect {
+            self.pack_chunks_in(changed_canvas_rect);
+        }
+
+        changed_canvas_rect
+    }
+
+    /// Replaces each allocated pixel within `canvas_rect` with `f` of it.
+    /// Returns the canvas rect that has been altered, or `None` if nothing
+    /// was allocated within it.
+    fn map_pixels<F>(&mut self, canvas_rect: CanvasRect, f: F) -> Option<CanvasRect>
+    where
+        F: Fn(Pixel) -> Pixel,
+    {
+        let mut changed = false;
+
+        for chunk_position in self.chunk_positions_in_rect(canvas_rect) {
+            let mut chunk = match self.chunk(chunk_position) {
+                Some(chunk) => chunk.into_owned(),
+                None => continue,
+            };
+            let chunk_top_left = self.chunk_canvas_rect(chunk_position).top_left;
+            let rect_in_chunk =
+                canvas_rect.translate((-chunk_top_left.0, -chunk_top_left.1).into());
+            let bottom_right = rect_in_chunk.bottom_right();
+
+            for (position, pixel) in chunk.enumerate_pixels_mut() {
+                let (x, y) = (position.0 as i32, position.1 as i32);
+
+                if (rect_in_chunk.top_left.0..=bottom_right.0).contains(&x)
+                    && (rect_in_chunk.top_left.1..=bottom_right.1).contains(&y)
+                {
+                    *pixel = f(*pixel);
+                }
+            }
+
+            self.replace_chunk(chunk_position, Some(chunk));
+            changed = true;
+        }
+
+        changed.then_some(canvas_rect)
+    }
+
+    /// Makes every pixel within `canvas_rect` transparent, unallocating chunks
+    /// that are left completely transparent. Returns the canvas rect that has
+    /// been altered, or `None` if nothing was allocated within it.
+    pub fn clear_rect(&mut self, canvas_rect: CanvasRect) -> Option<CanvasRect> {
+        self.erase_rect(canvas_rect, 255)
+    }
+
+    fn erase_rect(&mut self, canvas_rect: CanvasRect, strength: u8) -> Option<CanvasRect> {
+        self.erase_with(canvas_rect, |mask, top_left| {
+            mask.fill_rect(
+                strength,
+                DrawRect {
+                    top_left,
+                    dimensions: canvas_rect.dimensions,
                 },
-                bump,
             )
+        })
+    }
+
+    fn erase_oval(&mut self, canvas_rect: CanvasRect, strength: u8) -> Option<CanvasRect> {
+        let oval = Oval::build_from_bound(
+            canvas_rect.dimensions.width as u32,
+            canvas_rect.dimensions.height as u32,
+        )
+        .color(Pixel::new_rgba(0, 0, 0, strength))
+        .build();
+        let oval_mask = MaskChunk::from_alpha(&oval.rasterize());
+        let canvas_rect = CanvasRect {
+            top_left: canvas_rect.top_left,
+            dimensions: oval_mask.dimensions(),
+        };
+
+        self.erase_with(canvas_rect, |mask, top_left| {
+            mask.blit(&oval_mask.as_window(), top_left)
+        })
+    }
+
+    /// Reduces the alpha of the allocated pixels within `canvas_rect` by the
+    /// values of a mask the size of a chunk, drawn by `draw_mask` given the
+    /// position of the rect relative to the chunk. Chunks left completely
+    /// transparent are unallocated. Returns the canvas rect that has been
+    /// altered, or `None` if nothing was allocated within it.
+    fn erase_with<F>(&mut self, canvas_rect: CanvasRect, draw_mask: F) -> Option<CanvasRect>
+    where
+        F: Fn(&mut MaskChunk, DrawPosition),
+    {
+        let mut changed = false;
+
+        for chunk_position in self.chunk_positions_in_rect(canvas_rect) {
+            let mut chunk = match self.chunk(chunk_position) {
+                Some(chunk) => chunk.into_owned(),
+                None => continue,
+            };
+            let chunk_top_left = self.chunk_canvas_rect(chunk_position).top_left;
+
+            let mut mask = MaskChunk::new(self.chunk_size, self.chunk_size);
+            draw_mask(
+                &mut mask,
+                (
+                    canvas_rect.top_left.0 - chunk_top_left.0,
+                    canvas_rect.top_left.1 - chunk_top_left.1,
+                )
+                    .into(),
+            );
+
+            for (pixel, strength) in chunk.pixels_mut().iter_mut().zip(mask.pixels()) {
+                let (r, g, b, a) = pixel.as_rgba();
+                let a = (a as u32 * (255 - *strength as u32) + 127) / 255;
+                *pixel = Pixel::new_rgba(r, g, b, a as u8);
+            }
+
+            let is_transparent = chunk.pixels().iter().all(|pixel| pixel.as_rgba().3 == 0);
+            self.replace_chunk(chunk_position, (!is_transparent).then_some(chunk));
+            changed = true;
         }
+
+        changed.then_some(canvas_rect)
     }
 
-    fn rasterize_canvas_rect_into_bump<'bump>(
+    /// Replaces the chunk at a chunk position, unallocating it if `chunk` is `None`.
+    /// Returns the canvas rect of the replaced chunk, or `None` if the new chunk is
+    /// not of the layer's chunk size.
+    pub fn replace_chunk(
         &mut self,
+        chunk_position: ChunkPosition,
+        chunk: Option<BoxRasterChunk>,
+    ) -> Option<CanvasRect> {
+        match chunk {
+            Some(chunk) => {
+                let chunk_dimensions = Dimensions {
+                    width: self.chunk_size,
+                    height: self.chunk_size,
+                };
+
+                if chunk.dimensions() != chunk_dimensions {
+                    return None;
+                }
+
+                self.chunks
+                    .insert(chunk_position, Arc::new(ChunkStorage::Full(chunk)));
+                self.chunk_index.insert(chunk_position);
+                self.pack_chunks_at([chunk_position]);
+            }
+            None => {
+                self.chunks.remove(&chunk_position);
+                self.chunk_index.remove(chunk_position);
+            }
+        }
+
+        Some(self.chunk_canvas_rect(chunk_position))
+    }
+}
+
+/// How pixels copied onto a layer are combined with the pixels already there.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum CopyMode {
+    /// Replaces the pixels already there, including with transparency. On
+    /// alpha locked layers only the colors of the pixels are replaced.
+    Blit,
+    /// Composites over the pixels already there.
+    Composite,
+}
+
+/// An editing action that can be applied to a raster canvas.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RasterLayerAction {
+    /// Fills a rect with `pixel`.
+    FillRect(CanvasRect, Pixel),
+    /// Draws an oval bounded by a canvas rect, filled with `pixel`.
+    FillOval(CanvasRect, Pixel),
+    /// Draws a disc blending from its center to its edge, with the top left
+    /// of its bounds at a canvas position.
+    FillRadialGradient(CanvasPosition, RadialGradientDisc),
+    /// Draws a disc blending around its center, with the top left of its
+    /// bounds at a canvas position.
+    FillConicGradient(CanvasPosition, ConicGradient),
+    /// Fills a rect whose corners are rounded off with a radius with `pixel`.
+    FillRoundedRect(CanvasRect, u32, Pixel),
+    /// Draws an antialiased convex polygon through the corners of pixels at
+    /// the vertices, filled with `pixel`.
+    FillPolygon(Vec<CanvasPosition>, Pixel),
+    /// Reduces the alpha of the pixels within a rect by a strength from 0 to
+    /// 255, where 255 makes them completely transparent.
+    EraseRect(CanvasRect, u8),
+    /// Reduces the alpha of the pixels within an oval bounded by a rect by a
+    /// strength from 0 to 255, softened at the edge like `FillOval`.
+    EraseOval(CanvasRect, u8),
+    /// Draws an antialiased line between the centers of two pixels, covering
+    /// every pixel within `radius` of the segment between them.
+    DrawLine {
+        from: CanvasPosition,
+        to: CanvasPosition,
+        radius: u32,
+        color: Pixel,
+    },
+    /// Strokes a path of straight and curved segments, antialiased at the
+    /// edges of the stroke.
+    StrokePath(Path),
+    /// Scatters round dabs randomly around a position.
+    Spray(Spray),
+    /// Paints a round dab of pixels copied from elsewhere in the layer.
+    CloneStamp(CloneStamp),
+    /// Fills the area of similar color connected to a position.
+    FloodFill(FloodFill),
+    /// Draws a glow around the content of the layer within a canvas rect.
+    /// Content outside of the rect still casts a glow into it.
+    Glow(CanvasRect, Glow),
+    /// Filters the pixels within a canvas rect with a kernel, including the
+    /// pixels around the rect in the sums of the pixels near its edges.
+    ApplyKernel(CanvasRect, Kernel),
+    /// Changes the hue, saturation and value of the pixels within a canvas
+    /// rect, keeping their alpha.
+    AdjustHsv(CanvasRect, HsvAdjustment),
+    /// Draws antialiased text with its top left at the text object's position.
+    /// Unlike text on a `TextLayer`, the text can't be edited once drawn.
+    DrawText(TextObject),
+}
+
+/// A round dab of pixels copied from an offset area, the primitive behind clone
+/// stamp tools. A stroke is a series of stamps sharing a `source_offset`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CloneStamp {
+    /// The center of the painted dab.
+    pub center: CanvasPosition,
+    /// The offset from the painted dab to the area it is copied from.
+    pub source_offset: CanvasPosition,
+    pub diameter: u32,
+    pub falloff: Falloff,
+}
+
+impl CloneStamp {
+    fn dab(&self) -> BoxRasterChunk {
+        Oval::build_from_bound(self.diameter, self.diameter)
+            .falloff(self.falloff)
+            .build()
+            .rasterize()
+    }
+
+    /// The canvas rect painted by the stamp.
+    pub fn destination_rect(&self) -> CanvasRect {
+        let (width, height) = Oval::build_from_bound(self.diameter, self.diameter)
+            .build()
+            .bounding_box();
+
+        CanvasRect {
+            top_left: (
+                self.center.0 - width as i32 / 2,
+                self.center.1 - height as i32 / 2,
+            )
+                .into(),
+            dimensions: Dimensions { width, height },
+        }
+    }
+
+    /// The canvas rect the stamp copies pixels from.
+    pub fn source_rect(&self) -> CanvasRect {
+        self.destination_rect().translate(self.source_offset)
+    }
+
+    /// Masks pixels copied from the source rect with the shape of the dab.
+    fn mask(&self, source: &BoxRasterChunk) -> BoxRasterChunk {
+        let dab = self.dab();
+        let Dimensions { width, height } = dab.dimensions();
+
+        let pixels = source
+            .pixels()
+            .iter()
+            .zip(dab.pixels().iter())
+            .map(|(pixel, dab_pixel)| {
+                let (r, g, b, a) = pixel.as_rgba();
+                let coverage = dab_pixel.as_rgba().3;
+
+                Pixel::new_rgba(r, g, b, ((a as u32 * coverage as u32) / 255) as u8)
+            })
+            .collect();
+
+        BoxRasterChunk::from_vec(pixels, width, height)
+            .expect("source should be rasterized with the dimensions of the dab")
+    }
+}
+
+/// A burst of round dabs scattered randomly within a radius of a center. The
+/// dabs are placed from `seed`, so performing the same spray twice gives the
+/// same result.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Spray {
+    pub center: CanvasPosition,
+    pub radius: u32,
+    /// The number of dabs in the spray.
+    pub density: u32,
+    pub dab_diameter: u32,
+    /// How much the diameter of each dab can vary, as a percentage of `dab_diameter`.
+    pub size_jitter: u32,
+    pub color: Pixel,
+    pub falloff: Falloff,
+    pub seed: u64,
+}
+
+impl Spray {
+    /// The top left and shape of every dab in the spray.
+    fn dabs(&self) -> Vec<(CanvasPosition, Oval)> {
+        let mut rng = CanvasRng::new(self.seed);
+        let jitter = self.size_jitter as f32 / 100.0;
+
+        (0..self.density)
+            .map(|_| {
+                // Taking the root of the distance spreads dabs evenly over the disc
+                let angle = rng.next_f32() * std::f32::consts::TAU;
+                let distance = rng.next_f32().sqrt() * self.radius as f32;
+                let size_factor = 1.0 + jitter * (rng.next_f32() * 2.0 - 1.0);
+
+                let diameter = ((self.dab_diameter as f32 * size_factor).round() as u32).max(1);
+                let dab_center = (
+                    self.center.0 + (angle.cos() * distance).round() as i32,
+                    self.center.1 + (angle.sin() * distance).round() as i32,
+                );
+                let top_left = (
+                    dab_center.0 - diameter as i32 / 2,
+                    dab_center.1 - diameter as i32 / 2,
+                );
+
+                let oval = Oval::build_from_bound(diameter, diameter)
+                    .color(self.color)
+                    .falloff(self.falloff)
+                    .build();
+
+                (top_left.into(), oval)
+            })
+            .collect()
+    }
+}
+
+/// Fills the area of similar color connected to a position, as a fill bucket
+/// would. Since layers have no edges, the fill spreads no further than `bounds`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FloodFill {
+    pub position: CanvasPosition,
+    pub color: Pixel,
+    /// The largest difference in any channel from the color at `position`
+    /// that a pixel can have and still be filled.
+    pub tolerance: u8,
+    pub bounds: CanvasRect,
+}
+
+impl FloodFill {
+    fn is_similar(&self, a: Pixel, b: Pixel) -> bool {
+        let (r_a, g_a, b_a, a_a) = a.as_rgba();
+        let (r_b, g_b, b_b, a_b) = b.as_rgba();
+
+        [(r_a, r_b), (g_a, g_b), (b_a, b_b), (a_a, a_b)]
+            .into_iter()
+            .all(|(a, b)| a.abs_diff(b) <= self.tolerance)
+    }
+
+    /// Rasterizes the fill from `source`, the contents of the bounds, returning
+    /// the fill with its top left. Returns `None` if the position is not within
+    /// the bounds.
+    fn rasterize(&self, source: &BoxRasterChunk) -> Option<(CanvasPosition, BoxRasterChunk)> {
+        let Dimensions { width, height } = self.bounds.dimensions;
+        let x = usize::try_from(self.position.0 - self.bounds.top_left.0).ok()?;
+        let y = usize::try_from(self.position.1 - self.bounds.top_left.1).ok()?;
+        if x >= width || y >= height {
+            return None;
+        }
+
+        let pixels = source.pixels();
+        let target = pixels[y * width + x];
+
+        let mut filled = vec![false; width * height];
+        let mut stack = vec![(x, y)];
+        filled[y * width + x] = true;
+
+        let (mut left, mut top, mut right, mut bottom) = (x, y, x, y);
+
+        while let Some((x, y)) = stack.pop() {
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x);
+            bottom = bottom.max(y);
+
+            let neighbours = [
+                (x.checked_sub(1), Some(y)),
+                (Some(x + 1).filter(|x| *x < width), Some(y)),
+                (Some(x), y.checked_sub(1)),
+                (Some(x), Some(y + 1).filter(|y| *y < height)),
+            ];
+
+            for (x, y) in neighbours {
+                if let (Some(x), Some(y)) = (x, y) {
+                    let index = y * width + x;
+                    if !filled[index] && self.is_similar(pixels[index], target) {
+                        filled[index] = true;
+                        stack.push((x, y));
+                    }
+                }
+            }
+        }
+
+        let fill_width = right - left + 1;
+        let fill_height = bottom - top + 1;
+        let fill_pixels = (top..=bottom)
+            .flat_map(|y| (left..=right).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                if filled[y * width + x] {
+                    self.color
+                } else {
+                    colors::transparent()
+                }
+            })
+            .collect();
+
+        let fill = BoxRasterChunk::from_vec(fill_pixels, fill_width, fill_height)
+            .expect("fill pixels should match the fill dimensions");
+        let top_left = self
+            .bounds
+            .top_left
+            .translate((left as i32, top as i32).into());
+
+        Some((top_left, fill))
+    }
+}
+
+/// The shape drawn by `RasterLayerAction::FillRoundedRect`.
+fn rounded_rect_shape(canvas_rect: CanvasRect, radius: u32, pixel: Pixel) -> RoundedRectangle {
+    RoundedRectangle::new(
+        canvas_rect.dimensions.width as f32,
+        canvas_rect.dimensions.height as f32,
+        radius as f32,
+        pixel,
+    )
+}
+
+/// The shape drawn by `RasterLayerAction::FillPolygon`, with the canvas
+/// position of the top left of its bounding box.
+fn polygon_shape(vertices: &[CanvasPosition], pixel: Pixel) -> (CanvasPosition, ConvexPolygon) {
+    let left = vertices.iter().map(|v| v.0).min().unwrap_or(0);
+    let top = vertices.iter().map(|v| v.1).min().unwrap_or(0);
+    let vertices: Vec<(f32, f32)> = vertices.iter().map(|v| (v.0 as f32, v.1 as f32)).collect();
+
+    ((left, top).into(), ConvexPolygon::new(&vertices, pixel))
+}
+
+/// The canvas rect covered by a line drawn with `RasterLayerAction::DrawLine`.
+fn line_rect(from: CanvasPosition, to: CanvasPosition, radius: u32) -> CanvasRect {
+    CanvasRect {
+        top_left: (
+            from.0.min(to.0) - radius as i32,
+            from.1.min(to.1) - radius as i32,
+        )
+            .into(),
+        dimensions: Dimensions {
+            width: (from.0.abs_diff(to.0) + 2 * radius + 1) as usize,
+            height: (from.1.abs_diff(to.1) + 2 * radius + 1) as usize,
+        },
+    }
+}
+
+/// The canvas rect covered by a path stroked with
+/// `RasterLayerAction::StrokePath`.
+fn path_rect(path: &Path) -> CanvasRect {
+    let (width, height) = path.bounding_box();
+
+    CanvasRect {
+        top_left: path.origin().into(),
+        dimensions: Dimensions { width, height },
+    }
+}
+
+/// Rasterizes a line drawn with `RasterLayerAction::DrawLine`, returning it
+/// with the canvas rect it covers. Pixels within `radius` of the segment are
+/// fully covered, fading out over the pixel beyond that to antialias the edge.
+fn rasterize_line(
+    from: CanvasPosition,
+    to: CanvasPosition,
+    radius: u32,
+    color: Pixel,
+) -> (CanvasRect, BoxRasterChunk) {
+    let canvas_rect = line_rect(from, to, radius);
+    let (r, g, b, a) = color.as_rgba();
+
+    let relative = |position: CanvasPosition| {
+        (
+            (position.0 - canvas_rect.top_left.0) as f32,
+            (position.1 - canvas_rect.top_left.1) as f32,
+        )
+    };
+    let (from, to) = (relative(from), relative(to));
+    let direction = (to.0 - from.0, to.1 - from.1);
+    let length_squared = direction.0 * direction.0 + direction.1 * direction.1;
+
+    let pixels = canvas_rect
+        .dimensions
+        .iter_pixels()
+        .map(|position| {
+            let p = (position.0 as f32, position.1 as f32);
+            let t = if length_squared == 0.0 {
+                0.0
+            } else {
+                (((p.0 - from.0) * direction.0 + (p.1 - from.1) * direction.1) / length_squared)
+                    .clamp(0.0, 1.0)
+            };
+            let nearest = (from.0 + direction.0 * t, from.1 + direction.1 * t);
+            let distance = ((p.0 - nearest.0).powi(2) + (p.1 - nearest.1).powi(2)).sqrt();
+
+            let coverage = (radius as f32 + 1.0 - distance).clamp(0.0, 1.0);
+            Pixel::new_rgba(r, g, b, (a as f32 * coverage).round() as u8)
+        })
+        .collect();
+
+    let line = BoxRasterChunk::from_vec(
+        pixels,
+        canvas_rect.dimensions.width,
+        canvas_rect.dimensions.height,
+    )
+    .expect("a pixel is produced for every position of the rect");
+
+    (canvas_rect, line)
+}
+
+impl RasterLayerAction {
+    pub fn fill_rect(canvas_rect: CanvasRect, pixel: Pixel) -> RasterLayerAction {
+        RasterLayerAction::FillRect(canvas_rect, pixel)
+    }
+
+    pub fn fill_oval(canvas_rect: CanvasRect, pixel: Pixel) -> RasterLayerAction {
+        RasterLayerAction::FillOval(canvas_rect, pixel)
+    }
+
+    pub fn fill_radial_gradient(
+        top_left: CanvasPosition,
+        disc: RadialGradientDisc,
+    ) -> RasterLayerAction {
+        RasterLayerAction::FillRadialGradient(top_left, disc)
+    }
+
+    pub fn fill_conic_gradient(top_left: CanvasPosition, cone: ConicGradient) -> RasterLayerAction {
+        RasterLayerAction::FillConicGradient(top_left, cone)
+    }
+
+    pub fn fill_rounded_rect(
         canvas_rect: CanvasRect,
-        bump: &'bump bumpalo::Bump,
-    ) -> BumpRasterChunk<'bump> {
-        let chunk_rect = self.find_chunk_rect_in_canvas_rect(canvas_rect);
+        radius: u32,
+        pixel: Pixel,
+    ) -> RasterLayerAction {
+        RasterLayerAction::FillRoundedRect(canvas_rect, radius, pixel)
+    }
 
-        let Dimensions {
-            width: view_width,
-            height: view_height,
-        } = canvas_rect.dimensions;
-        let mut raster_result = BumpRasterChunk::new(view_width, view_height, bump);
+    pub fn fill_polygon(vertices: Vec<CanvasPosition>, pixel: Pixel) -> RasterLayerAction {
+        RasterLayerAction::FillPolygon(vertices, pixel)
+    }
 
-        for (raster_chunk, chunk_rect_position) in self.iter_chunks_in_rect(chunk_rect) {
-            let ChunkRectPosition {
-                top_left_in_chunk,
-                width,
-                height,
-                x_chunk_offset: _,
-                y_chunk_offset: _,
-                x_pixel_offset,
-                y_pixel_offset,
-            } = chunk_rect_position;
+    pub fn erase_rect(canvas_rect: CanvasRect, strength: u8) -> RasterLayerAction {
+        RasterLayerAction::EraseRect(canvas_rect, strength)
+    }
+
+    pub fn erase_oval(canvas_rect: CanvasRect, strength: u8) -> RasterLayerAction {
+        RasterLayerAction::EraseOval(canvas_rect, strength)
+    }
+
+    pub fn draw_line(
+        from: CanvasPosition,
+        to: CanvasPosition,
+        radius: u32,
+        color: Pixel,
+    ) -> RasterLayerAction {
+        RasterLayerAction::DrawLine {
+            from,
+            to,
+            radius,
+            color,
+        }
+    }
+
+    pub fn stroke_path(path: Path) -> RasterLayerAction {
+        RasterLayerAction::StrokePath(path)
+    }
+
+    pub fn spray(spray: Spray) -> RasterLayerAction {
+        RasterLayerAction::Spray(spray)
+    }
 
-            let raster_chunk = raster_chunk.unwrap_or(&self.blank_chunk);
+    pub fn clone_stamp(clone_stamp: CloneStamp) -> RasterLayerAction {
+        RasterLayerAction::CloneStamp(clone_stamp)
+    }
+
+    pub fn flood_fill(flood_fill: FloodFill) -> RasterLayerAction {
+        RasterLayerAction::FloodFill(flood_fill)
+    }
+
+    pub fn glow(canvas_rect: CanvasRect, glow: Glow) -> RasterLayerAction {
+        RasterLayerAction::Glow(canvas_rect, glow)
+    }
+
+    pub fn apply_kernel(canvas_rect: CanvasRect, kernel: Kernel) -> RasterLayerAction {
+        RasterLayerAction::ApplyKernel(canvas_rect, kernel)
+    }
+
+    pub fn adjust_hsv(
+        canvas_rect: CanvasRect,
+        hue_shift: f32,
+        saturation_scale: f32,
+        value_scale: f32,
+    ) -> RasterLayerAction {
+        RasterLayerAction::AdjustHsv(
+            canvas_rect,
+            HsvAdjustment::new(hue_shift, saturation_scale, value_scale),
+        )
+    }
+
+    pub fn draw_text(text_object: TextObject) -> RasterLayerAction {
+        RasterLayerAction::DrawText(text_object)
+    }
+
+    /// A canvas rect containing every pixel the action can alter, known before
+    /// it is performed. Returns `None` if the action can't alter anything.
+    pub fn bounding_rect(&self) -> Option<CanvasRect> {
+        fn shape_rect(top_left: CanvasPosition, shape: &impl Polygon) -> CanvasRect {
+            let (width, height) = shape.bounding_box();
+
+            CanvasRect {
+                top_left,
+                dimensions: Dimensions { width, height },
+            }
+        }
+        let oval_rect = |top_left: CanvasPosition, oval: &Oval| shape_rect(top_left, oval);
+
+        use RasterLayerAction::*;
+        match self {
+            FillRect(canvas_rect, _)
+            | FillRoundedRect(canvas_rect, ..)
+            | EraseRect(canvas_rect, _)
+            | Glow(canvas_rect, _)
+            | ApplyKernel(canvas_rect, _)
+            | AdjustHsv(canvas_rect, _) => Some(*canvas_rect),
+            FillPolygon(vertices, pixel) => {
+                if vertices.len() < 3 {
+                    return None;
+                }
+
+                let (top_left, polygon) = polygon_shape(vertices, *pixel);
+                let (width, height) = polygon.bounding_box();
+
+                Some(CanvasRect {
+                    top_left,
+                    dimensions: Dimensions { width, height },
+                })
+            }
+            FillOval(rect, _) | EraseOval(rect, _) => {
+                let oval = Oval::build_from_bound(
+                    rect.dimensions.width as u32,
+                    rect.dimensions.height as u32,
+                )
+                .build();
+
+                Some(oval_rect(rect.top_left, &oval))
+            }
+            FillRadialGradient(top_left, disc) => Some(shape_rect(*top_left, disc)),
+            FillConicGradient(top_left, cone) => Some(shape_rect(*top_left, cone)),
+            DrawLine {
+                from, to, radius, ..
+            } => Some(line_rect(*from, *to, *radius)),
+            StrokePath(path) => Some(path_rect(path)),
+            Spray(spray) => spray
+                .dabs()
+                .iter()
+                .map(|(top_left, oval)| oval_rect(*top_left, oval))
+                .reduce(|a, b| a.spanning_rect(&b)),
+            CloneStamp(clone_stamp) => Some(clone_stamp.destination_rect()),
+            FloodFill(flood_fill) => Some(flood_fill.bounds),
+            DrawText(text_object) => Some(text_object.canvas_rect()),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ChunkRectPosition {
+    pub top_left_in_chunk: PixelPosition,
+    pub width: usize,
+    pub height: usize,
+    pub x_chunk_offset: usize,
+    pub y_chunk_offset: usize,
+    pub x_pixel_offset: usize,
+    pub y_pixel_offset: usize,
+}
+
+/// A rectangle in chunk-space, also denotes where it starts
+/// and ends in the top-left and bottom-right chunks.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ChunkRect {
+    pub top_left_chunk: ChunkPosition,
+    pub chunk_dimensions: Dimensions,
+    pub top_left_in_chunk: PixelPosition,
+    pub bottom_right_in_chunk: PixelPosition,
+}
+
+impl ChunkRect {
+    /// Whether the chunk at `chunk_position` is one of the chunks of the rect.
+    pub fn contains_chunk(&self, chunk_position: ChunkPosition) -> bool {
+        let (x, y) = (
+            chunk_position.0 - self.top_left_chunk.0,
+            chunk_position.1 - self.top_left_chunk.1,
+        );
+
+        (0..self.chunk_dimensions.width as i32).contains(&x)
+            && (0..self.chunk_dimensions.height as i32).contains(&y)
+    }
+
+    /// Get the position most top-left within a chunk that is within the chunk rect.
+    /// Returns `None` if the requested position is not within this chunk-rect.
+    pub fn top_left_in_chunk(&self, chunk_position: ChunkPosition) -> Option<PixelPosition> {
+        let bottom_right_chunk = self.top_left_chunk.translate(
+            (
+                self.chunk_dimensions.width as i32,
+                self.chunk_dimensions.height as i32,
+            )
+                .into(),
+        );
+
+        let position_past_top_left =
+            chunk_position.0 < self.top_left_chunk.0 || chunk_position.1 < self.top_left_chunk.1;
+        let position_past_bottom_right =
+            chunk_position.0 > bottom_right_chunk.0 || chunk_position.1 > bottom_right_chunk.1;
+        if position_past_top_left || position_past_bottom_right {
+            None
+        } else {
+            let left_in_chunk = if chunk_position.0 == self.top_left_chunk.0 {
+                self.top_left_in_chunk.0
+            } else {
+                0
+            };
+
+            let top_in_chunk = if chunk_position.1 == self.top_left_chunk.1 {
+                self.top_left_in_chunk.1
+            } else {
+                0
+            };
+
+            Some((left_in_chunk, top_in_chunk).into())
+        }
+    }
+}
+
+impl RasterLayer {
+    fn find_chunk_rect_in_canvas_rect(&self, canvas_rect: CanvasRect) -> ChunkRect {
+        let CanvasRect {
+            top_left,
+            dimensions,
+        } = canvas_rect;
+
+        let top_left_chunk = top_left.containing_chunk(self.chunk_size);
+        let top_left_in_chunk = top_left.position_in_containing_chunk(self.chunk_size);
+
+        let bottom_right =
+            top_left.translate((dimensions.width as i32 - 1, dimensions.height as i32 - 1).into());
+        let bottom_right_chunk = bottom_right.containing_chunk(self.chunk_size);
+        let bottom_right_in_chunk = bottom_right.position_in_containing_chunk(self.chunk_size);
+
+        let chunk_span = top_left_chunk.span(bottom_right_chunk);
+
+        ChunkRect {
+            top_left_chunk,
+            chunk_dimensions: chunk_span,
+            top_left_in_chunk,
+            bottom_right_in_chunk,
+        }
+    }
+
+    /// The part of the chunk at `chunk_position` covered by
+    /// `chunk_rect_position`, found while reading `canvas_rect`.
+    fn chunk_window<'a>(
+        raster_chunk: &'a BoxRasterChunk,
+        chunk_position: ChunkPosition,
+        canvas_rect: CanvasRect,
+        chunk_rect_position: &ChunkRectPosition,
+    ) -> Result<RasterWindow<'a>, RasterizeError> {
+        let ChunkRectPosition {
+            top_left_in_chunk,
+            width,
+            height,
+            ..
+        } = *chunk_rect_position;
+
+        RasterWindow::new(raster_chunk, top_left_in_chunk, width, height).ok_or(
+            RasterizeError::WindowOutsideChunk {
+                chunk_position,
+                canvas_rect,
+                window_rect: RasterRect {
+                    top_left: top_left_in_chunk,
+                    dimensions: Dimensions { width, height },
+                },
+            },
+        )
+    }
+
+    /// Rasterizes a canvas rect of the layer like
+    /// `Layer::rasterize_canvas_rect_shared`, reporting inconsistent chunk
+    /// geometry instead of panicking.
+    pub fn try_rasterize_canvas_rect(
+        &self,
+        canvas_rect: CanvasRect,
+    ) -> Result<BoxRasterChunk, RasterizeError> {
+        let Dimensions { width, height } = canvas_rect.dimensions;
+        CanvasRect::try_new(canvas_rect.top_left, canvas_rect.dimensions)?;
+        let mut raster_result = BoxRasterChunk::try_new(width, height)?;
+
+        self.read_chunks_in_rect(canvas_rect, |raster_window, draw_position_in_result| {
+            raster_result.blit(raster_window, draw_position_in_result)
+        })?;
+
+        Ok(raster_result)
+    }
+
+    /// Copies the pixels within a canvas rect out of the layer, reading across
+    /// the chunks it spans. Unallocated areas are transparent.
+    pub fn extract_rect(&self, canvas_rect: CanvasRect) -> BoxRasterChunk {
+        self.try_rasterize_canvas_rect(canvas_rect)
+            .expect("chunks of a raster layer should be of its chunk size")
+    }
+
+    /// Rasterizes a canvas rect of the layer into `bump` like
+    /// `Layer::rasterize_canvas_rect_into_bump`, reporting inconsistent chunk
+    /// geometry instead of panicking.
+    pub fn try_rasterize_canvas_rect_into_bump<'bump>(
+        &self,
+        canvas_rect: CanvasRect,
+        bump: &'bump bumpalo::Bump,
+    ) -> Result<BumpRasterChunk<'bump>, RasterizeError> {
+        let Dimensions { width, height } =
+            CanvasRect::try_new(canvas_rect.top_left, canvas_rect.dimensions)?.dimensions;
+        let mut raster_result = BumpRasterChunk::new(width, height, bump);
+
+        self.read_chunks_in_rect(canvas_rect, |raster_window, draw_position_in_result| {
+            raster_result.blit(raster_window, draw_position_in_result)
+        })?;
+
+        Ok(raster_result)
+    }
+
+    /// Calls `f` with a window of each chunk covering `canvas_rect`, along with
+    /// the position of the window relative to the top left of the rect.
+    fn read_chunks_in_rect<F>(
+        &self,
+        canvas_rect: CanvasRect,
+        mut f: F,
+    ) -> Result<(), RasterizeError>
+    where
+        F: FnMut(&RasterWindow, DrawPosition),
+    {
+        let chunk_rect = self.find_chunk_rect_in_canvas_rect(canvas_rect);
+
+        for (raster_chunk, chunk_rect_position) in self.iter_chunks_in_rect(chunk_rect) {
+            let chunk_position = chunk_rect.top_left_chunk.translate(
+                (
+                    chunk_rect_position.x_chunk_offset,
+                    chunk_rect_position.y_chunk_offset,
+                )
+                    .unchecked_into_position(),
+            );
+
+            let raster_chunk = match raster_chunk {
+                Some(storage) => storage.to_chunk(self.chunk_dimensions()),
+                None => Cow::Borrowed(&self.blank_chunk),
+            };
+
+            let raster_window = RasterLayer::chunk_window(
+                &raster_chunk,
+                chunk_position,
+                canvas_rect,
+                &chunk_rect_position,
+            )?;
+
+            let draw_position_in_result: DrawPosition = (
+                chunk_rect_position.x_pixel_offset,
+                chunk_rect_position.y_pixel_offset,
+            )
+                .unchecked_into_position();
+
+            f(&raster_window, draw_position_in_result);
+        }
+
+        Ok(())
+    }
+
+    fn iter_chunks_in_rect(&self, chunk_rect: ChunkRect) -> RasterChunkIterator<'_> {
+        RasterChunkIterator::new(self, chunk_rect)
+    }
+
+    fn iter_mut_chunks_in_rect(&mut self, chunk_rect: ChunkRect) -> RasterChunkIteratorMut<'_> {
+        RasterChunkIteratorMut::new(self, chunk_rect)
+    }
+
+    /// Allocates the chunks drawn on while iterating over the chunks of a
+    /// rect that weren't allocated before.
+    fn insert_drawn_chunks(&mut self, drawn_chunks: HashMap<ChunkPosition, BoxRasterChunk>) {
+        for (chunk_position, raster_chunk) in drawn_chunks {
+            self.chunks
+                .insert(chunk_position, Arc::new(ChunkStorage::Full(raster_chunk)));
+            self.chunk_index.insert(chunk_position);
+        }
+    }
+
+    /// Composites a `RasterWindow` onto the layer with the top left at the position provided.
+    fn composite_over(&mut self, top_left: CanvasPosition, source: &RasterWindow) -> CanvasRect {
+        self.draw_window(top_left, source, CopyMode::Composite)
+    }
+
+    /// Draws a `RasterWindow` onto the layer with the top left at the position
+    /// provided, writing directly into the chunks it covers.
+    fn draw_window(
+        &mut self,
+        top_left: CanvasPosition,
+        source: &RasterWindow,
+        mode: CopyMode,
+    ) -> CanvasRect {
+        let canvas_rect = CanvasRect {
+            top_left,
+            dimensions: source.dimensions(),
+        };
+
+        let chunk_rect = self.find_chunk_rect_in_canvas_rect(canvas_rect);
+        let mut raster_chunks_need_insert = HashMap::new();
+        let chunk_dimensions = self.chunk_dimensions();
+        let alpha_locked = self.alpha_locked;
+
+        for (raster_chunk, chunk_rect_position) in self.iter_mut_chunks_in_rect(chunk_rect) {
+            let ChunkRectPosition {
+                top_left_in_chunk,
+                width: _,
+                height: _,
+                x_chunk_offset,
+                y_chunk_offset,
+                x_pixel_offset,
+                y_pixel_offset,
+            } = chunk_rect_position;
+
+            let pixel_offset: (i32, i32) = (x_pixel_offset as i32, y_pixel_offset as i32);
+
+            let top_left_in_chunk: (i32, i32) =
+                (top_left_in_chunk.0 as i32, top_left_in_chunk.1 as i32);
+
+            let top_left_in_chunk = (
+                top_left_in_chunk.0 - pixel_offset.0,
+                top_left_in_chunk.1 - pixel_offset.1,
+            );
+
+            let draw = |raster_chunk: &mut BoxRasterChunk| match mode {
+                CopyMode::Blit => {
+                    blit_onto_chunk(raster_chunk, source, top_left_in_chunk.into(), alpha_locked)
+                }
+                CopyMode::Composite => composite_onto_chunk(
+                    raster_chunk,
+                    source,
+                    top_left_in_chunk.into(),
+                    alpha_locked,
+                ),
+            };
+
+            if let Some(storage) = raster_chunk {
+                draw(storage.to_mut(chunk_dimensions));
+            } else {
+                let chunk_position = chunk_rect
+                    .top_left_chunk
+                    .translate((x_chunk_offset, y_chunk_offset).unchecked_into_position());
+                let mut raster_chunk =
+                    BoxRasterChunk::new(chunk_dimensions.width, chunk_dimensions.height);
+                draw(&mut raster_chunk);
+                raster_chunks_need_insert.insert(chunk_position, raster_chunk);
+            }
+        }
+
+        self.insert_drawn_chunks(raster_chunks_need_insert);
+
+        canvas_rect
+    }
+
+    /// Draws a raster onto the layer with its top left at `top_left`, such as
+    /// an image decoded with `BoxRasterChunk::decode_png`, returning the canvas
+    /// rect that has been altered.
+    pub fn draw_raster(
+        &mut self,
+        top_left: CanvasPosition,
+        raster: &BoxRasterChunk,
+        mode: CopyMode,
+    ) -> CanvasRect {
+        let changed_canvas_rect = self.draw_window(top_left, &raster.as_window(), mode);
+
+        self.pack_chunks_in(changed_canvas_rect);
+        self.chunk_usage.record(&changed_canvas_rect);
+
+        changed_canvas_rect
+    }
+
+    /// Draws an image onto the layer with its top left at `top_left`, replacing
+    /// the pixels already there, returning the canvas rect that has been
+    /// altered.
+    #[cfg(feature = "image")]
+    pub fn import_image(
+        &mut self,
+        top_left: CanvasPosition,
+        image: &::image::RgbaImage,
+    ) -> CanvasRect {
+        self.draw_raster(
+            top_left,
+            &BoxRasterChunk::from_rgba_image(image),
+            CopyMode::Blit,
+        )
+    }
+
+    /// Copies the pixels of `source` within `canvas_rect` onto the same area of
+    /// this layer, returning the canvas rect that has been altered. Only the
+    /// chunks of `source` within the rect are read, and they are drawn straight
+    /// into the chunks of this layer, so the layers may have different chunk
+    /// sizes. With `CopyMode::Composite`, unallocated areas of `source` are
+    /// skipped, since they are transparent.
+    pub fn copy_region_from(
+        &mut self,
+        source: &RasterLayer,
+        canvas_rect: CanvasRect,
+        mode: CopyMode,
+    ) -> CanvasRect {
+        let chunk_rect = source.find_chunk_rect_in_canvas_rect(canvas_rect);
+
+        for (raster_chunk, chunk_rect_position) in source.iter_chunks_in_rect(chunk_rect) {
+            let ChunkRectPosition {
+                top_left_in_chunk,
+                width,
+                height,
+                x_pixel_offset,
+                y_pixel_offset,
+                ..
+            } = chunk_rect_position;
+
+            let raster_chunk = match (raster_chunk, mode) {
+                (Some(storage), _) => storage.to_chunk(source.chunk_dimensions()),
+                (None, CopyMode::Blit) => Cow::Borrowed(&source.blank_chunk),
+                (None, CopyMode::Composite) => continue,
+            };
+
+            let raster_window = RasterWindow::new(&raster_chunk, top_left_in_chunk, width, height)
+                .expect("ChunkRectPosition returned by iter_chunks_in_rect should be completely contained in chunk");
+
+            let top_left = canvas_rect
+                .top_left
+                .translate((x_pixel_offset, y_pixel_offset).unchecked_into_position());
+
+            self.draw_window(top_left, &raster_window, mode);
+        }
+
+        self.pack_chunks_in(canvas_rect);
+        self.chunk_usage.record(&canvas_rect);
+
+        canvas_rect
+    }
+
+    /// Paints a clone stamp using `source`, the contents of the stamp's source
+    /// rect, returning the canvas rect that has been altered. The source is read
+    /// in full before anything is painted, so it may come from this layer even
+    /// when the source and destination overlap.
+    pub fn apply_clone_stamp(
+        &mut self,
+        clone_stamp: CloneStamp,
+        source: &BoxRasterChunk,
+    ) -> CanvasRect {
+        let destination_rect = clone_stamp.destination_rect();
+
+        let changed_canvas_rect = self.composite_over(
+            destination_rect.top_left,
+            &clone_stamp.mask(source).as_window(),
+        );
+        self.pack_chunks_in(changed_canvas_rect);
+
+        changed_canvas_rect
+    }
+
+    /// Composites `pixel` over `canvas_rect`. Chunks the rect covers are
+    /// filled through `ChunkStorage::composite_fill` rather than by drawing a
+    /// raster onto them.
+    fn fill_rect(&mut self, canvas_rect: CanvasRect, pixel: Pixel) {
+        let chunk_rect = self.find_chunk_rect_in_canvas_rect(canvas_rect);
+        let alpha_locked = self.alpha_locked;
+        let chunk_dimensions = self.chunk_dimensions();
+        let mut raster_chunks_need_insert = HashMap::new();
+
+        for (raster_chunk, chunk_rect_position) in self.iter_mut_chunks_in_rect(chunk_rect) {
+            let ChunkRectPosition {
+                top_left_in_chunk,
+                width,
+                height,
+                x_chunk_offset,
+                y_chunk_offset,
+                x_pixel_offset: _,
+                y_pixel_offset: _,
+            } = chunk_rect_position;
+
+            let covers_chunk = width == chunk_dimensions.width && height == chunk_dimensions.height;
+            let draw_chunk = || BoxRasterChunk::new_fill(pixel, width, height);
+
+            match raster_chunk {
+                Some(storage) if covers_chunk => {
+                    storage.composite_fill(pixel, chunk_dimensions, alpha_locked)
+                }
+                Some(storage) => composite_onto_chunk(
+                    storage.to_mut(chunk_dimensions),
+                    &draw_chunk().as_window(),
+                    top_left_in_chunk.unchecked_into_position(),
+                    alpha_locked,
+                ),
+                None => {
+                    let chunk_position = chunk_rect
+                        .top_left_chunk
+                        .translate((x_chunk_offset, y_chunk_offset).unchecked_into_position());
+                    let mut raster_chunk =
+                        BoxRasterChunk::new(chunk_dimensions.width, chunk_dimensions.height);
+                    composite_onto_chunk(
+                        &mut raster_chunk,
+                        &draw_chunk().as_window(),
+                        top_left_in_chunk.unchecked_into_position(),
+                        alpha_locked,
+                    );
+                    raster_chunks_need_insert.insert(chunk_position, raster_chunk);
+                }
+            }
+        }
+
+        self.insert_drawn_chunks(raster_chunks_need_insert);
+        self.compress_chunks_covered_by(canvas_rect);
+    }
+
+    /// Performs a raster canvas action, returning the canvas rect that
+    /// has been altered by it.
+    pub fn perform_action_with_cache(
+        &mut self,
+        action: RasterLayerAction,
+        shape_cache: &mut ShapeCache,
+    ) -> Option<CanvasRect> {
+        use RasterLayerAction::*;
+        let changed_canvas_rect = match action {
+            FillRect(canvas_rect, pixel) => {
+                self.fill_rect(canvas_rect, pixel);
+
+                Some(canvas_rect)
+            }
+            FillOval(rect, pixel) => {
+                let oval = Oval::build_from_bound(
+                    rect.dimensions.width as u32,
+                    rect.dimensions.height as u32,
+                )
+                .color(pixel)
+                .build();
+
+                let oval_raster = shape_cache.get_oval(oval);
+
+                let canvas_rect = self.composite_over(rect.top_left, &oval_raster.as_window());
+
+                Some(canvas_rect)
+            }
+            FillRadialGradient(top_left, disc) => {
+                let disc_raster = shape_cache.get_radial_gradient(disc);
+
+                Some(self.composite_over(top_left, &disc_raster.as_window()))
+            }
+            FillConicGradient(top_left, cone) => {
+                let cone_raster = shape_cache.get_conic_gradient(cone);
+
+                Some(self.composite_over(top_left, &cone_raster.as_window()))
+            }
+            FillRoundedRect(canvas_rect, radius, pixel) => {
+                let shape = rounded_rect_shape(canvas_rect, radius, pixel).rasterize();
+
+                Some(self.composite_over(canvas_rect.top_left, &shape.as_window()))
+            }
+            FillPolygon(vertices, _) if vertices.len() < 3 => None,
+            FillPolygon(vertices, pixel) => {
+                let (top_left, polygon) = polygon_shape(&vertices, pixel);
+
+                Some(self.composite_over(top_left, &polygon.rasterize().as_window()))
+            }
+            EraseRect(canvas_rect, strength) => self.erase_rect(canvas_rect, strength),
+            EraseOval(canvas_rect, strength) => self.erase_oval(canvas_rect, strength),
+            DrawLine {
+                from,
+                to,
+                radius,
+                color,
+            } => {
+                let (canvas_rect, line) = rasterize_line(from, to, radius, color);
+
+                Some(self.composite_over(canvas_rect.top_left, &line.as_window()))
+            }
+            StrokePath(path) => {
+                Some(self.composite_over(path.origin().into(), &path.rasterize().as_window()))
+            }
+            Spray(spray) => spray
+                .dabs()
+                .into_iter()
+                .map(|(top_left, oval)| {
+                    self.composite_over(top_left, &shape_cache.get_oval(oval).as_window())
+                })
+                .reduce(|a, b| a.spanning_rect(&b)),
+            CloneStamp(clone_stamp) => {
+                let source = self.rasterize_canvas_rect_shared(clone_stamp.source_rect());
+
+                Some(self.apply_clone_stamp(clone_stamp, &source))
+            }
+            FloodFill(flood_fill) => {
+                let source = self.rasterize_canvas_rect_shared(flood_fill.bounds);
+                let (top_left, fill) = flood_fill.rasterize(&source)?;
+
+                Some(self.composite_over(top_left, &fill.as_window()))
+            }
+            Glow(canvas_rect, glow) => {
+                let glow_raster = glow.rasterize_canvas_rect(canvas_rect, |source_rect| {
+                    self.rasterize_canvas_rect_shared(source_rect)
+                });
+
+                Some(self.composite_over(canvas_rect.top_left, &glow_raster.as_window()))
+            }
+            ApplyKernel(canvas_rect, kernel) => {
+                let filtered = kernel.rasterize_canvas_rect(canvas_rect, |source_rect| {
+                    self.rasterize_canvas_rect_shared(source_rect)
+                });
+
+                Some(self.draw_window(canvas_rect.top_left, &filtered.as_window(), CopyMode::Blit))
+            }
+            AdjustHsv(canvas_rect, adjustment) => {
+                self.map_pixels(canvas_rect, |pixel| adjustment.apply(pixel))
+            }
+            DrawText(text_object) => Some(
+                self.composite_over(text_object.position, &text_object.rasterize().as_window()),
+            ),
+        };
+
+        if let Some(changed_canvas_rect) = changed_canvas_rect {
+            self.pack_chunks_in(changed_canvas_rect);
+            self.chunk_usage.record(&changed_canvas_rect);
+        }
+
+        changed_canvas_rect
+    }
+
+    /// Paints a stroke of `brush` stamps through `points`, returning the
+    /// canvas rect that has been altered by it.
+    pub fn draw_stroke(&mut self, points: &[CanvasPosition], brush: &Brush) -> Option<CanvasRect> {
+        let (stroke_rect, stroke) = brush.rasterize_stroke(points)?;
+
+        let changed_canvas_rect = self.composite_over(stroke_rect.top_left, &stroke.as_window());
+        self.pack_chunks_in(changed_canvas_rect);
+        self.chunk_usage.record(&changed_canvas_rect);
+
+        Some(changed_canvas_rect)
+    }
+
+    /// Performs a raster canvas action like `perform_action_with_cache`, but
+    /// only changes the pixels of `selection`, returning the canvas rect that
+    /// has been altered by it. Only the chunks the action changed are written
+    /// back, and those left fully transparent are unallocated.
+    pub fn perform_action_in_selection(
+        &mut self,
+        action: RasterLayerAction,
+        selection: &Selection,
+        shape_cache: &mut ShapeCache,
+    ) -> Option<CanvasRect> {
+        let bounding_rect = action.bounding_rect()?;
+        let before: HashMap<ChunkPosition, Arc<ChunkStorage>> = self
+            .allocated_chunk_positions_in_rect(bounding_rect)
+            .into_iter()
+            .filter_map(|chunk_position| {
+                Some((chunk_position, self.chunk_storage(chunk_position)?))
+            })
+            .collect();
+
+        let changed_canvas_rect = self.perform_action_with_cache(action, shape_cache)?;
+
+        let chunk_dimensions = self.chunk_dimensions();
+        for chunk_position in self.chunk_positions_in_rect(changed_canvas_rect) {
+            let before = before.get(&chunk_position);
+            let after = self.chunks.get(&chunk_position);
+            let unchanged = match (before, after) {
+                (Some(before), Some(after)) => Arc::ptr_eq(before, after),
+                (None, None) => true,
+                _ => false,
+            };
+            if unchanged {
+                continue;
+            }
+
+            let to_chunk = |storage: Option<&Arc<ChunkStorage>>| match storage {
+                Some(storage) => storage.to_chunk(chunk_dimensions).into_owned(),
+                None => BoxRasterChunk::new(chunk_dimensions.width, chunk_dimensions.height),
+            };
+            let before = to_chunk(before);
+            let mut after = to_chunk(after);
+            selection.restrict_change(self.chunk_canvas_rect(chunk_position), &before, &mut after);
+
+            let storage = (!after.pixels().iter().all(|pixel| pixel.as_rgba().3 == 0))
+                .then(|| Arc::new(ChunkStorage::Full(after)));
+            self.swap_chunk_storage(chunk_position, storage);
+        }
+
+        Some(changed_canvas_rect)
+    }
+
+    /// Checks that `action` can be performed on the layer without panicking:
+    /// that the rect it covers passes `Rect::try_new` and that the allocated
+    /// chunks within it are of the layer's chunk size.
+    pub fn check_action(&self, action: &RasterLayerAction) -> Result<(), RasterizeError> {
+        let Some(bounding_rect) = action.bounding_rect() else {
+            return Ok(());
+        };
+        let bounding_rect = CanvasRect::try_new(bounding_rect.top_left, bounding_rect.dimensions)?;
+
+        let chunk_dimensions = self.chunk_dimensions();
+        for chunk_position in self.allocated_chunk_positions_in_rect(bounding_rect) {
+            if !self.chunks[&chunk_position].fits(chunk_dimensions) {
+                return Err(RasterizeError::ChunkSizeMismatch {
+                    chunk_position,
+                    chunk_dimensions,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Performs an action like `RasterLayer::perform_action`, returning an
+    /// error instead of panicking if it doesn't pass
+    /// `RasterLayer::check_action`.
+    pub fn try_perform_action(
+        &mut self,
+        action: RasterLayerAction,
+    ) -> Result<Option<CanvasRect>, RasterizeError> {
+        self.check_action(&action)?;
+
+        Ok(self.perform_action(action))
+    }
+
+    /// Performs a raster canvas action, returning the canvas rect that
+    /// has been altered by it.
+    ///
+    /// # Panics
+    ///
+    /// If the action doesn't pass `RasterLayer::check_action`. Use
+    /// `RasterLayer::try_perform_action` with actions from untrusted input.
+    pub fn perform_action(&mut self, action: RasterLayerAction) -> Option<CanvasRect> {
+        use RasterLayerAction::*;
+        let changed_canvas_rect = match action {
+            FillRect(canvas_rect, pixel) => {
+                self.fill_rect(canvas_rect, pixel);
+
+                Some(canvas_rect)
+            }
+            FillOval(rect, pixel) => {
+                let oval = Oval::build_from_bound(
+                    rect.dimensions.width as u32,
+                    rect.dimensions.height as u32,
+                )
+                .color(pixel)
+                .build();
+
+                let canvas_rect = self.composite_over(rect.top_left, &oval.rasterize().as_window());
+
+                Some(canvas_rect)
+            }
+            FillRadialGradient(top_left, disc) => {
+                Some(self.composite_over(top_left, &disc.rasterize().as_window()))
+            }
+            FillConicGradient(top_left, cone) => {
+                Some(self.composite_over(top_left, &cone.rasterize().as_window()))
+            }
+            FillRoundedRect(canvas_rect, radius, pixel) => {
+                let shape = rounded_rect_shape(canvas_rect, radius, pixel).rasterize();
+
+                Some(self.composite_over(canvas_rect.top_left, &shape.as_window()))
+            }
+            FillPolygon(vertices, _) if vertices.len() < 3 => None,
+            FillPolygon(vertices, pixel) => {
+                let (top_left, polygon) = polygon_shape(&vertices, pixel);
+
+                Some(self.composite_over(top_left, &polygon.rasterize().as_window()))
+            }
+            EraseRect(canvas_rect, strength) => self.erase_rect(canvas_rect, strength),
+            EraseOval(canvas_rect, strength) => self.erase_oval(canvas_rect, strength),
+            DrawLine {
+                from,
+                to,
+                radius,
+                color,
+            } => {
+                let (canvas_rect, line) = rasterize_line(from, to, radius, color);
+
+                Some(self.composite_over(canvas_rect.top_left, &line.as_window()))
+            }
+            StrokePath(path) => {
+                Some(self.composite_over(path.origin().into(), &path.rasterize().as_window()))
+            }
+            Spray(spray) => spray
+                .dabs()
+                .into_iter()
+                .map(|(top_left, oval)| {
+                    self.composite_over(top_left, &oval.rasterize().as_window())
+                })
+                .reduce(|a, b| a.spanning_rect(&b)),
+            CloneStamp(clone_stamp) => {
+                let source = self.rasterize_canvas_rect_shared(clone_stamp.source_rect());
+
+                Some(self.apply_clone_stamp(clone_stamp, &source))
+            }
+            FloodFill(flood_fill) => {
+                let source = self.rasterize_canvas_rect_shared(flood_fill.bounds);
+                let (top_left, fill) = flood_fill.rasterize(&source)?;
+
+                Some(self.composite_over(top_left, &fill.as_window()))
+            }
+            Glow(canvas_rect, glow) => {
+                let glow_raster = glow.rasterize_canvas_rect(canvas_rect, |source_rect| {
+                    self.rasterize_canvas_rect_shared(source_rect)
+                });
+
+                Some(self.composite_over(canvas_rect.top_left, &glow_raster.as_window()))
+            }
+            ApplyKernel(canvas_rect, kernel) => {
+                let filtered = kernel.rasterize_canvas_rect(canvas_rect, |source_rect| {
+                    self.rasterize_canvas_rect_shared(source_rect)
+                });
+
+                Some(self.draw_window(canvas_rect.top_left, &filtered.as_window(), CopyMode::Blit))
+            }
+            AdjustHsv(canvas_rect, adjustment) => {
+                self.map_pixels(canvas_rect, |pixel| adjustment.apply(pixel))
+            }
+            DrawText(text_object) => Some(
+                self.composite_over(text_object.position, &text_object.rasterize().as_window()),
+            ),
+        };
+
+        if let Some(changed_canvas_rect) = changed_canvas_rect {
+            self.pack_chunks_in(changed_canvas_rect);
+            self.chunk_usage.record(&changed_canvas_rect);
+        }
+
+        changed_canvas_rect
+    }
+}
+
+impl Layer for RasterLayer {
+    fn rasterize(&mut self, view: &CanvasView) -> BoxRasterChunk {
+        let mut raster = self.rasterize_canvas_rect(CanvasRect {
+            top_left: view.top_left,
+            dimensions: view.canvas_dimensions,
+        });
+
+        raster.nn_scale(view.view_dimensions);
+
+        raster
+    }
+
+    fn rasterize_canvas_rect(&mut self, canvas_rect: CanvasRect) -> BoxRasterChunk {
+        self.rasterize_canvas_rect_shared(canvas_rect)
+    }
+
+    fn rasterize_canvas_rect_shared(&self, canvas_rect: CanvasRect) -> BoxRasterChunk {
+        self.extract_rect(canvas_rect)
+    }
+
+    fn clear(&mut self) {
+        self.chunks.clear();
+        self.chunk_index.clear();
+    }
+
+    fn content_bounds(&self) -> Option<CanvasRect> {
+        self.allocated_chunk_positions()
+            .into_iter()
+            .map(|chunk_position| self.chunk_canvas_rect(chunk_position))
+            .reduce(|a, b| a.spanning_rect(&b))
+    }
+
+    fn blend_if(&self) -> Option<BlendIf> {
+        self.blend_if
+    }
+
+    fn set_blend_if(&mut self, blend_if: Option<BlendIf>) {
+        self.blend_if = blend_if;
+    }
+
+    fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+    }
+
+    fn glow(&self) -> Option<Glow> {
+        self.glow
+    }
+
+    fn set_glow(&mut self, glow: Option<Glow>) {
+        self.glow = glow;
+    }
+
+    fn rasterize_into_bump<'bump>(
+        &mut self,
+        view: &CanvasView,
+        bump: &'bump bumpalo::Bump,
+    ) -> BumpRasterChunk<'bump> {
+        if view.canvas_dimensions != view.view_dimensions {
+            let mut raster = self.rasterize_canvas_rect_into_bump(
+                CanvasRect {
+                    top_left: view.top_left,
+                    dimensions: view.canvas_dimensions,
+                },
+                bump,
+            );
+            raster.nn_scale_into_bump(view.view_dimensions, bump)
+        } else {
+            self.rasterize_canvas_rect_into_bump(
+                CanvasRect {
+                    top_left: view.top_left,
+                    dimensions: view.canvas_dimensions,
+                },
+                bump,
+            )
+        }
+    }
+
+    fn rasterize_canvas_rect_into_bump<'bump>(
+        &mut self,
+        canvas_rect: CanvasRect,
+        bump: &'bump bumpalo::Bump,
+    ) -> BumpRasterChunk<'bump> {
+        self.try_rasterize_canvas_rect_into_bump(canvas_rect, bump)
+            .expect("chunks of a raster layer should be of its chunk size")
+    }
+
+    fn try_rasterize_canvas_rect(
+        &mut self,
+        canvas_rect: CanvasRect,
+    ) -> Result<BoxRasterChunk, RasterizeError> {
+        RasterLayer::try_rasterize_canvas_rect(self, canvas_rect)
+    }
+
+    fn try_rasterize_canvas_rect_into_bump<'bump>(
+        &mut self,
+        canvas_rect: CanvasRect,
+        bump: &'bump bumpalo::Bump,
+    ) -> Result<BumpRasterChunk<'bump>, RasterizeError> {
+        RasterLayer::try_rasterize_canvas_rect_into_bump(self, canvas_rect, bump)
+    }
+}
+
+/// The serialized form of a `RasterLayer`, holding only the chunks that
+/// have been allocated.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename = "RasterLayer")]
+struct SerializedRasterLayer<'a> {
+    chunk_size: usize,
+    pixel_format: PixelFormat,
+    chunks: Vec<(ChunkPosition, Cow<'a, BoxRasterChunk>)>,
+    blend_if: Option<BlendIf>,
+    blend_mode: BlendMode,
+    glow: Option<Glow>,
+    #[serde(default)]
+    alpha_locked: bool,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for RasterLayer {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut chunks: Vec<(ChunkPosition, Cow<'_, BoxRasterChunk>)> = self
+            .chunks
+            .iter()
+            .map(|(chunk_position, storage)| {
+                (*chunk_position, storage.to_chunk(self.chunk_dimensions()))
+            })
+            .collect();
+        chunks.sort_by_key(|(chunk_position, _)| (chunk_position.1, chunk_position.0));
+
+        SerializedRasterLayer {
+            chunk_size: self.chunk_size,
+            pixel_format: self.pixel_format,
+            chunks,
+            blend_if: self.blend_if,
+            blend_mode: self.blend_mode,
+            glow: self.glow,
+            alpha_locked: self.alpha_locked,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for RasterLayer {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let serialized_layer = SerializedRasterLayer::deserialize(deserializer)?;
+        let chunk_size = serialized_layer.chunk_size;
+
+        if chunk_size == 0 {
+            return Err(serde::de::Error::custom("raster layer chunk size is 0"));
+        }
+
+        let mut layer = RasterLayer::new(chunk_size);
+
+        for (chunk_position, chunk) in serialized_layer.chunks {
+            let chunk_dimensions = Dimensions {
+                width: chunk_size,
+                height: chunk_size,
+            };
+
+            if chunk.dimensions() != chunk_dimensions {
+                return Err(serde::de::Error::custom(format!(
+                    "chunk at {chunk_position:?} is not {chunk_size}x{chunk_size}"
+                )));
+            }
+
+            layer.chunks.insert(
+                chunk_position,
+                Arc::new(ChunkStorage::Full(chunk.into_owned())),
+            );
+            layer.chunk_index.insert(chunk_position);
+        }
+
+        layer.set_pixel_format(serialized_layer.pixel_format);
+        layer.blend_if = serialized_layer.blend_if;
+        layer.blend_mode = serialized_layer.blend_mode;
+        layer.glow = serialized_layer.glow;
+        layer.alpha_locked = serialized_layer.alpha_locked;
+
+        Ok(layer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assert_raster_eq,
+        primitives::rect::DrawRect,
+        raster::{pixels::colors, GlowStyle},
+    };
+
+    /// The number of chunks of `raster_layer` whose storage matches `stored`.
+    fn stored_chunks(raster_layer: &RasterLayer, stored: fn(&ChunkStorage) -> bool) -> usize {
+        raster_layer
+            .chunks
+            .values()
+            .filter(|storage| stored(storage))
+            .count()
+    }
+
+    fn is_packed(storage: &ChunkStorage) -> bool {
+        matches!(storage, ChunkStorage::Packed(_))
+    }
+
+    fn is_full(storage: &ChunkStorage) -> bool {
+        matches!(storage, ChunkStorage::Full(_))
+    }
+
+    #[test]
+    fn chunk_visibility_easy() {
+        let raster_layer = RasterLayer::new(10);
+
+        let mut canvas_rect = CanvasRect::at_origin(Dimensions {
+            width: 10,
+            height: 10,
+        });
+
+        assert_eq!(
+            raster_layer.find_chunk_rect_in_canvas_rect(canvas_rect),
+            ChunkRect {
+                top_left_chunk: (0, 0).into(),
+                chunk_dimensions: Dimensions {
+                    width: 1,
+                    height: 1
+                },
+                top_left_in_chunk: (0, 0).into(),
+                bottom_right_in_chunk: (9, 9).into(),
+            }
+        );
+
+        canvas_rect.top_left = (-5, -2).into();
+
+        assert_eq!(
+            raster_layer.find_chunk_rect_in_canvas_rect(canvas_rect),
+            ChunkRect {
+                top_left_chunk: (-1, -1).into(),
+                chunk_dimensions: Dimensions {
+                    width: 2,
+                    height: 2
+                },
+                top_left_in_chunk: (10 - 5, 10 - 2).into(),
+                bottom_right_in_chunk: (9 - 5, 9 - 2).into(),
+            }
+        );
+    }
+
+    #[test]
+    fn chunk_visibility_medium() {
+        let raster_layer = RasterLayer::new(1024);
+
+        let mut canvas_rect = CanvasRect::at_origin(Dimensions {
+            width: 2000,
+            height: 2000,
+        });
+        canvas_rect.top_left = (-500, -500).into();
+
+        assert_eq!(
+            raster_layer.find_chunk_rect_in_canvas_rect(canvas_rect),
+            ChunkRect {
+                top_left_chunk: (-1, -1).into(),
+                chunk_dimensions: Dimensions {
+                    width: 3,
+                    height: 3
+                },
+                top_left_in_chunk: (524, 524).into(),
+                bottom_right_in_chunk: (500 - 24 - 1, 500 - 24 - 1).into(),
+            }
+        );
+    }
+
+    #[test]
+    fn chunk_visibility_hard() {
+        let raster_layer = RasterLayer::new(512);
+
+        let mut canvas_rect = CanvasRect::at_origin(Dimensions {
+            width: 2000,
+            height: 1000,
+        });
+        canvas_rect.top_left = (-500, -1000).into();
+
+        assert_eq!(
+            raster_layer.find_chunk_rect_in_canvas_rect(canvas_rect),
+            ChunkRect {
+                top_left_chunk: (-1, -2).into(),
+                chunk_dimensions: Dimensions {
+                    width: 4,
+                    height: 2
+                },
+                top_left_in_chunk: (12, 24).into(),
+                bottom_right_in_chunk: (512 - 36 - 1, 512 - 1).into(),
+            }
+        );
+    }
+
+    #[test]
+    fn rasterize_offset() {
+        let mut raster_layer = RasterLayer::new(10);
+
+        let red_chunk = BoxRasterChunk::new_fill(colors::red(), 10, 10);
+        raster_layer
+            .chunks
+            .insert((0, 0).into(), ChunkStorage::Full(red_chunk.clone()).into());
+
+        let mut view = CanvasView::new(10, 10);
+
+        view.translate((-5, 0).into());
+
+        let mut expected_result = BoxRasterChunk::new(10, 10);
+        // DrawPosition::from((5, 0)), 5, 10
+        expected_result.fill_rect(
+            colors::red(),
+            DrawRect {
+                top_left: (5, 0).into(),
+                dimensions: Dimensions {
+                    width: 5,
+                    height: 10,
+                },
+            },
+        );
+
+        let raster = raster_layer.rasterize(&view);
+
+        assert_raster_eq!(raster, expected_result);
+    }
+
+    #[test]
+    fn rasterization_easy() {
+        let mut raster_layer = RasterLayer::new(10);
+
+        let red_chunk = BoxRasterChunk::new_fill(colors::red(), 10, 10);
+
+        raster_layer
+            .chunks
+            .insert((0, 0).into(), ChunkStorage::Full(red_chunk.clone()).into());
+
+        let view = CanvasView::new(11, 11);
+
+        let mut expected_result = BoxRasterChunk::new(11, 11);
+
+        expected_result.blit(&red_chunk.as_window(), DrawPosition::from((0, 0)));
+
+        let raster = raster_layer.rasterize(&view);
+
+        assert_raster_eq!(raster, expected_result);
+    }
+
+    #[test]
+    fn rasterization_medium() {
+        let mut raster_layer = RasterLayer::new(10);
+
+        let red_chunk = BoxRasterChunk::new_fill(colors::red(), 10, 10);
+        let green_chunk = BoxRasterChunk::new_fill(colors::green(), 10, 10);
+
+        raster_layer
+            .chunks
+            .insert((0, 0).into(), ChunkStorage::Full(red_chunk.clone()).into());
+        raster_layer.chunks.insert(
+            (1, 0).into(),
+            ChunkStorage::Full(green_chunk.clone()).into(),
+        );
+
+        let view = CanvasView::new(15, 10);
+
+        let mut expected_result = BoxRasterChunk::new(15, 10);
+
+        expected_result.blit(&red_chunk.as_window(), DrawPosition::from((0, 0)));
+        expected_result.blit(&green_chunk.as_window(), DrawPosition::from((10, 0)));
+
+        let raster = raster_layer.rasterize(&view);
+
+        assert_raster_eq!(raster, expected_result);
+    }
+
+    #[test]
+    fn rasterization_hard() {
+        let mut raster_layer = RasterLayer::new(100);
+
+        let red_chunk = BoxRasterChunk::new_fill(colors::red(), 100, 100);
+        let green_chunk = BoxRasterChunk::new_fill(colors::green(), 100, 100);
+
+        raster_layer
+            .chunks
+            .insert((0, 0).into(), ChunkStorage::Full(red_chunk.clone()).into());
+        raster_layer.chunks.insert(
+            (-1, -1).into(),
+            ChunkStorage::Full(green_chunk.clone()).into(),
+        );
+
+        let mut view = CanvasView::new(150, 200);
+        view.translate((-275, -115).into());
+
+        let mut expected_result = BoxRasterChunk::new(150, 200);
+
+        expected_result.blit(&red_chunk.as_window(), DrawPosition::from((250, 100)));
+        expected_result.blit(
+            &green_chunk.as_window(),
+            DrawPosition::from((100 - 275, 100 - 115)),
+        );
+
+        let raster = raster_layer.rasterize(&view);
+
+        assert_raster_eq!(raster, expected_result);
+    }
+
+    #[test]
+    fn fill_rect_easy() {
+        let mut raster_layer = RasterLayer::new(10);
+
+        let rect = CanvasRect {
+            top_left: (0, 0).into(),
+            dimensions: Dimensions {
+                width: 10,
+                height: 10,
+            },
+        };
+        let red_fill = RasterLayerAction::fill_rect(rect, colors::red());
+
+        raster_layer.perform_action(red_fill);
+
+        let view = CanvasView::new(10, 10);
+        let raster = raster_layer.rasterize(&view);
+
+        let expected = BoxRasterChunk::new_fill(colors::red(), 10, 10);
+
+        assert_raster_eq!(raster, expected);
+    }
+
+    #[test]
+    fn fill_rect_medium() {
+        let mut raster_layer = RasterLayer::new(10);
+
+        let rect = CanvasRect {
+            top_left: (0, 0).into(),
+            dimensions: Dimensions {
+                width: 5,
+                height: 5,
+            },
+        };
+        let red_fill = RasterLayerAction::fill_rect(rect, colors::red());
+
+        raster_layer.perform_action(red_fill);
+
+        let view = CanvasView::new(10, 10);
+        let raster = raster_layer.rasterize(&view);
+
+        let mut expected = BoxRasterChunk::new(10, 10);
+
+        let red_chunk = BoxRasterChunk::new_fill(colors::red(), 5, 5);
+
+        expected.blit(&red_chunk.as_window(), (0, 0).into());
+
+        assert_raster_eq!(raster, expected);
+    }
+
+    #[test]
+    fn fill_rect_action_hard() {
+        let mut raster_layer = RasterLayer::new(10);
+
+        let left_rect = CanvasRect {
+            top_left: (0, 0).into(),
+            dimensions: Dimensions {
+                width: 5,
+                height: 5,
+            },
+        };
+        let right_rect = CanvasRect {
+            top_left: (6, 0).into(),
+            dimensions: Dimensions {
+                width: 5,
+                height: 5,
+            },
+        };
+        let red_fill = RasterLayerAction::fill_rect(left_rect, colors::red());
+        let blue_fill = RasterLayerAction::fill_rect(right_rect, colors::blue());
+
+        raster_layer.perform_action(red_fill);
+        raster_layer.perform_action(blue_fill);
+
+        let view = CanvasView::new(15, 10);
+        let raster = raster_layer.rasterize(&view);
+
+        let mut expected = BoxRasterChunk::new(15, 10);
+
+        let red_chunk = BoxRasterChunk::new_fill(colors::red(), 5, 5);
+        let blue_chunk = BoxRasterChunk::new_fill(colors::blue(), 5, 5);
+
+        expected.blit(&red_chunk.as_window(), (0, 0).into());
+        expected.blit(&blue_chunk.as_window(), (6, 0).into());
+
+        assert_raster_eq!(raster, expected);
+    }
+
+    #[test]
+    fn scaled_rasterization() {
+        let mut raster_layer = RasterLayer::new(20);
+        let left_rect = CanvasRect {
+            top_left: (9, 9).into(),
+            dimensions: Dimensions {
+                width: 2,
+                height: 2,
+            },
+        };
+        let red_fill = RasterLayerAction::fill_rect(left_rect, colors::red());
+        raster_layer.perform_action(red_fill);
+
+        let mut view = CanvasView::new(20, 20);
+        view.pin_resize_canvas(Dimensions {
+            width: 10,
+            height: 10,
+        });
+
+        let raster = raster_layer.rasterize(&view);
+
+        let mut expected = BoxRasterChunk::new(10, 10);
+        expected.fill_rect(
+            colors::red(),
+            DrawRect {
+                top_left: (4, 4).into(),
+                dimensions: Dimensions {
+                    width: 2,
+                    height: 2,
+                },
+            },
+        );
+
+        expected.nn_scale(Dimensions {
+            width: 20,
+            height: 20,
+        });
+
+        assert_raster_eq!(raster, expected);
+    }
+
+    #[test]
+    fn fill_oval_easy() {
+        let mut raster_layer = RasterLayer::new(30);
+        let view = CanvasView::new(30, 30);
+
+        let rect = CanvasRect {
+            top_left: (10, 10).into(),
+            dimensions: Dimensions {
+                width: 10,
+                height: 10,
+            },
+        };
+
+        let red_oval = RasterLayerAction::fill_oval(rect, colors::red());
+        raster_layer.perform_action(red_oval);
+
+        let raster = raster_layer.rasterize(&view);
+
+        let mut expected = BoxRasterChunk::new(30, 30);
+        let oval = Oval::build_from_bound(10, 10).color(colors::red()).build();
+        expected.composite_over(&oval.rasterize().as_window(), DrawPosition::from((10, 10)));
+
+        assert_raster_eq!(raster, expected);
+    }
+
+    #[test]
+    fn fill_oval_medium() {
+        let mut raster_layer = RasterLayer::new(30);
+        let view = CanvasView::new(30, 30);
+
+        let rect = CanvasRect {
+            top_left: (10, 15).into(),
+            dimensions: Dimensions {
+                width: 10,
+                height: 10,
+            },
+        };
+
+        let red_oval = RasterLayerAction::fill_oval(rect, colors::red());
+        raster_layer.perform_action(red_oval);
+
+        let raster = raster_layer.rasterize(&view);
+
+        let mut expected = BoxRasterChunk::new(30, 30);
+        let oval = Oval::build_from_bound(10, 10).color(colors::red()).build();
+        expected.composite_over(&oval.rasterize().as_window(), DrawPosition::from((10, 15)));
+
+        assert_raster_eq!(raster, expected);
+    }
+
+    #[test]
+    fn fill_oval_border() {
+        let mut raster_layer = RasterLayer::new(30);
+        let view = CanvasView::new(60, 60);
+
+        let rect = CanvasRect {
+            top_left: (25, 10).into(),
+            dimensions: Dimensions {
+                width: 10,
+                height: 10,
+            },
+        };
+
+        let red_oval = RasterLayerAction::fill_oval(rect, colors::red());
+        raster_layer.perform_action(red_oval);
+
+        let raster = raster_layer.rasterize(&view);
+
+        let mut expected = BoxRasterChunk::new(60, 60);
+        let oval = Oval::build_from_bound(10, 10).color(colors::red()).build();
+        expected.composite_over(&oval.rasterize().as_window(), DrawPosition::from((25, 10)));
 
-            let raster_window = RasterWindow::new(raster_chunk, top_left_in_chunk, width, height)
-                .expect("ChunkRectPosition returned by iter_chunks_in_rect should be completely contained in chunk");
+        assert_raster_eq!(raster, expected);
+    }
 
-            let draw_position_in_result: DrawPosition =
-                (x_pixel_offset, y_pixel_offset).unchecked_into_position();
+    #[test]
+    fn gradients_are_drawn_through_the_shape_cache() {
+        let mut cached_layer = RasterLayer::new(16);
+        let mut raster_layer = RasterLayer::new(16);
+        let mut shape_cache = ShapeCache::new();
+        let canvas_rect = CanvasRect::at_origin(Dimensions {
+            width: 48,
+            height: 48,
+        });
 
-            raster_result.blit(&raster_window, draw_position_in_result);
+        let disc = RadialGradientDisc::new(10.0, colors::red(), colors::blue());
+        let cone = ConicGradient::new(8.0, 90.0, colors::green(), colors::red());
+        let actions = [
+            RasterLayerAction::fill_radial_gradient((3, 5).into(), disc),
+            RasterLayerAction::fill_conic_gradient((24, 20).into(), cone),
+        ];
+
+        for action in actions {
+            let expected_rect = action.bounding_rect();
+            assert_eq!(
+                cached_layer.perform_action_with_cache(action.clone(), &mut shape_cache),
+                expected_rect
+            );
+            assert_eq!(raster_layer.perform_action(action), expected_rect);
         }
 
-        raster_result
-    }
-}
+        let mut expected = BoxRasterChunk::new(48, 48);
+        expected.composite_over(&disc.rasterize().as_window(), (3, 5).into());
+        expected.composite_over(&cone.rasterize().as_window(), (24, 20).into());
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::{
-        assert_raster_eq,
-        primitives::rect::{DrawRect, RasterRect},
-        raster::pixels::colors,
-    };
+        let cached_raster = cached_layer.rasterize_canvas_rect(canvas_rect);
+        assert_raster_eq!(cached_raster, expected);
+        let raster = raster_layer.rasterize_canvas_rect(canvas_rect);
+        assert_raster_eq!(raster, expected);
+    }
 
     #[test]
-    fn chunk_visibility_easy() {
-        let raster_layer = RasterLayer::new(10);
+    fn spray_is_deterministic() {
+        let spray = Spray {
+            center: (40, 40).into(),
+            radius: 12,
+            density: 20,
+            dab_diameter: 4,
+            size_jitter: 50,
+            color: colors::blue(),
+            falloff: Falloff::Linear,
+            seed: 7,
+        };
 
-        let mut canvas_rect = CanvasRect::at_origin(Dimensions {
-            width: 10,
-            height: 10,
-        });
+        let mut layer_a = RasterLayer::new(32);
+        let mut layer_b = RasterLayer::new(32);
 
-        assert_eq!(
-            raster_layer.find_chunk_rect_in_canvas_rect(canvas_rect),
-            ChunkRect {
-                top_left_chunk: (0, 0).into(),
-                chunk_dimensions: Dimensions {
-                    width: 1,
-                    height: 1
-                },
-                top_left_in_chunk: (0, 0).into(),
-                bottom_right_in_chunk: (9, 9).into(),
-            }
-        );
+        let changed_rect_a = layer_a
+            .perform_action(RasterLayerAction::spray(spray))
+            .unwrap();
+        let changed_rect_b = layer_b
+            .perform_action_with_cache(RasterLayerAction::spray(spray), &mut ShapeCache::new())
+            .unwrap();
 
-        canvas_rect.top_left = (-5, -2).into();
+        assert_eq!(changed_rect_a, changed_rect_b);
+        assert_eq!(layer_a.chunk_hashes(), layer_b.chunk_hashes());
 
-        assert_eq!(
-            raster_layer.find_chunk_rect_in_canvas_rect(canvas_rect),
-            ChunkRect {
-                top_left_chunk: (-1, -1).into(),
-                chunk_dimensions: Dimensions {
-                    width: 2,
-                    height: 2
-                },
-                top_left_in_chunk: (10 - 5, 10 - 2).into(),
-                bottom_right_in_chunk: (9 - 5, 9 - 2).into(),
-            }
-        );
+        let spray_area = CanvasRect {
+            top_left: (40 - 12 - 6, 40 - 12 - 6).into(),
+            dimensions: Dimensions {
+                width: 12 * 2 + 12,
+                height: 12 * 2 + 12,
+            },
+        };
+        assert!(spray_area.contains_with_offset(&changed_rect_a).is_some());
+
+        let mut layer_c = RasterLayer::new(32);
+        layer_c.perform_action(RasterLayerAction::spray(Spray { seed: 8, ..spray }));
+
+        assert_ne!(layer_a.chunk_hashes(), layer_c.chunk_hashes());
     }
 
     #[test]
-    fn chunk_visibility_medium() {
-        let raster_layer = RasterLayer::new(1024);
+    fn clone_stamp_overlapping_source() {
+        let mut raster_layer = RasterLayer::new(8);
 
-        let mut canvas_rect = CanvasRect::at_origin(Dimensions {
-            width: 2000,
-            height: 2000,
-        });
-        canvas_rect.top_left = (-500, -500).into();
+        let red_rect = CanvasRect {
+            top_left: (0, 0).into(),
+            dimensions: Dimensions {
+                width: 10,
+                height: 10,
+            },
+        };
+        raster_layer.perform_action(RasterLayerAction::fill_rect(red_rect, colors::red()));
 
-        assert_eq!(
-            raster_layer.find_chunk_rect_in_canvas_rect(canvas_rect),
-            ChunkRect {
-                top_left_chunk: (-1, -1).into(),
-                chunk_dimensions: Dimensions {
-                    width: 3,
-                    height: 3
-                },
-                top_left_in_chunk: (524, 524).into(),
-                bottom_right_in_chunk: (500 - 24 - 1, 500 - 24 - 1).into(),
-            }
+        let clone_stamp = CloneStamp {
+            center: (12, 5).into(),
+            source_offset: (-4, 0).into(),
+            diameter: 8,
+            falloff: Falloff::Linear,
+        };
+
+        let source_before = raster_layer.rasterize_canvas_rect_shared(clone_stamp.source_rect());
+        let mut expected = raster_layer
+            .rasterize_canvas_rect_shared(red_rect.spanning_rect(&clone_stamp.destination_rect()));
+        let destination_rect = clone_stamp.destination_rect();
+        expected.composite_over(
+            &clone_stamp.mask(&source_before).as_window(),
+            DrawPosition::from((destination_rect.top_left.0, destination_rect.top_left.1)),
         );
-    }
 
-    #[test]
-    fn chunk_visibility_hard() {
-        let raster_layer = RasterLayer::new(512);
+        let changed_rect = raster_layer.perform_action(RasterLayerAction::clone_stamp(clone_stamp));
+        assert_eq!(changed_rect, Some(destination_rect));
 
-        let mut canvas_rect = CanvasRect::at_origin(Dimensions {
-            width: 2000,
-            height: 1000,
-        });
-        canvas_rect.top_left = (-500, -1000).into();
+        let raster = raster_layer.rasterize_canvas_rect(red_rect.spanning_rect(&destination_rect));
+        assert_raster_eq!(raster, expected);
 
+        // Stamped from the red area, past the original edge of the fill
         assert_eq!(
-            raster_layer.find_chunk_rect_in_canvas_rect(canvas_rect),
-            ChunkRect {
-                top_left_chunk: (-1, -2).into(),
-                chunk_dimensions: Dimensions {
-                    width: 4,
-                    height: 2
-                },
-                top_left_in_chunk: (12, 24).into(),
-                bottom_right_in_chunk: (512 - 36 - 1, 512 - 1).into(),
-            }
+            raster_layer
+                .rasterize_canvas_rect_shared(CanvasRect {
+                    top_left: (11, 5).into(),
+                    dimensions: Dimensions {
+                        width: 1,
+                        height: 1
+                    }
+                })
+                .pixels()[0],
+            colors::red()
         );
     }
 
     #[test]
-    fn rasterize_offset() {
-        let mut raster_layer = RasterLayer::new(10);
+    fn flood_fill_stays_within_region() {
+        let mut raster_layer = RasterLayer::new(8);
 
-        let red_chunk = BoxRasterChunk::new_fill(colors::red(), 10, 10);
-        raster_layer.chunks.insert((0, 0).into(), red_chunk.clone());
-
-        let mut view = CanvasView::new(10, 10);
+        // A red ring around a transparent hole, split across chunks
+        raster_layer.perform_action(RasterLayerAction::fill_rect(
+            CanvasRect {
+                top_left: (2, 2).into(),
+                dimensions: Dimensions {
+                    width: 10,
+                    height: 2,
+                },
+            },
+            colors::red(),
+        ));
+        raster_layer.perform_action(RasterLayerAction::fill_rect(
+            CanvasRect {
+                top_left: (2, 10).into(),
+                dimensions: Dimensions {
+                    width: 10,
+                    height: 2,
+                },
+            },
+            colors::red(),
+        ));
+        raster_layer.perform_action(RasterLayerAction::fill_rect(
+            CanvasRect {
+                top_left: (2, 4).into(),
+                dimensions: Dimensions {
+                    width: 2,
+                    height: 6,
+                },
+            },
+            colors::red(),
+        ));
+        raster_layer.perform_action(RasterLayerAction::fill_rect(
+            CanvasRect {
+                top_left: (10, 4).into(),
+                dimensions: Dimensions {
+                    width: 2,
+                    height: 6,
+                },
+            },
+            colors::red(),
+        ));
 
-        view.translate((-5, 0).into());
+        let hole = CanvasRect {
+            top_left: (4, 4).into(),
+            dimensions: Dimensions {
+                width: 6,
+                height: 6,
+            },
+        };
 
-        let mut expected_result = BoxRasterChunk::new(10, 10);
-        // DrawPosition::from((5, 0)), 5, 10
-        expected_result.fill_rect(
-            colors::red(),
+        let changed_rect = raster_layer
+            .perform_action(RasterLayerAction::flood_fill(FloodFill {
+                position: (6, 6).into(),
+                color: colors::blue(),
+                tolerance: 0,
+                bounds: CanvasRect::at_origin(Dimensions {
+                    width: 16,
+                    height: 16,
+                }),
+            }))
+            .unwrap();
+
+        assert_eq!(changed_rect, hole);
+
+        let raster = raster_layer.rasterize_canvas_rect(CanvasRect {
+            top_left: (2, 2).into(),
+            dimensions: Dimensions {
+                width: 10,
+                height: 10,
+            },
+        });
+        let mut expected = BoxRasterChunk::new_fill(colors::red(), 10, 10);
+        expected.fill_rect(
+            colors::blue(),
             DrawRect {
-                top_left: (5, 0).into(),
+                top_left: (2, 2).into(),
                 dimensions: Dimensions {
-                    width: 5,
-                    height: 10,
+                    width: 6,
+                    height: 6,
                 },
             },
         );
 
-        let raster = raster_layer.rasterize(&view);
-
-        assert_raster_eq!(raster, expected_result);
+        assert_raster_eq!(raster, expected);
     }
 
     #[test]
-    fn rasterization_easy() {
-        let mut raster_layer = RasterLayer::new(10);
-
-        let red_chunk = BoxRasterChunk::new_fill(colors::red(), 10, 10);
+    fn packed_layers_keep_content_between_actions() {
+        let mut rgba_layer = RasterLayer::new(8);
+        let mut packed_layer = RasterLayer::new(8).with_pixel_format(PixelFormat::Rgb565A8);
 
-        raster_layer.chunks.insert((0, 0).into(), red_chunk.clone());
-
-        let view = CanvasView::new(11, 11);
+        let first_rect = CanvasRect {
+            top_left: (2, 2).into(),
+            dimensions: Dimensions {
+                width: 12,
+                height: 12,
+            },
+        };
+        let second_rect = CanvasRect {
+            top_left: (6, 6).into(),
+            dimensions: Dimensions {
+                width: 12,
+                height: 4,
+            },
+        };
+        let actions = [
+            RasterLayerAction::fill_rect(first_rect, Pixel::new_rgb(200, 100, 50)),
+            RasterLayerAction::fill_rect(second_rect, Pixel::new_rgba(0, 0, 255, 128)),
+            RasterLayerAction::fill_oval(second_rect, colors::green()),
+        ];
+
+        for action in actions {
+            assert_eq!(
+                rgba_layer.perform_action(action.clone()),
+                packed_layer.perform_action(action)
+            );
+        }
 
-        let mut expected_result = BoxRasterChunk::new(11, 11);
+        assert_eq!(stored_chunks(&packed_layer, is_packed), 6);
 
-        expected_result.blit(&red_chunk.as_window(), DrawPosition::from((0, 0)));
+        let area = first_rect.spanning_rect(&second_rect);
+        let rgba_raster = rgba_layer.rasterize_canvas_rect(area);
+        let packed_raster = packed_layer.rasterize_canvas_rect(area);
 
-        let raster = raster_layer.rasterize(&view);
+        for (rgba_pixel, packed_pixel) in rgba_raster
+            .pixels()
+            .iter()
+            .zip(packed_raster.pixels().iter())
+        {
+            assert!(rgba_pixel.is_close(packed_pixel, 8));
+        }
 
-        assert_raster_eq!(raster, expected_result);
+        packed_layer.set_pixel_format(PixelFormat::Rgba8888);
+        assert_eq!(stored_chunks(&packed_layer, is_packed), 0);
+        assert_eq!(stored_chunks(&packed_layer, is_full), 6);
     }
 
     #[test]
-    fn rasterization_medium() {
-        let mut raster_layer = RasterLayer::new(10);
-
-        let red_chunk = BoxRasterChunk::new_fill(colors::red(), 10, 10);
-        let green_chunk = BoxRasterChunk::new_fill(colors::green(), 10, 10);
+    fn strokes_and_replaced_chunks_are_packed() {
+        let mut packed_layer = RasterLayer::new(8).with_pixel_format(PixelFormat::Rgb565A8);
 
-        raster_layer.chunks.insert((0, 0).into(), red_chunk.clone());
-        raster_layer
-            .chunks
-            .insert((1, 0).into(), green_chunk.clone());
+        let points: [CanvasPosition; 2] = [(2, 3).into(), (29, 5).into()];
+        packed_layer.draw_stroke(&points, &Brush::new(4, colors::red()));
+        packed_layer.replace_chunk((0, 4).into(), Some(BoxRasterChunk::new(8, 8)));
 
-        let view = CanvasView::new(15, 10);
+        assert_eq!(stored_chunks(&packed_layer, is_full), 0);
+        assert_eq!(stored_chunks(&packed_layer, is_packed), 5);
+    }
 
-        let mut expected_result = BoxRasterChunk::new(15, 10);
+    #[test]
+    fn chunk_iteration_sees_chunks_of_every_storage() {
+        let mut raster_layer = RasterLayer::new(8);
+        raster_layer.set_pixel_format(PixelFormat::Rgb565A8);
+        let canvas_rect = CanvasRect::at_origin(Dimensions {
+            width: 16,
+            height: 8,
+        });
 
-        expected_result.blit(&red_chunk.as_window(), DrawPosition::from((0, 0)));
-        expected_result.blit(&green_chunk.as_window(), DrawPosition::from((10, 0)));
+        raster_layer.perform_action(RasterLayerAction::fill_rect(
+            CanvasRect::at_origin(Dimensions {
+                width: 8,
+                height: 8,
+            }),
+            colors::red(),
+        ));
+        raster_layer.perform_action(RasterLayerAction::fill_rect(
+            CanvasRect {
+                top_left: (8, 0).into(),
+                dimensions: Dimensions {
+                    width: 4,
+                    height: 8,
+                },
+            },
+            colors::blue(),
+        ));
+        assert_eq!(stored_chunks(&raster_layer, is_packed), 1);
+        assert_eq!(stored_chunks(&raster_layer, ChunkStorage::is_compressed), 2);
 
-        let raster = raster_layer.rasterize(&view);
+        let chunk_rect = raster_layer.find_chunk_rect_in_canvas_rect(canvas_rect);
+        assert!(raster_layer
+            .iter_chunks_in_rect(chunk_rect)
+            .all(|(storage, _)| storage.is_some()));
 
-        assert_raster_eq!(raster, expected_result);
+        let mut expected = BoxRasterChunk::new(16, 8);
+        expected.fill_rect(
+            colors::red(),
+            DrawRect {
+                top_left: (0, 0).into(),
+                dimensions: Dimensions {
+                    width: 8,
+                    height: 8,
+                },
+            },
+        );
+        expected.fill_rect(
+            colors::blue(),
+            DrawRect {
+                top_left: (8, 0).into(),
+                dimensions: Dimensions {
+                    width: 4,
+                    height: 8,
+                },
+            },
+        );
+        let raster = raster_layer.rasterize_canvas_rect(canvas_rect);
+        assert_raster_eq!(raster, expected);
     }
 
     #[test]
-    fn rasterization_hard() {
-        let mut raster_layer = RasterLayer::new(100);
-
-        let red_chunk = BoxRasterChunk::new_fill(colors::red(), 100, 100);
-        let green_chunk = BoxRasterChunk::new_fill(colors::green(), 100, 100);
+    fn cloned_layers_share_chunks_until_drawn_on() {
+        let mut raster_layer = RasterLayer::new(8);
+        for top_left in [(0, 0), (8, 0)] {
+            raster_layer.perform_action(RasterLayerAction::fill_rect(
+                CanvasRect {
+                    top_left: top_left.into(),
+                    dimensions: Dimensions {
+                        width: 4,
+                        height: 4,
+                    },
+                },
+                colors::red(),
+            ));
+        }
 
-        raster_layer.chunks.insert((0, 0).into(), red_chunk.clone());
-        raster_layer
+        let mut cloned_layer = raster_layer.clone();
+        assert!(raster_layer
             .chunks
-            .insert((-1, -1).into(), green_chunk.clone());
+            .iter()
+            .all(|(chunk_position, storage)| Arc::ptr_eq(
+                storage,
+                &cloned_layer.chunks[chunk_position]
+            )));
+
+        cloned_layer.perform_action(RasterLayerAction::fill_rect(
+            CanvasRect::at_origin(Dimensions {
+                width: 2,
+                height: 2,
+            }),
+            colors::blue(),
+        ));
+        let first_chunk = ChunkPosition::from((0, 0));
+        let second_chunk = ChunkPosition::from((1, 0));
+        assert!(!Arc::ptr_eq(
+            &raster_layer.chunks[&first_chunk],
+            &cloned_layer.chunks[&first_chunk]
+        ));
+        assert!(Arc::ptr_eq(
+            &raster_layer.chunks[&second_chunk],
+            &cloned_layer.chunks[&second_chunk]
+        ));
+        assert_eq!(
+            raster_layer
+                .chunk(first_chunk)
+                .and_then(|chunk| chunk.pixels().first().copied()),
+            Some(colors::red())
+        );
+    }
 
-        let mut view = CanvasView::new(150, 200);
-        view.translate((-275, -115).into());
+    #[test]
+    fn filled_chunks_are_compressed_until_drawn_on() {
+        let mut raster_layer = RasterLayer::new(8);
+        let filled_rect = CanvasRect::at_origin(Dimensions {
+            width: 24,
+            height: 24,
+        });
 
-        let mut expected_result = BoxRasterChunk::new(150, 200);
+        raster_layer.perform_action(RasterLayerAction::fill_rect(filled_rect, colors::red()));
+        assert_eq!(stored_chunks(&raster_layer, ChunkStorage::is_compressed), 9);
+        assert_eq!(raster_layer.chunk_bytes(), 9 * 4);
 
-        expected_result.blit(&red_chunk.as_window(), DrawPosition::from((250, 100)));
-        expected_result.blit(
-            &green_chunk.as_window(),
-            DrawPosition::from((100 - 275, 100 - 115)),
+        let dot_rect = CanvasRect {
+            top_left: (1, 1).into(),
+            dimensions: Dimensions {
+                width: 2,
+                height: 2,
+            },
+        };
+        raster_layer.perform_action(RasterLayerAction::fill_rect(dot_rect, colors::blue()));
+        assert_eq!(stored_chunks(&raster_layer, is_full), 1);
+        assert_eq!(stored_chunks(&raster_layer, ChunkStorage::is_compressed), 8);
+
+        let mut expected = BoxRasterChunk::new_fill(colors::red(), 24, 24);
+        expected.composite_over(
+            &BoxRasterChunk::new_fill(colors::blue(), 2, 2).as_window(),
+            (1, 1).into(),
         );
+        let raster = raster_layer.rasterize_canvas_rect(filled_rect);
+        assert_raster_eq!(raster, expected);
 
-        let raster = raster_layer.rasterize(&view);
+        raster_layer.compress_chunks();
+        assert_eq!(stored_chunks(&raster_layer, is_full), 0);
+        assert!(raster_layer.chunk_bytes() < 24 * 24 * 4);
 
-        assert_raster_eq!(raster, expected_result);
+        let raster = raster_layer.rasterize_canvas_rect(filled_rect);
+        assert_raster_eq!(raster, expected);
     }
 
     #[test]
-    fn fill_rect_easy() {
-        let mut raster_layer = RasterLayer::new(10);
+    fn average_colors_are_weighted_by_opacity() {
+        let mut raster_layer = RasterLayer::new(4);
+        raster_layer.perform_action(RasterLayerAction::fill_rect(
+            CanvasRect::at_origin(Dimensions {
+                width: 4,
+                height: 4,
+            }),
+            colors::red(),
+        ));
+        raster_layer.perform_action(RasterLayerAction::fill_rect(
+            CanvasRect {
+                top_left: (4, 0).into(),
+                dimensions: Dimensions {
+                    width: 4,
+                    height: 2,
+                },
+            },
+            colors::blue(),
+        ));
+
+        let layer_rect = CanvasRect::at_origin(Dimensions {
+            width: 8,
+            height: 8,
+        });
+        assert_eq!(
+            raster_layer.average_color(layer_rect),
+            raster_layer
+                .rasterize_canvas_rect(layer_rect)
+                .average_color()
+        );
+        assert_eq!(
+            raster_layer.average_color(CanvasRect::at_origin(Dimensions {
+                width: 8,
+                height: 4,
+            })),
+            Pixel::new_rgba(170, 0, 85, 191)
+        );
+        assert_eq!(
+            raster_layer.average_color(CanvasRect {
+                top_left: (0, 4).into(),
+                dimensions: Dimensions {
+                    width: 8,
+                    height: 4,
+                },
+            }),
+            colors::transparent()
+        );
+    }
+
+    #[test]
+    fn allocated_chunks_are_iterated_without_visiting_empty_ones() {
+        let mut raster_layer = RasterLayer::new(4);
+        for (x, y) in [(4000, -4000), (0, 0), (5, 1)] {
+            raster_layer.perform_action(RasterLayerAction::fill_rect(
+                CanvasRect {
+                    top_left: (x, y).into(),
+                    dimensions: Dimensions {
+                        width: 2,
+                        height: 2,
+                    },
+                },
+                colors::red(),
+            ));
+        }
 
-        let rect = CanvasRect {
-            top_left: (0, 0).into(),
+        let huge_rect = CanvasRect {
+            top_left: (-8000, -8000).into(),
             dimensions: Dimensions {
-                width: 10,
-                height: 10,
+                width: 16000,
+                height: 16000,
             },
         };
-        let red_fill = RasterLayerAction::fill_rect(rect, colors::red());
-
-        raster_layer.perform_action(red_fill);
-
-        let view = CanvasView::new(10, 10);
-        let raster = raster_layer.rasterize(&view);
-
-        let expected = BoxRasterChunk::new_fill(colors::red(), 10, 10);
+        let chunk_positions: Vec<ChunkPosition> = raster_layer
+            .iter_allocated_chunks_in_rect(huge_rect)
+            .map(|(chunk_position, _)| chunk_position)
+            .collect();
+        assert_eq!(
+            chunk_positions,
+            vec![(1000, -1000).into(), (0, 0).into(), (1, 0).into()]
+        );
 
-        assert_raster_eq!(raster, expected);
+        let small_rect = CanvasRect::at_origin(Dimensions {
+            width: 6,
+            height: 8,
+        });
+        assert_eq!(
+            raster_layer.allocated_chunk_positions_in_rect(small_rect),
+            vec![(0, 0).into(), (1, 0).into()]
+        );
+        let histogram = raster_layer.histogram(small_rect);
+        assert_eq!(histogram.alpha[255], 6);
+        assert_eq!(histogram.alpha[0], 42);
     }
 
     #[test]
-    fn fill_rect_medium() {
-        let mut raster_layer = RasterLayer::new(10);
+    fn histograms_count_unallocated_chunks_as_transparent() {
+        let mut raster_layer = RasterLayer::new(8);
+        raster_layer.perform_action(RasterLayerAction::fill_rect(
+            CanvasRect::at_origin(Dimensions {
+                width: 8,
+                height: 8,
+            }),
+            colors::red(),
+        ));
+        raster_layer.perform_action(RasterLayerAction::fill_rect(
+            CanvasRect {
+                top_left: (8, 0).into(),
+                dimensions: Dimensions {
+                    width: 2,
+                    height: 2,
+                },
+            },
+            Pixel::new_rgb(10, 20, 30),
+        ));
 
-        let rect = CanvasRect {
-            top_left: (0, 0).into(),
+        let histogram_rect = CanvasRect {
+            top_left: (4, -4).into(),
             dimensions: Dimensions {
-                width: 5,
-                height: 5,
+                width: 8,
+                height: 8,
             },
         };
-        let red_fill = RasterLayerAction::fill_rect(rect, colors::red());
-
-        raster_layer.perform_action(red_fill);
+        let histogram = raster_layer.histogram(histogram_rect);
+
+        assert_eq!(histogram.pixel_count(), 64);
+        assert_eq!(histogram.red[255], 16);
+        assert_eq!(histogram.red[10], 4);
+        assert_eq!(histogram.green[20], 4);
+        assert_eq!(histogram.alpha[255], 20);
+        assert_eq!(histogram.alpha[0], 44);
+        assert_eq!(
+            histogram,
+            raster_layer
+                .rasterize_canvas_rect(histogram_rect)
+                .histogram()
+        );
+    }
 
-        let view = CanvasView::new(10, 10);
-        let raster = raster_layer.rasterize(&view);
+    #[test]
+    fn occupancy_counts_transparent_and_single_color_chunks() {
+        let mut raster_layer = RasterLayer::new(4);
+        assert_eq!(raster_layer.occupancy().compactable_fraction(), 0.0);
+
+        raster_layer.perform_action(RasterLayerAction::fill_rect(
+            CanvasRect::at_origin(Dimensions {
+                width: 8,
+                height: 4,
+            }),
+            colors::red(),
+        ));
+        raster_layer.perform_action(RasterLayerAction::fill_rect(
+            CanvasRect {
+                top_left: (3, 0).into(),
+                dimensions: Dimensions {
+                    width: 4,
+                    height: 1,
+                },
+            },
+            colors::blue(),
+        ));
+        raster_layer.replace_chunk((0, 1).into(), Some(BoxRasterChunk::new(4, 4)));
 
-        let mut expected = BoxRasterChunk::new(10, 10);
+        let occupancy = raster_layer.occupancy();
+        assert_eq!(
+            occupancy,
+            LayerOccupancy {
+                populated_chunks: 3,
+                transparent_chunks: 1,
+                single_color_chunks: 0,
+            }
+        );
 
-        let red_chunk = BoxRasterChunk::new_fill(colors::red(), 5, 5);
+        raster_layer.perform_action(RasterLayerAction::fill_rect(
+            CanvasRect::at_origin(Dimensions {
+                width: 4,
+                height: 4,
+            }),
+            colors::green(),
+        ));
+        let occupancy = raster_layer.occupancy();
+        assert_eq!(occupancy.single_color_chunks, 1);
+        assert!((occupancy.transparent_fraction() - 1.0 / 3.0).abs() < f32::EPSILON);
+        assert!((occupancy.compactable_fraction() - 2.0 / 3.0).abs() < f32::EPSILON);
+    }
 
-        expected.blit(&red_chunk.as_window(), (0, 0).into());
+    #[test]
+    fn fills_covering_chunks_composite_onto_fixed_chunks() {
+        let mut raster_layer = RasterLayer::new(128);
+        let pixels = (0..192 * 128)
+            .map(|i| Pixel::new_rgb((i % 192) as u8, (i / 192) as u8, 0))
+            .collect();
+        let noise = BoxRasterChunk::from_vec(pixels, 192, 128).unwrap();
+        raster_layer.draw_raster((0, 0).into(), &noise, CopyMode::Blit);
+        assert_eq!(stored_chunks(&raster_layer, is_full), 2);
+
+        let translucent_blue = Pixel::new_rgba(0, 0, 255, 100);
+        let layer_rect = CanvasRect::at_origin(noise.dimensions());
+        raster_layer.perform_action(RasterLayerAction::fill_rect(layer_rect, translucent_blue));
+        let is_fixed = |storage: &ChunkStorage| matches!(storage, ChunkStorage::Fixed128(_));
+        assert_eq!(stored_chunks(&raster_layer, is_fixed), 1);
+
+        let mut expected = noise.clone();
+        expected.composite_over(
+            &BoxRasterChunk::new_fill(translucent_blue, 192, 128).as_window(),
+            (0, 0).into(),
+        );
+        let raster = raster_layer.rasterize_canvas_rect(layer_rect);
+        assert_raster_eq!(raster, expected);
 
+        raster_layer.perform_action(RasterLayerAction::fill_rect(
+            CanvasRect {
+                top_left: (1, 1).into(),
+                dimensions: Dimensions {
+                    width: 1,
+                    height: 1,
+                },
+            },
+            colors::red(),
+        ));
+        assert_eq!(stored_chunks(&raster_layer, is_fixed), 0);
+        expected.fill_rect(
+            colors::red(),
+            DrawRect {
+                top_left: (1, 1).into(),
+                dimensions: Dimensions {
+                    width: 1,
+                    height: 1,
+                },
+            },
+        );
+        let raster = raster_layer.rasterize_canvas_rect(layer_rect);
         assert_raster_eq!(raster, expected);
     }
 
     #[test]
-    fn fill_rect_action_hard() {
-        let mut raster_layer = RasterLayer::new(10);
+    fn alpha_locked_layers_only_recolor_drawn_pixels() {
+        let mut raster_layer = RasterLayer::new(4);
+        let drawn_rect = CanvasRect::at_origin(Dimensions {
+            width: 4,
+            height: 8,
+        });
+        let translucent_red = Pixel::new_rgba(255, 0, 0, 128);
+        raster_layer.perform_action(RasterLayerAction::fill_rect(drawn_rect, translucent_red));
+        raster_layer.set_alpha_locked(true);
 
-        let left_rect = CanvasRect {
-            top_left: (0, 0).into(),
-            dimensions: Dimensions {
-                width: 5,
-                height: 5,
+        let layer_rect = CanvasRect::at_origin(Dimensions {
+            width: 8,
+            height: 8,
+        });
+        raster_layer.perform_action(RasterLayerAction::fill_rect(layer_rect, colors::blue()));
+        raster_layer.perform_action(RasterLayerAction::draw_line(
+            (0, 6).into(),
+            (7, 6).into(),
+            0,
+            colors::green(),
+        ));
+
+        let mut expected = BoxRasterChunk::new_fill(colors::transparent(), 8, 8);
+        expected.fill_rect(
+            Pixel::new_rgba(0, 0, 255, 128),
+            DrawRect {
+                top_left: (0, 0).into(),
+                dimensions: Dimensions {
+                    width: 4,
+                    height: 8,
+                },
             },
-        };
-        let right_rect = CanvasRect {
-            top_left: (6, 0).into(),
-            dimensions: Dimensions {
-                width: 5,
-                height: 5,
+        );
+        expected.fill_rect(
+            Pixel::new_rgba(0, 255, 0, 128),
+            DrawRect {
+                top_left: (0, 6).into(),
+                dimensions: Dimensions {
+                    width: 4,
+                    height: 1,
+                },
             },
-        };
-        let red_fill = RasterLayerAction::fill_rect(left_rect, colors::red());
-        let blue_fill = RasterLayerAction::fill_rect(right_rect, colors::blue());
-
-        raster_layer.perform_action(red_fill);
-        raster_layer.perform_action(blue_fill);
-
-        let view = CanvasView::new(15, 10);
-        let raster = raster_layer.rasterize(&view);
+        );
+        let raster = raster_layer.rasterize_canvas_rect(layer_rect);
+        assert_raster_eq!(raster, expected);
 
-        let mut expected = BoxRasterChunk::new(15, 10);
+        raster_layer.set_alpha_locked(false);
+        raster_layer.perform_action(RasterLayerAction::fill_rect(layer_rect, colors::blue()));
+        let raster = raster_layer.rasterize_canvas_rect(layer_rect);
+        let expected = BoxRasterChunk::new_fill(colors::blue(), 8, 8);
+        assert_raster_eq!(raster, expected);
+    }
 
-        let red_chunk = BoxRasterChunk::new_fill(colors::red(), 5, 5);
-        let blue_chunk = BoxRasterChunk::new_fill(colors::blue(), 5, 5);
+    #[test]
+    fn alpha_locked_layers_keep_their_alpha_when_blitted_onto() {
+        let mut raster_layer = RasterLayer::new(4);
+        let drawn_rect = CanvasRect::at_origin(Dimensions {
+            width: 4,
+            height: 8,
+        });
+        let translucent_red = Pixel::new_rgba(255, 0, 0, 128);
+        raster_layer.perform_action(RasterLayerAction::fill_rect(drawn_rect, translucent_red));
+        raster_layer.set_alpha_locked(true);
 
-        expected.blit(&red_chunk.as_window(), (0, 0).into());
-        expected.blit(&blue_chunk.as_window(), (6, 0).into());
+        let layer_rect = CanvasRect::at_origin(Dimensions {
+            width: 8,
+            height: 8,
+        });
+        let before = raster_layer.rasterize_canvas_rect(layer_rect);
+        raster_layer.perform_action(RasterLayerAction::apply_kernel(
+            layer_rect,
+            Kernel::box_blur(1),
+        ));
+        let blurred = raster_layer.rasterize_canvas_rect(layer_rect);
+        for (blurred_pixel, pixel) in blurred.pixels().iter().zip(before.pixels()) {
+            assert_eq!(blurred_pixel.as_rgba().3, pixel.as_rgba().3);
+        }
 
+        raster_layer.draw_raster(
+            (0, 0).into(),
+            &BoxRasterChunk::new_fill(colors::blue(), 8, 8),
+            CopyMode::Blit,
+        );
+        let mut expected = BoxRasterChunk::new_fill(colors::transparent(), 8, 8);
+        expected.fill_rect(
+            Pixel::new_rgba(0, 0, 255, 128),
+            DrawRect {
+                top_left: (0, 0).into(),
+                dimensions: drawn_rect.dimensions,
+            },
+        );
+        let raster = raster_layer.rasterize_canvas_rect(layer_rect);
         assert_raster_eq!(raster, expected);
     }
 
     #[test]
-    fn scaled_rasterization() {
-        let mut raster_layer = RasterLayer::new(20);
-        let left_rect = CanvasRect {
-            top_left: (9, 9).into(),
+    fn adjusting_hsv_recolors_a_region() {
+        let mut raster_layer = RasterLayer::new(4);
+        let filled_rect = CanvasRect::at_origin(Dimensions {
+            width: 8,
+            height: 8,
+        });
+        raster_layer.perform_action(RasterLayerAction::fill_rect(filled_rect, colors::red()));
+
+        let adjusted_rect = CanvasRect {
+            top_left: (2, 2).into(),
             dimensions: Dimensions {
-                width: 2,
-                height: 2,
+                width: 4,
+                height: 12,
             },
         };
-        let red_fill = RasterLayerAction::fill_rect(left_rect, colors::red());
-        raster_layer.perform_action(red_fill);
-
-        let mut view = CanvasView::new(20, 20);
-        view.pin_resize_canvas(Dimensions {
-            width: 10,
-            height: 10,
-        });
-
-        let raster = raster_layer.rasterize(&view);
+        assert_eq!(
+            raster_layer.perform_action(RasterLayerAction::adjust_hsv(
+                adjusted_rect,
+                120.0,
+                1.0,
+                0.5
+            )),
+            Some(adjusted_rect)
+        );
 
-        let mut expected = BoxRasterChunk::new(10, 10);
+        let mut expected = BoxRasterChunk::new_fill(colors::red(), 8, 8);
         expected.fill_rect(
-            colors::red(),
+            Pixel::new_rgb(0, 128, 0),
             DrawRect {
-                top_left: (4, 4).into(),
+                top_left: (2, 2).into(),
                 dimensions: Dimensions {
-                    width: 2,
-                    height: 2,
+                    width: 4,
+                    height: 6,
                 },
             },
         );
-
-        expected.nn_scale(Dimensions {
-            width: 20,
-            height: 20,
-        });
-
+        let raster = raster_layer.rasterize_canvas_rect(filled_rect);
         assert_raster_eq!(raster, expected);
+        assert!(raster_layer.chunk((0, 3).into()).is_none());
     }
 
     #[test]
-    fn fill_oval_easy() {
-        let mut raster_layer = RasterLayer::new(30);
-        let view = CanvasView::new(30, 30);
+    fn glow_is_cast_into_rect_from_outside() {
+        let mut raster_layer = RasterLayer::new(8);
+
+        raster_layer.perform_action(RasterLayerAction::fill_rect(
+            CanvasRect::at_origin(Dimensions {
+                width: 4,
+                height: 4,
+            }),
+            colors::red(),
+        ));
 
-        let rect = CanvasRect {
-            top_left: (10, 10).into(),
+        let glow_rect = CanvasRect {
+            top_left: (4, 0).into(),
             dimensions: Dimensions {
-                width: 10,
-                height: 10,
+                width: 4,
+                height: 4,
             },
         };
+        let changed_rect = raster_layer.perform_action(RasterLayerAction::glow(
+            glow_rect,
+            Glow::new(colors::blue(), 2, GlowStyle::Outline),
+        ));
+        assert_eq!(changed_rect, Some(glow_rect));
+
+        let raster = raster_layer.rasterize_canvas_rect(CanvasRect::at_origin(Dimensions {
+            width: 8,
+            height: 4,
+        }));
+        let pixels = raster.pixels();
+
+        for y in 0..4 {
+            assert_eq!(pixels[y * 8 + 3], colors::red());
+            assert_eq!(pixels[y * 8 + 4], colors::blue());
+            assert_eq!(pixels[y * 8 + 5], colors::blue());
+            assert_eq!(pixels[y * 8 + 6].as_rgba().3, 0);
+        }
+    }
 
-        let red_oval = RasterLayerAction::fill_oval(rect, colors::red());
-        raster_layer.perform_action(red_oval);
+    #[test]
+    fn stroked_paths_are_drawn_across_chunks() {
+        let mut raster_layer = RasterLayer::new(4);
+        let mut path = Path::new((1.5, 1.5), 1.0, colors::red());
+        path.line_to((9.5, 1.5))
+            .quadratic_to((9.5, 9.5), (1.5, 9.5));
+
+        let action = RasterLayerAction::stroke_path(path);
+        let bounding_rect = action.bounding_rect();
+        assert_eq!(raster_layer.perform_action(action), bounding_rect);
+        assert_eq!(
+            bounding_rect,
+            Some(CanvasRect {
+                top_left: (0, 0).into(),
+                dimensions: Dimensions {
+                    width: 11,
+                    height: 11,
+                },
+            })
+        );
 
-        let raster = raster_layer.rasterize(&view);
+        let raster = raster_layer.rasterize_canvas_rect(CanvasRect::at_origin(Dimensions {
+            width: 12,
+            height: 12,
+        }));
+        let pixel_at = |x: usize, y: usize| raster.pixels()[y * 12 + x];
+        assert_eq!(pixel_at(5, 1), colors::red());
+        assert_eq!(pixel_at(1, 9), colors::red());
+        assert_eq!(pixel_at(5, 5), colors::transparent());
+    }
 
-        let mut expected = BoxRasterChunk::new(30, 30);
-        let oval = Oval::build_from_bound(10, 10).color(colors::red()).build();
-        expected.composite_over(&oval.rasterize().as_window(), DrawPosition::from((10, 10)));
+    #[test]
+    fn draw_line_is_antialiased_across_chunks() {
+        let mut raster_layer = RasterLayer::new(8);
+        let action = RasterLayerAction::draw_line((2, 5).into(), (13, 9).into(), 1, colors::red());
+        let bounding_rect = action.bounding_rect();
 
-        assert_raster_eq!(raster, expected);
+        let changed_rect = raster_layer.perform_action(action);
+        assert_eq!(changed_rect, bounding_rect);
+        assert_eq!(
+            changed_rect,
+            Some(CanvasRect {
+                top_left: (1, 4).into(),
+                dimensions: Dimensions {
+                    width: 14,
+                    height: 7,
+                },
+            })
+        );
+
+        let raster = raster_layer.rasterize_canvas_rect_shared(changed_rect.unwrap());
+        let pixel_at = |x: i32, y: i32| raster.pixels()[(y - 4) as usize * 14 + (x - 1) as usize];
+
+        assert_eq!(pixel_at(2, 5), colors::red());
+        assert_eq!(pixel_at(8, 7), colors::red());
+        assert_eq!(pixel_at(13, 9), colors::red());
+        assert_eq!(pixel_at(14, 4).as_rgba().3, 0);
+
+        let edge_alpha = pixel_at(8, 9).as_rgba().3;
+        assert!(edge_alpha > 0 && edge_alpha < 255);
     }
 
     #[test]
-    fn fill_oval_medium() {
-        let mut raster_layer = RasterLayer::new(30);
-        let view = CanvasView::new(30, 30);
-
-        let rect = CanvasRect {
-            top_left: (10, 15).into(),
-            dimensions: Dimensions {
-                width: 10,
-                height: 10,
-            },
-        };
+    fn fill_polygon_stamps_convex_shapes() {
+        let mut raster_layer = RasterLayer::new(8);
+        let action = RasterLayerAction::fill_polygon(
+            vec![(-4, -4).into(), (12, -4).into(), (-4, 12).into()],
+            colors::red(),
+        );
+        let bounding_rect = action.bounding_rect();
 
-        let red_oval = RasterLayerAction::fill_oval(rect, colors::red());
-        raster_layer.perform_action(red_oval);
+        let changed_rect = raster_layer.perform_action(action);
+        assert_eq!(changed_rect, bounding_rect);
 
-        let raster = raster_layer.rasterize(&view);
+        let raster = raster_layer.rasterize_canvas_rect_shared(changed_rect.unwrap());
+        let pixel_at = |x: i32, y: i32| raster.pixels()[(y + 4) as usize * 16 + (x + 4) as usize];
 
-        let mut expected = BoxRasterChunk::new(30, 30);
-        let oval = Oval::build_from_bound(10, 10).color(colors::red()).build();
-        expected.composite_over(&oval.rasterize().as_window(), DrawPosition::from((10, 15)));
+        assert_eq!(pixel_at(-3, -3), colors::red());
+        assert_eq!(pixel_at(2, 2), colors::red());
+        assert_eq!(pixel_at(10, 10).as_rgba().3, 0);
 
-        assert_raster_eq!(raster, expected);
+        assert_eq!(
+            RasterLayerAction::fill_polygon(vec![(0, 0).into(), (4, 4).into()], colors::red())
+                .bounding_rect(),
+            None
+        );
     }
 
     #[test]
-    fn fill_oval_border() {
-        let mut raster_layer = RasterLayer::new(30);
-        let view = CanvasView::new(60, 60);
+    fn erasing_reduces_alpha_and_frees_chunks() {
+        let mut raster_layer = RasterLayer::new(8);
+        let rect = CanvasRect::at_origin(Dimensions {
+            width: 16,
+            height: 8,
+        });
+        raster_layer.perform_action(RasterLayerAction::fill_rect(rect, colors::red()));
 
-        let rect = CanvasRect {
-            top_left: (25, 10).into(),
+        let left_chunk = CanvasRect::at_origin(Dimensions {
+            width: 8,
+            height: 8,
+        });
+        assert_eq!(
+            raster_layer.perform_action(RasterLayerAction::erase_rect(left_chunk, 255)),
+            Some(left_chunk)
+        );
+        assert!(raster_layer.chunk((0, 0).into()).is_none());
+        assert_eq!(
+            raster_layer.perform_action(RasterLayerAction::erase_rect(left_chunk, 255)),
+            None
+        );
+
+        let right_chunk = raster_layer.chunk_canvas_rect((1, 0).into());
+        raster_layer.perform_action(RasterLayerAction::erase_rect(right_chunk, 128));
+        let chunk = raster_layer.chunk((1, 0).into()).unwrap();
+        assert_eq!(chunk.pixels()[0].as_rgba(), (255, 0, 0, 127));
+
+        raster_layer.perform_action(RasterLayerAction::erase_oval(right_chunk, 255));
+        let chunk = raster_layer.chunk((1, 0).into()).unwrap();
+        assert_eq!(chunk.pixels()[4 * 8 + 4].as_rgba().3, 0);
+        assert_eq!(chunk.pixels()[0].as_rgba().3, 127);
+    }
+
+    #[test]
+    fn alpha_round_trips_through_mask() {
+        let mut raster_layer = RasterLayer::new(8);
+        let red_rect = CanvasRect {
+            top_left: (-2, -2).into(),
             dimensions: Dimensions {
-                width: 10,
-                height: 10,
+                width: 6,
+                height: 6,
             },
         };
+        raster_layer.perform_action(RasterLayerAction::fill_rect(red_rect, colors::red()));
 
-        let red_oval = RasterLayerAction::fill_oval(rect, colors::red());
-        raster_layer.perform_action(red_oval);
+        let mut mask = raster_layer.extract_alpha();
+        assert_eq!(mask.value((0, 0).into()), 255);
+        assert_eq!(mask.value((4, 4).into()), 0);
+        assert_eq!(mask.value((100, 100).into()), 0);
 
-        let raster = raster_layer.rasterize(&view);
+        mask.fill_rect(
+            CanvasRect {
+                top_left: (-2, -2).into(),
+                dimensions: Dimensions {
+                    width: 2,
+                    height: 6,
+                },
+            },
+            128,
+        );
+        mask.set_value((5, 5).into(), 255);
 
-        let mut expected = BoxRasterChunk::new(60, 60);
-        let oval = Oval::build_from_bound(10, 10).color(colors::red()).build();
-        expected.composite_over(&oval.rasterize().as_window(), DrawPosition::from((25, 10)));
+        let changed_rect = raster_layer.apply_alpha(&mask).unwrap();
+        assert!(changed_rect.contains_with_offset(&red_rect).is_some());
 
-        assert_raster_eq!(raster, expected);
+        let raster = raster_layer.rasterize_canvas_rect(red_rect);
+        let pixels = raster.pixels();
+        assert_eq!(pixels[0], Pixel::new_rgba(255, 0, 0, 128));
+        assert_eq!(pixels[2], colors::red());
+        assert_eq!(raster_layer.extract_alpha(), mask);
+    }
+
+    #[test]
+    fn rasterizing_corrupt_chunks_reports_an_error() {
+        let mut raster_layer = RasterLayer::new(8);
+        raster_layer.chunks.insert(
+            (1, 0).into(),
+            ChunkStorage::Full(BoxRasterChunk::new(4, 4)).into(),
+        );
+        raster_layer.chunk_index.insert((1, 0).into());
+
+        let canvas_rect = CanvasRect::at_origin(Dimensions {
+            width: 16,
+            height: 8,
+        });
+
+        assert_eq!(
+            raster_layer.try_rasterize_canvas_rect(canvas_rect),
+            Err(RasterizeError::WindowOutsideChunk {
+                chunk_position: (1, 0).into(),
+                canvas_rect,
+                window_rect: RasterRect {
+                    top_left: (0, 0).into(),
+                    dimensions: Dimensions {
+                        width: 8,
+                        height: 8
+                    },
+                },
+            })
+        );
+
+        let bump = bumpalo::Bump::new();
+        assert!(raster_layer
+            .try_rasterize_canvas_rect_into_bump(canvas_rect, &bump)
+            .is_err());
+
+        assert_eq!(
+            raster_layer
+                .try_perform_action(RasterLayerAction::fill_rect(canvas_rect, colors::red())),
+            Err(RasterizeError::ChunkSizeMismatch {
+                chunk_position: (1, 0).into(),
+                chunk_dimensions: Dimensions {
+                    width: 8,
+                    height: 8
+                },
+            })
+        );
     }
 }
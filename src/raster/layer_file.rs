@@ -0,0 +1,165 @@
+//! Saving raster layers as standalone files, so layers can be shared between
+//! documents or kept as assets such as stamps and backgrounds.
+//!
+//! Like the saves of documents, a layer file holds the chunks of the layer
+//! encoded as PNG images, so only allocated chunks take up space. The
+//! blending settings of a layer are not saved, since they belong to the
+//! document the layer is placed in.
+
+use std::io::{self, Read, Write};
+
+use thiserror::Error;
+
+use super::{
+    chunks::{BoxRasterChunk, PngError},
+    RasterLayer,
+};
+use crate::{
+    encoding::{
+        read_blob, read_chunk_position, read_chunk_size, read_header, read_u32, write_blob,
+        write_chunk_position, write_chunk_size, write_header, write_u32, DecodeError,
+    },
+    primitives::position::ChunkPosition,
+};
+
+const MAGIC: &[u8; 4] = b"MBRL";
+const VERSION: u8 = 1;
+
+/// An error from saving or loading a layer file.
+#[derive(Error, Debug)]
+pub enum LayerFileError {
+    #[error("failed to read or write the layer file: {0}")]
+    Io(#[from] io::Error),
+    #[error("failed to encode or decode a chunk: {0}")]
+    Png(#[from] PngError),
+    #[error("the data is not a layer file of a supported version")]
+    InvalidFormat,
+    #[error("chunk {0:?} is not of the layer's chunk size")]
+    InvalidChunk(ChunkPosition),
+}
+
+impl From<DecodeError> for LayerFileError {
+    fn from(error: DecodeError) -> Self {
+        match error {
+            DecodeError::Io(error) => LayerFileError::Io(error),
+            DecodeError::InvalidFormat => LayerFileError::InvalidFormat,
+        }
+    }
+}
+
+impl RasterLayer {
+    /// Writes the chunks of the layer as a layer file, to be loaded with
+    /// `RasterLayer::load`.
+    pub fn save<W: Write>(&self, mut writer: W) -> Result<(), LayerFileError> {
+        write_header(&mut writer, MAGIC, VERSION)?;
+        write_chunk_size(&mut writer, self.chunk_size)?;
+
+        let mut chunks: Vec<_> = self
+            .allocated_chunk_positions()
+            .into_iter()
+            .filter_map(|chunk_position| Some((chunk_position, self.chunk(chunk_position)?)))
+            .collect();
+        chunks.sort_by_key(|(chunk_position, _)| (chunk_position.1, chunk_position.0));
+
+        write_u32(&mut writer, chunks.len() as u32)?;
+        for (chunk_position, chunk) in chunks {
+            write_chunk_position(&mut writer, chunk_position)?;
+            write_blob(&mut writer, &chunk.encode_png()?)?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Loads a layer saved with `RasterLayer::save`, with the chunk size it was
+    /// saved with.
+    pub fn load<R: Read>(mut reader: R) -> Result<RasterLayer, LayerFileError> {
+        read_header(&mut reader, MAGIC, VERSION)?;
+        let mut layer = RasterLayer::new(read_chunk_size(&mut reader)?);
+
+        let chunk_count = read_u32(&mut reader)?;
+        for _ in 0..chunk_count {
+            let chunk_position = read_chunk_position(&mut reader)?;
+            let chunk = BoxRasterChunk::decode_png(&read_blob(&mut reader)?)?;
+
+            if layer.replace_chunk(chunk_position, Some(chunk)).is_none() {
+                return Err(LayerFileError::InvalidChunk(chunk_position));
+            }
+        }
+
+        Ok(layer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assert_raster_eq,
+        primitives::{dimensions::Dimensions, rect::CanvasRect},
+        raster::{pixels::colors, RasterLayerAction},
+    };
+
+    #[test]
+    fn layers_are_loaded_as_they_were_saved() {
+        let mut layer = RasterLayer::new(8);
+        layer.perform_action(RasterLayerAction::fill_rect(
+            CanvasRect {
+                top_left: (-4, 2).into(),
+                dimensions: Dimensions {
+                    width: 12,
+                    height: 3,
+                },
+            },
+            colors::red(),
+        ));
+        layer.perform_action(RasterLayerAction::draw_line(
+            (0, 0).into(),
+            (20, 9).into(),
+            1,
+            colors::blue(),
+        ));
+
+        let mut file = Vec::new();
+        layer.save(&mut file).unwrap();
+        let loaded = RasterLayer::load(file.as_slice()).unwrap();
+
+        assert_eq!(loaded.chunk_size(), 8);
+        assert_eq!(loaded.chunk_hashes(), layer.chunk_hashes());
+        let layer_rect = CanvasRect {
+            top_left: (-8, -8).into(),
+            dimensions: Dimensions {
+                width: 40,
+                height: 24,
+            },
+        };
+        let (loaded_raster, saved_raster) = (
+            loaded.extract_rect(layer_rect),
+            layer.extract_rect(layer_rect),
+        );
+        assert_raster_eq!(loaded_raster, saved_raster);
+
+        assert!(matches!(
+            RasterLayer::load(&file[..file.len() - 1]),
+            Err(LayerFileError::Io(_))
+        ));
+        file[0] = b'X';
+        assert!(matches!(
+            RasterLayer::load(file.as_slice()),
+            Err(LayerFileError::InvalidFormat)
+        ));
+    }
+
+    #[test]
+    fn huge_chunk_sizes_are_rejected() {
+        let mut file = MAGIC.to_vec();
+        file.push(VERSION);
+        file.extend_from_slice(&(1u32 << 20).to_le_bytes());
+        file.extend_from_slice(&0u32.to_le_bytes());
+
+        assert!(matches!(
+            RasterLayer::load(file.as_slice()),
+            Err(LayerFileError::InvalidFormat)
+        ));
+    }
+}
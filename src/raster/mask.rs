@@ -0,0 +1,226 @@
+//! Single channel layers of coverage values, such as the transparency of a
+//! raster layer extracted to be edited on its own.
+
+use std::collections::HashMap;
+
+use super::{
+    chunks::MaskChunk,
+    source::{MutRasterSource, RasterSource},
+};
+use crate::primitives::{
+    dimensions::Dimensions,
+    position::{CanvasPosition, ChunkPosition, DrawPosition},
+    rect::{CanvasRect, DrawRect},
+};
+
+/// A layer of coverage values from 0 to 255, stored in square chunks like a
+/// `RasterLayer` but with chunks of a single byte per pixel. Unallocated chunks have a
+/// coverage of 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaskLayer {
+    chunk_size: usize,
+    chunks: HashMap<ChunkPosition, MaskChunk>,
+}
+
+impl MaskLayer {
+    pub fn new(chunk_size: usize) -> MaskLayer {
+        MaskLayer {
+            chunk_size: chunk_size.max(1),
+            chunks: HashMap::new(),
+        }
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// The chunk at a chunk position, if it has been allocated.
+    pub fn chunk(&self, chunk_position: ChunkPosition) -> Option<&MaskChunk> {
+        self.chunks.get(&chunk_position)
+    }
+
+    /// The positions of every allocated chunk, in no particular order.
+    pub fn chunk_positions(&self) -> impl Iterator<Item = ChunkPosition> + '_ {
+        self.chunks.keys().copied()
+    }
+
+    /// Replaces the chunk at a chunk position, unallocating it if `chunk` is
+    /// `None`. Returns `false` without changing anything if the chunk is not of
+    /// the layer's chunk size.
+    pub fn replace_chunk(
+        &mut self,
+        chunk_position: ChunkPosition,
+        chunk: Option<MaskChunk>,
+    ) -> bool {
+        match chunk {
+            Some(chunk) => {
+                let chunk_dimensions = Dimensions {
+                    width: self.chunk_size,
+                    height: self.chunk_size,
+                };
+
+                if chunk.dimensions() != chunk_dimensions {
+                    return false;
+                }
+
+                self.chunks.insert(chunk_position, chunk);
+            }
+            None => {
+                self.chunks.remove(&chunk_position);
+            }
+        }
+
+        true
+    }
+
+    fn chunk_mut_or_allocate(&mut self, chunk_position: ChunkPosition) -> &mut MaskChunk {
+        let chunk_size = self.chunk_size;
+
+        self.chunks
+            .entry(chunk_position)
+            .or_insert_with(|| MaskChunk::new(chunk_size, chunk_size))
+    }
+
+    pub fn value(&self, position: CanvasPosition) -> u8 {
+        self.chunks
+            .get(&position.containing_chunk(self.chunk_size))
+            .and_then(|chunk| {
+                chunk.pixel_at_position(position.position_in_containing_chunk(self.chunk_size))
+            })
+            .unwrap_or(0)
+    }
+
+    pub fn set_value(&mut self, position: CanvasPosition, value: u8) {
+        let position_in_chunk = position.position_in_containing_chunk(self.chunk_size);
+        let chunk = self.chunk_mut_or_allocate(position.containing_chunk(self.chunk_size));
+
+        if let Some(subrow) = chunk.mut_subrow_from_position(position_in_chunk, 1) {
+            subrow[0] = value;
+        }
+    }
+
+    /// The positions of the chunks overlapping `canvas_rect`.
+    fn chunk_positions_in_rect(&self, canvas_rect: CanvasRect) -> Vec<ChunkPosition> {
+        if canvas_rect.is_degenerate() {
+            return Vec::new();
+        }
+
+        let top_left_chunk = canvas_rect.top_left.containing_chunk(self.chunk_size);
+        let bottom_right_chunk = canvas_rect.bottom_right().containing_chunk(self.chunk_size);
+
+        (top_left_chunk.1..=bottom_right_chunk.1)
+            .flat_map(|y| (top_left_chunk.0..=bottom_right_chunk.0).map(move |x| (x, y).into()))
+            .collect()
+    }
+
+    /// The position of `canvas_rect` relative to the top left of a chunk.
+    fn draw_position_in_chunk(
+        &self,
+        canvas_rect: CanvasRect,
+        chunk_position: ChunkPosition,
+    ) -> DrawPosition {
+        let chunk_top_left = chunk_position.mul(self.chunk_size as i32);
+
+        (
+            canvas_rect.top_left.0 - chunk_top_left.0,
+            canvas_rect.top_left.1 - chunk_top_left.1,
+        )
+            .into()
+    }
+
+    /// Sets every value within `canvas_rect`, returning the canvas rect that
+    /// has been altered.
+    pub fn fill_rect(&mut self, canvas_rect: CanvasRect, value: u8) -> CanvasRect {
+        for chunk_position in self.chunk_positions_in_rect(canvas_rect) {
+            if value == 0 && !self.chunks.contains_key(&chunk_position) {
+                continue;
+            }
+
+            let draw_rect = DrawRect {
+                top_left: self.draw_position_in_chunk(canvas_rect, chunk_position),
+                dimensions: canvas_rect.dimensions,
+            };
+
+            self.chunk_mut_or_allocate(chunk_position)
+                .fill_rect(value, draw_rect);
+        }
+
+        canvas_rect
+    }
+
+    /// Copies the values of `mask` onto the layer with its top left at
+    /// `top_left`, returning the canvas rect that has been altered.
+    pub fn blit_mask(&mut self, top_left: CanvasPosition, mask: &MaskChunk) -> CanvasRect {
+        let canvas_rect = CanvasRect {
+            top_left,
+            dimensions: mask.dimensions(),
+        };
+
+        for chunk_position in self.chunk_positions_in_rect(canvas_rect) {
+            let draw_position = self.draw_position_in_chunk(canvas_rect, chunk_position);
+
+            self.chunk_mut_or_allocate(chunk_position)
+                .blit(&mask.as_window(), draw_position);
+        }
+
+        canvas_rect
+    }
+
+    /// The smallest canvas rect containing every value that isn't 0, or `None`
+    /// if every value is 0.
+    pub fn content_bounds(&self) -> Option<CanvasRect> {
+        let chunk_size = self.chunk_size;
+
+        self.chunks
+            .iter()
+            .filter_map(|(chunk_position, chunk)| {
+                let chunk_top_left = chunk_position.mul(chunk_size as i32);
+                let (mut left, mut top) = (chunk_size, chunk_size);
+                let (mut right, mut bottom) = (0, 0);
+
+                for (i, _) in chunk.pixels().iter().enumerate().filter(|(_, v)| **v > 0) {
+                    let (x, y) = (i % chunk_size, i / chunk_size);
+                    left = left.min(x);
+                    top = top.min(y);
+                    right = right.max(x);
+                    bottom = bottom.max(y);
+                }
+
+                (left <= right && top <= bottom).then(|| {
+                    CanvasRect::from_points(
+                        (
+                            chunk_top_left.0 + left as i32,
+                            chunk_top_left.1 + top as i32,
+                        )
+                            .into(),
+                        (
+                            chunk_top_left.0 + right as i32,
+                            chunk_top_left.1 + bottom as i32,
+                        )
+                            .into(),
+                    )
+                })
+            })
+            .reduce(|a, b| a.spanning_rect(&b))
+    }
+
+    /// The values within `canvas_rect` as a mask chunk of its dimensions.
+    pub fn rasterize_canvas_rect(&self, canvas_rect: CanvasRect) -> MaskChunk {
+        let Dimensions { width, height } = canvas_rect.dimensions;
+        let mut mask = MaskChunk::new(width, height);
+
+        for chunk_position in self.chunk_positions_in_rect(canvas_rect) {
+            if let Some(chunk) = self.chunks.get(&chunk_position) {
+                let chunk_position_in_rect =
+                    self.draw_position_in_chunk(canvas_rect, chunk_position);
+
+                mask.blit(
+                    &chunk.as_window(),
+                    (-chunk_position_in_rect.0, -chunk_position_in_rect.1).into(),
+                );
+            }
+        }
+
+        mask
+    }
+}
@@ -1,10 +1,35 @@
 //! Manipulation of raster data in the form of discretized chunks.
 
+pub mod blend_if;
+pub mod brush;
+mod chunk_index;
+pub mod chunk_size;
 pub mod chunks;
+pub mod distance;
+pub mod filter;
+pub mod glow;
+/// Iterators behind the pixel and chunk iteration methods of other types,
+/// which aren't meant to be named directly.
+#[doc(hidden)]
 pub mod iter;
 pub mod layer;
+pub mod layer_file;
+pub mod mask;
 pub mod pixels;
+pub mod selection;
 pub mod source;
 
-pub use layer::{RasterLayer, RasterLayerAction};
-pub use pixels::Pixel;
+pub use blend_if::{BlendIf, LuminosityRange};
+pub use brush::{Brush, BrushShape};
+pub use chunk_size::LayerOccupancy;
+pub use distance::{DistanceField, TiledDistanceTransform};
+pub use filter::Kernel;
+pub use glow::{Glow, GlowStyle};
+pub use layer::{
+    CloneStamp, CopyMode, FloodFill, RasterLayer, RasterLayerAction, RasterizeError, Spray,
+};
+pub use layer_file::LayerFileError;
+pub use mask::MaskLayer;
+pub use pixels::{BlendMode, HsvAdjustment, Pixel};
+pub use selection::Selection;
+pub use source::{Component, MutRasterSource, RasterSource, Subsource};
@@ -1,8 +1,70 @@
 //! An RGBA pixel type that supports alpha compositing.
 
+use std::hash::{Hash, Hasher};
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Pixel(pub u32);
 
+/// How the color of a pixel is combined with the color of the pixel it is
+/// drawn over, before being alpha composited.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlendMode {
+    /// The color drawn replaces the color below.
+    #[default]
+    Normal,
+    /// Darkens by multiplying the colors.
+    Multiply,
+    /// Lightens by multiplying the inverses of the colors.
+    Screen,
+    /// Multiplies dark areas and screens light areas of the color below.
+    Overlay,
+    /// Adds the colors, clipping at white.
+    Add,
+    /// Keeps the darker of each color channel.
+    Darken,
+    /// Keeps the lighter of each color channel.
+    Lighten,
+}
+
+impl BlendMode {
+    /// Blends a color channel drawn over a backdrop channel, both from 0 to 255.
+    fn blend_channel(&self, backdrop: u32, source: u32) -> u32 {
+        use BlendMode::*;
+        match self {
+            Normal => source,
+            Multiply => backdrop * source / 255,
+            Screen => backdrop + source - backdrop * source / 255,
+            Overlay => {
+                if backdrop < 128 {
+                    2 * backdrop * source / 255
+                } else {
+                    255 - 2 * (255 - backdrop) * (255 - source) / 255
+                }
+            }
+            Add => (backdrop + source).min(255),
+            Darken => backdrop.min(source),
+            Lighten => backdrop.max(source),
+        }
+    }
+}
+
+/// `2^32 / alpha` rounded up for each alpha, so that dividing by an alpha
+/// is a multiplication and a shift. `benches/composite.rs` compares
+/// compositing with it against dividing.
+const ALPHA_RECIPROCALS: [u64; 256] = {
+    let mut reciprocals = [0; 256];
+    let mut alpha = 1;
+    while alpha < 256 {
+        reciprocals[alpha] = (1u64 << 32).div_ceil(alpha as u64);
+        alpha += 1;
+    }
+
+    reciprocals
+};
+
 impl Pixel {
     pub fn new_rgb(r: u8, g: u8, b: u8) -> Pixel {
         Pixel::new_rgba(r, g, b, 255)
@@ -71,12 +133,18 @@ impl Pixel {
         (a1 + a2 - ((a1 * a2) >> 8)).min(255)
     }
 
+    /// The component of the composited pixel, dividing by the composited
+    /// alpha with a multiplication by its reciprocal instead of a division.
+    /// The result is at most 1 more than dividing exactly, and only when the
+    /// exact quotient is within 2^-15 of the next whole number.
     fn composite_component(c1: u32, a1: u32, c2: u32, a2: u32, a_o: u32) -> u32 {
         if a_o == 0 {
             return 255;
         }
 
-        ((c1 * a1 + c2 * a2 - ((c2 * a2 * a1) >> 8)) / a_o).min(255)
+        let numerator = (c1 * a1 + c2 * a2 - ((c2 * a2 * a1) >> 8)) as u64;
+
+        (((numerator * ALPHA_RECIPROCALS[a_o as usize]) >> 32) as u32).min(255)
     }
 
     /// Composes another pixel over this one.
@@ -85,7 +153,7 @@ impl Pixel {
         let (r2, g2, b2, a2) = self.as_rgba_u32();
 
         let a_o = Pixel::composite_alpha(a1, a2);
-        let a_o_u32 = a_o as u32;
+        let a_o_u32 = a_o;
 
         let (nr, ng, nb) = (
             Pixel::composite_component(r1, a1, r2, a2, a_o_u32),
@@ -96,6 +164,67 @@ impl Pixel {
         self.0 = nr + (ng << 8) + (nb << 16) + (a_o << 24);
     }
 
+    /// Composes another pixel over this one while keeping the alpha of this
+    /// one, so only its color changes, such as for drawing on an alpha locked
+    /// layer. Transparent pixels are left as they are.
+    pub fn composite_over_preserving_alpha(&mut self, over: &Self) {
+        let a = self.as_rgba_u32().3;
+        if a == 0 {
+            return;
+        }
+
+        self.composite_over(over);
+        self.0 = (self.0 & 0x00ffffff) + (a << 24);
+    }
+
+    /// Replaces the color of this pixel with that of another while keeping
+    /// the alpha of this one, such as for blitting onto an alpha locked layer.
+    /// Transparent pixels are left as they are, as are pixels replaced by a
+    /// transparent pixel, since it has no color to take.
+    pub fn blit_preserving_alpha(&mut self, from: &Self) {
+        let a = self.as_rgba_u32().3;
+        if a == 0 || from.as_rgba_u32().3 == 0 {
+            return;
+        }
+
+        self.0 = (from.0 & 0x00ffffff) + (a << 24);
+    }
+
+    /// Draws another pixel over this one, blending the colors with `blend_mode`
+    /// where this pixel is opaque before compositing. With `BlendMode::Normal`
+    /// this is the same as `Pixel::composite_over`.
+    pub fn blend_over(&mut self, over: &Self, blend_mode: BlendMode) {
+        if blend_mode == BlendMode::Normal {
+            self.composite_over(over);
+            return;
+        }
+
+        let (r1, g1, b1, a1) = over.as_rgba_u32();
+        let (r2, g2, b2, a2) = self.as_rgba_u32();
+
+        // Where there is nothing below, the color drawn is left unblended.
+        let blend = |backdrop: u32, source: u32| {
+            ((255 - a2) * source + a2 * blend_mode.blend_channel(backdrop, source)) / 255
+        };
+
+        let blended = Pixel::new_rgba(
+            blend(r2, r1) as u8,
+            blend(g2, g1) as u8,
+            blend(b2, b1) as u8,
+            a1 as u8,
+        );
+
+        self.composite_over(&blended);
+    }
+
+    /// The perceived brightness of the pixel's color from 0 to 255, using the
+    /// Rec. 601 luma weights. Alpha is not taken into account.
+    pub fn luminosity(&self) -> u8 {
+        let (r, g, b, _) = self.as_rgba_u32();
+
+        ((r * 299 + g * 587 + b * 114 + 500) / 1000) as u8
+    }
+
     /// Returns whether a pixel is `close` to another. A pixel is `close` to
     /// another if the difference between each pixel's value is lesser than
     /// the provided delta.
@@ -109,6 +238,127 @@ impl Pixel {
             && a.abs_diff(o_a) <= delta
     }
 
+    /// The color `t` of the way from this one to `other`, with `t` in
+    /// `\[0,1\]`, interpolating the stored sRGB components directly. This is
+    /// what most painting software does, though mixing saturated colors
+    /// passes through darker colors than mixing light would.
+    pub fn lerp(&self, other: &Pixel, t: f32) -> Pixel {
+        let t = t.clamp(0.0, 1.0);
+        let (r1, g1, b1, a1) = self.as_rgba();
+        let (r2, g2, b2, a2) = other.as_rgba();
+        let lerp = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * t).round() as u8;
+
+        Pixel::new_rgba(lerp(r1, r2), lerp(g1, g2), lerp(b1, b2), lerp(a1, a2))
+    }
+
+    /// The color `t` of the way from this one to `other`, with `t` in
+    /// `\[0,1\]`, interpolating in linear light so the mix is as bright as
+    /// mixing the light of the two colors. Alpha is interpolated directly.
+    pub fn lerp_linear(&self, other: &Pixel, t: f32) -> Pixel {
+        let t = t.clamp(0.0, 1.0);
+        let (r1, g1, b1, a1) = self.as_norm_rgba();
+        let (r2, g2, b2, a2) = other.as_norm_rgba();
+        let lerp = |from: f32, to: f32| {
+            let (from, to) = (srgb_to_linear(from), srgb_to_linear(to));
+
+            linear_to_srgb(from + (to - from) * t)
+        };
+        let to_component = |component: f32| (component.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+        Pixel::new_rgba(
+            to_component(lerp(r1, r2)),
+            to_component(lerp(g1, g2)),
+            to_component(lerp(b1, b2)),
+            to_component(a1 + (a2 - a1) * t),
+        )
+    }
+
+    /// The hue of the color in degrees from 0 to 360, along with its chroma,
+    /// the largest and the smallest of its normalized components.
+    fn hue_chroma(&self) -> (f32, f32, f32, f32) {
+        let (r, g, b, _) = self.as_norm_rgba();
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let chroma = max - min;
+
+        let hue = if chroma == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / chroma).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / chroma + 2.0)
+        } else {
+            60.0 * ((r - g) / chroma + 4.0)
+        };
+
+        (hue, chroma, max, min)
+    }
+
+    /// A pixel with a hue in degrees, which wraps around, a chroma and the
+    /// amount added to every component, all normalized.
+    fn from_hue_chroma(hue: f32, chroma: f32, offset: f32, alpha: f32) -> Pixel {
+        let sector = hue.rem_euclid(360.0) / 60.0;
+        let x = chroma * (1.0 - (sector.rem_euclid(2.0) - 1.0).abs());
+
+        let (r, g, b) = match sector as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let component = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+        Pixel::new_rgba(
+            component(r + offset),
+            component(g + offset),
+            component(b + offset),
+            component(alpha),
+        )
+    }
+
+    /// The color as hue in degrees from 0 to 360, then saturation, value and
+    /// alpha normalized to \[0,1\].
+    pub fn to_hsva(&self) -> (f32, f32, f32, f32) {
+        let (hue, chroma, max, _) = self.hue_chroma();
+        let saturation = if max == 0.0 { 0.0 } else { chroma / max };
+
+        (hue, saturation, max, self.as_norm_rgba().3)
+    }
+
+    /// Creates a pixel from a hue in degrees, which wraps around, and
+    /// saturation, value and alpha that will be clamped to \[0,1\].
+    pub fn from_hsva(h: f32, s: f32, v: f32, a: f32) -> Pixel {
+        let (s, v) = (s.clamp(0.0, 1.0), v.clamp(0.0, 1.0));
+        let chroma = v * s;
+
+        Pixel::from_hue_chroma(h, chroma, v - chroma, a.clamp(0.0, 1.0))
+    }
+
+    /// The color as hue in degrees from 0 to 360, then saturation, lightness
+    /// and alpha normalized to \[0,1\].
+    pub fn to_hsla(&self) -> (f32, f32, f32, f32) {
+        let (hue, chroma, max, min) = self.hue_chroma();
+        let lightness = (max + min) / 2.0;
+        let saturation = if chroma == 0.0 {
+            0.0
+        } else {
+            chroma / (1.0 - (2.0 * lightness - 1.0).abs())
+        };
+
+        (hue, saturation, lightness, self.as_norm_rgba().3)
+    }
+
+    /// Creates a pixel from a hue in degrees, which wraps around, and
+    /// saturation, lightness and alpha that will be clamped to \[0,1\].
+    pub fn from_hsla(h: f32, s: f32, l: f32, a: f32) -> Pixel {
+        let (s, l) = (s.clamp(0.0, 1.0), l.clamp(0.0, 1.0));
+        let chroma = (1.0 - (2.0 * l - 1.0).abs()) * s;
+
+        Pixel::from_hue_chroma(h, chroma, l - chroma / 2.0, a.clamp(0.0, 1.0))
+    }
+
     /// Returns the euclidean distance from one pixel to another.
     pub fn eu_distance(&self, other: &Pixel) -> f32 {
         let (r_a, g_a, b_a, a_a) = self.as_norm_rgba();
@@ -123,6 +373,118 @@ impl Pixel {
     }
 }
 
+fn srgb_to_linear(component: f32) -> f32 {
+    if component <= 0.04045 {
+        component / 12.92
+    } else {
+        ((component + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub(crate) fn linear_to_srgb(component: f32) -> f32 {
+    if component <= 0.0031308 {
+        component * 12.92
+    } else {
+        1.055 * component.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// The color at `t` along a gradient through color stops, given as positions
+/// in ascending order and their colors. Colors before the first stop and
+/// after the last are those of the stops. Returns `None` if there are no
+/// stops.
+pub fn gradient_at(stops: &[(f32, Pixel)], t: f32) -> Option<Pixel> {
+    let (first, last) = (stops.first()?, stops.last()?);
+
+    if t <= first.0 {
+        return Some(first.1);
+    }
+
+    let color = stops
+        .windows(2)
+        .find(|pair| t <= pair[1].0)
+        .map(|pair| {
+            let ((from_t, from), (to_t, to)) = (pair[0], pair[1]);
+            let span = to_t - from_t;
+
+            if span > 0.0 {
+                from.lerp(&to, (t - from_t) / span)
+            } else {
+                to
+            }
+        })
+        .unwrap_or(last.1);
+
+    Some(color)
+}
+
+/// Fills `pixels` with a gradient through color stops like `gradient_at`,
+/// spreading positions from 0 at the first pixel to 1 at the last. Pixels are
+/// left as they are if there are no stops.
+pub fn fill_gradient(pixels: &mut [Pixel], stops: &[(f32, Pixel)]) {
+    let last_index = pixels.len().saturating_sub(1).max(1) as f32;
+
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        if let Some(color) = gradient_at(stops, i as f32 / last_index) {
+            *pixel = color;
+        }
+    }
+}
+
+/// A change to the hue, saturation and value of colors, such as for
+/// recoloring a region.
+#[derive(Debug, Copy, Clone)]
+pub struct HsvAdjustment {
+    /// Degrees to turn the hue by.
+    pub hue_shift: f32,
+    pub saturation_scale: f32,
+    pub value_scale: f32,
+}
+
+impl HsvAdjustment {
+    pub fn new(hue_shift: f32, saturation_scale: f32, value_scale: f32) -> HsvAdjustment {
+        HsvAdjustment {
+            hue_shift,
+            saturation_scale,
+            value_scale,
+        }
+    }
+
+    /// The pixel with its color adjusted, keeping its alpha.
+    pub fn apply(&self, pixel: Pixel) -> Pixel {
+        let (h, s, v, a) = pixel.to_hsva();
+
+        Pixel::from_hsva(
+            h + self.hue_shift,
+            s * self.saturation_scale,
+            v * self.value_scale,
+            a,
+        )
+    }
+
+    fn bits(&self) -> [u32; 3] {
+        [
+            self.hue_shift.to_bits(),
+            self.saturation_scale.to_bits(),
+            self.value_scale.to_bits(),
+        ]
+    }
+}
+
+impl PartialEq for HsvAdjustment {
+    fn eq(&self, other: &Self) -> bool {
+        self.bits() == other.bits()
+    }
+}
+
+impl Eq for HsvAdjustment {}
+
+impl Hash for HsvAdjustment {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.bits().hash(state);
+    }
+}
+
 /// Common color definitions.
 pub mod colors {
     use super::Pixel;
@@ -174,6 +536,112 @@ mod tests {
         assert!(should_be_grey.is_close(&Pixel::new_rgba(191, 191, 191, 255), 2));
     }
 
+    #[test]
+    fn composited_components_are_within_1_of_exact_division() {
+        for a_o in 1..=255 {
+            for a1 in (0..=a_o).step_by(3) {
+                for c1 in (0..=255).step_by(5) {
+                    for (c2, a2) in [(0, 255), (255, 255), (37, 128), (200, a_o)] {
+                        let numerator = c1 * a1 + c2 * a2 - ((c2 * a2 * a1) >> 8);
+                        let exact = (numerator / a_o).min(255);
+                        let component = Pixel::composite_component(c1, a1, c2, a2, a_o);
+
+                        assert!(component.abs_diff(exact) <= 1);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn interpolating_colors_and_gradients() {
+        let black = colors::black();
+        let white = colors::white();
+        assert_eq!(black.lerp(&white, 0.5), Pixel::new_rgb(128, 128, 128));
+        assert_eq!(black.lerp(&white, 2.0), white);
+        assert_eq!(
+            black.lerp_linear(&white, 0.5),
+            Pixel::new_rgb(188, 188, 188)
+        );
+        assert_eq!(
+            colors::red().lerp_linear(&colors::transparent(), 0.0),
+            colors::red()
+        );
+
+        let stops = [
+            (0.0, colors::red()),
+            (0.5, colors::green()),
+            (1.0, colors::blue()),
+        ];
+        assert_eq!(gradient_at(&[], 0.5), None);
+        assert_eq!(gradient_at(&stops, -1.0), Some(colors::red()));
+        assert_eq!(gradient_at(&stops, 0.75), Some(Pixel::new_rgb(0, 128, 128)));
+        assert_eq!(gradient_at(&stops, 2.0), Some(colors::blue()));
+
+        let mut pixels = [colors::transparent(); 5];
+        fill_gradient(&mut pixels, &stops);
+        assert_eq!(
+            pixels,
+            [
+                colors::red(),
+                Pixel::new_rgb(128, 128, 0),
+                colors::green(),
+                Pixel::new_rgb(0, 128, 128),
+                colors::blue()
+            ]
+        );
+    }
+
+    #[test]
+    fn hsv_and_hsl_round_trip() {
+        let pixels = [
+            colors::red(),
+            colors::white(),
+            colors::black(),
+            Pixel::new_rgba(30, 160, 220, 128),
+            Pixel::new_rgb(250, 10, 130),
+            Pixel::new_rgb(90, 90, 40),
+        ];
+
+        for pixel in pixels {
+            let (h, s, v, a) = pixel.to_hsva();
+            assert_eq!(Pixel::from_hsva(h, s, v, a), pixel);
+
+            let (h, s, l, a) = pixel.to_hsla();
+            assert_eq!(Pixel::from_hsla(h, s, l, a), pixel);
+        }
+
+        assert_eq!(colors::blue().to_hsva(), (240.0, 1.0, 1.0, 1.0));
+        assert_eq!(Pixel::from_hsla(480.0, 1.0, 0.5, 1.0), colors::green());
+        assert_eq!(
+            HsvAdjustment::new(-120.0, 0.5, 1.0).apply(colors::blue()),
+            Pixel::new_rgb(128, 255, 128)
+        );
+    }
+
+    #[test]
+    fn blend_modes() {
+        let backdrop = Pixel::new_rgb(200, 100, 0);
+        let source = Pixel::new_rgb(100, 200, 255);
+        let blended = |blend_mode| {
+            let mut pixel = backdrop;
+            pixel.blend_over(&source, blend_mode);
+            pixel
+        };
+
+        assert_eq!(blended(BlendMode::Normal), source);
+        assert!(blended(BlendMode::Multiply).is_close(&Pixel::new_rgb(78, 78, 0), 1));
+        assert!(blended(BlendMode::Screen).is_close(&Pixel::new_rgb(222, 222, 255), 1));
+        assert!(blended(BlendMode::Overlay).is_close(&Pixel::new_rgb(189, 156, 0), 1));
+        assert!(blended(BlendMode::Add).is_close(&Pixel::new_rgb(255, 255, 255), 1));
+        assert!(blended(BlendMode::Darken).is_close(&Pixel::new_rgb(100, 100, 0), 1));
+        assert!(blended(BlendMode::Lighten).is_close(&Pixel::new_rgb(200, 200, 255), 1));
+
+        let mut over_transparent = colors::transparent();
+        over_transparent.blend_over(&source, BlendMode::Multiply);
+        assert!(over_transparent.is_close(&source, 1));
+    }
+
     #[cfg(test)]
     fn float_max_delta(a: (f32, f32, f32, f32), b: (f32, f32, f32, f32)) -> f32 {
         (a.0 - b.0)
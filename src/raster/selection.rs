@@ -0,0 +1,266 @@
+//! Selections, which limit the pixels raster actions can change.
+
+use super::{
+    chunks::{BoxRasterChunk, MaskChunk},
+    mask::MaskLayer,
+    pixels::{colors, Pixel},
+};
+use crate::{
+    primitives::{position::CanvasPosition, rect::CanvasRect},
+    vector::shapes::{Oval, RasterizablePolygon},
+};
+
+/// The chunk size of the coverage of selections made by the shape
+/// constructors.
+pub const DEFAULT_SELECTION_CHUNK_SIZE: usize = 128;
+
+/// The pixels of a canvas that raster actions can change, as a coverage from 0
+/// to 255 for each pixel. Pixels with partial coverage are changed partially,
+/// which softens the edges of oval selections.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selection {
+    coverage: MaskLayer,
+    /// Whether the coverage of the selection is the inverse of `coverage`, so
+    /// that everything outside of it is selected.
+    inverted: bool,
+}
+
+impl Selection {
+    /// Selects the pixels covered by `coverage`.
+    pub fn from_mask(coverage: MaskLayer) -> Selection {
+        Selection {
+            coverage,
+            inverted: false,
+        }
+    }
+
+    /// Selects nothing.
+    pub fn empty() -> Selection {
+        Selection::from_mask(MaskLayer::new(DEFAULT_SELECTION_CHUNK_SIZE))
+    }
+
+    /// Selects every pixel.
+    pub fn all() -> Selection {
+        Selection::empty().invert()
+    }
+
+    /// Selects the pixels within a canvas rect.
+    pub fn rect(canvas_rect: CanvasRect) -> Selection {
+        let mut coverage = MaskLayer::new(DEFAULT_SELECTION_CHUNK_SIZE);
+        coverage.fill_rect(canvas_rect, u8::MAX);
+
+        Selection::from_mask(coverage)
+    }
+
+    /// Selects an oval bounded by a canvas rect, partially selecting the pixels
+    /// along its edge.
+    pub fn oval(canvas_rect: CanvasRect) -> Selection {
+        let oval = Oval::build_from_bound(
+            canvas_rect.dimensions.width as u32,
+            canvas_rect.dimensions.height as u32,
+        )
+        .build();
+
+        let mut coverage = MaskLayer::new(DEFAULT_SELECTION_CHUNK_SIZE);
+        coverage.blit_mask(
+            canvas_rect.top_left,
+            &MaskChunk::from_alpha(&oval.rasterize()),
+        );
+
+        Selection::from_mask(coverage)
+    }
+
+    /// Selects the pixels this selection doesn't, with partially selected
+    /// pixels taking the remaining coverage.
+    pub fn invert(self) -> Selection {
+        Selection {
+            inverted: !self.inverted,
+            ..self
+        }
+    }
+
+    pub fn is_inverted(&self) -> bool {
+        self.inverted
+    }
+
+    /// The smallest canvas rect containing every pixel that is at least
+    /// partially selected, or `None` if nothing is selected or the selection is
+    /// inverted, since inverted selections extend without bound.
+    pub fn bounds(&self) -> Option<CanvasRect> {
+        if self.inverted {
+            return None;
+        }
+
+        self.coverage.content_bounds()
+    }
+
+    /// How much a pixel is selected, from 0 for not at all to 255 for
+    /// completely.
+    pub fn coverage(&self, position: CanvasPosition) -> u8 {
+        let value = self.coverage.value(position);
+
+        if self.inverted {
+            u8::MAX - value
+        } else {
+            value
+        }
+    }
+
+    /// The coverage of the pixels within `canvas_rect` as a mask chunk of its
+    /// dimensions.
+    pub fn rasterize_canvas_rect(&self, canvas_rect: CanvasRect) -> MaskChunk {
+        let mut mask = self.coverage.rasterize_canvas_rect(canvas_rect);
+
+        if self.inverted {
+            for value in mask.iter_pixels_mut() {
+                *value = u8::MAX - *value;
+            }
+        }
+
+        mask
+    }
+
+    /// Limits the change from `before` to `after` to the pixels of the
+    /// selection, where both are rasters of `canvas_rect`. Partially selected
+    /// pixels are mixed between the two.
+    pub(super) fn restrict_change(
+        &self,
+        canvas_rect: CanvasRect,
+        before: &BoxRasterChunk,
+        after: &mut BoxRasterChunk,
+    ) {
+        let mask = self.rasterize_canvas_rect(canvas_rect);
+
+        for ((after, before), coverage) in after
+            .iter_pixels_mut()
+            .zip(before.pixels().iter())
+            .zip(mask.pixels().iter())
+        {
+            *after = mix_premultiplied(*before, *after, *coverage);
+        }
+    }
+}
+
+/// Mixes two pixels by `amount` from 0 for only `from` to 255 for only `to`,
+/// weighting colors by their alpha so transparent pixels don't darken the mix.
+fn mix_premultiplied(from: Pixel, to: Pixel, amount: u8) -> Pixel {
+    match amount {
+        0 => from,
+        u8::MAX => to,
+        _ => {
+            let t = amount as f32 / 255.0;
+            let (from_r, from_g, from_b, from_a) = from.as_norm_rgba();
+            let (to_r, to_g, to_b, to_a) = to.as_norm_rgba();
+
+            let a = from_a + (to_a - from_a) * t;
+            if a <= 0.0 {
+                return colors::transparent();
+            }
+
+            let mix = |from_c: f32, to_c: f32| {
+                (from_c * from_a + (to_c * to_a - from_c * from_a) * t) / a
+            };
+
+            Pixel::new_rgba_norm(mix(from_r, to_r), mix(from_g, to_g), mix(from_b, to_b), a)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        canvas::{Layer, ShapeCache},
+        primitives::dimensions::Dimensions,
+        raster::{pixels::colors, RasterLayer, RasterLayerAction},
+    };
+
+    #[test]
+    fn actions_only_change_selected_pixels() {
+        let mut raster_layer = RasterLayer::new(8);
+        let mut shape_cache = ShapeCache::new();
+        let whole_rect = CanvasRect::at_origin(Dimensions {
+            width: 20,
+            height: 20,
+        });
+        let selected_rect = CanvasRect {
+            top_left: (4, 6).into(),
+            dimensions: Dimensions {
+                width: 6,
+                height: 5,
+            },
+        };
+
+        let selection = Selection::rect(selected_rect);
+        raster_layer.perform_action_in_selection(
+            RasterLayerAction::fill_rect(whole_rect, colors::red()),
+            &selection,
+            &mut shape_cache,
+        );
+
+        let raster = raster_layer.rasterize_canvas_rect_shared(whole_rect);
+        for (position, pixel) in raster.as_window().enumerate_pixels() {
+            let position: CanvasPosition = (position.0 as i32, position.1 as i32).into();
+            let expected = if selection.coverage(position) == u8::MAX {
+                colors::red()
+            } else {
+                colors::transparent()
+            };
+
+            assert_eq!(pixel, expected);
+        }
+
+        raster_layer.perform_action_in_selection(
+            RasterLayerAction::erase_rect(whole_rect, u8::MAX),
+            &selection.invert(),
+            &mut shape_cache,
+        );
+        assert_eq!(
+            raster_layer.rasterize_canvas_rect_shared(whole_rect),
+            raster
+        );
+
+        let oval_selection = Selection::oval(selected_rect);
+        assert_eq!(oval_selection.coverage((7, 8).into()), u8::MAX);
+        assert_eq!(oval_selection.coverage((4, 6).into()), 0);
+        assert_eq!(oval_selection.invert().coverage((0, 0).into()), u8::MAX);
+    }
+
+    #[test]
+    fn actions_in_selections_only_allocate_selected_chunks() {
+        let mut raster_layer = RasterLayer::new(8);
+        let mut shape_cache = ShapeCache::new();
+        let whole_rect = CanvasRect::at_origin(Dimensions {
+            width: 32,
+            height: 32,
+        });
+        let first_chunk_rect = CanvasRect::at_origin(Dimensions {
+            width: 8,
+            height: 8,
+        });
+
+        raster_layer.perform_action_in_selection(
+            RasterLayerAction::fill_rect(whole_rect, colors::red()),
+            &Selection::rect(first_chunk_rect),
+            &mut shape_cache,
+        );
+        assert_eq!(
+            raster_layer.allocated_chunk_positions_in_rect(whole_rect),
+            vec![(0, 0).into()]
+        );
+
+        raster_layer.perform_action(RasterLayerAction::fill_rect(whole_rect, colors::red()));
+        raster_layer.perform_action_in_selection(
+            RasterLayerAction::erase_rect(whole_rect, u8::MAX),
+            &Selection::rect(first_chunk_rect),
+            &mut shape_cache,
+        );
+        assert_eq!(
+            raster_layer
+                .allocated_chunk_positions_in_rect(whole_rect)
+                .len(),
+            15
+        );
+        assert!(raster_layer.is_transparent_in(first_chunk_rect));
+    }
+}
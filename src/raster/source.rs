@@ -1,11 +1,78 @@
+use std::ops::Range;
+
 use crate::primitives::{
     dimensions::Dimensions,
     position::{DrawPosition, PixelPosition},
-    rect::RasterRect,
+    rect::{DrawRect, RasterRect},
 };
 
 use super::Pixel;
 
+/// A value stored for each pixel of a raster source, such as an RGBA `Pixel`
+/// or the coverage of a mask.
+pub trait Component: Copy + PartialEq + std::fmt::Debug {
+    /// The value of a pixel that has nothing drawn on it.
+    fn empty() -> Self;
+    /// Draws `over` on top of this value.
+    fn composite_over(&mut self, over: &Self);
+    /// Whether drawing this value over another replaces it.
+    fn is_opaque(&self) -> bool;
+    /// Whether drawing this value over another leaves it unchanged.
+    fn is_clear(&self) -> bool;
+
+    /// Draws a row of values over another. Opaque values are stored as they
+    /// are and clear values are skipped, since the interiors and exteriors
+    /// of shapes are mostly made of them.
+    fn composite_row(dest: &mut [Self], source: &[Self]) {
+        for (dest, source) in dest.iter_mut().zip(source.iter()) {
+            if source.is_opaque() {
+                *dest = *source;
+            } else if !source.is_clear() {
+                dest.composite_over(source);
+            }
+        }
+    }
+}
+
+impl Component for Pixel {
+    fn empty() -> Self {
+        super::pixels::colors::transparent()
+    }
+
+    fn composite_over(&mut self, over: &Self) {
+        Pixel::composite_over(self, over)
+    }
+
+    fn is_opaque(&self) -> bool {
+        self.as_rgba().3 == 255
+    }
+
+    fn is_clear(&self) -> bool {
+        self.as_rgba().3 == 0
+    }
+}
+
+/// Coverage values, where drawing over a value covers the remaining
+/// uncovered portion in proportion to the value drawn.
+impl Component for u8 {
+    fn empty() -> Self {
+        0
+    }
+
+    fn composite_over(&mut self, over: &Self) {
+        let remaining = 255 - *over as u32;
+        *self = (*over as u32 + (*self as u32 * remaining + 127) / 255) as u8;
+    }
+
+    fn is_opaque(&self) -> bool {
+        *self == 255
+    }
+
+    fn is_clear(&self) -> bool {
+        *self == 0
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct BoundedPosition {
     pub position: PixelPosition,
@@ -13,11 +80,11 @@ pub struct BoundedPosition {
 }
 
 pub trait Subsource {
-    fn subsource_at<'a>(&'a self, subrect: RasterRect) -> Option<Self>
+    fn subsource_at(&self, subrect: RasterRect) -> Option<Self>
     where
         Self: Sized;
-    fn subsource_within_at<'a, S: RasterSource>(
-        &'a self,
+    fn subsource_within_at<S: RasterSource>(
+        &self,
         other: &S,
         position: DrawPosition,
     ) -> Option<Self>
@@ -26,32 +93,76 @@ pub trait Subsource {
 }
 
 pub trait RasterSource {
+    /// The type of value stored for each pixel.
+    type Pixel: Component;
+
     fn dimensions(&self) -> Dimensions;
     /// Bounds a position into the underlying collection.
     fn bound_position(&self, position: DrawPosition) -> BoundedPosition {
         self.dimensions().bound_position(position)
     }
     /// A slice of the row within the raster source.
-    fn row(&self, row_num: usize) -> Option<&[Pixel]>;
-    fn subrow_from_position(&self, start_position: PixelPosition, width: usize)
-        -> Option<&[Pixel]>;
-    fn bounded_subrow_from_position(&self, start_position: DrawPosition, width: usize) -> &[Pixel];
-    fn pixel_at_position(&self, position: PixelPosition) -> Option<Pixel>;
-    fn pixel_at_bounded_position(&self, position: DrawPosition) -> Pixel;
+    fn row(&self, row_num: usize) -> Option<&[Self::Pixel]>;
+    fn subrow_from_position(
+        &self,
+        start_position: PixelPosition,
+        width: usize,
+    ) -> Option<&[Self::Pixel]>;
+    fn bounded_subrow_from_position(
+        &self,
+        start_position: DrawPosition,
+        width: usize,
+    ) -> &[Self::Pixel];
+    fn pixel_at_position(&self, position: PixelPosition) -> Option<Self::Pixel>;
+    fn pixel_at_bounded_position(&self, position: DrawPosition) -> Self::Pixel;
 }
 
 pub trait MutRasterSource: RasterSource {
-    fn mut_row(&mut self, row_num: usize) -> Option<&mut [Pixel]>;
+    fn mut_row(&mut self, row_num: usize) -> Option<&mut [Self::Pixel]>;
     fn mut_subrow_from_position(
         &mut self,
         start_position: PixelPosition,
         width: usize,
-    ) -> Option<&mut [Pixel]>;
+    ) -> Option<&mut [Self::Pixel]>;
     fn mut_bounded_subrow_from_position(
         &mut self,
         start_position: DrawPosition,
         width: usize,
-    ) -> &mut [Pixel];
-    fn mut_pixel_at_position(&mut self, position: PixelPosition) -> Option<&mut Pixel>;
-    fn mut_pixel_at_bounded_position(&mut self, position: DrawPosition) -> &mut Pixel;
+    ) -> &mut [Self::Pixel];
+    fn mut_pixel_at_position(&mut self, position: PixelPosition) -> Option<&mut Self::Pixel>;
+    fn mut_pixel_at_bounded_position(&mut self, position: DrawPosition) -> &mut Self::Pixel;
+
+    /// Sets the pixels of a row within `columns`, ignoring the portion outside
+    /// the source.
+    fn fill_row_span(&mut self, row_num: usize, columns: Range<usize>, pixel: Self::Pixel) {
+        if let Some(row) = self.mut_row(row_num) {
+            let width = row.len();
+
+            if let Some(span) = row.get_mut(columns.start.min(width)..columns.end.min(width)) {
+                span.fill(pixel);
+            }
+        }
+    }
+
+    /// Sets the pixels within `rect`, ignoring the portion outside the source.
+    /// Each row is filled as a single span.
+    fn fill_region(&mut self, rect: DrawRect, pixel: Self::Pixel) {
+        let dimensions = self.dimensions();
+        let clamp = |value: i32, max: usize| value.clamp(0, max as i32) as usize;
+
+        let columns = clamp(rect.top_left.0, dimensions.width)
+            ..clamp(
+                rect.top_left.0 + rect.dimensions.width as i32,
+                dimensions.width,
+            );
+        let rows = clamp(rect.top_left.1, dimensions.height)
+            ..clamp(
+                rect.top_left.1 + rect.dimensions.height as i32,
+                dimensions.height,
+            );
+
+        for row_num in rows {
+            self.fill_row_span(row_num, columns.clone(), pixel);
+        }
+    }
 }
@@ -0,0 +1,194 @@
+use std::{
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use ab_glyph::{Font as _, FontArc, Glyph, GlyphId, PxScale, ScaleFont};
+use thiserror::Error;
+
+#[cfg(feature = "shaping")]
+use std::sync::OnceLock;
+
+#[cfg(feature = "shaping")]
+use super::shaping::RustybuzzFace;
+use super::shaping::{default_shaper, ShapingOptions, TextShaper};
+use crate::{
+    primitives::dimensions::Dimensions,
+    raster::{chunks::BoxRasterChunk, source::MutRasterSource, Pixel},
+};
+
+#[derive(Error, Debug)]
+#[error("font data could not be parsed")]
+pub struct InvalidFontError;
+
+static NEXT_FONT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A font that text can be rasterized with. Fonts share their data, so they
+/// are cheap to clone. Fonts are equal to their clones, but not to the same
+/// font data loaded again.
+#[derive(Clone)]
+pub struct Font {
+    id: u64,
+    font: FontArc,
+    shaper: Arc<dyn TextShaper + Send + Sync>,
+    /// The face rustybuzz shapes text with, parsed the first time it's needed.
+    #[cfg(feature = "shaping")]
+    rustybuzz_face: Arc<OnceLock<Option<RustybuzzFace>>>,
+}
+
+impl Debug for Font {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Font")
+            .field("glyph_count", &self.font.glyph_count())
+            .finish()
+    }
+}
+
+impl PartialEq for Font {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for Font {}
+
+/// Glyphs positioned relative to the top left of a block of text.
+struct TextLayout {
+    glyphs: Vec<Glyph>,
+    dimensions: Dimensions,
+}
+
+impl Font {
+    /// Loads a font from the contents of a TrueType or OpenType file.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Font, InvalidFontError> {
+        let font = FontArc::try_from_vec(data).map_err(|_| InvalidFontError)?;
+
+        Ok(Font {
+            id: NEXT_FONT_ID.fetch_add(1, Ordering::Relaxed),
+            font,
+            shaper: Arc::new(default_shaper()),
+            #[cfg(feature = "shaping")]
+            rustybuzz_face: Arc::default(),
+        })
+    }
+
+    /// Replaces the shaper used to lay out text with this font.
+    pub fn with_shaper<S: TextShaper + Send + Sync + 'static>(self, shaper: S) -> Font {
+        Font {
+            // Text is laid out differently with another shaper
+            id: NEXT_FONT_ID.fetch_add(1, Ordering::Relaxed),
+            shaper: Arc::new(shaper),
+            ..self
+        }
+    }
+
+    pub(super) fn ab_glyph_font(&self) -> &FontArc {
+        &self.font
+    }
+
+    /// The rustybuzz face of the font, or `None` if rustybuzz can't read it.
+    #[cfg(feature = "shaping")]
+    pub(super) fn rustybuzz_face(&self) -> Option<&rustybuzz::Face<'_>> {
+        self.rustybuzz_face
+            .get_or_init(|| RustybuzzFace::parse(&self.font))
+            .as_ref()
+            .map(RustybuzzFace::face)
+    }
+
+    /// Lays out `text` at a pixel size. Lines are separated by `'\n'`.
+    fn layout(&self, text: &str, size: f32, options: &ShapingOptions) -> TextLayout {
+        let scale = PxScale::from(size);
+        let scaled_font = self.font.as_scaled(scale);
+        let line_height = scaled_font.height() + scaled_font.line_gap();
+
+        let mut glyphs = vec![];
+        let mut width: f32 = 0.0;
+        let mut line_count = 0;
+
+        for (line_num, line) in text.split('\n').enumerate() {
+            let baseline = line_num as f32 * line_height + scaled_font.ascent();
+            let shaped_line = self.shaper.shape_line(self, line, size, options);
+
+            glyphs.extend(shaped_line.glyphs.iter().map(|shaped_glyph| Glyph {
+                id: GlyphId(shaped_glyph.glyph_id),
+                scale,
+                position: ab_glyph::point(shaped_glyph.x, baseline + shaped_glyph.y),
+            }));
+
+            width = width.max(shaped_line.advance);
+            line_count = line_num + 1;
+        }
+
+        let height = (line_count - 1) as f32 * line_height + scaled_font.height();
+
+        TextLayout {
+            glyphs,
+            dimensions: Dimensions {
+                width: (width.ceil() as usize).max(1),
+                height: (height.ceil() as usize).max(1),
+            },
+        }
+    }
+
+    /// The dimensions `text` occupies when rasterized at a pixel size.
+    pub fn measure(&self, text: &str, size: f32, options: &ShapingOptions) -> Dimensions {
+        self.layout(text, size, options).dimensions
+    }
+
+    /// Rasterizes `text` at a pixel size onto a transparent chunk with the
+    /// dimensions returned by `Font::measure`.
+    pub fn rasterize(
+        &self,
+        text: &str,
+        size: f32,
+        color: Pixel,
+        options: &ShapingOptions,
+    ) -> BoxRasterChunk {
+        let TextLayout { glyphs, dimensions } = self.layout(text, size, options);
+        let mut raster = BoxRasterChunk::new(dimensions.width, dimensions.height);
+
+        let (r, g, b, a) = color.as_rgba();
+
+        for glyph in glyphs {
+            if let Some(outlined_glyph) = self.font.outline_glyph(glyph) {
+                let bounds = outlined_glyph.px_bounds();
+
+                outlined_glyph.draw(|x, y, coverage| {
+                    let x = bounds.min.x as i32 + x as i32;
+                    let y = bounds.min.y as i32 + y as i32;
+
+                    if x < 0 || y < 0 {
+                        return;
+                    }
+
+                    if let Some(pixel) =
+                        raster.mut_pixel_at_position((x as usize, y as usize).into())
+                    {
+                        let alpha = (a as f32 * coverage.min(1.0)).round() as u8;
+
+                        // Glyphs can overlap slightly, so keep the strongest coverage
+                        if alpha > pixel.as_rgba().3 {
+                            *pixel = Pixel::new_rgba(r, g, b, alpha);
+                        }
+                    }
+                });
+            }
+        }
+
+        raster
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_font_data_is_rejected() {
+        assert!(Font::from_bytes(vec![]).is_err());
+        assert!(Font::from_bytes(b"not a font".to_vec()).is_err());
+    }
+}
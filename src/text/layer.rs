@@ -0,0 +1,497 @@
+use std::collections::{BTreeMap, HashMap};
+
+use bumpalo::Bump;
+
+use super::{font::Font, shaping::ShapingOptions};
+use crate::{
+    canvas::{CanvasView, Layer, ViewRotation},
+    primitives::{
+        dimensions::Dimensions,
+        position::{CanvasPosition, DrawPosition},
+        rect::CanvasRect,
+    },
+    raster::{
+        chunks::{raster_chunk::BumpRasterChunk, BoxRasterChunk},
+        BlendIf, BlendMode, Glow, Pixel, RasterizeError,
+    },
+};
+
+/// Identifies a text object within a `TextLayer`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TextObjectId(usize);
+
+/// An editable block of text placed on the canvas.
+#[derive(Debug, Clone)]
+pub struct TextObject {
+    pub text: String,
+    pub font: Font,
+    /// The pixel size of the text at a view scale of 1.
+    pub size: f32,
+    /// The canvas position of the top left of the text.
+    pub position: CanvasPosition,
+    pub color: Pixel,
+    pub shaping_options: ShapingOptions,
+}
+
+impl TextObject {
+    pub fn new(
+        text: impl Into<String>,
+        font: Font,
+        size: f32,
+        position: CanvasPosition,
+        color: Pixel,
+    ) -> TextObject {
+        TextObject {
+            text: text.into(),
+            font,
+            size,
+            position,
+            color,
+            shaping_options: ShapingOptions::default(),
+        }
+    }
+
+    /// Sets the language the text is shaped for, as a BCP 47 tag such as `"en"`.
+    pub fn with_language(self, language: impl Into<String>) -> TextObject {
+        TextObject {
+            shaping_options: ShapingOptions {
+                language: Some(language.into()),
+            },
+            ..self
+        }
+    }
+
+    /// The canvas rect covered by the text.
+    pub fn canvas_rect(&self) -> CanvasRect {
+        CanvasRect {
+            top_left: self.position,
+            dimensions: self
+                .font
+                .measure(&self.text, self.size, &self.shaping_options),
+        }
+    }
+
+    /// Rasterizes the text at its size onto a transparent chunk covering
+    /// `TextObject::canvas_rect`.
+    pub fn rasterize(&self) -> BoxRasterChunk {
+        self.rasterize_at_scale(1.0)
+    }
+
+    fn rasterize_at_scale(&self, scale: f32) -> BoxRasterChunk {
+        self.font.rasterize(
+            &self.text,
+            self.size * scale,
+            self.color,
+            &self.shaping_options,
+        )
+    }
+}
+
+impl PartialEq for TextObject {
+    fn eq(&self, other: &Self) -> bool {
+        self.text == other.text
+            && self.font == other.font
+            && self.size.to_bits() == other.size.to_bits()
+            && self.position == other.position
+            && self.color == other.color
+            && self.shaping_options == other.shaping_options
+    }
+}
+
+impl Eq for TextObject {}
+
+/// An edit to a text layer.
+#[derive(Debug, Clone)]
+pub enum TextLayerAction {
+    AddText(TextObject),
+    RemoveText(TextObjectId),
+    SetText(TextObjectId, String),
+    MoveText(TextObjectId, CanvasPosition),
+    ResizeText(TextObjectId, f32),
+    RecolorText(TextObjectId, Pixel),
+}
+
+/// The raster of a text object at the last scale it was viewed at.
+#[derive(Debug, Clone)]
+struct CachedTextRaster {
+    scale_key: u32,
+    raster: BoxRasterChunk,
+}
+
+/// Quantizes a view scale so that scales that are practically the same can
+/// share a raster.
+fn scale_key(scale: f32) -> u32 {
+    (scale * 100.0).round() as u32
+}
+
+/// A layer of text objects that stay editable after being placed. Unlike text
+/// drawn onto a raster layer, text objects are rasterized lazily at the scale
+/// they are viewed at, so they stay sharp when zoomed in.
+#[derive(Debug, Clone, Default)]
+pub struct TextLayer {
+    objects: BTreeMap<TextObjectId, TextObject>,
+    next_id: usize,
+    raster_cache: HashMap<TextObjectId, CachedTextRaster>,
+    blend_if: Option<BlendIf>,
+    blend_mode: BlendMode,
+    glow: Option<Glow>,
+}
+
+impl TextLayer {
+    pub fn new() -> TextLayer {
+        TextLayer::default()
+    }
+
+    /// Adds a text object to the top of the layer.
+    pub fn add_text(&mut self, text_object: TextObject) -> TextObjectId {
+        let id = TextObjectId(self.next_id);
+        self.next_id += 1;
+
+        self.objects.insert(id, text_object);
+
+        id
+    }
+
+    /// Removes a text object, returning it if it existed.
+    pub fn remove_text(&mut self, id: TextObjectId) -> Option<TextObject> {
+        self.raster_cache.remove(&id);
+        self.objects.remove(&id)
+    }
+
+    pub fn text(&self, id: TextObjectId) -> Option<&TextObject> {
+        self.objects.get(&id)
+    }
+
+    /// The text objects of the layer from bottom to top.
+    pub fn iter(&self) -> impl Iterator<Item = (TextObjectId, &TextObject)> {
+        self.objects
+            .iter()
+            .map(|(id, text_object)| (*id, text_object))
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Edits a text object, returning the canvas rect that has been altered by
+    /// the edit. Returns `None` if there is no text object with the id.
+    pub fn edit_text<F>(&mut self, id: TextObjectId, f: F) -> Option<CanvasRect>
+    where
+        F: FnOnce(&mut TextObject),
+    {
+        let text_object = self.objects.get_mut(&id)?;
+        let old_canvas_rect = text_object.canvas_rect();
+
+        f(text_object);
+
+        self.raster_cache.remove(&id);
+
+        Some(old_canvas_rect.spanning_rect(&text_object.canvas_rect()))
+    }
+
+    /// Performs a text layer action, returning the canvas rect that has been
+    /// altered by it.
+    pub fn perform_action(&mut self, action: TextLayerAction) -> Option<CanvasRect> {
+        use TextLayerAction::*;
+        match action {
+            AddText(text_object) => {
+                let canvas_rect = text_object.canvas_rect();
+                self.add_text(text_object);
+
+                Some(canvas_rect)
+            }
+            RemoveText(id) => self
+                .remove_text(id)
+                .map(|text_object| text_object.canvas_rect()),
+            SetText(id, text) => self.edit_text(id, |text_object| text_object.text = text),
+            MoveText(id, position) => {
+                self.edit_text(id, |text_object| text_object.position = position)
+            }
+            ResizeText(id, size) => self.edit_text(id, |text_object| text_object.size = size),
+            RecolorText(id, color) => self.edit_text(id, |text_object| text_object.color = color),
+        }
+    }
+
+    /// A copy of the text objects of the layer, without its cached rasters or
+    /// layer settings, for restoring with `TextLayer::swap_objects`.
+    pub(crate) fn snapshot_objects(&self) -> TextLayer {
+        TextLayer {
+            objects: self.objects.clone(),
+            next_id: self.next_id,
+            ..TextLayer::default()
+        }
+    }
+
+    /// Exchanges the text objects of the layer with those of `other`, leaving
+    /// the settings of both layers in place.
+    pub(crate) fn swap_objects(&mut self, other: &mut TextLayer) {
+        std::mem::swap(&mut self.objects, &mut other.objects);
+        std::mem::swap(&mut self.next_id, &mut other.next_id);
+
+        self.raster_cache.clear();
+        other.raster_cache.clear();
+    }
+
+    /// Composites the text objects intersecting `canvas_rect` onto a raster of
+    /// `raster_dimensions`, with `scale` pixels per canvas unit.
+    fn composite_text<F>(
+        &self,
+        canvas_rect: CanvasRect,
+        raster_dimensions: Dimensions,
+        scale: f32,
+        mut rasterize_text: F,
+    ) -> BoxRasterChunk
+    where
+        F: FnMut(TextObjectId, &TextObject, &mut dyn FnMut(&BoxRasterChunk)),
+    {
+        let mut raster = BoxRasterChunk::new(raster_dimensions.width, raster_dimensions.height);
+
+        for (id, text_object) in self.objects.iter() {
+            if !canvas_rect.intersects(&text_object.canvas_rect()) {
+                continue;
+            }
+
+            let draw_position: DrawPosition = (
+                ((text_object.position.0 - canvas_rect.top_left.0) as f32 * scale).round() as i32,
+                ((text_object.position.1 - canvas_rect.top_left.1) as f32 * scale).round() as i32,
+            )
+                .into();
+
+            rasterize_text(*id, text_object, &mut |text_raster| {
+                raster.composite_over(&text_raster.as_window(), draw_position);
+            });
+        }
+
+        raster
+    }
+}
+
+impl Layer for TextLayer {
+    fn rasterize(&mut self, view: &CanvasView) -> BoxRasterChunk {
+        let scale = view
+            .view_dimensions
+            .relative_scale(view.canvas_dimensions)
+            .height_factor;
+        let key = scale_key(scale);
+
+        let mut raster_cache = std::mem::take(&mut self.raster_cache);
+
+        let raster = self.composite_text(
+            view.canvas_rect(),
+            view.view_dimensions,
+            scale,
+            |id, text_object, draw| {
+                let cached_raster = raster_cache
+                    .entry(id)
+                    .and_modify(|cached_raster| {
+                        if cached_raster.scale_key != key {
+                            *cached_raster = CachedTextRaster {
+                                scale_key: key,
+                                raster: text_object.rasterize_at_scale(scale),
+                            };
+                        }
+                    })
+                    .or_insert_with(|| CachedTextRaster {
+                        scale_key: key,
+                        raster: text_object.rasterize_at_scale(scale),
+                    });
+
+                draw(&cached_raster.raster);
+            },
+        );
+
+        self.raster_cache = raster_cache;
+
+        raster
+    }
+
+    fn rasterize_canvas_rect(&mut self, canvas_rect: CanvasRect) -> BoxRasterChunk {
+        self.rasterize(&CanvasView {
+            top_left: canvas_rect.top_left,
+            view_dimensions: canvas_rect.dimensions,
+            canvas_dimensions: canvas_rect.dimensions,
+            rotation: ViewRotation::NONE,
+        })
+    }
+
+    fn rasterize_canvas_rect_shared(&self, canvas_rect: CanvasRect) -> BoxRasterChunk {
+        self.composite_text(
+            canvas_rect,
+            canvas_rect.dimensions,
+            1.0,
+            |id, text_object, draw| match self.raster_cache.get(&id) {
+                Some(cached_raster) if cached_raster.scale_key == scale_key(1.0) => {
+                    draw(&cached_raster.raster)
+                }
+                _ => draw(&text_object.rasterize_at_scale(1.0)),
+            },
+        )
+    }
+
+    fn rasterize_into_bump<'bump>(
+        &mut self,
+        view: &CanvasView,
+        bump: &'bump Bump,
+    ) -> BumpRasterChunk<'bump> {
+        self.rasterize(view).as_window().to_chunk_into_bump(bump)
+    }
+
+    fn rasterize_canvas_rect_into_bump<'bump>(
+        &mut self,
+        canvas_rect: CanvasRect,
+        bump: &'bump Bump,
+    ) -> BumpRasterChunk<'bump> {
+        self.rasterize_canvas_rect(canvas_rect)
+            .as_window()
+            .to_chunk_into_bump(bump)
+    }
+
+    fn try_rasterize_canvas_rect(
+        &mut self,
+        canvas_rect: CanvasRect,
+    ) -> Result<BoxRasterChunk, RasterizeError> {
+        CanvasRect::try_new(canvas_rect.top_left, canvas_rect.dimensions)?;
+
+        Ok(self.rasterize_canvas_rect(canvas_rect))
+    }
+
+    fn try_rasterize_canvas_rect_into_bump<'bump>(
+        &mut self,
+        canvas_rect: CanvasRect,
+        bump: &'bump Bump,
+    ) -> Result<BumpRasterChunk<'bump>, RasterizeError> {
+        CanvasRect::try_new(canvas_rect.top_left, canvas_rect.dimensions)?;
+
+        Ok(self.rasterize_canvas_rect_into_bump(canvas_rect, bump))
+    }
+
+    fn clear(&mut self) {
+        self.objects.clear();
+        self.raster_cache.clear();
+    }
+
+    fn content_bounds(&self) -> Option<CanvasRect> {
+        self.objects
+            .values()
+            .map(TextObject::canvas_rect)
+            .reduce(|a, b| a.spanning_rect(&b))
+    }
+
+    fn blend_if(&self) -> Option<BlendIf> {
+        self.blend_if
+    }
+
+    fn set_blend_if(&mut self, blend_if: Option<BlendIf>) {
+        self.blend_if = blend_if;
+    }
+
+    fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+    }
+
+    fn glow(&self) -> Option<Glow> {
+        self.glow
+    }
+
+    fn set_glow(&mut self, glow: Option<Glow>) {
+        self.glow = glow;
+    }
+
+    fn rasterizes_at_view_scale(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        raster::{pixels::colors, source::RasterSource},
+        text::test_font::test_font_bytes,
+    };
+
+    fn text_layer_with(text: &str, size: f32) -> TextLayer {
+        let font = Font::from_bytes(test_font_bytes()).unwrap();
+        let mut text_layer = TextLayer::new();
+        text_layer.add_text(TextObject::new(
+            text,
+            font,
+            size,
+            (2, 3).into(),
+            colors::red(),
+        ));
+
+        text_layer
+    }
+
+    fn partially_covered_pixels(raster: &BoxRasterChunk) -> usize {
+        raster
+            .pixels()
+            .iter()
+            .filter(|pixel| !matches!(pixel.as_rgba().3, 0 | u8::MAX))
+            .count()
+    }
+
+    #[test]
+    fn text_is_rasterized_at_its_canvas_rect() {
+        let mut text_layer = text_layer_with("AI", 20.0);
+        let (_, text_object) = text_layer.iter().next().unwrap();
+        let text_rect = text_object.canvas_rect();
+        assert_eq!(text_rect.top_left, (2, 3).into());
+        assert_eq!(text_layer.content_bounds(), Some(text_rect));
+
+        let canvas_rect = CanvasRect::at_origin(Dimensions {
+            width: 32,
+            height: 32,
+        });
+        let raster = text_layer.rasterize_canvas_rect(canvas_rect);
+        assert_eq!(raster.dimensions(), canvas_rect.dimensions);
+        assert_eq!(text_layer.rasterize_canvas_rect_shared(canvas_rect), raster);
+
+        // The box of `A` spans 1 to 11 pixels from the left of the text at
+        // a size of 20
+        let alpha_at = |x, y| raster.pixel_at_position((x, y).into()).unwrap().as_rgba().3;
+        assert_eq!(
+            raster.pixel_at_position((2 + 5, 3 + 10).into()),
+            Some(colors::red())
+        );
+        assert_eq!(alpha_at(2, 3 + 10), 0);
+        assert_eq!(alpha_at(30, 30), 0);
+    }
+
+    #[test]
+    fn text_stays_sharp_when_rasterized_at_a_larger_view_scale() {
+        let mut text_layer = text_layer_with("AVI", 25.0);
+        let canvas_rect = CanvasRect::at_origin(Dimensions {
+            width: 40,
+            height: 32,
+        });
+        let view = CanvasView {
+            top_left: canvas_rect.top_left,
+            canvas_dimensions: canvas_rect.dimensions,
+            view_dimensions: Dimensions {
+                width: 160,
+                height: 128,
+            },
+            rotation: ViewRotation::NONE,
+        };
+
+        let mut upscaled = text_layer.rasterize_canvas_rect(canvas_rect);
+        upscaled.nn_scale(view.view_dimensions);
+        let view_raster = text_layer.rasterize(&view);
+
+        assert_eq!(view_raster.dimensions(), view.view_dimensions);
+        assert!(
+            partially_covered_pixels(&view_raster) < partially_covered_pixels(&upscaled),
+            "text should be rasterized at the view scale rather than upscaled"
+        );
+    }
+}
@@ -0,0 +1,11 @@
+//! Editable text objects and the layer that holds them.
+
+pub mod font;
+pub mod layer;
+pub mod shaping;
+#[cfg(test)]
+pub(crate) mod test_font;
+
+pub use font::{Font, InvalidFontError};
+pub use layer::{TextLayer, TextLayerAction, TextObject, TextObjectId};
+pub use shaping::{ShapingOptions, TextShaper};
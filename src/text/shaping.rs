@@ -0,0 +1,253 @@
+//! Conversion of lines of text into positioned glyphs.
+//!
+//! Without the `shaping` feature text is shaped by mapping each character to
+//! a glyph, which is fast and correct for ASCII. With the feature enabled,
+//! text is shaped by rustybuzz so ligatures and complex scripts are handled.
+
+#[cfg(feature = "shaping")]
+use ab_glyph::FontArc;
+use ab_glyph::{Font as _, PxScale, ScaleFont};
+
+use super::font::Font;
+
+/// Options that affect how text is shaped.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ShapingOptions {
+    /// A BCP 47 language tag such as `"en"` or `"tr"`, used to select
+    /// language specific glyph forms. When `None` the shaper decides.
+    pub language: Option<String>,
+}
+
+/// A glyph positioned along a line of text. Positions are in pixels relative
+/// to the start of the line's baseline, with y increasing downwards.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ShapedGlyph {
+    pub glyph_id: u16,
+    pub x: f32,
+    pub y: f32,
+    /// The byte index in the line of the first character this glyph represents.
+    pub cluster: usize,
+}
+
+/// A line of text converted into glyphs.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ShapedLine {
+    pub glyphs: Vec<ShapedGlyph>,
+    /// The width of the line in pixels.
+    pub advance: f32,
+}
+
+/// Something that can convert a line of text into positioned glyphs.
+pub trait TextShaper {
+    fn shape_line(
+        &self,
+        font: &Font,
+        line: &str,
+        size: f32,
+        options: &ShapingOptions,
+    ) -> ShapedLine;
+}
+
+/// A shaper that maps every character to a single glyph and applies kerning.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct SimpleShaper;
+
+impl TextShaper for SimpleShaper {
+    fn shape_line(
+        &self,
+        font: &Font,
+        line: &str,
+        size: f32,
+        _options: &ShapingOptions,
+    ) -> ShapedLine {
+        let scaled_font = font.ab_glyph_font().as_scaled(PxScale::from(size));
+
+        let mut glyphs = vec![];
+        let mut caret = 0.0;
+        let mut previous_glyph_id = None;
+
+        for (cluster, c) in line.char_indices() {
+            let glyph_id = scaled_font.glyph_id(c);
+
+            if let Some(previous_glyph_id) = previous_glyph_id {
+                caret += scaled_font.kern(previous_glyph_id, glyph_id);
+            }
+
+            glyphs.push(ShapedGlyph {
+                glyph_id: glyph_id.0,
+                x: caret,
+                y: 0.0,
+                cluster,
+            });
+
+            caret += scaled_font.h_advance(glyph_id);
+            previous_glyph_id = Some(glyph_id);
+        }
+
+        ShapedLine {
+            glyphs,
+            advance: caret,
+        }
+    }
+}
+
+/// A shaper backed by rustybuzz, supporting ligatures and complex scripts.
+#[cfg(feature = "shaping")]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct RustybuzzShaper;
+
+#[cfg(feature = "shaping")]
+impl TextShaper for RustybuzzShaper {
+    fn shape_line(
+        &self,
+        font: &Font,
+        line: &str,
+        size: f32,
+        options: &ShapingOptions,
+    ) -> ShapedLine {
+        use std::str::FromStr;
+
+        let face = match font.rustybuzz_face() {
+            Some(face) => face,
+            None => return SimpleShaper.shape_line(font, line, size, options),
+        };
+
+        let mut buffer = rustybuzz::UnicodeBuffer::new();
+        buffer.push_str(line);
+
+        if let Some(language) = options
+            .language
+            .as_deref()
+            .and_then(|language| rustybuzz::Language::from_str(language).ok())
+        {
+            buffer.set_language(language);
+        }
+        buffer.guess_segment_properties();
+
+        let glyph_buffer = rustybuzz::shape(face, &[], buffer);
+
+        // Positions are in font units, which ab_glyph scales by the same factor
+        let scale_factor = font
+            .ab_glyph_font()
+            .as_scaled(PxScale::from(size))
+            .h_scale_factor();
+
+        let mut glyphs = vec![];
+        let mut caret = 0.0;
+
+        for (info, position) in glyph_buffer
+            .glyph_infos()
+            .iter()
+            .zip(glyph_buffer.glyph_positions())
+        {
+            glyphs.push(ShapedGlyph {
+                glyph_id: info.glyph_id as u16,
+                x: caret + position.x_offset as f32 * scale_factor,
+                y: -position.y_offset as f32 * scale_factor,
+                cluster: info.cluster as usize,
+            });
+
+            caret += position.x_advance as f32 * scale_factor;
+        }
+
+        ShapedLine {
+            glyphs,
+            advance: caret,
+        }
+    }
+}
+
+/// The rustybuzz face of a font, parsed once per font since parsing the
+/// tables of a font takes much longer than shaping a line with them.
+#[cfg(feature = "shaping")]
+pub(super) struct RustybuzzFace {
+    // Borrows the data of `_font`, so it's declared first to be dropped first
+    face: rustybuzz::Face<'static>,
+    _font: FontArc,
+}
+
+#[cfg(feature = "shaping")]
+impl RustybuzzFace {
+    /// Parses the face of `font`, returning `None` if rustybuzz can't read it.
+    pub(super) fn parse(font: &FontArc) -> Option<RustybuzzFace> {
+        let font = font.clone();
+        let data = font.font_data();
+        // SAFETY: The data of a font is on the heap and doesn't move or change
+        // while the font is alive, and the font lives as long as the face.
+        let data: &'static [u8] = unsafe { std::slice::from_raw_parts(data.as_ptr(), data.len()) };
+        let face = rustybuzz::Face::from_slice(data, 0)?;
+
+        Some(RustybuzzFace { face, _font: font })
+    }
+
+    pub(super) fn face(&self) -> &rustybuzz::Face<'_> {
+        &self.face
+    }
+}
+
+/// The shaper fonts use unless one is provided with `Font::with_shaper`.
+#[cfg(feature = "shaping")]
+pub fn default_shaper() -> RustybuzzShaper {
+    RustybuzzShaper
+}
+
+/// The shaper fonts use unless one is provided with `Font::with_shaper`.
+#[cfg(not(feature = "shaping"))]
+pub fn default_shaper() -> SimpleShaper {
+    SimpleShaper
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::test_font::{
+        test_font_bytes, AV_KERNING, NARROW_ADVANCE, UNITS_PER_EM, WIDE_ADVANCE,
+    };
+
+    /// The size at which one font unit of the test font is a tenth of a pixel.
+    const SIZE: f32 = UNITS_PER_EM as f32 / 10.0;
+
+    fn pixels(font_units: i32) -> f32 {
+        font_units as f32 / 10.0
+    }
+
+    fn glyph_positions(shaped_line: &ShapedLine) -> Vec<(u16, f32)> {
+        shaped_line
+            .glyphs
+            .iter()
+            .map(|glyph| (glyph.glyph_id, glyph.x))
+            .collect()
+    }
+
+    fn assert_shapes_with_advances_and_kerning<S: TextShaper>(shaper: S) {
+        let font = Font::from_bytes(test_font_bytes()).unwrap();
+        let wide = WIDE_ADVANCE as i32;
+        let narrow = NARROW_ADVANCE as i32;
+        let kerning = AV_KERNING as i32;
+
+        let shaped_line = shaper.shape_line(&font, "AIV", SIZE, &ShapingOptions::default());
+        assert_eq!(
+            glyph_positions(&shaped_line),
+            [(1, 0.0), (2, pixels(wide)), (3, pixels(wide + narrow))]
+        );
+        assert_eq!(shaped_line.advance, pixels(2 * wide + narrow));
+
+        let shaped_line = shaper.shape_line(&font, "AV", SIZE, &ShapingOptions::default());
+        assert_eq!(
+            glyph_positions(&shaped_line),
+            [(1, 0.0), (3, pixels(wide + kerning))]
+        );
+        assert_eq!(shaped_line.advance, pixels(2 * wide + kerning));
+    }
+
+    #[test]
+    fn simple_shaper_applies_advances_and_kerning() {
+        assert_shapes_with_advances_and_kerning(SimpleShaper);
+    }
+
+    #[cfg(feature = "shaping")]
+    #[test]
+    fn rustybuzz_shaper_applies_advances_and_kerning() {
+        assert_shapes_with_advances_and_kerning(RustybuzzShaper);
+    }
+}
@@ -0,0 +1,226 @@
+//! A tiny TrueType font built in memory for tests, since the crate doesn't
+//! ship any font files. Its glyphs are solid boxes, so rasters of them are
+//! easy to reason about.
+//!
+//! The font has 1000 units per em, an ascent of 800 and a descent of 200.
+//! `A` and `V` are 600 units wide with a box from 50 to 550, `I` is 300 units
+//! wide with a box from 100 to 200, and the pair `AV` is kerned by -200.
+
+/// The advance of `A` and `V` in font units.
+pub const WIDE_ADVANCE: u16 = 600;
+/// The advance of `I` in font units.
+pub const NARROW_ADVANCE: u16 = 300;
+/// The kerning between `A` and a following `V` in font units.
+pub const AV_KERNING: i16 = -200;
+pub const UNITS_PER_EM: u16 = 1000;
+
+/// The characters of the font with their glyph ids, advances and the left,
+/// bottom, right and top of their boxes.
+const GLYPHS: [(char, u16, u16, [i16; 4]); 3] = [
+    ('A', 1, WIDE_ADVANCE, [50, 0, 550, 700]),
+    ('I', 2, NARROW_ADVANCE, [100, 0, 200, 700]),
+    ('V', 3, WIDE_ADVANCE, [50, 0, 550, 700]),
+];
+
+fn push_u16(bytes: &mut Vec<u8>, value: u16) {
+    bytes.extend_from_slice(&value.to_be_bytes());
+}
+
+fn push_i16(bytes: &mut Vec<u8>, value: i16) {
+    bytes.extend_from_slice(&value.to_be_bytes());
+}
+
+fn push_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_be_bytes());
+}
+
+fn head() -> Vec<u8> {
+    let mut table = vec![];
+    push_u32(&mut table, 0x0001_0000);
+    push_u32(&mut table, 0x0001_0000);
+    push_u32(&mut table, 0);
+    push_u32(&mut table, 0x5F0F_3CF5);
+    push_u16(&mut table, 0);
+    push_u16(&mut table, UNITS_PER_EM);
+    table.extend_from_slice(&[0; 16]);
+    for bound in [0, 0, 600, 700] {
+        push_i16(&mut table, bound);
+    }
+    push_u16(&mut table, 0);
+    push_u16(&mut table, 8);
+    push_i16(&mut table, 2);
+    // Long offsets in loca
+    push_i16(&mut table, 1);
+    push_i16(&mut table, 0);
+
+    table
+}
+
+fn hhea(glyph_count: u16) -> Vec<u8> {
+    let mut table = vec![];
+    push_u32(&mut table, 0x0001_0000);
+    push_i16(&mut table, 800);
+    push_i16(&mut table, -200);
+    push_i16(&mut table, 0);
+    push_u16(&mut table, WIDE_ADVANCE);
+    for value in [0, 0, 600, 1, 0, 0, 0, 0, 0, 0, 0] {
+        push_i16(&mut table, value);
+    }
+    push_u16(&mut table, glyph_count);
+
+    table
+}
+
+fn maxp(glyph_count: u16) -> Vec<u8> {
+    let mut table = vec![];
+    push_u32(&mut table, 0x0000_5000);
+    push_u16(&mut table, glyph_count);
+
+    table
+}
+
+fn hmtx() -> Vec<u8> {
+    let mut table = vec![];
+    push_u16(&mut table, WIDE_ADVANCE);
+    push_i16(&mut table, 0);
+    for (_, _, advance, [left, ..]) in GLYPHS {
+        push_u16(&mut table, advance);
+        push_i16(&mut table, left);
+    }
+
+    table
+}
+
+/// The glyf and loca tables, with an empty `.notdef` glyph.
+fn glyf_and_loca() -> (Vec<u8>, Vec<u8>) {
+    let mut glyf = vec![];
+    let mut loca = vec![];
+    push_u32(&mut loca, 0);
+    push_u32(&mut loca, 0);
+
+    for (_, _, _, [left, bottom, right, top]) in GLYPHS {
+        push_i16(&mut glyf, 1);
+        for bound in [left, bottom, right, top] {
+            push_i16(&mut glyf, bound);
+        }
+        push_u16(&mut glyf, 3);
+        push_u16(&mut glyf, 0);
+        // Four points on the curve, with coordinates as 16 bit deltas
+        glyf.extend_from_slice(&[1; 4]);
+        for delta in [left, 0, right - left, 0] {
+            push_i16(&mut glyf, delta);
+        }
+        for delta in [bottom, top - bottom, 0, bottom - top] {
+            push_i16(&mut glyf, delta);
+        }
+
+        push_u32(&mut loca, glyf.len() as u32);
+    }
+
+    (glyf, loca)
+}
+
+/// A Windows Unicode cmap with a format 4 segment per character.
+fn cmap() -> Vec<u8> {
+    let segments: Vec<(u16, i16)> = GLYPHS
+        .iter()
+        .map(|(c, glyph_id, ..)| (*c as u16, (*glyph_id as i16).wrapping_sub(*c as i16)))
+        .chain(std::iter::once((0xFFFF, 1)))
+        .collect();
+    let segment_count = segments.len() as u16;
+    let search_range = 2 * 2u16.pow(segment_count.ilog2());
+
+    let mut subtable = vec![];
+    push_u16(&mut subtable, 4);
+    push_u16(&mut subtable, 16 + 8 * segment_count);
+    push_u16(&mut subtable, 0);
+    push_u16(&mut subtable, 2 * segment_count);
+    push_u16(&mut subtable, search_range);
+    push_u16(&mut subtable, segment_count.ilog2() as u16);
+    push_u16(&mut subtable, 2 * segment_count - search_range);
+    for (code, _) in &segments {
+        push_u16(&mut subtable, *code);
+    }
+    push_u16(&mut subtable, 0);
+    for (code, _) in &segments {
+        push_u16(&mut subtable, *code);
+    }
+    for (_, delta) in &segments {
+        push_i16(&mut subtable, *delta);
+    }
+    for _ in &segments {
+        push_u16(&mut subtable, 0);
+    }
+
+    let mut table = vec![];
+    push_u16(&mut table, 0);
+    push_u16(&mut table, 1);
+    push_u16(&mut table, 3);
+    push_u16(&mut table, 1);
+    push_u32(&mut table, 12);
+    table.extend(subtable);
+
+    table
+}
+
+/// A kern table with the single pair `AV`.
+fn kern() -> Vec<u8> {
+    let mut table = vec![];
+    push_u16(&mut table, 0);
+    push_u16(&mut table, 1);
+    push_u16(&mut table, 0);
+    push_u16(&mut table, 6 + 8 + 6);
+    // Horizontal kerning values in format 0
+    push_u16(&mut table, 1);
+    push_u16(&mut table, 1);
+    push_u16(&mut table, 6);
+    push_u16(&mut table, 0);
+    push_u16(&mut table, 0);
+    push_u16(&mut table, 1);
+    push_u16(&mut table, 3);
+    push_i16(&mut table, AV_KERNING);
+
+    table
+}
+
+/// The bytes of the test font.
+pub fn test_font_bytes() -> Vec<u8> {
+    let glyph_count = GLYPHS.len() as u16 + 1;
+    let (glyf, loca) = glyf_and_loca();
+
+    // Tables must be sorted by tag
+    let tables: [(&[u8; 4], Vec<u8>); 8] = [
+        (b"cmap", cmap()),
+        (b"glyf", glyf),
+        (b"head", head()),
+        (b"hhea", hhea(glyph_count)),
+        (b"hmtx", hmtx()),
+        (b"kern", kern()),
+        (b"loca", loca),
+        (b"maxp", maxp(glyph_count)),
+    ];
+
+    let mut font = vec![];
+    push_u32(&mut font, 0x0001_0000);
+    push_u16(&mut font, tables.len() as u16);
+    push_u16(&mut font, 128);
+    push_u16(&mut font, 3);
+    push_u16(&mut font, 0);
+
+    let mut offset = 12 + 16 * tables.len();
+    for (tag, table) in &tables {
+        font.extend_from_slice(*tag);
+        push_u32(&mut font, 0);
+        push_u32(&mut font, offset as u32);
+        push_u32(&mut font, table.len() as u32);
+
+        offset += table.len().next_multiple_of(4);
+    }
+
+    for (_, table) in &tables {
+        font.extend_from_slice(table);
+        font.resize(font.len().next_multiple_of(4), 0);
+    }
+
+    font
+}
@@ -0,0 +1,370 @@
+use super::{cursor::view_scale, CanvasAction, Cursor, Tool};
+use crate::{
+    canvas::CanvasView,
+    primitives::{
+        dimensions::Dimensions, position::CanvasPosition, position::PixelPosition, rect::CanvasRect,
+    },
+    raster::{Pixel, RasterLayerAction},
+};
+
+/// The positions of round dabs along a stroke, spaced closely enough that
+/// they overlap into a continuous line.
+#[derive(Debug, Clone, Default)]
+struct Stroke {
+    last_position: Option<CanvasPosition>,
+}
+
+impl Stroke {
+    fn begin(&mut self, position: CanvasPosition) -> Vec<CanvasPosition> {
+        self.last_position = Some(position);
+
+        vec![position]
+    }
+
+    fn extend(&mut self, position: CanvasPosition, spacing: f32) -> Vec<CanvasPosition> {
+        let last_position = match self.last_position {
+            Some(last_position) => last_position,
+            None => return vec![],
+        };
+
+        let dx = (position.0 - last_position.0) as f32;
+        let dy = (position.1 - last_position.1) as f32;
+        let steps = ((dx.hypot(dy) / spacing).floor() as i32).max(0);
+
+        if steps == 0 {
+            return vec![];
+        }
+
+        let step_fraction = spacing / dx.hypot(dy);
+        let dabs: Vec<CanvasPosition> = (1..=steps)
+            .map(|step| {
+                let t = step as f32 * step_fraction;
+                (
+                    last_position.0 + (dx * t).round() as i32,
+                    last_position.1 + (dy * t).round() as i32,
+                )
+                    .into()
+            })
+            .collect();
+
+        self.last_position = dabs.last().copied();
+
+        dabs
+    }
+
+    fn is_active(&self) -> bool {
+        self.last_position.is_some()
+    }
+
+    fn end(&mut self) {
+        self.last_position = None;
+    }
+}
+
+/// The actions painting the dabs at the start of a stroke, grouped so that the
+/// whole stroke is undone at once.
+fn begin_stroke_actions(dab_actions: Vec<CanvasAction>) -> Vec<CanvasAction> {
+    std::iter::once(CanvasAction::BeginHistoryGroup)
+        .chain(dab_actions)
+        .collect()
+}
+
+fn dab_rect(center: CanvasPosition, diameter: u32, pressure: f32) -> CanvasRect {
+    let diameter = ((diameter as f32 * pressure.clamp(0.0, 1.0)).round() as usize).max(1);
+
+    CanvasRect {
+        top_left: (
+            center.0 - diameter as i32 / 2,
+            center.1 - diameter as i32 / 2,
+        )
+            .into(),
+        dimensions: Dimensions {
+            width: diameter,
+            height: diameter,
+        },
+    }
+}
+
+/// Paints round dabs along the pointer's path, with pressure scaling the size
+/// of each dab.
+#[derive(Debug, Clone)]
+pub struct BrushTool {
+    pub layer_num: usize,
+    pub color: Pixel,
+    pub diameter: u32,
+    stroke: Stroke,
+}
+
+impl BrushTool {
+    pub fn new(layer_num: usize, color: Pixel, diameter: u32) -> BrushTool {
+        BrushTool {
+            layer_num,
+            color,
+            diameter,
+            stroke: Stroke::default(),
+        }
+    }
+
+    fn dab_actions(&self, dabs: Vec<CanvasPosition>, pressure: f32) -> Vec<CanvasAction> {
+        dabs.into_iter()
+            .map(|center| CanvasAction::RasterLayer {
+                layer_num: self.layer_num,
+                action: RasterLayerAction::fill_oval(
+                    dab_rect(center, self.diameter, pressure),
+                    self.color,
+                ),
+            })
+            .collect()
+    }
+
+    fn spacing(&self) -> f32 {
+        (self.diameter as f32 / 4.0).max(1.0)
+    }
+}
+
+impl Tool for BrushTool {
+    fn on_pointer_down(
+        &mut self,
+        view: &CanvasView,
+        position: PixelPosition,
+        pressure: f32,
+    ) -> Vec<CanvasAction> {
+        let dabs = self.stroke.begin(view.transform_view_to_canvas(position));
+
+        begin_stroke_actions(self.dab_actions(dabs, pressure))
+    }
+
+    fn on_pointer_move(
+        &mut self,
+        view: &CanvasView,
+        position: PixelPosition,
+        pressure: f32,
+    ) -> Vec<CanvasAction> {
+        let dabs = self
+            .stroke
+            .extend(view.transform_view_to_canvas(position), self.spacing());
+
+        self.dab_actions(dabs, pressure)
+    }
+
+    fn on_pointer_up(
+        &mut self,
+        view: &CanvasView,
+        position: PixelPosition,
+        pressure: f32,
+    ) -> Vec<CanvasAction> {
+        if !self.stroke.is_active() {
+            return vec![];
+        }
+
+        let mut actions = self.on_pointer_move(view, position, pressure);
+        actions.push(CanvasAction::EndHistoryGroup);
+        self.stroke.end();
+
+        actions
+    }
+
+    fn cursor(&self, view: &CanvasView) -> Cursor {
+        Cursor::brush_outline(self.diameter as f32 * view_scale(view))
+    }
+}
+
+/// Removes paint along the pointer's path by erasing round dabs to
+/// transparency, with pressure scaling the size of each dab.
+#[derive(Debug, Clone)]
+pub struct EraserTool {
+    pub layer_num: usize,
+    pub diameter: u32,
+    /// How much of the alpha each dab removes, from 0 to 255.
+    pub strength: u8,
+    stroke: Stroke,
+}
+
+impl EraserTool {
+    /// Creates an eraser that erases completely.
+    pub fn new(layer_num: usize, diameter: u32) -> EraserTool {
+        EraserTool::with_strength(layer_num, diameter, 255)
+    }
+
+    pub fn with_strength(layer_num: usize, diameter: u32, strength: u8) -> EraserTool {
+        EraserTool {
+            layer_num,
+            diameter,
+            strength,
+            stroke: Stroke::default(),
+        }
+    }
+
+    pub fn diameter(&self) -> u32 {
+        self.diameter
+    }
+
+    pub fn set_diameter(&mut self, diameter: u32) {
+        self.diameter = diameter;
+    }
+
+    fn dab_actions(&self, dabs: Vec<CanvasPosition>, pressure: f32) -> Vec<CanvasAction> {
+        dabs.into_iter()
+            .map(|center| CanvasAction::RasterLayer {
+                layer_num: self.layer_num,
+                action: RasterLayerAction::erase_oval(
+                    dab_rect(center, self.diameter, pressure),
+                    self.strength,
+                ),
+            })
+            .collect()
+    }
+
+    fn spacing(&self) -> f32 {
+        (self.diameter as f32 / 4.0).max(1.0)
+    }
+}
+
+impl Tool for EraserTool {
+    fn on_pointer_down(
+        &mut self,
+        view: &CanvasView,
+        position: PixelPosition,
+        pressure: f32,
+    ) -> Vec<CanvasAction> {
+        let dabs = self.stroke.begin(view.transform_view_to_canvas(position));
+
+        begin_stroke_actions(self.dab_actions(dabs, pressure))
+    }
+
+    fn on_pointer_move(
+        &mut self,
+        view: &CanvasView,
+        position: PixelPosition,
+        pressure: f32,
+    ) -> Vec<CanvasAction> {
+        let dabs = self
+            .stroke
+            .extend(view.transform_view_to_canvas(position), self.spacing());
+
+        self.dab_actions(dabs, pressure)
+    }
+
+    fn on_pointer_up(
+        &mut self,
+        view: &CanvasView,
+        position: PixelPosition,
+        pressure: f32,
+    ) -> Vec<CanvasAction> {
+        if !self.stroke.is_active() {
+            return vec![];
+        }
+
+        let mut actions = self.on_pointer_move(view, position, pressure);
+        actions.push(CanvasAction::EndHistoryGroup);
+        self.stroke.end();
+
+        actions
+    }
+
+    fn cursor(&self, view: &CanvasView) -> Cursor {
+        Cursor::brush_outline(self.diameter as f32 * view_scale(view))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raster::pixels::colors;
+
+    #[test]
+    fn brush_stroke_dabs_are_spaced() {
+        let view = CanvasView::new(100, 100);
+        let mut brush = BrushTool::new(0, colors::red(), 8);
+
+        assert_eq!(
+            brush.on_pointer_move(&view, (10, 10).into(), 1.0),
+            vec![],
+            "moving without pressing shouldn't paint"
+        );
+
+        let down = brush.on_pointer_down(&view, (10, 10).into(), 1.0);
+        assert_eq!(
+            down,
+            vec![
+                CanvasAction::BeginHistoryGroup,
+                CanvasAction::RasterLayer {
+                    layer_num: 0,
+                    action: RasterLayerAction::fill_oval(
+                        CanvasRect {
+                            top_left: (6, 6).into(),
+                            dimensions: Dimensions {
+                                width: 8,
+                                height: 8
+                            }
+                        },
+                        colors::red()
+                    )
+                }
+            ]
+        );
+
+        // A diameter of 8 places a dab every 2 pixels
+        let moved = brush.on_pointer_move(&view, (20, 10).into(), 1.0);
+        assert_eq!(moved.len(), 5);
+
+        let up = brush.on_pointer_up(&view, (21, 10).into(), 1.0);
+        assert_eq!(up, vec![CanvasAction::EndHistoryGroup]);
+        assert_eq!(brush.on_pointer_up(&view, (21, 10).into(), 1.0), vec![]);
+        assert_eq!(brush.on_pointer_move(&view, (40, 10).into(), 1.0), vec![]);
+    }
+
+    #[test]
+    fn eraser_erases_layer_to_transparency_in_one_undo_step() {
+        use crate::{
+            assert_raster_eq,
+            canvas::{Canvas, HistoryAction, Layer},
+            raster::chunks::BoxRasterChunk,
+        };
+
+        let view = CanvasView::new(32, 32);
+        let mut canvas = Canvas::default();
+        canvas.add_layer(crate::raster::RasterLayer::new(16).into());
+
+        let rect = CanvasRect::at_origin(Dimensions {
+            width: 32,
+            height: 32,
+        });
+        canvas.perform_raster_action(0, RasterLayerAction::fill_rect(rect, colors::red()));
+
+        let mut eraser = EraserTool::new(0, 8);
+        let mut actions = eraser.on_pointer_down(&view, (8, 8).into(), 1.0);
+        actions.extend(eraser.on_pointer_up(&view, (8, 24).into(), 1.0));
+
+        for action in actions {
+            match action {
+                CanvasAction::RasterLayer { layer_num, action } => {
+                    assert!(matches!(action, RasterLayerAction::EraseOval(_, 255)));
+                    canvas.perform_raster_action(layer_num, action);
+                }
+                CanvasAction::BeginHistoryGroup => canvas.history_mut().begin_group(),
+                CanvasAction::EndHistoryGroup => canvas.history_mut().end_group(),
+                CanvasAction::SetView(_) => {}
+            }
+        }
+
+        let raster = canvas
+            .raster_layer(0)
+            .unwrap()
+            .rasterize_canvas_rect_shared(rect);
+        assert_eq!(raster.pixels()[16 * 32 + 8].as_rgba().3, 0);
+        assert_eq!(raster.pixels()[16 * 32 + 20], colors::red());
+
+        canvas.undo();
+        let raster = canvas
+            .raster_layer(0)
+            .unwrap()
+            .rasterize_canvas_rect_shared(rect);
+        let expected = BoxRasterChunk::new_fill(colors::red(), 32, 32);
+        assert_raster_eq!(raster, expected);
+        assert!(matches!(
+            canvas.history().undo_action(),
+            Some(HistoryAction::Raster(RasterLayerAction::FillRect(..)))
+        ));
+    }
+}
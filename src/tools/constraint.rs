@@ -0,0 +1,154 @@
+//! Constraints applied to drag gestures by held keyboard modifiers.
+
+use crate::primitives::{position::CanvasPosition, rect::CanvasRect};
+
+/// The keyboard modifiers held during a gesture.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    /// Keeps shapes square and snaps lines to multiples of 45°.
+    pub shift: bool,
+    /// Draws shapes and lines outwards from where the drag started.
+    pub alt: bool,
+}
+
+fn sign(n: i32) -> i32 {
+    if n < 0 {
+        -1
+    } else {
+        1
+    }
+}
+
+/// The rect for a shape dragged from `anchor` to `current`. With shift the rect
+/// is square, sized by the larger side of the drag, and with alt `anchor` is
+/// its center rather than a corner.
+pub fn constrain_rect(
+    anchor: CanvasPosition,
+    current: CanvasPosition,
+    modifiers: Modifiers,
+) -> CanvasRect {
+    let (mut dx, mut dy) = (current.0 - anchor.0, current.1 - anchor.1);
+
+    if modifiers.shift {
+        let side = dx.abs().max(dy.abs());
+        dx = side * sign(dx);
+        dy = side * sign(dy);
+    }
+
+    let corner = anchor.translate((dx, dy).into());
+    if modifiers.alt {
+        CanvasRect::from_points(anchor.translate((-dx, -dy).into()), corner)
+    } else {
+        CanvasRect::from_points(anchor, corner)
+    }
+}
+
+/// The ends of a line dragged from `anchor` to `current`. With shift the line
+/// is snapped to the nearest multiple of 45°, and with alt `anchor` is its
+/// middle rather than its start.
+pub fn constrain_line(
+    anchor: CanvasPosition,
+    current: CanvasPosition,
+    modifiers: Modifiers,
+) -> (CanvasPosition, CanvasPosition) {
+    let (mut dx, mut dy) = (current.0 - anchor.0, current.1 - anchor.1);
+
+    if modifiers.shift {
+        let octant = (dy as f32).atan2(dx as f32) / std::f32::consts::FRAC_PI_4;
+        let octant = octant.round() as i32;
+
+        (dx, dy) = match octant.rem_euclid(4) {
+            0 => (dx, 0),
+            2 => (0, dy),
+            _ => {
+                let length = (dx.abs() + dy.abs()) / 2;
+                (length * sign(dx), length * sign(dy))
+            }
+        };
+    }
+
+    let end = anchor.translate((dx, dy).into());
+    if modifiers.alt {
+        (anchor.translate((-dx, -dy).into()), end)
+    } else {
+        (anchor, end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::dimensions::Dimensions;
+
+    #[test]
+    fn rect_constraints() {
+        let anchor = (10, 10).into();
+        let current = (4, 14).into();
+
+        assert_eq!(
+            constrain_rect(anchor, current, Modifiers::default()),
+            CanvasRect::from_points((4, 10).into(), (10, 14).into())
+        );
+        assert_eq!(
+            constrain_rect(
+                anchor,
+                current,
+                Modifiers {
+                    shift: true,
+                    alt: false
+                }
+            ),
+            CanvasRect::from_points((4, 10).into(), (10, 16).into())
+        );
+        assert_eq!(
+            constrain_rect(
+                anchor,
+                current,
+                Modifiers {
+                    shift: true,
+                    alt: true
+                }
+            ),
+            CanvasRect {
+                top_left: (4, 4).into(),
+                dimensions: Dimensions {
+                    width: 13,
+                    height: 13
+                }
+            }
+        );
+    }
+
+    #[test]
+    fn line_snapping() {
+        let shift = Modifiers {
+            shift: true,
+            alt: false,
+        };
+        let anchor: CanvasPosition = (0, 0).into();
+
+        assert_eq!(
+            constrain_line(anchor, (10, 2).into(), shift),
+            (anchor, (10, 0).into())
+        );
+        assert_eq!(
+            constrain_line(anchor, (-1, -10).into(), shift),
+            (anchor, (0, -10).into())
+        );
+        assert_eq!(
+            constrain_line(anchor, (-9, 11).into(), shift),
+            (anchor, (-10, 10).into())
+        );
+        assert_eq!(
+            constrain_line(
+                anchor,
+                (3, 4).into(),
+                Modifiers {
+                    shift: false,
+                    alt: true
+                }
+            ),
+            ((-3, -4).into(), (3, 4).into())
+        );
+    }
+}
@@ -0,0 +1,154 @@
+//! Cursor images for tools, drawn in black with a white outline so they stay
+//! visible on any content.
+
+use crate::{
+    canvas::CanvasView,
+    primitives::position::PixelPosition,
+    raster::{chunks::BoxRasterChunk, pixels::colors, Pixel},
+};
+
+/// The largest cursor browsers reliably accept for CSS cursors. Larger cursors
+/// have to be drawn as an overlay.
+pub const MAX_CSS_CURSOR_SIZE: usize = 128;
+
+/// The smallest brush outline that is drawn, since smaller outlines are
+/// hard to see. Brushes smaller than this show a crosshair instead.
+const MIN_OUTLINE_DIAMETER: f32 = 4.0;
+
+const CROSSHAIR_SIZE: usize = 15;
+
+const FILL_BUCKET: [&str; 16] = [
+    "      B         ",
+    "     BwB        ",
+    "    BwwwB       ",
+    "   BwwwwwB      ",
+    "  BwwwwwwwB     ",
+    " BwwwwwwwwwB    ",
+    "BwwwwwwwwwwwB   ",
+    " BwwwwwwwwwB B  ",
+    "  BwwwwwwwB BwB ",
+    "   BwwwwwB  BwB ",
+    "    BwwwB   BwB ",
+    "     BwB     B  ",
+    "      B         ",
+    "                ",
+    "                ",
+    "                ",
+];
+
+/// A cursor image and the position within it that points at the cursor
+/// position, in view pixels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cursor {
+    pub raster: BoxRasterChunk,
+    pub hotspot: PixelPosition,
+}
+
+impl Cursor {
+    /// A crosshair centered on the cursor position.
+    pub fn crosshair() -> Cursor {
+        let center = CROSSHAIR_SIZE / 2;
+
+        Cursor::from_fn(
+            CROSSHAIR_SIZE,
+            CROSSHAIR_SIZE,
+            (center, center),
+            |x, y| match (x.abs_diff(center), y.abs_diff(center)) {
+                (0, _) | (_, 0) => colors::black(),
+                (1, _) | (_, 1) => colors::white(),
+                _ => colors::transparent(),
+            },
+        )
+    }
+
+    /// A circle outlining a brush, given the brush diameter in view pixels.
+    pub fn brush_outline(diameter: f32) -> Cursor {
+        if diameter.is_nan() || diameter < MIN_OUTLINE_DIAMETER {
+            return Cursor::crosshair();
+        }
+
+        let radius = diameter / 2.0;
+        // Room for the white outline on either side of the circle
+        let size = diameter.ceil() as usize + 4;
+        let center = size as f32 / 2.0;
+
+        Cursor::from_fn(size, size, (size / 2, size / 2), |x, y| {
+            let dx = x as f32 + 0.5 - center;
+            let dy = y as f32 + 0.5 - center;
+            let distance_from_edge = (dx.hypot(dy) - radius).abs();
+
+            if distance_from_edge <= 0.5 {
+                colors::black()
+            } else if distance_from_edge <= 1.5 {
+                colors::white()
+            } else {
+                colors::transparent()
+            }
+        })
+    }
+
+    /// A paint bucket pointing with the tip of its drip.
+    pub fn fill_bucket() -> Cursor {
+        Cursor::from_fn(16, 16, (13, 11), |x, y| {
+            match FILL_BUCKET[y].as_bytes()[x] {
+                b'B' => colors::black(),
+                b'w' => colors::white(),
+                _ => colors::transparent(),
+            }
+        })
+    }
+
+    fn from_fn<F>(width: usize, height: usize, hotspot: (usize, usize), f: F) -> Cursor
+    where
+        F: Fn(usize, usize) -> Pixel,
+    {
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| f(x, y))
+            .collect();
+
+        Cursor {
+            raster: BoxRasterChunk::from_vec(pixels, width, height)
+                .expect("cursor pixels should match the cursor dimensions"),
+            hotspot: hotspot.into(),
+        }
+    }
+}
+
+/// The number of view pixels per canvas pixel in a view.
+pub(super) fn view_scale(view: &CanvasView) -> f32 {
+    view.view_dimensions
+        .relative_scale(view.canvas_dimensions)
+        .width_factor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        primitives::dimensions::Dimensions,
+        tools::{BrushTool, Tool},
+    };
+
+    #[test]
+    fn brush_cursor_follows_size_and_zoom() {
+        let mut brush = BrushTool::new(0, colors::red(), 10);
+        let mut view = CanvasView::new(100, 100);
+
+        let cursor = brush.cursor(&view);
+        assert_eq!(cursor, Cursor::brush_outline(10.0));
+        assert_eq!(cursor.raster.dimensions().width, 14);
+        assert_eq!(cursor.hotspot, (7, 7).into());
+        assert_eq!(cursor.raster.pixels()[7 * 14 + 2], colors::black());
+        assert_eq!(cursor.raster.pixels()[7 * 14 + 7], colors::transparent());
+
+        view.canvas_dimensions = Dimensions {
+            width: 50,
+            height: 50,
+        };
+        assert_eq!(brush.cursor(&view), Cursor::brush_outline(20.0));
+
+        brush.diameter = 1;
+        assert_eq!(brush.cursor(&view), Cursor::crosshair());
+    }
+}
@@ -0,0 +1,67 @@
+use super::{CanvasAction, Cursor, Tool};
+use crate::{
+    canvas::CanvasView,
+    primitives::position::PixelPosition,
+    raster::{FloodFill, Pixel, RasterLayerAction},
+};
+
+/// Flood fills the area under the pointer, limited to the part of the canvas
+/// that is in view.
+#[derive(Debug, Clone)]
+pub struct FillTool {
+    pub layer_num: usize,
+    pub color: Pixel,
+    /// See `FloodFill::tolerance`.
+    pub tolerance: u8,
+}
+
+impl FillTool {
+    pub fn new(layer_num: usize, color: Pixel, tolerance: u8) -> FillTool {
+        FillTool {
+            layer_num,
+            color,
+            tolerance,
+        }
+    }
+}
+
+impl Tool for FillTool {
+    fn on_pointer_down(
+        &mut self,
+        view: &CanvasView,
+        position: PixelPosition,
+        _pressure: f32,
+    ) -> Vec<CanvasAction> {
+        vec![CanvasAction::RasterLayer {
+            layer_num: self.layer_num,
+            action: RasterLayerAction::flood_fill(FloodFill {
+                position: view.transform_view_to_canvas(position),
+                color: self.color,
+                tolerance: self.tolerance,
+                bounds: view.canvas_rect(),
+            }),
+        }]
+    }
+
+    fn on_pointer_move(
+        &mut self,
+        _view: &CanvasView,
+        _position: PixelPosition,
+        _pressure: f32,
+    ) -> Vec<CanvasAction> {
+        vec![]
+    }
+
+    fn on_pointer_up(
+        &mut self,
+        _view: &CanvasView,
+        _position: PixelPosition,
+        _pressure: f32,
+    ) -> Vec<CanvasAction> {
+        vec![]
+    }
+
+    fn cursor(&self, _view: &CanvasView) -> Cursor {
+        Cursor::fill_bucket()
+    }
+}
@@ -0,0 +1,73 @@
+//! Translation of pointer input into canvas actions.
+//!
+//! Frontends forward pointer events to the active `Tool` and apply the
+//! `CanvasAction`s it returns, so the state machines behind tools like brushes
+//! and shape dragging don't need to be reimplemented by every frontend.
+//! Pointer positions are in view space, and pressure is in `[0, 1]`, with
+//! devices that don't report pressure using `1.0`. Wrapping a tool in
+//! `PressureCalibrated` maps pressure through a `PressureCurve` first.
+
+mod brush;
+mod constraint;
+mod cursor;
+mod fill;
+mod pan_zoom;
+mod pressure;
+mod shape;
+
+pub use brush::{BrushTool, EraserTool};
+pub use constraint::{constrain_line, constrain_rect, Modifiers};
+pub use cursor::{Cursor, MAX_CSS_CURSOR_SIZE};
+pub use fill::FillTool;
+pub use pan_zoom::PanZoomTool;
+pub use pressure::{PressureCalibrated, PressureCurve, PressureCurveError};
+pub use shape::{ShapeKind, ShapeTool};
+
+use crate::{canvas::CanvasView, primitives::position::PixelPosition, raster::RasterLayerAction};
+
+/// Something a tool wants done in response to input.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CanvasAction {
+    /// An action to perform with `Canvas::perform_raster_action`.
+    RasterLayer {
+        layer_num: usize,
+        action: RasterLayerAction,
+    },
+    /// A view the frontend should display from now on.
+    SetView(CanvasView),
+    /// Starts grouping the actions that follow into one undo step, with
+    /// `History::begin_group`, such as for the dabs of a stroke.
+    BeginHistoryGroup,
+    /// Ends the group started by the last `CanvasAction::BeginHistoryGroup`,
+    /// with `History::end_group`.
+    EndHistoryGroup,
+}
+
+/// A tool that converts pointer input into canvas actions.
+pub trait Tool {
+    fn on_pointer_down(
+        &mut self,
+        view: &CanvasView,
+        position: PixelPosition,
+        pressure: f32,
+    ) -> Vec<CanvasAction>;
+    fn on_pointer_move(
+        &mut self,
+        view: &CanvasView,
+        position: PixelPosition,
+        pressure: f32,
+    ) -> Vec<CanvasAction>;
+    fn on_pointer_up(
+        &mut self,
+        view: &CanvasView,
+        position: PixelPosition,
+        pressure: f32,
+    ) -> Vec<CanvasAction>;
+    /// Updates the keyboard modifiers held, which can change during a gesture.
+    fn set_modifiers(&mut self, _modifiers: Modifiers) {}
+    /// The cursor to show for the tool over a view. The cursor can change when
+    /// the tool's settings or the view's zoom change.
+    fn cursor(&self, _view: &CanvasView) -> Cursor {
+        Cursor::crosshair()
+    }
+}
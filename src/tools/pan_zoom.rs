@@ -0,0 +1,128 @@
+use super::{CanvasAction, Tool};
+use crate::{
+    canvas::CanvasView,
+    primitives::{dimensions::Scale, position::PixelPosition},
+};
+
+/// Moves the view by dragging, and zooms it around a point with `zoom_at`.
+#[derive(Debug, Clone, Default)]
+pub struct PanZoomTool {
+    drag_start: Option<(CanvasView, PixelPosition)>,
+}
+
+impl PanZoomTool {
+    pub fn new() -> PanZoomTool {
+        PanZoomTool::default()
+    }
+
+    /// Zooms the view by `factor`, keeping the canvas position under `position`
+    /// in place. Factors above 1 zoom in. Zooms that would make the view too
+    /// small or too large are ignored.
+    pub fn zoom_at(
+        &mut self,
+        view: &CanvasView,
+        position: PixelPosition,
+        factor: f32,
+    ) -> Vec<CanvasAction> {
+        let canvas_dimensions = match view.canvas_dimensions.try_scale(Scale {
+            width_factor: 1.0 / factor,
+            height_factor: 1.0 / factor,
+        }) {
+            Ok(canvas_dimensions) => canvas_dimensions,
+            Err(_) => return vec![],
+        };
+
+        let anchor = view.transform_view_to_canvas(position);
+        let offset = canvas_dimensions.transform_point(position, view.view_dimensions);
+
+        vec![CanvasAction::SetView(CanvasView {
+            top_left: (anchor.0 - offset.0 as i32, anchor.1 - offset.1 as i32).into(),
+            canvas_dimensions,
+            ..*view
+        })]
+    }
+}
+
+impl Tool for PanZoomTool {
+    fn on_pointer_down(
+        &mut self,
+        view: &CanvasView,
+        position: PixelPosition,
+        _pressure: f32,
+    ) -> Vec<CanvasAction> {
+        self.drag_start = Some((*view, position));
+
+        vec![]
+    }
+
+    fn on_pointer_move(
+        &mut self,
+        _view: &CanvasView,
+        position: PixelPosition,
+        _pressure: f32,
+    ) -> Vec<CanvasAction> {
+        let (start_view, start_position) = match self.drag_start {
+            Some(drag_start) => drag_start,
+            None => return vec![],
+        };
+
+        // Measured against the view at the start of the drag, since the
+        // current view has already been moved by the drag
+        let start = start_view.transform_view_to_canvas(start_position);
+        let current = start_view.transform_view_to_canvas(position);
+
+        let mut view = start_view;
+        view.translate((start.0 - current.0, start.1 - current.1).into());
+
+        vec![CanvasAction::SetView(view)]
+    }
+
+    fn on_pointer_up(
+        &mut self,
+        view: &CanvasView,
+        position: PixelPosition,
+        pressure: f32,
+    ) -> Vec<CanvasAction> {
+        let actions = self.on_pointer_move(view, position, pressure);
+        self.drag_start = None;
+
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::dimensions::Dimensions;
+
+    #[test]
+    fn dragging_pans_and_zooming_keeps_anchor() {
+        let view = CanvasView::new(100, 100);
+        let mut tool = PanZoomTool::new();
+
+        tool.on_pointer_down(&view, (50, 50).into(), 1.0);
+        let actions = tool.on_pointer_up(&view, (60, 45).into(), 1.0);
+
+        let mut expected_view = view;
+        expected_view.translate((-10, 5).into());
+        assert_eq!(actions, vec![CanvasAction::SetView(expected_view)]);
+
+        let actions = tool.zoom_at(&view, (20, 40).into(), 2.0);
+        let zoomed_view = match actions.as_slice() {
+            [CanvasAction::SetView(zoomed_view)] => *zoomed_view,
+            _ => panic!("zooming should set the view"),
+        };
+
+        assert_eq!(
+            zoomed_view.canvas_dimensions,
+            Dimensions {
+                width: 50,
+                height: 50
+            }
+        );
+        assert_eq!(
+            zoomed_view.transform_view_to_canvas((20, 40).into()),
+            view.transform_view_to_canvas((20, 40).into())
+        );
+    }
+}
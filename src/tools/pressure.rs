@@ -0,0 +1,256 @@
+//! Calibration of stylus pressure before it reaches tools.
+
+use thiserror::Error;
+
+use super::{CanvasAction, Cursor, Modifiers, Tool};
+use crate::{canvas::CanvasView, primitives::position::PixelPosition};
+
+#[derive(Error, Debug, Clone, Copy, PartialEq)]
+pub enum PressureCurveError {
+    #[error("a pressure curve needs at least 2 control points, {0} given")]
+    TooFewPoints(usize),
+    #[error("control point ({0}, {1}) is outside of [0, 1]")]
+    PointOutOfRange(f32, f32),
+    #[error("control point inputs must be strictly increasing")]
+    InputsNotIncreasing,
+}
+
+/// A transfer curve mapping the pressure reported by a device to the pressure
+/// tools receive. The curve passes through control points and is interpolated
+/// between them with a monotone cubic spline, so it only rises or falls where
+/// the control points do and never overshoots them.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "Vec<(f32, f32)>", into = "Vec<(f32, f32)>")
+)]
+pub struct PressureCurve {
+    points: Vec<(f32, f32)>,
+    /// The slope of the curve at each control point.
+    tangents: Vec<f32>,
+}
+
+impl PressureCurve {
+    /// Creates a curve through `(input, output)` control points, which must be
+    /// within `[0, 1]` and have strictly increasing inputs. Inputs before the
+    /// first point or after the last map to the output of that point.
+    pub fn new(points: Vec<(f32, f32)>) -> Result<PressureCurve, PressureCurveError> {
+        if points.len() < 2 {
+            return Err(PressureCurveError::TooFewPoints(points.len()));
+        }
+
+        if let Some((x, y)) = points
+            .iter()
+            .find(|(x, y)| !(0.0..=1.0).contains(x) || !(0.0..=1.0).contains(y))
+        {
+            return Err(PressureCurveError::PointOutOfRange(*x, *y));
+        }
+
+        if points.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+            return Err(PressureCurveError::InputsNotIncreasing);
+        }
+
+        let tangents = monotone_tangents(&points);
+
+        Ok(PressureCurve { points, tangents })
+    }
+
+    /// The curve that leaves pressure unchanged.
+    pub fn linear() -> PressureCurve {
+        PressureCurve {
+            points: vec![(0.0, 0.0), (1.0, 1.0)],
+            tangents: vec![1.0, 1.0],
+        }
+    }
+
+    pub fn points(&self) -> &[(f32, f32)] {
+        &self.points
+    }
+
+    /// Maps a pressure reported by a device through the curve. A pressure of
+    /// NaN is treated as no pressure.
+    pub fn apply(&self, pressure: f32) -> f32 {
+        let (first, last) = (self.points[0], self.points[self.points.len() - 1]);
+        if pressure.is_nan() || pressure <= first.0 {
+            return first.1;
+        }
+        if pressure >= last.0 {
+            return last.1;
+        }
+
+        let segment = self
+            .points
+            .windows(2)
+            .position(|pair| pressure < pair[1].0)
+            .expect("pressure is checked to be before the last control point");
+
+        let ((x0, y0), (x1, y1)) = (self.points[segment], self.points[segment + 1]);
+        let (m0, m1) = (self.tangents[segment], self.tangents[segment + 1]);
+
+        let h = x1 - x0;
+        let t = (pressure - x0) / h;
+        let (t2, t3) = (t * t, t * t * t);
+
+        let value = (2.0 * t3 - 3.0 * t2 + 1.0) * y0
+            + (t3 - 2.0 * t2 + t) * h * m0
+            + (-2.0 * t3 + 3.0 * t2) * y1
+            + (t3 - t2) * h * m1;
+
+        value.clamp(0.0, 1.0)
+    }
+}
+
+impl Default for PressureCurve {
+    fn default() -> Self {
+        PressureCurve::linear()
+    }
+}
+
+impl TryFrom<Vec<(f32, f32)>> for PressureCurve {
+    type Error = PressureCurveError;
+
+    fn try_from(points: Vec<(f32, f32)>) -> Result<Self, Self::Error> {
+        PressureCurve::new(points)
+    }
+}
+
+impl From<PressureCurve> for Vec<(f32, f32)> {
+    fn from(curve: PressureCurve) -> Self {
+        curve.points
+    }
+}
+
+/// Tangents at each control point that keep the spline monotone between
+/// points, using the method of Fritsch and Carlson.
+fn monotone_tangents(points: &[(f32, f32)]) -> Vec<f32> {
+    let secants: Vec<f32> = points
+        .windows(2)
+        .map(|pair| (pair[1].1 - pair[0].1) / (pair[1].0 - pair[0].0))
+        .collect();
+
+    let mut tangents = Vec::with_capacity(points.len());
+    tangents.push(secants[0]);
+    for pair in secants.windows(2) {
+        if pair[0] * pair[1] <= 0.0 {
+            tangents.push(0.0);
+        } else {
+            tangents.push((pair[0] + pair[1]) / 2.0);
+        }
+    }
+    tangents.push(secants[secants.len() - 1]);
+
+    for (segment, secant) in secants.iter().enumerate() {
+        if *secant == 0.0 {
+            tangents[segment] = 0.0;
+            tangents[segment + 1] = 0.0;
+            continue;
+        }
+
+        let a = tangents[segment] / secant;
+        let b = tangents[segment + 1] / secant;
+        let magnitude = a.hypot(b);
+
+        if magnitude > 3.0 {
+            let scale = 3.0 / magnitude;
+            tangents[segment] = scale * a * secant;
+            tangents[segment + 1] = scale * b * secant;
+        }
+    }
+
+    tangents
+}
+
+/// Wraps a tool so that pressure is mapped through a curve before the tool
+/// receives it.
+#[derive(Debug, Clone)]
+pub struct PressureCalibrated<T: Tool> {
+    pub tool: T,
+    pub curve: PressureCurve,
+}
+
+impl<T: Tool> PressureCalibrated<T> {
+    pub fn new(tool: T, curve: PressureCurve) -> PressureCalibrated<T> {
+        PressureCalibrated { tool, curve }
+    }
+}
+
+impl<T: Tool> Tool for PressureCalibrated<T> {
+    fn on_pointer_down(
+        &mut self,
+        view: &CanvasView,
+        position: PixelPosition,
+        pressure: f32,
+    ) -> Vec<CanvasAction> {
+        self.tool
+            .on_pointer_down(view, position, self.curve.apply(pressure))
+    }
+
+    fn on_pointer_move(
+        &mut self,
+        view: &CanvasView,
+        position: PixelPosition,
+        pressure: f32,
+    ) -> Vec<CanvasAction> {
+        self.tool
+            .on_pointer_move(view, position, self.curve.apply(pressure))
+    }
+
+    fn on_pointer_up(
+        &mut self,
+        view: &CanvasView,
+        position: PixelPosition,
+        pressure: f32,
+    ) -> Vec<CanvasAction> {
+        self.tool
+            .on_pointer_up(view, position, self.curve.apply(pressure))
+    }
+
+    fn set_modifiers(&mut self, modifiers: Modifiers) {
+        self.tool.set_modifiers(modifiers);
+    }
+
+    fn cursor(&self, view: &CanvasView) -> Cursor {
+        self.tool.cursor(view)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pressure_curve_is_monotone_through_points() {
+        let curve =
+            PressureCurve::new(vec![(0.1, 0.0), (0.3, 0.05), (0.5, 0.6), (1.0, 1.0)]).unwrap();
+
+        assert_eq!(curve.apply(0.0), 0.0);
+        assert_eq!(curve.apply(0.1), 0.0);
+        assert!((curve.apply(0.5) - 0.6).abs() < 1e-6);
+        assert_eq!(curve.apply(1.0), 1.0);
+
+        let samples: Vec<f32> = (0..=100).map(|i| curve.apply(i as f32 / 100.0)).collect();
+        assert!(samples.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        assert_eq!(
+            PressureCurve::new(vec![(0.5, 0.5)]),
+            Err(PressureCurveError::TooFewPoints(1))
+        );
+        assert_eq!(
+            PressureCurve::new(vec![(0.5, 0.0), (0.5, 1.0)]),
+            Err(PressureCurveError::InputsNotIncreasing)
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn pressure_curve_round_trips_through_serde() {
+        let curve = PressureCurve::new(vec![(0.0, 0.0), (0.4, 0.7), (1.0, 1.0)]).unwrap();
+
+        let json = serde_json::to_string(&curve).unwrap();
+        assert_eq!(json, "[[0.0,0.0],[0.4,0.7],[1.0,1.0]]");
+        assert_eq!(serde_json::from_str::<PressureCurve>(&json).unwrap(), curve);
+
+        assert!(serde_json::from_str::<PressureCurve>("[[0.0,0.0],[2.0,1.0]]").is_err());
+    }
+}
@@ -0,0 +1,158 @@
+use super::{constrain_line, constrain_rect, CanvasAction, Modifiers, Tool};
+use crate::{
+    canvas::CanvasView,
+    primitives::{
+        position::{CanvasPosition, PixelPosition},
+        rect::CanvasRect,
+    },
+    raster::{Pixel, RasterLayerAction},
+};
+
+/// The shape drawn by a `ShapeTool`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ShapeKind {
+    Rect,
+    Oval,
+    /// A line from where the drag started to where it ended, covering the
+    /// pixels within `radius` of it.
+    Line {
+        radius: u32,
+    },
+}
+
+/// Draws a filled shape spanning the drag from pointer down to pointer up,
+/// constrained by the held modifiers as described by `constrain_rect`, or by
+/// `constrain_line` for lines.
+#[derive(Debug, Clone)]
+pub struct ShapeTool {
+    pub layer_num: usize,
+    pub color: Pixel,
+    pub kind: ShapeKind,
+    anchor: Option<CanvasPosition>,
+    current: Option<CanvasPosition>,
+    modifiers: Modifiers,
+}
+
+impl ShapeTool {
+    pub fn new(layer_num: usize, color: Pixel, kind: ShapeKind) -> ShapeTool {
+        ShapeTool {
+            layer_num,
+            color,
+            kind,
+            anchor: None,
+            current: None,
+            modifiers: Modifiers::default(),
+        }
+    }
+
+    /// The canvas rect the shape would fill if the drag ended now, for
+    /// drawing a preview. `None` when no drag is in progress.
+    pub fn preview_rect(&self) -> Option<CanvasRect> {
+        Some(constrain_rect(self.anchor?, self.current?, self.modifiers))
+    }
+
+    /// The ends of the line that would be drawn if the drag ended now, for
+    /// drawing a preview of a line. `None` when no drag is in progress.
+    pub fn preview_line(&self) -> Option<(CanvasPosition, CanvasPosition)> {
+        Some(constrain_line(self.anchor?, self.current?, self.modifiers))
+    }
+}
+
+impl Tool for ShapeTool {
+    fn on_pointer_down(
+        &mut self,
+        view: &CanvasView,
+        position: PixelPosition,
+        _pressure: f32,
+    ) -> Vec<CanvasAction> {
+        let position = view.transform_view_to_canvas(position);
+        self.anchor = Some(position);
+        self.current = Some(position);
+
+        vec![]
+    }
+
+    fn on_pointer_move(
+        &mut self,
+        view: &CanvasView,
+        position: PixelPosition,
+        _pressure: f32,
+    ) -> Vec<CanvasAction> {
+        if self.anchor.is_some() {
+            self.current = Some(view.transform_view_to_canvas(position));
+        }
+
+        vec![]
+    }
+
+    fn on_pointer_up(
+        &mut self,
+        view: &CanvasView,
+        position: PixelPosition,
+        pressure: f32,
+    ) -> Vec<CanvasAction> {
+        self.on_pointer_move(view, position, pressure);
+
+        let (canvas_rect, (from, to)) = match (self.preview_rect(), self.preview_line()) {
+            (Some(canvas_rect), Some(line)) => (canvas_rect, line),
+            _ => return vec![],
+        };
+        self.anchor = None;
+        self.current = None;
+
+        let action = match self.kind {
+            ShapeKind::Rect => RasterLayerAction::fill_rect(canvas_rect, self.color),
+            ShapeKind::Oval => RasterLayerAction::fill_oval(canvas_rect, self.color),
+            ShapeKind::Line { radius } => {
+                RasterLayerAction::draw_line(from, to, radius, self.color)
+            }
+        };
+
+        vec![CanvasAction::RasterLayer {
+            layer_num: self.layer_num,
+            action,
+        }]
+    }
+
+    fn set_modifiers(&mut self, modifiers: Modifiers) {
+        self.modifiers = modifiers;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raster::pixels::colors;
+
+    #[test]
+    fn line_shapes_snap_to_45_degrees_with_shift() {
+        let view = CanvasView::new(100, 100);
+        let mut shape_tool = ShapeTool::new(0, colors::red(), ShapeKind::Line { radius: 2 });
+        shape_tool.set_modifiers(Modifiers {
+            shift: true,
+            alt: false,
+        });
+
+        shape_tool.on_pointer_down(&view, (10, 10).into(), 1.0);
+        shape_tool.on_pointer_move(&view, (30, 12).into(), 1.0);
+        assert_eq!(
+            shape_tool.preview_line(),
+            Some(((10, 10).into(), (30, 10).into()))
+        );
+
+        let up = shape_tool.on_pointer_up(&view, (30, 12).into(), 1.0);
+        assert_eq!(
+            up,
+            vec![CanvasAction::RasterLayer {
+                layer_num: 0,
+                action: RasterLayerAction::draw_line(
+                    (10, 10).into(),
+                    (30, 10).into(),
+                    2,
+                    colors::red()
+                ),
+            }]
+        );
+        assert_eq!(shape_tool.preview_line(), None);
+    }
+}
@@ -0,0 +1,200 @@
+//! Lining up shapes of a vector layer by their bounds.
+
+use crate::primitives::{position::CanvasPoint, rect::CanvasRect};
+
+use super::layer::{ShapeId, VectorLayer, VectorShape};
+
+/// The part of the bounds of shapes to line up, along with the same part of
+/// the bounds of the whole selection.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Alignment {
+    Left,
+    CenterX,
+    Right,
+    Top,
+    CenterY,
+    Bottom,
+}
+
+/// A direction along the canvas.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+/// The start and end of the bounds of a shape along an axis.
+fn extent(shape: &VectorShape, axis: Axis) -> (f32, f32) {
+    let dimensions = shape.raster().dimensions();
+
+    match axis {
+        Axis::Horizontal => (shape.position.0, shape.position.0 + dimensions.width as f32),
+        Axis::Vertical => (
+            shape.position.1,
+            shape.position.1 + dimensions.height as f32,
+        ),
+    }
+}
+
+/// The shape moved along an axis so that its bounds start at `start`.
+fn with_start(shape: &VectorShape, axis: Axis, start: f32) -> CanvasPoint {
+    match axis {
+        Axis::Horizontal => (start, shape.position.1).into(),
+        Axis::Vertical => (shape.position.0, start).into(),
+    }
+}
+
+impl VectorLayer {
+    /// The shapes with the ids, or `None` if any of them doesn't exist.
+    fn selected_shapes(&self, ids: &[ShapeId]) -> Option<Vec<(ShapeId, &VectorShape)>> {
+        ids.iter().map(|id| Some((*id, self.shape(*id)?))).collect()
+    }
+
+    /// Moves shapes to new positions, returning the canvas rect that has been
+    /// altered.
+    fn move_shapes(&mut self, positions: Vec<(ShapeId, CanvasPoint)>) -> Option<CanvasRect> {
+        positions
+            .into_iter()
+            .filter_map(|(id, position)| self.move_shape(id, position))
+            .reduce(|a, b| a.spanning_rect(&b))
+    }
+
+    /// Lines up the bounds of shapes with the bounds of all of them, returning
+    /// the canvas rect that has been altered. No shape is moved if any of them
+    /// doesn't exist, returning `None`.
+    pub fn align(&mut self, ids: &[ShapeId], alignment: Alignment) -> Option<CanvasRect> {
+        let axis = match alignment {
+            Alignment::Left | Alignment::CenterX | Alignment::Right => Axis::Horizontal,
+            Alignment::Top | Alignment::CenterY | Alignment::Bottom => Axis::Vertical,
+        };
+
+        let shapes = self.selected_shapes(ids)?;
+        let (start, end) = shapes.iter().map(|(_, shape)| extent(shape, axis)).reduce(
+            |(a_start, a_end), (b_start, b_end)| (a_start.min(b_start), a_end.max(b_end)),
+        )?;
+
+        let positions = shapes
+            .iter()
+            .map(|(id, shape)| {
+                let (shape_start, shape_end) = extent(shape, axis);
+                let new_start = match alignment {
+                    Alignment::Left | Alignment::Top => start,
+                    Alignment::Right | Alignment::Bottom => end - (shape_end - shape_start),
+                    Alignment::CenterX | Alignment::CenterY => {
+                        (start + end - (shape_end - shape_start)) / 2.0
+                    }
+                };
+
+                (*id, with_start(shape, axis, new_start))
+            })
+            .collect();
+
+        self.move_shapes(positions)
+    }
+
+    /// Spaces shapes out along an axis with `spacing` between the bounds of
+    /// each shape and the next, keeping the first shape in place. Shapes are
+    /// kept in the order they are along the axis. Returns the canvas rect that
+    /// has been altered, or `None` without moving any shape if any of them
+    /// doesn't exist.
+    pub fn distribute(&mut self, ids: &[ShapeId], axis: Axis, spacing: f32) -> Option<CanvasRect> {
+        let mut shapes = self.selected_shapes(ids)?;
+        shapes.sort_by(|(_, a), (_, b)| extent(a, axis).0.total_cmp(&extent(b, axis).0));
+
+        let mut next_start = extent(shapes.first()?.1, axis).0;
+        let positions = shapes
+            .iter()
+            .map(|(id, shape)| {
+                let (shape_start, shape_end) = extent(shape, axis);
+                let position = with_start(shape, axis, next_start);
+                next_start += shape_end - shape_start + spacing;
+
+                (*id, position)
+            })
+            .collect();
+
+        self.move_shapes(positions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        canvas::Canvas,
+        raster::pixels::colors,
+        vector::{shapes::Rectangle, VectorLayerAction},
+    };
+
+    #[test]
+    fn aligning_and_distributing_shapes() {
+        let mut vector_layer = VectorLayer::new();
+        let rectangle = |width| Rectangle::new(width, 4.0, colors::red());
+        let (small, _) = vector_layer.add_shape(VectorShape::new((2.0, 0.0), rectangle(2.0)));
+        let (large, _) = vector_layer.add_shape(VectorShape::new((10.0, 20.0), rectangle(6.0)));
+
+        let aligned_rect = vector_layer.align(&[small, large], Alignment::Right);
+        assert_eq!(aligned_rect.map(|rect| rect.top_left), Some((2, 0).into()));
+        assert_eq!(
+            vector_layer.shape(small).unwrap().position,
+            (14.0, 0.0).into()
+        );
+        assert_eq!(
+            vector_layer.shape(large).unwrap().position,
+            (10.0, 20.0).into()
+        );
+
+        vector_layer.align(&[small, large], Alignment::CenterX);
+        assert_eq!(
+            vector_layer.shape(small).unwrap().position,
+            (12.0, 0.0).into()
+        );
+
+        let (last, _) = vector_layer.add_shape(VectorShape::new((0.0, 8.0), rectangle(2.0)));
+        vector_layer.distribute(&[small, large, last], Axis::Vertical, 1.0);
+        let vertical_positions: Vec<f32> = [small, last, large]
+            .iter()
+            .map(|id| vector_layer.shape(*id).unwrap().position.1)
+            .collect();
+        assert_eq!(vertical_positions, vec![0.0, 5.0, 10.0]);
+
+        let (removed, _) = vector_layer.add_shape(VectorShape::new((0.0, 0.0), rectangle(2.0)));
+        vector_layer.remove_shape(removed);
+
+        let mut canvas = Canvas::default();
+        canvas.add_layer(vector_layer.into());
+        assert_eq!(
+            canvas.perform_vector_action(
+                0,
+                VectorLayerAction::Align(vec![small, removed], Alignment::Top)
+            ),
+            None
+        );
+        canvas.perform_vector_action(
+            0,
+            VectorLayerAction::Align(vec![small, last], Alignment::Top),
+        );
+        assert_eq!(
+            canvas
+                .vector_layer(0)
+                .unwrap()
+                .shape(last)
+                .unwrap()
+                .position,
+            (0.0, 0.0).into()
+        );
+
+        canvas.undo();
+        assert_eq!(
+            canvas
+                .vector_layer(0)
+                .unwrap()
+                .shape(last)
+                .unwrap()
+                .position,
+            (0.0, 5.0).into()
+        );
+    }
+}
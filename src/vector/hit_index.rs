@@ -0,0 +1,108 @@
+//! A low resolution index of which shape of a vector layer is on top at each
+//! point, for answering hit tests that repeat at every pointer move without
+//! testing every shape.
+
+use std::collections::HashMap;
+
+use crate::primitives::{
+    position::{CanvasPosition, ChunkPosition},
+    rect::CanvasRect,
+};
+
+use super::layer::ShapeId;
+
+/// The number of cells along each side of a chunk of the index.
+const HIT_INDEX_CHUNK_CELLS: usize = 32;
+
+/// The topmost shape of a square cell of `cell_size` canvas pixels, sampled at
+/// the center of the cell. Chunks of cells are built when first queried and
+/// dropped when shapes within them change, so only areas that are hit tested
+/// are indexed.
+#[derive(Debug, Clone)]
+pub(super) struct HitIndex {
+    cell_size: usize,
+    chunks: HashMap<ChunkPosition, Box<[Option<ShapeId>]>>,
+}
+
+impl HitIndex {
+    pub(super) fn new(cell_size: usize) -> HitIndex {
+        HitIndex {
+            cell_size: cell_size.max(1),
+            chunks: HashMap::new(),
+        }
+    }
+
+    pub(super) fn cell_size(&self) -> usize {
+        self.cell_size
+    }
+
+    /// The canvas size of a side of a chunk.
+    fn chunk_span(&self) -> usize {
+        self.cell_size * HIT_INDEX_CHUNK_CELLS
+    }
+
+    /// The shape on top of the cell containing `position`, building the cell's
+    /// chunk with `shape_at` if it isn't indexed.
+    pub(super) fn shape_at<F>(&mut self, position: CanvasPosition, shape_at: F) -> Option<ShapeId>
+    where
+        F: Fn(CanvasPosition) -> Option<ShapeId>,
+    {
+        let chunk_span = self.chunk_span();
+        let cell_size = self.cell_size;
+        let chunk_position = position.containing_chunk(chunk_span);
+
+        let cells = self.chunks.entry(chunk_position).or_insert_with(|| {
+            let chunk_origin = (
+                chunk_position.0 * chunk_span as i32,
+                chunk_position.1 * chunk_span as i32,
+            );
+            let cell_center = cell_size as i32 / 2;
+
+            (0..HIT_INDEX_CHUNK_CELLS)
+                .flat_map(|y| (0..HIT_INDEX_CHUNK_CELLS).map(move |x| (x, y)))
+                .map(|(x, y)| {
+                    shape_at(
+                        (
+                            chunk_origin.0 + (x * cell_size) as i32 + cell_center,
+                            chunk_origin.1 + (y * cell_size) as i32 + cell_center,
+                        )
+                            .into(),
+                    )
+                })
+                .collect()
+        });
+
+        let position_in_chunk = position.position_in_containing_chunk(chunk_span);
+        let cell = (position_in_chunk.1 / cell_size) * HIT_INDEX_CHUNK_CELLS
+            + position_in_chunk.0 / cell_size;
+
+        cells[cell]
+    }
+
+    /// Drops the chunks overlapping a canvas rect whose shapes have changed.
+    pub(super) fn invalidate(&mut self, canvas_rect: &CanvasRect) {
+        let chunk_span = self.chunk_span();
+        let top_left = canvas_rect.top_left.containing_chunk(chunk_span);
+        let bottom_right = canvas_rect
+            .top_left
+            .translate(
+                (
+                    canvas_rect.dimensions.width as i32 - 1,
+                    canvas_rect.dimensions.height as i32 - 1,
+                )
+                    .into(),
+            )
+            .containing_chunk(chunk_span);
+
+        self.chunks.retain(|chunk_position, _| {
+            chunk_position.0 < top_left.0
+                || chunk_position.0 > bottom_right.0
+                || chunk_position.1 < top_left.1
+                || chunk_position.1 > bottom_right.1
+        });
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.chunks.clear();
+    }
+}
@@ -1,7 +1,844 @@
-use crate::primitives::position::CanvasPosition;
+use std::{collections::BTreeMap, fmt, sync::Arc};
 
-use super::shapes::RasterizablePolygon;
+use bumpalo::Bump;
 
+use super::{
+    align::{Alignment, Axis},
+    hit_index::HitIndex,
+    scaled_cache::ScaledRasterCache,
+    shapes::{Polygon, RasterizablePolygon},
+    style::{Style, StyledPolygon},
+};
+use crate::{
+    canvas::{CanvasView, Layer},
+    primitives::{
+        dimensions::Dimensions,
+        position::{CanvasPoint, CanvasPosition, DrawPosition},
+        rect::CanvasRect,
+        transform::Affine,
+    },
+    raster::{
+        chunks::{raster_chunk::BumpRasterChunk, BoxRasterChunk},
+        BlendIf, BlendMode, Glow, RasterSource, RasterizeError,
+    },
+};
+
+/// Identifies a shape within a `VectorLayer`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ShapeId(usize);
+
+/// A polygon placed on the canvas. The polygon is rasterized once when the
+/// shape is created or transformed, and the raster is shared between copies of
+/// the shape.
+#[derive(Clone)]
+pub struct VectorShape {
+    /// The canvas point of the top left of the shape. The point is resolved
+    /// to the nearest pixel of each view the shape is rasterized for, so shapes
+    /// can be placed between canvas pixels while zoomed in.
+    pub position: CanvasPoint,
+    polygon: Arc<dyn RasterizablePolygon + Send + Sync>,
+    style: Option<Style>,
+    raster: Arc<BoxRasterChunk>,
+    /// The scaling and rotation of the polygon's own raster, about its top left.
+    linear_transform: Affine,
+    /// The offset of the top left of `raster` from the top left of the
+    /// polygon's own raster after `linear_transform`.
+    raster_offset: CanvasPoint,
+}
+
+impl VectorShape {
+    pub fn new<P: RasterizablePolygon + Send + Sync + 'static>(
+        position: impl Into<CanvasPoint>,
+        polygon: P,
+    ) -> VectorShape {
+        let raster = Arc::new(polygon.rasterize());
+
+        VectorShape {
+            position: position.into(),
+            polygon: Arc::new(polygon),
+            style: None,
+            raster,
+            linear_transform: Affine::IDENTITY,
+            raster_offset: (0.0, 0.0).into(),
+        }
+    }
+
+    /// A shape painted with `style`, with its fill and stroke rasterized
+    /// together. The stroke extends the raster by half of its width on every
+    /// side, so `position` is the top left of the stroke rather than the polygon.
+    pub fn new_styled<P: Polygon + Send + Sync + 'static>(
+        position: impl Into<CanvasPoint>,
+        polygon: P,
+        style: Style,
+    ) -> VectorShape {
+        let styled = StyledPolygon::new(polygon, style);
+        let raster = Arc::new(styled.rasterize());
+
+        VectorShape {
+            position: position.into(),
+            polygon: Arc::new(styled),
+            style: Some(style),
+            raster,
+            linear_transform: Affine::IDENTITY,
+            raster_offset: (0.0, 0.0).into(),
+        }
+    }
+
+    /// The transform from the polygon's own raster to the canvas.
+    pub fn transform(&self) -> Affine {
+        let origin = self.position.translate(self.raster_offset.mul(-1.0));
+
+        self.linear_transform
+            .then(&Affine::translate(origin.0, origin.1))
+    }
+
+    /// The shape with `transform` applied after its current transform. The
+    /// polygon is rasterized again unless only its position changes, so
+    /// repeated transforms don't blur it. Returns `None` if the transform
+    /// collapses the shape onto a line.
+    pub fn transformed(&self, transform: &Affine) -> Option<VectorShape> {
+        let new_transform = self.transform().then(transform);
+        let linear_transform = new_transform.linear();
+        let origin = new_transform.apply_snapped((0.0, 0.0).into());
+
+        if linear_transform == self.linear_transform {
+            return Some(VectorShape {
+                position: origin.translate(self.raster_offset),
+                ..self.clone()
+            });
+        }
+
+        let (raster_offset, raster) = self.polygon.rasterize().transformed(&linear_transform)?;
+
+        Some(VectorShape {
+            position: origin.translate(raster_offset),
+            raster: Arc::new(raster),
+            linear_transform,
+            raster_offset,
+            ..self.clone()
+        })
+    }
+
+    /// The style of the shape, if it was created with one.
+    pub fn style(&self) -> Option<&Style> {
+        self.style.as_ref()
+    }
+
+    pub fn polygon(&self) -> &dyn RasterizablePolygon {
+        self.polygon.as_ref()
+    }
+
+    pub fn raster(&self) -> &BoxRasterChunk {
+        &self.raster
+    }
+
+    /// The canvas rect covering the shape, including pixels it only covers
+    /// partially when placed between pixels.
+    pub fn canvas_rect(&self) -> CanvasRect {
+        let Dimensions { width, height } = self.raster.dimensions();
+        let top_left = self.position.containing_pixel();
+        let right = (self.position.0 + width as f32).ceil() as i32;
+        let bottom = (self.position.1 + height as f32).ceil() as i32;
+
+        CanvasRect {
+            top_left,
+            dimensions: Dimensions {
+                width: (right - top_left.0) as usize,
+                height: (bottom - top_left.1) as usize,
+            },
+        }
+    }
+}
+
+impl VectorShape {
+    /// Whether the shape covers the canvas pixel at `position` with any opacity,
+    /// where it is drawn without scaling.
+    pub fn covers(&self, position: CanvasPosition) -> bool {
+        let top_left = self.position.round();
+        let (x, y) = (position.0 - top_left.0, position.1 - top_left.1);
+
+        if x < 0 || y < 0 {
+            return false;
+        }
+
+        self.raster
+            .pixel_at_position((x as usize, y as usize).into())
+            .is_some_and(|pixel| pixel.as_rgba().3 > 0)
+    }
+}
+
+impl fmt::Debug for VectorShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VectorShape")
+            .field("position", &self.position)
+            .field("dimensions", &self.raster.dimensions())
+            .finish()
+    }
+}
+
+/// An edit to a vector layer.
+#[derive(Debug, Clone)]
+pub enum VectorLayerAction {
+    AddShape(VectorShape),
+    RemoveShape(ShapeId),
+    MoveShape(ShapeId, CanvasPoint),
+    /// Applies a transform to every shape, or none of them if any doesn't exist.
+    TransformShapes(Vec<ShapeId>, Affine),
+    BringToFront(ShapeId),
+    SendToBack(ShapeId),
+    /// Restacks the first shape directly above the second.
+    MoveAbove(ShapeId, ShapeId),
+    Align(Vec<ShapeId>, Alignment),
+    /// Spaces shapes out along an axis with a gap between each.
+    Distribute(Vec<ShapeId>, Axis, f32),
+}
+
+/// A layer of shapes that stay editable after being placed, drawn from bottom
+/// to top. Shapes are added to the top, and can be restacked afterwards.
+#[derive(Debug, Clone, Default)]
 pub struct VectorLayer {
-    shapes: Vec<(CanvasPosition, Box<dyn RasterizablePolygon>)>,
+    shapes: BTreeMap<ShapeId, VectorShape>,
+    /// The ids of the shapes from bottom to top.
+    order: Vec<ShapeId>,
+    next_id: usize,
+    blend_if: Option<BlendIf>,
+    blend_mode: BlendMode,
+    glow: Option<Glow>,
+    hit_index: Option<HitIndex>,
+    scaled_rasters: ScaledRasterCache,
+}
+
+impl VectorLayer {
+    pub fn new() -> VectorLayer {
+        VectorLayer::default()
+    }
+
+    /// Adds a shape to the top of the layer, returning its id and the canvas
+    /// rect that has been altered.
+    pub fn add_shape(&mut self, shape: VectorShape) -> (ShapeId, CanvasRect) {
+        let id = ShapeId(self.next_id);
+        self.next_id += 1;
+
+        let canvas_rect = shape.canvas_rect();
+        self.shapes.insert(id, shape);
+        self.order.push(id);
+        self.invalidate_hit_index(&canvas_rect);
+
+        (id, canvas_rect)
+    }
+
+    /// Removes a shape, returning it with the canvas rect that has been
+    /// altered if it existed.
+    pub fn remove_shape(&mut self, id: ShapeId) -> Option<(VectorShape, CanvasRect)> {
+        let shape = self.shapes.remove(&id)?;
+        self.order.retain(|ordered_id| *ordered_id != id);
+        self.scaled_rasters.invalidate(id);
+
+        let canvas_rect = shape.canvas_rect();
+        self.invalidate_hit_index(&canvas_rect);
+
+        Some((shape, canvas_rect))
+    }
+
+    /// Moves a shape, returning the canvas rect that has been altered. Returns
+    /// `None` if there is no shape with the id.
+    pub fn move_shape(&mut self, id: ShapeId, position: CanvasPoint) -> Option<CanvasRect> {
+        let shape = self.shapes.get_mut(&id)?;
+        let old_canvas_rect = shape.canvas_rect();
+
+        shape.position = position;
+
+        let changed_canvas_rect = old_canvas_rect.spanning_rect(&shape.canvas_rect());
+        self.invalidate_hit_index(&changed_canvas_rect);
+
+        Some(changed_canvas_rect)
+    }
+
+    /// Applies a shared transform to shapes, such as a rotation about the
+    /// center of a selection of them, returning the canvas rect that has been
+    /// altered. No shape is changed if any of them doesn't exist or the
+    /// transform collapses them onto a line, returning `None`.
+    pub fn transform_shapes(&mut self, ids: &[ShapeId], transform: &Affine) -> Option<CanvasRect> {
+        let transformed_shapes = ids
+            .iter()
+            .map(|id| Some((*id, self.shapes.get(id)?.transformed(transform)?)))
+            .collect::<Option<Vec<_>>>()?;
+
+        let mut changed_canvas_rect: Option<CanvasRect> = None;
+        for (id, shape) in transformed_shapes {
+            let new_canvas_rect = shape.canvas_rect();
+            self.scaled_rasters.invalidate(id);
+
+            if let Some(old_shape) = self.shapes.insert(id, shape) {
+                let shape_rect = old_shape.canvas_rect().spanning_rect(&new_canvas_rect);
+                changed_canvas_rect = Some(match changed_canvas_rect {
+                    Some(changed_canvas_rect) => changed_canvas_rect.spanning_rect(&shape_rect),
+                    None => shape_rect,
+                });
+            }
+        }
+        let changed_canvas_rect = changed_canvas_rect?;
+
+        self.invalidate_hit_index(&changed_canvas_rect);
+
+        Some(changed_canvas_rect)
+    }
+
+    pub fn shape(&self, id: ShapeId) -> Option<&VectorShape> {
+        self.shapes.get(&id)
+    }
+
+    /// The position of a shape in the stacking order, with `0` being the
+    /// bottom. Positions shift as shapes are added, removed and restacked.
+    pub fn z_index(&self, id: ShapeId) -> Option<usize> {
+        self.order.iter().position(|ordered_id| *ordered_id == id)
+    }
+
+    /// Restacks a shape to `z_index`, returning the canvas rect that has
+    /// been altered.
+    fn restack(
+        &mut self,
+        id: ShapeId,
+        z_index: impl FnOnce(&[ShapeId]) -> usize,
+    ) -> Option<CanvasRect> {
+        let old_z_index = self.z_index(id)?;
+        self.order.remove(old_z_index);
+
+        let new_z_index = z_index(&self.order);
+        self.order.insert(new_z_index, id);
+
+        let canvas_rect = self.shapes.get(&id)?.canvas_rect();
+        self.invalidate_hit_index(&canvas_rect);
+
+        Some(canvas_rect)
+    }
+
+    /// Moves a shape above every other shape, returning the canvas rect that
+    /// has been altered. Returns `None` if there is no shape with the id.
+    pub fn bring_to_front(&mut self, id: ShapeId) -> Option<CanvasRect> {
+        self.restack(id, |order| order.len())
+    }
+
+    /// Moves a shape below every other shape, returning the canvas rect that
+    /// has been altered. Returns `None` if there is no shape with the id.
+    pub fn send_to_back(&mut self, id: ShapeId) -> Option<CanvasRect> {
+        self.restack(id, |_| 0)
+    }
+
+    /// Moves a shape directly above `other`, returning the canvas rect that
+    /// has been altered. Returns `None` if either shape doesn't exist or they
+    /// are the same shape.
+    pub fn move_above(&mut self, id: ShapeId, other: ShapeId) -> Option<CanvasRect> {
+        if id == other || !self.shapes.contains_key(&other) {
+            return None;
+        }
+
+        self.restack(id, |order| {
+            order
+                .iter()
+                .position(|ordered_id| *ordered_id == other)
+                .map_or(order.len(), |other_z_index| other_z_index + 1)
+        })
+    }
+
+    /// The topmost shape covering the canvas pixel at `position`, testing
+    /// every shape of the layer.
+    pub fn shape_at(&self, position: CanvasPosition) -> Option<ShapeId> {
+        self.iter()
+            .rev()
+            .find(|(_, shape)| shape.covers(position))
+            .map(|(id, _)| id)
+    }
+
+    /// The topmost shape at `position` like `VectorLayer::shape_at`, answered
+    /// from the hit index if the layer has one.
+    pub fn hit_test(&mut self, position: CanvasPosition) -> Option<ShapeId> {
+        match self.hit_index.take() {
+            Some(mut hit_index) => {
+                let id = hit_index.shape_at(position, |position| self.shape_at(position));
+                self.hit_index = Some(hit_index);
+
+                id
+            }
+            None => self.shape_at(position),
+        }
+    }
+
+    /// The size of the square cells of canvas pixels the hit index resolves
+    /// shapes to, if the layer has one.
+    pub fn hit_index_cell_size(&self) -> Option<usize> {
+        self.hit_index.as_ref().map(HitIndex::cell_size)
+    }
+
+    /// Indexes which shape is on top in cells of `cell_size` canvas pixels for
+    /// `VectorLayer::hit_test`, or stops indexing with `None`. Hit tests are
+    /// then exact to a cell, which is enough for hover highlights and costs a
+    /// lookup rather than a test of every shape. Cells are indexed as they are
+    /// first hit tested, and reindexed after the shapes over them change.
+    pub fn set_hit_index_cell_size(&mut self, cell_size: Option<usize>) {
+        self.hit_index = cell_size.map(HitIndex::new);
+    }
+
+    fn invalidate_hit_index(&mut self, canvas_rect: &CanvasRect) {
+        if let Some(hit_index) = &mut self.hit_index {
+            hit_index.invalidate(canvas_rect);
+        }
+    }
+
+    /// The shapes of the layer from bottom to top.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (ShapeId, &VectorShape)> {
+        self.order.iter().map(|id| (*id, &self.shapes[id]))
+    }
+
+    pub fn len(&self) -> usize {
+        self.shapes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shapes.is_empty()
+    }
+
+    /// A copy of the shapes of the layer, without its layer settings, for
+    /// restoring with `VectorLayer::swap_shapes`. Shapes share their rasters
+    /// with the copy, so this is cheap.
+    pub(crate) fn snapshot_shapes(&self) -> VectorLayer {
+        VectorLayer {
+            shapes: self.shapes.clone(),
+            order: self.order.clone(),
+            next_id: self.next_id,
+            ..VectorLayer::default()
+        }
+    }
+
+    /// Exchanges the shapes of the layer with those of `other`, leaving the
+    /// settings of both layers in place.
+    pub(crate) fn swap_shapes(&mut self, other: &mut VectorLayer) {
+        std::mem::swap(&mut self.shapes, &mut other.shapes);
+        std::mem::swap(&mut self.order, &mut other.order);
+        std::mem::swap(&mut self.next_id, &mut other.next_id);
+        self.scaled_rasters.clear();
+
+        if let Some(hit_index) = &mut self.hit_index {
+            hit_index.clear();
+        }
+    }
+
+    /// Composites the shapes intersecting `canvas_rect` onto a raster of
+    /// `raster_dimensions`, resolving their positions to the pixels of the
+    /// raster. Shapes scaled for the raster are cached until the scale or the
+    /// shape changes.
+    fn composite_shapes(
+        &self,
+        canvas_rect: CanvasRect,
+        raster_dimensions: Dimensions,
+    ) -> BoxRasterChunk {
+        let mut raster = BoxRasterChunk::new(raster_dimensions.width, raster_dimensions.height);
+        let scale = raster_dimensions.relative_scale(canvas_rect.dimensions);
+        let unscaled = raster_dimensions == canvas_rect.dimensions;
+
+        for (id, shape) in self.iter() {
+            if !canvas_rect.intersects(&shape.canvas_rect()) {
+                continue;
+            }
+
+            let draw_position: DrawPosition = (
+                ((shape.position.0 - canvas_rect.top_left.0 as f32) * scale.width_factor()).round()
+                    as i32,
+                ((shape.position.1 - canvas_rect.top_left.1 as f32) * scale.height_factor()).round()
+                    as i32,
+            )
+                .into();
+
+            if unscaled {
+                raster.composite_over(&shape.raster.as_window(), draw_position);
+            } else {
+                let scaled_shape = self.scaled_rasters.get_or_scale(
+                    id,
+                    &shape.raster,
+                    shape.raster.dimensions().scale(scale),
+                );
+
+                raster.composite_over(&scaled_shape.as_window(), draw_position);
+            }
+        }
+
+        raster
+    }
+
+    /// Performs a vector layer action, returning the canvas rect that has been
+    /// altered by it.
+    pub fn perform_action(&mut self, action: VectorLayerAction) -> Option<CanvasRect> {
+        use VectorLayerAction::*;
+        match action {
+            AddShape(shape) => Some(self.add_shape(shape).1),
+            RemoveShape(id) => self.remove_shape(id).map(|(_, canvas_rect)| canvas_rect),
+            MoveShape(id, position) => self.move_shape(id, position),
+            TransformShapes(ids, transform) => self.transform_shapes(&ids, &transform),
+            BringToFront(id) => self.bring_to_front(id),
+            SendToBack(id) => self.send_to_back(id),
+            MoveAbove(id, other) => self.move_above(id, other),
+            Align(ids, alignment) => self.align(&ids, alignment),
+            Distribute(ids, axis, spacing) => self.distribute(&ids, axis, spacing),
+        }
+    }
+}
+
+impl Layer for VectorLayer {
+    fn rasterize(&mut self, view: &CanvasView) -> BoxRasterChunk {
+        self.composite_shapes(view.canvas_rect(), view.view_dimensions)
+    }
+
+    fn rasterize_canvas_rect(&mut self, canvas_rect: CanvasRect) -> BoxRasterChunk {
+        self.rasterize_canvas_rect_shared(canvas_rect)
+    }
+
+    fn rasterize_canvas_rect_shared(&self, canvas_rect: CanvasRect) -> BoxRasterChunk {
+        self.composite_shapes(canvas_rect, canvas_rect.dimensions)
+    }
+
+    fn rasterize_into_bump<'bump>(
+        &mut self,
+        view: &CanvasView,
+        bump: &'bump Bump,
+    ) -> BumpRasterChunk<'bump> {
+        self.rasterize(view).as_window().to_chunk_into_bump(bump)
+    }
+
+    fn rasterize_canvas_rect_into_bump<'bump>(
+        &mut self,
+        canvas_rect: CanvasRect,
+        bump: &'bump Bump,
+    ) -> BumpRasterChunk<'bump> {
+        self.rasterize_canvas_rect_shared(canvas_rect)
+            .as_window()
+            .to_chunk_into_bump(bump)
+    }
+
+    fn try_rasterize_canvas_rect(
+        &mut self,
+        canvas_rect: CanvasRect,
+    ) -> Result<BoxRasterChunk, RasterizeError> {
+        CanvasRect::try_new(canvas_rect.top_left, canvas_rect.dimensions)?;
+
+        Ok(self.rasterize_canvas_rect(canvas_rect))
+    }
+
+    fn try_rasterize_canvas_rect_into_bump<'bump>(
+        &mut self,
+        canvas_rect: CanvasRect,
+        bump: &'bump Bump,
+    ) -> Result<BumpRasterChunk<'bump>, RasterizeError> {
+        CanvasRect::try_new(canvas_rect.top_left, canvas_rect.dimensions)?;
+
+        Ok(self.rasterize_canvas_rect_into_bump(canvas_rect, bump))
+    }
+
+    fn clear(&mut self) {
+        self.shapes.clear();
+        self.order.clear();
+        self.scaled_rasters.clear();
+
+        if let Some(hit_index) = &mut self.hit_index {
+            hit_index.clear();
+        }
+    }
+
+    fn content_bounds(&self) -> Option<CanvasRect> {
+        self.shapes
+            .values()
+            .map(VectorShape::canvas_rect)
+            .reduce(|a, b| a.spanning_rect(&b))
+    }
+
+    fn blend_if(&self) -> Option<BlendIf> {
+        self.blend_if
+    }
+
+    fn set_blend_if(&mut self, blend_if: Option<BlendIf>) {
+        self.blend_if = blend_if;
+    }
+
+    fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+    }
+
+    fn glow(&self) -> Option<Glow> {
+        self.glow
+    }
+
+    fn set_glow(&mut self, glow: Option<Glow>) {
+        self.glow = glow;
+    }
+
+    fn rasterizes_at_view_scale(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        canvas::Canvas,
+        primitives::dimensions::{Dimensions, Scale},
+        primitives::transform::Affine,
+        raster::pixels::colors,
+        vector::shapes::{Oval, Rectangle},
+    };
+
+    #[test]
+    fn shapes_report_dirty_rects() {
+        let mut vector_layer = VectorLayer::new();
+        let shape = VectorShape::new(
+            (4.0, 4.0),
+            Oval::build_from_bound(8, 8).color(colors::red()).build(),
+        );
+        let shape_rect = shape.canvas_rect();
+
+        let (id, added_rect) = vector_layer.add_shape(shape);
+        assert_eq!(added_rect, shape_rect);
+
+        let moved_rect = vector_layer.move_shape(id, (6.0, 4.0).into()).unwrap();
+        assert_eq!(moved_rect.top_left, (4, 4).into());
+        assert_eq!(moved_rect.dimensions.width, shape_rect.dimensions.width + 2);
+
+        let whole_rect = CanvasRect::at_origin(Dimensions {
+            width: 24,
+            height: 24,
+        });
+        let center = |raster: &BoxRasterChunk| {
+            let (x, y) = (
+                6 + shape_rect.dimensions.width / 2,
+                4 + shape_rect.dimensions.height / 2,
+            );
+            raster.pixels()[y * 24 + x]
+        };
+        assert_eq!(
+            center(&vector_layer.rasterize_canvas_rect_shared(whole_rect)),
+            colors::red()
+        );
+
+        let mut canvas = Canvas::default();
+        canvas.add_layer(vector_layer.into());
+        let removed_rect = canvas.perform_vector_action(0, VectorLayerAction::RemoveShape(id));
+        assert_eq!(removed_rect.map(|rect| rect.top_left), Some((6, 4).into()));
+        assert!(canvas.vector_layer(0).unwrap().is_empty());
+
+        canvas.undo();
+        assert_eq!(canvas.vector_layer(0).unwrap().len(), 1);
+        assert_eq!(
+            canvas.perform_vector_action(0, VectorLayerAction::RemoveShape(ShapeId(5))),
+            None
+        );
+    }
+
+    #[test]
+    fn shapes_between_pixels_resolve_per_view_scale() {
+        let oval = Oval::build_from_bound(4, 4).color(colors::red()).build();
+        let shape_raster = oval.rasterize();
+
+        let mut vector_layer = VectorLayer::new();
+        let (_, shape_rect) = vector_layer.add_shape(VectorShape::new((2.25, 1.0), oval));
+        assert_eq!(shape_rect.top_left, (2, 1).into());
+        assert_eq!(
+            shape_rect.dimensions.width,
+            shape_raster.dimensions().width + 1
+        );
+
+        let first_opaque_column = |raster: &BoxRasterChunk, row: usize| {
+            let width = raster.dimensions().width;
+            raster.pixels()[row * width..(row + 1) * width]
+                .iter()
+                .position(|pixel| pixel.as_rgba().3 > 0)
+                .unwrap()
+        };
+        let shape_column = first_opaque_column(&shape_raster, 2);
+
+        let unscaled =
+            vector_layer.rasterize_canvas_rect_shared(CanvasRect::at_origin(Dimensions {
+                width: 8,
+                height: 8,
+            }));
+        assert_eq!(first_opaque_column(&unscaled, 3), 2 + shape_column);
+
+        let mut view = CanvasView::new(8, 8);
+        view.view_dimensions = Dimensions {
+            width: 32,
+            height: 32,
+        };
+        let zoomed_in = vector_layer.rasterize(&view);
+        assert_eq!(first_opaque_column(&zoomed_in, 12), 9 + 4 * shape_column);
+    }
+
+    #[test]
+    fn scaled_shapes_are_cached_until_changed() {
+        let mut vector_layer = VectorLayer::new();
+        let square = || Rectangle::new(4.0, 4.0, colors::red());
+        let (kept, _) = vector_layer.add_shape(VectorShape::new((0.0, 0.0), square()));
+        let (transformed, _) = vector_layer.add_shape(VectorShape::new((8.0, 0.0), square()));
+
+        let mut view = CanvasView::new(16, 16);
+        view.view_dimensions = Dimensions {
+            width: 32,
+            height: 32,
+        };
+        vector_layer.rasterize(&view);
+        let kept_raster = vector_layer.scaled_rasters.cached_raster(kept).unwrap();
+        assert_eq!(
+            kept_raster.dimensions(),
+            Dimensions {
+                width: 8,
+                height: 8,
+            }
+        );
+
+        view.translate((2, 2).into());
+        vector_layer.rasterize(&view);
+        vector_layer.transform_shapes(&[transformed], &Affine::scale(2.0, 1.0));
+        assert!(Arc::ptr_eq(
+            &vector_layer.scaled_rasters.cached_raster(kept).unwrap(),
+            &kept_raster
+        ));
+        assert!(vector_layer
+            .scaled_rasters
+            .cached_raster(transformed)
+            .is_none());
+
+        view.pin_scale_canvas(Scale::new(0.5, 0.5).unwrap());
+        view.top_left = (0, 0).into();
+        vector_layer.rasterize(&view);
+        assert_eq!(
+            vector_layer
+                .scaled_rasters
+                .cached_raster(kept)
+                .unwrap()
+                .dimensions(),
+            Dimensions {
+                width: 16,
+                height: 16,
+            }
+        );
+    }
+
+    #[test]
+    fn hit_index_tracks_shape_changes() {
+        let mut vector_layer = VectorLayer::new();
+        let square = || Rectangle::new(8.0, 8.0, colors::red());
+        let (bottom, _) = vector_layer.add_shape(VectorShape::new((0.0, 0.0), square()));
+        let (top, _) = vector_layer.add_shape(VectorShape::new((4.0, 4.0), square()));
+
+        vector_layer.set_hit_index_cell_size(Some(1));
+        for (x, y) in (0..16).flat_map(|y| (0..16).map(move |x| (x, y))) {
+            let position = (x, y).into();
+            assert_eq!(
+                vector_layer.hit_test(position),
+                vector_layer.shape_at(position)
+            );
+        }
+        assert_eq!(vector_layer.hit_test((1, 1).into()), Some(bottom));
+        assert_eq!(vector_layer.hit_test((6, 6).into()), Some(top));
+
+        vector_layer.move_shape(top, (40.0, 40.0).into());
+        assert_eq!(vector_layer.hit_test((6, 6).into()), Some(bottom));
+        assert_eq!(vector_layer.hit_test((44, 44).into()), Some(top));
+        assert_eq!(vector_layer.hit_test((20, 20).into()), None);
+    }
+
+    #[test]
+    fn restacking_shapes_changes_drawing_order() {
+        let mut vector_layer = VectorLayer::new();
+        let square = |color| Rectangle::new(4.0, 4.0, color);
+        let (red, _) = vector_layer.add_shape(VectorShape::new((0.0, 0.0), square(colors::red())));
+        let (green, _) =
+            vector_layer.add_shape(VectorShape::new((0.0, 0.0), square(colors::green())));
+        let (blue, _) =
+            vector_layer.add_shape(VectorShape::new((0.0, 0.0), square(colors::blue())));
+
+        let rect = CanvasRect::at_origin(Dimensions {
+            width: 4,
+            height: 4,
+        });
+        let top_color = |vector_layer: &VectorLayer| {
+            vector_layer.rasterize_canvas_rect_shared(rect).pixels()[0]
+        };
+        assert!(top_color(&vector_layer).is_close(&colors::blue(), 2));
+
+        assert_eq!(vector_layer.bring_to_front(red), Some(rect));
+        assert!(top_color(&vector_layer).is_close(&colors::red(), 2));
+
+        vector_layer.send_to_back(red);
+        vector_layer.move_above(blue, green);
+        assert!(top_color(&vector_layer).is_close(&colors::blue(), 2));
+        assert_eq!(
+            vector_layer.iter().map(|(id, _)| id).collect::<Vec<_>>(),
+            vec![red, green, blue]
+        );
+        assert_eq!(vector_layer.move_above(blue, blue), None);
+
+        let mut canvas = Canvas::default();
+        canvas.add_layer(vector_layer.into());
+        canvas.perform_vector_action(0, VectorLayerAction::MoveAbove(red, blue));
+        assert_eq!(canvas.vector_layer(0).unwrap().z_index(red), Some(2));
+        assert_eq!(canvas.vector_shape_at(0, (1, 1).into()), Some(red));
+
+        canvas.undo();
+        assert_eq!(canvas.vector_layer(0).unwrap().z_index(red), Some(0));
+        assert_eq!(canvas.vector_shape_at(0, (1, 1).into()), Some(blue));
+    }
+
+    #[test]
+    fn transforming_shapes_together() {
+        let mut vector_layer = VectorLayer::new();
+        let square = || Rectangle::new(4.0, 2.0, colors::red());
+        let (left, _) = vector_layer.add_shape(VectorShape::new((0.0, 0.0), square()));
+        let (right, _) = vector_layer.add_shape(VectorShape::new((8.0, 0.0), square()));
+
+        let moved_rect = vector_layer
+            .transform_shapes(&[left, right], &Affine::translate(2.0, 3.0))
+            .unwrap();
+        assert_eq!(
+            moved_rect,
+            CanvasRect {
+                top_left: (0, 0).into(),
+                dimensions: Dimensions {
+                    width: 14,
+                    height: 5,
+                },
+            }
+        );
+        assert_eq!(
+            vector_layer.shape(right).unwrap().position,
+            (10.0, 3.0).into()
+        );
+
+        // A quarter turn about the center of both shapes
+        let quarter_turn = Affine::rotate(std::f32::consts::FRAC_PI_2).about((8.0, 4.0).into());
+        vector_layer.transform_shapes(&[left, right], &quarter_turn);
+
+        let left_rect = vector_layer.shape(left).unwrap().canvas_rect();
+        assert_eq!(
+            left_rect,
+            CanvasRect {
+                top_left: (7, -2).into(),
+                dimensions: Dimensions {
+                    width: 2,
+                    height: 4,
+                },
+            }
+        );
+        assert_eq!(vector_layer.shape_at((8, -1).into()), Some(left));
+        assert_eq!(vector_layer.shape_at((8, 9).into()), Some(right));
+        assert_eq!(vector_layer.shape_at((8, 4).into()), None);
+
+        let missing = ShapeId(10);
+        assert_eq!(
+            vector_layer.transform_shapes(&[left, missing], &Affine::translate(1.0, 0.0)),
+            None
+        );
+        assert_eq!(vector_layer.shape(left).unwrap().canvas_rect(), left_rect);
+    }
 }
@@ -1,2 +1,12 @@
+pub mod align;
+mod hit_index;
 pub mod layer;
+mod scaled_cache;
 pub mod shapes;
+pub mod snap;
+pub mod style;
+
+pub use align::{Alignment, Axis};
+pub use layer::{ShapeId, VectorLayer, VectorLayerAction, VectorShape};
+pub use snap::{SnapCandidate, SnapFeature};
+pub use style::{Fill, Stroke, Style, StyledPolygon};
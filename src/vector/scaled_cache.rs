@@ -0,0 +1,107 @@
+//! Rasters of the shapes of a vector layer scaled for the zoom they were last
+//! drawn at, so that rendering at the same zoom again, such as while panning,
+//! doesn't scale every shape again.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use crate::{
+    primitives::dimensions::Dimensions,
+    raster::chunks::{BoxRasterChunk, ScalingFilter},
+};
+
+use super::layer::ShapeId;
+
+struct ScaledRaster {
+    /// The raster of the shape that was scaled, which is replaced when the
+    /// shape is transformed.
+    source: Arc<BoxRasterChunk>,
+    raster: Arc<BoxRasterChunk>,
+}
+
+/// The scaled raster of each shape, keyed by the shape and the dimensions it
+/// is scaled to. The dimensions quantize the zoom to whole pixels of the
+/// shape, so zooms that scale a shape to the same size share its raster.
+#[derive(Default)]
+pub(super) struct ScaledRasterCache {
+    rasters: Mutex<HashMap<ShapeId, ScaledRaster>>,
+}
+
+impl ScaledRasterCache {
+    /// The raster of a shape scaled to `dimensions`, scaling `source` if it
+    /// isn't cached.
+    pub(super) fn get_or_scale(
+        &self,
+        id: ShapeId,
+        source: &Arc<BoxRasterChunk>,
+        dimensions: Dimensions,
+    ) -> Arc<BoxRasterChunk> {
+        let cached_raster = self.lock().get(&id).and_then(|scaled_raster| {
+            let is_current = Arc::ptr_eq(&scaled_raster.source, source)
+                && scaled_raster.raster.dimensions() == dimensions;
+
+            is_current.then(|| scaled_raster.raster.clone())
+        });
+
+        // Shapes are scaled without holding the lock, so readers on other
+        // threads aren't blocked on each other
+        cached_raster.unwrap_or_else(|| {
+            let raster = Arc::new(source.scaled(dimensions, ScalingFilter::NearestNeighbour));
+            self.lock().insert(
+                id,
+                ScaledRaster {
+                    source: source.clone(),
+                    raster: raster.clone(),
+                },
+            );
+
+            raster
+        })
+    }
+
+    /// Drops the scaled raster of a shape that has been changed or removed.
+    pub(super) fn invalidate(&mut self, id: ShapeId) {
+        self.rasters
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&id);
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.rasters
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    #[cfg(test)]
+    pub(super) fn cached_raster(&self, id: ShapeId) -> Option<Arc<BoxRasterChunk>> {
+        self.lock()
+            .get(&id)
+            .map(|scaled_raster| scaled_raster.raster.clone())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<ShapeId, ScaledRaster>> {
+        // A panic while the lock is held can't leave the cache inconsistent
+        self.rasters.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Copies of a layer start with an empty cache, since their shapes are scaled
+/// again when they are first drawn.
+impl Clone for ScaledRasterCache {
+    fn clone(&self) -> Self {
+        ScaledRasterCache::default()
+    }
+}
+
+impl fmt::Debug for ScaledRasterCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScaledRasterCache")
+            .field("len", &self.lock().len())
+            .finish()
+    }
+}
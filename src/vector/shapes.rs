@@ -1,4 +1,7 @@
-use std::ops::Mul;
+use std::{
+    hash::{Hash, Hasher},
+    ops::Mul,
+};
 
 use crate::{
     primitives::position::PixelPosition,
@@ -20,7 +23,7 @@ pub trait Polygon {
     }
 }
 
-fn color_from_inside_proportion(color: Pixel, p: u8) -> Pixel {
+pub(super) fn color_from_inside_proportion(color: Pixel, p: u8) -> Pixel {
     let u = p as f32 / 255.0;
     let (r, g, b, a) = color.as_rgba();
 
@@ -48,25 +51,66 @@ impl<T: Polygon> RasterizablePolygon for T {
         let bounding_box = self.bounding_box();
 
         let (width, height) = bounding_box;
-        let mut pixels = vec![colors::transparent(); width * height];
-
-        for y in 0..height {
-            for x in 0..width {
-                let p = (x, y).into();
-                let inside_proportion = self.inside_proportion(&p);
-                let color = self.color_from_inside_proportion(inside_proportion);
-                pixels[x + y * width] = color;
-            }
+        let mut raster_chunk = BoxRasterChunk::new(width, height);
+
+        for (position, pixel) in raster_chunk.enumerate_pixels_mut() {
+            let inside_proportion = self.inside_proportion(&position);
+            *pixel = self.color_from_inside_proportion(inside_proportion);
         }
 
-        BoxRasterChunk::new_fill_dynamic(
-            &mut |p| {
-                let inside_proportion = self.inside_proportion(&p);
-                self.color_from_inside_proportion(inside_proportion)
-            },
-            width,
-            height,
-        )
+        raster_chunk
+    }
+}
+
+/// The profile of the soft edge of a shape.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Falloff {
+    /// A linear ramp over the edge, with a steepness controlled by the roughness.
+    #[default]
+    Linear,
+    /// A bell curve over the whole shape, with a standard deviation relative to
+    /// the size of the shape. Produces airbrush-like dabs and ignores roughness.
+    Gaussian(f32),
+    /// An s-curve over the edge, with a steepness controlled by the roughness.
+    Smoothstep,
+}
+
+impl Falloff {
+    /// How much of a pixel is inside of a shape, given its distance from the center
+    /// relative to the size of the shape.
+    fn inside_proportion(&self, dist: f32, roughness: f32) -> u8 {
+        let edge_u = ((dist - 1.0) * roughness).clamp(0.0, 1.0);
+
+        let u = match *self {
+            Falloff::Linear => 1.0 - edge_u,
+            Falloff::Gaussian(sigma) => f32::exp(-dist.powi(2) / (2.0 * sigma.powi(2))),
+            Falloff::Smoothstep => 1.0 - edge_u.powi(2) * (3.0 - 2.0 * edge_u),
+        };
+
+        (u * 255.0).clamp(0.0, 255.0) as u8
+    }
+}
+
+impl PartialEq for Falloff {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Falloff::Linear, Falloff::Linear) => true,
+            (Falloff::Gaussian(a), Falloff::Gaussian(b)) => a.to_bits() == b.to_bits(),
+            (Falloff::Smoothstep, Falloff::Smoothstep) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Falloff {}
+
+impl Hash for Falloff {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+
+        if let Falloff::Gaussian(sigma) = self {
+            sigma.to_bits().hash(state);
+        }
     }
 }
 
@@ -78,6 +122,7 @@ pub struct OvalBuilder {
     half_height: f32,
     roughness: Option<f32>,
     color: Option<Pixel>,
+    falloff: Option<Falloff>,
 }
 
 impl OvalBuilder {
@@ -87,6 +132,7 @@ impl OvalBuilder {
             half_height: height,
             roughness: None,
             color: None,
+            falloff: None,
         }
     }
 
@@ -100,10 +146,16 @@ impl OvalBuilder {
         self
     }
 
+    pub fn falloff(&mut self, falloff: Falloff) -> &mut Self {
+        self.falloff = Some(falloff);
+        self
+    }
+
     pub fn build(&self) -> Oval {
         let mut oval = Oval::new(self.half_width, self.half_height);
         oval.roughness = (self.roughness.unwrap_or(10.0) * 10.0) as u32;
         oval.color = self.color.unwrap_or_else(colors::black);
+        oval.falloff = self.falloff.unwrap_or_default();
         oval
     }
 }
@@ -114,6 +166,7 @@ pub struct Oval {
     half_height: u32,
     roughness: u32,
     color: Pixel,
+    falloff: Falloff,
 }
 
 impl Oval {
@@ -125,6 +178,7 @@ impl Oval {
             half_height: (half_height * 10.0) as u32,
             roughness: (10.0 * 10.0) as u32,
             color: colors::black(),
+            falloff: Falloff::Linear,
         }
     }
 
@@ -162,6 +216,10 @@ impl Oval {
     pub fn half_height(&self) -> f32 {
         self.half_height as f32 / 10.0
     }
+
+    pub fn falloff(&self) -> Falloff {
+        self.falloff
+    }
 }
 
 impl Polygon for Oval {
@@ -192,11 +250,7 @@ impl Polygon for Oval {
 
         let dist = f32::sqrt(x.powi(2) / half_width.powi(2) + y.powi(2) / half_height.powi(2));
 
-        if dist < 1.0 {
-            255
-        } else {
-            ((1.0 - (dist - 1.0).mul(roughness)) * 255.0).clamp(0.0, 255.0) as u8
-        }
+        self.falloff.inside_proportion(dist, roughness)
     }
 
     fn color_from_inside_proportion(&self, p: u8) -> Pixel {
@@ -329,6 +383,729 @@ impl Polygon for LineSegment {
     }
 }
 
+/// The color of a pixel covered by `coverage` of a shape, from 0 to 1.
+fn color_with_coverage(color: Pixel, coverage: f32) -> Pixel {
+    let (r, g, b, a) = color.as_rgba();
+
+    Pixel::new_rgba(r, g, b, (a as f32 * coverage.clamp(0.0, 1.0)).round() as u8)
+}
+
+/// Converts a signed distance from the center of a pixel to the edge of a
+/// shape, negative inside of it, to an antialiased inside proportion.
+fn proportion_from_edge_distance(distance: f32) -> u8 {
+    ((0.5 - distance).clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// An axis-aligned rectangle, antialiased where its edges fall between pixels.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct Rectangle {
+    width: u32,
+    height: u32,
+    color: Pixel,
+}
+
+impl Rectangle {
+    pub fn new(width: f32, height: f32, color: Pixel) -> Rectangle {
+        Rectangle {
+            width: (width.max(0.0) * 10.0) as u32,
+            height: (height.max(0.0) * 10.0) as u32,
+            color,
+        }
+    }
+
+    pub fn width(&self) -> f32 {
+        self.width as f32 / 10.0
+    }
+
+    pub fn height(&self) -> f32 {
+        self.height as f32 / 10.0
+    }
+}
+
+impl Polygon for Rectangle {
+    fn bounding_box(&self) -> (usize, usize) {
+        (self.width().ceil() as usize, self.height().ceil() as usize)
+    }
+
+    fn inside_proportion(&self, p: &PixelPosition) -> u8 {
+        // The overlap of the pixel's square with the rectangle along each axis.
+        let x_coverage = (self.width() - p.0 as f32).clamp(0.0, 1.0);
+        let y_coverage = (self.height() - p.1 as f32).clamp(0.0, 1.0);
+
+        (x_coverage * y_coverage * 255.0).round() as u8
+    }
+
+    fn color_from_inside_proportion(&self, p: u8) -> Pixel {
+        color_with_coverage(self.color, p as f32 / 255.0)
+    }
+}
+
+/// A rectangle whose corners are rounded off with a `radius`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct RoundedRectangle {
+    rectangle: Rectangle,
+    radius: u32,
+}
+
+impl RoundedRectangle {
+    /// Create a rounded rectangle, limiting the radius to half of its shorter
+    /// side.
+    pub fn new(width: f32, height: f32, radius: f32, color: Pixel) -> RoundedRectangle {
+        let rectangle = Rectangle::new(width, height, color);
+        let radius = radius.clamp(0.0, rectangle.width().min(rectangle.height()) / 2.0);
+
+        RoundedRectangle {
+            rectangle,
+            radius: (radius * 10.0) as u32,
+        }
+    }
+
+    pub fn width(&self) -> f32 {
+        self.rectangle.width()
+    }
+
+    pub fn height(&self) -> f32 {
+        self.rectangle.height()
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius as f32 / 10.0
+    }
+}
+
+impl Polygon for RoundedRectangle {
+    fn bounding_box(&self) -> (usize, usize) {
+        self.rectangle.bounding_box()
+    }
+
+    fn inside_proportion(&self, p: &PixelPosition) -> u8 {
+        let radius = self.radius();
+        let half_size = (self.width() / 2.0, self.height() / 2.0);
+
+        // Distance from the pixel's center to the rectangle shrunk by the
+        // radius, which is then grown back by the radius with round corners.
+        let x = (p.0 as f32 + 0.5 - half_size.0).abs() - (half_size.0 - radius);
+        let y = (p.1 as f32 + 0.5 - half_size.1).abs() - (half_size.1 - radius);
+        let outside = f32::sqrt(x.max(0.0).powi(2) + y.max(0.0).powi(2));
+        let inside = x.max(y).min(0.0);
+
+        proportion_from_edge_distance(outside + inside - radius)
+    }
+
+    fn color_from_inside_proportion(&self, p: u8) -> Pixel {
+        self.rectangle.color_from_inside_proportion(p)
+    }
+}
+
+/// A convex polygon through a list of vertices, in either winding order.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ConvexPolygon {
+    vertices: Vec<(f32, f32)>,
+    color: Pixel,
+}
+
+impl ConvexPolygon {
+    /// Create a polygon through `vertices`, translated so that its bounding
+    /// box starts at the origin. Vertices that make the polygon concave
+    /// produce the convex polygon bounded by the lines through its edges.
+    pub fn new(vertices: &[(f32, f32)], color: Pixel) -> ConvexPolygon {
+        let min_x = vertices.iter().map(|v| v.0).fold(f32::INFINITY, f32::min);
+        let min_y = vertices.iter().map(|v| v.1).fold(f32::INFINITY, f32::min);
+
+        ConvexPolygon {
+            vertices: vertices
+                .iter()
+                .map(|(x, y)| (x - min_x, y - min_y))
+                .collect(),
+            color,
+        }
+    }
+
+    /// The vertices of the polygon, relative to the top left of its bounding box.
+    pub fn vertices(&self) -> &[(f32, f32)] {
+        &self.vertices
+    }
+
+    /// Twice the signed area of the polygon, positive when the vertices wind
+    /// clockwise in pixel space.
+    fn doubled_area(&self) -> f32 {
+        self.edges()
+            .map(|(a, b)| a.0 * b.1 - b.0 * a.1)
+            .sum::<f32>()
+    }
+
+    fn edges(&self) -> impl Iterator<Item = ((f32, f32), (f32, f32))> + '_ {
+        self.vertices
+            .iter()
+            .copied()
+            .zip(self.vertices.iter().copied().cycle().skip(1))
+    }
+}
+
+impl Polygon for ConvexPolygon {
+    fn bounding_box(&self) -> (usize, usize) {
+        let width = self.vertices.iter().map(|v| v.0).fold(0.0, f32::max);
+        let height = self.vertices.iter().map(|v| v.1).fold(0.0, f32::max);
+
+        (width.ceil() as usize, height.ceil() as usize)
+    }
+
+    fn inside_proportion(&self, p: &PixelPosition) -> u8 {
+        if self.vertices.len() < 3 {
+            return 0;
+        }
+
+        let center = (p.0 as f32 + 0.5, p.1 as f32 + 0.5);
+        let winding = self.doubled_area().signum();
+
+        // Inside a convex polygon, the distance to the edge is the smallest
+        // distance to the lines through its edges, so the signed distance is
+        // the largest signed distance to those lines.
+        let distance = self
+            .edges()
+            .filter_map(|(a, b)| {
+                let edge = (b.0 - a.0, b.1 - a.1);
+                let length = f32::sqrt(edge.0.powi(2) + edge.1.powi(2));
+                if length == 0.0 {
+                    return None;
+                }
+
+                let outward_normal = (edge.1 * winding / length, -edge.0 * winding / length);
+
+                Some((center.0 - a.0) * outward_normal.0 + (center.1 - a.1) * outward_normal.1)
+            })
+            .fold(f32::NEG_INFINITY, f32::max);
+
+        proportion_from_edge_distance(distance)
+    }
+
+    fn color_from_inside_proportion(&self, p: u8) -> Pixel {
+        color_with_coverage(self.color, p as f32 / 255.0)
+    }
+}
+
+/// How long, in pixels, the straight pieces curves are flattened into are at
+/// most, measured along the control polygon of the curve.
+const CURVE_FLATTENING_STEP: f32 = 2.0;
+const MAX_CURVE_PIECES: usize = 256;
+
+fn distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    f32::sqrt((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2))
+}
+
+fn lerp_point(a: (f32, f32), b: (f32, f32), t: f32) -> (f32, f32) {
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+}
+
+/// The distance from `p` to the segment between `a` and `b`.
+fn distance_to_segment(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let direction = (b.0 - a.0, b.1 - a.1);
+    let length_squared = direction.0 * direction.0 + direction.1 * direction.1;
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        (((p.0 - a.0) * direction.0 + (p.1 - a.1) * direction.1) / length_squared).clamp(0.0, 1.0)
+    };
+
+    distance(p, lerp_point(a, b, t))
+}
+
+/// The points a curve is flattened into after its start, enough that the
+/// pieces between them are at most `CURVE_FLATTENING_STEP` long.
+fn flatten_curve<F>(control_polygon_length: f32, point_at: F) -> impl Iterator<Item = (f32, f32)>
+where
+    F: Fn(f32) -> (f32, f32),
+{
+    let pieces = ((control_polygon_length / CURVE_FLATTENING_STEP).ceil() as usize)
+        .clamp(1, MAX_CURVE_PIECES);
+
+    (1..=pieces).map(move |piece| point_at(piece as f32 / pieces as f32))
+}
+
+/// A piece of a `Path`, continuing from where the previous piece ended.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PathSegment {
+    Line {
+        to: (f32, f32),
+    },
+    Quadratic {
+        control: (f32, f32),
+        to: (f32, f32),
+    },
+    Cubic {
+        control_a: (f32, f32),
+        control_b: (f32, f32),
+        to: (f32, f32),
+    },
+}
+
+impl PathSegment {
+    fn points(&self) -> Vec<(f32, f32)> {
+        match *self {
+            PathSegment::Line { to } => vec![to],
+            PathSegment::Quadratic { control, to } => vec![control, to],
+            PathSegment::Cubic {
+                control_a,
+                control_b,
+                to,
+            } => vec![control_a, control_b, to],
+        }
+    }
+}
+
+/// A line through a series of straight and curved segments, stroked
+/// `stroke_width` wide and antialiased at its edges.
+///
+/// Points are continuous coordinates, so the center of the pixel at `(x, y)`
+/// is at `(x + 0.5, y + 0.5)`. The path is rasterized with its bounding box
+/// starting at `Path::origin`.
+#[derive(Clone, Debug)]
+pub struct Path {
+    start: (f32, f32),
+    segments: Vec<PathSegment>,
+    stroke_width: f32,
+    color: Pixel,
+    /// The path flattened into straight pieces, starting at `start`.
+    polyline: Vec<(f32, f32)>,
+}
+
+impl Path {
+    /// Create a path starting at a point with no segments, which is stroked
+    /// as a dot.
+    pub fn new(start: (f32, f32), stroke_width: f32, color: Pixel) -> Path {
+        Path {
+            start,
+            segments: Vec::new(),
+            stroke_width: stroke_width.max(0.0),
+            color,
+            polyline: vec![start],
+        }
+    }
+
+    fn end(&self) -> (f32, f32) {
+        *self
+            .polyline
+            .last()
+            .expect("a path's polyline should contain its start")
+    }
+
+    pub fn line_to(&mut self, to: (f32, f32)) -> &mut Self {
+        self.segments.push(PathSegment::Line { to });
+        self.polyline.push(to);
+        self
+    }
+
+    /// Adds a quadratic bezier curve from the end of the path, bending
+    /// towards `control`.
+    pub fn quadratic_to(&mut self, control: (f32, f32), to: (f32, f32)) -> &mut Self {
+        let from = self.end();
+        let control_polygon_length = distance(from, control) + distance(control, to);
+
+        self.segments.push(PathSegment::Quadratic { control, to });
+        self.polyline
+            .extend(flatten_curve(control_polygon_length, |t| {
+                quadratic_point(from, control, to, t)
+            }));
+        self
+    }
+
+    /// Adds a cubic bezier curve from the end of the path, leaving towards
+    /// `control_a` and arriving from `control_b`.
+    pub fn cubic_to(
+        &mut self,
+        control_a: (f32, f32),
+        control_b: (f32, f32),
+        to: (f32, f32),
+    ) -> &mut Self {
+        let from = self.end();
+        let control_polygon_length =
+            distance(from, control_a) + distance(control_a, control_b) + distance(control_b, to);
+
+        self.segments.push(PathSegment::Cubic {
+            control_a,
+            control_b,
+            to,
+        });
+        self.polyline
+            .extend(flatten_curve(control_polygon_length, |t| {
+                cubic_point(from, control_a, control_b, to, t)
+            }));
+        self
+    }
+
+    pub fn start(&self) -> (f32, f32) {
+        self.start
+    }
+
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.segments
+    }
+
+    pub fn stroke_width(&self) -> f32 {
+        self.stroke_width
+    }
+
+    pub fn color(&self) -> Pixel {
+        self.color
+    }
+
+    /// How far the antialiased stroke reaches from the path.
+    fn reach(&self) -> f32 {
+        self.stroke_width / 2.0 + 0.5
+    }
+
+    /// The pixel at the top left of the bounding box of the stroked path.
+    pub fn origin(&self) -> (i32, i32) {
+        let reach = self.reach();
+        let left = self
+            .polyline
+            .iter()
+            .map(|p| p.0)
+            .fold(f32::INFINITY, f32::min);
+        let top = self
+            .polyline
+            .iter()
+            .map(|p| p.1)
+            .fold(f32::INFINITY, f32::min);
+
+        ((left - reach).floor() as i32, (top - reach).floor() as i32)
+    }
+
+    fn bits(&self) -> impl Iterator<Item = u32> + '_ {
+        [self.start.0, self.start.1, self.stroke_width]
+            .into_iter()
+            .chain(self.segments.iter().flat_map(|segment| {
+                segment
+                    .points()
+                    .into_iter()
+                    .flat_map(|point| [point.0, point.1])
+            }))
+            .map(f32::to_bits)
+    }
+}
+
+impl Polygon for Path {
+    fn bounding_box(&self) -> (usize, usize) {
+        let reach = self.reach();
+        let origin = self.origin();
+        let right = self
+            .polyline
+            .iter()
+            .map(|p| p.0)
+            .fold(f32::NEG_INFINITY, f32::max);
+        let bottom = self
+            .polyline
+            .iter()
+            .map(|p| p.1)
+            .fold(f32::NEG_INFINITY, f32::max);
+
+        (
+            ((right + reach).ceil() as i32 - origin.0).max(0) as usize,
+            ((bottom + reach).ceil() as i32 - origin.1).max(0) as usize,
+        )
+    }
+
+    fn inside_proportion(&self, p: &PixelPosition) -> u8 {
+        let origin = self.origin();
+        let center = (
+            origin.0 as f32 + p.0 as f32 + 0.5,
+            origin.1 as f32 + p.1 as f32 + 0.5,
+        );
+
+        let distance_to_path = match self.polyline.as_slice() {
+            [point] => distance(center, *point),
+            polyline => polyline
+                .windows(2)
+                .map(|piece| distance_to_segment(center, piece[0], piece[1]))
+                .fold(f32::INFINITY, f32::min),
+        };
+
+        proportion_from_edge_distance(distance_to_path - self.stroke_width / 2.0)
+    }
+
+    fn color_from_inside_proportion(&self, p: u8) -> Pixel {
+        color_with_coverage(self.color, p as f32 / 255.0)
+    }
+}
+
+/// Paths are compared by their segments rather than their flattened points.
+impl PartialEq for Path {
+    fn eq(&self, other: &Self) -> bool {
+        self.color == other.color
+            && self.segments.len() == other.segments.len()
+            && self
+                .segments
+                .iter()
+                .zip(other.segments.iter())
+                .all(|(a, b)| std::mem::discriminant(a) == std::mem::discriminant(b))
+            && self.bits().eq(other.bits())
+    }
+}
+
+impl Eq for Path {}
+
+impl Hash for Path {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.color.hash(state);
+
+        for segment in &self.segments {
+            std::mem::discriminant(segment).hash(state);
+        }
+
+        for bits in self.bits() {
+            bits.hash(state);
+        }
+    }
+}
+
+/// The point `t` of the way along a quadratic bezier curve.
+fn quadratic_point(from: (f32, f32), control: (f32, f32), to: (f32, f32), t: f32) -> (f32, f32) {
+    lerp_point(lerp_point(from, control, t), lerp_point(control, to, t), t)
+}
+
+/// The point `t` of the way along a cubic bezier curve.
+fn cubic_point(
+    from: (f32, f32),
+    control_a: (f32, f32),
+    control_b: (f32, f32),
+    to: (f32, f32),
+    t: f32,
+) -> (f32, f32) {
+    lerp_point(
+        quadratic_point(from, control_a, control_b, t),
+        quadratic_point(control_a, control_b, to, t),
+        t,
+    )
+}
+
+/// A curve from `from` to `to` bending towards a control point, stroked like
+/// a `Path`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuadraticBezier {
+    path: Path,
+}
+
+impl QuadraticBezier {
+    pub fn new(
+        from: (f32, f32),
+        control: (f32, f32),
+        to: (f32, f32),
+        stroke_width: f32,
+        color: Pixel,
+    ) -> QuadraticBezier {
+        let mut path = Path::new(from, stroke_width, color);
+        path.quadratic_to(control, to);
+
+        QuadraticBezier { path }
+    }
+
+    /// The stroked path of the curve, to continue it with more segments.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn into_path(self) -> Path {
+        self.path
+    }
+}
+
+impl Polygon for QuadraticBezier {
+    fn bounding_box(&self) -> (usize, usize) {
+        self.path.bounding_box()
+    }
+
+    fn inside_proportion(&self, p: &PixelPosition) -> u8 {
+        self.path.inside_proportion(p)
+    }
+
+    fn color_from_inside_proportion(&self, p: u8) -> Pixel {
+        self.path.color_from_inside_proportion(p)
+    }
+}
+
+/// A curve from `from` to `to` leaving towards one control point and arriving
+/// from another, stroked like a `Path`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CubicBezier {
+    path: Path,
+}
+
+impl CubicBezier {
+    pub fn new(
+        from: (f32, f32),
+        control_a: (f32, f32),
+        control_b: (f32, f32),
+        to: (f32, f32),
+        stroke_width: f32,
+        color: Pixel,
+    ) -> CubicBezier {
+        let mut path = Path::new(from, stroke_width, color);
+        path.cubic_to(control_a, control_b, to);
+
+        CubicBezier { path }
+    }
+
+    /// The stroked path of the curve, to continue it with more segments.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn into_path(self) -> Path {
+        self.path
+    }
+}
+
+impl Polygon for CubicBezier {
+    fn bounding_box(&self) -> (usize, usize) {
+        self.path.bounding_box()
+    }
+
+    fn inside_proportion(&self, p: &PixelPosition) -> u8 {
+        self.path.inside_proportion(p)
+    }
+
+    fn color_from_inside_proportion(&self, p: u8) -> Pixel {
+        self.path.color_from_inside_proportion(p)
+    }
+}
+
+/// Encodes a gradient position in `[0, 1]` as an inside proportion. Gradient
+/// shapes use `0` for pixels outside of them and `1..=255` for the position
+/// along the gradient.
+fn gradient_proportion(t: f32) -> u8 {
+    (1.0 + t.clamp(0.0, 1.0) * 254.0).round() as u8
+}
+
+/// The inverse of `gradient_proportion`, `None` for pixels outside of the shape.
+fn gradient_position(p: u8) -> Option<f32> {
+    if p == 0 {
+        None
+    } else {
+        Some((p - 1) as f32 / 254.0)
+    }
+}
+
+/// The offset of a pixel's center from the center of a disc with `radius`
+/// whose bounding box starts at the origin.
+fn offset_from_disc_center(p: &PixelPosition, radius: f32) -> (f32, f32) {
+    (p.0 as f32 + 0.5 - radius, p.1 as f32 + 0.5 - radius)
+}
+
+/// A disc whose color blends from `inner_color` at its center to
+/// `outer_color` at its edge.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct RadialGradientDisc {
+    radius: u32,
+    inner_color: Pixel,
+    outer_color: Pixel,
+}
+
+impl RadialGradientDisc {
+    pub fn new(radius: f32, inner_color: Pixel, outer_color: Pixel) -> RadialGradientDisc {
+        RadialGradientDisc {
+            radius: (radius * 10.0) as u32,
+            inner_color,
+            outer_color,
+        }
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius as f32 / 10.0
+    }
+}
+
+impl Polygon for RadialGradientDisc {
+    fn bounding_box(&self) -> (usize, usize) {
+        let diameter = (self.radius() * 2.0).ceil() as usize;
+
+        (diameter, diameter)
+    }
+
+    fn inside_proportion(&self, p: &PixelPosition) -> u8 {
+        let radius = self.radius();
+        let (x, y) = offset_from_disc_center(p, radius);
+        let dist = f32::sqrt(x.powi(2) + y.powi(2));
+
+        if dist > radius {
+            0
+        } else {
+            gradient_proportion(dist / radius)
+        }
+    }
+
+    fn color_from_inside_proportion(&self, p: u8) -> Pixel {
+        match gradient_position(p) {
+            Some(t) => self.inner_color.lerp(&self.outer_color, t),
+            None => colors::transparent(),
+        }
+    }
+}
+
+/// A disc whose color blends from `start_color` to `end_color` clockwise
+/// around its center, starting at `start_angle` degrees clockwise from the
+/// positive x axis.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct ConicGradient {
+    radius: u32,
+    start_angle: u32,
+    start_color: Pixel,
+    end_color: Pixel,
+}
+
+impl ConicGradient {
+    pub fn new(
+        radius: f32,
+        start_angle: f32,
+        start_color: Pixel,
+        end_color: Pixel,
+    ) -> ConicGradient {
+        ConicGradient {
+            radius: (radius * 10.0) as u32,
+            start_angle: (start_angle.rem_euclid(360.0) * 10.0) as u32,
+            start_color,
+            end_color,
+        }
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius as f32 / 10.0
+    }
+
+    pub fn start_angle(&self) -> f32 {
+        self.start_angle as f32 / 10.0
+    }
+}
+
+impl Polygon for ConicGradient {
+    fn bounding_box(&self) -> (usize, usize) {
+        let diameter = (self.radius() * 2.0).ceil() as usize;
+
+        (diameter, diameter)
+    }
+
+    fn inside_proportion(&self, p: &PixelPosition) -> u8 {
+        let radius = self.radius();
+        let (x, y) = offset_from_disc_center(p, radius);
+
+        if f32::sqrt(x.powi(2) + y.powi(2)) > radius {
+            return 0;
+        }
+
+        // y points down in pixel space, so this angle increases clockwise
+        let angle = y.atan2(x).to_degrees();
+        let t = (angle - self.start_angle()).rem_euclid(360.0) / 360.0;
+
+        gradient_proportion(t)
+    }
+
+    fn color_from_inside_proportion(&self, p: u8) -> Pixel {
+        match gradient_position(p) {
+            Some(t) => self.start_color.lerp(&self.end_color, t),
+            None => colors::transparent(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::raster::chunks::translate_rect_position_to_flat_index;
@@ -385,4 +1162,134 @@ mod tests {
             line_segment_raster.pixels()[20 * 20 - 19].is_close(&Pixel::new_rgba(255, 0, 0, 0), 2)
         );
     }
+
+    #[test]
+    fn radial_gradient_blends_outwards() {
+        let disc = RadialGradientDisc::new(10.0, colors::red(), colors::blue());
+        let raster = disc.rasterize();
+
+        assert_eq!(disc.bounding_box(), (20, 20));
+
+        let center = raster.pixels()[10 + 10 * 20];
+        assert!(center.is_close(&colors::red(), 40));
+
+        let near_edge = raster.pixels()[19 + 10 * 20];
+        assert!(near_edge.is_close(&colors::blue(), 40));
+
+        assert_eq!(raster.pixels()[0], colors::transparent());
+    }
+
+    #[test]
+    fn conic_gradient_blends_clockwise() {
+        let cone = ConicGradient::new(10.0, 0.0, colors::red(), colors::blue());
+        let raster = cone.rasterize();
+
+        let just_past_start = raster.pixels()[18 + 10 * 20];
+        assert!(just_past_start.is_close(&colors::red(), 20));
+
+        let half_way = raster.pixels()[1 + 9 * 20];
+        assert!(half_way.is_close(&colors::red().lerp(&colors::blue(), 0.5), 20));
+
+        let just_before_start = raster.pixels()[18 + 9 * 20];
+        assert!(just_before_start.is_close(&colors::blue(), 20));
+
+        assert_eq!(raster.pixels()[0], colors::transparent());
+    }
+
+    #[test]
+    fn oval_falloff_profiles() {
+        let linear = Oval::build(10.0, 10.0).roughness(2.0).build();
+        let smoothstep = Oval::build(10.0, 10.0)
+            .roughness(2.0)
+            .falloff(Falloff::Smoothstep)
+            .build();
+        let gaussian = Oval::build(10.0, 10.0)
+            .falloff(Falloff::Gaussian(0.4))
+            .build();
+
+        assert_eq!(
+            linear,
+            Oval::build(10.0, 10.0)
+                .roughness(2.0)
+                .falloff(Falloff::Linear)
+                .build()
+        );
+        assert_ne!(linear, smoothstep);
+        assert_ne!(
+            gaussian,
+            Oval::build(10.0, 10.0)
+                .falloff(Falloff::Gaussian(0.5))
+                .build()
+        );
+
+        // The center of the oval is at (11, 11), so (11, 11 + d) is d units from it
+        let proportion_at = |oval: &Oval, d: usize| oval.inside_proportion(&(11, 11 + d).into());
+
+        assert_eq!(proportion_at(&linear, 0), 255);
+        assert_eq!(proportion_at(&smoothstep, 0), 255);
+        assert_eq!(proportion_at(&gaussian, 0), 255);
+
+        // Just past the edge an s-curve stays more opaque than a linear ramp
+        assert!(proportion_at(&smoothstep, 11) > proportion_at(&linear, 11));
+
+        // A gaussian falls off gradually from the center rather than at the edge
+        assert!(proportion_at(&gaussian, 5) < 255);
+        assert!(proportion_at(&gaussian, 5) > proportion_at(&gaussian, 9));
+    }
+
+    #[test]
+    fn rectangles_and_polygons_antialias_their_edges() {
+        let rectangle = Rectangle::new(2.5, 2.0, colors::red()).rasterize();
+        assert_eq!(rectangle.dimensions().width, 3);
+        assert_eq!(rectangle.pixels()[0].as_rgba().3, 255);
+        assert!((rectangle.pixels()[2].as_rgba().3 as i32 - 128).abs() <= 1);
+
+        let rounded = RoundedRectangle::new(10.0, 10.0, 4.0, colors::red()).rasterize();
+        assert_eq!(rounded.pixels()[0].as_rgba().3, 0);
+        assert_eq!(rounded.pixels()[5 * 10 + 5].as_rgba().3, 255);
+        assert_eq!(rounded.pixels()[5 * 10].as_rgba().3, 255);
+
+        let clockwise = ConvexPolygon::new(&[(0.0, 0.0), (10.0, 0.0), (0.0, 10.0)], colors::red());
+        let counter_clockwise =
+            ConvexPolygon::new(&[(0.0, 10.0), (10.0, 0.0), (0.0, 0.0)], colors::red());
+        let triangle = clockwise.rasterize();
+
+        assert_eq!(triangle, counter_clockwise.rasterize());
+        assert_eq!(triangle.pixels()[10 + 1].as_rgba().3, 255);
+        assert_eq!(triangle.pixels()[8 * 10 + 8].as_rgba().3, 0);
+    }
+
+    #[test]
+    fn paths_stroke_lines_and_curves() {
+        let mut line = Path::new((0.5, 2.5), 2.0, colors::red());
+        line.line_to((8.5, 2.5));
+        assert_eq!(line.origin(), (-1, 1));
+        assert_eq!(line.bounding_box(), (11, 3));
+        let raster = line.rasterize();
+        let alpha_at = |x: usize, y: usize| raster.pixels()[y * 11 + x].as_rgba().3;
+        assert_eq!(alpha_at(4, 1), 255);
+        assert_eq!(alpha_at(4, 0), 128);
+        assert_eq!(alpha_at(4, 2), 128);
+
+        let quadratic =
+            QuadraticBezier::new((0.0, 0.0), (10.0, 10.0), (20.0, 0.0), 2.0, colors::red());
+        assert_eq!(quadratic.bounding_box(), (24, 9));
+        let curve = quadratic.rasterize();
+        assert_eq!(curve.pixels()[6 * 24 + 11].as_rgba().3, 255);
+        assert_eq!(curve.pixels()[2 * 24 + 12].as_rgba().3, 0);
+
+        let cubic = CubicBezier::new(
+            (0.0, 0.0),
+            (0.0, 10.0),
+            (10.0, 10.0),
+            (10.0, 0.0),
+            1.0,
+            colors::blue(),
+        );
+        let mut path = Path::new((0.0, 0.0), 1.0, colors::blue());
+        path.cubic_to((0.0, 10.0), (10.0, 10.0), (10.0, 0.0));
+        assert_eq!(cubic.path(), &path);
+        path.line_to((0.0, 0.0));
+        assert_ne!(cubic.into_path(), path);
+    }
 }
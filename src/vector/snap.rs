@@ -0,0 +1,121 @@
+//! Snapping positions to the bounds of the shapes of a vector layer, so that
+//! editors can place shapes in line with others.
+
+use std::cmp::Ordering;
+
+use crate::{
+    canvas::{Guide, GuideSnap},
+    primitives::position::CanvasPosition,
+};
+
+use super::layer::{ShapeId, VectorLayer};
+
+/// The part of the bounds of a shape a position can snap to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SnapFeature {
+    Corner,
+    /// The line through a side of the bounds.
+    Edge,
+    Center,
+}
+
+/// A part of the bounds of a shape near a position.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SnapCandidate {
+    pub shape: ShapeId,
+    pub feature: SnapFeature,
+    pub snap: GuideSnap,
+}
+
+impl VectorLayer {
+    /// The corners, edges and centers of the bounds of shapes within `radius`
+    /// of `position`, nearest first. Edges extend past the bounds, so
+    /// positions can be lined up with shapes that are far away.
+    pub fn snap_candidates(&self, position: CanvasPosition, radius: f32) -> Vec<SnapCandidate> {
+        let mut candidates: Vec<SnapCandidate> = self
+            .iter()
+            .flat_map(|(id, shape)| {
+                let rect = shape.canvas_rect();
+                let (left, top) = (rect.top_left.0, rect.top_left.1);
+                let right = left + rect.dimensions.width as i32;
+                let bottom = top + rect.dimensions.height as i32;
+                let center = (
+                    left + rect.dimensions.width as i32 / 2,
+                    top + rect.dimensions.height as i32 / 2,
+                );
+
+                let corners = [(left, top), (right, top), (left, bottom), (right, bottom)]
+                    .map(|corner| (SnapFeature::Corner, Guide::Anchor(corner.into())));
+                let edges = [
+                    Guide::Vertical(left),
+                    Guide::Vertical(right),
+                    Guide::Horizontal(top),
+                    Guide::Horizontal(bottom),
+                ]
+                .map(|guide| (SnapFeature::Edge, guide));
+
+                corners
+                    .into_iter()
+                    .chain(edges)
+                    .chain([(SnapFeature::Center, Guide::Anchor(center.into()))])
+                    .map(move |(feature, guide)| SnapCandidate {
+                        shape: id,
+                        feature,
+                        snap: GuideSnap {
+                            guide,
+                            snapped_position: guide.closest_position(position),
+                            distance: guide.distance_to(position),
+                        },
+                    })
+            })
+            .filter(|candidate| candidate.snap.distance <= radius)
+            .collect();
+
+        candidates.sort_by(|a, b| {
+            a.snap
+                .distance
+                .partial_cmp(&b.snap.distance)
+                .unwrap_or(Ordering::Equal)
+        });
+
+        candidates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        raster::pixels::colors,
+        vector::{shapes::Rectangle, VectorShape},
+    };
+
+    #[test]
+    fn snapping_to_nearby_shape_bounds() {
+        let mut vector_layer = VectorLayer::new();
+        let (id, _) = vector_layer.add_shape(VectorShape::new(
+            (10.0, 10.0),
+            Rectangle::new(10.0, 10.0, colors::red()),
+        ));
+
+        let candidates = vector_layer.snap_candidates((21, 9).into(), 2.0);
+        assert_eq!(candidates[0].shape, id);
+        assert_eq!(candidates[0].feature, SnapFeature::Edge);
+        assert_eq!(candidates[0].snap.distance, 1.0);
+        assert!(candidates.iter().any(|candidate| {
+            candidate.feature == SnapFeature::Corner
+                && candidate.snap.snapped_position == (20, 10).into()
+        }));
+
+        let centers: Vec<_> = vector_layer
+            .snap_candidates((15, 16).into(), 1.0)
+            .into_iter()
+            .map(|candidate| candidate.feature)
+            .collect();
+        assert_eq!(centers, vec![SnapFeature::Center]);
+
+        assert!(vector_layer
+            .snap_candidates((100, 100).into(), 5.0)
+            .is_empty());
+    }
+}
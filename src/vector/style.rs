@@ -0,0 +1,215 @@
+//! Fills and strokes of shapes, rasterized together in a single pass over the
+//! coverage of a polygon.
+
+use crate::{
+    primitives::{dimensions::Dimensions, position::PixelPosition},
+    raster::{chunks::BoxRasterChunk, pixels::colors, DistanceField, Pixel},
+};
+
+use super::shapes::{color_from_inside_proportion, Polygon, RasterizablePolygon};
+
+/// What the inside of a shape is painted with.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Fill {
+    Color(Pixel),
+    /// A blend from `start_color` at the top of the shape to `end_color` at
+    /// its bottom.
+    Gradient {
+        start_color: Pixel,
+        end_color: Pixel,
+    },
+}
+
+impl Fill {
+    fn color_at(&self, y: f32, height: f32) -> Pixel {
+        match *self {
+            Fill::Color(color) => color,
+            Fill::Gradient {
+                start_color,
+                end_color,
+            } => start_color.lerp(&end_color, y / height.max(1.0)),
+        }
+    }
+}
+
+/// A band of color along the edge of a shape, half inside and half outside of it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Stroke {
+    pub color: Pixel,
+    pub width: f32,
+}
+
+/// How a shape is painted.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Style {
+    pub fill: Option<Fill>,
+    pub stroke: Option<Stroke>,
+}
+
+impl Style {
+    pub fn fill(fill: Fill) -> Style {
+        Style {
+            fill: Some(fill),
+            stroke: None,
+        }
+    }
+
+    pub fn stroke(color: Pixel, width: f32) -> Style {
+        Style {
+            fill: None,
+            stroke: Some(Stroke { color, width }),
+        }
+    }
+
+    pub fn with_stroke(self, color: Pixel, width: f32) -> Style {
+        Style {
+            stroke: Some(Stroke { color, width }),
+            ..self
+        }
+    }
+
+    /// How far the stroke reaches outside of the shape.
+    fn outer_padding(&self) -> usize {
+        self.stroke
+            .map(|stroke| (stroke.width / 2.0).ceil() as usize)
+            .unwrap_or(0)
+    }
+}
+
+/// A polygon painted with a `Style`. The stroke is centered on the edge of the
+/// polygon, where its coverage crosses one half, so the raster extends past the
+/// polygon's bounding box by half of the stroke width on every side.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StyledPolygon<P: Polygon> {
+    polygon: P,
+    style: Style,
+}
+
+impl<P: Polygon> StyledPolygon<P> {
+    pub fn new(polygon: P, style: Style) -> StyledPolygon<P> {
+        StyledPolygon { polygon, style }
+    }
+
+    pub fn polygon(&self) -> &P {
+        &self.polygon
+    }
+
+    pub fn style(&self) -> &Style {
+        &self.style
+    }
+
+    /// The coverage of the polygon over the padded raster.
+    fn coverage(&self, padding: usize, dimensions: Dimensions) -> Vec<u8> {
+        let (polygon_width, polygon_height) = self.polygon.bounding_box();
+
+        (0..dimensions.height)
+            .flat_map(|y| (0..dimensions.width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let inside_polygon_box = (padding..padding + polygon_width).contains(&x)
+                    && (padding..padding + polygon_height).contains(&y);
+
+                if inside_polygon_box {
+                    self.polygon
+                        .inside_proportion(&PixelPosition::from((x - padding, y - padding)))
+                } else {
+                    0
+                }
+            })
+            .collect()
+    }
+}
+
+impl<P: Polygon> RasterizablePolygon for StyledPolygon<P> {
+    fn rasterize(&self) -> BoxRasterChunk {
+        let padding = self.style.outer_padding();
+        let (polygon_width, polygon_height) = self.polygon.bounding_box();
+        let dimensions = Dimensions {
+            width: polygon_width + padding * 2,
+            height: polygon_height + padding * 2,
+        };
+
+        let coverage = self.coverage(padding, dimensions);
+
+        // Distances to the nearest pixel on the other side of the edge, for
+        // pixels outside and inside of the polygon respectively.
+        let edge_distances = self.style.stroke.map(|_| {
+            let inside: Vec<bool> = coverage.iter().map(|c| *c >= 128).collect();
+            let outside: Vec<bool> = inside.iter().map(|inside| !inside).collect();
+
+            (
+                DistanceField::from_mask(&inside, dimensions).expect("mask covers the raster"),
+                DistanceField::from_mask(&outside, dimensions).expect("mask covers the raster"),
+            )
+        });
+
+        let mut raster_chunk = BoxRasterChunk::new(dimensions.width, dimensions.height);
+
+        for (position, pixel) in raster_chunk.enumerate_pixels_mut() {
+            let index = position.1 * dimensions.width + position.0;
+
+            *pixel = match self.style.fill {
+                Some(fill) => color_from_inside_proportion(
+                    fill.color_at(position.1 as f32 - padding as f32, polygon_height as f32),
+                    coverage[index],
+                ),
+                None => colors::transparent(),
+            };
+
+            if let (Some(stroke), Some((to_inside, to_outside))) =
+                (self.style.stroke, &edge_distances)
+            {
+                // Both fields are 0 on their own side, so only one contributes.
+                let to_inside = to_inside.distance(position).unwrap_or(0.0);
+                let to_outside = to_outside.distance(position).unwrap_or(0.0);
+                let edge_distance = (to_inside.max(to_outside) - 0.5).max(0.0);
+
+                let stroke_coverage = (stroke.width / 2.0 + 0.5 - edge_distance).clamp(0.0, 1.0);
+
+                if stroke_coverage > 0.0 {
+                    let (r, g, b, a) = stroke.color.as_rgba();
+                    let stroke_pixel =
+                        Pixel::new_rgba(r, g, b, (a as f32 * stroke_coverage).round() as u8);
+
+                    pixel.composite_over(&stroke_pixel);
+                }
+            }
+        }
+
+        raster_chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector::shapes::Oval;
+
+    #[test]
+    fn fill_and_stroke_rasterize_together() {
+        let oval = Oval::build_from_bound(20, 20).color(colors::red()).build();
+        let style = Style::fill(Fill::Color(colors::red())).with_stroke(colors::blue(), 2.0);
+        let styled = StyledPolygon::new(oval, style);
+
+        let raster = styled.rasterize();
+        let (polygon_width, polygon_height) = oval.bounding_box();
+        assert_eq!(
+            raster.dimensions(),
+            Dimensions {
+                width: polygon_width + 2,
+                height: polygon_height + 2,
+            }
+        );
+
+        let width = raster.dimensions().width;
+        let center_row = &raster.pixels()[(width / 2) * width..(width / 2 + 1) * width];
+
+        assert!(center_row[width / 2].is_close(&colors::red(), 2));
+        assert_eq!(center_row[0].as_rgba().3, 0);
+
+        let edge = center_row
+            .iter()
+            .position(|pixel| pixel.as_rgba().3 > 0)
+            .unwrap();
+        assert!(center_row[edge + 1].is_close(&colors::blue(), 2));
+    }
+}
@@ -0,0 +1,247 @@
+use js_sys::{Function, Uint8ClampedArray};
+use wasm_bindgen::prelude::*;
+
+use super::{js_region_observer, RasterProduct};
+use crate::{
+    canvas::{Canvas, CanvasView, ViewRotation},
+    primitives::{
+        dimensions::{Dimensions, Scale},
+        rect::CanvasRect,
+    },
+    raster::{chunks::BoxRasterChunk, Pixel, RasterLayer, RasterLayerAction},
+    text::TextLayer,
+    vector::VectorLayer,
+};
+
+/// Colors cross from JavaScript as `0xRRGGBBAA` numbers.
+fn pixel_from_rgba(rgba: u32) -> Pixel {
+    let [r, g, b, a] = rgba.to_be_bytes();
+
+    Pixel::new_rgba(r, g, b, a)
+}
+
+fn canvas_rect(x: i32, y: i32, width: usize, height: usize) -> CanvasRect {
+    CanvasRect {
+        top_left: (x, y).into(),
+        dimensions: Dimensions { width, height },
+    }
+}
+
+/// A canvas driven from JavaScript, along with the view it is presented
+/// through. Actions return whether they changed the canvas. Drawing actions
+/// throw if there is no raster layer to draw on or their rect is out of
+/// range, rather than panicking and taking down the wasm instance.
+#[wasm_bindgen]
+pub struct MboardCanvas {
+    canvas: Canvas,
+    view: CanvasView,
+    last_render: Option<BoxRasterChunk>,
+}
+
+impl MboardCanvas {
+    pub fn canvas(&self) -> &Canvas {
+        &self.canvas
+    }
+
+    pub fn canvas_mut(&mut self) -> &mut Canvas {
+        &mut self.canvas
+    }
+
+    pub fn view(&self) -> &CanvasView {
+        &self.view
+    }
+
+    fn perform_raster_action(
+        &mut self,
+        layer_num: usize,
+        action: RasterLayerAction,
+    ) -> Result<bool, JsError> {
+        Ok(self
+            .canvas
+            .try_perform_raster_action(layer_num, action)?
+            .is_some())
+    }
+}
+
+#[wasm_bindgen]
+impl MboardCanvas {
+    /// Creates an empty canvas presented through a view of `width` by
+    /// `height` pixels at the origin.
+    #[wasm_bindgen(constructor)]
+    pub fn new(width: usize, height: usize) -> MboardCanvas {
+        MboardCanvas {
+            canvas: Canvas::default(),
+            view: CanvasView::new(width, height),
+            last_render: None,
+        }
+    }
+
+    #[wasm_bindgen(getter, js_name = layerCount)]
+    pub fn layer_count(&self) -> usize {
+        self.canvas.layer_count()
+    }
+
+    #[wasm_bindgen(js_name = addRasterLayer)]
+    pub fn add_raster_layer(&mut self, chunk_size: usize) {
+        self.canvas.add_layer(RasterLayer::new(chunk_size).into());
+    }
+
+    #[wasm_bindgen(js_name = addTextLayer)]
+    pub fn add_text_layer(&mut self) {
+        self.canvas.add_layer(TextLayer::new().into());
+    }
+
+    #[wasm_bindgen(js_name = addVectorLayer)]
+    pub fn add_vector_layer(&mut self) {
+        self.canvas.add_layer(VectorLayer::new().into());
+    }
+
+    #[wasm_bindgen(js_name = fillRect)]
+    pub fn fill_rect(
+        &mut self,
+        layer_num: usize,
+        x: i32,
+        y: i32,
+        width: usize,
+        height: usize,
+        rgba: u32,
+    ) -> Result<bool, JsError> {
+        self.perform_raster_action(
+            layer_num,
+            RasterLayerAction::fill_rect(canvas_rect(x, y, width, height), pixel_from_rgba(rgba)),
+        )
+    }
+
+    #[wasm_bindgen(js_name = fillOval)]
+    pub fn fill_oval(
+        &mut self,
+        layer_num: usize,
+        x: i32,
+        y: i32,
+        width: usize,
+        height: usize,
+        rgba: u32,
+    ) -> Result<bool, JsError> {
+        self.perform_raster_action(
+            layer_num,
+            RasterLayerAction::fill_oval(canvas_rect(x, y, width, height), pixel_from_rgba(rgba)),
+        )
+    }
+
+    #[wasm_bindgen(js_name = drawLine)]
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_line(
+        &mut self,
+        layer_num: usize,
+        from_x: i32,
+        from_y: i32,
+        to_x: i32,
+        to_y: i32,
+        radius: u32,
+        rgba: u32,
+    ) -> Result<bool, JsError> {
+        self.perform_raster_action(
+            layer_num,
+            RasterLayerAction::draw_line(
+                (from_x, from_y).into(),
+                (to_x, to_y).into(),
+                radius,
+                pixel_from_rgba(rgba),
+            ),
+        )
+    }
+
+    #[wasm_bindgen(js_name = eraseRect)]
+    pub fn erase_rect(
+        &mut self,
+        layer_num: usize,
+        x: i32,
+        y: i32,
+        width: usize,
+        height: usize,
+        strength: u8,
+    ) -> Result<bool, JsError> {
+        self.perform_raster_action(
+            layer_num,
+            RasterLayerAction::erase_rect(canvas_rect(x, y, width, height), strength),
+        )
+    }
+
+    #[wasm_bindgen(js_name = clearLayer)]
+    pub fn clear_layer(&mut self, layer_num: usize) -> bool {
+        self.canvas.clear_layer(layer_num).is_some()
+    }
+
+    pub fn undo(&mut self) -> bool {
+        self.canvas.undo().is_some()
+    }
+
+    pub fn redo(&mut self) -> bool {
+        self.canvas.redo().is_some()
+    }
+
+    /// Calls `callback` with `{x, y, width, height}` whenever a canvas rect
+    /// changes, see `js_region_observer`.
+    #[wasm_bindgen(js_name = onRegionChanged)]
+    pub fn on_region_changed(&mut self, callback: Function) {
+        self.canvas.on_region_changed(js_region_observer(callback));
+    }
+
+    /// Moves the view by an offset in canvas pixels.
+    pub fn translate(&mut self, dx: i32, dy: i32) {
+        self.view.translate((dx, dy).into());
+    }
+
+    /// Zooms the view in by `factor` around its middle, or out for factors
+    /// below 1. Factors that aren't positive are ignored.
+    pub fn zoom(&mut self, factor: f32) {
+        if factor > 0.0 {
+            self.view.pin_scale_canvas(Scale {
+                width_factor: 1.0 / factor,
+                height_factor: 1.0 / factor,
+            });
+        }
+    }
+
+    /// Turns the canvas clockwise about the middle of the view by whole
+    /// degrees, or counterclockwise for negative degrees.
+    pub fn rotate(&mut self, degrees: i32) {
+        self.view.rotate(ViewRotation::from_degrees(degrees));
+    }
+
+    /// Changes the size of the view in pixels, such as when the HTML canvas it
+    /// is drawn to is resized, keeping its zoom and middle.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        let scale = self
+            .view
+            .canvas_dimensions
+            .relative_scale(self.view.view_dimensions);
+        let canvas_dimensions = Dimensions {
+            width: ((width as f32 * scale.width_factor).round() as usize).max(1),
+            height: ((height as f32 * scale.height_factor).round() as usize).max(1),
+        };
+
+        self.view.pin_resize_canvas(canvas_dimensions);
+        self.view.view_dimensions = Dimensions { width, height };
+    }
+
+    /// Renders the view into a `RasterProduct`, throwing if a layer of the
+    /// canvas is corrupt.
+    pub fn render(&mut self) -> Result<RasterProduct, JsError> {
+        Ok(self.canvas.try_render(&self.view)?.into())
+    }
+
+    /// Renders the view into RGBA8 bytes for the `ImageData` constructor,
+    /// without copying. The array is only valid until the next call into
+    /// wasm, like `renderViewToImageData`. Throws if a layer of the canvas is
+    /// corrupt.
+    #[wasm_bindgen(js_name = renderToImageData)]
+    pub fn render_to_image_data(&mut self) -> Result<Uint8ClampedArray, JsError> {
+        let render = self.last_render.insert(self.canvas.try_render(&self.view)?);
+
+        // SAFETY: The view is only used by JavaScript before the next call into
+        // wasm, as documented above, during which the render is kept alive by
+        // this canvas and wasm memory cannot grow.
+        Ok(unsafe { Uint8ClampedArray::view(render.as_rgba_bytes()) })
+    }
+}
@@ -0,0 +1,58 @@
+use std::cell::RefCell;
+
+use js_sys::Uint8ClampedArray;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    canvas::{Canvas, CanvasView},
+    raster::chunks::BoxRasterChunk,
+};
+
+/// The canvas rendered by `render_view_to_image_data`, along with its last
+/// render, which the returned bytes point into.
+#[derive(Default)]
+struct Document {
+    canvas: Canvas,
+    last_render: Option<BoxRasterChunk>,
+}
+
+thread_local! {
+    static DOCUMENT: RefCell<Document> = RefCell::new(Document::default());
+}
+
+/// Gives access to the canvas rendered by `render_view_to_image_data`, so it
+/// can be edited from Rust.
+pub fn with_document<R>(f: impl FnOnce(&mut Canvas) -> R) -> R {
+    DOCUMENT.with(|document| f(&mut document.borrow_mut().canvas))
+}
+
+/// Renders a view of `width` by `height` pixels with its top left at `x, y`
+/// on the canvas, returning its pixels as RGBA8 bytes that can be passed
+/// straight to the `ImageData` constructor.
+///
+/// The returned array is a view into wasm memory rather than a copy. Like
+/// `RasterProduct.rgbaBytes`, it is only valid until the next call into wasm,
+/// so it should be drawn with `putImageData` before calling back into wasm.
+/// Throws if a layer of the canvas is corrupt, rather than aborting.
+#[wasm_bindgen(js_name = renderViewToImageData)]
+pub fn render_view_to_image_data(
+    width: usize,
+    height: usize,
+    x: i32,
+    y: i32,
+) -> Result<Uint8ClampedArray, JsError> {
+    let mut view = CanvasView::new(width, height);
+    view.translate((x, y).into());
+
+    DOCUMENT.with(|document| {
+        let mut document = document.borrow_mut();
+
+        let render = document.canvas.try_render(&view)?;
+        let render = document.last_render.insert(render);
+
+        // SAFETY: The view is only used by JavaScript before the next call into
+        // wasm, as documented above, during which the render is kept alive by
+        // the document and wasm memory cannot grow.
+        Ok(unsafe { Uint8ClampedArray::view(render.as_rgba_bytes()) })
+    })
+}
@@ -0,0 +1,111 @@
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    canvas::{FrameScheduler, FrameWork},
+    primitives::{dimensions::Dimensions, rect::CanvasRect},
+};
+
+/// The kind of work a frame should do, see `FrameWork`.
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FrameWorkKind {
+    Skip,
+    Delta,
+    Refine,
+}
+
+/// A `FrameScheduler` driven from JavaScript by `requestAnimationFrame` ticks.
+#[wasm_bindgen(js_name = FrameScheduler)]
+#[derive(Debug, Clone)]
+pub struct WasmFrameScheduler {
+    scheduler: FrameScheduler,
+    last_delta: Option<CanvasRect>,
+}
+
+impl Default for WasmFrameScheduler {
+    fn default() -> Self {
+        WasmFrameScheduler::new()
+    }
+}
+
+#[wasm_bindgen(js_class = FrameScheduler)]
+impl WasmFrameScheduler {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmFrameScheduler {
+        WasmFrameScheduler {
+            scheduler: FrameScheduler::new(),
+            last_delta: None,
+        }
+    }
+
+    #[wasm_bindgen(js_name = setMaxFrameRate)]
+    pub fn set_max_frame_rate(&mut self, frames_per_second: f64) {
+        self.scheduler = self
+            .scheduler
+            .clone()
+            .with_max_frame_rate(frames_per_second);
+    }
+
+    #[wasm_bindgen(js_name = setSettleDelay)]
+    pub fn set_settle_delay(&mut self, settle_delay_ms: f64) {
+        self.scheduler = self.scheduler.clone().with_settle_delay(settle_delay_ms);
+    }
+
+    #[wasm_bindgen(js_name = markDirty)]
+    pub fn mark_dirty(&mut self, x: i32, y: i32, width: usize, height: usize) {
+        self.scheduler.mark_dirty(CanvasRect {
+            top_left: (x, y).into(),
+            dimensions: Dimensions { width, height },
+        });
+    }
+
+    #[wasm_bindgen(js_name = requestRefinement)]
+    pub fn request_refinement(&mut self) {
+        self.scheduler.request_refinement();
+    }
+
+    #[wasm_bindgen(js_name = hasPendingWork)]
+    pub fn has_pending_work(&self) -> bool {
+        self.scheduler.has_pending_work()
+    }
+
+    /// Decides the work for a frame from a `requestAnimationFrame` timestamp.
+    /// When the result is `Delta`, the rect to redraw is available from the
+    /// `delta` getters until the next tick.
+    pub fn tick(&mut self, timestamp_ms: f64) -> FrameWorkKind {
+        let work = self.scheduler.tick(timestamp_ms);
+
+        self.last_delta = match work {
+            FrameWork::Delta(canvas_rect) => Some(canvas_rect),
+            _ => None,
+        };
+
+        match work {
+            FrameWork::Skip => FrameWorkKind::Skip,
+            FrameWork::Delta(_) => FrameWorkKind::Delta,
+            FrameWork::Refine => FrameWorkKind::Refine,
+        }
+    }
+
+    #[wasm_bindgen(getter, js_name = deltaX)]
+    pub fn delta_x(&self) -> Option<i32> {
+        self.last_delta.map(|canvas_rect| canvas_rect.top_left.0)
+    }
+
+    #[wasm_bindgen(getter, js_name = deltaY)]
+    pub fn delta_y(&self) -> Option<i32> {
+        self.last_delta.map(|canvas_rect| canvas_rect.top_left.1)
+    }
+
+    #[wasm_bindgen(getter, js_name = deltaWidth)]
+    pub fn delta_width(&self) -> Option<usize> {
+        self.last_delta
+            .map(|canvas_rect| canvas_rect.dimensions.width)
+    }
+
+    #[wasm_bindgen(getter, js_name = deltaHeight)]
+    pub fn delta_height(&self) -> Option<usize> {
+        self.last_delta
+            .map(|canvas_rect| canvas_rect.dimensions.height)
+    }
+}
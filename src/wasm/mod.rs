@@ -0,0 +1,18 @@
+//! Bindings for using mboard from JavaScript through `wasm-bindgen`.
+//!
+//! Render results cross into JavaScript as a `RasterProduct`, which exposes
+//! its pixels as a view into wasm memory rather than a copy.
+//! `renderViewToImageData` renders a shared document canvas straight into
+//! bytes for `ImageData` in the same way.
+
+mod canvas;
+mod document;
+mod frame_scheduler;
+mod raster_product;
+mod region;
+
+pub use canvas::MboardCanvas;
+pub use document::{render_view_to_image_data, with_document};
+pub use frame_scheduler::{FrameWorkKind, WasmFrameScheduler};
+pub use raster_product::RasterProduct;
+pub use region::js_region_observer;
@@ -0,0 +1,65 @@
+use js_sys::Uint8Array;
+use wasm_bindgen::prelude::*;
+
+use crate::raster::chunks::BoxRasterChunk;
+
+/// A rendered raster handed to JavaScript.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct RasterProduct {
+    raster: BoxRasterChunk,
+}
+
+impl RasterProduct {
+    pub fn new(raster: BoxRasterChunk) -> RasterProduct {
+        RasterProduct { raster }
+    }
+
+    pub fn raster(&self) -> &BoxRasterChunk {
+        &self.raster
+    }
+
+    pub fn into_raster(self) -> BoxRasterChunk {
+        self.raster
+    }
+}
+
+impl From<BoxRasterChunk> for RasterProduct {
+    fn from(raster: BoxRasterChunk) -> RasterProduct {
+        RasterProduct::new(raster)
+    }
+}
+
+#[wasm_bindgen]
+impl RasterProduct {
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> usize {
+        self.raster.dimensions().width
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> usize {
+        self.raster.dimensions().height
+    }
+
+    /// The pixels as RGBA8 bytes in row-major order, suitable for `ImageData`.
+    ///
+    /// The returned array is a view into wasm memory rather than a copy. It is
+    /// only valid until the next call into wasm, since allocating can grow the
+    /// memory and detach the view, and it must not outlive this product. Use
+    /// `rgbaBytesCopy` when the bytes need to be kept.
+    #[wasm_bindgen(js_name = rgbaBytes)]
+    pub fn rgba_bytes(&self) -> Uint8Array {
+        // SAFETY: The view is only used by JavaScript before the next call into
+        // wasm, as documented above, during which the raster is not mutated or
+        // freed and wasm memory cannot grow.
+        unsafe { Uint8Array::view(self.raster.as_rgba_bytes()) }
+    }
+
+    /// The pixels as RGBA8 bytes in row-major order, copied into a new array
+    /// owned by JavaScript.
+    #[wasm_bindgen(js_name = rgbaBytesCopy)]
+    pub fn rgba_bytes_copy(&self) -> Uint8Array {
+        Uint8Array::from(self.raster.as_rgba_bytes())
+    }
+}
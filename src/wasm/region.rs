@@ -0,0 +1,27 @@
+use js_sys::{Function, Object, Reflect};
+use wasm_bindgen::JsValue;
+
+use crate::primitives::rect::CanvasRect;
+
+/// Adapts a JavaScript function into a callback for `Canvas::on_region_changed`.
+/// The function is called with an object of the form `{x, y, width, height}`
+/// describing the changed canvas rect, so JavaScript can schedule partial
+/// `putImageData` updates. Exceptions thrown by the function are ignored.
+pub fn js_region_observer(callback: Function) -> impl FnMut(&CanvasRect) + 'static {
+    move |canvas_rect| {
+        let region = Object::new();
+
+        let fields = [
+            ("x", canvas_rect.top_left.0 as f64),
+            ("y", canvas_rect.top_left.1 as f64),
+            ("width", canvas_rect.dimensions.width as f64),
+            ("height", canvas_rect.dimensions.height as f64),
+        ];
+        for (key, value) in fields {
+            // Setting a property on a fresh plain object can't fail
+            let _ = Reflect::set(&region, &JsValue::from_str(key), &JsValue::from_f64(value));
+        }
+
+        let _ = callback.call1(&JsValue::NULL, &region);
+    }
+}